@@ -0,0 +1,85 @@
+//! student / dept の schema を使った、SimpleDB 全体の benchmark
+//!
+//! `cargo bench --features bench` で実行する
+//! データの量は環境変数 SIMPLEDB_BENCH_STUDENTS と SIMPLEDB_BENCH_DEPARTMENTS で変えられる
+
+use std::env;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use simpledb::bench::{BenchDatabase, BenchScale};
+use tempfile::tempdir;
+
+fn scale() -> BenchScale {
+    let var = |name: &str, default: usize| {
+        env::var(name)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    };
+    BenchScale::new(
+        var("SIMPLEDB_BENCH_STUDENTS", 200),
+        var("SIMPLEDB_BENCH_DEPARTMENTS", 10),
+    )
+}
+
+fn insert(c: &mut Criterion) {
+    let scale = scale();
+    let mut group = c.benchmark_group("insert");
+    group.throughput(Throughput::Elements(scale.students as u64));
+    group.bench_function("students", |b| {
+        b.iter_batched(
+            || {
+                let dir = tempdir().unwrap();
+                let db = BenchDatabase::create(dir.path(), scale).unwrap();
+                (dir, db)
+            },
+            |(_dir, mut db)| db.insert_students(scale.students).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+}
+
+fn scan(c: &mut Criterion) {
+    let scale = scale();
+    let dir = tempdir().unwrap();
+    let db = BenchDatabase::populate(dir.path(), scale).unwrap();
+    let mut group = c.benchmark_group("scan");
+    group.throughput(Throughput::Elements(scale.students as u64));
+    group.bench_function("students", |b| {
+        b.iter(|| assert_eq!(db.scan_students().unwrap(), scale.students))
+    });
+    group.finish();
+}
+
+fn join(c: &mut Criterion) {
+    let scale = scale();
+    let dir = tempdir().unwrap();
+    let db = BenchDatabase::populate(dir.path(), scale).unwrap();
+    c.bench_function("join/students_with_departments", |b| {
+        b.iter(|| db.join_students_with_departments().unwrap())
+    });
+}
+
+fn recovery(c: &mut Criterion) {
+    let scale = scale();
+    c.bench_function("recovery/uncommitted_students", |b| {
+        b.iter_batched(
+            || {
+                let dir = tempdir().unwrap();
+                BenchDatabase::crash_with_uncommitted_students(dir.path(), scale, scale.students)
+                    .unwrap();
+                dir
+            },
+            |dir| BenchDatabase::recover(dir.path()).unwrap(),
+            BatchSize::PerIteration,
+        )
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = insert, scan, join, recovery
+}
+criterion_main!(benches);
@@ -0,0 +1,173 @@
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use crate::{
+    server::simpledb::{SimpleDB, SimpleDBConfig},
+    tx::transaction::Transaction,
+};
+
+/// 生成するデータの量
+#[derive(Debug, Clone, Copy)]
+pub struct BenchScale {
+    pub students: usize,
+    pub departments: usize,
+}
+
+impl BenchScale {
+    pub fn new(students: usize, departments: usize) -> Self {
+        Self {
+            students,
+            departments,
+        }
+    }
+}
+
+/**
+ * benches/ の benchmark から使う、student / dept の table を持つ database
+ *
+ * crate の内部の型は公開していないので、benchmark で測りたい操作はここで関数として公開する
+ * 各関数は SimpleDbError を anyhow::Error に変換して返す
+ */
+pub struct BenchDatabase {
+    db: SimpleDB,
+    scale: BenchScale,
+    // 次に insert する student の sid
+    next_sid: usize,
+}
+
+impl BenchDatabase {
+    // benchmark で buffer が足りなくならないように、既定よりも多めに確保する
+    const BUFFER_SIZE: usize = 64;
+
+    /// dir に空の student / dept の table を作成する
+    pub fn create(dir: &Path, scale: BenchScale) -> AnyhowResult<Self> {
+        let db = Self::open_db(dir)?;
+        let tx = db.new_tx()?;
+        db.executor().exec_update_command(
+            "create table student (sid int, sname varchar(10), gradyear int, majorid int)",
+            &tx,
+        )?;
+        db.executor()
+            .exec_update_command("create table dept (did int, dname varchar(10))", &tx)?;
+        tx.borrow_mut().commit()?;
+        Ok(Self {
+            db,
+            scale,
+            next_sid: 0,
+        })
+    }
+
+    /// dir に student / dept の table を作成し、scale の量のデータを入れる
+    pub fn populate(dir: &Path, scale: BenchScale) -> AnyhowResult<Self> {
+        let mut db = Self::create(dir, scale)?;
+        db.insert_departments()?;
+        db.insert_students(scale.students)?;
+        Ok(db)
+    }
+
+    /// dept に scale.departments 件の record を 1 つの transaction で insert する
+    pub fn insert_departments(&self) -> AnyhowResult<()> {
+        let tx = self.db.new_tx()?;
+        for did in 0..self.scale.departments {
+            self.db.executor().exec_update_command(
+                &format!(
+                    "insert into dept (did, dname) values ({}, 'dept{}')",
+                    did, did
+                ),
+                &tx,
+            )?;
+        }
+        tx.borrow_mut().commit()?;
+        Ok(())
+    }
+
+    /// student に count 件の record を 1 つの transaction で insert する
+    pub fn insert_students(&mut self, count: usize) -> AnyhowResult<()> {
+        let tx = self.db.new_tx()?;
+        self.insert_students_in(count, &tx)?;
+        tx.borrow_mut().commit()?;
+        Ok(())
+    }
+
+    /// student の全 record を読み、読んだ record の数を返す
+    pub fn scan_students(&self) -> AnyhowResult<usize> {
+        self.count_rows("select sid, sname, gradyear, majorid from student")
+    }
+
+    /// student と dept を majorid = did で結合し、結果の record の数を返す
+    pub fn join_students_with_departments(&self) -> AnyhowResult<usize> {
+        self.count_rows("select sname, dname from student, dept where majorid = did")
+    }
+
+    /// dir に scale の量のデータを入れた上で、count 件の student を insert した transaction を commit せずに止める
+    /// buffer は disk に書き出してあるので、次に open した database を recover すると count 件の insert が取り消される
+    pub fn crash_with_uncommitted_students(
+        dir: &Path,
+        scale: BenchScale,
+        count: usize,
+    ) -> AnyhowResult<()> {
+        let mut db = Self::populate(dir, scale)?;
+        let tx = db.db.new_tx()?;
+        db.insert_students_in(count, &tx)?;
+        db.db.buffer_manager().flush_all()?;
+        Ok(())
+    }
+
+    /// dir の database を open して recover する
+    pub fn recover(dir: &Path) -> AnyhowResult<()> {
+        let db = Self::open_db(dir)?;
+        let tx = db.new_tx()?;
+        tx.borrow_mut().recover()?;
+        Ok(())
+    }
+
+    fn open_db(dir: &Path) -> AnyhowResult<SimpleDB> {
+        let dir_name = dir
+            .to_str()
+            .ok_or_else(|| anyhow!("invalid directory name: {}", dir.display()))?;
+        Ok(SimpleDB::with_config(
+            dir_name,
+            SimpleDBConfig {
+                buffer_size: Self::BUFFER_SIZE,
+                ..Default::default()
+            },
+        )?)
+    }
+
+    fn insert_students_in(
+        &mut self,
+        count: usize,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        let departments = self.scale.departments.max(1);
+        for _ in 0..count {
+            let sid = self.next_sid;
+            self.db.executor().exec_update_command(
+                &format!(
+                    "insert into student (sid, sname, gradyear, majorid) values ({}, 's{}', {}, {})",
+                    sid,
+                    sid,
+                    2000 + sid % 25,
+                    sid % departments
+                ),
+                tx,
+            )?;
+            self.next_sid += 1;
+        }
+        Ok(())
+    }
+
+    fn count_rows(&self, query: &str) -> AnyhowResult<usize> {
+        let tx = self.db.new_tx()?;
+        let mut count = 0;
+        {
+            let mut scan = self.db.executor().exec_query(query, &tx)?;
+            while scan.move_next()? {
+                count += 1;
+            }
+        }
+        tx.borrow_mut().commit()?;
+        Ok(count)
+    }
+}
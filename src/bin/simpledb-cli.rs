@@ -0,0 +1,36 @@
+use std::{
+    env,
+    io::{self, IsTerminal},
+    path::Path,
+    process::ExitCode,
+};
+
+use simpledb::cli::run_repl;
+
+const USAGE: &str = "usage: simpledb-cli <db-dir>";
+
+fn main() -> ExitCode {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> anyhow::Result<()> {
+    let [dir] = args else {
+        return Err(anyhow::anyhow!(USAGE));
+    };
+    let stdin = io::stdin();
+    // pipe などから読む場合は prompt を出力しない
+    let prompt = stdin.is_terminal();
+    run_repl(
+        Path::new(dir),
+        stdin.lock(),
+        &mut io::stdout().lock(),
+        prompt,
+    )
+}
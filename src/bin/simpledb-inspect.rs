@@ -0,0 +1,50 @@
+use std::{env, path::Path, process::ExitCode};
+
+use simpledb::inspect::{inspect_log, inspect_table};
+
+const USAGE: &str = "usage: simpledb-inspect <db-dir> table <table-name> [--block-size N]
+       simpledb-inspect <db-dir> log [--block-size N]";
+const DEFAULT_BLOCK_SIZE: usize = 400;
+
+fn main() -> ExitCode {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match run(&args) {
+        Ok(output) => {
+            print!("{}", output);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> anyhow::Result<String> {
+    let (args, block_size) = split_block_size(args)?;
+    match args.as_slice() {
+        [dir, command, table_name] if command == "table" => {
+            inspect_table(Path::new(dir), table_name, block_size)
+        }
+        [dir, command] if command == "log" => inspect_log(Path::new(dir), block_size),
+        _ => Err(anyhow::anyhow!(USAGE)),
+    }
+}
+
+/// --block-size N を取り除いた引数と、block size を返す
+fn split_block_size(args: &[String]) -> anyhow::Result<(Vec<String>, usize)> {
+    let mut rest = vec![];
+    let mut block_size = DEFAULT_BLOCK_SIZE;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--block-size" {
+            block_size = iter
+                .next()
+                .and_then(|size| size.parse().ok())
+                .ok_or_else(|| anyhow::anyhow!(USAGE))?;
+        } else {
+            rest.push(arg.clone());
+        }
+    }
+    Ok((rest, block_size))
+}
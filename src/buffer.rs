@@ -1,2 +1,3 @@
 pub mod buffer;
 pub mod buffer_manager;
+pub mod wal_check;
@@ -1,3 +1,4 @@
+use crate::buffer::wal_check;
 use crate::file::{blockid, file_manager, page};
 use crate::log::log_manager;
 
@@ -10,27 +11,31 @@ use thiserror::Error;
  *
  * 以下のような機能を持つ:
  * - block の内容を page を通して読み書きする
- * - block が変更されたかどうかの追跡 (log sequence number と transaction number を用いる)
- * - いくつのクライアントがこの buffer を pin しているかの追跡
+ * - block が変更されたかどうかの追跡 (dirty flag と、変更した transaction の番号、log sequence number を用いる)
+ *
+ * いくつのクライアントがこの buffer を pin しているかは BufferManager が管理する
  */
 pub struct Buffer {
     fm: Arc<file_manager::FileManager>,
     lm: Arc<log_manager::LogManager>,
     contents: page::Page,
     block: Option<blockid::BlockId>, // None なら buffer は空
-    pins: usize,                     // この buffer を pin してほしいといったクライアントの数
     txnum: Option<u64>,              // transaction の番号。None なら transaction は走っていない
     lsn: Option<u64>,                // この buffer が最後に書き込まれた log sequence number
+    // block に書き込まれていない変更があるかどうか。書き込みに失敗した場合は true のまま残す
+    dirty: bool,
 }
 
 #[derive(Error, Debug)]
-pub(crate) enum BufferError {
+pub enum BufferError {
     #[error("I/O error: {0}")]
     Io(#[from] io::Error),
     #[error("Error from log manager: {0}")]
     Log(#[from] log_manager::LogError),
     #[error("Error from file manager: {0}")]
     FileManager(#[from] file_manager::FileManagerError),
+    #[error("Failed to flush block {0}: {1}")]
+    Flush(blockid::BlockId, log_manager::LogError),
 }
 
 impl Buffer {
@@ -41,9 +46,9 @@ impl Buffer {
             lm,
             block: None,
             contents: page::Page::new_from_size(block_size),
-            pins: 0,
             txnum: None,
             lsn: None,
+            dirty: false,
         }
     }
 
@@ -64,45 +69,60 @@ impl Buffer {
     pub fn set_modified(&mut self, txnum: u64, lsn: Option<u64>) {
         self.txnum = Some(txnum);
         self.lsn = lsn;
-    }
-
-    // buffer を通して block の読み書きをしているクライアントの数を追加する
-    pub fn pin(&mut self) {
-        self.pins += 1;
-    }
-
-    // buffer を通して block の読み書きをしているクライアントの数を減らす
-    pub fn unpin(&mut self) {
-        self.pins -= 1;
-    }
-
-    pub fn is_pinned(&self) -> bool {
-        self.pins > 0
+        self.dirty = true;
     }
 
     pub fn modifying_tx(&self) -> Option<u64> {
         self.txnum
     }
 
+    // block に書き込まれていない変更があるかどうかを返す
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
     // buffer が参照する block を変更する
     // WAL に従って buffer が参照する block に対して行われた変更を書き込む
+    // 変更の書き込みに失敗した場合は、元の block を参照したままエラーを返す
     pub(crate) fn assign_to_block(&mut self, block: &blockid::BlockId) -> Result<(), BufferError> {
         self.flush()?;
+        // ファイルごとに block size が異なりうるので、必要なら page を作り直す
+        let block_size = self.fm.block_size_of(block.file_name())?;
+        if self.contents.contents().len() != block_size {
+            self.contents = page::Page::new_from_size(block_size);
+        }
         self.block = Some(block.clone());
         self.fm.read(block, &mut self.contents)?;
-        self.pins = 0;
         Ok(())
     }
 
+    // buffer が参照する block を、変更を書き込まずに外す
+    // 削除するファイルの block を保持している場合に使う
+    pub(crate) fn discard(&mut self) {
+        self.block = None;
+        self.txnum = None;
+        self.lsn = None;
+        self.dirty = false;
+    }
+
     // buffer が参照する block に対して行われた変更を書き込み、永続性を保証する
-    pub(crate) fn flush(&mut self) -> Result<(), log_manager::LogError> {
-        if self.block.is_some() && self.txnum.is_some() {
-            let lsn = self.lsn.unwrap_or(0);
+    // 失敗した場合は dirty のまま残すので、変更が失われることはなく、再度 flush すれば書き込みを再試行できる
+    pub(crate) fn flush(&mut self) -> Result<(), BufferError> {
+        let Some(block) = self.block.as_ref().filter(|_| self.dirty) else {
+            return Ok(());
+        };
+        let lsn = self.lsn.unwrap_or(0);
+        let result = (|| -> Result<(), log_manager::LogError> {
             self.lm.flush(lsn)?;
-            self.fm
-                .write(self.block.as_ref().unwrap(), &self.contents)?;
-            self.txnum = None;
-        }
+            if cfg!(debug_assertions) {
+                wal_check::check_flush(block, lsn, self.lm.last_saved_lsn()?);
+            }
+            self.fm.write(block, &self.contents)?;
+            Ok(())
+        })();
+        result.map_err(|err| BufferError::Flush(block.clone(), err))?;
+        self.dirty = false;
+        self.txnum = None;
         Ok(())
     }
 }
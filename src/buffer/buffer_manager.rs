@@ -1,9 +1,11 @@
-use std::sync::{Arc, Condvar, Mutex};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
 use std::time;
 use thiserror::Error;
 
 use crate::buffer::buffer;
-use crate::file::{blockid, file_manager};
+use crate::file::{blockid, file_manager, page};
 use crate::log::log_manager;
 
 const MAX_PIN_WAIT_TIME_MS: u64 = 10_000; // 10 seconds
@@ -16,14 +18,77 @@ const MAX_PIN_WAIT_TIME_MS: u64 = 10_000; // 10 seconds
  *
  * buffer manager の性質により、pin されている間は、明示的に flush_all を呼ばない限り、buffer pool に書き込まれた内容は block に書き込まれない
  *
+ * 排他制御について:
+ * - block -> buffer の対応表と、pin されていない buffer の free list は PoolState として一つの mutex で保護する
+ *   ただし、この mutex を保持している間に disk I/O や全 buffer の走査は行わない
+ * - 各 buffer の pin 数は atomic な値として buffer ごとに持ち、buffer の mutex を取らずに更新する
+ * - pin 数が 0 と 1 の間で変化する場合 (free list に出し入れする必要がある場合) のみ PoolState の mutex を取る
+ * - mutex を取る順番は必ず PoolState -> 各 buffer の順とする
+ * - 追い出す block の変更は PoolState の mutex を離してから書き込むので、書き込みが終わるまでその block は evicting に入れておき、
+ *   その間にその block を pin しようとするクライアントは待たせる (disk から古い内容を読まないようにするため)
+ *
+ * buffer pool は用途 (BufferPoolKind) ごとに分けることができる
+ * 各 buffer はどれか一つの用途に属し、block はファイルに登録された用途の buffer にのみ割り当てられる
+ * これにより、大きな table の scan で catalog や index の block が追い出されることを防ぐ
+ * 登録されていないファイルや、buffer が一つもない用途のファイルは data 用の buffer を使う
+ *
  * プログラム全体で一つしかない想定
  */
 pub struct BufferManager {
     buffer_pool: Vec<Arc<Mutex<buffer::Buffer>>>,
-    num_available: Arc<(Mutex<usize>, Condvar)>,
+    // buffer_pool の各 buffer が属する用途
+    buffer_kinds: Vec<BufferPoolKind>,
+    // buffer_pool の各 buffer を pin しているクライアントの数
+    pin_counts: Vec<AtomicUsize>,
+    state: Mutex<PoolState>,
+    // buffer が free list に戻されたことを pin 待ちのクライアントに通知する
+    buffer_freed: Condvar,
+    num_available: AtomicUsize,
     max_pin_wait_time_ms: u64,
 }
 
+/**
+ * buffer pool 全体で共有する管理情報
+ */
+struct PoolState {
+    // block -> その block を保持している buffer の index
+    block_to_index: HashMap<blockid::BlockId, usize>,
+    // buffer の index -> その buffer が保持している block
+    index_to_block: Vec<Option<blockid::BlockId>>,
+    // 用途ごとの、pin されていない buffer の index。先頭にあるものほど長い間使われていない
+    free_lists: HashMap<BufferPoolKind, VecDeque<usize>>,
+    // ファイル名 -> そのファイルの block を割り当てる buffer の用途
+    file_kinds: HashMap<String, BufferPoolKind>,
+    // buffer から追い出している途中 (変更を書き込んでいる途中) の block
+    evicting: HashSet<blockid::BlockId>,
+}
+
+/// buffer pool を用途ごとに分けるときの用途
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BufferPoolKind {
+    Data,
+    Catalog,
+    Index,
+}
+
+/// 用途ごとの buffer の数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferPoolSizes {
+    pub data: usize,
+    pub catalog: usize,
+    pub index: usize,
+}
+
+impl BufferPoolSizes {
+    fn size(&self, kind: BufferPoolKind) -> usize {
+        match kind {
+            BufferPoolKind::Data => self.data,
+            BufferPoolKind::Catalog => self.catalog,
+            BufferPoolKind::Index => self.index,
+        }
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum BufferManagerError {
     #[error("Error from buffer: {0}")]
@@ -43,16 +108,61 @@ impl BufferManager {
         num_buffs: usize,
         max_pin_wait_time_ms: Option<u64>,
     ) -> BufferManager {
+        Self::with_pools(
+            fm,
+            lm,
+            BufferPoolSizes {
+                data: num_buffs,
+                ..Default::default()
+            },
+            max_pin_wait_time_ms,
+        )
+    }
+
+    /// 用途ごとに sizes で指定した数の buffer を持つ buffer manager を作成する
+    /// sizes.data が 0 の場合、用途が登録されていないファイルの block は pin できない
+    pub fn with_pools(
+        fm: Arc<file_manager::FileManager>,
+        lm: Arc<log_manager::LogManager>,
+        sizes: BufferPoolSizes,
+        max_pin_wait_time_ms: Option<u64>,
+    ) -> BufferManager {
+        let num_buffs = sizes.data + sizes.catalog + sizes.index;
         let mut buffer_pool = Vec::with_capacity(num_buffs);
-        for _ in 0..num_buffs {
-            buffer_pool.push(Arc::new(Mutex::new(buffer::Buffer::new(
-                fm.clone(),
-                lm.clone(),
-            ))));
+        let mut buffer_kinds = Vec::with_capacity(num_buffs);
+        let mut pin_counts = Vec::with_capacity(num_buffs);
+        let mut free_lists: HashMap<BufferPoolKind, VecDeque<usize>> = HashMap::new();
+        for kind in [
+            BufferPoolKind::Data,
+            BufferPoolKind::Catalog,
+            BufferPoolKind::Index,
+        ] {
+            for _ in 0..sizes.size(kind) {
+                free_lists
+                    .entry(kind)
+                    .or_default()
+                    .push_back(buffer_pool.len());
+                buffer_pool.push(Arc::new(Mutex::new(buffer::Buffer::new(
+                    fm.clone(),
+                    lm.clone(),
+                ))));
+                buffer_kinds.push(kind);
+                pin_counts.push(AtomicUsize::new(0));
+            }
         }
         BufferManager {
             buffer_pool,
-            num_available: Arc::new((Mutex::new(num_buffs), Condvar::new())),
+            buffer_kinds,
+            pin_counts,
+            state: Mutex::new(PoolState {
+                block_to_index: HashMap::new(),
+                index_to_block: vec![None; num_buffs],
+                free_lists,
+                file_kinds: HashMap::new(),
+                evicting: HashSet::new(),
+            }),
+            buffer_freed: Condvar::new(),
+            num_available: AtomicUsize::new(num_buffs),
             max_pin_wait_time_ms: match max_pin_wait_time_ms {
                 Some(ms) => ms,
                 None => MAX_PIN_WAIT_TIME_MS,
@@ -60,32 +170,159 @@ impl BufferManager {
         }
     }
 
+    /// filename の block を kind 用の buffer に割り当てるようにする
+    /// すでに buffer に割り当てられている block は、置き換えられるまでその buffer に残る
+    pub fn register_file(
+        &self,
+        filename: &str,
+        kind: BufferPoolKind,
+    ) -> Result<(), BufferManagerError> {
+        self.lock_state()?
+            .file_kinds
+            .insert(filename.to_string(), kind);
+        Ok(())
+    }
+
+    /**
+     * blk の内容の写しを返す。読み込み専用で block を読む scan のために使う
+     *
+     * block がすでに buffer pool にある場合は、pin せずにその buffer の mutex を短時間だけ取って内容を写す
+     * buffer pool にない場合は、通常通り pin して読み込んでから写して unpin する (追い出し途中の block を disk から古い内容で読まないようにするため)
+     * block に対する lock は取らないので、未 commit の変更を読まないようにするには呼び出し側で slock を取る必要がある
+     */
+    pub fn read_block_copy(
+        &self,
+        blk: &blockid::BlockId,
+    ) -> Result<page::Page, BufferManagerError> {
+        let cached = {
+            let state = self.lock_state()?;
+            state
+                .block_to_index
+                .get(blk)
+                .map(|&index| self.buffer_pool[index].clone())
+        };
+        if let Some(buf_lock) = cached {
+            let buf = buf_lock.lock().map_err(|_| BufferManagerError::Lock)?;
+            // PoolState の mutex を離してから buffer の mutex を取るまでの間に、別の block に割り当てられている場合がある
+            if buf.block() == Some(blk) {
+                return Ok(page::Page::new_from_vec(buf.contents().contents()));
+            }
+        }
+        let buf_lock = self.pin(blk)?;
+        let page = {
+            let buf = buf_lock.lock().map_err(|_| BufferManagerError::Lock)?;
+            page::Page::new_from_vec(buf.contents().contents())
+        };
+        self.unpin(buf_lock)?;
+        Ok(page)
+    }
+
+    /// filename の block を割り当てる buffer の用途を返す
+    pub fn pool_of(&self, filename: &str) -> Result<BufferPoolKind, BufferManagerError> {
+        let state = self.lock_state()?;
+        Ok(Self::kind_of(&state, filename))
+    }
+
+    /// kind 用の buffer のうち、pin されていない buffer の数を返す
+    pub fn available_in(&self, kind: BufferPoolKind) -> Result<usize, BufferManagerError> {
+        Ok(self
+            .lock_state()?
+            .free_lists
+            .get(&kind)
+            .map_or(0, VecDeque::len))
+    }
+
     // Buffer にある空きの buffer の数を返す
     pub fn available(&self) -> Result<usize, BufferManagerError> {
-        let (value, _) = &*self.num_available;
-        Ok(*value.lock().map_err(|_| BufferManagerError::Lock)?)
+        Ok(self.num_available.load(Ordering::SeqCst))
     }
 
     // buffer pool に書き込まれた内容を block に書き込み、永続性を保証する
+    // 書き込みに失敗した buffer があっても残りの buffer の書き込みは続け、最初の失敗を返す
     pub fn flush_all(&self) -> Result<(), BufferManagerError> {
+        let mut first_error = None;
         for buf_lock in &self.buffer_pool {
             let mut buf = buf_lock.lock().map_err(|_| BufferManagerError::Lock)?;
-            if buf.block().is_some() {
+            if let Err(err) = buf.flush() {
+                first_error.get_or_insert(err);
+            }
+        }
+        match first_error {
+            Some(err) => Err(err.into()),
+            None => Ok(()),
+        }
+    }
+
+    // filename の block を保持している buffer の内容だけを block に書き込む
+    // commit を待たずに disk 上に残しておきたいファイル (起動時に recovery より前に読む catalog など) に使う
+    pub fn flush_file(&self, filename: &str) -> Result<(), BufferManagerError> {
+        for buf_lock in &self.buffer_pool {
+            let mut buf = buf_lock.lock().map_err(|_| BufferManagerError::Lock)?;
+            if buf
+                .block()
+                .is_some_and(|block| block.file_name() == filename)
+            {
                 buf.flush()?;
             }
         }
         Ok(())
     }
 
+    // ファイルの block を保持している buffer を、変更を書き込まずに空にする
+    // 削除するファイルの変更が後から書き込まれてファイルが作り直されることを防ぐ
+    // pin されている buffer は空にできないので、その場合はエラーを返す
+    pub fn discard_file(&self, filename: &str) -> Result<(), BufferManagerError> {
+        let mut state = self.lock_state()?;
+        let indexes: Vec<usize> = state
+            .block_to_index
+            .iter()
+            .filter(|(block, _)| block.file_name() == filename)
+            .map(|(_, &index)| index)
+            .collect();
+        if indexes
+            .iter()
+            .any(|&index| self.pin_counts[index].load(Ordering::SeqCst) > 0)
+        {
+            return Err(BufferManagerError::Pin);
+        }
+        for index in indexes {
+            if let Some(block) = state.index_to_block[index].take() {
+                state.block_to_index.remove(&block);
+            }
+            self.buffer_pool[index]
+                .lock()
+                .map_err(|_| BufferManagerError::Lock)?
+                .discard();
+        }
+        Ok(())
+    }
+
     // 不要になった buffer を pin から外す
     pub fn unpin(&self, buf: Arc<Mutex<buffer::Buffer>>) -> Result<(), BufferManagerError> {
-        let mut buf = buf.lock().map_err(|_| BufferManagerError::Lock)?;
-        buf.unpin();
-        if !buf.is_pinned() {
-            let (value, cond) = &*self.num_available;
-            let mut num_available = value.lock().map_err(|_| BufferManagerError::Lock)?;
-            *num_available += 1;
-            cond.notify_all();
+        let index = self.index_of(&buf)?;
+        let pin_count = &self.pin_counts[index];
+        // 他にも pin しているクライアントがいる場合は、pool 全体の mutex を取らずに pin 数を減らす
+        let mut current = pin_count.load(Ordering::SeqCst);
+        while current > 1 {
+            match pin_count.compare_exchange(
+                current,
+                current - 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return Ok(()),
+                Err(actual) => current = actual,
+            }
+        }
+        // 最後の pin を外す場合は free list に戻す必要があるため、pool 全体の mutex を取る
+        let mut state = self.lock_state()?;
+        if pin_count.load(Ordering::SeqCst) == 0 {
+            return Err(BufferManagerError::Pin);
+        }
+        if pin_count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            Self::push_free_buffer(&mut state, self.buffer_kinds[index], index);
+            self.num_available.fetch_add(1, Ordering::SeqCst);
+            self.buffer_freed.notify_all();
         }
         Ok(())
     }
@@ -97,96 +334,312 @@ impl BufferManager {
         blk: &blockid::BlockId,
     ) -> Result<Arc<Mutex<buffer::Buffer>>, BufferManagerError> {
         let start = time::Instant::now();
-        let mut buff = self.try_to_pin(blk)?;
-        while buff.is_none() && get_waiting_time(start) < self.max_pin_wait_time_ms {
-            // buffer が確保できなかった場合、max_pin_wait_time_ms まで待つ
-            let (num_available_lock, cond) = &*self.num_available;
-            let num_available = num_available_lock
-                .lock()
-                .map_err(|_| BufferManagerError::Lock)?;
-            let (_num_available, _) = cond
+        let mut state = self.lock_state()?;
+        loop {
+            // 追い出し中の block は、変更の書き込みが終わるまで待ってから読み込む
+            if !state.evicting.contains(blk) {
+                if let Some(&index) = state.block_to_index.get(blk) {
+                    // すでに buffer pool に block を参照している buffer が存在する場合、その buffer を返す
+                    self.pin_index(&mut state, index);
+                    return Ok(self.buffer_pool[index].clone());
+                }
+                let kind = Self::kind_of(&state, blk.file_name());
+                if let Some(index) = Self::pop_free_buffer(&mut state, kind) {
+                    return self.assign_and_pin(state, index, blk);
+                }
+            }
+            // buffer が確保できなかった場合や追い出し中の場合、max_pin_wait_time_ms まで待つ
+            let waited = get_waiting_time(start);
+            if waited >= self.max_pin_wait_time_ms {
+                return Err(BufferManagerError::Pin);
+            }
+            let (next_state, _) = self
+                .buffer_freed
                 .wait_timeout(
-                    num_available,
-                    time::Duration::from_millis(self.max_pin_wait_time_ms),
+                    state,
+                    time::Duration::from_millis(self.max_pin_wait_time_ms - waited),
                 )
                 .map_err(|_| BufferManagerError::Pin)?;
             // buffer が空いた通知が来たので、再度 buffer 確保を試みる
-            buff = self.try_to_pin(blk)?;
-        }
-        match buff {
-            Some(b) => Ok(b),
-            None => Err(BufferManagerError::Pin),
+            state = next_state;
         }
     }
 
-    // buffer pool に block を割り当てを試みる
-    // 割り当てられなかった場合、None を返す
-    fn try_to_pin(
+    /**
+     * filename の start 番目から count 個の連続した block を、まとめて pin する
+     *
+     * 全ての block に割り当てられるだけの buffer が空くまで待ってから一度に pin するので、一部の block だけを pin した状態にはならない
+     * multibuffer の sort や join が chunk の block を 1 つずつ pin すると、途中まで確保した状態で他のクライアントと buffer を待ち合って
+     * deadlock することがあるため、その代わりに使う
+     * max_pin_wait_time_ms まで待っても buffer が足りない場合や、読み込みに失敗した場合は、何も pin せずにエラーを返す
+     */
+    pub fn pin_range(
         &self,
-        blk: &blockid::BlockId,
-    ) -> Result<Option<Arc<Mutex<buffer::Buffer>>>, BufferManagerError> {
-        let maybe_buf_lock = self.find_existing_buffer(blk)?;
-        let maybe_buf_lock = match maybe_buf_lock {
-            Some(buf_lock) => Some(buf_lock),
-            None => {
-                // buffer pool に block を参照している buffer が存在しない場合、pin されていない buffer から確保を試みる
-                let maybe_buf_lock = self.choose_unpinned_buffer()?;
-                match maybe_buf_lock {
-                    None => None,
-                    Some(buf_lock) => {
-                        // pin できる buffer が見つかった場合、その buffer に block を割り当てる
-                        let mut buf = buf_lock.lock().map_err(|_| BufferManagerError::Lock)?;
-                        buf.assign_to_block(blk)?;
-                        Some(buf_lock.clone())
-                    }
+        filename: &str,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<Arc<Mutex<buffer::Buffer>>>, BufferManagerError> {
+        let blocks: Vec<blockid::BlockId> = (start..start + count)
+            .map(|number| blockid::BlockId::new(filename, number))
+            .collect();
+        if blocks.is_empty() {
+            return Ok(vec![]);
+        }
+        let start_time = time::Instant::now();
+        let mut state = self.lock_state()?;
+        let kind = Self::kind_of(&state, filename);
+        let capacity = self.buffer_kinds.iter().filter(|&&k| k == kind).count();
+        loop {
+            // buffer pool にない block と、buffer pool にあっても pin されていない block は、どちらも free list の buffer を使う
+            let needed = blocks
+                .iter()
+                .filter(|blk| match state.block_to_index.get(blk) {
+                    Some(&index) => self.pin_counts[index].load(Ordering::SeqCst) == 0,
+                    None => true,
+                })
+                .count();
+            // pool の大きさを超える場合は、待っても確保できない
+            if needed > capacity {
+                return Err(BufferManagerError::Pin);
+            }
+            let free = state.free_lists.get(&kind).map_or(0, |list| list.len());
+            let evicting = blocks.iter().any(|blk| state.evicting.contains(blk));
+            if free >= needed && !evicting {
+                break;
+            }
+            let waited = get_waiting_time(start_time);
+            if waited >= self.max_pin_wait_time_ms {
+                return Err(BufferManagerError::Pin);
+            }
+            let (next_state, _) = self
+                .buffer_freed
+                .wait_timeout(
+                    state,
+                    time::Duration::from_millis(self.max_pin_wait_time_ms - waited),
+                )
+                .map_err(|_| BufferManagerError::Pin)?;
+            state = next_state;
+        }
+
+        // 空きを確認してから PoolState の mutex を離さずに、全ての block に buffer を割り当てる
+        // buffer pool にある block を先に pin して、その buffer が他の block に割り当てられないようにする
+        let mut indexes = blocks
+            .iter()
+            .map(|blk| {
+                let index = state.block_to_index.get(blk).copied();
+                if let Some(index) = index {
+                    self.pin_index(&mut state, index);
                 }
+                index
+            })
+            .collect::<Vec<_>>();
+        // 新しく block を割り当てた buffer と、その buffer が元々保持していた block
+        let mut assigned = vec![];
+        for (blk, slot) in blocks.iter().zip(indexes.iter_mut()) {
+            if slot.is_some() {
+                continue;
             }
-        };
-        match maybe_buf_lock {
-            Some(buf_lock) => {
-                let mut buf = buf_lock.lock().map_err(|_| BufferManagerError::Lock)?;
-                if !buf.is_pinned() {
-                    // pin する予定の buffer がこれ以前に pin されていない場合、この pin により available な buffer が一つ減ったことを意味する
-                    let (value, _) = &*self.num_available;
-                    let mut num_available = value.lock().map_err(|_| BufferManagerError::Lock)?;
-                    *num_available -= 1;
+            let index = Self::pop_free_buffer(&mut state, kind).ok_or(BufferManagerError::Pin)?;
+            let old_block = Self::start_eviction(&mut state, index);
+            state.block_to_index.insert(blk.clone(), index);
+            state.index_to_block[index] = Some(blk.clone());
+            self.pin_counts[index].fetch_add(1, Ordering::SeqCst);
+            self.num_available.fetch_sub(1, Ordering::SeqCst);
+            *slot = Some(index);
+            assigned.push((index, blk.clone(), old_block));
+        }
+
+        // assign_and_pin と同じく、buffer の mutex を取ってから PoolState の mutex を解放して読み込む
+        let mut guards = Vec::with_capacity(assigned.len());
+        for (index, _, _) in &assigned {
+            guards.push(
+                self.buffer_pool[*index]
+                    .lock()
+                    .map_err(|_| BufferManagerError::Lock)?,
+            );
+        }
+        let indexes = indexes.into_iter().flatten().collect::<Vec<_>>();
+        drop(state);
+        let mut loaded = 0;
+        let mut result = Ok(());
+        for (buf, (_, blk, _)) in guards.iter_mut().zip(&assigned) {
+            result = buf.assign_to_block(blk);
+            if result.is_err() {
+                break;
+            }
+            loaded += 1;
+        }
+        drop(guards);
+        // 読み込めた buffer の元の block は書き込みが終わっているので、pin できるようにする
+        for (_, _, old_block) in assigned.iter().take(loaded) {
+            self.finish_eviction(old_block.as_ref())?;
+        }
+        if let Err(err) = result {
+            // 読み込めなかった buffer の割り当てを取り消し、それ以外の buffer は unpin して何も pin していない状態に戻す
+            let not_loaded = assigned
+                .iter()
+                .skip(loaded)
+                .map(|(index, _, _)| *index)
+                .collect::<Vec<_>>();
+            for (index, blk, old_block) in assigned.into_iter().skip(loaded) {
+                self.cancel_assign(index, &blk, old_block)?;
+            }
+            for index in indexes {
+                if !not_loaded.contains(&index) {
+                    self.unpin(self.buffer_pool[index].clone())?;
                 }
+            }
+            return Err(err.into());
+        }
+        Ok(indexes
+            .into_iter()
+            .map(|index| self.buffer_pool[index].clone())
+            .collect())
+    }
+
+    fn lock_state(&self) -> Result<MutexGuard<'_, PoolState>, BufferManagerError> {
+        self.state.lock().map_err(|_| BufferManagerError::Lock)
+    }
 
-                buf.pin();
-                Ok(Some(buf_lock.clone()))
+    // index の buffer の pin 数を増やす
+    // PoolState の mutex を保持した状態で呼び出す必要がある
+    fn pin_index(&self, state: &mut PoolState, index: usize) {
+        if self.pin_counts[index].fetch_add(1, Ordering::SeqCst) == 0 {
+            // pin する予定の buffer がこれ以前に pin されていない場合、この pin により available な buffer が一つ減ったことを意味する
+            if let Some(free_list) = state.free_lists.get_mut(&self.buffer_kinds[index]) {
+                free_list.retain(|&i| i != index);
             }
-            None => Ok(None),
+            self.num_available.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    // filename の block を割り当てる buffer の用途を返す
+    // ファイルに登録された用途の buffer が一つもない場合は data 用の buffer を使う
+    fn kind_of(state: &PoolState, filename: &str) -> BufferPoolKind {
+        match state.file_kinds.get(filename) {
+            Some(&kind) if state.free_lists.contains_key(&kind) => kind,
+            _ => BufferPoolKind::Data,
         }
     }
 
-    // すでに buffer で保持している block の pin を要求された場合、その buffer を返す
-    fn find_existing_buffer(
+    // kind 用の free list から pin されていない buffer を一つ取り出す
+    // pin されていない buffer が存在しない場合は None を返す
+    fn pop_free_buffer(state: &mut PoolState, kind: BufferPoolKind) -> Option<usize> {
+        state.free_lists.get_mut(&kind)?.pop_front()
+    }
+
+    fn push_free_buffer(state: &mut PoolState, kind: BufferPoolKind, index: usize) {
+        state.free_lists.entry(kind).or_default().push_back(index);
+    }
+
+    // index の buffer に block を割り当てて pin する
+    // disk I/O は PoolState の mutex を解放してから、buffer の mutex のみを保持した状態で行う
+    fn assign_and_pin(
         &self,
+        mut state: MutexGuard<'_, PoolState>,
+        index: usize,
         blk: &blockid::BlockId,
-    ) -> Result<Option<Arc<Mutex<buffer::Buffer>>>, BufferManagerError> {
-        for buf_lock in &self.buffer_pool {
-            let buf = buf_lock.lock().map_err(|_| BufferManagerError::Lock)?;
-            if let Some(b) = buf.block() {
-                if b == blk {
-                    return Ok(Some(buf_lock.clone()));
-                }
-            }
+    ) -> Result<Arc<Mutex<buffer::Buffer>>, BufferManagerError> {
+        let old_block = Self::start_eviction(&mut state, index);
+        state.block_to_index.insert(blk.clone(), index);
+        state.index_to_block[index] = Some(blk.clone());
+        self.pin_counts[index].fetch_add(1, Ordering::SeqCst);
+        self.num_available.fetch_sub(1, Ordering::SeqCst);
+
+        let buf_lock = &self.buffer_pool[index];
+        // buffer の mutex を取ってから PoolState の mutex を解放することで、
+        // 同じ block を pin した他のクライアントは読み込みが終わるまで buffer の中身を参照できない
+        let result = {
+            let mut buf = buf_lock.lock().map_err(|_| BufferManagerError::Lock)?;
+            drop(state);
+            buf.assign_to_block(blk)
+        };
+        if let Err(err) = result {
+            self.cancel_assign(index, blk, old_block)?;
+            return Err(err.into());
         }
-        Ok(None)
+        self.finish_eviction(old_block.as_ref())?;
+        Ok(buf_lock.clone())
     }
 
-    // buffer pool から pin されていない buffer を選択する
-    // pin されていない buffer が存在しない場合は None を返す
-    fn choose_unpinned_buffer(
+    // index の buffer から元の block の割り当てを外し、変更の書き込みが終わるまで追い出し中として記録する
+    // PoolState の mutex を保持した状態で呼び出す必要がある
+    fn start_eviction(state: &mut PoolState, index: usize) -> Option<blockid::BlockId> {
+        let old_block = state.index_to_block[index].take()?;
+        state.block_to_index.remove(&old_block);
+        state.evicting.insert(old_block.clone());
+        Some(old_block)
+    }
+
+    // 追い出した block の変更の書き込みが終わったので、その block を待っているクライアントに通知する
+    fn finish_eviction(
         &self,
-    ) -> Result<Option<Arc<Mutex<buffer::Buffer>>>, BufferManagerError> {
-        for buf_lock in &self.buffer_pool {
-            let buf = buf_lock.lock().map_err(|_| BufferManagerError::Lock)?;
-            if !buf.is_pinned() {
-                return Ok(Some(buf_lock.clone()));
+        old_block: Option<&blockid::BlockId>,
+    ) -> Result<(), BufferManagerError> {
+        if let Some(old_block) = old_block {
+            self.lock_state()?.evicting.remove(old_block);
+            self.buffer_freed.notify_all();
+        }
+        Ok(())
+    }
+
+    // buffer の buffer_pool での index を返す
+    // pin されている buffer の block は置き換えられないので、buffer が保持している block から block_to_index で引く
+    // 読み込みに失敗して block が割り当てられていない buffer の場合のみ、buffer_pool を走査する
+    fn index_of(&self, buf: &Arc<Mutex<buffer::Buffer>>) -> Result<usize, BufferManagerError> {
+        let block = buf
+            .lock()
+            .map_err(|_| BufferManagerError::Lock)?
+            .block()
+            .cloned();
+        let index = match block {
+            Some(block) => self.lock_state()?.block_to_index.get(&block).copied(),
+            None => None,
+        };
+        match index.filter(|&index| Arc::ptr_eq(&self.buffer_pool[index], buf)) {
+            Some(index) => Ok(index),
+            None => self
+                .buffer_pool
+                .iter()
+                .position(|b| Arc::ptr_eq(b, buf))
+                .ok_or(BufferManagerError::Pin),
+        }
+    }
+
+    // 読み込みに失敗した (または読み込まなかった) buffer の、blk への割り当てと pin を取り消す
+    fn cancel_assign(
+        &self,
+        index: usize,
+        blk: &blockid::BlockId,
+        old_block: Option<blockid::BlockId>,
+    ) -> Result<(), BufferManagerError> {
+        let mut state = self.lock_state()?;
+        if state.block_to_index.get(blk) == Some(&index) {
+            state.block_to_index.remove(blk);
+            state.index_to_block[index] = None;
+        }
+        // 元の block の変更の書き込みに失敗した場合、buffer は元の block の変更を持ったままなので、
+        // 割り当てを元に戻して、次に元の block を pin した時に変更が失われないようにする
+        if let Some(old_block) = old_block {
+            state.evicting.remove(&old_block);
+            self.buffer_freed.notify_all();
+            let buf = self.buffer_pool[index]
+                .lock()
+                .map_err(|_| BufferManagerError::Lock)?;
+            if buf.is_dirty()
+                && buf.block() == Some(&old_block)
+                && !state.block_to_index.contains_key(&old_block)
+            {
+                state.block_to_index.insert(old_block.clone(), index);
+                state.index_to_block[index] = Some(old_block);
             }
         }
-        Ok(None)
+        if self.pin_counts[index].fetch_sub(1, Ordering::SeqCst) == 1 {
+            Self::push_free_buffer(&mut state, self.buffer_kinds[index], index);
+            self.num_available.fetch_add(1, Ordering::SeqCst);
+            self.buffer_freed.notify_all();
+        }
+        Ok(())
     }
 }
 
@@ -236,6 +689,109 @@ mod test_buffer_manager {
         assert!(buf3.is_ok());
     }
 
+    #[test]
+    fn test_pin_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_owned();
+
+        let file_manager = Arc::new(file_manager::FileManager::new(&path, 400));
+        let log_manager =
+            Arc::new(log_manager::LogManager::new(file_manager.clone(), "testlog").unwrap());
+        let buffer_manager = BufferManager::new(file_manager, log_manager, 4, Some(100));
+
+        // すでに pin されている block は、新しい buffer を使わずに pin できる
+        let buf1 = buffer_manager
+            .pin(&blockid::BlockId::new("testfile", 1))
+            .unwrap();
+        let range = buffer_manager.pin_range("testfile", 0, 4).unwrap();
+        assert_eq!(range.len(), 4);
+        assert!(Arc::ptr_eq(&range[1], &buf1));
+        assert_eq!(buffer_manager.available().unwrap(), 0);
+        for buf in range {
+            buffer_manager.unpin(buf).unwrap();
+        }
+        assert_eq!(buffer_manager.available().unwrap(), 3);
+
+        // 足りない分の buffer が空かない場合は、一部だけを pin せずにエラーを返す
+        let other = buffer_manager
+            .pin(&blockid::BlockId::new("otherfile", 0))
+            .unwrap();
+        assert!(matches!(
+            buffer_manager.pin_range("testfile", 2, 3),
+            Err(BufferManagerError::Pin)
+        ));
+        assert_eq!(buffer_manager.available().unwrap(), 2);
+        // pool の大きさを超える範囲は待たずにエラーを返す
+        assert!(matches!(
+            buffer_manager.pin_range("testfile", 10, 5),
+            Err(BufferManagerError::Pin)
+        ));
+        assert!(buffer_manager
+            .pin_range("testfile", 0, 0)
+            .unwrap()
+            .is_empty());
+
+        // 空いたら pin できる
+        buffer_manager.unpin(other).unwrap();
+        let range = buffer_manager.pin_range("testfile", 2, 3).unwrap();
+        assert_eq!(buffer_manager.available().unwrap(), 0);
+        for buf in range {
+            buffer_manager.unpin(buf).unwrap();
+        }
+        buffer_manager.unpin(buf1).unwrap();
+        assert_eq!(buffer_manager.available().unwrap(), 4);
+    }
+
+    #[test]
+    fn test_failed_flush_keeps_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_owned();
+
+        let file_manager = Arc::new(file_manager::FileManager::new(&path, 400));
+        let log_manager =
+            Arc::new(log_manager::LogManager::new(file_manager.clone(), "testlog").unwrap());
+        let buffer_manager = BufferManager::new(file_manager.clone(), log_manager, 1, Some(100));
+
+        let block = blockid::BlockId::new("testfile", 0);
+        let buf_lock = buffer_manager.pin(&block).unwrap();
+        {
+            let mut buf = buf_lock.lock().unwrap();
+            buf.contents_mut().set_int(0, 123);
+            buf.set_modified(1, Some(0));
+        }
+        buffer_manager.unpin(buf_lock).unwrap();
+
+        // ファイルと同じ名前の directory を作って、変更を書き込めないようにする
+        file_manager.delete_file("testfile").unwrap();
+        std::fs::create_dir(path.join("testfile")).unwrap();
+
+        // 追い出すための書き込みに失敗するので、別の block は pin できない
+        assert!(matches!(
+            buffer_manager.pin(&blockid::BlockId::new("other", 0)),
+            Err(BufferManagerError::Buffer(_))
+        ));
+        assert!(buffer_manager.flush_all().is_err());
+        // 書き込めなかった変更は buffer に残っている
+        let buf_lock = buffer_manager.pin(&block).unwrap();
+        {
+            let buf = buf_lock.lock().unwrap();
+            assert!(buf.is_dirty());
+            assert_eq!(buf.contents().get_int(0), 123);
+        }
+        buffer_manager.unpin(buf_lock).unwrap();
+
+        // 書き込めるようになれば、追い出す時に変更が書き込まれる
+        std::fs::remove_dir(path.join("testfile")).unwrap();
+        let buf_lock = buffer_manager
+            .pin(&blockid::BlockId::new("other", 0))
+            .unwrap();
+        assert!(!buf_lock.lock().unwrap().is_dirty());
+        buffer_manager.unpin(buf_lock).unwrap();
+        let mut page = page::Page::new_from_size(400);
+        file_manager.read(&block, &mut page).unwrap();
+        assert_eq!(page.get_int(0), 123);
+    }
+
     #[test]
     fn test_buffer_read_and_write() {
         let dir = tempfile::tempdir().unwrap();
@@ -355,4 +911,189 @@ mod test_buffer_manager {
             assert_eq!(page.get_int(0), 123);
         }
     }
+
+    #[test]
+    fn test_concurrent_pin_and_unpin() {
+        // 複数の thread から同時に pin / unpin しても、最終的に全ての buffer が空きに戻る
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_owned();
+
+        let file_manager = Arc::new(file_manager::FileManager::new(&path, 400));
+        let log_manager =
+            Arc::new(log_manager::LogManager::new(file_manager.clone(), "testlog").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(file_manager, log_manager, 3, Some(1000)));
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let buffer_manager = buffer_manager.clone();
+                std::thread::spawn(move || {
+                    for j in 0..50 {
+                        let blk = blockid::BlockId::new("testfile", (i + j) % 5);
+                        let buf = buffer_manager.pin(&blk).unwrap();
+                        assert_eq!(buf.lock().unwrap().block(), Some(&blk));
+                        buffer_manager.unpin(buf).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(buffer_manager.available().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_pin_while_evicting_dirty_block() {
+        // 変更された block を追い出している途中に別の thread がその block を pin しても、書き込み前の古い内容を読まない
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_owned();
+
+        let file_manager = Arc::new(file_manager::FileManager::new(&path, 400));
+        let log_manager =
+            Arc::new(log_manager::LogManager::new(file_manager.clone(), "testlog").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager,
+            log_manager.clone(),
+            2,
+            Some(1000),
+        ));
+
+        let block = blockid::BlockId::new("testfile", 0);
+        for i in 0..20 {
+            // block を変更して先に unpin し、次に追い出される buffer にする
+            let buf = buffer_manager.pin(&block).unwrap();
+            {
+                let mut buf = buf.lock().unwrap();
+                buf.contents_mut().set_int(0, i);
+                // 追い出す時に log の書き込みを待つようにして、追い出しにかかる時間を長くする
+                let lsn = log_manager.append(&[0; 8]).unwrap();
+                buf.set_modified(1, Some(lsn));
+            }
+            let filler = buffer_manager
+                .pin(&blockid::BlockId::new("otherfile", 2 * i as usize))
+                .unwrap();
+            buffer_manager.unpin(buf).unwrap();
+            buffer_manager.unpin(filler).unwrap();
+
+            let evictor = {
+                let buffer_manager = buffer_manager.clone();
+                std::thread::spawn(move || {
+                    let other = blockid::BlockId::new("otherfile", 2 * i as usize + 1);
+                    let buf = buffer_manager.pin(&other).unwrap();
+                    buffer_manager.unpin(buf).unwrap();
+                })
+            };
+            // block の割り当てが外されて追い出しが始まってから pin する
+            while buffer_manager
+                .lock_state()
+                .unwrap()
+                .block_to_index
+                .contains_key(&block)
+            {
+                std::thread::yield_now();
+            }
+            let buf = buffer_manager.pin(&block).unwrap();
+            assert_eq!(buf.lock().unwrap().contents().get_int(0), i);
+            buffer_manager.unpin(buf).unwrap();
+            evictor.join().unwrap();
+        }
+        assert_eq!(buffer_manager.available().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_read_block_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_owned();
+
+        let file_manager = Arc::new(file_manager::FileManager::new(&path, 400));
+        let log_manager =
+            Arc::new(log_manager::LogManager::new(file_manager.clone(), "testlog").unwrap());
+        let buffer_manager = BufferManager::new(file_manager, log_manager, 3, Some(100));
+
+        // buffer pool にある (まだ disk に書き込まれていない) 内容を読める
+        let blk = blockid::BlockId::new("testfile", 0);
+        let buf = buffer_manager.pin(&blk).unwrap();
+        {
+            let mut buf = buf.lock().unwrap();
+            buf.contents_mut().set_int(0, 123);
+            buf.set_modified(1, None);
+        }
+        let copy = buffer_manager.read_block_copy(&blk).unwrap();
+        assert_eq!(copy.get_int(0), 123);
+        // 写しを読んでも pin 数は変わらない
+        assert_eq!(buffer_manager.available().unwrap(), 2);
+        buffer_manager.unpin(buf).unwrap();
+
+        // buffer pool にない block は読み込んだ後に unpin される
+        let copy = buffer_manager
+            .read_block_copy(&blockid::BlockId::new("testfile", 1))
+            .unwrap();
+        assert_eq!(copy.get_int(0), 0);
+        assert_eq!(buffer_manager.available().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_pools() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().to_owned();
+
+        let file_manager = Arc::new(file_manager::FileManager::new(&path, 400));
+        let log_manager =
+            Arc::new(log_manager::LogManager::new(file_manager.clone(), "testlog").unwrap());
+        let sizes = BufferPoolSizes {
+            data: 2,
+            catalog: 1,
+            index: 0,
+        };
+        let buffer_manager = BufferManager::with_pools(file_manager, log_manager, sizes, Some(100));
+        buffer_manager
+            .register_file("catalog", BufferPoolKind::Catalog)
+            .unwrap();
+        // index 用の buffer はないので、data 用の buffer を使う
+        buffer_manager
+            .register_file("index", BufferPoolKind::Index)
+            .unwrap();
+        assert_eq!(buffer_manager.available().unwrap(), 3);
+        assert_eq!(
+            buffer_manager.pool_of("index").unwrap(),
+            BufferPoolKind::Data
+        );
+        assert_eq!(
+            buffer_manager
+                .available_in(BufferPoolKind::Catalog)
+                .unwrap(),
+            1
+        );
+
+        // catalog の block を読み込んで unpin しておく
+        let catalog_block = blockid::BlockId::new("catalog", 0);
+        let buf = buffer_manager.pin(&catalog_block).unwrap();
+        buffer_manager.unpin(buf).unwrap();
+
+        // data の block を大量に読んでも、catalog 用の buffer は使われない
+        for i in 0..10 {
+            let buf = buffer_manager
+                .pin(&blockid::BlockId::new("data", i))
+                .unwrap();
+            buffer_manager.unpin(buf).unwrap();
+        }
+        let buf = buffer_manager.pin(&catalog_block).unwrap();
+        assert_eq!(buf.lock().unwrap().block(), Some(&catalog_block));
+
+        // data 用の 2 つの buffer を pin すると、data と index の block はそれ以上 pin できない
+        let buf0 = buffer_manager.pin(&blockid::BlockId::new("data", 0));
+        let buf1 = buffer_manager.pin(&blockid::BlockId::new("index", 0));
+        assert!(buf0.is_ok() && buf1.is_ok());
+        assert!(buffer_manager
+            .pin(&blockid::BlockId::new("data", 1))
+            .is_err());
+        // catalog 用の buffer は catalog の block にしか使われない
+        buffer_manager.unpin(buf).unwrap();
+        assert!(buffer_manager
+            .pin(&blockid::BlockId::new("data", 1))
+            .is_err());
+        assert!(buffer_manager
+            .pin(&blockid::BlockId::new("catalog", 1))
+            .is_ok());
+    }
 }
@@ -0,0 +1,64 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::file::blockid::BlockId;
+
+/**
+ * WAL (write ahead logging) のルールが守られているかを確認するための debug 用の仕組み
+ *
+ * buffer の内容を block に書き込む前に、その buffer を最後に変更した log record (buffer の LSN) までが
+ * log file に書き込まれていなければならない
+ * group commit や background での flush などで buffer を書き込む経路が増えても、このルールが破られていないことを確認する
+ *
+ * debug build でのみ確認し、違反を見つけた場合は回数を数えて標準エラー出力に書き出す
+ * test の場合は strict mode として、違反を見つけた時点で panic する
+ */
+static VIOLATIONS: AtomicU64 = AtomicU64::new(0);
+
+const STRICT: bool = cfg!(test);
+
+/// block を書き込む直前に、buffer の LSN までの log が書き込み済みであることを確認する
+pub(crate) fn check_flush(block: &BlockId, lsn: u64, last_saved_lsn: u64) {
+    if lsn <= last_saved_lsn {
+        return;
+    }
+    VIOLATIONS.fetch_add(1, Ordering::SeqCst);
+    let message = format!(
+        "WAL violation: block {} with lsn {} is written before the log is flushed (last saved lsn: {})",
+        block, lsn, last_saved_lsn
+    );
+    if STRICT {
+        panic!("{}", message);
+    }
+    eprintln!("{}", message);
+}
+
+/// 起動してから見つかった WAL のルールの違反の回数を返す
+/// strict mode の test で、panic する前に違反が数えられていることを確認するために使う
+#[cfg(test)]
+pub(crate) fn violation_count() -> u64 {
+    VIOLATIONS.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod wal_check_test {
+    use super::*;
+
+    #[test]
+    fn test_check_flush() {
+        let block = BlockId::new("testfile", 0);
+        // log が buffer の LSN 以上まで書き込まれていれば問題ない
+        check_flush(&block, 0, 0);
+        check_flush(&block, 3, 3);
+        check_flush(&block, 3, 5);
+    }
+
+    #[test]
+    fn test_check_flush_violation() {
+        // strict mode では panic するが、違反の回数は panic する前に数える
+        let before = violation_count();
+        let result = std::panic::catch_unwind(|| check_flush(&BlockId::new("testfile", 0), 5, 3));
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.starts_with("WAL violation"));
+        assert!(violation_count() > before);
+    }
+}
@@ -0,0 +1,385 @@
+use std::{cell::RefCell, collections::HashMap, fmt, path::Path, rc::Rc};
+
+use anyhow::Result as AnyhowResult;
+
+use crate::{
+    file::blockid::BlockId,
+    inspect::open_db,
+    metadata::metadata_manager::MetadataManager,
+    query::constant::Constant,
+    record::{
+        layout::Layout,
+        partition::PartitionSpec,
+        record_page::{RecordPage, RecordPageFlag},
+        rid::Rid,
+        table_scan_factory::{TableScanFactory, TableScanFactoryImpl},
+    },
+    tx::{log::log_record_iterator::LogRecordIterator, transaction::Transaction},
+};
+
+/// check_database で見つかった不整合
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum CheckProblem {
+    /// catalog から table の layout を読めないか、field の位置が slot に収まらない
+    InvalidLayout { table: String, reason: String },
+    /// slot の flag が empty でも used でもない
+    InvalidSlotFlag {
+        block: BlockId,
+        slot: usize,
+        flag: i32,
+    },
+    /// index を読めないか、index を張った field が table に無い
+    InvalidIndex { index: String, reason: String },
+    /// index の entry が、存在しない record か、値の異なる record を指している
+    DanglingIndexEntry {
+        index: String,
+        val: Constant,
+        rid: Rid,
+    },
+    /// table の record に対応する entry が index に無い
+    MissingIndexEntry {
+        index: String,
+        val: Constant,
+        rid: Rid,
+    },
+    /// log record として読めない位置がある
+    UnreadableLog(String),
+}
+
+impl fmt::Display for CheckProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckProblem::InvalidLayout { table, reason } => {
+                write!(f, "invalid layout of table {}: {}", table, reason)
+            }
+            CheckProblem::InvalidSlotFlag { block, slot, flag } => {
+                write!(f, "invalid flag {} at slot {} of {}", flag, slot, block)
+            }
+            CheckProblem::InvalidIndex { index, reason } => {
+                write!(f, "invalid index {}: {}", index, reason)
+            }
+            CheckProblem::DanglingIndexEntry { index, val, rid } => write!(
+                f,
+                "index {} has entry {} for block {}, slot {:?} which does not match any record",
+                index,
+                val,
+                rid.block_number(),
+                rid.slot()
+            ),
+            CheckProblem::MissingIndexEntry { index, val, rid } => write!(
+                f,
+                "index {} has no entry {} for the record at block {}, slot {:?}",
+                index,
+                val,
+                rid.block_number(),
+                rid.slot()
+            ),
+            CheckProblem::UnreadableLog(reason) => write!(f, "unreadable log: {}", reason),
+        }
+    }
+}
+
+/// check_database の結果。確認したものの数と、見つかった不整合を持つ
+#[derive(Default, Debug)]
+pub struct CheckReport {
+    pub tables: usize,
+    pub blocks: usize,
+    pub records: usize,
+    pub indexes: usize,
+    pub index_entries: usize,
+    pub log_records: usize,
+    pub problems: Vec<CheckProblem>,
+}
+
+impl CheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "tables: {}, blocks: {}, records: {}",
+            self.tables, self.blocks, self.records
+        )?;
+        writeln!(
+            f,
+            "indexes: {}, index entries: {}",
+            self.indexes, self.index_entries
+        )?;
+        writeln!(f, "log records: {}", self.log_records)?;
+        writeln!(f, "problems: {}", self.problems.len())?;
+        for problem in &self.problems {
+            writeln!(f, "  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/**
+ * database の catalog, table のファイル, index, log を読み、不整合が無いかを確認する
+ *
+ * 次のことを確認し、見つかった不整合を CheckReport にまとめて返す
+ * - catalog の各 table の layout を fldcat から読め、field の位置が slot の中で重ならない
+ * - table の全 block の全 slot の flag が empty か used のどちらか
+ * - index の全 entry が同じ値を持つ record を指し、table の全 record の entry が index にある
+ * - log を最後まで log record として読める
+ *
+ * inspect と同じく recover はせずに disk の内容をそのまま読むので、他の process が同じ database を使っている間は使わないこと
+ * 不整合ではなく、ファイルを読めないなどで確認を続けられない場合は error を返す
+ */
+pub fn check_database(dir: &Path, block_size: usize) -> AnyhowResult<CheckReport> {
+    let db = open_db(dir, block_size)?;
+    let mut report = CheckReport::default();
+    let tx = db.new_tx()?;
+    let mdm = db.metadata_manager();
+    for table_name in mdm.get_table_names(&tx)? {
+        // external table のデータは database の外のファイルにある
+        if mdm.get_external_table_path(&table_name, &tx)?.is_some() {
+            continue;
+        }
+        check_table(mdm.as_ref(), &table_name, &tx, &mut report)?;
+    }
+    tx.borrow_mut().commit()?;
+
+    let mut iter = LogRecordIterator::new(db.log_manager())?;
+    while let Some(record) = iter.try_next() {
+        match record {
+            Ok(_) => report.log_records += 1,
+            Err(err) => {
+                report
+                    .problems
+                    .push(CheckProblem::UnreadableLog(err.to_string()));
+                break;
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn check_table(
+    mdm: &dyn MetadataManager,
+    table_name: &str,
+    tx: &Rc<RefCell<Transaction>>,
+    report: &mut CheckReport,
+) -> AnyhowResult<()> {
+    report.tables += 1;
+    let layout = match mdm.get_layout(table_name, tx) {
+        Ok(layout) => layout,
+        Err(err) => {
+            report.problems.push(CheckProblem::InvalidLayout {
+                table: table_name.to_string(),
+                reason: err.to_string(),
+            });
+            return Ok(());
+        }
+    };
+    if let Err(err) = layout.validate() {
+        report.problems.push(CheckProblem::InvalidLayout {
+            table: table_name.to_string(),
+            reason: err.to_string(),
+        });
+        return Ok(());
+    }
+
+    // partition に分けた table は、partition ごとのファイルを同じ layout で読む
+    let files = match layout.partition() {
+        Some(partition) => (0..partition.partition_count())
+            .map(|p| PartitionSpec::partition_table_name(table_name, p))
+            .collect(),
+        None => vec![table_name.to_string()],
+    };
+    let file_layout = layout.without_partition();
+    let problems_before = report.problems.len();
+    for file in &files {
+        check_blocks(&format!("{}.tbl", file), &file_layout, tx, report)?;
+    }
+    // flag を読めない slot があると table scan で record を読めないので、index は確認しない
+    if report.problems.len() > problems_before {
+        return Ok(());
+    }
+
+    for index_info in mdm.get_index_info(table_name, tx)? {
+        report.indexes += 1;
+        let field = index_info.field_name();
+        if !layout.schema().has_field(field) {
+            report.problems.push(CheckProblem::InvalidIndex {
+                index: index_info.index_name().to_string(),
+                reason: format!("field {} not found in table {}", field, table_name),
+            });
+            continue;
+        }
+        let entries = match index_info.entries(tx, layout.schema()) {
+            Ok(entries) => entries,
+            Err(err) => {
+                report.problems.push(CheckProblem::InvalidIndex {
+                    index: index_info.index_name().to_string(),
+                    reason: err.to_string(),
+                });
+                continue;
+            }
+        };
+        // Rid -> index を張った field の値。index の entry と対応がついたものから取り除く
+        let mut records = HashMap::new();
+        let mut scan = TableScanFactoryImpl::new().create(tx, table_name, &layout)?;
+        scan.before_first()?;
+        while scan.move_next()? {
+            records.insert(scan.get_rid()?, scan.get_val(field)?);
+        }
+        drop(scan);
+        for (val, rid) in entries {
+            report.index_entries += 1;
+            if records.get(&rid) == Some(&val) {
+                records.remove(&rid);
+            } else {
+                report.problems.push(CheckProblem::DanglingIndexEntry {
+                    index: index_info.index_name().to_string(),
+                    val,
+                    rid,
+                });
+            }
+        }
+        let mut missing = records.into_iter().collect::<Vec<_>>();
+        missing.sort_by_key(|(rid, _)| (rid.block_number(), rid.slot()));
+        for (rid, val) in missing {
+            report.problems.push(CheckProblem::MissingIndexEntry {
+                index: index_info.index_name().to_string(),
+                val,
+                rid,
+            });
+        }
+    }
+    Ok(())
+}
+
+// ファイルの全 block の全 slot の flag を確認し、使用中の slot の数を数える
+fn check_blocks(
+    filename: &str,
+    layout: &Layout,
+    tx: &Rc<RefCell<Transaction>>,
+    report: &mut CheckReport,
+) -> AnyhowResult<()> {
+    let block_count = tx.borrow().size(filename)?;
+    for block_number in 0..block_count {
+        report.blocks += 1;
+        let block = BlockId::new(filename, block_number);
+        let record_page = RecordPage::new(tx.clone(), &block, layout);
+        for slot in 0..record_page.slot_count()? {
+            let flag = record_page.raw_flag(slot)?;
+            match RecordPageFlag::from_i32(flag) {
+                Some(RecordPageFlag::Used) => report.records += 1,
+                Some(RecordPageFlag::Empty) => {}
+                None => report.problems.push(CheckProblem::InvalidSlotFlag {
+                    block: block.clone(),
+                    slot,
+                    flag,
+                }),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod check_test {
+    use super::*;
+    use crate::server::simpledb::SimpleDB;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_check_database() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+            let tx = db.new_tx().unwrap();
+            for cmd in [
+                "create table t (a int, b varchar(5))",
+                "create index a_idx on t (a)",
+                "create index b_idx on t (b) using hash",
+                "insert into t (a, b) values (1, 'one')",
+                "insert into t (a, b) values (2, 'two')",
+            ] {
+                db.executor().exec_update_command(cmd, &tx).unwrap();
+            }
+            tx.borrow_mut().commit().unwrap();
+            db.buffer_manager().flush_all().unwrap();
+        }
+
+        let report = check_database(dir.path(), 400).unwrap();
+        assert!(report.is_ok(), "{}", report);
+        assert_eq!(report.indexes, 2);
+        assert_eq!(report.index_entries, 4);
+        assert!(report.records >= 2);
+        assert!(report.log_records > 0);
+
+        // table の slot の flag を壊す
+        {
+            let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+            let tx = db.new_tx().unwrap();
+            let layout = db.metadata_manager().get_layout("t", &tx).unwrap();
+            let block = BlockId::new("t.tbl", 0);
+            tx.borrow().pin(&block).unwrap();
+            tx.borrow()
+                .set_int(&block, layout.flag_offset(1), 7, true)
+                .unwrap();
+            tx.borrow().unpin(&block).unwrap();
+            tx.borrow_mut().commit().unwrap();
+            db.buffer_manager().flush_all().unwrap();
+        }
+
+        let report = check_database(dir.path(), 400).unwrap();
+        assert!(!report.is_ok());
+        assert!(report.problems.contains(&CheckProblem::InvalidSlotFlag {
+            block: BlockId::new("t.tbl", 0),
+            slot: 1,
+            flag: 7,
+        }));
+        assert!(report.to_string().contains("invalid flag 7 at slot 1"));
+    }
+
+    #[test]
+    fn test_check_index_entries() {
+        let dir = tempdir().unwrap();
+        let deleted = {
+            let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+            let tx = db.new_tx().unwrap();
+            for cmd in [
+                "create table t (a int)",
+                "insert into t (a) values (1)",
+                "create index a_idx on t (a) using hash",
+            ] {
+                db.executor().exec_update_command(cmd, &tx).unwrap();
+            }
+            // index を更新せずに、table の record を削除して別の record を追加する
+            let layout = db.metadata_manager().get_layout("t", &tx).unwrap();
+            let mut scan = TableScanFactoryImpl::new()
+                .create(&tx, "t", &layout)
+                .unwrap();
+            scan.before_first().unwrap();
+            assert!(scan.move_next().unwrap());
+            let deleted = scan.get_rid().unwrap();
+            scan.delete().unwrap();
+            scan.insert().unwrap();
+            scan.set_val("a", &Constant::Int(2)).unwrap();
+            drop(scan);
+            tx.borrow_mut().commit().unwrap();
+            db.buffer_manager().flush_all().unwrap();
+            deleted
+        };
+
+        let report = check_database(dir.path(), 400).unwrap();
+        assert_eq!(report.problems.len(), 2, "{}", report);
+        assert!(report.problems.contains(&CheckProblem::DanglingIndexEntry {
+            index: "a_idx".to_string(),
+            val: Constant::Int(1),
+            rid: deleted,
+        }));
+        assert!(report.problems.iter().any(|problem| matches!(
+            problem,
+            CheckProblem::MissingIndexEntry { index, val, .. }
+                if index == "a_idx" && *val == Constant::Int(2)
+        )));
+    }
+}
@@ -0,0 +1,405 @@
+use std::{
+    cell::RefCell,
+    io::{BufRead, Write},
+    path::Path,
+    rc::Rc,
+};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use crate::{
+    error::{SimpleDbError, SimpleDbResult},
+    exec::{
+        executor::QueryResult,
+        session::{CursorResult, Session},
+    },
+    query::{constant::Constant, scan::ReadScan},
+    server::simpledb::SimpleDB,
+    tx::transaction::Transaction,
+};
+
+const PROMPT: &str = "simpledb> ";
+const CONTINUATION_PROMPT: &str = "      ... ";
+
+/// SQL 以外に shell が解釈するコマンド
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MetaCommand {
+    Commit,
+    Rollback,
+    Exit,
+}
+
+impl MetaCommand {
+    fn parse(input: &str) -> Option<MetaCommand> {
+        match input
+            .trim()
+            .trim_end_matches(';')
+            .trim()
+            .to_lowercase()
+            .as_str()
+        {
+            "commit" => Some(MetaCommand::Commit),
+            "rollback" => Some(MetaCommand::Rollback),
+            "exit" | "quit" => Some(MetaCommand::Exit),
+            _ => None,
+        }
+    }
+}
+
+/// dir の database を開き、input から読んだ文を Repl で実行する
+pub fn run_repl(
+    dir: &Path,
+    input: impl BufRead,
+    output: &mut impl Write,
+    prompt: bool,
+) -> AnyhowResult<()> {
+    let dir_name = dir
+        .to_str()
+        .ok_or_else(|| anyhow!("invalid directory name: {}", dir.display()))?;
+    let db = SimpleDB::new(dir_name)?;
+    Repl::new(&db).run(input, output, prompt)
+}
+
+/**
+ * SQL を 1 文ずつ読んで実行し、結果を表の形で出力する対話 shell
+ *
+ * 文は ; で区切り、複数行にまたがってもよい。commit / rollback / exit は ; がなくても受け付ける
+ * 文は明示的に commit されるまで同じ transaction の中で実行される
+ * transaction は最初の文を実行する時に開始し、commit / rollback の後は次の文で新しく開始する
+ * exit した時や入力が終わった時に commit されていない変更は rollback する
+ */
+pub struct Repl<'a> {
+    db: &'a SimpleDB,
+    session: Session<'a>,
+    tx: Option<Rc<RefCell<Transaction>>>,
+}
+
+impl<'a> Repl<'a> {
+    pub fn new(db: &'a SimpleDB) -> Self {
+        Self {
+            db,
+            session: db.new_session(),
+            tx: None,
+        }
+    }
+
+    /// input から文を読み、入力が終わるか exit されるまで実行し続ける
+    /// prompt が true の場合は、入力を待つたびに prompt を出力する
+    pub fn run(
+        &mut self,
+        input: impl BufRead,
+        output: &mut impl Write,
+        prompt: bool,
+    ) -> AnyhowResult<()> {
+        let mut buffer = String::new();
+        Self::write_prompt(output, prompt, &buffer)?;
+        for line in input.lines() {
+            let line = line?;
+            if buffer.trim().is_empty() {
+                if let Some(command) = MetaCommand::parse(&line) {
+                    if !self.exec_meta_command(command, output)? {
+                        return self.finish(output);
+                    }
+                    Self::write_prompt(output, prompt, &buffer)?;
+                    continue;
+                }
+            }
+            buffer.push_str(&line);
+            buffer.push('\n');
+            while let Some((statement, rest)) = split_statement(&buffer) {
+                let (statement, rest) = (statement.to_string(), rest.to_string());
+                buffer = rest;
+                if !self.exec_statement(&statement, output)? {
+                    return self.finish(output);
+                }
+            }
+            Self::write_prompt(output, prompt, &buffer)?;
+        }
+        // ; で終わっていない最後の文も実行する
+        if !buffer.trim().is_empty() {
+            let statement = buffer.clone();
+            if !self.exec_statement(&statement, output)? {
+                return self.finish(output);
+            }
+        }
+        self.finish(output)
+    }
+
+    fn write_prompt(output: &mut impl Write, prompt: bool, buffer: &str) -> AnyhowResult<()> {
+        if prompt {
+            if buffer.trim().is_empty() {
+                write!(output, "{}", PROMPT)?;
+            } else {
+                write!(output, "{}", CONTINUATION_PROMPT)?;
+            }
+            output.flush()?;
+        }
+        Ok(())
+    }
+
+    /// 1 文を実行して結果を出力する。exit された場合は false を返す
+    fn exec_statement(&mut self, statement: &str, output: &mut impl Write) -> AnyhowResult<bool> {
+        if statement.trim().is_empty() {
+            return Ok(true);
+        }
+        if let Some(command) = MetaCommand::parse(statement) {
+            return self.exec_meta_command(command, output);
+        }
+        match self.exec_sql(statement.trim()) {
+            Ok(result) => write!(output, "{}", result)?,
+            Err(err) => {
+                writeln!(output, "error: {}", err)?;
+                // deadlock などで失敗した transaction は続けられないので rollback する
+                if err.is_retryable() {
+                    self.end_tx(false)?;
+                    writeln!(output, "transaction rolled back")?;
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// meta command を実行する。exit された場合は false を返す
+    fn exec_meta_command(
+        &mut self,
+        command: MetaCommand,
+        output: &mut impl Write,
+    ) -> AnyhowResult<bool> {
+        match command {
+            MetaCommand::Commit => {
+                self.end_tx(true)?;
+                writeln!(output, "committed")?;
+            }
+            MetaCommand::Rollback => {
+                self.end_tx(false)?;
+                writeln!(output, "rolled back")?;
+            }
+            MetaCommand::Exit => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn finish(&mut self, output: &mut impl Write) -> AnyhowResult<()> {
+        if self.tx.is_some() {
+            self.end_tx(false)?;
+            writeln!(output, "rolled back open transaction")?;
+        }
+        output.flush()?;
+        Ok(())
+    }
+
+    fn exec_sql(&mut self, sql: &str) -> SimpleDbResult<String> {
+        let tx = self.tx()?;
+        let keyword = sql
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        if matches!(keyword.as_str(), "declare" | "fetch" | "close") {
+            return Ok(match self.session.exec_cursor_command(sql, &tx)? {
+                CursorResult::Declared => "cursor declared\n".to_string(),
+                CursorResult::Fetched { fields, rows } => format_table(&fields, &rows),
+                CursorResult::Closed => "cursor closed\n".to_string(),
+            });
+        }
+        Ok(match self.session.execute(sql, &tx)? {
+            QueryResult::Rows { fields, scan } => format_table(&fields, &read_rows(scan, &fields)?),
+            QueryResult::Count(count) => format!("{} records affected\n", count),
+            QueryResult::Ddl => "0 records affected\n".to_string(),
+            QueryResult::Explain(node) => node.to_string(),
+        })
+    }
+
+    // 実行中の transaction を返す。なければ新しく開始する
+    fn tx(&mut self) -> SimpleDbResult<Rc<RefCell<Transaction>>> {
+        if let Some(tx) = &self.tx {
+            return Ok(tx.clone());
+        }
+        let tx = self.db.new_tx()?;
+        self.tx = Some(tx.clone());
+        Ok(tx)
+    }
+
+    fn end_tx(&mut self, commit: bool) -> SimpleDbResult<()> {
+        // cursor の scan は transaction に紐づいているので、先に閉じる
+        self.session.close_all();
+        let Some(tx) = self.tx.take() else {
+            return Ok(());
+        };
+        if commit {
+            tx.borrow_mut().commit()?;
+        } else {
+            tx.borrow_mut().rollback()?;
+        }
+        Ok(())
+    }
+}
+
+/// buffer の先頭から最初の ; までの文と、残りの文字列を返す。; がなければ None を返す
+/// 文字列定数の中の ; は区切りとみなさない
+fn split_statement(buffer: &str) -> Option<(&str, &str)> {
+    let mut in_string = false;
+    for (i, c) in buffer.char_indices() {
+        match c {
+            '\'' => in_string = !in_string,
+            ';' if !in_string => return Some((&buffer[..i], &buffer[i + 1..])),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn read_rows(mut scan: Box<dyn ReadScan>, fields: &[String]) -> SimpleDbResult<Vec<Vec<Constant>>> {
+    let mut rows = vec![];
+    while scan.move_next().map_err(SimpleDbError::from)? {
+        rows.push(
+            fields
+                .iter()
+                .map(|field| scan.get_val(field))
+                .collect::<AnyhowResult<Vec<_>>>()?,
+        );
+    }
+    Ok(rows)
+}
+
+/// 結果の record を表の形に整形する。数値は右寄せ、それ以外は左寄せで表示する
+fn format_table(fields: &[String], rows: &[Vec<Constant>]) -> String {
+    let cells = rows
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|val| match val {
+                    Constant::String(val) => val.clone(),
+                    val => val.to_string(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let widths = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain([field.chars().count()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+
+    let mut table = String::new();
+    let header = fields
+        .iter()
+        .zip(&widths)
+        .map(|(field, width)| format!(" {:<width$} ", field, width = width))
+        .collect::<Vec<_>>();
+    table.push_str(header.join("|").trim_end());
+    table.push('\n');
+    let separator = widths
+        .iter()
+        .map(|width| "-".repeat(width + 2))
+        .collect::<Vec<_>>();
+    table.push_str(&separator.join("+"));
+    table.push('\n');
+    for (row, cell) in rows.iter().zip(&cells) {
+        let line = row
+            .iter()
+            .zip(cell)
+            .zip(&widths)
+            .map(|((val, cell), width)| match val {
+                Constant::Int(_) => format!(" {:>width$} ", cell, width = width),
+                _ => format!(" {:<width$} ", cell, width = width),
+            })
+            .collect::<Vec<_>>();
+        table.push_str(line.join("|").trim_end());
+        table.push('\n');
+    }
+    let suffix = if rows.len() == 1 { "" } else { "s" };
+    table.push_str(&format!("({} row{})\n", rows.len(), suffix));
+    table
+}
+
+#[cfg(test)]
+mod cli_test {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn run(db: &SimpleDB, input: &str) -> String {
+        let mut output = vec![];
+        Repl::new(db)
+            .run(input.as_bytes(), &mut output, false)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_run_statements() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        let output = run(
+            &db,
+            "create table t (a int, b varchar(10));
+insert into t (a, b) values (1, 'one');
+insert into t (a, b)
+    values (100, 'semi;colon');
+commit
+select a, b from t;
+select a from t where b = 'none';
+selct a from t;
+exit
+insert into t (a, b) values (2, 'two');
+",
+        );
+        assert_eq!(
+            output,
+            "0 records affected
+1 records affected
+1 records affected
+committed
+ a   | b
+-----+------------
+   1 | one
+ 100 | semi;colon
+(2 rows)
+ a
+---
+(0 rows)
+error: parse error: Unexpected token
+rolled back open transaction
+"
+        );
+    }
+
+    #[test]
+    fn test_rollback() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        let output = run(
+            &db,
+            "create table t (a int);
+commit;
+insert into t (a) values (1);
+rollback
+insert into t (a) values (2); insert into t (a) values (3)",
+        );
+        assert!(output.contains("rolled back\n"));
+        // 入力の終わりでは commit されていない変更を rollback する
+        assert!(output.ends_with("rolled back open transaction\n"));
+
+        let output = run(&db, "select a from t;\nshow tables;");
+        assert_eq!(
+            output,
+            " a
+---
+(0 rows)
+ table_name
+------------
+ t
+(1 row)
+rolled back open transaction
+"
+        );
+    }
+}
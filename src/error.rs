@@ -0,0 +1,297 @@
+use std::{io, string::FromUtf8Error};
+
+use thiserror::Error;
+
+#[cfg(feature = "sql")]
+use crate::{
+    exec::{executor::ExecutorError, session::SessionError},
+    metadata::{
+        access_list::AccessListError, identifier::IdentifierError, table_manager::TableManagerError,
+    },
+    parse::{lexer::LexerError, parser::ParserError},
+    plan::plan::PlanError,
+    query::{
+        aggregation_fn::AggregationFnError, memory_budget::MemoryBudgetError, scan::ReadScanError,
+    },
+    record::schema::{FieldTypeError, SchemaError},
+    server::{control_file::ControlFileError, kv_table::KvTableError},
+};
+use crate::{
+    file::{compression::CompressionError, encryption::EncryptionError},
+    log::{log_iterator::InvalidLogRecordError, log_manager::LogError},
+    tx::{
+        concurrency::lock_table::LockTableError,
+        idle_reaper::TransactionAbortedError,
+        log::record::log_record::LogRecordError,
+        recovery::recovery_manager::RecoveryError,
+        transaction::{TransactionCommitError, TransactionRecoverError, TransactionRollbackError},
+    },
+};
+
+/**
+ * SimpleDB の公開 API (SimpleDB, Executor, Transaction) が返す error
+ *
+ * 内部の各 module はそれぞれ独自の error を返すが、利用者がそれらを全て match するのは大変なので、
+ * 安定した分類にまとめて返す。元の error は source として保持しているので、詳細は source を辿れば参照できる
+ */
+#[derive(Error, Debug)]
+pub enum SimpleDbError {
+    /// SQL の字句解析・構文解析に失敗した
+    #[error("parse error: {0}")]
+    Parse(#[source] anyhow::Error),
+    /// table や field が存在しないなど、文の意味が正しくない
+    #[error("plan error: {0}")]
+    Plan(#[source] anyhow::Error),
+    /// read-only な table への書き込みなど、制約に違反した
+    #[error("constraint error: {0}")]
+    Constraint(#[source] anyhow::Error),
+    /// lock が取得できなかった
+    #[error("lock error: {0}")]
+    Lock(#[source] anyhow::Error),
+    /// disk の読み書きに失敗した
+    #[error("io error: {0}")]
+    Io(#[source] anyhow::Error),
+    /// disk 上のデータが壊れている (暗号化の鍵や block size が作成時と違う場合を含む)
+    #[error("corruption error: {0}")]
+    Corruption(#[source] anyhow::Error),
+    /// 上記のいずれにも当てはまらない error
+    #[error("internal error: {0}")]
+    Internal(#[source] anyhow::Error),
+}
+
+pub type SimpleDbResult<T> = Result<T, SimpleDbError>;
+
+/// 公開 API の実装の中で `?` を使えるように、各 module の error から変換できるようにする
+macro_rules! impl_from_module_error {
+    ($($error:ty),* $(,)?) => {
+        $(
+            impl From<$error> for SimpleDbError {
+                fn from(err: $error) -> SimpleDbError {
+                    anyhow::Error::from(err).into()
+                }
+            }
+        )*
+    };
+}
+
+impl_from_module_error!(
+    TransactionCommitError,
+    TransactionRollbackError,
+    TransactionRecoverError,
+    LogRecordError,
+    LogError,
+);
+#[cfg(feature = "sql")]
+impl_from_module_error!(ReadScanError, AccessListError, ControlFileError);
+
+/// SimpleDbError の分類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCategory {
+    Parse,
+    Plan,
+    Constraint,
+    Lock,
+    Io,
+    Corruption,
+    Internal,
+}
+
+impl SimpleDbError {
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            SimpleDbError::Parse(_) => ErrorCategory::Parse,
+            SimpleDbError::Plan(_) => ErrorCategory::Plan,
+            SimpleDbError::Constraint(_) => ErrorCategory::Constraint,
+            SimpleDbError::Lock(_) => ErrorCategory::Lock,
+            SimpleDbError::Io(_) => ErrorCategory::Io,
+            SimpleDbError::Corruption(_) => ErrorCategory::Corruption,
+            SimpleDbError::Internal(_) => ErrorCategory::Internal,
+        }
+    }
+
+    /// 新しい transaction で同じ処理をやり直せば成功する可能性がある error かどうか
+    /// この実装では deadlock も lock 取得の timeout として検出されるので、lock の timeout を再試行可能とする
+    pub fn is_retryable(&self) -> bool {
+        self.inner().chain().any(|err| {
+            matches!(
+                err.downcast_ref::<LockTableError>(),
+                Some(LockTableError::Timeout(_))
+            )
+        })
+    }
+
+    fn inner(&self) -> &anyhow::Error {
+        match self {
+            SimpleDbError::Parse(err)
+            | SimpleDbError::Plan(err)
+            | SimpleDbError::Constraint(err)
+            | SimpleDbError::Lock(err)
+            | SimpleDbError::Io(err)
+            | SimpleDbError::Corruption(err)
+            | SimpleDbError::Internal(err) => err,
+        }
+    }
+
+    fn new(category: ErrorCategory, err: anyhow::Error) -> SimpleDbError {
+        match category {
+            ErrorCategory::Parse => SimpleDbError::Parse(err),
+            ErrorCategory::Plan => SimpleDbError::Plan(err),
+            ErrorCategory::Constraint => SimpleDbError::Constraint(err),
+            ErrorCategory::Lock => SimpleDbError::Lock(err),
+            ErrorCategory::Io => SimpleDbError::Io(err),
+            ErrorCategory::Corruption => SimpleDbError::Corruption(err),
+            ErrorCategory::Internal => SimpleDbError::Internal(err),
+        }
+    }
+}
+
+impl From<anyhow::Error> for SimpleDbError {
+    /// error の chain を外側から辿り、最初に分類できた error の分類を使う
+    fn from(err: anyhow::Error) -> SimpleDbError {
+        let category = err
+            .chain()
+            .find_map(categorize)
+            .unwrap_or(ErrorCategory::Internal);
+        SimpleDbError::new(category, err)
+    }
+}
+
+/// 個々の error を分類する。分類できない場合は None を返し、その source を調べる
+fn categorize(err: &(dyn std::error::Error + 'static)) -> Option<ErrorCategory> {
+    if let Some(err) = err.downcast_ref::<SimpleDbError>() {
+        return Some(err.category());
+    }
+    #[cfg(feature = "sql")]
+    if let Some(category) = categorize_sql(err) {
+        return Some(category);
+    }
+    // idle transaction reaper に abort された transaction は、lock の timeout と同様に rollback してやり直せばよい
+    if err.is::<LockTableError>() || err.is::<TransactionAbortedError>() {
+        return Some(ErrorCategory::Lock);
+    }
+    if err.is::<io::Error>() {
+        return Some(ErrorCategory::Io);
+    }
+    if let Some(err) = err.downcast_ref::<EncryptionError>() {
+        return Some(match err {
+            EncryptionError::Unavailable => ErrorCategory::Internal,
+            EncryptionError::Encrypt | EncryptionError::Decrypt => ErrorCategory::Corruption,
+        });
+    }
+    if err.is::<CompressionError>()
+        || err.is::<FromUtf8Error>()
+        || err.is::<InvalidLogRecordError>()
+        || matches!(
+            err.downcast_ref::<RecoveryError>(),
+            Some(RecoveryError::MissingCheckpoint(_))
+        )
+    {
+        return Some(ErrorCategory::Corruption);
+    }
+    None
+}
+
+/// SQL の層 (parse / plan / exec / record) の error を分類する
+#[cfg(feature = "sql")]
+fn categorize_sql(err: &(dyn std::error::Error + 'static)) -> Option<ErrorCategory> {
+    if err.is::<ParserError>() || err.is::<LexerError>() {
+        return Some(ErrorCategory::Parse);
+    }
+    if let Some(err) = err.downcast_ref::<ExecutorError>() {
+        return Some(match err {
+            ExecutorError::InvalidCommand(_) => ErrorCategory::Plan,
+            ExecutorError::ReadOnlyTable(_) => ErrorCategory::Constraint,
+            ExecutorError::TransactionFactoryNotSet => ErrorCategory::Internal,
+            ExecutorError::RowPolicyViolation(_) => ErrorCategory::Constraint,
+            ExecutorError::UniqueViolation(..) => ErrorCategory::Constraint,
+            ExecutorError::Lock(_) => ErrorCategory::Internal,
+        });
+    }
+    if let Some(err) = err.downcast_ref::<PlanError>() {
+        return Some(match err {
+            PlanError::InvalidCall(_) => ErrorCategory::Plan,
+            PlanError::Internal(_) => ErrorCategory::Internal,
+        });
+    }
+    if let Some(TableManagerError::InvalidCall(_) | TableManagerError::AlreadyExists(_)) =
+        err.downcast_ref::<TableManagerError>()
+    {
+        return Some(ErrorCategory::Plan);
+    }
+    if let Some(err) = err.downcast_ref::<KvTableError>() {
+        return Some(match err {
+            KvTableError::InvalidCall(_) => ErrorCategory::Plan,
+            KvTableError::ReadOnlyTable(_) => ErrorCategory::Constraint,
+        });
+    }
+    if err.is::<MemoryBudgetError>() {
+        return Some(ErrorCategory::Constraint);
+    }
+    if let Some(err) = err.downcast_ref::<AggregationFnError>() {
+        return Some(match err {
+            AggregationFnError::TypeMismatch(_) => ErrorCategory::Plan,
+            AggregationFnError::Overflow(_) => ErrorCategory::Constraint,
+            AggregationFnError::InvalidCall(_) => ErrorCategory::Internal,
+        });
+    }
+    if err.is::<SchemaError>() || err.is::<SessionError>() || err.is::<IdentifierError>() {
+        return Some(ErrorCategory::Plan);
+    }
+    if err.is::<FieldTypeError>() {
+        return Some(ErrorCategory::Corruption);
+    }
+    // 作成時と異なる block size で開くと、暗号化の鍵が違う場合と同様にデータを正しく読めない
+    if let Some(
+        ControlFileError::Invalid(_)
+        | ControlFileError::UnsupportedVersion(_)
+        | ControlFileError::BlockSizeMismatch { .. }
+        | ControlFileError::UnknownBlockSize(_),
+    ) = err.downcast_ref::<ControlFileError>()
+    {
+        return Some(ErrorCategory::Corruption);
+    }
+    None
+}
+
+#[cfg(test)]
+mod error_test {
+    use std::io;
+
+    use anyhow::anyhow;
+
+    use super::{ErrorCategory, SimpleDbError};
+    use crate::{
+        file::file_manager::FileManagerError, tx::concurrency::lock_table::LockTableError,
+    };
+
+    #[test]
+    fn test_categorize_source_chain() {
+        // 内側の error まで辿って分類し、元の error は source として残る
+        let io_err = io::Error::new(io::ErrorKind::Other, "disk is full");
+        let err: SimpleDbError = anyhow::Error::from(FileManagerError::IoError(io_err))
+            .context("failed to write block")
+            .into();
+        assert_eq!(err.category(), ErrorCategory::Io);
+        let source = std::error::Error::source(&err).unwrap();
+        assert_eq!(source.to_string(), "failed to write block");
+
+        let err: SimpleDbError = anyhow!(LockTableError::Timeout("b".to_string())).into();
+        assert_eq!(err.category(), ErrorCategory::Lock);
+        assert!(err.is_retryable());
+        let err: SimpleDbError = anyhow!(LockTableError::Lock("b".to_string())).into();
+        assert_eq!(err.category(), ErrorCategory::Lock);
+        assert!(!err.is_retryable());
+
+        let err: SimpleDbError = anyhow!("unknown").into();
+        assert_eq!(err.category(), ErrorCategory::Internal);
+    }
+
+    #[cfg(feature = "sql")]
+    #[test]
+    fn test_categorize_sql_error() {
+        use crate::parse::parser::ParserError;
+
+        let err: SimpleDbError = anyhow!(ParserError::UnexpectedToken("x".to_string())).into();
+        assert_eq!(err.category(), ErrorCategory::Parse);
+    }
+}
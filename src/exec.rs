@@ -1 +1,5 @@
 pub mod executor;
+pub mod retry_policy;
+pub mod session;
+pub mod transaction_scope;
+pub mod validator;
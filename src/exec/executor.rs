@@ -1,30 +1,130 @@
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+    sync::{Arc, Mutex, MutexGuard},
+    thread,
+};
 
-use anyhow::Result as AnyhowResult;
+use anyhow::{anyhow, Result as AnyhowResult};
+use thiserror::Error;
 
 use crate::{
-    metadata::metadata_manager::MetadataManager,
+    error::SimpleDbResult,
+    index::common::{Index, IndexEntry},
+    metadata::{
+        identifier::{validate_identifier, IdentifierKind},
+        index_manager::IndexInfo,
+        metadata_manager::MetadataManager,
+    },
     parse::{
         content::{
-            create_table_data::CreateTableData, create_view_data::CreateViewData,
-            delete_data::DeleteData, insert_data::InsertData, update_data::UpdateData,
+            create_index_data::CreateIndexData, create_table_data::CreateTableData,
+            create_view_data::CreateViewData, delete_data::DeleteData, insert_data::InsertData,
+            query_data::QueryData, rename_column_data::RenameColumnData,
+            rename_index_data::RenameIndexData, rename_table_data::RenameTableData,
+            update_data::UpdateData,
         },
-        parser::UpdateCommand,
+        parser::{ShowCommand, UpdateCommand},
         parser_factory::ParserFactory,
     },
-    plan::{plan::Plan, predicate::Predicate, select_plan::SelectPlan, table_plan::TablePlan},
-    planner::query_planner::QueryPlanner,
-    query::scan::ReadScan,
-    tx::transaction::Transaction,
+    plan::{
+        expression::Expression, instrumented_plan::ExplainNode, plan::Plan,
+        plan_snapshot::PlanSnapshot, predicate::Predicate, select_plan::SelectPlan,
+        table_plan::TablePlan,
+    },
+    planner::{query_builder::Query, query_planner::QueryPlanner, row_policy::RowPolicy},
+    query::{
+        coercion,
+        constant::Constant,
+        date::Date,
+        memory_budget::MemoryBudget,
+        predicate::Predicate as PredicateForScan,
+        scan::{ReadScan, Scan, UpdateScan},
+        values_scan::ValuesScan,
+    },
+    record::{
+        rid::Rid,
+        schema::{FieldInfo, Schema},
+    },
+    tx::transaction::{Transaction, TransactionFactory},
+};
+
+use super::{
+    retry_policy::RetryPolicy,
+    transaction_scope::TransactionScope,
+    validator::{Diagnostic, Validator},
 };
 
+#[derive(Error, Debug)]
+pub enum ExecutorError {
+    #[error("invalid command: {0}")]
+    InvalidCommand(String),
+    #[error("table {0} is read-only")]
+    ReadOnlyTable(String),
+    #[error("transaction factory is not set")]
+    TransactionFactoryNotSet,
+    #[error("record does not satisfy the row policy of table {0}")]
+    RowPolicyViolation(String),
+    #[error("duplicate value {1} for unique index {0}")]
+    UniqueViolation(String, Constant),
+    #[error("failed to acquire lock of {0}")]
+    Lock(String),
+}
+
+// show tables, describe の結果の field
+const SHOW_TABLES_FIELDS: [&str; 1] = ["table_name"];
+const DESCRIBE_FIELDS: [&str; 4] = ["field_name", "type", "length", "offset"];
+
+/// execute で実行した文の結果
+pub enum QueryResult {
+    /// select, show tables, describe の結果。fields は scan から読める field の名前
+    Rows {
+        fields: Vec<String>,
+        scan: Box<dyn ReadScan>,
+    },
+    /// insert, update, delete の結果。変更した record の数
+    Count(u64),
+    /// create table などの、record を変更しない文の結果
+    Ddl,
+    /// explain analyze の結果
+    Explain(ExplainNode),
+}
+
+// transaction 番号 -> (table 名 -> その transaction で変更した record の数)
+type ModifiedRecords = HashMap<u32, HashMap<String, u64>>;
+
+/// create index で、table にすでにある record を index に登録する処理の進捗
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct IndexBuildProgress {
+    /// 作成している index の名前
+    pub index_name: String,
+    /// 読み終えた table の record の数
+    pub records_scanned: u64,
+    /// index に登録した entry の数。table を読み終えてから、まとめて登録する
+    pub entries_loaded: u64,
+}
+
+/// create index の進捗を受け取る callback
+pub type IndexBuildProgressCallback = Arc<dyn Fn(&IndexBuildProgress) + Send + Sync>;
+
 pub struct Executor {
     planner: Box<dyn QueryPlanner>,
     parser_factory: ParserFactory,
     metadata_manager: Arc<dyn MetadataManager>,
+    // exec_with_retry で新しい transaction を作成するために使う
+    transaction_factory: Option<Arc<TransactionFactory>>,
+    modified_records: Arc<Mutex<ModifiedRecords>>,
+    // commit した transaction で変更した record の数がこれを超えた table は、統計情報を計算し直す
+    stats_invalidation_threshold: u64,
+    index_build_progress: Option<IndexBuildProgressCallback>,
+    // catalog に保存された default 値の式の文字列 -> parse した式。insert のたびに parse し直さないために使う
+    default_expressions: Mutex<HashMap<String, Expression>>,
 }
 
 impl Executor {
+    const DEFAULT_STATS_INVALIDATION_THRESHOLD: u64 = 0;
+
     pub fn new(
         planner: Box<dyn QueryPlanner>,
         parser_factory: ParserFactory,
@@ -34,19 +134,144 @@ impl Executor {
             planner,
             parser_factory,
             metadata_manager,
+            transaction_factory: None,
+            modified_records: Arc::new(Mutex::new(HashMap::new())),
+            stats_invalidation_threshold: Self::DEFAULT_STATS_INVALIDATION_THRESHOLD,
+            index_build_progress: None,
+            default_expressions: Mutex::new(HashMap::new()),
         }
     }
+
+    /// 1 つの transaction で threshold より多くの record を変更した table の統計情報を、commit 時に計算し直すようにする
+    pub fn with_stats_invalidation_threshold(mut self, threshold: u64) -> Self {
+        self.stats_invalidation_threshold = threshold;
+        self
+    }
+
+    /// create index で table の record を index に登録する間、record を 1 つ読むたびと登録を終えた時に progress を呼び出す
+    pub fn with_index_build_progress<F: Fn(&IndexBuildProgress) + Send + Sync + 'static>(
+        mut self,
+        progress: F,
+    ) -> Self {
+        self.index_build_progress = Some(Arc::new(progress));
+        self
+    }
+
+    pub fn with_transaction_factory(
+        mut self,
+        transaction_factory: Arc<TransactionFactory>,
+    ) -> Self {
+        self.transaction_factory = Some(transaction_factory);
+        self
+    }
+
+    pub(crate) fn metadata_manager(&self) -> &Arc<dyn MetadataManager> {
+        &self.metadata_manager
+    }
     /// select クエリを実行し、その scan を返す。scan 自体の操作は client が行う必要がある
     pub fn exec_query(
         &self,
         cmd: &str,
         tx: &Rc<RefCell<Transaction>>,
-    ) -> AnyhowResult<Box<dyn ReadScan>> {
-        let mut parser = self.parser_factory.create(cmd.to_string())?;
-        let query_data = parser.parse_query()?;
-        let plan = self.planner.create_plan(&query_data, tx)?;
+    ) -> SimpleDbResult<Box<dyn ReadScan>> {
+        let query_data = self
+            .parser_factory
+            .create(cmd.to_string())
+            .and_then(|mut parser| parser.parse_query())?;
+        self.exec_query_data(&query_data, tx)
+    }
+    /// parse 済の select クエリを実行し、その scan を返す
+    pub fn exec_query_data(
+        &self,
+        query_data: &QueryData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<Box<dyn ReadScan>> {
+        self.exec_query_data_with_policy(query_data, &RowPolicy::new(), tx)
+    }
+    /// parse 済の select クエリを、row_policy で読める record に制限した上で実行し、その scan を返す
+    pub fn exec_query_data_with_policy(
+        &self,
+        query_data: &QueryData,
+        row_policy: &RowPolicy,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<Box<dyn ReadScan>> {
+        let budget = self.metadata_manager.new_query_budget();
+        let plan = self
+            .planner
+            .create_plan(query_data, row_policy, &budget, tx)?;
+        let mut scan = plan.open_read_scan()?;
+        scan.before_first()?;
+        Ok(scan)
+    }
+    /// builder で組み立てた query を実行し、その scan を返す
+    pub fn exec_built_query(
+        &self,
+        query: &Query,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<Box<dyn ReadScan>> {
+        let budget = self.metadata_manager.new_query_budget();
+        let plan = self.planner.create_plan_for_query(query, &budget, tx)?;
+        let mut scan = plan.open_read_scan()?;
+        scan.before_first()?;
+        Ok(scan)
+    }
+    /// explain analyze select ... 文を実行する
+    /// select クエリを最後まで読み切った上で、plan tree の各 node について見積もりと実際の record の数・block の数を返す
+    pub fn exec_explain_analyze(
+        &self,
+        cmd: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<ExplainNode> {
+        let query_data = self
+            .parser_factory
+            .create(cmd.to_string())
+            .and_then(|mut parser| parser.parse_explain_analyze())?;
+        self.exec_explain_analyze_data_with_policy(&query_data, &RowPolicy::new(), tx)
+    }
+    /// select 文を実行せずに、planner が作成する plan tree の形と見積もりを返す
+    pub fn plan_snapshot(
+        &self,
+        cmd: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<PlanSnapshot> {
+        let query_data = self
+            .parser_factory
+            .create(cmd.to_string())
+            .and_then(|mut parser| parser.parse_query())?;
+        let budget = self.metadata_manager.new_query_budget();
+        let (_, node) =
+            self.planner
+                .create_instrumented_plan(&query_data, &RowPolicy::new(), &budget, tx)?;
+        Ok(node.snapshot())
+    }
+    /// parse 済の select クエリを、row_policy で読める record に制限した上で、各 plan の結果を数えながら実行する
+    pub fn exec_explain_analyze_data_with_policy(
+        &self,
+        query_data: &QueryData,
+        row_policy: &RowPolicy,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<ExplainNode> {
+        let budget = self.metadata_manager.new_query_budget();
+        let (plan, node) = self
+            .planner
+            .create_instrumented_plan(query_data, row_policy, &budget, tx)?;
         let mut scan = plan.open_read_scan()?;
         scan.before_first()?;
+        while scan.move_next()? {}
+        Ok(node)
+    }
+    /// show tables, describe 文を実行し、結果を読む scan を返す
+    /// show tables は table_name を、describe は各 field の field_name, type, length, offset を record として返す
+    pub fn exec_show_command(
+        &self,
+        cmd: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<Box<dyn ReadScan>> {
+        let show_command = self
+            .parser_factory
+            .create(cmd.to_string())
+            .and_then(|mut parser| parser.parse_show_command())?;
+        let (_, scan) = self.exec_show(show_command, tx)?;
         Ok(scan)
     }
     /// create, update, delete などのクエリを実行する。影響を受けたレコードの数を返り値として返す
@@ -54,13 +279,189 @@ impl Executor {
         &self,
         cmd: &str,
         tx: &Rc<RefCell<Transaction>>,
-    ) -> AnyhowResult<u64> {
-        let mut parser = self.parser_factory.create(cmd.to_string())?;
-        let update_data = parser.parse_update_command()?;
-        match update_data {
-            UpdateCommand::Insert(insert_data) => self.exec_insert(&insert_data, tx),
-            UpdateCommand::Delete(delete_data) => self.exec_delete(&delete_data, tx),
-            UpdateCommand::Update(update_data) => self.execute_update(&update_data, tx),
+    ) -> SimpleDbResult<u64> {
+        self.exec_update_command_with_policy(cmd, &RowPolicy::new(), tx)
+    }
+    /// create, update, delete などのクエリを、row_policy で読み書きできる record に制限した上で実行する
+    /// insert や update の結果の record が row_policy を満たさない場合は error を返す
+    pub fn exec_update_command_with_policy(
+        &self,
+        cmd: &str,
+        row_policy: &RowPolicy,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<u64> {
+        let update_data = self
+            .parser_factory
+            .create(cmd.to_string())
+            .and_then(|mut parser| parser.parse_update_command())?;
+        self.exec_update_data(update_data, row_policy, tx)
+    }
+    /// 任意の文を実行し、文の種類に応じた結果を返す
+    /// declare cursor などの session が必要な文は実行できない
+    pub fn execute(&self, sql: &str, tx: &Rc<RefCell<Transaction>>) -> SimpleDbResult<QueryResult> {
+        self.execute_with_policy(sql, &RowPolicy::new(), tx)
+    }
+    /// 任意の文を、row_policy で読み書きできる record に制限した上で実行する
+    pub fn execute_with_policy(
+        &self,
+        sql: &str,
+        row_policy: &RowPolicy,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<QueryResult> {
+        let keyword = sql
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        let mut parser = self.parser_factory.create(sql.to_string())?;
+        match keyword.as_str() {
+            "select" => {
+                let query_data = parser.parse_query()?;
+                let scan = self.exec_query_data_with_policy(&query_data, row_policy, tx)?;
+                Ok(QueryResult::Rows {
+                    fields: query_data.get_fields().to_vec(),
+                    scan,
+                })
+            }
+            "explain" => {
+                let query_data = parser.parse_explain_analyze()?;
+                Ok(QueryResult::Explain(
+                    self.exec_explain_analyze_data_with_policy(&query_data, row_policy, tx)?,
+                ))
+            }
+            "show" | "describe" => {
+                let (fields, scan) = self.exec_show(parser.parse_show_command()?, tx)?;
+                Ok(QueryResult::Rows { fields, scan })
+            }
+            "declare" | "fetch" | "close" => Err(anyhow!(ExecutorError::InvalidCommand(
+                "cursor commands must be executed through a session".to_string()
+            ))
+            .into()),
+            _ => {
+                let update_data = parser.parse_update_command()?;
+                let is_ddl = !matches!(
+                    update_data,
+                    UpdateCommand::Insert(_) | UpdateCommand::Delete(_) | UpdateCommand::Update(_)
+                );
+                let count = self.exec_update_data(update_data, row_policy, tx)?;
+                Ok(if is_ddl {
+                    QueryResult::Ddl
+                } else {
+                    QueryResult::Count(count)
+                })
+            }
+        }
+    }
+    /// 文を実行せずに、構文・table や field の名前・型を検証し、見つかった問題を返す。問題がなければ空の Vec を返す
+    pub fn validate(
+        &self,
+        cmd: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<Vec<Diagnostic>> {
+        Ok(
+            Validator::new(self.metadata_manager.as_ref(), &self.parser_factory)
+                .validate(cmd, tx)?,
+        )
+    }
+    /// 入れ子にできる transaction の scope を開始する
+    /// parent が None の場合は新しい transaction を開始し、そうでなければ parent の中に savepoint を使った scope を作る
+    /// 呼び出し元がすでに transaction を持っているかどうかを知らなくても、同じように commit / rollback できる
+    pub fn begin_scope(
+        &self,
+        parent: Option<&Rc<RefCell<Transaction>>>,
+    ) -> SimpleDbResult<TransactionScope> {
+        if let Some(parent) = parent {
+            return TransactionScope::new_nested(parent);
+        }
+        let transaction_factory = self
+            .transaction_factory
+            .as_ref()
+            .ok_or_else(|| anyhow!(ExecutorError::TransactionFactoryNotSet))?;
+        Ok(TransactionScope::new_root(Rc::new(RefCell::new(
+            transaction_factory.create()?,
+        ))))
+    }
+    /// update 系の文を新しい transaction で実行し、成功したら commit する
+    /// lock の timeout などの再試行可能な error が起きた場合は、rollback した上で policy に従って新しい transaction で再実行する
+    pub fn exec_with_retry(&self, cmd: &str, policy: &RetryPolicy) -> SimpleDbResult<u64> {
+        let transaction_factory = self
+            .transaction_factory
+            .as_ref()
+            .ok_or_else(|| anyhow!(ExecutorError::TransactionFactoryNotSet))?;
+        let mut attempt = 0;
+        loop {
+            let tx = Rc::new(RefCell::new(transaction_factory.create()?));
+            match self.exec_update_command(cmd, &tx) {
+                Ok(update_count) => {
+                    tx.borrow_mut().commit()?;
+                    return Ok(update_count);
+                }
+                Err(err) => {
+                    tx.borrow_mut().rollback()?;
+                    if !err.is_retryable() || attempt >= policy.max_retries {
+                        return Err(err);
+                    }
+                    thread::sleep(policy.backoff(attempt));
+                    attempt += 1;
+                }
+            }
+        }
+    }
+    /// transaction で table の record を count 件変更したことを記録する
+    /// transaction が commit された時に、変更した record の数が閾値を超えた table の統計情報を捨てる
+    fn record_modifications(&self, table_name: &str, count: u64, tx: &Rc<RefCell<Transaction>>) {
+        if count == 0 {
+            return;
+        }
+        // 統計情報は実体の table 名で管理されている
+        let table_name = self.metadata_manager.resolve_table_name(table_name, tx);
+        // 最小値と最大値は、この transaction の変更を含まないので使わないようにする
+        self.metadata_manager.forget_table_range(&table_name);
+        let txnum = tx.borrow().tx_num();
+        let mut modified_records = self.modified_records.lock().unwrap();
+        if !modified_records.contains_key(&txnum) {
+            // transaction ごとに、最初に変更した時だけ hook を登録する
+            let modified_records = self.modified_records.clone();
+            let metadata_manager = self.metadata_manager.clone();
+            let threshold = self.stats_invalidation_threshold;
+            tx.borrow().on_complete(Box::new(move |committed| {
+                let counts = modified_records.lock().unwrap().remove(&txnum);
+                if !committed {
+                    return;
+                }
+                for (table_name, count) in counts.unwrap_or_default() {
+                    if count > threshold {
+                        metadata_manager.invalidate_table_stat(&table_name);
+                    } else {
+                        // 変更中に計算し直された範囲には、この transaction の変更が含まれていないことがある
+                        metadata_manager.forget_table_range(&table_name);
+                    }
+                }
+            }));
+        }
+        *modified_records
+            .entry(txnum)
+            .or_default()
+            .entry(table_name)
+            .or_default() += count;
+    }
+    /// parse 済の update 系の文を実行し、影響を受けた record の数を返す
+    pub(crate) fn exec_update_data(
+        &self,
+        update_data: UpdateCommand,
+        row_policy: &RowPolicy,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<u64> {
+        let update_count = match update_data {
+            UpdateCommand::Insert(insert_data) => self
+                .exec_insert(&insert_data, row_policy, tx)
+                .inspect(|count| self.record_modifications(insert_data.get_table(), *count, tx)),
+            UpdateCommand::Delete(delete_data) => self
+                .exec_delete(&delete_data, row_policy, tx)
+                .inspect(|count| self.record_modifications(delete_data.get_table(), *count, tx)),
+            UpdateCommand::Update(update_data) => self
+                .execute_update(&update_data, row_policy, tx)
+                .inspect(|count| self.record_modifications(update_data.get_table(), *count, tx)),
             UpdateCommand::CreateTable(create_table_data) => {
                 self.exec_create_table(&create_table_data, tx)
             }
@@ -68,49 +469,161 @@ impl Executor {
                 self.exec_create_view(&create_view_data, tx)
             }
             UpdateCommand::CreateIndex(create_index_data) => {
-                unimplemented!("create index is not implemented yet")
+                self.exec_create_index(&create_index_data, tx)
             }
-        }
+            UpdateCommand::RenameColumn(rename_column_data) => {
+                self.exec_rename_column(&rename_column_data, tx)
+            }
+            UpdateCommand::RenameTable(rename_table_data) => {
+                self.exec_rename_table(&rename_table_data, tx)
+            }
+            UpdateCommand::RenameIndex(rename_index_data) => {
+                self.exec_rename_index(&rename_index_data, tx)
+            }
+        }?;
+        Ok(update_count)
     }
-    fn exec_delete(&self, data: &DeleteData, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<u64> {
-        let plan = {
-            let plan = TablePlan::new(
-                data.get_table().clone(),
-                self.metadata_manager.as_ref(),
-                tx.clone(),
-            )?;
-            let plan = SelectPlan::new(
-                Box::new(plan),
-                Box::new(Predicate::Product(data.get_predicate().clone())),
-            );
-            Box::new(plan)
+    // show tables, describe を実行し、結果の field の名前と scan を返す
+    fn exec_show(
+        &self,
+        show_command: ShowCommand,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<(Vec<String>, Box<dyn ReadScan>)> {
+        let (fields, scan) = match show_command {
+            ShowCommand::Tables => (&SHOW_TABLES_FIELDS[..], self.exec_show_tables(tx)?),
+            ShowCommand::Describe(table_name) => {
+                (&DESCRIBE_FIELDS[..], self.exec_describe(&table_name, tx)?)
+            }
         };
-
+        let fields = fields.iter().map(|field| field.to_string()).collect();
+        Ok((fields, Box::new(scan)))
+    }
+    fn exec_show_tables(&self, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<ValuesScan> {
+        let rows = self
+            .metadata_manager
+            .get_table_names(tx)?
+            .into_iter()
+            .map(|table_name| vec![Constant::String(table_name)])
+            .collect();
+        Ok(ValuesScan::new(
+            SHOW_TABLES_FIELDS
+                .iter()
+                .map(|field| field.to_string())
+                .collect(),
+            rows,
+        ))
+    }
+    fn exec_describe(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<ValuesScan> {
+        let physical_name = self.metadata_manager.resolve_table_name(table_name, tx);
+        let layout = self.metadata_manager.get_layout(&physical_name, tx)?;
+        let schema = layout.schema();
+        let mut rows = vec![];
+        for (field, info) in schema.infos() {
+            let (field_type, length) = match info {
+                FieldInfo::Integer => ("int", 0),
+                FieldInfo::String(length) => ("varchar", length),
+                FieldInfo::Boolean => ("boolean", 0),
+                FieldInfo::BigInt => ("bigint", 0),
+                FieldInfo::Date => ("date", 0),
+                FieldInfo::Double => ("double", 0),
+            };
+            let offset = layout.offset(field).unwrap_or_default();
+            rows.push(vec![
+                Constant::String(field.to_string()),
+                Constant::String(field_type.to_string()),
+                Constant::Int(length as i32),
+                Constant::Int(offset as i32),
+            ]);
+        }
+        Ok(ValuesScan::new(
+            DESCRIBE_FIELDS
+                .iter()
+                .map(|field| field.to_string())
+                .collect(),
+            rows,
+        ))
+    }
+    fn exec_delete(
+        &self,
+        data: &DeleteData,
+        row_policy: &RowPolicy,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<u64> {
+        self.check_writable(data.get_table(), tx)?;
+        let predicate =
+            row_policy.restrict(data.get_predicate(), std::slice::from_ref(data.get_table()));
+        let plan = TablePlan::new(
+            data.get_table().clone(),
+            self.metadata_manager.as_ref(),
+            &self.metadata_manager.new_query_budget(),
+            tx.clone(),
+        )?
+        .with_partition_pruning(&predicate);
+        let mut indexes = self.open_indexes(data.get_table(), &plan, tx)?;
+        if indexes.is_empty() {
+            // 1 件ずつ SelectScan を通して delete するのではなく、table scan 上でまとめて削除する
+            return plan.delete_where(&predicate);
+        }
+        // index を張った table は、削除する record ごとに index の entry も削除する
+        let plan = SelectPlan::new(Box::new(plan), Box::new(Predicate::Product(predicate)));
         let mut scan = plan.open_update_scan()?;
-        let mut update_count = 0;
+        scan.before_first()?;
+        let mut delete_count = 0;
         while scan.move_next()? {
+            let rid = scan.get_rid()?;
+            for (index_info, index) in indexes.iter_mut() {
+                index.delete(&scan.get_val(index_info.field_name())?, &rid)?;
+            }
             scan.delete()?;
-            update_count += 1;
+            delete_count += 1;
         }
-        Ok(update_count)
+        Ok(delete_count)
     }
     fn execute_update(
         &self,
         data: &UpdateData,
+        row_policy: &RowPolicy,
         tx: &Rc<RefCell<Transaction>>,
     ) -> AnyhowResult<u64> {
-        let plan = {
+        self.check_writable(data.get_table(), tx)?;
+        // 途中の record で error が起きた場合に、それまでに更新した record も元に戻せるよう savepoint を作っておく
+        let savepoint = tx.borrow().savepoint()?;
+        self.update_records(data, row_policy, tx).or_else(|err| {
+            tx.borrow().rollback_to_savepoint(&savepoint)?;
+            Err(err)
+        })
+    }
+    /// execute_update の本体。error を返した場合、一部の record だけが更新されたままになっていることがある
+    fn update_records(
+        &self,
+        data: &UpdateData,
+        row_policy: &RowPolicy,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<u64> {
+        let (plan, mut indexes) = {
+            let predicate =
+                row_policy.restrict(data.get_predicate(), std::slice::from_ref(data.get_table()));
             let plan = TablePlan::new(
                 data.get_table().clone(),
                 self.metadata_manager.as_ref(),
+                &self.metadata_manager.new_query_budget(),
                 tx.clone(),
-            )?;
-            let plan = SelectPlan::new(
-                Box::new(plan),
-                Box::new(Predicate::Product(data.get_predicate().clone())),
-            );
-            Box::new(plan)
+            )?
+            .with_partition_pruning(&predicate);
+            // 値を変更する field に張られた index と、その field を include している index だけを更新すればよい
+            let indexes: Vec<_> = self
+                .open_indexes(data.get_table(), &plan, tx)?
+                .into_iter()
+                .filter(|(index_info, _)| index_info.covers(std::slice::from_ref(data.get_field())))
+                .collect();
+            let plan = SelectPlan::new(Box::new(plan), Box::new(Predicate::Product(predicate)));
+            (Box::new(plan), indexes)
         };
+        let schema = plan.get_schema().clone();
         let mut scan = plan.open_update_scan()?;
         scan.before_first()?;
         let mut update_count = 0;
@@ -119,32 +632,330 @@ impl Executor {
                 .get_new_value()
                 .convert_for_scan()
                 .eval(scan.as_ref())?;
+            let val = Self::coerce_value(&schema, data.get_field(), &val)?;
+            let old_val = scan.get_val(data.get_field())?;
+            // 更新前の index の key。index の entry を削除するのに使う
+            let mut old_keys = vec![];
+            for (index_info, index) in indexes.iter_mut() {
+                if index_info.field_name() == data.get_field() && val != old_val {
+                    Self::check_unique(index_info, index.as_mut(), &val)?;
+                }
+                old_keys.push(scan.get_val(index_info.field_name())?);
+            }
             scan.set_val(data.get_field(), &val)?;
+            // 更新によって row policy の外に record を移すことはできない
+            let (satisfied, updated_scan) =
+                Self::satisfies_row_policy(data.get_table(), row_policy, scan)?;
+            scan = updated_scan;
+            if !satisfied {
+                return Err(anyhow!(ExecutorError::RowPolicyViolation(
+                    data.get_table().clone()
+                )));
+            }
+            let rid = scan.get_rid()?;
+            for ((index_info, index), old_key) in indexes.iter_mut().zip(old_keys) {
+                index.delete(&old_key, &rid)?;
+                index.insert_entry(&Self::index_entry(index_info, scan.as_ref(), rid)?)?;
+            }
             update_count += 1;
         }
         Ok(update_count)
     }
-    fn exec_insert(&self, data: &InsertData, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<u64> {
+    /// scan が指している record が、table に設定された row policy を満たすかどうかを、確認した後の scan と共に返す
+    fn satisfies_row_policy(
+        table_name: &str,
+        row_policy: &RowPolicy,
+        scan: Box<dyn UpdateScan>,
+    ) -> AnyhowResult<(bool, Box<dyn UpdateScan>)> {
+        let Some(predicate) = row_policy.predicate(table_name) else {
+            return Ok((true, scan));
+        };
+        // predicate は Scan を受け取るので、一時的に包んで評価する
+        let scan = Scan::Updatable(scan);
+        let satisfied = predicate.convert_for_scan().is_satisfied(&scan)?;
+        let Scan::Updatable(scan) = scan else {
+            unreachable!()
+        };
+        Ok((satisfied, scan))
+    }
+    fn exec_insert(
+        &self,
+        data: &InsertData,
+        row_policy: &RowPolicy,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<u64> {
+        self.check_writable(data.get_table(), tx)?;
         let plan = TablePlan::new(
             data.get_table().clone(),
             self.metadata_manager.as_ref(),
+            &self.metadata_manager.new_query_budget(),
             tx.clone(),
         )?;
+        let schema = plan.get_schema().clone();
+        let defaults = {
+            let table_name = self
+                .metadata_manager
+                .resolve_table_name(data.get_table(), tx);
+            self.metadata_manager
+                .get_default_expressions(&table_name, tx)?
+        };
+        // column のリストが省略された場合は、catalog にある table の全 field をその順番で使う
+        let fields = if data.get_fields().is_empty() {
+            schema.fields()
+        } else {
+            data.get_fields().clone()
+        };
+        if fields.len() != data.get_values().len() {
+            return Err(anyhow!(ExecutorError::InvalidCommand(format!(
+                "the number of values ({}) does not match the number of fields ({}) for table {}",
+                data.get_values().len(),
+                fields.len(),
+                data.get_table()
+            ))));
+        }
+        // 途中で失敗しても書きかけの record が残らないように、全ての値を計算してから record を追加する
+        let mut row = schema
+            .infos()
+            .map(|(field, info)| (field.to_string(), Self::empty_value(info)))
+            .collect::<Vec<_>>();
+        let mut assigned = Vec::new();
+        for (field, val) in fields.iter().zip(data.get_values().iter()) {
+            let val = Self::coerce_value(&schema, field, val)?;
+            if let Some(entry) = row.iter_mut().find(|(name, _)| name == field) {
+                entry.1 = val.clone();
+            }
+            assigned.push((field.clone(), val));
+        }
+        // 値が指定されなかった field には default 値を評価して設定する
+        // default 値の式から他の field を参照する場合、その値は上で設定した後のものになる
+        for index in 0..row.len() {
+            let field = row[index].0.clone();
+            if fields.contains(&field) {
+                continue;
+            }
+            if let Some(expression) = defaults.get(&field) {
+                let (names, values): (Vec<_>, Vec<_>) = row.iter().cloned().unzip();
+                let mut current = ValuesScan::new(names, vec![values]);
+                current.move_next()?;
+                let val = self
+                    .default_expression(expression)?
+                    .convert_for_scan()
+                    .eval(&current)?;
+                let val = Self::coerce_value(&schema, &field, &val)?;
+                row[index].1 = val.clone();
+                assigned.push((field, val));
+            }
+        }
+        let mut indexes = self.open_indexes(data.get_table(), &plan, tx)?;
         let mut scan = plan.open_update_scan()?;
         drop(plan);
         scan.insert()?;
-        for (field, val) in data.get_fields().iter().zip(data.get_values().iter()) {
-            scan.set_val(field, val)?;
+        for (field, val) in &assigned {
+            if let Err(err) = scan.set_val(field, val) {
+                scan.delete()?;
+                return Err(err);
+            }
+        }
+        let (satisfied, mut scan) = Self::satisfies_row_policy(data.get_table(), row_policy, scan)?;
+        if !satisfied {
+            scan.delete()?;
+            return Err(anyhow!(ExecutorError::RowPolicyViolation(
+                data.get_table().clone()
+            )));
+        }
+        // 一部の index にだけ entry が残らないように、全ての unique な index を確認してから登録する
+        for (index_info, index) in indexes.iter_mut() {
+            let val = scan.get_val(index_info.field_name())?;
+            if let Err(err) = Self::check_unique(index_info, index.as_mut(), &val) {
+                scan.delete()?;
+                return Err(err);
+            }
+        }
+        let rid = scan.get_rid()?;
+        for (index_info, index) in indexes.iter_mut() {
+            index.insert_entry(&Self::index_entry(index_info, scan.as_ref(), rid)?)?;
         }
         Ok(1)
     }
+    /// catalog に保存された default 値の式を parse する。式は create table の時に検証しているので、ここでは型を確かめない
+    /// 一度 parse した式は文字列ごとに覚えておき、次からはそれを使う
+    fn default_expression(&self, text: &str) -> AnyhowResult<Expression> {
+        if let Some(expression) = self.lock_default_expressions()?.get(text) {
+            return Ok(expression.clone());
+        }
+        let expression = self
+            .parser_factory
+            .create(text.to_string())?
+            .parse_expression()?;
+        self.lock_default_expressions()?
+            .insert(text.to_string(), expression.clone());
+        Ok(expression)
+    }
+    fn lock_default_expressions(
+        &self,
+    ) -> AnyhowResult<MutexGuard<'_, HashMap<String, Expression>>> {
+        self.default_expressions
+            .lock()
+            .map_err(|_| anyhow!(ExecutorError::Lock("default expressions".to_string())))
+    }
+    /// 値を設定していない field が持つ、型ごとの初期値 (追加したばかりの record の field の値と同じ)
+    fn empty_value(info: FieldInfo) -> Constant {
+        match info {
+            FieldInfo::Integer => Constant::Int(0),
+            FieldInfo::String(_) => Constant::String(String::new()),
+            FieldInfo::Boolean => Constant::Bool(false),
+            FieldInfo::BigInt => Constant::BigInt(0),
+            FieldInfo::Date => Constant::Date(Date::from_days(0)),
+            FieldInfo::Double => Constant::Double(0.0),
+        }
+    }
+    /// scan が指している record (rid) を、include の field の値と共に index_info の index に登録する entry
+    fn index_entry(
+        index_info: &IndexInfo,
+        scan: &dyn UpdateScan,
+        rid: Rid,
+    ) -> AnyhowResult<IndexEntry> {
+        let included_vals = index_info
+            .included_fields()
+            .iter()
+            .map(|field| scan.get_val(field))
+            .collect::<AnyhowResult<Vec<_>>>()?;
+        Ok(IndexEntry::new(scan.get_val(index_info.field_name())?, rid)
+            .with_included_vals(included_vals))
+    }
+    /// table に張られた index を全て開き、その index の情報と一緒に返す
+    fn open_indexes(
+        &self,
+        table_name: &str,
+        plan: &TablePlan,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Vec<(IndexInfo, Box<dyn Index>)>> {
+        let table_name = self.metadata_manager.resolve_table_name(table_name, tx);
+        let mut indexes = vec![];
+        for index_info in self.metadata_manager.get_index_info(&table_name, tx)? {
+            let index = index_info.open(tx, plan.layout().schema())?;
+            indexes.push((index_info, index));
+        }
+        Ok(indexes)
+    }
+    /// unique な index に、値が key である record がすでにある場合は error を返す
+    /// before_first で key の slock を取るので、確認してから insert するまでに他の transaction が同じ値を追加することはない
+    fn check_unique(
+        index_info: &IndexInfo,
+        index: &mut dyn Index,
+        key: &Constant,
+    ) -> AnyhowResult<()> {
+        if !index_info.is_unique() {
+            return Ok(());
+        }
+        index.before_first(key)?;
+        if index.next()? {
+            return Err(anyhow!(ExecutorError::UniqueViolation(
+                index_info.index_name().to_string(),
+                key.clone()
+            )));
+        }
+        Ok(())
+    }
+    /// field に代入する値を、coercion の規則に従って field の型に変換する
+    fn coerce_value(schema: &Schema, field: &str, val: &Constant) -> AnyhowResult<Constant> {
+        // 存在しない field への代入は、scan が error を返す
+        let Some(info) = schema.info(field) else {
+            return Ok(val.clone());
+        };
+        coercion::coerce(val, info.get_type()).ok_or_else(|| {
+            anyhow!(ExecutorError::InvalidCommand(format!(
+                "value {} does not match the type of field {}",
+                val, field
+            )))
+        })
+    }
+    // external table は read-only なので、更新系の command の対象にはできない
+    fn check_writable(&self, table_name: &str, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<()> {
+        if self
+            .metadata_manager
+            .get_external_table_path(table_name, tx)?
+            .is_some()
+        {
+            return Err(anyhow!(ExecutorError::ReadOnlyTable(
+                table_name.to_string()
+            )));
+        }
+        Ok(())
+    }
     fn exec_create_table(
         &self,
         data: &CreateTableData,
         tx: &Rc<RefCell<Transaction>>,
     ) -> AnyhowResult<u64> {
-        self.metadata_manager
-            .create_table(data.get_table(), data.get_schema().clone(), tx)?;
+        if data.if_not_exists() {
+            // temp table の場合は、同じ transaction で作成した temp table だけを見る
+            let exists = if data.is_temp() {
+                self.metadata_manager
+                    .resolve_table_name(data.get_table(), tx)
+                    != *data.get_table()
+            } else {
+                self.metadata_manager.table_exists(data.get_table(), tx)?
+            };
+            if exists {
+                return Ok(0);
+            }
+        }
+        if data.is_temp() {
+            // temp table の情報は catalog に保存しないため、default 値も保存できない
+            if !data.get_defaults().is_empty() {
+                return Err(anyhow!(ExecutorError::InvalidCommand(
+                    "default values are not supported for temp tables".to_string()
+                )));
+            }
+            self.metadata_manager.create_temp_table(
+                data.get_table(),
+                data.get_schema().clone(),
+                tx,
+            )?;
+        } else if let Some(path) = data.get_external_path() {
+            // external table のデータはファイル側にあるため、default 値は意味を持たない
+            if !data.get_defaults().is_empty() {
+                return Err(anyhow!(ExecutorError::InvalidCommand(
+                    "default values are not supported for external tables".to_string()
+                )));
+            }
+            self.metadata_manager.create_external_table(
+                data.get_table(),
+                data.get_schema().clone(),
+                path,
+                tx,
+            )?;
+        } else {
+            // 問題のある default 値を持つ table を作らないように、table を作成する前に検証する
+            let diagnostics = Validator::new(self.metadata_manager.as_ref(), &self.parser_factory)
+                .validate_defaults(data);
+            if let Some(diagnostic) = diagnostics.into_iter().next() {
+                return Err(anyhow!(ExecutorError::InvalidCommand(diagnostic.message)));
+            }
+            match data.get_partition() {
+                Some(partition) => self.metadata_manager.create_partitioned_table(
+                    data.get_table(),
+                    data.get_schema().clone(),
+                    data.get_storage(),
+                    partition.clone(),
+                    tx,
+                )?,
+                None => self.metadata_manager.create_table_with_storage(
+                    data.get_table(),
+                    data.get_schema().clone(),
+                    data.get_storage(),
+                    tx,
+                )?,
+            }
+            let mut default_expressions = self.lock_default_expressions()?;
+            for (field, expression) in data.get_defaults() {
+                let text = expression.to_string();
+                self.metadata_manager
+                    .set_default_expression(data.get_table(), field, &text, tx)?;
+                default_expressions.insert(text, expression.clone());
+            }
+        }
         Ok(0)
     }
     fn exec_create_view(
@@ -156,4 +967,215 @@ impl Executor {
             .create_view(data.view_name(), &data.view_def().to_string(), tx)?;
         Ok(0)
     }
+    fn exec_create_index(
+        &self,
+        data: &CreateIndexData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<u64> {
+        let table_name = self
+            .metadata_manager
+            .resolve_table_name(data.table_name(), tx);
+        let index_info = IndexInfo::new(
+            data.index_name(),
+            &table_name,
+            data.field_name(),
+            data.is_unique(),
+        )
+        .with_index_type(data.index_type())
+        .with_included_fields(data.included_fields().to_vec());
+        // index のファイルを作る前に、create_index と同じ確認をしておく
+        validate_identifier(IdentifierKind::Index, data.index_name())?;
+        if self
+            .metadata_manager
+            .get_index(data.index_name(), tx)?
+            .is_some()
+        {
+            return Err(anyhow!(ExecutorError::InvalidCommand(format!(
+                "index {} already exists",
+                data.index_name()
+            ))));
+        }
+
+        // table にすでにある record を index に登録してから、idxcat に index を登録する
+        // idxcat の xlock は transaction が終わるまで他の transaction の query の planning を止めるので、
+        // table を読んでいる間は idxcat の slock だけを持つ。table の block も slock だけを取るので、その table を読む query も止めない
+        let budget = self.metadata_manager.new_query_budget();
+        let plan = TablePlan::new(
+            table_name,
+            self.metadata_manager.as_ref(),
+            &budget,
+            tx.clone(),
+        )?;
+        if plan.layout().partition().is_some() {
+            return Err(anyhow!(ExecutorError::InvalidCommand(format!(
+                "index on partitioned table {} is not supported",
+                data.table_name()
+            ))));
+        }
+        let fields: Vec<&str> = std::iter::once(data.field_name())
+            .chain(data.included_fields().iter().map(String::as_str))
+            .collect();
+        for (i, field) in fields.iter().enumerate() {
+            if !plan.layout().schema().has_field(field) {
+                return Err(anyhow!(ExecutorError::InvalidCommand(format!(
+                    "field {} not found in table {}",
+                    field,
+                    data.table_name()
+                ))));
+            }
+            if fields[..i].contains(field) {
+                return Err(anyhow!(ExecutorError::InvalidCommand(format!(
+                    "field {} is included in index {} more than once",
+                    field,
+                    data.index_name()
+                ))));
+            }
+        }
+        let mut progress = IndexBuildProgress {
+            index_name: data.index_name().to_string(),
+            ..IndexBuildProgress::default()
+        };
+        let mut entries = vec![];
+        {
+            let mut scan = plan.open_update_scan()?;
+            scan.before_first()?;
+            while scan.move_next()? {
+                let entry = Self::index_entry(&index_info, scan.as_ref(), scan.get_rid()?)?;
+                let size = std::iter::once(entry.data_val())
+                    .chain(entry.included_vals())
+                    .map(MemoryBudget::constant_size)
+                    .sum::<usize>();
+                budget.reserve(size + size_of::<Rid>())?;
+                entries.push(entry);
+                progress.records_scanned += 1;
+                self.report_index_build(&progress);
+            }
+        }
+        // 値の順に並べて、B-Tree は leaf と directory を詰めながらまとめて作る
+        entries.sort_by(|lhs, rhs| lhs.data_val().cmp(rhs.data_val()));
+        // すでに値の重複している table には unique な index を作れない
+        if index_info.is_unique() {
+            if let Some(pair) = entries
+                .windows(2)
+                .find(|pair| pair[0].data_val() == pair[1].data_val())
+            {
+                return Err(anyhow!(ExecutorError::UniqueViolation(
+                    index_info.index_name().to_string(),
+                    pair[0].data_val().clone()
+                )));
+            }
+        }
+        index_info
+            .open(tx, plan.layout().schema())?
+            .bulk_load(&entries)?;
+        progress.entries_loaded = entries.len() as u64;
+        self.report_index_build(&progress);
+
+        self.metadata_manager.create_index(&index_info, tx)?;
+        Ok(0)
+    }
+    fn report_index_build(&self, progress: &IndexBuildProgress) {
+        if let Some(callback) = &self.index_build_progress {
+            callback(progress);
+        }
+    }
+    fn exec_rename_column(
+        &self,
+        data: &RenameColumnData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<u64> {
+        let table_name = self
+            .metadata_manager
+            .resolve_table_name(data.get_table(), tx);
+        // 変更した field を参照している view の定義を書き換える
+        // 他の table の field の参照を書き換えないように、from 句の table の schema は変更前のものを使う
+        let mut renamed_views = Vec::new();
+        for (view_name, view_def) in self.metadata_manager.get_view_defs(tx)? {
+            let mut parser = self.parser_factory.create(view_def)?;
+            let query_data = parser.parse_query()?;
+            let mut tables_with_field = HashSet::new();
+            for table in query_data.get_tables() {
+                let schema = Validator::new(self.metadata_manager.as_ref(), &self.parser_factory)
+                    .source_schema(table, tx)?;
+                if schema.is_some_and(|schema| schema.has_field(data.get_old_field())) {
+                    tables_with_field.insert(table.clone());
+                }
+            }
+            if let Some(renamed) = query_data.rename_field(
+                data.get_table(),
+                data.get_old_field(),
+                data.get_new_field(),
+                |table| tables_with_field.contains(table),
+            ) {
+                renamed_views.push((view_name, renamed.to_string()));
+            }
+        }
+        self.metadata_manager.rename_field(
+            &table_name,
+            data.get_old_field(),
+            data.get_new_field(),
+            tx,
+        )?;
+        for (view_name, view_def) in renamed_views {
+            self.metadata_manager
+                .update_view_def(&view_name, &view_def, tx)?;
+        }
+        // 変更した field を参照している default 値の式を書き換える
+        for (field_name, text) in self
+            .metadata_manager
+            .get_default_expressions(&table_name, tx)?
+        {
+            let expression = self.default_expression(&text)?;
+            let renamed = expression
+                .rename_field(data.get_old_field(), data.get_new_field())
+                .to_string();
+            if renamed != expression.to_string() {
+                self.metadata_manager.update_default_expression(
+                    &table_name,
+                    &field_name,
+                    &renamed,
+                    tx,
+                )?;
+            }
+        }
+        Ok(0)
+    }
+    fn exec_rename_table(
+        &self,
+        data: &RenameTableData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<u64> {
+        // view と同じ名前にすると、query がどちらを読むのか分からなくなる
+        if self
+            .metadata_manager
+            .get_view_def(data.get_new_table(), tx)
+            .is_ok()
+        {
+            return Err(anyhow!(ExecutorError::InvalidCommand(format!(
+                "view {} already exists",
+                data.get_new_table()
+            ))));
+        }
+        self.metadata_manager
+            .rename_table(data.get_table(), data.get_new_table(), tx)?;
+        // 変更した table を参照している view の定義を書き換える
+        for (view_name, view_def) in self.metadata_manager.get_view_defs(tx)? {
+            let mut parser = self.parser_factory.create(view_def)?;
+            let query_data = parser.parse_query()?;
+            if let Some(renamed) = query_data.rename_table(data.get_table(), data.get_new_table()) {
+                self.metadata_manager
+                    .update_view_def(&view_name, &renamed.to_string(), tx)?;
+            }
+        }
+        Ok(0)
+    }
+    fn exec_rename_index(
+        &self,
+        data: &RenameIndexData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<u64> {
+        self.metadata_manager
+            .rename_index(data.get_index(), data.get_new_index(), tx)?;
+        Ok(0)
+    }
 }
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+/**
+ * Executor::exec_with_retry で、再試行可能な error が起きた時にどのように再試行するかの設定
+ *
+ * 再試行の間隔は initial_backoff から始めて、再試行のたびに 2 倍にする (max_backoff が上限)
+ */
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// 最初の実行に加えて、最大で何回再試行するか
+    pub max_retries: usize,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(1000),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// attempt 回目 (0 始まり) の再試行の前に待つ時間を返す
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        let factor = 1u32.checked_shl(attempt as u32).unwrap_or(u32::MAX);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+#[cfg(test)]
+mod retry_policy_test {
+    use std::time::Duration;
+
+    use super::RetryPolicy;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(50),
+        };
+        assert_eq!(policy.backoff(0), Duration::from_millis(10));
+        assert_eq!(policy.backoff(1), Duration::from_millis(20));
+        assert_eq!(policy.backoff(2), Duration::from_millis(40));
+        assert_eq!(policy.backoff(3), Duration::from_millis(50));
+        assert_eq!(policy.backoff(100), Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,208 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+use thiserror::Error;
+
+use crate::{
+    error::SimpleDbResult,
+    parse::{
+        content::cursor_data::{DeclareCursorData, FetchData},
+        parser::CursorCommand,
+        parser_factory::ParserFactory,
+    },
+    planner::row_policy::RowPolicy,
+    query::{constant::Constant, scan::ReadScan},
+    server::kv_table::KvTable,
+    tx::transaction::Transaction,
+};
+
+use super::executor::{Executor, QueryResult};
+
+/**
+ * client ごとの状態 (server-side cursor) を保持するクラス
+ *
+ * cursor は declare された時点で plan の scan を open し、close されるまでその scan を保持し続ける
+ * そのため、巨大な結果を fetch で少しずつ取得することができる
+ * cursor の scan は declare した transaction に紐づいているので、transaction を commit / rollback する前に close_all を呼ぶ必要がある
+ *
+ * session には table ごとの row policy を設定でき、この session を通して実行する文は全て row policy で制限される
+ */
+pub struct Session<'a> {
+    executor: &'a Executor,
+    parser_factory: ParserFactory,
+    // cursor 名 -> cursor
+    cursors: HashMap<String, Cursor>,
+    row_policy: RowPolicy,
+}
+
+struct Cursor {
+    fields: Vec<String>,
+    scan: Box<dyn ReadScan>,
+    // scan を最後まで読み切ったかどうか
+    is_exhausted: bool,
+}
+
+/// cursor command の実行結果
+#[derive(Debug, PartialEq)]
+pub enum CursorResult {
+    Declared,
+    /// fetch で取得した record。rows の長さが fetch で指定した数より小さい場合、cursor は最後まで読み切られている
+    Fetched {
+        fields: Vec<String>,
+        rows: Vec<Vec<Constant>>,
+    },
+    Closed,
+}
+
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error("cursor {0} already exists")]
+    CursorAlreadyExists(String),
+    #[error("cursor {0} not found")]
+    CursorNotFound(String),
+}
+
+impl<'a> Session<'a> {
+    pub fn new(executor: &'a Executor, parser_factory: ParserFactory) -> Self {
+        Self {
+            executor,
+            parser_factory,
+            cursors: HashMap::new(),
+            row_policy: RowPolicy::new(),
+        }
+    }
+
+    /// table に row policy を設定する。predicate は where 句と同じ形式で書く (例: "tenant = 1")
+    /// 以降この session で実行する文は、predicate を満たす record だけを読み書きできる
+    pub fn set_row_policy(&mut self, table_name: &str, predicate: &str) -> AnyhowResult<()> {
+        let predicate = self
+            .parser_factory
+            .create(predicate.to_string())?
+            .parse_predicate()?;
+        self.row_policy.set_predicate(table_name, predicate);
+        Ok(())
+    }
+
+    /// table に設定した row policy を削除する
+    pub fn remove_row_policy(&mut self, table_name: &str) {
+        self.row_policy.remove_predicate(table_name);
+    }
+
+    /// この session の row policy で読み書きできる record に制限した KvTable を返す
+    pub fn table(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<KvTable<'a>> {
+        Ok(KvTable::open(
+            table_name,
+            self.executor,
+            self.row_policy.clone(),
+            tx,
+        )?)
+    }
+
+    /// select クエリを row policy を適用して実行し、その scan を返す
+    pub fn exec_query(
+        &self,
+        cmd: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<Box<dyn ReadScan>> {
+        let query_data = self
+            .parser_factory
+            .create(cmd.to_string())
+            .and_then(|mut parser| parser.parse_query())?;
+        self.executor
+            .exec_query_data_with_policy(&query_data, &self.row_policy, tx)
+    }
+
+    /// create, update, delete などのクエリを row policy を適用して実行する
+    pub fn exec_update_command(
+        &self,
+        cmd: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<u64> {
+        self.executor
+            .exec_update_command_with_policy(cmd, &self.row_policy, tx)
+    }
+
+    /// 任意の文を row policy を適用して実行し、文の種類に応じた結果を返す
+    pub fn execute(&self, cmd: &str, tx: &Rc<RefCell<Transaction>>) -> SimpleDbResult<QueryResult> {
+        self.executor.execute_with_policy(cmd, &self.row_policy, tx)
+    }
+
+    /// declare cursor, fetch, close のいずれかの文を実行する
+    pub fn exec_cursor_command(
+        &mut self,
+        cmd: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<CursorResult> {
+        let mut parser = self.parser_factory.create(cmd.to_string())?;
+        match parser.parse_cursor_command()? {
+            CursorCommand::Declare(data) => self.declare(&data, tx),
+            CursorCommand::Fetch(data) => self.fetch(&data),
+            CursorCommand::Close(cursor) => self.close(&cursor),
+        }
+    }
+
+    /// 開いている cursor を全て閉じる
+    pub fn close_all(&mut self) {
+        self.cursors.clear();
+    }
+
+    fn declare(
+        &mut self,
+        data: &DeclareCursorData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<CursorResult> {
+        if self.cursors.contains_key(data.get_cursor()) {
+            return Err(anyhow!(SessionError::CursorAlreadyExists(
+                data.get_cursor().clone()
+            )));
+        }
+        let scan =
+            self.executor
+                .exec_query_data_with_policy(data.get_query(), &self.row_policy, tx)?;
+        self.cursors.insert(
+            data.get_cursor().clone(),
+            Cursor {
+                fields: data.get_query().get_fields().clone(),
+                scan,
+                is_exhausted: false,
+            },
+        );
+        Ok(CursorResult::Declared)
+    }
+
+    fn fetch(&mut self, data: &FetchData) -> AnyhowResult<CursorResult> {
+        let cursor = self
+            .cursors
+            .get_mut(data.get_cursor())
+            .ok_or_else(|| SessionError::CursorNotFound(data.get_cursor().clone()))?;
+        let mut rows = vec![];
+        // 一度読み切った cursor に対しては scan を進めない
+        while !cursor.is_exhausted && rows.len() < data.get_count() {
+            if !cursor.scan.move_next()? {
+                cursor.is_exhausted = true;
+                break;
+            }
+            let row = cursor
+                .fields
+                .iter()
+                .map(|field| cursor.scan.get_val(field))
+                .collect::<Result<Vec<_>, _>>()?;
+            rows.push(row);
+        }
+        Ok(CursorResult::Fetched {
+            fields: cursor.fields.clone(),
+            rows,
+        })
+    }
+
+    fn close(&mut self, cursor: &str) -> AnyhowResult<CursorResult> {
+        self.cursors
+            .remove(cursor)
+            .ok_or_else(|| SessionError::CursorNotFound(cursor.to_string()))?;
+        Ok(CursorResult::Closed)
+    }
+}
@@ -0,0 +1,87 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::anyhow;
+
+use crate::{
+    error::SimpleDbResult,
+    tx::transaction::{Savepoint, Transaction},
+};
+
+/**
+ * 入れ子にできる transaction の範囲
+ *
+ * Executor::begin_scope で作成する。親の transaction が無い場合は新しい transaction を開始し、
+ * commit / rollback でその transaction を commit / rollback する
+ * 親の transaction の中で作成した場合は開始時点の savepoint を作り、rollback ではその savepoint まで戻す
+ * この場合の commit は何もせず、変更が確定するかどうかは親の transaction の commit / rollback で決まる
+ *
+ * commit も rollback もせずに drop した場合は rollback する
+ */
+pub struct TransactionScope {
+    tx: Rc<RefCell<Transaction>>,
+    // 親の transaction の中で作成した場合、作成した時点の savepoint
+    savepoint: Option<Savepoint>,
+    // commit または rollback を済ませたかどうか
+    is_finished: bool,
+}
+
+impl TransactionScope {
+    /// 新しく開始した transaction の scope を作成する
+    pub(crate) fn new_root(tx: Rc<RefCell<Transaction>>) -> Self {
+        Self {
+            tx,
+            savepoint: None,
+            is_finished: false,
+        }
+    }
+
+    /// 親の transaction の中の scope を作成する
+    pub(crate) fn new_nested(parent: &Rc<RefCell<Transaction>>) -> SimpleDbResult<Self> {
+        let savepoint = parent.borrow().savepoint().map_err(|err| anyhow!(err))?;
+        Ok(Self {
+            tx: parent.clone(),
+            savepoint: Some(savepoint),
+            is_finished: false,
+        })
+    }
+
+    /// この scope の中で文を実行するための transaction を返す
+    /// 入れ子の scope の場合は親と同じ transaction を返す
+    pub fn tx(&self) -> &Rc<RefCell<Transaction>> {
+        &self.tx
+    }
+
+    /// 親の transaction の中で作成した scope かどうか
+    pub fn is_nested(&self) -> bool {
+        self.savepoint.is_some()
+    }
+
+    pub fn commit(mut self) -> SimpleDbResult<()> {
+        self.is_finished = true;
+        match self.savepoint {
+            Some(_) => Ok(()),
+            None => self.tx.borrow_mut().commit(),
+        }
+    }
+
+    pub fn rollback(mut self) -> SimpleDbResult<()> {
+        self.is_finished = true;
+        self.rollback_internal()
+    }
+
+    fn rollback_internal(&self) -> SimpleDbResult<()> {
+        match &self.savepoint {
+            Some(savepoint) => self.tx.borrow().rollback_to_savepoint(savepoint),
+            None => self.tx.borrow_mut().rollback(),
+        }
+    }
+}
+
+impl Drop for TransactionScope {
+    fn drop(&mut self) {
+        if !self.is_finished {
+            // drop の中では error を返せないので、rollback の失敗は無視する
+            let _ = self.rollback_internal();
+        }
+    }
+}
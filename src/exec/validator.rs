@@ -0,0 +1,745 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::Result as AnyhowResult;
+
+use crate::{
+    error::{ErrorCategory, SimpleDbError},
+    metadata::{constants::MAX_DEFAULT_EXPR_LENGTH, metadata_manager::MetadataManager},
+    parse::{
+        content::{
+            create_index_data::CreateIndexData, create_table_data::CreateTableData,
+            create_view_data::CreateViewData, insert_data::InsertData, query_data::QueryData,
+            rename_column_data::RenameColumnData, rename_index_data::RenameIndexData,
+            rename_table_data::RenameTableData, update_data::UpdateData,
+        },
+        parser::UpdateCommand,
+        parser_factory::ParserFactory,
+    },
+    plan::{aggregation::AggregationKind, expression::Expression, predicate::ProductPredicate},
+    query::{coercion, constant::Constant},
+    record::schema::{FieldType, Schema},
+    tx::transaction::Transaction,
+};
+
+/// 文の検証で見つかった問題
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 実行した場合に返される error の分類
+    pub category: ErrorCategory,
+    pub message: String,
+}
+
+impl Diagnostic {
+    fn parse(message: String) -> Diagnostic {
+        Diagnostic {
+            category: ErrorCategory::Parse,
+            message,
+        }
+    }
+
+    fn plan(message: String) -> Diagnostic {
+        Diagnostic {
+            category: ErrorCategory::Plan,
+            message,
+        }
+    }
+
+    fn constraint(message: String) -> Diagnostic {
+        Diagnostic {
+            category: ErrorCategory::Constraint,
+            message,
+        }
+    }
+}
+
+/**
+ * 文を実行せずに、構文・名前の解決・型の検査を行うクラス
+ *
+ * 見つかった問題は error ではなく Diagnostic として集めて返す
+ * catalog を読むこと以外には disk へのアクセスを行わず、table の内容も変更しない
+ */
+pub(crate) struct Validator<'a> {
+    metadata_manager: &'a dyn MetadataManager,
+    parser_factory: &'a ParserFactory,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(
+        metadata_manager: &'a dyn MetadataManager,
+        parser_factory: &'a ParserFactory,
+    ) -> Validator<'a> {
+        Validator {
+            metadata_manager,
+            parser_factory,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// cmd を検証し、見つかった問題を返す
+    /// catalog の読み込みに失敗した場合など、文の誤りではない error のみ Err として返す
+    pub fn validate(
+        mut self,
+        cmd: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Vec<Diagnostic>> {
+        let is_query = cmd
+            .trim_start()
+            .get(..6)
+            .is_some_and(|word| word.eq_ignore_ascii_case("select"));
+        if is_query {
+            match self
+                .parser_factory
+                .create(cmd.to_string())
+                .and_then(|mut parser| parser.parse_query())
+            {
+                Ok(data) => {
+                    self.query_schema(&data, tx)?;
+                }
+                Err(err) => self.diagnostics.push(Diagnostic::parse(err.to_string())),
+            }
+        } else {
+            match self
+                .parser_factory
+                .create(cmd.to_string())
+                .and_then(|mut parser| parser.parse_update_command())
+            {
+                Ok(command) => self.validate_update_command(&command, tx)?,
+                Err(err) => self.diagnostics.push(Diagnostic::parse(err.to_string())),
+            }
+        }
+        Ok(self.diagnostics)
+    }
+
+    /// create table の default 値の式を検証し、見つかった問題を返す
+    /// table を作成する前に executor から呼び出し、問題がある default 値を catalog に保存しないようにする
+    pub fn validate_defaults(mut self, data: &CreateTableData) -> Vec<Diagnostic> {
+        self.check_defaults(data);
+        self.diagnostics
+    }
+
+    fn validate_update_command(
+        &mut self,
+        command: &UpdateCommand,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        match command {
+            UpdateCommand::Insert(data) => self.validate_insert(data, tx),
+            UpdateCommand::Delete(data) => {
+                if let Some(schema) = self.writable_table_schema(data.get_table(), tx)? {
+                    self.check_predicate(data.get_predicate(), &schema);
+                }
+                Ok(())
+            }
+            UpdateCommand::Update(data) => self.validate_update(data, tx),
+            UpdateCommand::CreateTable(data) => self.validate_create_table(data, tx),
+            UpdateCommand::CreateView(data) => self.validate_create_view(data, tx),
+            UpdateCommand::CreateIndex(data) => self.validate_create_index(data, tx),
+            UpdateCommand::RenameColumn(data) => self.validate_rename_column(data, tx),
+            UpdateCommand::RenameTable(data) => self.validate_rename_table(data, tx),
+            UpdateCommand::RenameIndex(data) => self.validate_rename_index(data, tx),
+        }
+    }
+
+    fn validate_insert(
+        &mut self,
+        data: &InsertData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        let Some(schema) = self.writable_table_schema(data.get_table(), tx)? else {
+            return Ok(());
+        };
+        let fields = if data.get_fields().is_empty() {
+            schema.fields()
+        } else {
+            data.get_fields().clone()
+        };
+        if fields.len() != data.get_values().len() {
+            self.diagnostics.push(Diagnostic::plan(format!(
+                "the number of values ({}) does not match the number of fields ({}) for table {}",
+                data.get_values().len(),
+                fields.len(),
+                data.get_table()
+            )));
+        }
+        for (field, val) in fields.iter().zip(data.get_values().iter()) {
+            if let Some(field_type) = self.field_type(field, &schema) {
+                self.check_type(field, field_type, val);
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_update(
+        &mut self,
+        data: &UpdateData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        let Some(schema) = self.writable_table_schema(data.get_table(), tx)? else {
+            return Ok(());
+        };
+        let field_type = self.field_type(data.get_field(), &schema);
+        let value_type = self.expression_type(data.get_new_value(), &schema);
+        if let (Some(field_type), Some(value_type)) = (field_type, value_type) {
+            if !coercion::can_assign(value_type, field_type) {
+                self.diagnostics.push(Diagnostic::plan(format!(
+                    "cannot assign {} to field {}",
+                    data.get_new_value(),
+                    data.get_field()
+                )));
+            }
+        }
+        self.check_predicate(data.get_predicate(), &schema);
+        Ok(())
+    }
+
+    fn validate_create_table(
+        &mut self,
+        data: &CreateTableData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        if !data.if_not_exists() && self.lookup_table_schema(data.get_table(), tx)?.is_some() {
+            self.diagnostics.push(Diagnostic::plan(format!(
+                "table {} already exists",
+                data.get_table()
+            )));
+        }
+        self.check_defaults(data);
+        if let Some(partition) = data.get_partition() {
+            if !data.get_schema().has_field(partition.field()) {
+                self.diagnostics.push(Diagnostic::plan(format!(
+                    "partition field {} not found in table {}",
+                    partition.field(),
+                    data.get_table()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// default 値の式が field に代入できる型になるかを確かめる
+    /// 式は文字列にして catalog に保存するので、保存できる長さであることと、その文字列から同じ式に戻せることも確かめる
+    fn check_defaults(&mut self, data: &CreateTableData) {
+        for (field, expression) in data.get_defaults() {
+            let field_type = self.field_type(field, data.get_schema());
+            let value_type = self.expression_type(expression, data.get_schema());
+            if let (Some(field_type), Some(value_type)) = (field_type, value_type) {
+                if !coercion::can_assign(value_type, field_type) {
+                    self.diagnostics.push(Diagnostic::plan(format!(
+                        "default value {} does not match the type of field {}",
+                        expression, field
+                    )));
+                }
+            }
+            let text = expression.to_string();
+            if text.len() > MAX_DEFAULT_EXPR_LENGTH {
+                self.diagnostics.push(Diagnostic::plan(format!(
+                    "default value {} of field {} is too long (max {} bytes)",
+                    expression, field, MAX_DEFAULT_EXPR_LENGTH
+                )));
+                continue;
+            }
+            let reparsed = self
+                .parser_factory
+                .create(text)
+                .and_then(|mut parser| parser.parse_expression());
+            if !matches!(reparsed, Ok(reparsed) if reparsed == *expression) {
+                self.diagnostics.push(Diagnostic::plan(format!(
+                    "default value {} of field {} cannot be stored in the catalog",
+                    expression, field
+                )));
+            }
+        }
+    }
+
+    fn validate_create_view(
+        &mut self,
+        data: &CreateViewData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        if self
+            .metadata_manager
+            .get_view_def(data.view_name(), tx)
+            .is_ok()
+        {
+            self.diagnostics.push(Diagnostic::plan(format!(
+                "view {} already exists",
+                data.view_name()
+            )));
+        }
+        self.query_schema(data.view_def(), tx)?;
+        Ok(())
+    }
+
+    fn validate_create_index(
+        &mut self,
+        data: &CreateIndexData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        if self
+            .metadata_manager
+            .get_index(data.index_name(), tx)?
+            .is_some()
+        {
+            self.diagnostics.push(Diagnostic::plan(format!(
+                "index {} already exists",
+                data.index_name()
+            )));
+        }
+        if let Some(schema) = self.table_schema(data.table_name(), tx)? {
+            self.field_type(data.field_name(), &schema);
+        }
+        Ok(())
+    }
+
+    fn validate_rename_column(
+        &mut self,
+        data: &RenameColumnData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        let Some(schema) = self.table_schema(data.get_table(), tx)? else {
+            return Ok(());
+        };
+        self.field_type(data.get_old_field(), &schema);
+        if schema.has_field(data.get_new_field()) {
+            self.diagnostics.push(Diagnostic::plan(format!(
+                "field {} already exists in table {}",
+                data.get_new_field(),
+                data.get_table()
+            )));
+        }
+        Ok(())
+    }
+
+    fn validate_rename_table(
+        &mut self,
+        data: &RenameTableData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        if self.lookup_table_schema(data.get_table(), tx)?.is_none() {
+            self.diagnostics.push(Diagnostic::plan(format!(
+                "table {} not found",
+                data.get_table()
+            )));
+        }
+        if self
+            .lookup_table_schema(data.get_new_table(), tx)?
+            .is_some()
+            || self
+                .metadata_manager
+                .get_view_def(data.get_new_table(), tx)
+                .is_ok()
+        {
+            self.diagnostics.push(Diagnostic::plan(format!(
+                "table {} already exists",
+                data.get_new_table()
+            )));
+        }
+        Ok(())
+    }
+
+    fn validate_rename_index(
+        &mut self,
+        data: &RenameIndexData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        if self
+            .metadata_manager
+            .get_index(data.get_index(), tx)?
+            .is_none()
+        {
+            self.diagnostics.push(Diagnostic::plan(format!(
+                "index {} not found",
+                data.get_index()
+            )));
+        }
+        if self
+            .metadata_manager
+            .get_index(data.get_new_index(), tx)?
+            .is_some()
+        {
+            self.diagnostics.push(Diagnostic::plan(format!(
+                "index {} already exists",
+                data.get_new_index()
+            )));
+        }
+        Ok(())
+    }
+
+    /// query の結果の schema を返す。参照している table が解決できなかった場合は None を返す
+    fn query_schema(
+        &mut self,
+        data: &QueryData,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Option<Schema>> {
+        let mut schema = Schema::new();
+        let mut resolved = true;
+        for table in data.get_tables() {
+            match self.table_schema(table, tx)? {
+                Some(table_schema) => schema.add_all(&table_schema)?,
+                None => resolved = false,
+            }
+        }
+        // table が解決できない場合、その table の field についての問題まで報告すると冗長になる
+        if !resolved {
+            return Ok(None);
+        }
+        self.check_predicate(data.get_predicate(), &schema);
+        // group にまとめる場合、select する値は group by の field と集約関数の値から求める
+        let schema = if data.is_grouped() {
+            self.grouped_schema(data, &schema)?
+        } else {
+            schema
+        };
+        let mut output = Schema::new();
+        for field in data.get_fields() {
+            // 集計できない集約関数の値は、問題を記録した時に schema から除いている
+            if data.get_aggregation(field).is_some() {
+                if schema.has_field(field) {
+                    output.add(field, &schema)?;
+                }
+                continue;
+            }
+            match data.get_expression(field) {
+                Some(expression) => {
+                    if schema.has_field(field) {
+                        self.diagnostics
+                            .push(Diagnostic::plan(format!("field {} already exists", field)));
+                    }
+                    if self.expression_type(expression, &schema).is_some() {
+                        if let Some(info) = expression.field_info(&schema) {
+                            output.add_field(field, info);
+                        }
+                    }
+                }
+                None => {
+                    if self.field_type(field, &schema).is_some() {
+                        output.add(field, &schema)?;
+                    }
+                }
+            }
+        }
+        // 並べ替えは select した値に対して行うので、select していない field では並べ替えられない
+        for (field, _) in data.get_order_by() {
+            if !data.get_fields().contains(field) {
+                self.diagnostics.push(Diagnostic::plan(format!(
+                    "cannot order by {}. the field must appear in the select list",
+                    field
+                )));
+            }
+        }
+        Ok(Some(output))
+    }
+
+    /// group にまとめた後の record の schema を返す。group by の field と、集約関数の値の field を持つ
+    /// 集約関数で集計できない field は問題として記録し、schema に含めない
+    fn grouped_schema(&mut self, data: &QueryData, schema: &Schema) -> AnyhowResult<Schema> {
+        let mut grouped = Schema::new();
+        for field in data.get_group_fields() {
+            if self.field_type(field, schema).is_some() {
+                grouped.add(field, schema)?;
+            }
+        }
+        for (name, aggregation) in data.get_aggregations() {
+            let Some(field_type) = self.field_type(aggregation.field(), schema) else {
+                continue;
+            };
+            if matches!(
+                aggregation.kind(),
+                AggregationKind::Sum | AggregationKind::Avg
+            ) && field_type != FieldType::Integer
+            {
+                self.diagnostics.push(Diagnostic::plan(format!(
+                    "cannot aggregate {}. expected int field",
+                    aggregation
+                )));
+                continue;
+            }
+            if let Some(info) = aggregation.field_info(schema) {
+                grouped.add_field(name, info);
+            }
+        }
+        // group by に無い field は group の中で値が 1 つに決まらない
+        // 同じ field について field not found を重ねて報告しないように、問題を記録した field は schema に含める
+        for field in data.get_fields() {
+            if data.get_aggregation(field).is_some() {
+                continue;
+            }
+            let referenced = match data.get_expression(field) {
+                Some(expression) => expression.fields(),
+                None => vec![field.clone()],
+            };
+            for field in referenced {
+                if schema.has_field(&field) && !grouped.has_field(&field) {
+                    self.diagnostics.push(Diagnostic::plan(format!(
+                        "field {} must appear in group by or be used in an aggregation function",
+                        field
+                    )));
+                    grouped.add(&field, schema)?;
+                }
+            }
+        }
+        Ok(grouped)
+    }
+
+    /// from 句に書ける table (view を含む) の schema を返す。存在しない場合は None を返す
+    pub fn source_schema(
+        mut self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Option<Schema>> {
+        self.table_schema(table_name, tx)
+    }
+
+    /// query の from 句に書かれた table (view を含む) の schema を返す。存在しない場合は問題として記録する
+    fn table_schema(
+        &mut self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Option<Schema>> {
+        if let Ok(view_def) = self.metadata_manager.get_view_def(table_name, tx) {
+            let view_data = self.parser_factory.create(view_def)?.parse_query()?;
+            return self.query_schema(&view_data, tx);
+        }
+        let schema = self.lookup_table_schema(table_name, tx)?;
+        if schema.is_none() {
+            self.diagnostics
+                .push(Diagnostic::plan(format!("table {} not found", table_name)));
+        }
+        Ok(schema)
+    }
+
+    /// 書き込み対象の table の schema を返す。external table の場合は問題として記録する
+    fn writable_table_schema(
+        &mut self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Option<Schema>> {
+        let Some(schema) = self.lookup_table_schema(table_name, tx)? else {
+            self.diagnostics
+                .push(Diagnostic::plan(format!("table {} not found", table_name)));
+            return Ok(None);
+        };
+        if self
+            .metadata_manager
+            .get_external_table_path(table_name, tx)?
+            .is_some()
+        {
+            self.diagnostics.push(Diagnostic::constraint(format!(
+                "table {} is read-only",
+                table_name
+            )));
+        }
+        Ok(Some(schema))
+    }
+
+    /// catalog から table の schema を取得する。table が存在しない場合は None を返す
+    fn lookup_table_schema(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Option<Schema>> {
+        let table_name = self.metadata_manager.resolve_table_name(table_name, tx);
+        match self.metadata_manager.get_layout(&table_name, tx) {
+            Ok(layout) => Ok(Some(layout.schema().clone())),
+            Err(err) => {
+                // 存在しない table の参照は Plan に分類される。それ以外 (io error など) は検証を続けられない
+                let err = SimpleDbError::from(err);
+                if err.category() == ErrorCategory::Plan {
+                    Ok(None)
+                } else {
+                    Err(err.into())
+                }
+            }
+        }
+    }
+
+    fn check_predicate(&mut self, predicate: &ProductPredicate, schema: &Schema) {
+        for term in predicate.terms() {
+            let (lhs, rhs) = term.operands();
+            let lhs_type = self.expression_type(lhs, schema);
+            let rhs_type = self.expression_type(rhs, schema);
+            if let (Some(lhs_type), Some(rhs_type)) = (lhs_type, rhs_type) {
+                if coercion::common_type(lhs_type, rhs_type).is_none() {
+                    self.diagnostics
+                        .push(Diagnostic::plan(format!("cannot compare {}", term)));
+                }
+            }
+        }
+    }
+
+    fn check_type(&mut self, field_name: &str, field_type: FieldType, val: &Constant) {
+        if !coercion::can_assign(coercion::type_of(val), field_type) {
+            self.diagnostics.push(Diagnostic::plan(format!(
+                "value {} does not match the type of field {}",
+                val, field_name
+            )));
+        }
+    }
+
+    fn expression_type(&mut self, expression: &Expression, schema: &Schema) -> Option<FieldType> {
+        match expression {
+            Expression::Constant(constant) => Some(coercion::type_of(constant)),
+            Expression::Field(field_name) => self.field_type(field_name, schema),
+            Expression::Case(case) => {
+                let mut types = vec![];
+                for (predicate, expression) in case.branches() {
+                    self.check_predicate(predicate, schema);
+                    types.push(self.expression_type(expression, schema));
+                }
+                types.push(self.expression_type(case.otherwise(), schema));
+                let types = types.into_iter().collect::<Option<Vec<_>>>()?;
+                // case 式の値は、すべての結果を変換できる共通の型になる
+                let result_type = types
+                    .iter()
+                    .skip(1)
+                    .try_fold(types[0], |lhs, rhs| coercion::common_type(lhs, *rhs));
+                if result_type.is_none() {
+                    self.diagnostics.push(Diagnostic::plan(format!(
+                        "the results of {} have different types",
+                        case
+                    )));
+                }
+                result_type
+            }
+            Expression::Predicate(predicate) => {
+                self.check_predicate(predicate, schema);
+                Some(FieldType::Boolean)
+            }
+        }
+    }
+
+    /// field の型を返す。schema に存在しない場合は問題として記録する
+    fn field_type(&mut self, field_name: &str, schema: &Schema) -> Option<FieldType> {
+        let field_type = schema.info(field_name).map(|info| info.get_type());
+        if field_type.is_none() {
+            self.diagnostics
+                .push(Diagnostic::plan(format!("field {} not found", field_name)));
+        }
+        field_type
+    }
+}
+
+#[cfg(test)]
+mod validator_test {
+    use tempfile::tempdir;
+
+    use crate::{error::ErrorCategory, server::simpledb::SimpleDB};
+
+    #[test]
+    fn test_validate() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        executor
+            .exec_update_command("create table student (sid int, sname varchar(10))", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("create table dept (did int, dname varchar(10))", &tx)
+            .unwrap();
+
+        let messages = |cmd: &str| -> Vec<(ErrorCategory, String)> {
+            executor
+                .validate(cmd, &tx)
+                .unwrap()
+                .into_iter()
+                .map(|diagnostic| (diagnostic.category, diagnostic.message))
+                .collect()
+        };
+        assert!(messages("select sid, sname from student where sid = 1").is_empty());
+        assert!(messages("select sname, dname from student, dept where sid = did").is_empty());
+        assert!(messages("insert into student (sid, sname) values (1, 'joe')").is_empty());
+        assert_eq!(
+            messages("select did from student"),
+            vec![(ErrorCategory::Plan, "field did not found".to_string())]
+        );
+        assert_eq!(
+            messages("select sid from unknown"),
+            vec![(ErrorCategory::Plan, "table unknown not found".to_string())]
+        );
+        assert_eq!(
+            messages("select sid from student where sname = 1"),
+            vec![(ErrorCategory::Plan, "cannot compare sname = 1".to_string())]
+        );
+        assert_eq!(
+            messages("select sid from student where sid >= 'x'"),
+            vec![(ErrorCategory::Plan, "cannot compare sid >= 'x'".to_string())]
+        );
+        assert_eq!(
+            messages("insert into student (sid, sname) values ('1', 'joe')"),
+            vec![(
+                ErrorCategory::Plan,
+                "value '1' does not match the type of field sid".to_string()
+            )]
+        );
+        assert_eq!(
+            messages("create table student (a int)"),
+            vec![(
+                ErrorCategory::Plan,
+                "table student already exists".to_string()
+            )]
+        );
+        assert!(
+            messages("update student set sname = case when sid = 1 then 'one' else sname end")
+                .is_empty()
+        );
+        assert_eq!(
+            messages("update student set sname = case when sid = 1 then 1 else 'x' end"),
+            vec![(
+                ErrorCategory::Plan,
+                "the results of case when sid = 1 then 1 else 'x' end have different types"
+                    .to_string()
+            )]
+        );
+        assert_eq!(
+            messages("update student set sid = case when sname = 1 then 1 else 2 end"),
+            vec![(ErrorCategory::Plan, "cannot compare sname = 1".to_string())]
+        );
+        assert!(messages("select sid = 1 as is_first, true from student").is_empty());
+        assert_eq!(
+            messages("select sid = 1 as sname from student"),
+            vec![(
+                ErrorCategory::Plan,
+                "field sname already exists".to_string()
+            )]
+        );
+        assert_eq!(messages("select from student")[0].0, ErrorCategory::Parse);
+        assert!(
+            messages("select sname, count(sid), max(sid) as m from student group by sname")
+                .is_empty()
+        );
+        assert_eq!(
+            messages("select sid, count(sname) from student"),
+            vec![(
+                ErrorCategory::Plan,
+                "field sid must appear in group by or be used in an aggregation function"
+                    .to_string()
+            )]
+        );
+        assert_eq!(
+            messages("select sum(sname) from student"),
+            vec![(
+                ErrorCategory::Plan,
+                "cannot aggregate sum(sname). expected int field".to_string()
+            )]
+        );
+        assert!(messages("select sname, sid from student order by sid desc, sname").is_empty());
+        assert_eq!(
+            messages("select sname from student order by sid"),
+            vec![(
+                ErrorCategory::Plan,
+                "cannot order by sid. the field must appear in the select list".to_string()
+            )]
+        );
+
+        // 検証では文は実行されない
+        executor
+            .validate("insert into student (sid, sname) values (1, 'joe')", &tx)
+            .unwrap();
+        let mut scan = executor.exec_query("select sid from student", &tx).unwrap();
+        assert!(!scan.move_next().unwrap());
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+}
@@ -1,3 +1,5 @@
 pub mod blockid;
+pub mod compression;
+pub mod encryption;
 pub mod file_manager;
 pub mod page;
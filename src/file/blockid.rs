@@ -3,22 +3,19 @@ use std::fmt::{Display, Formatter};
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct BlockId {
     filename: String,
-    blknum: BlockNumber,
+    blknum: usize,
 }
 
-#[derive(Clone, Debug, Eq, Hash, PartialEq)]
-enum BlockNumber {
-    Number(usize),
-    EndOfFile,
-}
+// file の長さを守る lock のためだけに使う、実在しない block の番号
+const END_OF_FILE: usize = usize::MAX;
 
 impl Display for BlockId {
     fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
-        let blknum = match self.blknum {
-            BlockNumber::Number(n) => n.to_string(),
-            BlockNumber::EndOfFile => "end of file".to_string(),
-        };
-        write!(f, "[file {}, block {}]", self.filename, blknum)
+        if self.is_end_of_file() {
+            write!(f, "[file {}, end of file]", self.filename)
+        } else {
+            write!(f, "[file {}, block {}]", self.filename, self.blknum)
+        }
     }
 }
 
@@ -26,15 +23,19 @@ impl BlockId {
     pub fn new(filename: &str, blknum: usize) -> BlockId {
         BlockId {
             filename: filename.to_string(),
-            blknum: BlockNumber::Number(blknum),
+            blknum,
         }
     }
 
+    /// file の末尾を表す block を作る
+    /// 末尾まで読んだ scan と append を lock で排他するためだけに使い、読み書きはできない
     pub fn new_end_of_file(filename: &str) -> BlockId {
-        BlockId {
-            filename: filename.to_string(),
-            blknum: BlockNumber::EndOfFile,
-        }
+        BlockId::new(filename, END_OF_FILE)
+    }
+
+    /// file の長さを表す、lock のためだけに使う block かどうか
+    pub fn is_end_of_file(&self) -> bool {
+        self.blknum == END_OF_FILE
     }
 
     pub fn file_name(&self) -> &str {
@@ -42,12 +43,6 @@ impl BlockId {
     }
 
     pub fn number(&self) -> usize {
-        match self.blknum {
-            BlockNumber::Number(n) => n,
-            BlockNumber::EndOfFile => {
-                eprintln!("Warning: BlockId's number method is not expected to be called on end of file block. return 0");
-                0
-            }
-        }
+        self.blknum
     }
 }
@@ -0,0 +1,137 @@
+use std::io::{self, Read};
+
+use thiserror::Error;
+
+/**
+ * page を圧縮して block に保存するための関数群
+ *
+ * 圧縮されたファイルでは、1 block は header (4 bytes) と payload からなる
+ * header の値によって payload の形式が決まる:
+ * - 0: まだ書き込まれていない block。page は 0 埋めとして扱う
+ * - RAW_PAYLOAD: 圧縮すると逆に大きくなってしまったため、page をそのまま保存している
+ * - 正の値: LZ4 で圧縮した payload のバイト数
+ *
+ * 圧縮後の payload だけを読み書きするので、文字列の多い table では I/O の量を減らすことができる
+ * 実際の圧縮処理は compression feature が有効な場合にのみ行われる
+ */
+pub(crate) const COMPRESSION_HEADER_LEN: usize = 4;
+const RAW_PAYLOAD: i32 = -1;
+
+#[derive(Error, Debug)]
+pub enum CompressionError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("corrupted block: {0}")]
+    Corrupted(String),
+}
+
+/// page の内容を header 付きの block の形式に変換する
+pub(crate) fn encode(contents: &[u8]) -> Vec<u8> {
+    #[cfg(feature = "compression")]
+    {
+        let compressed = lz4_flex::block::compress(contents);
+        if compressed.len() < contents.len() {
+            let mut bytes = Vec::with_capacity(COMPRESSION_HEADER_LEN + compressed.len());
+            bytes.extend_from_slice(&(compressed.len() as i32).to_be_bytes());
+            bytes.extend_from_slice(&compressed);
+            return bytes;
+        }
+    }
+    let mut bytes = Vec::with_capacity(COMPRESSION_HEADER_LEN + contents.len());
+    bytes.extend_from_slice(&RAW_PAYLOAD.to_be_bytes());
+    bytes.extend_from_slice(contents);
+    bytes
+}
+
+/// reader の現在位置から header 付きの block を読み、page の内容を復元する
+pub(crate) fn read_block<R: Read>(
+    reader: &mut R,
+    contents: &mut [u8],
+) -> Result<(), CompressionError> {
+    let mut header = [0u8; COMPRESSION_HEADER_LEN];
+    // ファイルの末尾より後ろは、まだ書き込まれていない block として扱う
+    if read_fully(reader, &mut header)? < COMPRESSION_HEADER_LEN {
+        contents.fill(0);
+        return Ok(());
+    }
+    match i32::from_be_bytes(header) {
+        0 => {
+            contents.fill(0);
+            Ok(())
+        }
+        RAW_PAYLOAD => {
+            reader.read_exact(contents)?;
+            Ok(())
+        }
+        len if len > 0 => {
+            let mut compressed = vec![0u8; len as usize];
+            reader.read_exact(&mut compressed)?;
+            decompress(&compressed, contents)
+        }
+        len => Err(CompressionError::Corrupted(format!(
+            "invalid header value: {}",
+            len
+        ))),
+    }
+}
+
+#[cfg(feature = "compression")]
+fn decompress(compressed: &[u8], contents: &mut [u8]) -> Result<(), CompressionError> {
+    let len = lz4_flex::block::decompress_into(compressed, contents)
+        .map_err(|e| CompressionError::Corrupted(e.to_string()))?;
+    if len != contents.len() {
+        return Err(CompressionError::Corrupted(format!(
+            "decompressed size {} does not match the block size {}",
+            len,
+            contents.len()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "compression"))]
+fn decompress(_compressed: &[u8], _contents: &mut [u8]) -> Result<(), CompressionError> {
+    Err(CompressionError::Corrupted(
+        "compressed block found, but the compression feature is disabled".to_string(),
+    ))
+}
+
+// EOF に達するまで読み、読めたバイト数を返す
+fn read_fully<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize, io::Error> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod compression_test {
+    use std::io::{Seek, SeekFrom, Write};
+
+    use super::*;
+
+    #[test]
+    fn test_encode_and_read_block() {
+        let mut file = tempfile::tempfile().unwrap();
+        let mut contents = vec![0u8; 400];
+        contents[10..20].copy_from_slice(b"aaaaaaaaaa");
+
+        // 0 が多い page は小さく圧縮される
+        let bytes = encode(&contents);
+        assert!(bytes.len() < contents.len());
+        file.write_all(&bytes).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        let mut read = vec![1u8; 400];
+        read_block(&mut file, &mut read).unwrap();
+        assert_eq!(read, contents);
+
+        // ファイルの末尾より後ろは 0 埋め
+        let mut read = vec![1u8; 400];
+        read_block(&mut file, &mut read).unwrap();
+        assert_eq!(read, vec![0u8; 400]);
+    }
+}
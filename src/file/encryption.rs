@@ -0,0 +1,121 @@
+use thiserror::Error;
+
+/**
+ * block を暗号化して保存するためのクラス
+ *
+ * 暗号化された block は nonce (12 bytes), 暗号文, 認証 tag (16 bytes) からなる
+ * block の入れ替えを検出できるように、ファイル名と block 番号を associated data として使う
+ * すべて 0 の block も認証できなければ復号に失敗する。まだ書き込まれていない block かどうかは FileManager がファイルの長さから判断する
+ * 実際の暗号化処理は encryption feature が有効な場合にのみ行われる
+ */
+pub(crate) const NONCE_LEN: usize = 12;
+pub(crate) const TAG_LEN: usize = 16;
+/// 暗号化によって増える 1 block あたりのバイト数
+pub(crate) const ENCRYPTION_OVERHEAD: usize = NONCE_LEN + TAG_LEN;
+pub const ENCRYPTION_KEY_LEN: usize = 32;
+
+pub(crate) struct BlockCipher {
+    #[cfg(feature = "encryption")]
+    cipher: aes_gcm::Aes256Gcm,
+}
+
+#[derive(Error, Debug)]
+pub enum EncryptionError {
+    #[error("encryption is not available. enable the encryption feature")]
+    Unavailable,
+    #[error("failed to encrypt block")]
+    Encrypt,
+    #[error("failed to decrypt block. the key may be wrong or the block may be corrupted")]
+    Decrypt,
+}
+
+#[cfg(feature = "encryption")]
+impl BlockCipher {
+    pub(crate) fn new(key: &[u8; ENCRYPTION_KEY_LEN]) -> Result<BlockCipher, EncryptionError> {
+        use aes_gcm::{Aes256Gcm, Key, KeyInit};
+        Ok(BlockCipher {
+            cipher: Aes256Gcm::new(&Key::<Aes256Gcm>::from(*key)),
+        })
+    }
+
+    /// plaintext を暗号化し、nonce || 暗号文 || tag の形式で返す
+    pub(crate) fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        use aes_gcm::aead::{Aead, Generate, Nonce, Payload};
+        let nonce = Nonce::<aes_gcm::Aes256Gcm>::generate();
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .map_err(|_| EncryptionError::Encrypt)?;
+        let mut bytes = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        bytes.extend_from_slice(&nonce);
+        bytes.extend_from_slice(&ciphertext);
+        Ok(bytes)
+    }
+
+    /// encrypt で作成したバイト列を復号する
+    pub(crate) fn decrypt(&self, bytes: &[u8], aad: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        use aes_gcm::aead::{Aead, Nonce, Payload};
+        if bytes.len() < ENCRYPTION_OVERHEAD {
+            return Err(EncryptionError::Decrypt);
+        }
+        let nonce = Nonce::<aes_gcm::Aes256Gcm>::try_from(&bytes[..NONCE_LEN])
+            .map_err(|_| EncryptionError::Decrypt)?;
+        self.cipher
+            .decrypt(
+                &nonce,
+                Payload {
+                    msg: &bytes[NONCE_LEN..],
+                    aad,
+                },
+            )
+            .map_err(|_| EncryptionError::Decrypt)
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+impl BlockCipher {
+    pub(crate) fn new(_key: &[u8; ENCRYPTION_KEY_LEN]) -> Result<BlockCipher, EncryptionError> {
+        Err(EncryptionError::Unavailable)
+    }
+
+    pub(crate) fn encrypt(
+        &self,
+        _plaintext: &[u8],
+        _aad: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        Err(EncryptionError::Unavailable)
+    }
+
+    pub(crate) fn decrypt(&self, _bytes: &[u8], _aad: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        Err(EncryptionError::Unavailable)
+    }
+}
+
+#[cfg(all(test, feature = "encryption"))]
+mod encryption_test {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let cipher = BlockCipher::new(&[7u8; ENCRYPTION_KEY_LEN]).unwrap();
+        let plaintext = b"secret data".to_vec();
+        let bytes = cipher.encrypt(&plaintext, b"file:0").unwrap();
+        assert_eq!(bytes.len(), plaintext.len() + ENCRYPTION_OVERHEAD);
+        assert!(!bytes.windows(plaintext.len()).any(|w| w == plaintext));
+        assert_eq!(cipher.decrypt(&bytes, b"file:0").unwrap(), plaintext);
+
+        // associated data が違う (別の block に移された) 場合は復号できない
+        assert!(cipher.decrypt(&bytes, b"file:1").is_err());
+        // 鍵が違う場合も復号できない
+        let other = BlockCipher::new(&[8u8; ENCRYPTION_KEY_LEN]).unwrap();
+        assert!(other.decrypt(&bytes, b"file:0").is_err());
+        // すべて 0 に書き換えられた block も復号できない
+        assert!(cipher.decrypt(&[0u8; 40], b"file:0").is_err());
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs,
     io::{self, Read, Seek, Write},
     os::unix::fs::OpenOptionsExt,
@@ -9,19 +9,43 @@ use std::{
 use thiserror::Error;
 
 use super::blockid::BlockId;
+use super::compression::{self, CompressionError, COMPRESSION_HEADER_LEN};
+use super::encryption::{BlockCipher, EncryptionError, ENCRYPTION_KEY_LEN, ENCRYPTION_OVERHEAD};
 use super::page::Page;
 
+// 圧縮してから暗号化した block の先頭に置く、暗号文の長さのバイト数
+const CIPHERTEXT_LEN_BYTES: usize = 4;
+// extent として確保しただけの block がある間、ファイルの長さ (watermark) を保存しておくファイルの suffix
+const WATERMARK_SUFFIX: &str = ".watermark";
+
 /**
  * simpledb では、block の中身は page を通して読み書きされる。
  * その読み書きの直接的な interface を提供するクラス
  *
  * file の中に block を連続して配置することで block を扱っているため、名前を FileManager としている
+ *
+ * block size は基本的に全ファイル共通だが、set_block_size でファイルごとに異なる block size を設定することもできる
+ * (幅の広い table のファイルだけ大きな block を使う、など)
+ * また、set_compressed で指定したファイルは page を圧縮して保存する (compression feature が必要)
+ * with_encryption_key で鍵を指定した場合は、log file を含むすべてのファイルの block を暗号化して保存する (encryption feature が必要)
  */
 pub struct FileManager {
     db_directory: path::PathBuf,
     blocksize: usize,
     is_new: bool,
     open_files: Mutex<HashMap<String, fs::File>>,
+    // ファイル名 -> そのファイルの block size。登録されていないファイルは blocksize を使う
+    file_block_sizes: Mutex<HashMap<String, usize>>,
+    // append でファイルを伸ばす時に、一度に確保する block の数
+    extent_size: usize,
+    // ファイル名 -> 使用中の block 数 (watermark)。extent として確保しただけの block は含まない
+    // 開き直した時に復元できるように、ファイルの物理的な長さより短い間は "{ファイル名}.watermark" に保存する
+    // open_files と同時に lock する場合は、open_files を先に lock する
+    file_lengths: Mutex<HashMap<String, usize>>,
+    // page を圧縮して保存するファイル
+    compressed_files: Mutex<HashSet<String>>,
+    // block を暗号化する場合の暗号器
+    cipher: Option<BlockCipher>,
 }
 
 #[derive(Error, Debug)]
@@ -30,6 +54,12 @@ pub enum FileManagerError {
     LockError,
     #[error("I/O error: {0}")]
     IoError(#[from] io::Error),
+    #[error("compression error: {0}")]
+    Compression(#[from] CompressionError),
+    #[error("compression is not available. enable the compression feature")]
+    CompressionUnavailable,
+    #[error("encryption error: {0}")]
+    Encryption(#[from] EncryptionError),
 }
 
 impl FileManager {
@@ -43,7 +73,7 @@ impl FileManager {
         for file_path in file_paths {
             match file_path {
                 Ok(file) => {
-                    if file.path().starts_with("temp") {
+                    if file.file_name().to_string_lossy().starts_with("temp") {
                         fs::remove_file(file.path()).unwrap_or_else(|err| eprintln!("{err}"));
                     }
                 }
@@ -58,12 +88,35 @@ impl FileManager {
             blocksize,
             is_new,
             open_files: Mutex::new(HashMap::<String, fs::File>::new()),
+            file_block_sizes: Mutex::new(HashMap::new()),
+            extent_size: 1,
+            file_lengths: Mutex::new(HashMap::new()),
+            compressed_files: Mutex::new(HashSet::new()),
+            cipher: None,
         }
     }
 
+    /// すべてのファイルの block を、指定した鍵を使って AES-GCM で暗号化して保存するようにする
+    /// 既存のデータベースに対しては、作成時と同じ鍵を指定する必要がある
+    pub fn with_encryption_key(
+        mut self,
+        key: &[u8; ENCRYPTION_KEY_LEN],
+    ) -> Result<FileManager, FileManagerError> {
+        self.cipher = Some(BlockCipher::new(key)?);
+        Ok(self)
+    }
+
+    /// append でファイルを伸ばす時に、一度に extent_size 個の block を確保するようにする
+    /// bulk load のように append が続く場合に、system call の回数やファイルの断片化を減らすことができる
+    pub fn with_extent_size(mut self, extent_size: usize) -> FileManager {
+        self.extent_size = extent_size.max(1);
+        self
+    }
+
     // ブロックの内容を page に読み込む
     pub fn read(&self, blk: &BlockId, p: &mut Page) -> Result<(), FileManagerError> {
-        let blocksize = self.blocksize;
+        let stride = self.block_stride(blk.file_name())?;
+        let is_compressed = self.is_compressed(blk.file_name())?;
 
         self.cache_file(blk.file_name())?;
         let mut open_files = self
@@ -74,7 +127,41 @@ impl FileManager {
 
         match file {
             Some(file) => {
-                file.seek(io::SeekFrom::Start(blk.number() as u64 * blocksize as u64))?;
+                let start = blk.number() as u64 * stride as u64;
+                file.seek(io::SeekFrom::Start(start))?;
+                if let Some(cipher) = &self.cipher {
+                    // ファイルの長さ (watermark) より後ろは、まだ書き込まれていない block として扱う
+                    // append した block は暗号化した 0 埋めの page を書き込んでいるので、それより前の block は必ず認証する
+                    let length = self
+                        .file_lengths
+                        .lock()
+                        .map_err(|_| FileManagerError::LockError)?
+                        .get(blk.file_name())
+                        .copied()
+                        .unwrap_or(0);
+                    if blk.number() >= length {
+                        p.contents_mut().fill(0);
+                        return Ok(());
+                    }
+                    let mut bytes = vec![0u8; stride];
+                    file.read_exact(&mut bytes)?;
+                    if is_compressed {
+                        // 圧縮してから暗号化した block は、暗号文の長さと暗号文からなる
+                        let (len, ciphertext) = bytes.split_at(CIPHERTEXT_LEN_BYTES);
+                        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+                        let ciphertext = ciphertext.get(..len).ok_or(EncryptionError::Decrypt)?;
+                        let plaintext = cipher.decrypt(ciphertext, &Self::associated_data(blk))?;
+                        compression::read_block(&mut plaintext.as_slice(), p.contents_mut())?;
+                    } else {
+                        p.contents_mut()
+                            .copy_from_slice(&cipher.decrypt(&bytes, &Self::associated_data(blk))?);
+                    }
+                    return Ok(());
+                }
+                if is_compressed {
+                    compression::read_block(file, p.contents_mut())?;
+                    return Ok(());
+                }
                 // read_exact を使うよう言われているが、block は最初空なので、read_exact で想定されるバイト数だけ読めるとは限らない
                 // TODO: read_exact を使うほうが安全ではあるので、そのように変更する
                 #[allow(clippy::unused_io_amount)]
@@ -87,7 +174,8 @@ impl FileManager {
 
     // page の内容を block に書き込む
     pub fn write(&self, blk: &BlockId, p: &Page) -> Result<(), FileManagerError> {
-        let blocksize = self.blocksize;
+        let stride = self.block_stride(blk.file_name())?;
+        let is_compressed = self.is_compressed(blk.file_name())?;
         self.cache_file(blk.file_name())?;
         let mut open_files = self
             .open_files
@@ -97,10 +185,38 @@ impl FileManager {
 
         match file {
             Some(file) => {
-                file.seek(std::io::SeekFrom::Start(
-                    blk.number() as u64 * blocksize as u64,
-                ))?;
-                file.write_all(p.contents())?;
+                let start = blk.number() as u64 * stride as u64;
+                file.seek(std::io::SeekFrom::Start(start))?;
+                if let Some(cipher) = &self.cipher {
+                    file.write_all(&Self::encrypt_block(
+                        cipher,
+                        blk,
+                        p.contents(),
+                        is_compressed,
+                        stride,
+                    )?)?;
+                } else if is_compressed {
+                    let mut bytes = compression::encode(p.contents());
+                    // ファイルの長さは block 単位で数えるので、末尾の block は stride まで 0 で埋める
+                    if file.metadata()?.len() < start + stride as u64 {
+                        bytes.resize(stride, 0);
+                    }
+                    file.write_all(&bytes)?;
+                } else {
+                    file.write_all(p.contents())?;
+                }
+                // recovery などで append されていない block に書き込んだ場合は、その block までをファイルの長さとみなす
+                let mut file_lengths = self
+                    .file_lengths
+                    .lock()
+                    .map_err(|_| FileManagerError::LockError)?;
+                if let Some(length) = file_lengths.get_mut(blk.file_name()) {
+                    if *length <= blk.number() {
+                        *length = blk.number() + 1;
+                        let physical_length = (file.metadata()?.len() / stride as u64) as usize;
+                        self.save_length(blk.file_name(), *length, physical_length)?;
+                    }
+                }
                 Ok(())
             }
             None => Err(file_not_found_error()),
@@ -108,45 +224,116 @@ impl FileManager {
     }
 
     // ファイルの末尾に新しいブロックを追加する
+    // extent_size が 2 以上の場合、ファイルの物理的な長さが足りなくなった時にまとめて extent_size 個の block を確保する
     pub fn append(&self, filename: &str) -> Result<BlockId, FileManagerError> {
-        let blknum = self.length(filename)?;
-        let block = BlockId::new(filename, blknum);
-        let blocksize = self.blocksize;
-
+        let stride = self.block_stride(filename)?;
+        let is_compressed = self.is_compressed(filename)?;
+        // 暗号化する場合は、すべて 0 の block を認証せずに読まないように、追加する block に暗号化した 0 埋めの page を書き込む
+        let empty_block = match &self.cipher {
+            Some(cipher) => Some((
+                cipher,
+                vec![0u8; self.block_size_of(filename)?],
+                is_compressed,
+            )),
+            None => None,
+        };
         self.cache_file(filename)?;
         let mut open_files = self
             .open_files
             .lock()
             .map_err(|_| FileManagerError::LockError)?;
+        let mut file_lengths = self
+            .file_lengths
+            .lock()
+            .map_err(|_| FileManagerError::LockError)?;
         let file = open_files.get_mut(filename);
 
-        match file {
-            Some(file) => {
-                file.seek(std::io::SeekFrom::Start((blknum * blocksize) as u64))?;
-
-                let bytes = vec![0u8; blocksize];
-                file.write_all(&bytes)?;
-
+        match (file, file_lengths.get_mut(filename)) {
+            (Some(file), Some(length)) => {
+                let blknum = *length;
+                let mut physical_length = (file.metadata()?.len() / stride as u64) as usize;
+                // 確保済みの block を使う場合は、watermark が保存されているので更新する必要がある
+                let has_watermark = blknum < physical_length;
+                let block = BlockId::new(filename, blknum);
+                let encrypted = match &empty_block {
+                    Some((cipher, contents, is_compressed)) => Some(Self::encrypt_block(
+                        cipher,
+                        &block,
+                        contents,
+                        *is_compressed,
+                        stride,
+                    )?),
+                    None => None,
+                };
+                file.seek(std::io::SeekFrom::Start((blknum * stride) as u64))?;
+                if !has_watermark {
+                    // 追加する block の内容と、extent として確保する残りの block をまとめて書き込む
+                    let mut bytes = vec![0u8; stride * self.extent_size];
+                    if let Some(encrypted) = &encrypted {
+                        bytes[..stride].copy_from_slice(encrypted);
+                    }
+                    file.write_all(&bytes)?;
+                    physical_length = blknum + self.extent_size;
+                } else if let Some(encrypted) = &encrypted {
+                    file.write_all(encrypted)?;
+                }
+                *length = blknum + 1;
+                if has_watermark || *length < physical_length {
+                    self.save_length(filename, *length, physical_length)?;
+                }
                 Ok(block)
             }
-            None => Err(file_not_found_error()),
+            _ => Err(file_not_found_error()),
         }
     }
 
+    // ファイルの block 数を返す。extent として先に確保しただけの block は含まない
+    /// ファイルの長さ (watermark) が length block より短い場合は、length になるまで append する
+    /// 削除されたファイルを作り直さないように、ファイルが存在しない場合は何もしない
+    pub fn ensure_length(&self, filename: &str, length: usize) -> Result<(), FileManagerError> {
+        if !self.db_directory.join(filename).exists() {
+            return Ok(());
+        }
+        while self.length(filename)? < length {
+            self.append(filename)?;
+        }
+        Ok(())
+    }
+
     pub fn length(&self, filename: &str) -> Result<usize, FileManagerError> {
         self.cache_file(filename)?;
-        let open_files = self
+        let file_lengths = self
+            .file_lengths
+            .lock()
+            .map_err(|_| FileManagerError::LockError)?;
+        file_lengths
+            .get(filename)
+            .copied()
+            .ok_or_else(file_not_found_error)
+    }
+
+    /// ファイルを閉じて削除する。ファイルが存在しない場合は何もしない
+    /// 削除するファイルの block を buffer pool に残さないようにするのは呼び出し側の責任
+    pub fn delete_file(&self, filename: &str) -> Result<(), FileManagerError> {
+        let mut open_files = self
             .open_files
             .lock()
             .map_err(|_| FileManagerError::LockError)?;
-        let file = open_files.get(filename);
-        match file {
-            Some(file) => {
-                let metadata = file.metadata()?;
-                Ok((metadata.len() / self.blocksize as u64) as usize)
-            }
-            None => Err(file_not_found_error()),
-        }
+        open_files.remove(filename);
+        self.file_lengths
+            .lock()
+            .map_err(|_| FileManagerError::LockError)?
+            .remove(filename);
+        self.file_block_sizes
+            .lock()
+            .map_err(|_| FileManagerError::LockError)?
+            .remove(filename);
+        self.compressed_files
+            .lock()
+            .map_err(|_| FileManagerError::LockError)?
+            .remove(filename);
+        self.remove_if_exists(&self.watermark_path(filename))?;
+        self.remove_if_exists(&self.db_directory.join(filename))
     }
 
     pub fn is_new(&self) -> bool {
@@ -157,7 +344,100 @@ impl FileManager {
         self.blocksize
     }
 
+    /// ファイルの block size を設定する
+    /// ファイルにすでに書き込まれている block と異なる block size を設定してはいけない
+    pub fn set_block_size(&self, filename: &str, blocksize: usize) -> Result<(), FileManagerError> {
+        let mut file_block_sizes = self
+            .file_block_sizes
+            .lock()
+            .map_err(|_| FileManagerError::LockError)?;
+        if blocksize == self.blocksize {
+            file_block_sizes.remove(filename);
+        } else {
+            file_block_sizes.insert(filename.to_string(), blocksize);
+        }
+        Ok(())
+    }
+
+    /// ファイルの page を圧縮して保存するように設定する
+    /// すでに圧縮せずに書き込まれている block があるファイルに対して設定してはいけない
+    pub fn set_compressed(&self, filename: &str) -> Result<(), FileManagerError> {
+        if !cfg!(feature = "compression") {
+            return Err(FileManagerError::CompressionUnavailable);
+        }
+        self.compressed_files
+            .lock()
+            .map_err(|_| FileManagerError::LockError)?
+            .insert(filename.to_string());
+        Ok(())
+    }
+
+    pub fn is_compressed(&self, filename: &str) -> Result<bool, FileManagerError> {
+        Ok(self
+            .compressed_files
+            .lock()
+            .map_err(|_| FileManagerError::LockError)?
+            .contains(filename))
+    }
+
+    /// ファイルの block size を返す。set_block_size で設定されていない場合は共通の block size を返す
+    pub fn block_size_of(&self, filename: &str) -> Result<usize, FileManagerError> {
+        let file_block_sizes = self
+            .file_block_sizes
+            .lock()
+            .map_err(|_| FileManagerError::LockError)?;
+        Ok(*file_block_sizes.get(filename).unwrap_or(&self.blocksize))
+    }
+
+    // ファイル上で 1 block が占めるバイト数
+    // 圧縮されたファイルでは header の分、暗号化する場合は nonce と tag の分だけ block size より大きくなる
+    // 圧縮したファイルを暗号化する場合は、さらに暗号文の長さの分だけ大きくなる
+    fn block_stride(&self, filename: &str) -> Result<usize, FileManagerError> {
+        let mut stride = self.block_size_of(filename)?;
+        let is_compressed = self.is_compressed(filename)?;
+        if is_compressed {
+            stride += COMPRESSION_HEADER_LEN;
+        }
+        if self.cipher.is_some() {
+            stride += ENCRYPTION_OVERHEAD;
+            if is_compressed {
+                stride += CIPHERTEXT_LEN_BYTES;
+            }
+        }
+        Ok(stride)
+    }
+
+    // 暗号化の associated data
+    // 別のファイルや別の位置の block と入れ替えられたことを検出できるように、ファイル名と block 番号を含める
+    // table の rename ではファイルを置き換えずに block を新しいファイルに書き直すので、ファイル名は block の識別子として使える
+    fn associated_data(blk: &BlockId) -> Vec<u8> {
+        let mut aad = blk.file_name().as_bytes().to_vec();
+        aad.extend_from_slice(&(blk.number() as u64).to_be_bytes());
+        aad
+    }
+
+    // page の内容を暗号化して、ファイルに書き込む stride バイトの形にする
+    fn encrypt_block(
+        cipher: &BlockCipher,
+        blk: &BlockId,
+        contents: &[u8],
+        is_compressed: bool,
+        stride: usize,
+    ) -> Result<Vec<u8>, FileManagerError> {
+        if !is_compressed {
+            return Ok(cipher.encrypt(contents, &Self::associated_data(blk))?);
+        }
+        // 暗号文は圧縮できないので、圧縮してから暗号化し、暗号文の長さと暗号文を stride まで 0 で埋めて書き込む
+        let ciphertext =
+            cipher.encrypt(&compression::encode(contents), &Self::associated_data(blk))?;
+        let mut bytes = (ciphertext.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&ciphertext);
+        bytes.resize(stride, 0);
+        Ok(bytes)
+    }
+
     fn cache_file(&self, filename: &str) -> Result<(), FileManagerError> {
+        let stride = self.block_stride(filename)?;
         let mut open_files = self
             .open_files
             .lock()
@@ -170,10 +450,70 @@ impl FileManager {
                 .create(true)
                 .custom_flags(libc::O_SYNC)
                 .open(self.db_directory.join(filename))?;
+            let length = self.initial_length(filename, &file, stride)?;
             open_files.insert(filename.to_string(), file);
+            self.file_lengths
+                .lock()
+                .map_err(|_| FileManagerError::LockError)?
+                .insert(filename.to_string(), length);
         }
         Ok(())
     }
+
+    // ファイルを開いた時点でのファイルの長さ (block 数) を求める
+    // extent として確保しただけの block がある場合は保存された watermark を使い、そうでなければ物理的な長さを使う
+    fn initial_length(
+        &self,
+        filename: &str,
+        file: &fs::File,
+        stride: usize,
+    ) -> Result<usize, FileManagerError> {
+        let physical_length = (file.metadata()?.len() / stride as u64) as usize;
+        match fs::read(self.watermark_path(filename)) {
+            Ok(bytes) => {
+                let bytes: [u8; 8] = bytes.as_slice().try_into().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "invalid watermark file")
+                })?;
+                Ok((u64::from_be_bytes(bytes) as usize).min(physical_length))
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(physical_length),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    // ファイルの長さ (watermark) を保存する
+    // 物理的な長さと一致する場合は開き直した時に物理的な長さから求められるので、保存した watermark を削除する
+    fn save_length(
+        &self,
+        filename: &str,
+        length: usize,
+        physical_length: usize,
+    ) -> Result<(), FileManagerError> {
+        let path = self.watermark_path(filename);
+        if length >= physical_length {
+            return self.remove_if_exists(&path);
+        }
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .custom_flags(libc::O_SYNC)
+            .open(path)?;
+        file.write_all(&(length as u64).to_be_bytes())?;
+        Ok(())
+    }
+
+    fn watermark_path(&self, filename: &str) -> path::PathBuf {
+        self.db_directory
+            .join(format!("{}{}", filename, WATERMARK_SUFFIX))
+    }
+
+    fn remove_if_exists(&self, path: &path::Path) -> Result<(), FileManagerError> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
 }
 
 fn file_not_found_error() -> FileManagerError {
@@ -208,6 +548,75 @@ mod test_file_manager {
         assert_eq!(file_manager.block_size(), 400);
     }
 
+    #[test]
+    fn test_per_file_block_size() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let file_manager = FileManager::new(dir.path(), 400);
+        file_manager.set_block_size("wide_file", 800).unwrap();
+        assert_eq!(file_manager.block_size_of("wide_file").unwrap(), 800);
+        assert_eq!(file_manager.block_size_of("test_file").unwrap(), 400);
+
+        file_manager.append("wide_file").unwrap();
+        let block = file_manager.append("wide_file").unwrap();
+        assert_eq!(file_manager.length("wide_file").unwrap(), 2);
+
+        // block 1 は 800 バイト目から始まる
+        let mut page = Page::new_from_size(800);
+        page.set_int(796, 123);
+        file_manager.write(&block, &page).unwrap();
+        let mut read_page = Page::new_from_size(800);
+        file_manager.read(&block, &mut read_page).unwrap();
+        assert_eq!(read_page.get_int(796), 123);
+        assert_eq!(
+            fs::metadata(dir.path().join("wide_file")).unwrap().len(),
+            1600
+        );
+    }
+
+    #[test]
+    fn test_append_with_extent() {
+        let dir = tempfile::tempdir().unwrap();
+        let file_size =
+            |dir: &tempfile::TempDir| fs::metadata(dir.path().join("test_file")).unwrap().len();
+
+        {
+            let file_manager = FileManager::new(dir.path(), 400).with_extent_size(4);
+            let mut page = Page::new_from_size(400);
+            for i in 0..5 {
+                let block = file_manager.append("test_file").unwrap();
+                assert_eq!(block.number(), i);
+                assert_eq!(file_manager.length("test_file").unwrap(), i + 1);
+                page.set_int(0, i as i32 + 1);
+                file_manager.write(&block, &page).unwrap();
+                // 4 block ずつまとめて確保される
+                assert_eq!(file_size(&dir), if i < 4 { 1600 } else { 3200 });
+            }
+        }
+
+        // 開き直しても、extent として確保しただけの block はファイルの長さに含まれない
+        let file_manager = FileManager::new(dir.path(), 400).with_extent_size(4);
+        assert_eq!(file_manager.length("test_file").unwrap(), 5);
+        // 何も書き込んでいない block も、append した block はファイルの長さに含まれる
+        let block = file_manager.append("test_file").unwrap();
+        assert_eq!(block.number(), 5);
+        assert_eq!(file_size(&dir), 3200);
+        drop(file_manager);
+
+        let file_manager = FileManager::new(dir.path(), 400).with_extent_size(4);
+        assert_eq!(file_manager.length("test_file").unwrap(), 6);
+        // 確保した block をすべて使うと watermark は不要になる
+        file_manager.append("test_file").unwrap();
+        file_manager.append("test_file").unwrap();
+        assert!(!dir.path().join("test_file.watermark").exists());
+        drop(file_manager);
+
+        let file_manager = FileManager::new(dir.path(), 400);
+        assert_eq!(file_manager.length("test_file").unwrap(), 8);
+        file_manager.delete_file("test_file").unwrap();
+        assert!(!dir.path().join("test_file.watermark").exists());
+    }
+
     #[test]
     fn test_read_and_write() {
         let dir = tempfile::tempdir().unwrap();
@@ -224,6 +633,108 @@ mod test_file_manager {
         assert_eq!(read_page.get_int(0), 123);
     }
 
+    #[test]
+    #[cfg(all(feature = "compression", feature = "encryption"))]
+    fn test_compressed_and_encrypted_file() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let file_manager = FileManager::new(dir.path(), 400)
+            .with_encryption_key(&[3u8; ENCRYPTION_KEY_LEN])
+            .unwrap();
+        file_manager.set_compressed("test_file").unwrap();
+        let stride = 400 + COMPRESSION_HEADER_LEN + ENCRYPTION_OVERHEAD + CIPHERTEXT_LEN_BYTES;
+        let mut page = Page::new_from_size(400);
+        page.set_string(0, &"secret".repeat(20));
+        for _ in 0..2 {
+            let block = file_manager.append("test_file").unwrap();
+            file_manager.write(&block, &page).unwrap();
+        }
+        page.set_int(396, 7);
+        file_manager
+            .write(&BlockId::new("test_file", 0), &page)
+            .unwrap();
+
+        // 圧縮してから暗号化するので、暗号文は block size よりずっと短く、平文も現れない
+        let bytes = fs::read(dir.path().join("test_file")).unwrap();
+        assert_eq!(bytes.len(), stride * 2);
+        let len = u32::from_be_bytes(bytes[..CIPHERTEXT_LEN_BYTES].try_into().unwrap()) as usize;
+        assert!(len < 400 / 2);
+        assert!(!bytes.windows(6).any(|w| w == b"secret"));
+
+        let mut read_page = Page::new_from_size(400);
+        file_manager
+            .read(&BlockId::new("test_file", 0), &mut read_page)
+            .unwrap();
+        assert_eq!(read_page.get_string(0).unwrap(), "secret".repeat(20));
+        assert_eq!(read_page.get_int(396), 7);
+        file_manager
+            .read(&BlockId::new("test_file", 1), &mut read_page)
+            .unwrap();
+        assert_eq!(read_page.get_int(396), 0);
+        // まだ書き込まれていない block は 0 埋めで読める
+        file_manager
+            .read(&BlockId::new("test_file", 2), &mut read_page)
+            .unwrap();
+        assert!(read_page.contents().iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    #[cfg(feature = "encryption")]
+    fn test_encrypted_block_is_authenticated() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let file_manager = FileManager::new(dir.path(), 400)
+            .with_extent_size(4)
+            .with_encryption_key(&[3u8; ENCRYPTION_KEY_LEN])
+            .unwrap();
+        let stride = 400 + ENCRYPTION_OVERHEAD;
+        let mut page = Page::new_from_size(400);
+        for (filename, value) in [("file_a", 1), ("file_b", 2)] {
+            let block = file_manager.append(filename).unwrap();
+            page.set_int(0, value);
+            file_manager.write(&block, &page).unwrap();
+        }
+        // append しただけの block と、extent として確保しただけの block は 0 埋めで読める
+        let block = file_manager.append("file_a").unwrap();
+        let mut read_page = Page::new_from_size(400);
+        file_manager.read(&block, &mut read_page).unwrap();
+        assert!(read_page.contents().iter().all(|&b| b == 0));
+        file_manager
+            .read(&BlockId::new("file_a", 2), &mut read_page)
+            .unwrap();
+        assert!(read_page.contents().iter().all(|&b| b == 0));
+
+        // 別のファイルの block と入れ替えると復号できない
+        let path = dir.path().join("file_a");
+        let original = fs::read(&path).unwrap();
+        let mut swapped = original.clone();
+        swapped[..stride].copy_from_slice(&fs::read(dir.path().join("file_b")).unwrap()[..stride]);
+        fs::write(&path, &swapped).unwrap();
+        assert!(file_manager
+            .read(&BlockId::new("file_a", 0), &mut read_page)
+            .is_err());
+        // 同じファイルの別の block と入れ替えても復号できない
+        let mut swapped = original.clone();
+        swapped[..stride].copy_from_slice(&original[stride..stride * 2]);
+        fs::write(&path, &swapped).unwrap();
+        assert!(file_manager
+            .read(&BlockId::new("file_a", 0), &mut read_page)
+            .is_err());
+        // 書き込み済みの block をすべて 0 に書き換えても、未書き込みの block としては扱わない
+        let mut zeroed = original.clone();
+        zeroed[..stride].fill(0);
+        fs::write(&path, &zeroed).unwrap();
+        assert!(file_manager
+            .read(&BlockId::new("file_a", 0), &mut read_page)
+            .is_err());
+
+        fs::write(&path, &original).unwrap();
+        file_manager
+            .read(&BlockId::new("file_a", 0), &mut read_page)
+            .unwrap();
+        assert_eq!(read_page.get_int(0), 1);
+    }
+
     #[test]
     fn test_append() {
         let dir = tempfile::tempdir().unwrap();
@@ -1,5 +1,80 @@
+use thiserror::Error;
+
 use crate::constants::INTEGER_BYTE_LEN;
 
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum PageError {
+    #[error("out of bounds access: offset {offset}, length {length}, page size {page_size}")]
+    OutOfBounds {
+        offset: usize,
+        length: usize,
+        page_size: usize,
+    },
+    #[error("invalid byte length {length} at offset {offset}")]
+    InvalidLength { offset: usize, length: i32 },
+    #[error("invalid bool value {value} at offset {offset}")]
+    InvalidBool { offset: usize, value: u8 },
+    #[error("invalid utf-8 string at offset {offset}")]
+    InvalidString { offset: usize },
+}
+
+/**
+ * Page に固定長で読み書きできる値
+ *
+ * どの型も big endian で保存する
+ */
+pub trait FixedWidth: Sized {
+    const BYTE_LEN: usize;
+
+    fn read(bytes: &[u8], offset: usize) -> Result<Self, PageError>;
+    fn write(&self, bytes: &mut [u8]);
+}
+
+macro_rules! impl_fixed_width_for_number {
+    ($($ty:ty),*) => {
+        $(
+            impl FixedWidth for $ty {
+                const BYTE_LEN: usize = std::mem::size_of::<$ty>();
+
+                fn read(bytes: &[u8], _offset: usize) -> Result<Self, PageError> {
+                    let mut buf = [0u8; std::mem::size_of::<$ty>()];
+                    buf.copy_from_slice(bytes);
+                    Ok(<$ty>::from_be_bytes(buf))
+                }
+
+                fn write(&self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_be_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_fixed_width_for_number!(i32, u32, i64, u64, f64);
+
+impl FixedWidth for bool {
+    const BYTE_LEN: usize = 1;
+
+    fn read(bytes: &[u8], offset: usize) -> Result<Self, PageError> {
+        // 0 / 1 以外は壊れた値として扱う
+        match bytes[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(PageError::InvalidBool { offset, value }),
+        }
+    }
+
+    fn write(&self, bytes: &mut [u8]) {
+        bytes[0] = *self as u8;
+    }
+}
+
+/**
+ * block 1 つ分の byte 列
+ *
+ * get_xxx / set_xxx は範囲外の offset を渡すと panic する
+ * 壊れているかもしれない byte 列を読む場合など、範囲外の offset を error として扱いたい場合は try_xxx を使う
+ */
 pub struct Page {
     bb: Vec<u8>,
 }
@@ -16,28 +91,37 @@ impl Page {
     }
 
     pub fn get_int(&self, offset: usize) -> i32 {
-        let mut bytes = [0u8; INTEGER_BYTE_LEN];
-        bytes.copy_from_slice(&self.bb[offset..offset + INTEGER_BYTE_LEN]);
-        i32::from_be_bytes(bytes)
+        self.try_get_int(offset).unwrap_or_else(|e| panic!("{}", e))
     }
 
     pub fn set_int(&mut self, offset: usize, n: i32) {
-        let bytes = n.to_be_bytes();
-        self.bb[offset..offset + INTEGER_BYTE_LEN].copy_from_slice(&bytes);
+        self.try_set_int(offset, n)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// offset から 8 byte を f64 として読む。i64 と同じく big endian で保存する
+    pub fn get_double(&self, offset: usize) -> f64 {
+        self.try_get_f64(offset).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    pub fn set_double(&mut self, offset: usize, f: f64) {
+        self.try_set_f64(offset, f)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
     pub fn get_bytes(&self, offset: usize) -> Vec<u8> {
-        let length = self.get_int(offset) as usize;
-        let pos = offset + INTEGER_BYTE_LEN;
-        let mut bytes = vec![0u8; length];
-        bytes.copy_from_slice(&self.bb[pos..pos + length]);
-        return bytes;
+        self.get_bytes_ref(offset).to_vec()
+    }
+
+    /// get_bytes と同じ byte 列を、copy せずに page の中を指す slice として返す
+    pub fn get_bytes_ref(&self, offset: usize) -> &[u8] {
+        self.try_get_bytes_ref(offset)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
     pub fn set_bytes(&mut self, offset: usize, b: &[u8]) {
-        self.set_int(offset, b.len() as i32);
-        let pos = offset + INTEGER_BYTE_LEN;
-        self.bb[pos..pos + b.len()].copy_from_slice(&b);
+        self.try_set_bytes(offset, b)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
     pub fn get_string(&self, offset: usize) -> Result<String, std::string::FromUtf8Error> {
@@ -45,11 +129,131 @@ impl Page {
         String::from_utf8(b)
     }
 
+    /// get_string と同じ文字列を、String を作らずに page の中を指す &str として返す
+    /// 値を比べるだけの場合など、文字列を所有する必要がない場合に allocation を省くために使う
+    pub fn get_str(&self, offset: usize) -> Result<&str, std::str::Utf8Error> {
+        std::str::from_utf8(self.get_bytes_ref(offset))
+    }
+
     pub fn set_string(&mut self, offset: usize, s: &str) {
         let b = s.as_bytes();
         self.set_bytes(offset, b);
     }
 
+    /// offset から T::BYTE_LEN byte を T として読む
+    pub fn try_get<T: FixedWidth>(&self, offset: usize) -> Result<T, PageError> {
+        T::read(self.range(offset, T::BYTE_LEN)?, offset)
+    }
+
+    /// offset から T::BYTE_LEN byte に value を書き込む。範囲外の場合は何も書き込まない
+    pub fn try_set<T: FixedWidth>(&mut self, offset: usize, value: T) -> Result<(), PageError> {
+        let bytes = self.range_mut(offset, T::BYTE_LEN)?;
+        value.write(bytes);
+        Ok(())
+    }
+
+    pub fn try_get_int(&self, offset: usize) -> Result<i32, PageError> {
+        self.try_get(offset)
+    }
+
+    pub fn try_set_int(&mut self, offset: usize, n: i32) -> Result<(), PageError> {
+        self.try_set(offset, n)
+    }
+
+    pub fn try_get_u64(&self, offset: usize) -> Result<u64, PageError> {
+        self.try_get(offset)
+    }
+
+    pub fn try_set_u64(&mut self, offset: usize, n: u64) -> Result<(), PageError> {
+        self.try_set(offset, n)
+    }
+
+    pub fn try_get_i64(&self, offset: usize) -> Result<i64, PageError> {
+        self.try_get(offset)
+    }
+
+    pub fn try_set_i64(&mut self, offset: usize, n: i64) -> Result<(), PageError> {
+        self.try_set(offset, n)
+    }
+
+    pub fn try_get_bool(&self, offset: usize) -> Result<bool, PageError> {
+        self.try_get(offset)
+    }
+
+    pub fn try_set_bool(&mut self, offset: usize, b: bool) -> Result<(), PageError> {
+        self.try_set(offset, b)
+    }
+
+    pub fn try_get_f64(&self, offset: usize) -> Result<f64, PageError> {
+        self.try_get(offset)
+    }
+
+    pub fn try_set_f64(&mut self, offset: usize, f: f64) -> Result<(), PageError> {
+        self.try_set(offset, f)
+    }
+
+    /// offset にある長さと、その後に続く byte 列を読む
+    /// 長さが負の場合や、byte 列が page の外まで続く場合は error を返す
+    pub fn try_get_bytes(&self, offset: usize) -> Result<Vec<u8>, PageError> {
+        Ok(self.try_get_bytes_ref(offset)?.to_vec())
+    }
+
+    /// try_get_bytes と同じ byte 列を、copy せずに page の中を指す slice として返す
+    pub fn try_get_bytes_ref(&self, offset: usize) -> Result<&[u8], PageError> {
+        let length = self.try_get_int(offset)?;
+        if length < 0 {
+            return Err(PageError::InvalidLength { offset, length });
+        }
+        self.range(offset + INTEGER_BYTE_LEN, length as usize)
+    }
+
+    /// 長さと byte 列を書き込む。byte 列の最後までが page に収まらない場合は何も書き込まない
+    pub fn try_set_bytes(&mut self, offset: usize, b: &[u8]) -> Result<(), PageError> {
+        let length = i32::try_from(b.len())
+            .map_err(|_| self.out_of_bounds(offset, INTEGER_BYTE_LEN + b.len()))?;
+        self.range(offset, INTEGER_BYTE_LEN + b.len())?;
+        self.try_set_int(offset, length)?;
+        self.range_mut(offset + INTEGER_BYTE_LEN, b.len())?
+            .copy_from_slice(b);
+        Ok(())
+    }
+
+    pub fn try_get_string(&self, offset: usize) -> Result<String, PageError> {
+        self.try_get_str(offset).map(str::to_string)
+    }
+
+    /// try_get_string と同じ文字列を、String を作らずに page の中を指す &str として返す
+    pub fn try_get_str(&self, offset: usize) -> Result<&str, PageError> {
+        std::str::from_utf8(self.try_get_bytes_ref(offset)?)
+            .map_err(|_| PageError::InvalidString { offset })
+    }
+
+    pub fn try_set_string(&mut self, offset: usize, s: &str) -> Result<(), PageError> {
+        self.try_set_bytes(offset, s.as_bytes())
+    }
+
+    fn range(&self, offset: usize, length: usize) -> Result<&[u8], PageError> {
+        match offset.checked_add(length) {
+            Some(end) if end <= self.bb.len() => Ok(&self.bb[offset..end]),
+            _ => Err(self.out_of_bounds(offset, length)),
+        }
+    }
+
+    fn range_mut(&mut self, offset: usize, length: usize) -> Result<&mut [u8], PageError> {
+        match offset.checked_add(length) {
+            Some(end) if end <= self.bb.len() => Ok(&mut self.bb[offset..end]),
+            _ => Err(self.out_of_bounds(offset, length)),
+        }
+    }
+
+    fn out_of_bounds(&self, offset: usize, length: usize) -> PageError {
+        PageError::OutOfBounds {
+            offset,
+            length,
+            page_size: self.bb.len(),
+        }
+    }
+
     pub fn max_length(strlen: usize) -> usize {
         // utf-8 での最大長は 4 byte なはず...
         // https://ja.wikipedia.org/wiki/UTF-8?utm_source=chatgpt.com
@@ -84,10 +288,92 @@ mod test_page {
         assert_eq!(page.get_int(0), 123);
         assert_eq!(page.get_bytes(8), vec![1, 2, 3, 4, 5]);
         assert_eq!(page.get_string(20).unwrap(), "hello");
+        // copy せずに読んでも同じ値になる
+        assert_eq!(page.get_str(20).unwrap(), "hello");
+        assert_eq!(page.get_bytes_ref(8), &[1, 2, 3, 4, 5]);
+        assert_eq!(page.try_get_str(20), Ok("hello"));
 
         let contents = page.contents();
         assert_eq!(contents.len(), 400);
         assert_eq!(contents[0..4], vec![0, 0, 0, 123]);
         assert_eq!(contents[8..17], vec![0, 0, 0, 5, 1, 2, 3, 4, 5]);
+
+        // double は bit 列をそのまま保存するので、int 2 つ分として読むこともできる
+        page.set_double(40, -1.25);
+        assert_eq!(page.get_double(40), -1.25);
+        assert_eq!(page.try_get_i64(40), Ok((-1.25f64).to_bits() as i64));
+    }
+
+    #[test]
+    fn test_fixed_width_values() {
+        let mut page = Page::new_from_size(32);
+
+        page.try_set_u64(0, u64::MAX - 1).unwrap();
+        page.try_set_i64(8, -42).unwrap();
+        page.try_set_f64(16, 3.5).unwrap();
+        page.try_set_bool(24, true).unwrap();
+        page.try_set_bool(25, false).unwrap();
+
+        assert_eq!(page.try_get_u64(0).unwrap(), u64::MAX - 1);
+        assert_eq!(page.try_get_i64(8).unwrap(), -42);
+        assert_eq!(page.try_get_f64(16).unwrap(), 3.5);
+        assert!(page.try_get_bool(24).unwrap());
+        assert!(!page.try_get_bool(25).unwrap());
+        assert_eq!(page.contents()[8..16], (-42i64).to_be_bytes());
+
+        // 0 / 1 以外の byte は bool として読めない
+        page.try_set::<u32>(26, 2 << 24).unwrap();
+        assert_eq!(
+            page.try_get_bool(26),
+            Err(PageError::InvalidBool {
+                offset: 26,
+                value: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_out_of_bounds() {
+        let mut page = Page::new_from_size(16);
+
+        assert_eq!(
+            page.try_get_int(13),
+            Err(PageError::OutOfBounds {
+                offset: 13,
+                length: 4,
+                page_size: 16
+            })
+        );
+        assert!(page.try_set_u64(9, 1).is_err());
+        assert!(page.try_get_int(usize::MAX).is_err());
+        assert!(page.try_set_int(12, 1).is_ok());
+
+        // byte 列が収まらない場合は、長さも含めて何も書き込まない
+        assert!(page.try_set_bytes(4, &[1; 9]).is_err());
+        assert_eq!(page.contents()[..12], [0; 12]);
+        page.try_set_bytes(4, &[1; 8]).unwrap();
+        assert_eq!(page.try_get_bytes(4).unwrap(), vec![1; 8]);
+
+        // 壊れた長さは error になる
+        page.try_set_int(0, -1).unwrap();
+        assert_eq!(
+            page.try_get_bytes(0),
+            Err(PageError::InvalidLength {
+                offset: 0,
+                length: -1
+            })
+        );
+        page.try_set_int(0, 100).unwrap();
+        assert!(matches!(
+            page.try_get_string(0),
+            Err(PageError::OutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds access")]
+    fn test_unchecked_access_panics() {
+        let page = Page::new_from_size(16);
+        page.get_int(16);
     }
 }
@@ -0,0 +1,6 @@
+pub mod btree_dir;
+pub mod btree_index;
+pub mod btree_leaf;
+pub mod btree_page;
+pub mod common;
+pub mod hash_index;
@@ -0,0 +1,167 @@
+use std::{cell::RefCell, cmp::Ordering, ops::Bound, rc::Rc};
+
+use crate::{
+    file::blockid::BlockId, query::constant::Constant, record::layout::Layout,
+    tx::transaction::Transaction,
+};
+
+use super::btree_page::{BTreePage, BTreePageError};
+
+/**
+ * B-Tree の directory の entry
+ *
+ * 値が data_val 以上の record は、block_num の block (またはその子孫) にある
+ */
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DirEntry {
+    data_val: Constant,
+    block_num: usize,
+}
+
+impl DirEntry {
+    pub fn new(data_val: Constant, block_num: usize) -> Self {
+        DirEntry {
+            data_val,
+            block_num,
+        }
+    }
+
+    pub fn data_val(&self) -> &Constant {
+        &self.data_val
+    }
+
+    pub fn block_num(&self) -> usize {
+        self.block_num
+    }
+}
+
+/**
+ * B-Tree の directory の block を読み書きするための構造体
+ *
+ * flag が 0 の block は leaf の block を指し、それ以外の block は 1 つ下の level の directory の block を指す
+ */
+pub struct BTreeDir {
+    tx: Rc<RefCell<Transaction>>,
+    layout: Layout,
+    contents: BTreePage,
+}
+
+impl BTreeDir {
+    pub fn new(tx: Rc<RefCell<Transaction>>, block: &BlockId, layout: &Layout) -> Self {
+        let contents = BTreePage::new(tx.clone(), block, layout);
+        BTreeDir {
+            tx,
+            layout: layout.clone(),
+            contents,
+        }
+    }
+
+    /// search_key を持つ record がある (または追加すべき) leaf の block の番号を返す
+    pub fn search(&mut self, search_key: &Constant) -> Result<usize, BTreePageError> {
+        let mut child_block = self.find_child_block(search_key)?;
+        while self.contents.get_flag()? > 0 {
+            self.contents = BTreePage::new(self.tx.clone(), &child_block, &self.layout);
+            child_block = self.find_child_block(search_key)?;
+        }
+        Ok(child_block.number())
+    }
+
+    /// 値が lo と hi の間にある record が入っている可能性のある leaf の block の番号を、値の順に返す
+    /// i 番目の entry の子には、i 番目の entry の値以上で i + 1 番目の entry の値より小さい値の record が入っている
+    pub fn leaf_blocks_in_range(
+        &self,
+        lo: Bound<&Constant>,
+        hi: Bound<&Constant>,
+    ) -> Result<Vec<usize>, BTreePageError> {
+        let num_recs = self.contents.get_num_recs()?;
+        let mut blocks = vec![];
+        for slot in 0..num_recs {
+            // 子の値の下限が hi より大きい場合は、それ以降の子も範囲に入らない
+            let lower = self.contents.get_data_val(slot)?;
+            if slot > 0 {
+                match hi {
+                    Bound::Included(hi) | Bound::Excluded(hi) if lower > *hi => break,
+                    _ => {}
+                }
+            }
+            // 子の値の上限が lo 以下の場合は、この子は範囲に入らない
+            if slot + 1 < num_recs {
+                let upper = self.contents.get_data_val(slot + 1)?;
+                match lo {
+                    Bound::Included(lo) | Bound::Excluded(lo) if upper <= *lo => continue,
+                    _ => {}
+                }
+            }
+            let child_num = self.contents.get_child_num(slot)?;
+            if self.contents.get_flag()? == 0 {
+                blocks.push(child_num);
+            } else {
+                let child_block = BlockId::new(self.contents.block().file_name(), child_num);
+                let child = BTreeDir::new(self.tx.clone(), &child_block, &self.layout);
+                blocks.extend(child.leaf_blocks_in_range(lo, hi)?);
+            }
+        }
+        Ok(blocks)
+    }
+
+    /// root の block が分割された場合に呼ぶ
+    /// root の中身を新しい block に移し、元の中身と entry を指す 2 つの entry を持つ 1 つ上の level の root にする
+    /// root の block の位置は変わらない
+    pub fn make_new_root(&mut self, entry: &DirEntry) -> Result<(), BTreePageError> {
+        let first_val = self.contents.get_data_val(0)?;
+        let level = self.contents.get_flag()?;
+        let new_block = self.contents.split(0, level)?;
+        let old_root = DirEntry::new(first_val, new_block.number());
+        self.insert_entry(&old_root)?;
+        self.insert_entry(entry)?;
+        self.contents.set_flag(level + 1)?;
+        Ok(())
+    }
+
+    /// 子の block が分割されてできた entry を directory に追加する
+    /// この block も分割された場合は、新しい block を指す entry を返す
+    pub fn insert(&mut self, entry: &DirEntry) -> Result<Option<DirEntry>, BTreePageError> {
+        if self.contents.get_flag()? == 0 {
+            return self.insert_entry(entry);
+        }
+        let child_block = self.find_child_block(entry.data_val())?;
+        let mut child = BTreeDir::new(self.tx.clone(), &child_block, &self.layout);
+        match child.insert(entry)? {
+            Some(new_entry) => self.insert_entry(&new_entry),
+            None => Ok(None),
+        }
+    }
+
+    fn insert_entry(&mut self, entry: &DirEntry) -> Result<Option<DirEntry>, BTreePageError> {
+        let new_slot = self
+            .contents
+            .find_slot_before(entry.data_val())?
+            .map_or(0, |slot| slot + 1);
+        self.contents
+            .insert_dir(new_slot, entry.data_val(), entry.block_num())?;
+        if !self.contents.is_full()? {
+            return Ok(None);
+        }
+        // block が一杯になったので、後ろ半分を新しい block に移す
+        let level = self.contents.get_flag()?;
+        let split_pos = self.contents.get_num_recs()? / 2;
+        let split_val = self.contents.get_data_val(split_pos)?;
+        let new_block = self.contents.split(split_pos, level)?;
+        Ok(Some(DirEntry::new(split_val, new_block.number())))
+    }
+
+    // search_key を持つ record がある子の block を返す
+    fn find_child_block(&self, search_key: &Constant) -> Result<BlockId, BTreePageError> {
+        let mut slot = self.contents.find_slot_before(search_key)?;
+        let next_slot = slot.map_or(0, |slot| slot + 1);
+        if next_slot < self.contents.get_num_recs()?
+            && self.contents.compare_data_val(next_slot, search_key)? == Some(Ordering::Equal)
+        {
+            slot = Some(next_slot);
+        }
+        // directory の最初の entry はその型の最小値なので、通常は None にならない
+        // 型の異なる値で探した場合などは、一番左の子を返す
+        let block_num = self.contents.get_child_num(slot.unwrap_or(0))?;
+        Ok(BlockId::new(self.contents.block().file_name(), block_num))
+    }
+}
@@ -0,0 +1,824 @@
+use std::{cell::RefCell, collections::VecDeque, ops::Bound, rc::Rc};
+
+use crate::{
+    file::blockid::BlockId,
+    query::{constant::Constant, date::Date},
+    record::{
+        layout::Layout,
+        rid::Rid,
+        schema::{FieldInfo, Schema},
+    },
+    tx::transaction::Transaction,
+};
+
+use super::{
+    btree_dir::{BTreeDir, DirEntry},
+    btree_leaf::BTreeLeaf,
+    btree_page::BTreePage,
+    common::{
+        in_range, key_lock_block, Index, IndexEntry, IndexError, INDEX_BLOCK_FIELD,
+        INDEX_DATAVAL_FIELD,
+    },
+};
+
+// before_first_range で指定した範囲の record を、leaf を 1 つずつ読みながら辿るための状態
+struct RangeCursor {
+    lo: Bound<Constant>,
+    hi: Bound<Constant>,
+    // まだ読んでいない leaf の block の番号 (値の順)
+    leaf_blocks: VecDeque<usize>,
+    // 読んだ leaf にあった、範囲に入る record のうち、まだ返していないもの
+    records: VecDeque<IndexEntry>,
+    current: Option<IndexEntry>,
+    // hi より大きい値の record を読んだら、それ以降の leaf は読まない
+    finished: bool,
+}
+
+/**
+ * B-Tree による index
+ *
+ * leaf の block は {index 名}.leaf に、directory の block は {index 名}.dir に保存する
+ * directory の root は常に {index 名}.dir の 0 番目の block にある
+ */
+pub struct BTreeIndex {
+    tx: Rc<RefCell<Transaction>>,
+    index_name: String,
+    dir_layout: Layout,
+    leaf_layout: Layout,
+    leaf_filename: String,
+    root_block: BlockId,
+    // before_first で開いた leaf。before_first を呼ぶまでは None
+    leaf: Option<BTreeLeaf>,
+    // before_first_range で開いた範囲。before_first を呼んだ場合は None
+    range: Option<RangeCursor>,
+}
+
+impl BTreeIndex {
+    /// index を開く。index のファイルがまだ無い場合は、空の index を作成する
+    /// leaf_layout は index_layout で作成したものを渡す
+    pub fn new(
+        tx: Rc<RefCell<Transaction>>,
+        index_name: &str,
+        leaf_layout: &Layout,
+    ) -> Result<Self, IndexError> {
+        let key_info =
+            leaf_layout
+                .schema()
+                .info(INDEX_DATAVAL_FIELD)
+                .ok_or(IndexError::InvalidCall(format!(
+                    "field {} not found in the leaf layout",
+                    INDEX_DATAVAL_FIELD
+                )))?;
+
+        let leaf_filename = format!("{}.leaf", index_name);
+        if tx.borrow().size(&leaf_filename)? == 0 {
+            let block = tx.borrow().append(&leaf_filename)?;
+            BTreePage::new(tx.clone(), &block, leaf_layout).format(-1)?;
+        }
+
+        let mut dir_schema = Schema::new();
+        dir_schema.add_field(INDEX_BLOCK_FIELD, FieldInfo::Integer);
+        dir_schema.add_field(INDEX_DATAVAL_FIELD, key_info);
+        let dir_layout = Layout::new(dir_schema)?;
+        let dir_filename = format!("{}.dir", index_name);
+        let root_block = BlockId::new(&dir_filename, 0);
+        if tx.borrow().size(&dir_filename)? == 0 {
+            // root は leaf の 0 番目の block だけを指す状態から始める
+            let block = tx.borrow().append(&dir_filename)?;
+            let root = BTreePage::new(tx.clone(), &block, &dir_layout);
+            root.format(0)?;
+            root.insert_dir(0, &Self::min_value(key_info), 0)?;
+        }
+
+        Ok(BTreeIndex {
+            tx,
+            index_name: index_name.to_string(),
+            dir_layout,
+            leaf_layout: leaf_layout.clone(),
+            leaf_filename,
+            root_block,
+            leaf: None,
+            range: None,
+        })
+    }
+
+    /// index の leaf と directory を保存するファイルの名前を返す
+    pub fn file_names(index_name: &str) -> Vec<String> {
+        vec![
+            format!("{}.leaf", index_name),
+            format!("{}.dir", index_name),
+        ]
+    }
+
+    /// index を 1 回検索する時に読む block の数の見積もり
+    /// directory の各 level で 1 block ずつと、leaf の 1 block を読むとみなす
+    pub fn search_cost(num_blocks: u64, records_per_block: u64) -> u64 {
+        if num_blocks <= 1 || records_per_block <= 1 {
+            return 1;
+        }
+        1 + ((num_blocks as f64).ln() / (records_per_block as f64).ln()).ceil() as u64
+    }
+
+    /// index の全ての entry を (値, Rid) の組で返す。整合性の確認に使う
+    /// overflow block も含めて、leaf のファイルの block を順に読む
+    pub fn entries(
+        tx: &Rc<RefCell<Transaction>>,
+        index_name: &str,
+        leaf_layout: &Layout,
+    ) -> Result<Vec<(Constant, Rid)>, IndexError> {
+        let leaf_filename = format!("{}.leaf", index_name);
+        let block_count = tx.borrow().size(&leaf_filename)?;
+        let mut entries = vec![];
+        for block_num in 0..block_count {
+            let page = BTreePage::new(
+                tx.clone(),
+                &BlockId::new(&leaf_filename, block_num),
+                leaf_layout,
+            );
+            for slot in 0..page.get_num_recs()? {
+                entries.push((page.get_data_val(slot)?, page.get_data_rid(slot)?));
+            }
+        }
+        Ok(entries)
+    }
+
+    // directory の最初の entry に使う、その型で一番小さい値
+    fn min_value(key_info: FieldInfo) -> Constant {
+        match key_info {
+            FieldInfo::Integer => Constant::Int(i32::MIN),
+            FieldInfo::String(_) => Constant::String(String::new()),
+            FieldInfo::Boolean => Constant::Bool(false),
+            FieldInfo::BigInt => Constant::BigInt(i64::MIN),
+            FieldInfo::Date => Constant::Date(Date::from_days(i32::MIN)),
+            // NaN は double の値として保存しないので、-inf が一番小さい
+            FieldInfo::Double => Constant::Double(f64::NEG_INFINITY),
+        }
+    }
+
+    // 値の昇順に並べた entries を、leaf の block に先頭から詰めて入れる
+    // 同じ値の record は 1 つの leaf (と、その overflow block) にまとめる
+    // 作った leaf の block を指す、directory の一番下の level の entry を返す
+    fn load_leaves(&self, entries: &[IndexEntry]) -> Result<Vec<DirEntry>, IndexError> {
+        let key_info =
+            self.leaf_layout
+                .schema()
+                .info(INDEX_DATAVAL_FIELD)
+                .ok_or(IndexError::InvalidCall(format!(
+                    "field {} not found in the leaf layout",
+                    INDEX_DATAVAL_FIELD
+                )))?;
+        let mut dir_entries = vec![DirEntry::new(Self::min_value(key_info), 0)];
+        let mut leaf = BTreePage::new(
+            self.tx.clone(),
+            &BlockId::new(&self.leaf_filename, 0),
+            &self.leaf_layout,
+        );
+        let capacity = leaf.capacity()?;
+        // overflow block を持つ leaf には、それ以上 record を入れない
+        let mut is_closed = false;
+        for group in entries.chunk_by(|lhs, rhs| lhs.data_val() == rhs.data_val()) {
+            let num_recs = leaf.get_num_recs()?;
+            if is_closed || (num_recs > 0 && num_recs + group.len() > capacity) {
+                let block = leaf.append_new(-1)?;
+                dir_entries.push(DirEntry::new(group[0].data_val().clone(), block.number()));
+                leaf = BTreePage::new(self.tx.clone(), &block, &self.leaf_layout);
+                is_closed = false;
+            }
+            if group.len() <= capacity {
+                for entry in group {
+                    let slot = leaf.get_num_recs()?;
+                    leaf.insert_leaf(slot, entry)?;
+                }
+                continue;
+            }
+            // 1 つの block に収まらない同じ値の record は、先頭の 1 件だけを leaf に残して残りを overflow block に入れる
+            leaf.insert_leaf(0, &group[0])?;
+            let mut prev = BTreePage::new(self.tx.clone(), leaf.block(), &self.leaf_layout);
+            for chunk in group[1..].chunks(capacity) {
+                let block = prev.append_new(-1)?;
+                prev.set_flag(block.number() as i32)?;
+                prev = BTreePage::new(self.tx.clone(), &block, &self.leaf_layout);
+                for (slot, entry) in chunk.iter().enumerate() {
+                    prev.insert_leaf(slot, entry)?;
+                }
+            }
+            is_closed = true;
+        }
+        Ok(dir_entries)
+    }
+
+    // 1 つ下の level の block を指す entries から、directory を下の level から順に作る
+    // 1 つの block に収まった level を root とし、root の block (directory の 0 番目の block) に書き込む
+    fn load_directory(&self, mut entries: Vec<DirEntry>) -> Result<(), IndexError> {
+        let root = BTreePage::new(self.tx.clone(), &self.root_block, &self.dir_layout);
+        let capacity = root.capacity()?;
+        let mut level = 0;
+        while entries.len() > capacity {
+            let mut parents = vec![];
+            for chunk in entries.chunks(capacity) {
+                let block = root.append_new(level)?;
+                let page = BTreePage::new(self.tx.clone(), &block, &self.dir_layout);
+                for (slot, entry) in chunk.iter().enumerate() {
+                    page.insert_dir(slot, entry.data_val(), entry.block_num())?;
+                }
+                parents.push(DirEntry::new(chunk[0].data_val().clone(), block.number()));
+            }
+            entries = parents;
+            level += 1;
+        }
+        // root は leaf の 0 番目の block だけを指す状態で作られているので、その entry を置き換える
+        while root.get_num_recs()? > 0 {
+            root.delete(0)?;
+        }
+        root.set_flag(level)?;
+        for (slot, entry) in entries.iter().enumerate() {
+            root.insert_dir(slot, entry.data_val(), entry.block_num())?;
+        }
+        Ok(())
+    }
+
+    // record が 1 つも無い index かどうか
+    // root が leaf の 0 番目の block だけを指し、その block が空であれば、他の block は使われていない
+    // (rollback した create index が追加した block は、ファイルに残っていても使われない)
+    fn is_empty(&self) -> Result<bool, IndexError> {
+        let root = BTreePage::new(self.tx.clone(), &self.root_block, &self.dir_layout);
+        let leaf = BTreePage::new(
+            self.tx.clone(),
+            &BlockId::new(&self.leaf_filename, 0),
+            &self.leaf_layout,
+        );
+        Ok(root.get_flag()? == 0
+            && root.get_num_recs()? <= 1
+            && leaf.get_flag()? < 0
+            && leaf.get_num_recs()? == 0)
+    }
+
+    // leaf の block と、その overflow block にある範囲内の record を、値の順に cursor に読み込む
+    // overflow block には先頭の record と同じ値の record しか無いので、先頭の record の直後に辿る
+    fn read_range_leaf(
+        &self,
+        cursor: &mut RangeCursor,
+        block_num: usize,
+    ) -> Result<(), IndexError> {
+        let leaf = BTreePage::new(
+            self.tx.clone(),
+            &BlockId::new(&self.leaf_filename, block_num),
+            &self.leaf_layout,
+        );
+        let num_recs = leaf.get_num_recs()?;
+        let mut records = vec![];
+        if num_recs > 0 {
+            records.push(leaf.get_entry(0)?);
+            let mut flag = leaf.get_flag()?;
+            while flag >= 0 {
+                let overflow = BTreePage::new(
+                    self.tx.clone(),
+                    &BlockId::new(&self.leaf_filename, flag as usize),
+                    &self.leaf_layout,
+                );
+                for slot in 0..overflow.get_num_recs()? {
+                    records.push(overflow.get_entry(slot)?);
+                }
+                flag = overflow.get_flag()?;
+            }
+            for slot in 1..num_recs {
+                records.push(leaf.get_entry(slot)?);
+            }
+        }
+        for record in records {
+            if in_range(record.data_val(), &cursor.lo, &cursor.hi) {
+                cursor.records.push_back(record);
+            } else if !in_range(record.data_val(), &cursor.lo, &Bound::Unbounded) {
+                // lo より小さい値は読み飛ばす
+                continue;
+            } else {
+                cursor.finished = true;
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn next_in_range(&mut self) -> Result<bool, IndexError> {
+        let mut cursor = match self.range.take() {
+            Some(cursor) => cursor,
+            None => return Ok(self.leaf_mut()?.next()?),
+        };
+        let result = loop {
+            if let Some(record) = cursor.records.pop_front() {
+                cursor.current = Some(record);
+                break Ok(true);
+            }
+            cursor.current = None;
+            if cursor.finished {
+                break Ok(false);
+            }
+            match cursor.leaf_blocks.pop_front() {
+                Some(block_num) => {
+                    if let Err(err) = self.read_range_leaf(&mut cursor, block_num) {
+                        break Err(err);
+                    }
+                }
+                None => break Ok(false),
+            }
+        };
+        self.range = Some(cursor);
+        result
+    }
+
+    fn leaf(&self) -> Result<&BTreeLeaf, IndexError> {
+        self.leaf.as_ref().ok_or(IndexError::InvalidCall(
+            "no search key is specified for the index. you need to call before_first first"
+                .to_string(),
+        ))
+    }
+
+    // before_first_range で開いた範囲の、今いる record
+    fn range_record(&self) -> Result<&IndexEntry, IndexError> {
+        self.range
+            .as_ref()
+            .and_then(|range| range.current.as_ref())
+            .ok_or(IndexError::InvalidCall(
+                "no record is specified for the index. you need to call next first".to_string(),
+            ))
+    }
+
+    fn leaf_mut(&mut self) -> Result<&mut BTreeLeaf, IndexError> {
+        self.leaf.as_mut().ok_or(IndexError::InvalidCall(
+            "no search key is specified for the index. you need to call before_first first"
+                .to_string(),
+        ))
+    }
+}
+
+impl Index for BTreeIndex {
+    fn before_first(&mut self, search_key: &Constant) -> Result<(), IndexError> {
+        self.tx
+            .borrow()
+            .slock_predicate(&key_lock_block(&self.index_name, search_key))?;
+        // 前に開いていた leaf の pin を先に外す
+        self.leaf = None;
+        self.range = None;
+        let mut root = BTreeDir::new(self.tx.clone(), &self.root_block, &self.dir_layout);
+        let block_num = root.search(search_key)?;
+        let leaf_block = BlockId::new(&self.leaf_filename, block_num);
+        self.leaf = Some(BTreeLeaf::new(
+            self.tx.clone(),
+            &leaf_block,
+            &self.leaf_layout,
+            search_key,
+        )?);
+        Ok(())
+    }
+
+    // 範囲を読む間は key lock を取らず、読んだ directory と leaf の block の slock で、範囲への値の追加・削除を防ぐ
+    // 範囲に入る値を追加・削除する transaction は、その値が入る leaf の block を書き換えるので、slock と衝突する
+    fn before_first_range(
+        &mut self,
+        lo: Bound<Constant>,
+        hi: Bound<Constant>,
+    ) -> Result<(), IndexError> {
+        self.leaf = None;
+        self.range = None;
+        let root = BTreeDir::new(self.tx.clone(), &self.root_block, &self.dir_layout);
+        let leaf_blocks = root.leaf_blocks_in_range(lo.as_ref(), hi.as_ref())?;
+        self.range = Some(RangeCursor {
+            lo,
+            hi,
+            leaf_blocks: leaf_blocks.into(),
+            records: VecDeque::new(),
+            current: None,
+            finished: false,
+        });
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<bool, IndexError> {
+        self.next_in_range()
+    }
+
+    fn get_data_rid(&self) -> Result<Rid, IndexError> {
+        if self.range.is_some() {
+            return Ok(*self.range_record()?.data_rid());
+        }
+        Ok(self.leaf()?.get_data_rid()?)
+    }
+
+    fn get_data_val(&self) -> Result<Constant, IndexError> {
+        if self.range.is_some() {
+            return Ok(self.range_record()?.data_val().clone());
+        }
+        Ok(self.leaf()?.get_data_val()?)
+    }
+
+    fn get_included_val(&self, pos: usize) -> Result<Constant, IndexError> {
+        if self.range.is_some() {
+            return self
+                .range_record()?
+                .included_vals()
+                .get(pos)
+                .cloned()
+                .ok_or_else(|| {
+                    IndexError::InvalidCall(format!("included field {} not found", pos))
+                });
+        }
+        Ok(self.leaf()?.get_included_val(pos)?)
+    }
+
+    fn insert_entry(&mut self, entry: &IndexEntry) -> Result<(), IndexError> {
+        let data_val = entry.data_val();
+        self.tx
+            .borrow()
+            .xlock_predicate(&key_lock_block(&self.index_name, data_val))?;
+        self.before_first(data_val)?;
+        let entry = self.leaf_mut()?.insert(entry)?;
+        self.leaf = None;
+        let entry = match entry {
+            Some(entry) => entry,
+            None => return Ok(()),
+        };
+        let mut root = BTreeDir::new(self.tx.clone(), &self.root_block, &self.dir_layout);
+        if let Some(new_entry) = root.insert(&entry)? {
+            root.make_new_root(&new_entry)?;
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, data_val: &Constant, data_rid: &Rid) -> Result<(), IndexError> {
+        self.tx
+            .borrow()
+            .xlock_predicate(&key_lock_block(&self.index_name, data_val))?;
+        self.before_first(data_val)?;
+        self.leaf_mut()?.delete(data_rid)?;
+        self.leaf = None;
+        Ok(())
+    }
+
+    // 作成中の index は他の transaction から見えないので、key lock は取らない
+    fn bulk_load(&mut self, entries: &[IndexEntry]) -> Result<(), IndexError> {
+        if !self.is_empty()? {
+            return Err(IndexError::InvalidCall(format!(
+                "bulk load requires an empty index, but index {} already has records",
+                self.index_name
+            )));
+        }
+        if entries
+            .windows(2)
+            .any(|pair| pair[0].data_val() > pair[1].data_val())
+        {
+            return Err(IndexError::InvalidCall(
+                "entries for bulk load must be sorted by value".to_string(),
+            ));
+        }
+        // 空の index はすでに作成されているので、そのまま使う
+        if entries.is_empty() {
+            return Ok(());
+        }
+        self.leaf = None;
+        self.range = None;
+        let dir_entries = self.load_leaves(entries)?;
+        self.load_directory(dir_entries)
+    }
+}
+
+#[cfg(test)]
+mod btree_index_test {
+    use super::*;
+    use crate::{
+        buffer::buffer_manager::BufferManager,
+        file::file_manager::FileManager,
+        index::common::index_layout,
+        log::log_manager::LogManager,
+        tx::{concurrency::lock_table::LockTable, transaction::TransactionFactory},
+    };
+    use std::sync::Arc;
+    use tempfile::{tempdir, TempDir};
+
+    fn setup_factory(dir: &TempDir) -> TransactionFactory {
+        let file_manager = Arc::new(FileManager::new(dir.path(), 200));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager.clone(),
+            log_manager.clone(),
+            8,
+            Some(10),
+        ));
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        TransactionFactory::new(file_manager, log_manager, buffer_manager, lock_table)
+    }
+
+    fn lookup(index: &mut BTreeIndex, key: &Constant) -> Vec<Rid> {
+        index.before_first(key).unwrap();
+        let mut rids = vec![];
+        while index.next().unwrap() {
+            rids.push(index.get_data_rid().unwrap());
+        }
+        rids.sort_by_key(|rid| (rid.block_number(), rid.slot()));
+        rids
+    }
+
+    #[test]
+    fn test_insert_search_and_delete() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let layout = index_layout(FieldInfo::Integer, &[]).unwrap();
+        let mut index = BTreeIndex::new(tx.clone(), "idx", &layout).unwrap();
+
+        // leaf と directory の block が何度も分割されるように、ばらばらの順番で入れる
+        let n: usize = 500;
+        for i in 0..n {
+            let key = (i * 37) % n;
+            index
+                .insert_entry(&IndexEntry::new(
+                    Constant::Int((key % 100) as i32),
+                    Rid::new(key, Some(0)),
+                ))
+                .unwrap();
+        }
+        assert!(tx.borrow().size("idx.leaf").unwrap() > 1);
+        assert!(tx.borrow().size("idx.dir").unwrap() > 1);
+
+        for key in [0, 42, 99] {
+            let expected: Vec<Rid> = (0..n / 100)
+                .map(|i| Rid::new(i * 100 + key, Some(0)))
+                .collect();
+            assert_eq!(lookup(&mut index, &Constant::Int(key as i32)), expected);
+        }
+        assert!(lookup(&mut index, &Constant::Int(100)).is_empty());
+        assert!(lookup(&mut index, &Constant::Int(-1)).is_empty());
+
+        // 削除した record だけが見つからなくなる。存在しない record の削除は何もしない
+        index
+            .delete(&Constant::Int(42), &Rid::new(142, Some(0)))
+            .unwrap();
+        index
+            .delete(&Constant::Int(42), &Rid::new(143, Some(0)))
+            .unwrap();
+        assert_eq!(
+            lookup(&mut index, &Constant::Int(42)),
+            vec![
+                Rid::new(42, Some(0)),
+                Rid::new(242, Some(0)),
+                Rid::new(342, Some(0)),
+                Rid::new(442, Some(0)),
+            ]
+        );
+        drop(index);
+
+        // すべての pin が外れている
+        assert!(tx.borrow().pinned_blocks().is_empty());
+        tx.borrow_mut().commit().unwrap();
+
+        // 作成済みの index を開き直しても同じ内容が読める
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let mut index = BTreeIndex::new(tx.clone(), "idx", &layout).unwrap();
+        assert_eq!(lookup(&mut index, &Constant::Int(42)).len(), 4);
+        drop(index);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_bulk_load() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let layout = index_layout(FieldInfo::Integer, &[]).unwrap();
+        let mut index = BTreeIndex::new(tx.clone(), "idx", &layout).unwrap();
+
+        // 7 は 1 つの leaf に収まらない数だけ入れて、overflow block を使わせる
+        let mut entries: Vec<IndexEntry> = (0..600)
+            .map(|i| IndexEntry::new(Constant::Int(i % 150), Rid::new(i as usize, Some(0))))
+            .chain((0..40).map(|i| IndexEntry::new(Constant::Int(7), Rid::new(1000 + i, Some(1)))))
+            .collect();
+        entries.sort_by(|lhs, rhs| lhs.data_val().cmp(rhs.data_val()));
+        index.bulk_load(&entries).unwrap();
+        assert!(tx.borrow().size("idx.leaf").unwrap() > 1);
+        assert!(tx.borrow().size("idx.dir").unwrap() > 1);
+        assert_eq!(
+            BTreeIndex::entries(&tx, "idx", &layout).unwrap().len(),
+            entries.len()
+        );
+
+        for key in [0, 7, 42, 149] {
+            let mut expected: Vec<Rid> = entries
+                .iter()
+                .filter(|entry| *entry.data_val() == Constant::Int(key))
+                .map(|entry| *entry.data_rid())
+                .collect();
+            expected.sort_by_key(|rid| (rid.block_number(), rid.slot()));
+            assert_eq!(lookup(&mut index, &Constant::Int(key)), expected);
+        }
+        assert!(lookup(&mut index, &Constant::Int(150)).is_empty());
+        assert!(lookup(&mut index, &Constant::Int(-1)).is_empty());
+
+        // bulk load した後も、通常の insert と delete ができる
+        index
+            .insert_entry(&IndexEntry::new(Constant::Int(42), Rid::new(2000, Some(0))))
+            .unwrap();
+        index
+            .delete(&Constant::Int(42), &Rid::new(42, Some(0)))
+            .unwrap();
+        assert_eq!(
+            lookup(&mut index, &Constant::Int(42)),
+            vec![
+                Rid::new(192, Some(0)),
+                Rid::new(342, Some(0)),
+                Rid::new(492, Some(0)),
+                Rid::new(2000, Some(0)),
+            ]
+        );
+
+        // 空でない index には bulk load できない
+        assert!(index.bulk_load(&entries).is_err());
+        drop(index);
+        assert!(tx.borrow().pinned_blocks().is_empty());
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_included_vals() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let layout = index_layout(
+            FieldInfo::Integer,
+            &[FieldInfo::String(8), FieldInfo::Integer],
+        )
+        .unwrap();
+        let mut index = BTreeIndex::new(tx.clone(), "idx", &layout).unwrap();
+
+        // leaf が分割されるだけの record を入れても、include した値が key と一緒に移動する
+        let entry = |i: usize| {
+            IndexEntry::new(Constant::Int((i % 50) as i32), Rid::new(i, Some(0)))
+                .with_included_vals(vec![
+                    Constant::from(format!("n{}", i)),
+                    Constant::Int(i as i32 * 10),
+                ])
+        };
+        for i in 0..200 {
+            index.insert_entry(&entry(i)).unwrap();
+        }
+        assert!(tx.borrow().size("idx.leaf").unwrap() > 1);
+
+        index.before_first(&Constant::Int(7)).unwrap();
+        let mut found = vec![];
+        while index.next().unwrap() {
+            assert_eq!(index.get_data_val().unwrap(), Constant::Int(7));
+            let block = index.get_data_rid().unwrap().block_number();
+            assert_eq!(
+                index.get_included_val(0).unwrap(),
+                Constant::from(format!("n{}", block))
+            );
+            assert_eq!(
+                index.get_included_val(1).unwrap(),
+                Constant::Int(block as i32 * 10)
+            );
+            assert!(index.get_included_val(2).is_err());
+            found.push(block);
+        }
+        found.sort();
+        assert_eq!(found, vec![7, 57, 107, 157]);
+
+        // 範囲の検索でも、include した値を読める
+        index
+            .before_first_range(Bound::Included(Constant::Int(48)), Bound::Unbounded)
+            .unwrap();
+        let mut count = 0;
+        while index.next().unwrap() {
+            let block = index.get_data_rid().unwrap().block_number();
+            assert_eq!(
+                index.get_included_val(1).unwrap(),
+                Constant::Int(block as i32 * 10)
+            );
+            count += 1;
+        }
+        assert_eq!(count, 8);
+
+        // include した field の数が layout と合わない entry は追加できない
+        assert!(index
+            .insert_entry(&IndexEntry::new(Constant::Int(1), Rid::new(500, Some(0))))
+            .is_err());
+        drop(index);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_range_search() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let layout = index_layout(FieldInfo::Integer, &[]).unwrap();
+        let mut index = BTreeIndex::new(tx.clone(), "idx", &layout).unwrap();
+
+        // leaf が分割され、50 は overflow block を使うように入れる
+        for i in 0..300 {
+            index
+                .insert_entry(&IndexEntry::new(
+                    Constant::Int(i % 100),
+                    Rid::new(i as usize, Some(0)),
+                ))
+                .unwrap();
+        }
+        for i in 0..40 {
+            index
+                .insert_entry(&IndexEntry::new(
+                    Constant::Int(50),
+                    Rid::new(1000 + i, Some(0)),
+                ))
+                .unwrap();
+        }
+        assert!(tx.borrow().size("idx.leaf").unwrap() > 2);
+
+        let search = |index: &mut BTreeIndex, lo: Bound<i32>, hi: Bound<i32>| {
+            index
+                .before_first_range(lo.map(Constant::Int), hi.map(Constant::Int))
+                .unwrap();
+            let mut blocks = vec![];
+            while index.next().unwrap() {
+                blocks.push(index.get_data_rid().unwrap().block_number());
+            }
+            blocks
+        };
+        // 値の順に返すので、Rid の block 番号 (を 100 で割った余り) は昇順に並ぶ
+        let blocks = search(&mut index, Bound::Excluded(47), Bound::Included(52));
+        let keys: Vec<usize> = blocks
+            .iter()
+            .map(|block| if *block >= 1000 { 50 } else { block % 100 })
+            .collect();
+        assert_eq!(keys.len(), 5 * 3 + 40);
+        assert!(keys.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(keys.first(), Some(&48));
+        assert_eq!(keys.last(), Some(&52));
+
+        assert_eq!(
+            search(&mut index, Bound::Unbounded, Bound::Excluded(2)).len(),
+            6
+        );
+        assert_eq!(
+            search(&mut index, Bound::Included(98), Bound::Unbounded).len(),
+            6
+        );
+        assert_eq!(
+            search(&mut index, Bound::Unbounded, Bound::Unbounded).len(),
+            340
+        );
+        assert!(search(&mut index, Bound::Excluded(99), Bound::Unbounded).is_empty());
+        assert!(search(&mut index, Bound::Included(10), Bound::Excluded(10)).is_empty());
+
+        // 範囲の検索の後も、値を指定した検索ができる
+        assert_eq!(lookup(&mut index, &Constant::Int(3)).len(), 3);
+        drop(index);
+        assert!(tx.borrow().pinned_blocks().is_empty());
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_many_duplicates_use_overflow_blocks() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let layout = index_layout(FieldInfo::String(4), &[]).unwrap();
+        let mut index = BTreeIndex::new(tx.clone(), "idx", &layout).unwrap();
+
+        // 1 つの block に収まらない数の同じ値を入れる
+        for i in 0..50 {
+            index
+                .insert_entry(&IndexEntry::new(
+                    Constant::from("same"),
+                    Rid::new(i, Some(i)),
+                ))
+                .unwrap();
+        }
+        index
+            .insert_entry(&IndexEntry::new(
+                Constant::from("a"),
+                Rid::new(100, Some(0)),
+            ))
+            .unwrap();
+        index
+            .insert_entry(&IndexEntry::new(
+                Constant::from("z"),
+                Rid::new(101, Some(0)),
+            ))
+            .unwrap();
+
+        assert_eq!(lookup(&mut index, &Constant::from("same")).len(), 50);
+        assert_eq!(
+            lookup(&mut index, &Constant::from("a")),
+            vec![Rid::new(100, Some(0))]
+        );
+        assert_eq!(
+            lookup(&mut index, &Constant::from("z")),
+            vec![Rid::new(101, Some(0))]
+        );
+
+        drop(index);
+
+        // before_first を呼ぶ前は読めない
+        let index = BTreeIndex::new(tx.clone(), "idx", &layout).unwrap();
+        assert!(index.get_data_rid().is_err());
+        drop(index);
+        tx.borrow_mut().commit().unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
+
+use crate::{
+    file::blockid::BlockId,
+    query::constant::Constant,
+    record::{layout::Layout, rid::Rid},
+    tx::transaction::Transaction,
+};
+
+use super::{
+    btree_dir::DirEntry,
+    btree_page::{BTreePage, BTreePageError},
+    common::IndexEntry,
+};
+
+/**
+ * B-Tree の leaf の block を読み書きするための構造体
+ *
+ * 作成時に指定した search_key を持つ record を、overflow block も含めて順に辿る
+ * 同じ値の record が 1 つの block に収まらない場合は、先頭の 1 件だけを残して残りを overflow block に移す
+ */
+pub struct BTreeLeaf {
+    tx: Rc<RefCell<Transaction>>,
+    layout: Layout,
+    search_key: Constant,
+    contents: BTreePage,
+    // 今いる slot。None の場合は block の最初の record の直前にいる
+    current_slot: Option<usize>,
+}
+
+impl BTreeLeaf {
+    pub fn new(
+        tx: Rc<RefCell<Transaction>>,
+        block: &BlockId,
+        layout: &Layout,
+        search_key: &Constant,
+    ) -> Result<Self, BTreePageError> {
+        let contents = BTreePage::new(tx.clone(), block, layout);
+        let current_slot = contents.find_slot_before(search_key)?;
+        Ok(BTreeLeaf {
+            tx,
+            layout: layout.clone(),
+            search_key: search_key.clone(),
+            contents,
+            current_slot,
+        })
+    }
+
+    /// search_key を持つ次の record に移動する。もう無い場合は false を返す
+    pub fn next(&mut self) -> Result<bool, BTreePageError> {
+        let slot = self.current_slot.map_or(0, |slot| slot + 1);
+        self.current_slot = Some(slot);
+        if slot >= self.contents.get_num_recs()? {
+            self.try_overflow()
+        } else if self.contents.compare_data_val(slot, &self.search_key)? == Some(Ordering::Equal) {
+            Ok(true)
+        } else {
+            self.try_overflow()
+        }
+    }
+
+    pub fn get_data_rid(&self) -> Result<Rid, BTreePageError> {
+        self.contents.get_data_rid(self.current_slot()?)
+    }
+
+    pub fn get_data_val(&self) -> Result<Constant, BTreePageError> {
+        self.contents.get_data_val(self.current_slot()?)
+    }
+
+    /// 今いる record に保存している、include で指定した pos 番目の field の値を返す
+    pub fn get_included_val(&self, pos: usize) -> Result<Constant, BTreePageError> {
+        self.contents.get_included_val(self.current_slot()?, pos)
+    }
+
+    fn current_slot(&self) -> Result<usize, BTreePageError> {
+        self.current_slot.ok_or(BTreePageError::InvalidCall(
+            "no record is specified for the leaf. you need to call next first".to_string(),
+        ))
+    }
+
+    /// search_key を持つ record のうち、data の record が data_rid のものを削除する
+    /// 削除できた場合は true を返す
+    pub fn delete(&mut self, data_rid: &Rid) -> Result<bool, BTreePageError> {
+        while self.next()? {
+            if self.get_data_rid()? == *data_rid {
+                // next で true を返した時点で current_slot は必ず Some になっている
+                if let Some(slot) = self.current_slot {
+                    self.contents.delete(slot)?;
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// 値が search_key の entry を追加する
+    /// block が分割された場合は、新しい block を指す directory の entry を返す
+    pub fn insert(&mut self, entry: &IndexEntry) -> Result<Option<DirEntry>, BTreePageError> {
+        // overflow block を持つ block の先頭の値より小さい値を入れる場合は、
+        // 既存の record をすべて新しい block に移して、この block には入れる値だけを残す
+        if self.contents.get_flag()? >= 0 && self.contents.get_data_val(0)? > self.search_key {
+            let first_val = self.contents.get_data_val(0)?;
+            let new_block = self.contents.split(0, self.contents.get_flag()?)?;
+            self.current_slot = Some(0);
+            self.contents.set_flag(-1)?;
+            self.contents.insert_leaf(0, entry)?;
+            return Ok(Some(DirEntry::new(first_val, new_block.number())));
+        }
+
+        let slot = self.current_slot.map_or(0, |slot| slot + 1);
+        self.current_slot = Some(slot);
+        self.contents.insert_leaf(slot, entry)?;
+        if !self.contents.is_full()? {
+            return Ok(None);
+        }
+
+        // block が一杯になったので分割する
+        let num_recs = self.contents.get_num_recs()?;
+        let first_key = self.contents.get_data_val(0)?;
+        let last_key = self.contents.get_data_val(num_recs - 1)?;
+        if first_key == last_key {
+            // すべて同じ値なので、先頭以外の record を overflow block に移す
+            let new_block = self.contents.split(1, self.contents.get_flag()?)?;
+            self.contents.set_flag(new_block.number() as i32)?;
+            return Ok(None);
+        }
+        // 同じ値の record が 2 つの block に分かれないように分割する位置を決める
+        let mut split_pos = num_recs / 2;
+        let mut split_key = self.contents.get_data_val(split_pos)?;
+        if split_key == first_key {
+            // 右に向かって、次の値を探す
+            while self.contents.get_data_val(split_pos)? == split_key {
+                split_pos += 1;
+            }
+            split_key = self.contents.get_data_val(split_pos)?;
+        } else {
+            // 左に向かって、その値を持つ最初の record を探す
+            while self.contents.get_data_val(split_pos - 1)? == split_key {
+                split_pos -= 1;
+            }
+        }
+        let new_block = self.contents.split(split_pos, -1)?;
+        Ok(Some(DirEntry::new(split_key, new_block.number())))
+    }
+
+    // 今の block に search_key を持つ record がもう無い場合に、overflow block に移動する
+    fn try_overflow(&mut self) -> Result<bool, BTreePageError> {
+        if self.contents.get_num_recs()? == 0 {
+            return Ok(false);
+        }
+        let first_key = self.contents.get_data_val(0)?;
+        let flag = self.contents.get_flag()?;
+        if self.search_key != first_key || flag < 0 {
+            return Ok(false);
+        }
+        let next_block = BlockId::new(self.contents.block().file_name(), flag as usize);
+        self.contents = BTreePage::new(self.tx.clone(), &next_block, &self.layout);
+        self.current_slot = Some(0);
+        Ok(true)
+    }
+}
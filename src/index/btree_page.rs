@@ -0,0 +1,377 @@
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
+
+use thiserror::Error;
+
+use crate::{
+    constants::INTEGER_BYTE_LEN,
+    file::{blockid::BlockId, file_manager::FileManagerError},
+    query::{constant::Constant, date::Date},
+    record::{layout::Layout, rid::Rid, schema::FieldInfo},
+    tx::transaction::{
+        Transaction, TransactionGetError, TransactionSetError, TransactionSizeError,
+    },
+};
+
+use super::common::{
+    included_field_count, index_included_field, IndexEntry, INDEX_BLOCK_FIELD, INDEX_DATAVAL_FIELD,
+    INDEX_ID_FIELD,
+};
+
+// block の先頭にある flag の位置
+const FLAG_OFFSET: usize = 0;
+// block の先頭にある、block に入っている record 数の位置
+const NUM_RECS_OFFSET: usize = INTEGER_BYTE_LEN;
+// block の header の大きさ。最初の slot はこの位置から始まる
+const HEADER_SIZE: usize = 2 * INTEGER_BYTE_LEN;
+
+/**
+ * B-Tree の directory と leaf の block の中身を読み書きするための構造体
+ *
+ * block の先頭に flag と record 数を保存し、その後ろに layout に従った record を値の昇順に詰めて並べる
+ * flag は directory では block の level (leaf を直接指す block が 0)、leaf では overflow block の番号 (無い場合は -1) を表す
+ * slot の並びは layout の slot をそのまま使うので、各 slot の先頭の flag の領域は使わない
+ */
+pub struct BTreePage {
+    tx: Rc<RefCell<Transaction>>,
+    block: BlockId,
+    layout: Layout,
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum BTreePageError {
+    #[error("invalid call error: {0}")]
+    InvalidCall(String),
+    #[error("transaction get error: {0}")]
+    TransactionGet(#[from] TransactionGetError),
+    #[error("transaction set error: {0}")]
+    TransactionSet(#[from] TransactionSetError),
+    #[error("transaction size error: {0}")]
+    TransactionSize(#[from] TransactionSizeError),
+    #[error("file manager error: {0}")]
+    FileManager(#[from] FileManagerError),
+}
+
+impl Drop for BTreePage {
+    fn drop(&mut self) {
+        // new で pin した block を unpin する
+        self.tx.borrow().unpin(&self.block).unwrap();
+    }
+}
+
+impl BTreePage {
+    pub fn new(tx: Rc<RefCell<Transaction>>, block: &BlockId, layout: &Layout) -> Self {
+        let page = BTreePage {
+            tx,
+            block: block.clone(),
+            layout: layout.clone(),
+        };
+        page.tx.borrow().pin(block).unwrap();
+        page
+    }
+
+    pub fn block(&self) -> &BlockId {
+        &self.block
+    }
+
+    /// 値が search_key より小さい record のうち、最後のものの slot を返す
+    /// そのような record が無い場合は None を返す
+    pub fn find_slot_before(&self, search_key: &Constant) -> Result<Option<usize>, BTreePageError> {
+        let num_recs = self.get_num_recs()?;
+        let mut slot = 0;
+        while slot < num_recs && self.compare_data_val(slot, search_key)? == Some(Ordering::Less) {
+            slot += 1;
+        }
+        Ok(slot.checked_sub(1))
+    }
+
+    /// もう 1 つ record を追加すると block に収まらないかどうか
+    pub fn is_full(&self) -> Result<bool, BTreePageError> {
+        let block_size = self.tx.borrow().block_size_of(self.block.file_name())?;
+        Ok(self.slot_position(self.get_num_recs()? + 1) >= block_size)
+    }
+
+    /// 分割せずに block に入れておける record の最大数。これより多く入れると is_full が true になる
+    pub fn capacity(&self) -> Result<usize, BTreePageError> {
+        let block_size = self.tx.borrow().block_size_of(self.block.file_name())?;
+        let mut capacity = 0;
+        while self.slot_position(capacity + 2) < block_size {
+            capacity += 1;
+        }
+        Ok(capacity)
+    }
+
+    /// split_pos 以降の record を新しい block に移し、その block を返す
+    /// 新しい block の flag は flag にする
+    pub fn split(&self, split_pos: usize, flag: i32) -> Result<BlockId, BTreePageError> {
+        let new_block = self.append_new(flag)?;
+        let new_page = BTreePage::new(self.tx.clone(), &new_block, &self.layout);
+        self.transfer_records(split_pos, &new_page)?;
+        Ok(new_block)
+    }
+
+    pub fn get_data_val(&self, slot: usize) -> Result<Constant, BTreePageError> {
+        self.get_val(slot, INDEX_DATAVAL_FIELD)
+    }
+
+    /// slot の値を val と比べる。get_data_val の値を比べるのと同じ結果になる
+    /// 探索中に何度も呼ばれるので、string の値は String を作らずに page の上で比べる
+    pub fn compare_data_val(
+        &self,
+        slot: usize,
+        val: &Constant,
+    ) -> Result<Option<Ordering>, BTreePageError> {
+        if let (Some(FieldInfo::String(_)), Constant::String(val)) =
+            (self.layout.schema().info(INDEX_DATAVAL_FIELD), val)
+        {
+            let offset = self.field_position(slot, INDEX_DATAVAL_FIELD)?;
+            let ordering = self
+                .tx
+                .borrow()
+                .read_str(&self.block, offset, |s| s.cmp(val.as_str()))?;
+            return Ok(Some(ordering));
+        }
+        Ok(self.get_data_val(slot)?.partial_cmp(val))
+    }
+
+    pub fn get_flag(&self) -> Result<i32, BTreePageError> {
+        Ok(self.tx.borrow().get_int(&self.block, FLAG_OFFSET)?)
+    }
+
+    pub fn set_flag(&self, flag: i32) -> Result<(), BTreePageError> {
+        self.tx
+            .borrow()
+            .set_int(&self.block, FLAG_OFFSET, flag, true)?;
+        Ok(())
+    }
+
+    /// file の末尾に、flag を設定した空の block を追加する
+    pub fn append_new(&self, flag: i32) -> Result<BlockId, BTreePageError> {
+        let block = self.tx.borrow().append(self.block.file_name())?;
+        let page = BTreePage::new(self.tx.clone(), &block, &self.layout);
+        page.format(flag)?;
+        Ok(block)
+    }
+
+    /// block を、flag を設定した空の状態に初期化する。ここで施した変更は log には保存しない
+    pub fn format(&self, flag: i32) -> Result<(), BTreePageError> {
+        let tx = self.tx.borrow();
+        tx.set_int(&self.block, FLAG_OFFSET, flag, false)?;
+        tx.set_int(&self.block, NUM_RECS_OFFSET, 0, false)?;
+        let block_size = tx.block_size_of(self.block.file_name())?;
+        let mut slot = 0;
+        while self.slot_position(slot + 1) <= block_size {
+            for (field, info) in self.layout.schema().infos() {
+                let offset = self.field_position(slot, field)?;
+                match info {
+                    FieldInfo::Integer | FieldInfo::Boolean | FieldInfo::Date => {
+                        tx.set_int(&self.block, offset, 0, false)?
+                    }
+                    FieldInfo::BigInt => tx.set_bigint(&self.block, offset, 0, false)?,
+                    FieldInfo::Double => tx.set_double(&self.block, offset, 0.0, false)?,
+                    FieldInfo::String(_) => tx.set_string(&self.block, offset, "", false)?,
+                }
+            }
+            slot += 1;
+        }
+        Ok(())
+    }
+
+    /// directory の slot にある、子の block の番号を返す
+    pub fn get_child_num(&self, slot: usize) -> Result<usize, BTreePageError> {
+        Ok(self.get_int(slot, INDEX_BLOCK_FIELD)? as usize)
+    }
+
+    /// directory の slot に、値が val で子の block の番号が block_num の record を挿入する
+    pub fn insert_dir(
+        &self,
+        slot: usize,
+        val: &Constant,
+        block_num: usize,
+    ) -> Result<(), BTreePageError> {
+        self.insert(slot)?;
+        self.set_val(slot, INDEX_DATAVAL_FIELD, val)?;
+        self.set_int(slot, INDEX_BLOCK_FIELD, block_num as i32)?;
+        Ok(())
+    }
+
+    /// leaf の slot にある、data の record の Rid を返す
+    pub fn get_data_rid(&self, slot: usize) -> Result<Rid, BTreePageError> {
+        let block_num = self.get_int(slot, INDEX_BLOCK_FIELD)? as usize;
+        let id = self.get_int(slot, INDEX_ID_FIELD)?;
+        Ok(Rid::new(block_num, usize::try_from(id).ok()))
+    }
+
+    /// leaf の slot にある、include で指定した pos 番目の field の値を返す
+    pub fn get_included_val(&self, slot: usize, pos: usize) -> Result<Constant, BTreePageError> {
+        self.get_val(slot, &index_included_field(pos))
+    }
+
+    /// leaf の slot にある record を、include で指定した field の値も含めて返す
+    pub fn get_entry(&self, slot: usize) -> Result<IndexEntry, BTreePageError> {
+        let included_vals = (0..included_field_count(&self.layout))
+            .map(|pos| self.get_included_val(slot, pos))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(
+            IndexEntry::new(self.get_data_val(slot)?, self.get_data_rid(slot)?)
+                .with_included_vals(included_vals),
+        )
+    }
+
+    /// leaf の slot に entry を挿入する
+    pub fn insert_leaf(&self, slot: usize, entry: &IndexEntry) -> Result<(), BTreePageError> {
+        if entry.included_vals().len() != included_field_count(&self.layout) {
+            return Err(BTreePageError::InvalidCall(format!(
+                "the index stores {} included fields, but the entry has {} values",
+                included_field_count(&self.layout),
+                entry.included_vals().len()
+            )));
+        }
+        self.insert(slot)?;
+        self.set_val(slot, INDEX_DATAVAL_FIELD, entry.data_val())?;
+        let rid = entry.data_rid();
+        self.set_int(slot, INDEX_BLOCK_FIELD, rid.block_number() as i32)?;
+        // slot を持たない Rid は -1 として保存する
+        let id = rid.slot().map_or(-1, |slot| slot as i32);
+        self.set_int(slot, INDEX_ID_FIELD, id)?;
+        for (pos, val) in entry.included_vals().iter().enumerate() {
+            self.set_val(slot, &index_included_field(pos), val)?;
+        }
+        Ok(())
+    }
+
+    /// slot の record を削除し、後ろの record を 1 つずつ前に詰める
+    pub fn delete(&self, slot: usize) -> Result<(), BTreePageError> {
+        let num_recs = self.get_num_recs()?;
+        for i in slot + 1..num_recs {
+            self.copy_record(i, i - 1)?;
+        }
+        self.set_num_recs(num_recs - 1)
+    }
+
+    pub fn get_num_recs(&self) -> Result<usize, BTreePageError> {
+        Ok(self.tx.borrow().get_int(&self.block, NUM_RECS_OFFSET)? as usize)
+    }
+
+    // slot 以降の record を 1 つずつ後ろにずらして、slot を空ける
+    fn insert(&self, slot: usize) -> Result<(), BTreePageError> {
+        let num_recs = self.get_num_recs()?;
+        for i in (slot..num_recs).rev() {
+            self.copy_record(i, i + 1)?;
+        }
+        self.set_num_recs(num_recs + 1)
+    }
+
+    fn copy_record(&self, from: usize, to: usize) -> Result<(), BTreePageError> {
+        for field in self.layout.schema().fields_iter() {
+            let val = self.get_val(from, field)?;
+            self.set_val(to, field, &val)?;
+        }
+        Ok(())
+    }
+
+    // split_pos 以降の record を dest の先頭から順に移す
+    fn transfer_records(&self, split_pos: usize, dest: &BTreePage) -> Result<(), BTreePageError> {
+        let mut dest_slot = 0;
+        while split_pos < self.get_num_recs()? {
+            dest.insert(dest_slot)?;
+            for field in self.layout.schema().fields_iter() {
+                let val = self.get_val(split_pos, field)?;
+                dest.set_val(dest_slot, field, &val)?;
+            }
+            self.delete(split_pos)?;
+            dest_slot += 1;
+        }
+        Ok(())
+    }
+
+    fn set_num_recs(&self, num_recs: usize) -> Result<(), BTreePageError> {
+        self.tx
+            .borrow()
+            .set_int(&self.block, NUM_RECS_OFFSET, num_recs as i32, true)?;
+        Ok(())
+    }
+
+    fn get_int(&self, slot: usize, field_name: &str) -> Result<i32, BTreePageError> {
+        let offset = self.field_position(slot, field_name)?;
+        Ok(self.tx.borrow().get_int(&self.block, offset)?)
+    }
+
+    fn set_int(&self, slot: usize, field_name: &str, val: i32) -> Result<(), BTreePageError> {
+        let offset = self.field_position(slot, field_name)?;
+        self.tx.borrow().set_int(&self.block, offset, val, true)?;
+        Ok(())
+    }
+
+    fn get_val(&self, slot: usize, field_name: &str) -> Result<Constant, BTreePageError> {
+        let offset = self.field_position(slot, field_name)?;
+        let tx = self.tx.borrow();
+        match self.layout.schema().info(field_name) {
+            Some(FieldInfo::Integer) => Ok(Constant::Int(tx.get_int(&self.block, offset)?)),
+            Some(FieldInfo::String(_)) => Ok(Constant::String(tx.get_string(&self.block, offset)?)),
+            Some(FieldInfo::Boolean) => Ok(Constant::Bool(tx.get_int(&self.block, offset)? != 0)),
+            Some(FieldInfo::BigInt) => Ok(Constant::BigInt(tx.get_bigint(&self.block, offset)?)),
+            Some(FieldInfo::Date) => Ok(Constant::Date(Date::from_days(
+                tx.get_int(&self.block, offset)?,
+            ))),
+            Some(FieldInfo::Double) => Ok(Constant::Double(tx.get_double(&self.block, offset)?)),
+            None => Err(BTreePageError::InvalidCall(format!(
+                "field {} not found",
+                field_name
+            ))),
+        }
+    }
+
+    fn set_val(&self, slot: usize, field_name: &str, val: &Constant) -> Result<(), BTreePageError> {
+        let offset = self.field_position(slot, field_name)?;
+        let tx = self.tx.borrow();
+        match (self.layout.schema().info(field_name), val) {
+            (Some(FieldInfo::Integer), Constant::Int(val)) => {
+                tx.set_int(&self.block, offset, *val, true)?
+            }
+            (Some(FieldInfo::String(len)), Constant::String(val)) => {
+                if val.chars().count() > len {
+                    return Err(BTreePageError::InvalidCall(format!(
+                        "string is too long. field: {}, len: {}, val: {}",
+                        field_name, len, val
+                    )));
+                }
+                tx.set_string(&self.block, offset, val, true)?
+            }
+            (Some(FieldInfo::Boolean), Constant::Bool(val)) => {
+                tx.set_int(&self.block, offset, *val as i32, true)?
+            }
+            (Some(FieldInfo::BigInt), Constant::BigInt(val)) => {
+                tx.set_bigint(&self.block, offset, *val, true)?
+            }
+            (Some(FieldInfo::Date), Constant::Date(val)) => {
+                tx.set_int(&self.block, offset, val.days(), true)?
+            }
+            (Some(FieldInfo::Double), Constant::Double(val)) => {
+                tx.set_double(&self.block, offset, *val, true)?
+            }
+            _ => {
+                return Err(BTreePageError::InvalidCall(format!(
+                    "value {} does not match the type of field {}",
+                    val, field_name
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    // block の先頭から、slot の先頭までの byte 数
+    fn slot_position(&self, slot: usize) -> usize {
+        HEADER_SIZE + self.layout.slot_offset(slot)
+    }
+
+    fn field_position(&self, slot: usize, field_name: &str) -> Result<usize, BTreePageError> {
+        let offset = self
+            .layout
+            .offset(field_name)
+            .ok_or(BTreePageError::InvalidCall(format!(
+                "field {} not found",
+                field_name
+            )))?;
+        Ok(self.slot_position(slot) + offset)
+    }
+}
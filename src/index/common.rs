@@ -0,0 +1,256 @@
+use std::ops::Bound;
+
+#[cfg(test)]
+use mockall::automock;
+use thiserror::Error;
+
+use crate::{
+    buffer::buffer_manager::BufferManagerError,
+    file::blockid::BlockId,
+    query::constant::Constant,
+    record::{
+        layout::{Layout, LayoutError},
+        partition::stable_hash,
+        rid::Rid,
+        schema::{FieldInfo, Schema},
+        table_scan_factory::TableScanFactoryError,
+    },
+    tx::transaction::{TransactionGetError, TransactionSetError, TransactionSizeError},
+};
+
+use super::btree_page::BTreePageError;
+
+/// index の record で、index を張った field の値を保存する field
+pub const INDEX_DATAVAL_FIELD: &str = "dataval";
+/// index の record で、data の record がある block の番号 (directory では子の block の番号) を保存する field
+pub const INDEX_BLOCK_FIELD: &str = "block";
+/// index の record で、data の record の slot を保存する field
+pub const INDEX_ID_FIELD: &str = "id";
+// index の record で、include で指定した field の値を保存する field の名前の prefix。後ろに field の順番を付ける
+const INDEX_INCLUDED_FIELD_PREFIX: &str = "included";
+
+#[derive(Error, Debug)]
+pub(crate) enum IndexError {
+    #[error("invalid call error: {0}")]
+    InvalidCall(String),
+    #[error("btree page error: {0}")]
+    BTreePage(#[from] BTreePageError),
+    #[error("transaction size error: {0}")]
+    TransactionSize(#[from] TransactionSizeError),
+    #[error("layout error: {0}")]
+    Layout(#[from] LayoutError),
+    #[error("table scan factory error: {0}")]
+    TableScanFactory(#[from] TableScanFactoryError),
+    #[error("scan error: {0}")]
+    Scan(anyhow::Error),
+    #[error("key lock error: {0}")]
+    KeyLock(#[from] TransactionGetError),
+    #[error("key lock error: {0}")]
+    KeyLockExclusive(#[from] TransactionSetError),
+    #[error("buffer manager error: {0}")]
+    BufferManager(#[from] BufferManagerError),
+}
+
+/// index の種類
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug)]
+pub enum IndexType {
+    /// 範囲の検索にも使える B-Tree による index
+    #[default]
+    BTree = 0,
+    /// 一致する値の検索にしか使えないが、検索で読む block の少ない静的 hash による index
+    Hash = 1,
+}
+
+impl IndexType {
+    pub fn from_i32(n: i32) -> Option<IndexType> {
+        match n {
+            0 => Some(IndexType::BTree),
+            1 => Some(IndexType::Hash),
+            _ => None,
+        }
+    }
+
+    /// 値の範囲で検索できるかどうか
+    pub fn supports_range(&self) -> bool {
+        matches!(self, IndexType::BTree)
+    }
+}
+
+/// 型が key_info の field に張る index の record の layout を返す
+/// include で指定した field (型は included_infos) の値を保存する field も加える
+/// 元の table の field 名ではなく順番で名前を付けるので、field 名を変更しても index のファイルはそのまま使える
+/// B-Tree の leaf と hash index の bucket は、どちらもこの layout で record を保存する
+pub fn index_layout(
+    key_info: FieldInfo,
+    included_infos: &[FieldInfo],
+) -> Result<Layout, LayoutError> {
+    let mut schema = Schema::new();
+    schema.add_field(INDEX_BLOCK_FIELD, FieldInfo::Integer);
+    schema.add_field(INDEX_ID_FIELD, FieldInfo::Integer);
+    schema.add_field(INDEX_DATAVAL_FIELD, key_info);
+    for (pos, info) in included_infos.iter().enumerate() {
+        schema.add_field(&index_included_field(pos), *info);
+    }
+    Layout::new(schema)
+}
+
+/// index の record で、include で指定した pos 番目の field の値を保存する field の名前
+pub fn index_included_field(pos: usize) -> String {
+    format!("{}{}", INDEX_INCLUDED_FIELD_PREFIX, pos)
+}
+
+/// layout の record に保存する、include で指定した field の数
+pub fn included_field_count(layout: &Layout) -> usize {
+    layout
+        .schema()
+        .fields_iter()
+        .filter(|field| field.starts_with(INDEX_INCLUDED_FIELD_PREFIX))
+        .count()
+}
+
+/// index に登録する 1 つの entry
+#[derive(Clone, PartialEq, Debug)]
+pub struct IndexEntry {
+    data_val: Constant,
+    // include で指定した field の値。index の定義の順に並べる
+    included_vals: Vec<Constant>,
+    data_rid: Rid,
+}
+
+impl IndexEntry {
+    pub fn new(data_val: Constant, data_rid: Rid) -> Self {
+        Self {
+            data_val,
+            included_vals: vec![],
+            data_rid,
+        }
+    }
+
+    pub fn with_included_vals(mut self, included_vals: Vec<Constant>) -> Self {
+        self.included_vals = included_vals;
+        self
+    }
+
+    pub fn data_val(&self) -> &Constant {
+        &self.data_val
+    }
+
+    pub fn included_vals(&self) -> &[Constant] {
+        &self.included_vals
+    }
+
+    pub fn data_rid(&self) -> &Rid {
+        &self.data_rid
+    }
+}
+
+/// index_name の index の値が key である record の集合を表す、predicate lock 用の BlockId
+///
+/// index で値を検索する transaction はこの BlockId の slock を、値を追加・削除する transaction は xlock を取る
+/// block の lock だけでは、検索した後に他の transaction が同じ値の record を追加する (phantom) のを防げないため、
+/// 検索した値そのものを lock する
+/// 値は hash で block 番号に変換するので、hash が衝突した異なる値も同じ lock になる (安全側に倒れる)
+pub fn key_lock_block(index_name: &str, key: &Constant) -> BlockId {
+    BlockId::new(
+        &format!("{}.keylock", index_name),
+        stable_hash(key) as usize,
+    )
+}
+
+/// index で探す record の、index を張った field の値の条件
+#[derive(Clone, PartialEq, Debug)]
+pub enum IndexSearch {
+    /// 値が等しい record を探す
+    Equal(Constant),
+    /// 値が下限と上限の間にある record を探す。Unbounded の端は制限しない
+    Range(Bound<Constant>, Bound<Constant>),
+}
+
+impl IndexSearch {
+    /// 条件を満たす最初の record の直前に、index の cursor を移動する
+    pub fn before_first(&self, index: &mut dyn Index) -> Result<(), IndexError> {
+        match self {
+            IndexSearch::Equal(val) => index.before_first(val),
+            IndexSearch::Range(lo, hi) => index.before_first_range(lo.clone(), hi.clone()),
+        }
+    }
+
+    /// field_name の値についての条件として表示する (explain の label に使う)
+    pub fn display(&self, field_name: &str) -> String {
+        match self {
+            IndexSearch::Equal(val) => format!("{} = {}", field_name, val),
+            IndexSearch::Range(lo, hi) => {
+                let mut conditions = vec![];
+                match lo {
+                    Bound::Included(lo) => conditions.push(format!("{} >= {}", field_name, lo)),
+                    Bound::Excluded(lo) => conditions.push(format!("{} > {}", field_name, lo)),
+                    Bound::Unbounded => {}
+                }
+                match hi {
+                    Bound::Included(hi) => conditions.push(format!("{} <= {}", field_name, hi)),
+                    Bound::Excluded(hi) => conditions.push(format!("{} < {}", field_name, hi)),
+                    Bound::Unbounded => {}
+                }
+                conditions.join(" and ")
+            }
+        }
+    }
+}
+
+/// val が lo と hi の間にあるかどうか
+pub fn in_range(val: &Constant, lo: &Bound<Constant>, hi: &Bound<Constant>) -> bool {
+    let above_lo = match lo {
+        Bound::Included(lo) => val >= lo,
+        Bound::Excluded(lo) => val > lo,
+        Bound::Unbounded => true,
+    };
+    let below_hi = match hi {
+        Bound::Included(hi) => val <= hi,
+        Bound::Excluded(hi) => val < hi,
+        Bound::Unbounded => true,
+    };
+    above_lo && below_hi
+}
+
+/**
+ * field の値から、その値を持つ record の Rid を探すための index
+ *
+ * before_first で探す値を指定してから next を呼ぶことで、その値を持つ record を 1 つずつ辿ることができる
+ * before_first_range で値の範囲を指定した場合は、範囲に入る値を持つ record を辿る
+ */
+#[cfg_attr(test, automock)]
+pub trait Index {
+    /// search_key を持つ最初の record の直前に cursor を移動する
+    /// search_key の key lock (key_lock_block) の slock を取り、transaction が終わるまで同じ値の追加・削除を防ぐ
+    fn before_first(&mut self, search_key: &Constant) -> Result<(), IndexError>;
+    /// 値が lo と hi の間にある最初の record の直前に cursor を移動する
+    /// 範囲を読む間は読んだ block の slock を取るので、transaction が終わるまで範囲への値の追加・削除を防ぐ
+    /// 範囲で検索できない index は error を返す
+    fn before_first_range(
+        &mut self,
+        lo: Bound<Constant>,
+        hi: Bound<Constant>,
+    ) -> Result<(), IndexError>;
+    /// before_first で指定した値 (before_first_range で指定した範囲) を持つ、次の record に移動する。もう無い場合は false を返す
+    fn next(&mut self) -> Result<bool, IndexError>;
+    /// 今いる record が指している、data の record の Rid を返す
+    fn get_data_rid(&self) -> Result<Rid, IndexError>;
+    /// 今いる record の、index を張った field の値を返す
+    fn get_data_val(&self) -> Result<Constant, IndexError>;
+    /// 今いる record に保存している、include で指定した pos 番目の field の値を返す
+    fn get_included_val(&self, pos: usize) -> Result<Constant, IndexError>;
+    /// entry を index に追加する。include で指定した field の値も一緒に保存する
+    /// entry の値の key lock の xlock を取る。delete も同様
+    fn insert_entry(&mut self, entry: &IndexEntry) -> Result<(), IndexError>;
+    /// 値が data_val である data の record (data_rid) を index から削除する
+    /// 見つからなかった場合は何もしない
+    fn delete(&mut self, data_val: &Constant, data_rid: &Rid) -> Result<(), IndexError>;
+    /// 作成したばかりの空の index に、値の昇順に並べた entries をまとめて追加する
+    /// 既定では 1 つずつ insert する。B-Tree は leaf と directory を詰めながら下の level から順に作る
+    fn bulk_load(&mut self, entries: &[IndexEntry]) -> Result<(), IndexError> {
+        for entry in entries {
+            self.insert_entry(entry)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+use std::{cell::RefCell, ops::Bound, rc::Rc};
+
+use crate::{
+    query::{constant::Constant, scan::UpdateScan},
+    record::{
+        layout::Layout,
+        partition::stable_hash,
+        rid::Rid,
+        table_scan_factory::{TableScanFactory, TableScanFactoryImpl},
+    },
+    tx::transaction::Transaction,
+};
+
+use super::common::{
+    included_field_count, index_included_field, key_lock_block, Index, IndexEntry, IndexError,
+    INDEX_BLOCK_FIELD, INDEX_DATAVAL_FIELD, INDEX_ID_FIELD,
+};
+
+/**
+ * 静的 hash による index
+ *
+ * 値の hash で決まる bucket ごとに {index 名}.bucket{bucket 番号} という table を作り、その table に index の record を保存する
+ * bucket の数は固定なので、record が増えると 1 つの bucket の table が長くなり、検索で読む block も増える
+ * 一致する値の検索にしか使えない
+ */
+pub struct HashIndex {
+    tx: Rc<RefCell<Transaction>>,
+    index_name: String,
+    layout: Layout,
+    table_scan_factory: Box<dyn TableScanFactory>,
+    // before_first で指定した値。before_first を呼ぶまでは None
+    search_key: Option<Constant>,
+    // search_key の bucket の table を読む scan
+    scan: Option<Box<dyn UpdateScan>>,
+}
+
+impl HashIndex {
+    pub const NUM_BUCKETS: usize = 100;
+
+    /// index を開く。bucket の table は record を追加する時に作成される
+    /// layout は index_layout で作成したものを渡す
+    pub fn new(tx: Rc<RefCell<Transaction>>, index_name: &str, layout: &Layout) -> Self {
+        HashIndex {
+            tx,
+            index_name: index_name.to_string(),
+            layout: layout.clone(),
+            table_scan_factory: Box::new(TableScanFactoryImpl::new()),
+            search_key: None,
+            scan: None,
+        }
+    }
+
+    /// index を 1 回検索する時に読む block の数の見積もり
+    /// record が bucket に均等に分かれているとみなし、1 つの bucket の table をすべて読む
+    pub fn search_cost(num_blocks: u64, _records_per_block: u64) -> u64 {
+        num_blocks.div_ceil(Self::NUM_BUCKETS as u64)
+    }
+
+    /// val の record を保存する bucket の table 名を返す
+    pub fn bucket_table_name(index_name: &str, val: &Constant) -> String {
+        let bucket = stable_hash(val) % Self::NUM_BUCKETS as u64;
+        format!("{}.bucket{}", index_name, bucket)
+    }
+
+    /// 全ての bucket の table のファイルの名前を返す
+    pub fn file_names(index_name: &str) -> Vec<String> {
+        (0..Self::NUM_BUCKETS)
+            .map(|bucket| format!("{}.bucket{}.tbl", index_name, bucket))
+            .collect()
+    }
+
+    /// index の全ての entry を (値, Rid) の組で返す。整合性の確認に使う
+    /// まだ record が追加されていない bucket の table は読まない
+    pub fn entries(
+        tx: &Rc<RefCell<Transaction>>,
+        index_name: &str,
+        layout: &Layout,
+    ) -> Result<Vec<(Constant, Rid)>, IndexError> {
+        let table_scan_factory = TableScanFactoryImpl::new();
+        let mut entries = vec![];
+        for bucket in 0..Self::NUM_BUCKETS {
+            let table_name = format!("{}.bucket{}", index_name, bucket);
+            if tx.borrow().size(&format!("{}.tbl", table_name))? == 0 {
+                continue;
+            }
+            let mut scan = table_scan_factory.create_read_only(tx, &table_name, layout)?;
+            scan.before_first().map_err(IndexError::Scan)?;
+            while scan.move_next().map_err(IndexError::Scan)? {
+                let block_num = scan.get_int(INDEX_BLOCK_FIELD).map_err(IndexError::Scan)?;
+                let id = scan.get_int(INDEX_ID_FIELD).map_err(IndexError::Scan)?;
+                let val = scan
+                    .get_val(INDEX_DATAVAL_FIELD)
+                    .map_err(IndexError::Scan)?;
+                entries.push((val, Rid::new(block_num as usize, usize::try_from(id).ok())));
+            }
+        }
+        Ok(entries)
+    }
+
+    fn scan(&self) -> Result<&dyn UpdateScan, IndexError> {
+        self.scan.as_deref().ok_or(IndexError::InvalidCall(
+            "no search key is specified for the index. you need to call before_first first"
+                .to_string(),
+        ))
+    }
+}
+
+impl Index for HashIndex {
+    fn before_first(&mut self, search_key: &Constant) -> Result<(), IndexError> {
+        self.tx
+            .borrow()
+            .slock_predicate(&key_lock_block(&self.index_name, search_key))?;
+        // 前に開いていた scan の pin を先に外す
+        self.scan = None;
+        let table_name = Self::bucket_table_name(&self.index_name, search_key);
+        let mut scan = self
+            .table_scan_factory
+            .create(&self.tx, &table_name, &self.layout)?;
+        scan.before_first().map_err(IndexError::Scan)?;
+        self.search_key = Some(search_key.clone());
+        self.scan = Some(scan);
+        Ok(())
+    }
+
+    // 値の hash で bucket を分けているので、値の範囲では探せない
+    fn before_first_range(
+        &mut self,
+        _lo: Bound<Constant>,
+        _hi: Bound<Constant>,
+    ) -> Result<(), IndexError> {
+        Err(IndexError::InvalidCall(
+            "hash index does not support range search".to_string(),
+        ))
+    }
+
+    fn next(&mut self) -> Result<bool, IndexError> {
+        let search_key = self.search_key.clone();
+        let scan = self.scan.as_mut().ok_or(IndexError::InvalidCall(
+            "no search key is specified for the index. you need to call before_first first"
+                .to_string(),
+        ))?;
+        while scan.move_next().map_err(IndexError::Scan)? {
+            if Some(
+                scan.get_val(INDEX_DATAVAL_FIELD)
+                    .map_err(IndexError::Scan)?,
+            ) == search_key
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_data_rid(&self) -> Result<Rid, IndexError> {
+        let scan = self.scan()?;
+        let block_num = scan.get_int(INDEX_BLOCK_FIELD).map_err(IndexError::Scan)?;
+        let id = scan.get_int(INDEX_ID_FIELD).map_err(IndexError::Scan)?;
+        Ok(Rid::new(block_num as usize, usize::try_from(id).ok()))
+    }
+
+    fn get_data_val(&self) -> Result<Constant, IndexError> {
+        self.scan()?
+            .get_val(INDEX_DATAVAL_FIELD)
+            .map_err(IndexError::Scan)
+    }
+
+    fn get_included_val(&self, pos: usize) -> Result<Constant, IndexError> {
+        self.scan()?
+            .get_val(&index_included_field(pos))
+            .map_err(IndexError::Scan)
+    }
+
+    fn insert_entry(&mut self, entry: &IndexEntry) -> Result<(), IndexError> {
+        if entry.included_vals().len() != included_field_count(&self.layout) {
+            return Err(IndexError::InvalidCall(format!(
+                "the index stores {} included fields, but the entry has {} values",
+                included_field_count(&self.layout),
+                entry.included_vals().len()
+            )));
+        }
+        let (data_val, data_rid) = (entry.data_val(), entry.data_rid());
+        self.tx
+            .borrow()
+            .xlock_predicate(&key_lock_block(&self.index_name, data_val))?;
+        self.before_first(data_val)?;
+        let scan = self.scan.as_mut().ok_or(IndexError::InvalidCall(
+            "bucket scan is not opened".to_string(),
+        ))?;
+        scan.insert().map_err(IndexError::Scan)?;
+        scan.set_int(INDEX_BLOCK_FIELD, data_rid.block_number() as i32)
+            .map_err(IndexError::Scan)?;
+        // slot を持たない Rid は -1 として保存する
+        let id = data_rid.slot().map_or(-1, |slot| slot as i32);
+        scan.set_int(INDEX_ID_FIELD, id).map_err(IndexError::Scan)?;
+        scan.set_val(INDEX_DATAVAL_FIELD, data_val)
+            .map_err(IndexError::Scan)?;
+        for (pos, val) in entry.included_vals().iter().enumerate() {
+            scan.set_val(&index_included_field(pos), val)
+                .map_err(IndexError::Scan)?;
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, data_val: &Constant, data_rid: &Rid) -> Result<(), IndexError> {
+        self.tx
+            .borrow()
+            .xlock_predicate(&key_lock_block(&self.index_name, data_val))?;
+        self.before_first(data_val)?;
+        while self.next()? {
+            if self.get_data_rid()? == *data_rid {
+                if let Some(scan) = self.scan.as_mut() {
+                    scan.delete().map_err(IndexError::Scan)?;
+                }
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod hash_index_test {
+    use super::*;
+    use crate::{
+        buffer::buffer_manager::BufferManager,
+        file::file_manager::FileManager,
+        index::common::index_layout,
+        log::log_manager::LogManager,
+        record::schema::FieldInfo,
+        tx::{concurrency::lock_table::LockTable, transaction::TransactionFactory},
+    };
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_insert_search_and_delete() {
+        let dir = tempdir().unwrap();
+        let file_manager = Arc::new(FileManager::new(dir.path(), 400));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager.clone(),
+            log_manager.clone(),
+            8,
+            Some(10),
+        ));
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        let factory =
+            TransactionFactory::new(file_manager, log_manager, buffer_manager, lock_table);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let layout = index_layout(FieldInfo::String(8), &[]).unwrap();
+        let mut index = HashIndex::new(tx.clone(), "idx", &layout);
+
+        for i in 0..30 {
+            let key = Constant::from(format!("key{}", i % 3));
+            index
+                .insert_entry(&IndexEntry::new(key.clone(), Rid::new(i, Some(i % 4))))
+                .unwrap();
+        }
+
+        let lookup = |index: &mut HashIndex, key: &str| {
+            index.before_first(&Constant::from(key)).unwrap();
+            let mut rids = vec![];
+            while index.next().unwrap() {
+                rids.push(index.get_data_rid().unwrap());
+            }
+            rids
+        };
+        assert_eq!(lookup(&mut index, "key1").len(), 10);
+        assert!(lookup(&mut index, "none").is_empty());
+
+        index
+            .delete(&Constant::from("key1"), &Rid::new(4, Some(0)))
+            .unwrap();
+        let rids = lookup(&mut index, "key1");
+        assert_eq!(rids.len(), 9);
+        assert!(!rids.contains(&Rid::new(4, Some(0))));
+        assert_eq!(lookup(&mut index, "key2").len(), 10);
+
+        drop(index);
+        assert!(tx.borrow().pinned_blocks().is_empty());
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_key_lock_prevents_phantom() {
+        let dir = tempdir().unwrap();
+        let file_manager = Arc::new(FileManager::new(dir.path(), 400));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager.clone(),
+            log_manager.clone(),
+            8,
+            Some(10),
+        ));
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        let factory =
+            TransactionFactory::new(file_manager, log_manager, buffer_manager, lock_table);
+        let layout = index_layout(FieldInfo::Integer, &[]).unwrap();
+
+        // tx1 が値 1 を検索した後は、他の transaction は値 1 の record を追加できない
+        let tx1 = Rc::new(RefCell::new(factory.create().unwrap()));
+        let mut index1 = HashIndex::new(tx1.clone(), "idx", &layout);
+        index1.before_first(&Constant::Int(1)).unwrap();
+        assert!(!index1.next().unwrap());
+        drop(index1);
+
+        let tx2 = Rc::new(RefCell::new(factory.create().unwrap()));
+        let mut index2 = HashIndex::new(tx2.clone(), "idx", &layout);
+        assert!(matches!(
+            index2.insert_entry(&IndexEntry::new(Constant::Int(1), Rid::new(0, Some(0)))),
+            Err(IndexError::KeyLockExclusive(_))
+        ));
+        drop(index2);
+        tx2.borrow_mut().rollback().unwrap();
+
+        // tx1 が終われば追加できる
+        tx1.borrow_mut().commit().unwrap();
+        let tx3 = Rc::new(RefCell::new(factory.create().unwrap()));
+        let mut index3 = HashIndex::new(tx3.clone(), "idx", &layout);
+        index3
+            .insert_entry(&IndexEntry::new(Constant::Int(1), Rid::new(0, Some(0))))
+            .unwrap();
+        drop(index3);
+        tx3.borrow_mut().commit().unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+use std::{fmt::Write, path::Path};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use crate::{
+    file::blockid::BlockId,
+    record::dump::dump_block,
+    server::simpledb::{SimpleDB, SimpleDBConfig},
+    tx::log::log_record_iterator::LogRecordIterator,
+};
+
+/**
+ * table_name の slot の配置と、全 block を dump_block で出力する
+ *
+ * SimpleDB を open して transaction の中で読むので、他の process が同じ database を使っている間は使わないこと
+ * recover はしないので、disk に書き出された block の内容がそのまま出力される
+ * (commit されていない変更も含み、disk に書き出されていない変更は含まない)
+ */
+pub fn inspect_table(dir: &Path, table_name: &str, block_size: usize) -> AnyhowResult<String> {
+    let db = open_db(dir, block_size)?;
+    let tx = db.new_tx()?;
+    if !db.metadata_manager().table_exists(table_name, &tx)? {
+        return Err(anyhow!("table {} does not exist", table_name));
+    }
+    let layout = db.metadata_manager().get_layout(table_name, &tx)?;
+    let file_name = format!("{}.tbl", table_name);
+    let block_count = tx.borrow().size(&file_name)?;
+    let mut dump = format!("table {}, blocks: {}\n{}", table_name, block_count, layout);
+    for block_number in 0..block_count {
+        let block = BlockId::new(&file_name, block_number);
+        dump.push_str(&dump_block(&tx, &block, &layout)?);
+    }
+    tx.borrow_mut().commit()?;
+    Ok(dump)
+}
+
+/// log record を新しいものから順に 1 行ずつ出力する
+/// log record として読めない位置があった場合は、その位置を出力してそこで止める
+pub fn inspect_log(dir: &Path, block_size: usize) -> AnyhowResult<String> {
+    let db = open_db(dir, block_size)?;
+    let mut iter = LogRecordIterator::new(db.log_manager())?;
+    let mut dump = String::new();
+    while let Some(record) = iter.try_next() {
+        match record {
+            Ok(record) => writeln!(dump, "{:?}", record)?,
+            Err(err) => writeln!(dump, "<{}>", err)?,
+        }
+    }
+    Ok(dump)
+}
+
+pub(crate) fn open_db(dir: &Path, block_size: usize) -> AnyhowResult<SimpleDB> {
+    let dir_name = dir
+        .to_str()
+        .ok_or_else(|| anyhow!("invalid directory name: {}", dir.display()))?;
+    Ok(SimpleDB::with_config(
+        dir_name,
+        SimpleDBConfig {
+            block_size,
+            ..Default::default()
+        },
+    )?)
+}
+
+#[cfg(test)]
+mod inspect_test {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_inspect_table_and_log() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+            let tx = db.new_tx().unwrap();
+            db.executor()
+                .exec_update_command("create table t (a int, b varchar(5))", &tx)
+                .unwrap();
+            db.executor()
+                .exec_update_command("insert into t (a, b) values (42, 'hello')", &tx)
+                .unwrap();
+            tx.borrow_mut().commit().unwrap();
+            // commit では buffer は書き出されないので、明示的に disk へ書き出しておく
+            db.buffer_manager().flush_all().unwrap();
+        }
+
+        let dump = inspect_table(dir.path(), "t", 400).unwrap();
+        assert!(dump.starts_with("table t, blocks: 1\nslot size: 32, header: 4, padding: 0\n"));
+        assert!(dump.contains("  @8 b varchar(5) (24)\n"));
+        assert!(dump.contains("used { a@4=42, b@8='hello' }"));
+        assert!(inspect_table(dir.path(), "missing", 400).is_err());
+
+        let log = inspect_log(dir.path(), 400).unwrap();
+        assert!(log.lines().any(|line| line.contains("Commit")));
+    }
+}
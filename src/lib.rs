@@ -1,13 +1,47 @@
-mod buffer;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod buffer;
+#[cfg(feature = "sql")]
+pub mod check;
+#[cfg(feature = "sql")]
+pub mod cli;
 mod constants;
+pub mod error;
+#[cfg(feature = "sql")]
 mod exec;
-mod file;
-mod log;
+pub mod file;
+#[cfg(feature = "sql")]
+mod index;
+#[cfg(feature = "sql")]
+pub mod inspect;
+pub mod log;
+#[cfg(feature = "sql")]
 mod metadata;
+#[cfg(feature = "sql")]
 mod parse;
+#[cfg(feature = "sql")]
 mod plan;
+#[cfg(feature = "sql")]
 mod planner;
+#[cfg(feature = "sql")]
 mod query;
+#[cfg(feature = "sql")]
 mod record;
+#[cfg(feature = "sql")]
 mod server;
-mod tx;
+pub mod tx;
+
+// storage と transaction の層だけを埋め込む場合は、file / log / buffer / tx の module を直接使う
+// SQL の層 (KvTable や query builder を含む) は、よく使う型だけをここから公開する
+pub use crate::error::{ErrorCategory, SimpleDbError, SimpleDbResult};
+#[cfg(feature = "sql")]
+pub use crate::{
+    exec::executor::{Executor, QueryResult},
+    plan::{expression::Expression, predicate::ProductPredicate},
+    planner::query_builder::Query,
+    query::{constant::Constant, scan::ReadScan},
+    server::{
+        kv_table::{KvRow, KvTable},
+        simpledb::{SimpleDB, SimpleDBConfig},
+    },
+};
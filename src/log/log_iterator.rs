@@ -1,5 +1,7 @@
 use std::sync::Arc;
 
+use thiserror::Error;
+
 use crate::constants::INTEGER_BYTE_LEN;
 use crate::file::blockid;
 use crate::file::file_manager;
@@ -13,6 +15,24 @@ pub struct LogIterator {
     block: blockid::BlockId,
     page: page::Page,
     current_pos: usize, // block 内部での位置
+    // 直前に読んだ log record の block 内での位置
+    last_pos: Option<usize>,
+    // 長さが壊れた log record を読んだ場合は true になり、以降は何も返さない
+    broken: bool,
+}
+
+/**
+ * log record として解釈できない byte 列を読んだことを表す error
+ *
+ * framed が true の場合は log record の長さは読めている (中身だけが壊れている) ので、その log record を飛ばして前の log record を読み続けられる
+ * false の場合は長さが壊れているため、それより前の log record の位置がわからない
+ */
+#[derive(Error, Debug)]
+#[error("invalid log record at block {block}, offset {offset}")]
+pub struct InvalidLogRecordError {
+    pub block: blockid::BlockId,
+    pub offset: usize,
+    pub framed: bool,
 }
 
 /**
@@ -37,12 +57,82 @@ impl LogIterator {
             block: block.clone(),
             page: page::Page::new_from_size(block_size),
             current_pos: 0,
+            last_pos: None,
+            broken: false,
         };
         log_iterator.move_to_block(block)?;
 
         Ok(log_iterator)
     }
 
+    /**
+     * block の内容として、disk ではなく page (メモリ上の log page の写し) を使って iterator を作成する
+     *
+     * block より前の block は従来通り disk から読む。block を移るときに log page は書き出されているので、前の block は disk 上で最新になっている
+     * page の内容は disk に書き込まれているとは限らないので、この iterator から LogReverseIterator を作ってはいけない
+     */
+    pub fn from_page(
+        fm: Arc<file_manager::FileManager>,
+        block: &blockid::BlockId,
+        page: page::Page,
+    ) -> LogIterator {
+        let current_pos = page.get_int(0) as usize;
+        LogIterator {
+            fm,
+            block: block.clone(),
+            page,
+            current_pos,
+            last_pos: None,
+            broken: false,
+        }
+    }
+
+    /**
+     * 次の log record を読む。Iterator::next と異なり、log record の長さが壊れている場合は error を返す
+     *
+     * error を返した後は None を返し続ける
+     */
+    pub fn try_next(&mut self) -> Option<Result<Vec<u8>, InvalidLogRecordError>> {
+        if self.broken {
+            return None;
+        }
+        let block_size = self.fm.block_size();
+        let block_number = self.block.number();
+        // block の最後まで読んだ && ログの最初の block まで読んだ
+        if self.current_pos >= block_size && block_number == 0 {
+            return None;
+        }
+        // 今の block の最後まで読んでいたら、前の block に移動する
+        if self.current_pos >= block_size {
+            let prev_block = blockid::BlockId::new(self.block.file_name(), block_number - 1);
+            if self.move_to_block(&prev_block).is_err() {
+                return None;
+            }
+        }
+        // 長さと中身が block に収まっていない場合は、log record が壊れている
+        let remaining = block_size.saturating_sub(self.current_pos + INTEGER_BYTE_LEN);
+        if self.current_pos + INTEGER_BYTE_LEN > block_size
+            || self.page.get_int(self.current_pos) < 0
+            || self.page.get_int(self.current_pos) as usize > remaining
+        {
+            self.broken = true;
+            return Some(Err(InvalidLogRecordError {
+                block: self.block.clone(),
+                offset: self.current_pos,
+                framed: false,
+            }));
+        }
+        self.last_pos = Some(self.current_pos);
+        let log_rec = self.page.get_bytes(self.current_pos);
+        self.current_pos += INTEGER_BYTE_LEN + log_rec.len();
+        Some(Ok(log_rec))
+    }
+
+    /// 直前に読んだ log record の位置 (block と block 内の offset)
+    pub fn last_position(&self) -> Option<(blockid::BlockId, usize)> {
+        self.last_pos.map(|pos| (self.block.clone(), pos))
+    }
+
     fn move_to_block(
         &mut self,
         block: &blockid::BlockId,
@@ -59,23 +149,7 @@ impl Iterator for LogIterator {
     type Item = Vec<u8>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let block_size = self.fm.block_size();
-        let block_number = self.block.number();
-        // block の最後まで読んだ && ログの最初の block まで読んだ
-        if self.current_pos == block_size && block_number == 0 {
-            return None;
-        }
-        // 今の block の最後まで読んでいたら、前の block に移動する
-        if self.current_pos == block_size {
-            let prev_block = blockid::BlockId::new(self.block.file_name(), block_number - 1);
-            match self.move_to_block(&prev_block) {
-                Ok(_) => {}
-                Err(_) => return None,
-            }
-        }
-        let log_rec = self.page.get_bytes(self.current_pos);
-        self.current_pos += INTEGER_BYTE_LEN + log_rec.len();
-        Some(log_rec)
+        self.try_next()?.ok()
     }
 }
 
@@ -22,6 +22,8 @@ pub struct LogManager {
     current_block: Mutex<blockid::BlockId>,
     latest_lsn: Mutex<u64>, // LSN = log sequence number
     last_saved_lsn: Mutex<u64>,
+    // 起動してから最後に書き込まれた checkpoint record の LSN
+    last_checkpoint_lsn: Mutex<u64>,
 }
 
 #[derive(Error, Debug)]
@@ -48,8 +50,14 @@ impl LogManager {
             current_block
         };
 
-        let latest_lsn = 0;
-        let last_saved_lsn = 0;
+        // LSN は log file の先頭の log record から 1 から順に振られる番号とする
+        // 起動をまたいでも同じ log record が同じ LSN を持つよう、起動時に log file にある log record の数から数え始める
+        let latest_lsn = if log_size == 0 {
+            0
+        } else {
+            log_iterator::LogIterator::new(fm.clone(), &current_block)?.count() as u64
+        };
+        let last_saved_lsn = latest_lsn;
         Ok(LogManager {
             fm: fm,
             logfile: logfile.to_string(),
@@ -57,6 +65,7 @@ impl LogManager {
             current_block: Mutex::new(current_block),
             latest_lsn: Mutex::new(latest_lsn),
             last_saved_lsn: Mutex::new(last_saved_lsn),
+            last_checkpoint_lsn: Mutex::new(0),
         })
     }
 
@@ -108,6 +117,76 @@ impl LogManager {
         )?)
     }
 
+    /**
+     * log record を最新順から読むための iterator を、log page を flush せずに作成する
+     *
+     * 最新の block はメモリ上の log page の写しから読むので、短い transaction の rollback のように最近の log record だけを読む場合は disk I/O が発生しない
+     * 返される iterator からは LogReverseIterator を作れない (LogIterator::from_page を参照)
+     */
+    pub fn tail_iterator(&self) -> Result<log_iterator::LogIterator, LogError> {
+        let log_page = self.log_page.lock().map_err(|_| LogError::LockError)?;
+        let current_block = self.current_block.lock().map_err(|_| LogError::LockError)?;
+        Ok(log_iterator::LogIterator::from_page(
+            self.fm.clone(),
+            &current_block,
+            page::Page::new_from_vec(log_page.contents()),
+        ))
+    }
+
+    /**
+     * 最新の log record の LSN を返す。log record が 1 つもない場合は 0 を返す
+     */
+    pub fn latest_lsn(&self) -> Result<u64, LogError> {
+        Ok(*self.latest_lsn.lock().map_err(|_| LogError::LockError)?)
+    }
+
+    // ファイルに書き込み済みの log record の中で最大の LSN を返す
+    pub fn last_saved_lsn(&self) -> Result<u64, LogError> {
+        Ok(*self
+            .last_saved_lsn
+            .lock()
+            .map_err(|_| LogError::LockError)?)
+    }
+
+    /**
+     * 最後に書き込まれた checkpoint record の LSN を返す。checkpoint が書き込まれていない場合は 0 を返す
+     * 以前の起動で書き込まれた checkpoint は、set_last_checkpoint_lsn で設定されていれば返す
+     */
+    pub fn last_checkpoint_lsn(&self) -> Result<u64, LogError> {
+        Ok(*self
+            .last_checkpoint_lsn
+            .lock()
+            .map_err(|_| LogError::LockError)?)
+    }
+
+    /// lsn の log record が checkpoint record であることを記録する
+    pub fn set_last_checkpoint_lsn(&self, lsn: u64) -> Result<(), LogError> {
+        *self
+            .last_checkpoint_lsn
+            .lock()
+            .map_err(|_| LogError::LockError)? = lsn;
+        Ok(())
+    }
+
+    /**
+     * log record を最新順から読むための iterator と、その時点で最新の log record の LSN を返す
+     *
+     * LSN は log file の先頭の log record から 1 から順に振られる番号なので、iterator が返す n 番目 (0 始まり) の log record の LSN は latest_lsn - n になる
+     */
+    pub fn iterator_with_latest_lsn(&self) -> Result<(log_iterator::LogIterator, u64), LogError> {
+        // append の途中の log record を数えてしまわないように、log page の lock を持ったまま LSN を読む
+        let log_page = self.log_page.lock().map_err(|_| LogError::LockError)?;
+        let current_block = self.current_block.lock().map_err(|_| LogError::LockError)?;
+        self.fm.write(&current_block, &log_page)?;
+        let latest_lsn = *self.latest_lsn.lock().map_err(|_| LogError::LockError)?;
+        *self
+            .last_saved_lsn
+            .lock()
+            .map_err(|_| LogError::LockError)? = latest_lsn;
+        let iter = log_iterator::LogIterator::new(self.fm.clone(), &current_block)?;
+        Ok((iter, latest_lsn))
+    }
+
     /**
      * 少なくとも lsn までの log record を block に書き込んで、永続性を保証する
      */
@@ -224,4 +303,48 @@ mod test_log_manager {
             assert_eq!(log_rev_iter.next(), Some(log_record.to_vec()));
         }
     }
+
+    #[test]
+    fn test_lsn_after_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let fm = Arc::new(file_manager::FileManager::new(dir.path(), 400));
+        {
+            let log_manager = LogManager::new(fm.clone(), "log_file").unwrap();
+            for i in 0..100 {
+                log_manager
+                    .append(format!("test log record {}", i).as_bytes())
+                    .unwrap();
+            }
+            log_manager.flush_all().unwrap();
+        }
+
+        // 開き直しても、既存の log record の続きから LSN を振る
+        let log_manager = LogManager::new(fm, "log_file").unwrap();
+        assert_eq!(log_manager.latest_lsn().unwrap(), 100);
+        assert_eq!(log_manager.append(b"next log record").unwrap(), 101);
+    }
+
+    #[test]
+    fn test_tail_iterator() {
+        let dir = tempfile::tempdir().unwrap();
+        let fm = file_manager::FileManager::new(dir.path(), 400);
+        let log_manager = LogManager::new(Arc::new(fm), "log_file").unwrap();
+
+        // 複数の block にまたがるように書き込む
+        for i in 0..100 {
+            let log_record_str = format!("test log record {}", i);
+            log_manager.append(log_record_str.as_bytes()).unwrap();
+        }
+        let last_saved_lsn = *log_manager.last_saved_lsn.lock().unwrap();
+
+        let mut log_iter = log_manager.tail_iterator().unwrap();
+        for i in (0..100).rev() {
+            let log_record_str = format!("test log record {}", i);
+            assert_eq!(log_iter.next(), Some(log_record_str.as_bytes().to_vec()));
+        }
+        assert_eq!(log_iter.next(), None);
+        // flush されていない
+        assert_eq!(*log_manager.last_saved_lsn.lock().unwrap(), last_saved_lsn);
+        assert!(last_saved_lsn < 100);
+    }
 }
@@ -1,3 +1,33 @@
-fn main() {
-    println!("Hello, world!");
+use std::{env, path::Path, process::ExitCode};
+
+use simpledb::check::check_database;
+
+const USAGE: &str = "usage: simpledb --check <db-dir> [--block-size N]";
+const DEFAULT_BLOCK_SIZE: usize = 400;
+
+fn main() -> ExitCode {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match run(&args) {
+        Ok(true) => ExitCode::SUCCESS,
+        // 不整合が見つかった場合は、結果を出力した上で失敗として終了する
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// 確認した結果を出力し、不整合が無かったかどうかを返す
+fn run(args: &[String]) -> anyhow::Result<bool> {
+    let (dir, block_size) = match args {
+        [flag, dir] if flag == "--check" => (dir, DEFAULT_BLOCK_SIZE),
+        [flag, dir, option, size] if flag == "--check" && option == "--block-size" => {
+            (dir, size.parse().map_err(|_| anyhow::anyhow!(USAGE))?)
+        }
+        _ => return Err(anyhow::anyhow!(USAGE)),
+    };
+    let report = check_database(Path::new(dir), block_size)?;
+    print!("{}", report);
+    Ok(report.is_ok())
 }
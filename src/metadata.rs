@@ -1,4 +1,9 @@
+pub mod access_list;
 pub mod constants;
+pub mod default_value_manager;
+pub mod external_table_manager;
+pub mod identifier;
+pub mod index_manager;
 pub mod metadata_manager;
 pub mod stat_info;
 pub mod stat_manager;
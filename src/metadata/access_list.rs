@@ -0,0 +1,168 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+
+use thiserror::Error;
+
+use crate::file::{
+    blockid::BlockId,
+    file_manager::{FileManager, FileManagerError},
+    page::Page,
+};
+
+/**
+ * 最近参照された table の一覧を保持するクラス
+ *
+ * 起動時の warm-up で、前回よく使われていた table の block を先に読み込んでおくために使う
+ * 一覧は 1 行 1 table 名の文字列として、FileManager を通して block 単位でファイルに保存する
+ * (暗号化が有効な場合は table 名も暗号化される。先頭の block の最初の 4 bytes に文字列の長さを書く)
+ * 先頭にあるものほど最近参照された table である
+ */
+pub struct AccessList {
+    file_manager: Arc<FileManager>,
+    filename: String,
+    capacity: usize,
+    tables: Mutex<VecDeque<String>>,
+}
+
+#[derive(Error, Debug)]
+pub enum AccessListError {
+    #[error("file manager error: {0}")]
+    FileManager(#[from] FileManagerError),
+    #[error("invalid access list: {0}")]
+    Invalid(String),
+    #[error("Failed to acquire lock")]
+    Lock,
+}
+
+impl AccessList {
+    const LENGTH_BYTES: usize = 4;
+
+    /// filename に保存された一覧を読み込む。ファイルが存在しない場合は空の一覧を返す
+    pub fn load(
+        file_manager: Arc<FileManager>,
+        filename: &str,
+        capacity: usize,
+    ) -> Result<AccessList, AccessListError> {
+        let content = Self::read_content(&file_manager, filename)?;
+        let tables = content
+            .lines()
+            .filter(|line| !line.is_empty())
+            .take(capacity)
+            .map(|line| line.to_string())
+            .collect();
+        Ok(AccessList {
+            file_manager,
+            filename: filename.to_string(),
+            capacity,
+            tables: Mutex::new(tables),
+        })
+    }
+
+    fn read_content(file_manager: &FileManager, filename: &str) -> Result<String, AccessListError> {
+        let length = file_manager.length(filename)?;
+        if length == 0 {
+            return Ok(String::new());
+        }
+        let block_size = file_manager.block_size_of(filename)?;
+        let mut bytes = Vec::new();
+        let mut page = Page::new_from_size(block_size);
+        for blknum in 0..length {
+            file_manager.read(&BlockId::new(filename, blknum), &mut page)?;
+            bytes.extend_from_slice(page.contents());
+        }
+        let len = u32::from_be_bytes(bytes[..Self::LENGTH_BYTES].try_into().unwrap()) as usize;
+        let content = bytes
+            .get(Self::LENGTH_BYTES..Self::LENGTH_BYTES + len)
+            .ok_or_else(|| AccessListError::Invalid(format!("length {} is too long", len)))?;
+        String::from_utf8(content.to_vec()).map_err(|e| AccessListError::Invalid(e.to_string()))
+    }
+
+    /// table が参照されたことを記録する
+    pub fn record(&self, table_name: &str) -> Result<(), AccessListError> {
+        let mut tables = self.tables.lock().map_err(|_| AccessListError::Lock)?;
+        if tables.front().map(|t| t.as_str()) == Some(table_name) {
+            return Ok(());
+        }
+        tables.retain(|t| t != table_name);
+        tables.push_front(table_name.to_string());
+        tables.truncate(self.capacity);
+        Ok(())
+    }
+
+    /// 最近参照された順に table 名を返す
+    pub fn tables(&self) -> Result<Vec<String>, AccessListError> {
+        let tables = self.tables.lock().map_err(|_| AccessListError::Lock)?;
+        Ok(tables.iter().cloned().collect())
+    }
+
+    /// 一覧をファイルに保存する
+    pub fn save(&self) -> Result<(), AccessListError> {
+        let mut content = self.tables()?.join("\n");
+        content.push('\n');
+        let mut bytes = (content.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(content.as_bytes());
+
+        let block_size = self.file_manager.block_size_of(&self.filename)?;
+        let length = self.file_manager.length(&self.filename)?;
+        for (blknum, chunk) in bytes.chunks(block_size).enumerate() {
+            let block = if blknum < length {
+                BlockId::new(&self.filename, blknum)
+            } else {
+                self.file_manager.append(&self.filename)?
+            };
+            let mut page = Page::new_from_size(block_size);
+            page.contents_mut()[..chunk.len()].copy_from_slice(chunk);
+            self.file_manager.write(&block, &page)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod access_list_test {
+    use std::sync::Arc;
+
+    use tempfile::tempdir;
+
+    use super::AccessList;
+    use crate::file::file_manager::FileManager;
+
+    #[test]
+    fn test_record_and_reload() {
+        let dir = tempdir().unwrap();
+        let file_manager = Arc::new(FileManager::new(dir.path(), 400));
+        let access_list = AccessList::load(file_manager.clone(), "access.list", 3).unwrap();
+        assert!(access_list.tables().unwrap().is_empty());
+
+        for table_name in ["a", "b", "a", "c", "d"] {
+            access_list.record(table_name).unwrap();
+        }
+        // 最近参照されたものから capacity 個だけ残る
+        assert_eq!(access_list.tables().unwrap(), vec!["d", "c", "a"]);
+        access_list.save().unwrap();
+
+        let reloaded = AccessList::load(file_manager, "access.list", 2).unwrap();
+        assert_eq!(reloaded.tables().unwrap(), vec!["d", "c"]);
+    }
+
+    #[test]
+    fn test_save_across_blocks() {
+        let dir = tempdir().unwrap();
+        let file_manager = Arc::new(FileManager::new(dir.path(), 32));
+        let access_list = AccessList::load(file_manager.clone(), "access.list", 8).unwrap();
+        for i in 0..8 {
+            access_list.record(&format!("table_{}", i)).unwrap();
+        }
+        access_list.save().unwrap();
+        // 1 block に収まらない一覧は複数の block に分けて保存される
+        assert!(file_manager.length("access.list").unwrap() > 1);
+
+        // 短くなった一覧を保存し直しても、後ろの block の古い内容は読まない
+        let access_list = AccessList::load(file_manager.clone(), "access.list", 1).unwrap();
+        access_list.save().unwrap();
+        let reloaded = AccessList::load(file_manager, "access.list", 8).unwrap();
+        assert_eq!(reloaded.tables().unwrap(), vec!["table_7"]);
+    }
+}
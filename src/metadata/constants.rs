@@ -1,6 +1,9 @@
 pub(crate) const TBLCAT_TABLE_NAME: &str = "tblcat";
-pub(crate) const TBLCAT_TBLNAME_FIELD: &str = "tblname";
 pub(crate) const TBLCAT_SLOTSIZE_FIELD: &str = "slotsize";
+// table ごとの block size。0 の場合は FileManager 共通の block size を使う
+pub(crate) const TBLCAT_BLKSIZE_FIELD: &str = "blksize";
+// page を圧縮して保存する table なら 1、そうでなければ 0
+pub(crate) const TBLCAT_COMPRESS_FIELD: &str = "compress";
 
 pub(crate) const FLDCAT_TABLE_NAME: &str = "fldcat";
 pub(crate) const FCAT_TBLNAME_FIELD: &str = "tblname";
@@ -9,11 +12,76 @@ pub(crate) const FCAT_TYPE_FIELD: &str = "type";
 pub(crate) const FCAT_LENGTH_FIELD: &str = "length";
 pub(crate) const FCAT_OFFSET_FIELD: &str = "offset";
 
+// tblcat と fldcat の record を table 名で探すための B-Tree index。idxcat には登録しない
+pub(crate) const TBLCAT_INDEX_NAME: &str = "tblcatidx";
+pub(crate) const FLDCAT_INDEX_NAME: &str = "fldcatidx";
+pub(crate) const SYSTEM_INDEX_NAMES: [&str; 2] = [TBLCAT_INDEX_NAME, FLDCAT_INDEX_NAME];
+
+// temp table の実体となるファイル名の prefix。FileManager は起動時にこの prefix を持つファイルを削除する
+pub(crate) const TEMP_TABLE_PREFIX: &str = "temp";
+
 pub(crate) const MAX_TABLE_NAME_LENGTH: usize = 32;
 pub(crate) const MAX_FIELD_NAME_LENGTH: usize = 32;
 
-pub(crate) const MAX_VIEWDEF_LENGTH: usize = 1024;
+// view の定義はこの長さの断片に分けて viewcat に保存する。viewcat の 1 record が 1 block (400 bytes) に収まる長さにしている
+pub(crate) const VIEWDEF_CHUNK_LENGTH: usize = 60;
 pub(crate) const MAX_VIEW_NAME_LENGTH: usize = 32;
 pub(crate) const VIEWCAT_TABLE_NAME: &str = "viewcat";
 pub(crate) const VIEWCAT_VIEW_NAME_FIELD: &str = "viewname";
+// 定義の断片の番号 (0 始まり)
+pub(crate) const VIEWCAT_SEQ_FIELD: &str = "seq";
 pub(crate) const VIEWCAT_VIEW_DEF_FIELD: &str = "viewdef";
+
+// idxcat の 1 record が 1 block (400 bytes) に収まる長さにしている
+pub(crate) const MAX_INDEX_NAME_LENGTH: usize = 24;
+pub(crate) const IDXCAT_TABLE_NAME: &str = "idxcat";
+pub(crate) const IDXCAT_IDXNAME_FIELD: &str = "indexname";
+pub(crate) const IDXCAT_TBLNAME_FIELD: &str = "tablename";
+pub(crate) const IDXCAT_FLDNAME_FIELD: &str = "fieldname";
+// unique index なら 1、そうでなければ 0
+pub(crate) const IDXCAT_UNIQUE_FIELD: &str = "isunique";
+// index の種類。B-Tree なら 0、hash なら 1
+pub(crate) const IDXCAT_TYPE_FIELD: &str = "indextype";
+
+// index の record に一緒に保存する field (create index の include で指定した field) を 1 つずつ保存する
+pub(crate) const IDXINCCAT_TABLE_NAME: &str = "idxinccat";
+pub(crate) const IDXINCCAT_IDXNAME_FIELD: &str = "indexname";
+pub(crate) const IDXINCCAT_FLDNAME_FIELD: &str = "fieldname";
+// include で指定した順番 (0 始まり)
+pub(crate) const IDXINCCAT_POS_FIELD: &str = "pos";
+
+// defaultcat の 1 record が 1 block (400 bytes) に収まる長さにしている
+pub(crate) const MAX_DEFAULT_EXPR_LENGTH: usize = 24;
+pub(crate) const DEFAULTCAT_TABLE_NAME: &str = "defaultcat";
+pub(crate) const DEFAULTCAT_TBLNAME_FIELD: &str = "tblname";
+pub(crate) const DEFAULTCAT_FLDNAME_FIELD: &str = "fldname";
+pub(crate) const DEFAULTCAT_EXPR_FIELD: &str = "defexpr";
+
+// extcat の 1 record が 1 block (400 bytes) に収まる長さにしている
+pub(crate) const MAX_EXTERNAL_PATH_LENGTH: usize = 64;
+pub(crate) const EXTCAT_TABLE_NAME: &str = "extcat";
+pub(crate) const EXTCAT_TBLNAME_FIELD: &str = "tblname";
+pub(crate) const EXTCAT_PATH_FIELD: &str = "path";
+
+// partition に分けた table の、partition ごとの設定を 1 record ずつ保存する
+pub(crate) const MAX_PARTITION_COUNT: usize = 64;
+pub(crate) const PARTCAT_TABLE_NAME: &str = "partcat";
+pub(crate) const PARTCAT_TBLNAME_FIELD: &str = "tblname";
+pub(crate) const PARTCAT_FLDNAME_FIELD: &str = "fldname";
+// hash partition なら 0、range partition なら 1
+pub(crate) const PARTCAT_SCHEME_FIELD: &str = "scheme";
+pub(crate) const PARTCAT_PARTNO_FIELD: &str = "partno";
+// range partition の場合、partition に入る値の下限。最初の partition と hash partition では使わない
+pub(crate) const PARTCAT_LOWER_FIELD: &str = "lower";
+
+// 起動時の warm-up で先頭の block を読み込んでおく catalog table
+pub(crate) const CATALOG_TABLE_NAMES: [&str; 8] = [
+    TBLCAT_TABLE_NAME,
+    FLDCAT_TABLE_NAME,
+    VIEWCAT_TABLE_NAME,
+    IDXCAT_TABLE_NAME,
+    IDXINCCAT_TABLE_NAME,
+    DEFAULTCAT_TABLE_NAME,
+    EXTCAT_TABLE_NAME,
+    PARTCAT_TABLE_NAME,
+];
@@ -0,0 +1,357 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+use thiserror::Error;
+
+use crate::{
+    record::{
+        schema::{FieldInfo, Schema},
+        table_scan_factory::TableScanFactory,
+    },
+    tx::transaction::Transaction,
+};
+
+use super::{
+    constants::{
+        DEFAULTCAT_EXPR_FIELD, DEFAULTCAT_FLDNAME_FIELD, DEFAULTCAT_TABLE_NAME,
+        DEFAULTCAT_TBLNAME_FIELD, MAX_DEFAULT_EXPR_LENGTH, MAX_FIELD_NAME_LENGTH,
+        MAX_TABLE_NAME_LENGTH,
+    },
+    table_manager::TableManager,
+};
+
+pub trait DefaultValueManager {
+    fn setup_if_not_exists(&self, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<()>;
+    fn set_default_expression(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        expression: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
+    fn get_default_expressions(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<HashMap<String, String>>;
+    fn update_default_expression(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        expression: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
+    fn rename_field(
+        &self,
+        table_name: &str,
+        old_field_name: &str,
+        new_field_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
+    fn rename_table(
+        &self,
+        table_name: &str,
+        new_table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
+}
+
+/**
+ * field の default 値の式の保存及び取得を行うためのクラス
+ *
+ * 内部的には defaultcat という table に (table 名, field 名, 式の文字列) を保存している
+ * 式は parse できる文字列の形で保存し、評価は insert を実行するときに executor が行う
+ */
+pub struct DefaultValueManagerImpl<'a> {
+    table_manager: &'a dyn TableManager,
+    table_scan_factory: Box<dyn TableScanFactory>,
+}
+
+pub struct DefaultValueManagerFactory {}
+
+impl DefaultValueManagerFactory {
+    pub fn create<'a>(
+        table_manager: &'a dyn TableManager,
+        table_scan_factory: Box<dyn TableScanFactory>,
+    ) -> Box<dyn DefaultValueManager + 'a> {
+        let default_value_manager = DefaultValueManagerImpl::new(table_manager, table_scan_factory);
+        Box::new(default_value_manager)
+    }
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum DefaultValueManagerError {
+    #[error("invalid call error: {0}")]
+    InvalidCall(String),
+}
+
+impl<'a> DefaultValueManagerImpl<'a> {
+    pub fn new(
+        table_manager: &'a dyn TableManager,
+        table_scan_factory: Box<dyn TableScanFactory>,
+    ) -> DefaultValueManagerImpl<'a> {
+        DefaultValueManagerImpl {
+            table_manager,
+            table_scan_factory,
+        }
+    }
+
+    // defaultcat がすでに作成されているかどうか
+    fn is_set_up(&self, tx: &Rc<RefCell<Transaction>>) -> bool {
+        self.table_manager
+            .get_layout(DEFAULTCAT_TABLE_NAME, tx)
+            .is_ok()
+    }
+}
+
+impl DefaultValueManager for DefaultValueManagerImpl<'_> {
+    // default 値を管理するために必要なファイルがまだ作成されていない場合、作成する
+    // このメソッドは何回呼んでも問題ない
+    fn setup_if_not_exists(&self, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<()> {
+        if self.is_set_up(tx) {
+            return Ok(());
+        }
+        let mut schema = Schema::new();
+        schema.add_field(
+            DEFAULTCAT_TBLNAME_FIELD,
+            FieldInfo::String(MAX_TABLE_NAME_LENGTH),
+        );
+        schema.add_field(
+            DEFAULTCAT_FLDNAME_FIELD,
+            FieldInfo::String(MAX_FIELD_NAME_LENGTH),
+        );
+        schema.add_field(
+            DEFAULTCAT_EXPR_FIELD,
+            FieldInfo::String(MAX_DEFAULT_EXPR_LENGTH),
+        );
+        self.table_manager
+            .create_table(DEFAULTCAT_TABLE_NAME, schema, tx)?;
+        Ok(())
+    }
+
+    // field の default 値の式を保存する
+    fn set_default_expression(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        expression: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        if expression.len() > MAX_DEFAULT_EXPR_LENGTH {
+            return Err(anyhow!(DefaultValueManagerError::InvalidCall(format!(
+                "default expression for {}.{} is too long (max {} bytes)",
+                table_name, field_name, MAX_DEFAULT_EXPR_LENGTH
+            ))));
+        }
+        let layout = self.table_manager.get_layout(DEFAULTCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, DEFAULTCAT_TABLE_NAME, &layout)?;
+        ts.insert()?;
+        ts.set_string(DEFAULTCAT_TBLNAME_FIELD, table_name)?;
+        ts.set_string(DEFAULTCAT_FLDNAME_FIELD, field_name)?;
+        ts.set_string(DEFAULTCAT_EXPR_FIELD, expression)?;
+        Ok(())
+    }
+
+    // table の field 名 -> default 値の式 のマップを返す
+    // defaultcat がまだ作成されていない場合は、default 値が一つも設定されていないものとみなす
+    fn get_default_expressions(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<HashMap<String, String>> {
+        let mut defaults = HashMap::new();
+        if !self.is_set_up(tx) {
+            return Ok(defaults);
+        }
+        let layout = self.table_manager.get_layout(DEFAULTCAT_TABLE_NAME, tx)?;
+        let mut ts =
+            self.table_scan_factory
+                .create_read_only(tx, DEFAULTCAT_TABLE_NAME, &layout)?;
+        while ts.move_next()? {
+            if ts.get_string(DEFAULTCAT_TBLNAME_FIELD)? == table_name {
+                defaults.insert(
+                    ts.get_string(DEFAULTCAT_FLDNAME_FIELD)?,
+                    ts.get_string(DEFAULTCAT_EXPR_FIELD)?,
+                );
+            }
+        }
+        Ok(defaults)
+    }
+
+    // 既存の default 値の式を置き換える。default 値が設定されていない field の場合は何もしない
+    fn update_default_expression(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        expression: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        if expression.len() > MAX_DEFAULT_EXPR_LENGTH {
+            return Err(anyhow!(DefaultValueManagerError::InvalidCall(format!(
+                "default expression for {}.{} is too long (max {} bytes)",
+                table_name, field_name, MAX_DEFAULT_EXPR_LENGTH
+            ))));
+        }
+        if !self.is_set_up(tx) {
+            return Ok(());
+        }
+        let layout = self.table_manager.get_layout(DEFAULTCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, DEFAULTCAT_TABLE_NAME, &layout)?;
+        while ts.move_next()? {
+            if ts.get_string(DEFAULTCAT_TBLNAME_FIELD)? == table_name
+                && ts.get_string(DEFAULTCAT_FLDNAME_FIELD)? == field_name
+            {
+                ts.set_string(DEFAULTCAT_EXPR_FIELD, expression)?;
+            }
+        }
+        Ok(())
+    }
+
+    // field 名の変更に合わせて、default 値の設定を付け替える
+    fn rename_field(
+        &self,
+        table_name: &str,
+        old_field_name: &str,
+        new_field_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        if !self.is_set_up(tx) {
+            return Ok(());
+        }
+        let layout = self.table_manager.get_layout(DEFAULTCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, DEFAULTCAT_TABLE_NAME, &layout)?;
+        while ts.move_next()? {
+            if ts.get_string(DEFAULTCAT_TBLNAME_FIELD)? == table_name
+                && ts.get_string(DEFAULTCAT_FLDNAME_FIELD)? == old_field_name
+            {
+                ts.set_string(DEFAULTCAT_FLDNAME_FIELD, new_field_name)?;
+            }
+        }
+        Ok(())
+    }
+
+    // table 名の変更に合わせて、default 値の設定を付け替える
+    fn rename_table(
+        &self,
+        table_name: &str,
+        new_table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        if !self.is_set_up(tx) {
+            return Ok(());
+        }
+        let layout = self.table_manager.get_layout(DEFAULTCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, DEFAULTCAT_TABLE_NAME, &layout)?;
+        while ts.move_next()? {
+            if ts.get_string(DEFAULTCAT_TBLNAME_FIELD)? == table_name {
+                ts.set_string(DEFAULTCAT_TBLNAME_FIELD, new_table_name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod default_value_manager_test {
+    use super::*;
+    use crate::{
+        buffer::buffer_manager::BufferManager,
+        file::file_manager::FileManager,
+        log::log_manager::LogManager,
+        metadata::table_manager::TableManagerImpl,
+        record::table_scan_factory::TableScanFactoryImpl,
+        tx::{concurrency::lock_table::LockTable, transaction::TransactionFactory},
+    };
+    use std::sync::Arc;
+    use tempfile::{tempdir, TempDir};
+
+    fn setup_factory(dir: &TempDir) -> TransactionFactory {
+        let file_manager = Arc::new(FileManager::new(dir.path(), 400));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager.clone(),
+            log_manager.clone(),
+            8,
+            Some(10),
+        ));
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        TransactionFactory::new(file_manager, log_manager, buffer_manager, lock_table)
+    }
+
+    #[test]
+    fn test_set_and_get_default_expressions() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let table_manager = TableManagerImpl::new(Arc::new(TableScanFactoryImpl::new())).unwrap();
+        table_manager.setup_if_not_exists(&tx).unwrap();
+        let default_value_manager =
+            DefaultValueManagerImpl::new(&table_manager, Box::new(TableScanFactoryImpl::new()));
+
+        // setup 前は default 値は一つもない
+        assert!(default_value_manager
+            .get_default_expressions("tbl", &tx)
+            .unwrap()
+            .is_empty());
+
+        // 何回呼び出しても大丈夫
+        default_value_manager.setup_if_not_exists(&tx).unwrap();
+        default_value_manager.setup_if_not_exists(&tx).unwrap();
+
+        default_value_manager
+            .set_default_expression("tbl", "a", "0", &tx)
+            .unwrap();
+        default_value_manager
+            .set_default_expression("tbl", "b", "'none'", &tx)
+            .unwrap();
+        default_value_manager
+            .set_default_expression("other", "a", "1", &tx)
+            .unwrap();
+
+        let defaults = default_value_manager
+            .get_default_expressions("tbl", &tx)
+            .unwrap();
+        assert_eq!(defaults.len(), 2);
+        assert_eq!(defaults.get("a"), Some(&"0".to_string()));
+        assert_eq!(defaults.get("b"), Some(&"'none'".to_string()));
+
+        default_value_manager
+            .rename_field("tbl", "a", "c", &tx)
+            .unwrap();
+        let defaults = default_value_manager
+            .get_default_expressions("tbl", &tx)
+            .unwrap();
+        assert_eq!(defaults.get("a"), None);
+        assert_eq!(defaults.get("c"), Some(&"0".to_string()));
+
+        default_value_manager
+            .update_default_expression("tbl", "c", "c + 1", &tx)
+            .unwrap();
+        // default 値が設定されていない field は何もしない
+        default_value_manager
+            .update_default_expression("tbl", "d", "1", &tx)
+            .unwrap();
+        let defaults = default_value_manager
+            .get_default_expressions("tbl", &tx)
+            .unwrap();
+        assert_eq!(defaults.len(), 2);
+        assert_eq!(defaults.get("c"), Some(&"c + 1".to_string()));
+        assert_eq!(
+            default_value_manager
+                .get_default_expressions("other", &tx)
+                .unwrap()
+                .get("a"),
+            Some(&"1".to_string())
+        );
+
+        tx.borrow_mut().commit().unwrap();
+    }
+}
@@ -0,0 +1,262 @@
+use std::{cell::RefCell, rc::Rc};
+
+use thiserror::Error;
+
+use crate::{
+    query::scan::{ReadScanError, UpdateScanError},
+    record::{
+        schema::{FieldInfo, Schema},
+        table_scan_factory::{TableScanFactory, TableScanFactoryError},
+    },
+    tx::transaction::Transaction,
+};
+
+use super::{
+    constants::{
+        EXTCAT_PATH_FIELD, EXTCAT_TABLE_NAME, EXTCAT_TBLNAME_FIELD, MAX_EXTERNAL_PATH_LENGTH,
+        MAX_TABLE_NAME_LENGTH,
+    },
+    table_manager::{TableManager, TableManagerError},
+};
+
+pub trait ExternalTableManager {
+    fn setup_if_not_exists(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), ExternalTableManagerError>;
+    fn create_external_table(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        path: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), ExternalTableManagerError>;
+    fn get_external_path(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<Option<String>, ExternalTableManagerError>;
+    fn rename_table(
+        &self,
+        table_name: &str,
+        new_table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), ExternalTableManagerError>;
+}
+
+/**
+ * 外部の CSV / TSV ファイルを read-only な table (external table) として登録するためのクラス
+ *
+ * schema は通常の table と同じく tblcat, fldcat に保存し、ファイルの path は extcat という table に保存している
+ * external table のデータは simpledb の block としては保存されない
+ */
+pub struct ExternalTableManagerImpl<'a> {
+    table_manager: &'a dyn TableManager,
+    table_scan_factory: Box<dyn TableScanFactory>,
+}
+
+pub struct ExternalTableManagerFactory {}
+
+impl ExternalTableManagerFactory {
+    pub fn create<'a>(
+        table_manager: &'a dyn TableManager,
+        table_scan_factory: Box<dyn TableScanFactory>,
+    ) -> Box<dyn ExternalTableManager + 'a> {
+        let external_table_manager =
+            ExternalTableManagerImpl::new(table_manager, table_scan_factory);
+        Box::new(external_table_manager)
+    }
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum ExternalTableManagerError {
+    #[error("table manager error: {0}")]
+    TableManager(#[from] TableManagerError),
+    #[error("read scan error: {0}")]
+    ReadScan(#[from] ReadScanError),
+    #[error("update scan error: {0}")]
+    UpdateScan(#[from] UpdateScanError),
+    #[error("table scan factory error: {0}")]
+    TableScanFactory(#[from] TableScanFactoryError),
+    #[error("invalid call error: {0}")]
+    InvalidCall(String),
+    // TODO: 治す
+    #[error("anyhow error: {0}")]
+    Anyhow(#[from] anyhow::Error),
+}
+
+impl<'a> ExternalTableManagerImpl<'a> {
+    pub fn new(
+        table_manager: &'a dyn TableManager,
+        table_scan_factory: Box<dyn TableScanFactory>,
+    ) -> ExternalTableManagerImpl<'a> {
+        ExternalTableManagerImpl {
+            table_manager,
+            table_scan_factory,
+        }
+    }
+
+    // extcat がすでに作成されているかどうか
+    fn is_set_up(&self, tx: &Rc<RefCell<Transaction>>) -> bool {
+        self.table_manager.get_layout(EXTCAT_TABLE_NAME, tx).is_ok()
+    }
+}
+
+impl ExternalTableManager for ExternalTableManagerImpl<'_> {
+    // external table を管理するために必要なファイルがまだ作成されていない場合、作成する
+    // このメソッドは何回呼んでも問題ない
+    fn setup_if_not_exists(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), ExternalTableManagerError> {
+        if self.is_set_up(tx) {
+            return Ok(());
+        }
+        let mut schema = Schema::new();
+        schema.add_field(
+            EXTCAT_TBLNAME_FIELD,
+            FieldInfo::String(MAX_TABLE_NAME_LENGTH),
+        );
+        schema.add_field(
+            EXTCAT_PATH_FIELD,
+            FieldInfo::String(MAX_EXTERNAL_PATH_LENGTH),
+        );
+        self.table_manager
+            .create_table(EXTCAT_TABLE_NAME, schema, tx)?;
+        Ok(())
+    }
+
+    // external table を登録する
+    fn create_external_table(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        path: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), ExternalTableManagerError> {
+        if path.chars().count() > MAX_EXTERNAL_PATH_LENGTH {
+            return Err(ExternalTableManagerError::InvalidCall(format!(
+                "path for external table {} is too long (max {} characters)",
+                table_name, MAX_EXTERNAL_PATH_LENGTH
+            )));
+        }
+        self.table_manager.create_table(table_name, schema, tx)?;
+        let layout = self.table_manager.get_layout(EXTCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, EXTCAT_TABLE_NAME, &layout)?;
+        ts.insert()?;
+        ts.set_string(EXTCAT_TBLNAME_FIELD, table_name)?;
+        ts.set_string(EXTCAT_PATH_FIELD, path)?;
+        Ok(())
+    }
+
+    // external table のファイルの path を返す。external table でない場合は None を返す
+    fn get_external_path(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<Option<String>, ExternalTableManagerError> {
+        if !self.is_set_up(tx) {
+            return Ok(None);
+        }
+        let layout = self.table_manager.get_layout(EXTCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create_read_only(tx, EXTCAT_TABLE_NAME, &layout)?;
+        while ts.move_next()? {
+            if ts.get_string(EXTCAT_TBLNAME_FIELD)? == table_name {
+                return Ok(Some(ts.get_string(EXTCAT_PATH_FIELD)?));
+            }
+        }
+        Ok(None)
+    }
+
+    // table 名の変更に合わせて、ファイルの path を付け替える
+    fn rename_table(
+        &self,
+        table_name: &str,
+        new_table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), ExternalTableManagerError> {
+        if !self.is_set_up(tx) {
+            return Ok(());
+        }
+        let layout = self.table_manager.get_layout(EXTCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, EXTCAT_TABLE_NAME, &layout)?;
+        while ts.move_next()? {
+            if ts.get_string(EXTCAT_TBLNAME_FIELD)? == table_name {
+                ts.set_string(EXTCAT_TBLNAME_FIELD, new_table_name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod external_table_manager_test {
+    use super::*;
+    use crate::{
+        buffer::buffer_manager::BufferManager,
+        file::file_manager::FileManager,
+        log::log_manager::LogManager,
+        metadata::table_manager::TableManagerImpl,
+        record::table_scan_factory::TableScanFactoryImpl,
+        tx::{concurrency::lock_table::LockTable, transaction::TransactionFactory},
+    };
+    use std::sync::Arc;
+    use tempfile::{tempdir, TempDir};
+
+    fn setup_factory(dir: &TempDir) -> TransactionFactory {
+        let file_manager = Arc::new(FileManager::new(dir.path(), 400));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager.clone(),
+            log_manager.clone(),
+            8,
+            Some(10),
+        ));
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        TransactionFactory::new(file_manager, log_manager, buffer_manager, lock_table)
+    }
+
+    #[test]
+    fn test_create_external_table() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let table_manager = TableManagerImpl::new(Arc::new(TableScanFactoryImpl::new())).unwrap();
+        table_manager.setup_if_not_exists(&tx).unwrap();
+        let external_table_manager =
+            ExternalTableManagerImpl::new(&table_manager, Box::new(TableScanFactoryImpl::new()));
+        external_table_manager.setup_if_not_exists(&tx).unwrap();
+
+        let mut schema = Schema::new();
+        schema.add_field("id", FieldInfo::Integer);
+        external_table_manager
+            .create_external_table("ext", schema.clone(), "/data/ext.csv", &tx)
+            .unwrap();
+
+        // schema は通常の table と同様に取得できる
+        assert_eq!(
+            table_manager.get_layout("ext", &tx).unwrap().schema(),
+            &schema
+        );
+        assert_eq!(
+            external_table_manager
+                .get_external_path("ext", &tx)
+                .unwrap(),
+            Some("/data/ext.csv".to_string())
+        );
+        assert_eq!(
+            external_table_manager
+                .get_external_path("other", &tx)
+                .unwrap(),
+            None
+        );
+
+        tx.borrow_mut().commit().unwrap();
+    }
+}
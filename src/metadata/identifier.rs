@@ -0,0 +1,173 @@
+use thiserror::Error;
+
+use crate::parse::keyword::reserved_keywords;
+
+use super::constants::{
+    MAX_FIELD_NAME_LENGTH, MAX_INDEX_NAME_LENGTH, MAX_TABLE_NAME_LENGTH, MAX_VIEW_NAME_LENGTH,
+};
+
+/// 名前を検証する対象の種類。catalog に保存できる長さが種類ごとに異なる
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IdentifierKind {
+    Table,
+    Field,
+    View,
+    Index,
+}
+
+impl IdentifierKind {
+    /// catalog に保存できる名前の最大の文字数
+    pub fn max_length(&self) -> usize {
+        match self {
+            IdentifierKind::Table => MAX_TABLE_NAME_LENGTH,
+            IdentifierKind::Field => MAX_FIELD_NAME_LENGTH,
+            IdentifierKind::View => MAX_VIEW_NAME_LENGTH,
+            IdentifierKind::Index => MAX_INDEX_NAME_LENGTH,
+        }
+    }
+}
+
+impl std::fmt::Display for IdentifierKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            IdentifierKind::Table => "table",
+            IdentifierKind::Field => "field",
+            IdentifierKind::View => "view",
+            IdentifierKind::Index => "index",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum IdentifierError {
+    #[error("{0} name must not be empty")]
+    Empty(IdentifierKind),
+    #[error("{kind} name {name} is too long (max {max_length} characters)")]
+    TooLong {
+        kind: IdentifierKind,
+        name: String,
+        max_length: usize,
+    },
+    #[error("{kind} name {name} contains an invalid character {invalid:?}")]
+    InvalidCharacter {
+        kind: IdentifierKind,
+        name: String,
+        invalid: char,
+    },
+    #[error(
+        "{kind} name {name} conflicts with reserved word of {syntax}{}",
+        suggestion_message(.suggestions)
+    )]
+    ReservedWord {
+        kind: IdentifierKind,
+        name: String,
+        // name を予約語として使う文法の名前
+        syntax: &'static str,
+        suggestions: Vec<String>,
+    },
+}
+
+impl IdentifierError {
+    /// name が予約語である場合の error を、代わりに使える名前の候補と一緒に作る
+    /// name が予約語でない場合は None を返す
+    pub fn reserved_word(kind: IdentifierKind, name: &str) -> Option<IdentifierError> {
+        let keywords = reserved_keywords();
+        let syntax = keywords.syntax_of(name)?;
+        Some(IdentifierError::ReservedWord {
+            kind,
+            name: name.to_string(),
+            syntax,
+            suggestions: keywords.suggest_identifiers(kind, name),
+        })
+    }
+}
+
+fn suggestion_message(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!(" (did you mean {}?)", suggestions.join(" or "))
+    }
+}
+
+/// 識別子の先頭に使える文字かどうか
+pub(crate) fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+/// 識別子の 2 文字目以降に使える文字かどうか
+pub(crate) fn is_identifier_part(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/**
+ * table, field, view, index の名前が catalog に保存できるかを検証する
+ *
+ * catalog の varchar に収まらない名前は保存時に壊れてしまうので、作成する前にここで弾く
+ * 文字の種類は Lexer が識別子として読める範囲に合わせ、予約語と同じ名前は SQL から参照できないので許さない
+ */
+pub(crate) fn validate_identifier(kind: IdentifierKind, name: &str) -> Result<(), IdentifierError> {
+    let mut chars = name.chars();
+    let Some(first) = chars.next() else {
+        return Err(IdentifierError::Empty(kind));
+    };
+    let invalid = if is_identifier_start(first) {
+        chars.find(|c| !is_identifier_part(*c))
+    } else {
+        Some(first)
+    };
+    if let Some(invalid) = invalid {
+        return Err(IdentifierError::InvalidCharacter {
+            kind,
+            name: name.to_string(),
+            invalid,
+        });
+    }
+    if name.chars().count() > kind.max_length() {
+        return Err(IdentifierError::TooLong {
+            kind,
+            name: name.to_string(),
+            max_length: kind.max_length(),
+        });
+    }
+    match IdentifierError::reserved_word(kind, name) {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod identifier_test {
+    use super::*;
+
+    #[test]
+    fn test_validate_identifier() {
+        assert!(validate_identifier(IdentifierKind::Table, "student").is_ok());
+        assert!(validate_identifier(IdentifierKind::Field, "_major_id2").is_ok());
+        assert!(validate_identifier(IdentifierKind::Table, &"a".repeat(32)).is_ok());
+
+        assert!(matches!(
+            validate_identifier(IdentifierKind::Table, ""),
+            Err(IdentifierError::Empty(IdentifierKind::Table))
+        ));
+        assert!(matches!(
+            validate_identifier(IdentifierKind::View, &"a".repeat(33)),
+            Err(IdentifierError::TooLong { max_length: 32, .. })
+        ));
+        assert!(matches!(
+            validate_identifier(IdentifierKind::Field, "1st"),
+            Err(IdentifierError::InvalidCharacter { invalid: '1', .. })
+        ));
+        assert!(matches!(
+            validate_identifier(IdentifierKind::Field, "first-name"),
+            Err(IdentifierError::InvalidCharacter { invalid: '-', .. })
+        ));
+        let err = validate_identifier(IdentifierKind::Index, "select").unwrap_err();
+        assert!(matches!(err, IdentifierError::ReservedWord { .. }));
+        assert_eq!(
+            err.to_string(),
+            "index name select conflicts with reserved word of select (did you mean select_ or index_select?)"
+        );
+    }
+}
@@ -0,0 +1,786 @@
+use std::{cell::RefCell, rc::Rc};
+
+use thiserror::Error;
+
+use crate::{
+    buffer::buffer_manager::BufferPoolKind,
+    index::{
+        btree_index::BTreeIndex,
+        common::{index_layout, Index, IndexError, IndexType},
+        hash_index::HashIndex,
+    },
+    query::{
+        constant::Constant,
+        scan::{ReadScan, ReadScanError, UpdateScanError},
+    },
+    record::{
+        layout::Layout,
+        rid::Rid,
+        schema::{FieldInfo, Schema},
+        table_scan_factory::{TableScanFactory, TableScanFactoryError},
+    },
+    tx::transaction::{Transaction, TransactionCopyError},
+};
+
+use super::{
+    constants::{
+        IDXCAT_FLDNAME_FIELD, IDXCAT_IDXNAME_FIELD, IDXCAT_TABLE_NAME, IDXCAT_TBLNAME_FIELD,
+        IDXCAT_TYPE_FIELD, IDXCAT_UNIQUE_FIELD, IDXINCCAT_FLDNAME_FIELD, IDXINCCAT_IDXNAME_FIELD,
+        IDXINCCAT_POS_FIELD, IDXINCCAT_TABLE_NAME, MAX_FIELD_NAME_LENGTH, MAX_INDEX_NAME_LENGTH,
+        MAX_TABLE_NAME_LENGTH, SYSTEM_INDEX_NAMES,
+    },
+    identifier::{validate_identifier, IdentifierError, IdentifierKind},
+    table_manager::{TableManager, TableManagerError},
+};
+
+/// idxcat に保存されている 1 つの index の情報
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct IndexInfo {
+    index_name: String,
+    table_name: String,
+    field_name: String,
+    unique: bool,
+    index_type: IndexType,
+    // index の record に一緒に保存する field (include で指定した field)。idxinccat に保存する
+    included_fields: Vec<String>,
+}
+
+impl IndexInfo {
+    pub fn new(index_name: &str, table_name: &str, field_name: &str, unique: bool) -> Self {
+        Self {
+            index_name: index_name.to_string(),
+            table_name: table_name.to_string(),
+            field_name: field_name.to_string(),
+            unique,
+            index_type: IndexType::default(),
+            included_fields: vec![],
+        }
+    }
+
+    pub fn with_index_type(mut self, index_type: IndexType) -> Self {
+        self.index_type = index_type;
+        self
+    }
+
+    pub fn with_included_fields(mut self, included_fields: Vec<String>) -> Self {
+        self.included_fields = included_fields;
+        self
+    }
+
+    pub fn index_name(&self) -> &str {
+        &self.index_name
+    }
+
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    pub fn field_name(&self) -> &str {
+        &self.field_name
+    }
+
+    pub fn is_unique(&self) -> bool {
+        self.unique
+    }
+
+    pub fn index_type(&self) -> IndexType {
+        self.index_type
+    }
+
+    pub fn included_fields(&self) -> &[String] {
+        &self.included_fields
+    }
+
+    /// fields の値が全て index の record から読めるかどうか
+    /// 読める場合は table の block を読まずに、index だけを読んで query に答えられる
+    pub fn covers(&self, fields: &[String]) -> bool {
+        fields.iter().all(|field| {
+            *field == self.field_name || self.included_fields.iter().any(|inc| inc == field)
+        })
+    }
+
+    // index を張った table の schema から、index の record の layout を作る
+    fn layout(&self, table_schema: &Schema) -> Result<Layout, IndexError> {
+        let info = |field: &str| {
+            table_schema.info(field).ok_or_else(|| {
+                IndexError::InvalidCall(format!(
+                    "field {} of index {} not found in table {}",
+                    field, self.index_name, self.table_name
+                ))
+            })
+        };
+        let included_infos = self
+            .included_fields
+            .iter()
+            .map(|field| info(field))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(index_layout(info(&self.field_name)?, &included_infos)?)
+    }
+
+    /// index を開く。table_schema は index を張った table の schema
+    pub(crate) fn open(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+        table_schema: &Schema,
+    ) -> Result<Box<dyn Index>, IndexError> {
+        let layout = self.layout(table_schema)?;
+        // index の block が大きな table の scan で追い出されないよう、index 用の buffer を使う
+        for filename in self.file_names() {
+            tx.borrow()
+                .register_file(&filename, BufferPoolKind::Index)?;
+        }
+        Ok(match self.index_type {
+            IndexType::BTree => Box::new(BTreeIndex::new(tx.clone(), &self.index_name, &layout)?),
+            IndexType::Hash => Box::new(HashIndex::new(tx.clone(), &self.index_name, &layout)),
+        })
+    }
+
+    /// index の全ての entry を (値, Rid) の組で返す。table_schema は index を張った table の schema
+    pub(crate) fn entries(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+        table_schema: &Schema,
+    ) -> Result<Vec<(Constant, Rid)>, IndexError> {
+        let layout = self.layout(table_schema)?;
+        match self.index_type {
+            IndexType::BTree => BTreeIndex::entries(tx, &self.index_name, &layout),
+            IndexType::Hash => HashIndex::entries(tx, &self.index_name, &layout),
+        }
+    }
+
+    /// index の内容を保存しているファイルの名前を返す
+    pub(crate) fn file_names(&self) -> Vec<String> {
+        match self.index_type {
+            IndexType::BTree => BTreeIndex::file_names(&self.index_name),
+            IndexType::Hash => HashIndex::file_names(&self.index_name),
+        }
+    }
+
+    /// index の 1 つの block に入る record の数
+    pub(crate) fn records_per_block(
+        &self,
+        table_schema: &Schema,
+        block_size: usize,
+    ) -> Result<u64, IndexError> {
+        Ok(self
+            .layout(table_schema)?
+            .slots_per_block(block_size)
+            .max(1) as u64)
+    }
+
+    /// record が num_records 個ある table の index を 1 回検索する時に読む block の数の見積もり
+    pub(crate) fn search_cost(
+        &self,
+        num_records: u64,
+        table_schema: &Schema,
+        block_size: usize,
+    ) -> Result<u64, IndexError> {
+        let records_per_block = self.records_per_block(table_schema, block_size)?;
+        let num_blocks = num_records.div_ceil(records_per_block);
+        Ok(match self.index_type {
+            IndexType::BTree => BTreeIndex::search_cost(num_blocks, records_per_block),
+            IndexType::Hash => HashIndex::search_cost(num_blocks, records_per_block),
+        })
+    }
+}
+
+pub trait IndexManager {
+    fn setup_if_not_exists(&self, tx: &Rc<RefCell<Transaction>>) -> Result<(), IndexManagerError>;
+    fn create_index(
+        &self,
+        index_info: &IndexInfo,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), IndexManagerError>;
+    /// 名前が index_name の index の情報を取得する。存在しない場合は None を返す
+    fn get_index(
+        &self,
+        index_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<Option<IndexInfo>, IndexManagerError>;
+    /// table に作成されている index の情報を作成した順に取得する
+    fn get_index_info(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<Vec<IndexInfo>, IndexManagerError>;
+    fn rename_field(
+        &self,
+        table_name: &str,
+        old_field_name: &str,
+        new_field_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), IndexManagerError>;
+    fn rename_table(
+        &self,
+        table_name: &str,
+        new_table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), IndexManagerError>;
+    /// index の名前を変更する。idxcat の record と、index のファイルの名前を変更する
+    fn rename_index(
+        &self,
+        index_name: &str,
+        new_index_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), IndexManagerError>;
+}
+
+/**
+ * index の作成及び index の情報の取得を行うためのクラス
+ *
+ * 内部的には idxcat という table に (index 名, table 名, field 名, unique かどうか) を保存している
+ * include で指定した field は idxinccat という table に (index 名, field 名, 順番) として 1 つずつ保存する
+ * index の名前は table をまたいで一意にする
+ */
+pub struct IndexManagerImpl<'a> {
+    table_manager: &'a dyn TableManager,
+    table_scan_factory: Box<dyn TableScanFactory>,
+}
+
+pub struct IndexManagerFactory {}
+
+impl IndexManagerFactory {
+    pub fn create<'a>(
+        table_manager: &'a dyn TableManager,
+        table_scan_factory: Box<dyn TableScanFactory>,
+    ) -> Box<dyn IndexManager + 'a> {
+        let index_manager = IndexManagerImpl::new(table_manager, table_scan_factory);
+        Box::new(index_manager)
+    }
+}
+
+#[derive(Error, Debug)]
+pub(crate) enum IndexManagerError {
+    #[error("table manager error: {0}")]
+    TableManager(#[from] TableManagerError),
+    #[error("read scan error: {0}")]
+    ReadScan(#[from] ReadScanError),
+    #[error("update scan error: {0}")]
+    UpdateScan(#[from] UpdateScanError),
+    #[error("table scan factory error: {0}")]
+    TableScanFactory(#[from] TableScanFactoryError),
+    #[error("invalid call error: {0}")]
+    InvalidCall(String),
+    #[error("identifier error: {0}")]
+    Identifier(#[from] IdentifierError),
+    #[error("transaction error: {0}")]
+    Transaction(#[from] TransactionCopyError),
+    // TODO: 治す
+    #[error("anyhow error: {0}")]
+    Anyhow(#[from] anyhow::Error),
+}
+
+impl<'a> IndexManagerImpl<'a> {
+    pub fn new(
+        table_manager: &'a dyn TableManager,
+        table_scan_factory: Box<dyn TableScanFactory>,
+    ) -> IndexManagerImpl<'a> {
+        IndexManagerImpl {
+            table_manager,
+            table_scan_factory,
+        }
+    }
+
+    // idxcat がすでに作成されているかどうか
+    fn is_set_up(&self, tx: &Rc<RefCell<Transaction>>) -> bool {
+        self.table_manager.get_layout(IDXCAT_TABLE_NAME, tx).is_ok()
+    }
+
+    // idxinccat は idxcat より後に追加したので、それ以前に作った database には無いことがある
+    fn has_included_catalog(&self, tx: &Rc<RefCell<Transaction>>) -> bool {
+        self.table_manager
+            .get_layout(IDXINCCAT_TABLE_NAME, tx)
+            .is_ok()
+    }
+
+    // idxcat から読んだ index の情報に、idxinccat に保存した include の field を加える
+    fn with_included_fields(
+        &self,
+        mut index_infos: Vec<IndexInfo>,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<Vec<IndexInfo>, IndexManagerError> {
+        if index_infos.is_empty() || !self.has_included_catalog(tx) {
+            return Ok(index_infos);
+        }
+        let layout = self.table_manager.get_layout(IDXINCCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create_read_only(tx, IDXINCCAT_TABLE_NAME, &layout)?;
+        let mut included = vec![vec![]; index_infos.len()];
+        while ts.move_next()? {
+            let index_name = ts.get_string(IDXINCCAT_IDXNAME_FIELD)?;
+            if let Some(i) = index_infos
+                .iter()
+                .position(|index_info| index_info.index_name() == index_name)
+            {
+                included[i].push((
+                    ts.get_int(IDXINCCAT_POS_FIELD)?,
+                    ts.get_string(IDXINCCAT_FLDNAME_FIELD)?,
+                ));
+            }
+        }
+        for (index_info, mut fields) in index_infos.iter_mut().zip(included) {
+            fields.sort();
+            index_info.included_fields = fields.into_iter().map(|(_, field)| field).collect();
+        }
+        Ok(index_infos)
+    }
+
+    // idxcat の現在の record を IndexInfo に変換する。include の field は with_included_fields で加える
+    fn read_index_info(ts: &mut dyn ReadScan) -> Result<IndexInfo, IndexManagerError> {
+        Ok(IndexInfo::new(
+            &ts.get_string(IDXCAT_IDXNAME_FIELD)?,
+            &ts.get_string(IDXCAT_TBLNAME_FIELD)?,
+            &ts.get_string(IDXCAT_FLDNAME_FIELD)?,
+            ts.get_int(IDXCAT_UNIQUE_FIELD)? != 0,
+        )
+        .with_index_type(IndexType::from_i32(ts.get_int(IDXCAT_TYPE_FIELD)?).unwrap_or_default()))
+    }
+}
+
+impl IndexManager for IndexManagerImpl<'_> {
+    // index を管理するために必要なファイルがまだ作成されていない場合、作成する
+    // このメソッドは何回呼んでも問題ない
+    fn setup_if_not_exists(&self, tx: &Rc<RefCell<Transaction>>) -> Result<(), IndexManagerError> {
+        if !self.has_included_catalog(tx) {
+            let mut schema = Schema::new();
+            schema.add_field(
+                IDXINCCAT_IDXNAME_FIELD,
+                FieldInfo::String(MAX_INDEX_NAME_LENGTH),
+            );
+            schema.add_field(
+                IDXINCCAT_FLDNAME_FIELD,
+                FieldInfo::String(MAX_FIELD_NAME_LENGTH),
+            );
+            schema.add_field(IDXINCCAT_POS_FIELD, FieldInfo::Integer);
+            self.table_manager
+                .create_table(IDXINCCAT_TABLE_NAME, schema, tx)?;
+        }
+        if self.is_set_up(tx) {
+            return Ok(());
+        }
+        let mut schema = Schema::new();
+        schema.add_field(
+            IDXCAT_IDXNAME_FIELD,
+            FieldInfo::String(MAX_INDEX_NAME_LENGTH),
+        );
+        schema.add_field(
+            IDXCAT_TBLNAME_FIELD,
+            FieldInfo::String(MAX_TABLE_NAME_LENGTH),
+        );
+        schema.add_field(
+            IDXCAT_FLDNAME_FIELD,
+            FieldInfo::String(MAX_FIELD_NAME_LENGTH),
+        );
+        schema.add_field(IDXCAT_UNIQUE_FIELD, FieldInfo::Integer);
+        schema.add_field(IDXCAT_TYPE_FIELD, FieldInfo::Integer);
+        self.table_manager
+            .create_table(IDXCAT_TABLE_NAME, schema, tx)?;
+        Ok(())
+    }
+
+    // index を作成する
+    // 同じ名前の index がすでにある場合や、table に index を作る field がない場合は error を返す
+    fn create_index(
+        &self,
+        index_info: &IndexInfo,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), IndexManagerError> {
+        validate_identifier(IdentifierKind::Index, index_info.index_name())?;
+        let table_layout = self.table_manager.get_layout(index_info.table_name(), tx)?;
+        if !table_layout.schema().has_field(index_info.field_name()) {
+            return Err(IndexManagerError::InvalidCall(format!(
+                "field {} not found in table {}",
+                index_info.field_name(),
+                index_info.table_name()
+            )));
+        }
+        for (pos, field) in index_info.included_fields().iter().enumerate() {
+            if !table_layout.schema().has_field(field) {
+                return Err(IndexManagerError::InvalidCall(format!(
+                    "field {} not found in table {}",
+                    field,
+                    index_info.table_name()
+                )));
+            }
+            if field == index_info.field_name()
+                || index_info.included_fields()[..pos].contains(field)
+            {
+                return Err(IndexManagerError::InvalidCall(format!(
+                    "field {} is included in index {} more than once",
+                    field,
+                    index_info.index_name()
+                )));
+            }
+        }
+        // partition に分けた table の Rid は partition の中の位置なので、index から record を指せない
+        if table_layout.partition().is_some() {
+            return Err(IndexManagerError::InvalidCall(format!(
+                "index on partitioned table {} is not supported",
+                index_info.table_name()
+            )));
+        }
+        // catalog の index と同じ名前の index は作れない
+        if SYSTEM_INDEX_NAMES.contains(&index_info.index_name())
+            || self.get_index(index_info.index_name(), tx)?.is_some()
+        {
+            return Err(IndexManagerError::InvalidCall(format!(
+                "index {} already exists",
+                index_info.index_name()
+            )));
+        }
+        let layout = self.table_manager.get_layout(IDXCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, IDXCAT_TABLE_NAME, &layout)?;
+        ts.insert()?;
+        ts.set_string(IDXCAT_IDXNAME_FIELD, index_info.index_name())?;
+        ts.set_string(IDXCAT_TBLNAME_FIELD, index_info.table_name())?;
+        ts.set_string(IDXCAT_FLDNAME_FIELD, index_info.field_name())?;
+        ts.set_int(IDXCAT_UNIQUE_FIELD, index_info.is_unique() as i32)?;
+        ts.set_int(IDXCAT_TYPE_FIELD, index_info.index_type() as i32)?;
+        if index_info.included_fields().is_empty() {
+            return Ok(());
+        }
+        let layout = self.table_manager.get_layout(IDXINCCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, IDXINCCAT_TABLE_NAME, &layout)?;
+        for (pos, field) in index_info.included_fields().iter().enumerate() {
+            ts.insert()?;
+            ts.set_string(IDXINCCAT_IDXNAME_FIELD, index_info.index_name())?;
+            ts.set_string(IDXINCCAT_FLDNAME_FIELD, field)?;
+            ts.set_int(IDXINCCAT_POS_FIELD, pos as i32)?;
+        }
+        Ok(())
+    }
+
+    // idxcat がまだ作成されていない場合は、index が一つもないものとみなす
+    fn get_index(
+        &self,
+        index_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<Option<IndexInfo>, IndexManagerError> {
+        if !self.is_set_up(tx) {
+            return Ok(None);
+        }
+        let layout = self.table_manager.get_layout(IDXCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create_read_only(tx, IDXCAT_TABLE_NAME, &layout)?;
+        while ts.move_next()? {
+            if ts.get_string(IDXCAT_IDXNAME_FIELD)? == index_name {
+                let index_info = Self::read_index_info(ts.as_mut())?;
+                drop(ts);
+                return Ok(self.with_included_fields(vec![index_info], tx)?.pop());
+            }
+        }
+        Ok(None)
+    }
+
+    // idxcat がまだ作成されていない場合は、index が一つもないものとみなす
+    fn get_index_info(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<Vec<IndexInfo>, IndexManagerError> {
+        if !self.is_set_up(tx) {
+            return Ok(vec![]);
+        }
+        let layout = self.table_manager.get_layout(IDXCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create_read_only(tx, IDXCAT_TABLE_NAME, &layout)?;
+        let mut indexes = vec![];
+        while ts.move_next()? {
+            if ts.get_string(IDXCAT_TBLNAME_FIELD)? == table_name {
+                indexes.push(Self::read_index_info(ts.as_mut())?);
+            }
+        }
+        drop(ts);
+        self.with_included_fields(indexes, tx)
+    }
+
+    // field 名の変更に合わせて、index の対象の field を付け替える
+    fn rename_field(
+        &self,
+        table_name: &str,
+        old_field_name: &str,
+        new_field_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), IndexManagerError> {
+        if !self.is_set_up(tx) {
+            return Ok(());
+        }
+        let layout = self.table_manager.get_layout(IDXCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, IDXCAT_TABLE_NAME, &layout)?;
+        let mut index_names = vec![];
+        while ts.move_next()? {
+            if ts.get_string(IDXCAT_TBLNAME_FIELD)? != table_name {
+                continue;
+            }
+            index_names.push(ts.get_string(IDXCAT_IDXNAME_FIELD)?);
+            if ts.get_string(IDXCAT_FLDNAME_FIELD)? == old_field_name {
+                ts.set_string(IDXCAT_FLDNAME_FIELD, new_field_name)?;
+            }
+        }
+        drop(ts);
+        // table の index が include している field も付け替える
+        if index_names.is_empty() || !self.has_included_catalog(tx) {
+            return Ok(());
+        }
+        let layout = self.table_manager.get_layout(IDXINCCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, IDXINCCAT_TABLE_NAME, &layout)?;
+        while ts.move_next()? {
+            if index_names.contains(&ts.get_string(IDXINCCAT_IDXNAME_FIELD)?)
+                && ts.get_string(IDXINCCAT_FLDNAME_FIELD)? == old_field_name
+            {
+                ts.set_string(IDXINCCAT_FLDNAME_FIELD, new_field_name)?;
+            }
+        }
+        Ok(())
+    }
+
+    // table 名の変更に合わせて、index の対象の table を付け替える
+    fn rename_table(
+        &self,
+        table_name: &str,
+        new_table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), IndexManagerError> {
+        if !self.is_set_up(tx) {
+            return Ok(());
+        }
+        let layout = self.table_manager.get_layout(IDXCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, IDXCAT_TABLE_NAME, &layout)?;
+        while ts.move_next()? {
+            if ts.get_string(IDXCAT_TBLNAME_FIELD)? == table_name {
+                ts.set_string(IDXCAT_TBLNAME_FIELD, new_table_name)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn rename_index(
+        &self,
+        index_name: &str,
+        new_index_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), IndexManagerError> {
+        validate_identifier(IdentifierKind::Index, new_index_name)?;
+        let index_info = self.get_index(index_name, tx)?.ok_or_else(|| {
+            IndexManagerError::InvalidCall(format!("index {} not found", index_name))
+        })?;
+        if SYSTEM_INDEX_NAMES.contains(&new_index_name)
+            || self.get_index(new_index_name, tx)?.is_some()
+        {
+            return Err(IndexManagerError::InvalidCall(format!(
+                "index {} already exists",
+                new_index_name
+            )));
+        }
+
+        // ファイル名は index 名から始まるので、先頭の index 名だけを置き換える
+        let new_index_info = IndexInfo::new(
+            new_index_name,
+            index_info.table_name(),
+            index_info.field_name(),
+            index_info.is_unique(),
+        )
+        .with_index_type(index_info.index_type())
+        .with_included_fields(index_info.included_fields().to_vec());
+        for (filename, new_filename) in index_info
+            .file_names()
+            .into_iter()
+            .zip(new_index_info.file_names())
+        {
+            // hash index のまだ record が追加されていない bucket はファイルが空なので、そのままにする
+            if tx
+                .borrow()
+                .size(&filename)
+                .map_err(TransactionCopyError::from)?
+                == 0
+            {
+                continue;
+            }
+            tx.borrow().rename_file(&filename, &new_filename)?;
+        }
+
+        let layout = self.table_manager.get_layout(IDXCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, IDXCAT_TABLE_NAME, &layout)?;
+        while ts.move_next()? {
+            if ts.get_string(IDXCAT_IDXNAME_FIELD)? == index_name {
+                ts.set_string(IDXCAT_IDXNAME_FIELD, new_index_name)?;
+            }
+        }
+        drop(ts);
+        if new_index_info.included_fields().is_empty() {
+            return Ok(());
+        }
+        let layout = self.table_manager.get_layout(IDXINCCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, IDXINCCAT_TABLE_NAME, &layout)?;
+        while ts.move_next()? {
+            if ts.get_string(IDXINCCAT_IDXNAME_FIELD)? == index_name {
+                ts.set_string(IDXINCCAT_IDXNAME_FIELD, new_index_name)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod index_manager_test {
+    use super::*;
+    use crate::{
+        buffer::buffer_manager::BufferManager,
+        file::file_manager::FileManager,
+        log::log_manager::LogManager,
+        metadata::table_manager::TableManagerImpl,
+        record::table_scan_factory::TableScanFactoryImpl,
+        tx::{concurrency::lock_table::LockTable, transaction::TransactionFactory},
+    };
+    use std::sync::Arc;
+    use tempfile::{tempdir, TempDir};
+
+    fn setup_factory(dir: &TempDir) -> TransactionFactory {
+        let file_manager = Arc::new(FileManager::new(dir.path(), 400));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager.clone(),
+            log_manager.clone(),
+            8,
+            Some(10),
+        ));
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        TransactionFactory::new(file_manager, log_manager, buffer_manager, lock_table)
+    }
+
+    #[test]
+    fn test_create_and_get_index() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let table_manager = TableManagerImpl::new(Arc::new(TableScanFactoryImpl::new())).unwrap();
+        table_manager.setup_if_not_exists(&tx).unwrap();
+        let mut schema = Schema::new();
+        schema.add_field("a", FieldInfo::Integer);
+        schema.add_field("b", FieldInfo::String(10));
+        table_manager.create_table("tbl", schema, &tx).unwrap();
+        let index_manager =
+            IndexManagerImpl::new(&table_manager, Box::new(TableScanFactoryImpl::new()));
+
+        // setup 前は index は一つもない
+        assert!(index_manager.get_index_info("tbl", &tx).unwrap().is_empty());
+        assert_eq!(index_manager.get_index("idx_a", &tx).unwrap(), None);
+
+        // 何回呼び出しても大丈夫
+        index_manager.setup_if_not_exists(&tx).unwrap();
+        index_manager.setup_if_not_exists(&tx).unwrap();
+
+        let idx_a = IndexInfo::new("idx_a", "tbl", "a", false);
+        let idx_b = IndexInfo::new("idx_b", "tbl", "b", true).with_index_type(IndexType::Hash);
+        index_manager.create_index(&idx_a, &tx).unwrap();
+        index_manager.create_index(&idx_b, &tx).unwrap();
+        assert_eq!(
+            index_manager.get_index_info("tbl", &tx).unwrap(),
+            vec![idx_a.clone(), idx_b.clone()]
+        );
+        assert_eq!(index_manager.get_index("idx_b", &tx).unwrap(), Some(idx_b));
+        assert!(index_manager
+            .get_index_info("other", &tx)
+            .unwrap()
+            .is_empty());
+
+        // 同じ名前の index や、存在しない table / field への index は作成できない
+        let err = index_manager
+            .create_index(&IndexInfo::new("idx_a", "tbl", "b", false), &tx)
+            .unwrap_err();
+        assert!(err.to_string().contains("index idx_a already exists"));
+        let err = index_manager
+            .create_index(&IndexInfo::new("idx_c", "tbl", "c", false), &tx)
+            .unwrap_err();
+        assert!(err.to_string().contains("field c not found in table tbl"));
+        assert!(index_manager
+            .create_index(&IndexInfo::new("idx_d", "no_such_table", "a", false), &tx)
+            .is_err());
+        assert!(index_manager
+            .create_index(&IndexInfo::new("select", "tbl", "a", false), &tx)
+            .is_err());
+        // catalog の system index と同じ名前は使えない
+        assert!(index_manager
+            .create_index(&IndexInfo::new("tblcatidx", "tbl", "a", false), &tx)
+            .is_err());
+
+        index_manager.rename_field("tbl", "a", "c", &tx).unwrap();
+        assert_eq!(
+            index_manager.get_index("idx_a", &tx).unwrap(),
+            Some(IndexInfo::new("idx_a", "tbl", "c", false))
+        );
+
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_included_fields() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let table_manager = TableManagerImpl::new(Arc::new(TableScanFactoryImpl::new())).unwrap();
+        table_manager.setup_if_not_exists(&tx).unwrap();
+        let mut schema = Schema::new();
+        schema.add_field("a", FieldInfo::Integer);
+        schema.add_field("b", FieldInfo::String(10));
+        schema.add_field("c", FieldInfo::Integer);
+        table_manager.create_table("tbl", schema, &tx).unwrap();
+        let index_manager =
+            IndexManagerImpl::new(&table_manager, Box::new(TableScanFactoryImpl::new()));
+        index_manager.setup_if_not_exists(&tx).unwrap();
+
+        // include した field は指定した順に保存される
+        let idx = IndexInfo::new("idx", "tbl", "a", false)
+            .with_included_fields(vec!["c".to_string(), "b".to_string()]);
+        index_manager.create_index(&idx, &tx).unwrap();
+        assert_eq!(
+            index_manager.get_index("idx", &tx).unwrap(),
+            Some(idx.clone())
+        );
+        assert_eq!(index_manager.get_index_info("tbl", &tx).unwrap(), vec![idx]);
+        let fields = |fields: &[&str]| fields.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        let idx = index_manager.get_index("idx", &tx).unwrap().unwrap();
+        assert!(idx.covers(&fields(&["a", "b"])));
+        assert!(!idx.covers(&fields(&["a", "d"])));
+
+        // 存在しない field、重複した field、index を張る field は include できない
+        for included in [vec!["d"], vec!["b", "b"], vec!["a"]] {
+            let idx =
+                IndexInfo::new("idx2", "tbl", "a", false).with_included_fields(fields(&included));
+            assert!(index_manager.create_index(&idx, &tx).is_err());
+        }
+
+        // field 名や index 名を変更すると、include した field の情報も変わる
+        index_manager.rename_field("tbl", "b", "name", &tx).unwrap();
+        index_manager
+            .rename_index("idx", "idx_renamed", &tx)
+            .unwrap();
+        assert_eq!(index_manager.get_index("idx", &tx).unwrap(), None);
+        assert_eq!(
+            index_manager.get_index("idx_renamed", &tx).unwrap(),
+            Some(
+                IndexInfo::new("idx_renamed", "tbl", "a", false)
+                    .with_included_fields(fields(&["c", "name"]))
+            )
+        );
+
+        tx.borrow_mut().commit().unwrap();
+    }
+}
@@ -3,23 +3,65 @@ use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
 use anyhow::Result as AnyhowResult;
 
 use crate::{
-    record::{layout::Layout, schema::Schema, table_scan_factory::TableScanFactoryImpl},
+    query::memory_budget::MemoryBudget,
+    record::{
+        layout::{Layout, StorageOptions},
+        partition::PartitionSpec,
+        schema::Schema,
+        table_scan_factory::TableScanFactoryImpl,
+    },
     tx::transaction::Transaction,
 };
 
 use super::{
-    stat_info::StatInfo, stat_manager::StatManagerFactory, table_manager::TableManager,
+    access_list::AccessList,
+    default_value_manager::DefaultValueManagerFactory,
+    external_table_manager::ExternalTableManagerFactory,
+    index_manager::{IndexInfo, IndexManagerFactory},
+    stat_info::StatInfo,
+    stat_manager::{StatCache, StatManagerFactory},
+    table_manager::TableManager,
     view_manager::ViewManagerFactory,
 };
 
 pub trait MetadataManager {
+    /// table が存在するかどうかを返す。temp table の場合は実体の名前で指定する
+    fn table_exists(&self, table_name: &str, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<bool>;
+    /// catalog に登録されている table の名前を返す。catalog 自身と temp table は含まない
+    fn get_table_names(&self, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<Vec<String>>;
     fn create_table(
         &self,
         table_name: &str,
         schema: Schema,
         tx: &Rc<RefCell<Transaction>>,
     ) -> AnyhowResult<()>;
+    /// block size や圧縮などの保存方法を指定して table を作成する
+    fn create_table_with_storage(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        storage: StorageOptions,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
+    /// field の値で record を partition に分けて保存する table を作成する
+    fn create_partitioned_table(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        storage: StorageOptions,
+        partition: PartitionSpec,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
     fn get_layout(&self, table_name: &str, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<Layout>;
+    /// 指定された transaction からのみ見える temp table を作成する
+    fn create_temp_table(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
+    /// table 名を実体の table 名に変換する (temp table の場合はその実体の名前になる)
+    fn resolve_table_name(&self, table_name: &str, tx: &Rc<RefCell<Transaction>>) -> String;
 
     fn create_view(
         &self,
@@ -28,19 +70,135 @@ pub trait MetadataManager {
         tx: &Rc<RefCell<Transaction>>,
     ) -> AnyhowResult<()>;
     fn get_view_def(&self, view_name: &str, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<String>;
+    /// 全ての view の (view 名, 定義) を取得する
+    fn get_view_defs(&self, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<Vec<(String, String)>>;
+    /// 既存の view の定義を置き換える
+    fn update_view_def(
+        &self,
+        view_name: &str,
+        view_def: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
 
+    /// table の統計情報を取得する。統計情報を計算する場合、その間にメモリ上に保持する値は budget に計上する
     fn get_table_stat(
         &self,
         table_name: &str,
+        budget: &MemoryBudget,
         tx: &Rc<RefCell<Transaction>>,
     ) -> AnyhowResult<HashMap<String, StatInfo>>;
+    /// 1 つの query で使うメモリの budget を作成する。query の plan の作成と実行の間で共有する
+    fn new_query_budget(&self) -> MemoryBudget;
+    /// 計算済みの table の統計情報を捨てて、次に取得する時に計算し直すようにする
+    fn invalidate_table_stat(&self, table_name: &str);
+    /// 計算済みの table の各 field の最小値と最大値を使わないようにする
+    fn forget_table_range(&self, table_name: &str);
+
+    /// field の default 値の式を保存する
+    fn set_default_expression(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        expression: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
+    /// table の field 名 -> default 値の式 のマップを取得する
+    fn get_default_expressions(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<HashMap<String, String>>;
+    /// 既存の field の default 値の式を置き換える
+    fn update_default_expression(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        expression: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
+
+    /// table の field 名を変更する。field を参照している catalog の情報 (default 値など) も合わせて変更する
+    /// view の定義や default 値の式の書き換えは行わないので、呼び出し側で get_view_defs, update_view_def や
+    /// get_default_expressions, update_default_expression を使って行う必要がある
+    fn rename_field(
+        &self,
+        table_name: &str,
+        old_field_name: &str,
+        new_field_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
+
+    /// table の名前を変更する。table を参照している catalog の情報 (index, default 値など) も合わせて変更する
+    /// view の定義の書き換えは行わないので、呼び出し側で get_view_defs, update_view_def を使って行う必要がある
+    fn rename_table(
+        &self,
+        table_name: &str,
+        new_table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
+
+    /// table の field に index を作成する
+    fn create_index(
+        &self,
+        index_info: &IndexInfo,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
+    /// 名前が index_name の index の情報を取得する。存在しない場合は None を返す
+    fn get_index(
+        &self,
+        index_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Option<IndexInfo>>;
+    /// table に作成されている index の情報を作成した順に取得する
+    fn get_index_info(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Vec<IndexInfo>>;
+
+    /// index の名前を変更する
+    fn rename_index(
+        &self,
+        index_name: &str,
+        new_index_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
+
+    /// 外部の CSV / TSV ファイルを read-only な table として登録する
+    fn create_external_table(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        path: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()>;
+    /// external table のファイルの path を取得する。external table でない場合は None を返す
+    fn get_external_table_path(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Option<String>>;
 }
 
 pub struct MetadataManagerImpl {
     table_manager: Arc<dyn TableManager>,
+    // 設定されている場合、layout を取得した table を最近参照された table として記録する
+    access_list: Option<Arc<AccessList>>,
+    // 統計情報の収集などでメモリ上に値を保持する時の、1 つの query あたりの上限 (byte)。None の場合は制限しない
+    query_memory_limit: Option<usize>,
+    // get_table_stat の呼び出しをまたいで共有する統計情報
+    stat_cache: Arc<StatCache>,
 }
 
 impl MetadataManager for MetadataManagerImpl {
+    fn table_exists(&self, table_name: &str, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<bool> {
+        Ok(self.table_manager.table_exists(table_name, tx)?)
+    }
+
+    fn get_table_names(&self, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<Vec<String>> {
+        Ok(self.table_manager.table_names(tx)?)
+    }
+
     fn create_table(
         &self,
         table_name: &str,
@@ -50,8 +208,52 @@ impl MetadataManager for MetadataManagerImpl {
         Ok(self.table_manager.create_table(table_name, schema, tx)?)
     }
 
+    fn create_table_with_storage(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        storage: StorageOptions,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        Ok(self
+            .table_manager
+            .create_table_with_storage(table_name, schema, storage, tx)?)
+    }
+
+    fn create_partitioned_table(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        storage: StorageOptions,
+        partition: PartitionSpec,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        Ok(self
+            .table_manager
+            .create_partitioned_table(table_name, schema, storage, partition, tx)?)
+    }
+
     fn get_layout(&self, table_name: &str, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<Layout> {
-        Ok(self.table_manager.get_layout(table_name, tx)?)
+        let layout = self.table_manager.get_layout(table_name, tx)?;
+        if let Some(access_list) = &self.access_list {
+            access_list.record(table_name)?;
+        }
+        Ok(layout)
+    }
+
+    fn create_temp_table(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        Ok(self
+            .table_manager
+            .create_temp_table(table_name, schema, tx)?)
+    }
+
+    fn resolve_table_name(&self, table_name: &str, tx: &Rc<RefCell<Transaction>>) -> String {
+        self.table_manager.resolve_table_name(table_name, tx)
     }
 
     fn create_view(
@@ -64,6 +266,7 @@ impl MetadataManager for MetadataManagerImpl {
             self.table_manager.as_ref(),
             Box::new(TableScanFactoryImpl::new()),
         );
+        view_manager.setup_if_not_exists(tx)?;
         Ok(view_manager.create_view(view_name, view_def, tx)?)
     }
 
@@ -75,21 +278,240 @@ impl MetadataManager for MetadataManagerImpl {
         Ok(view_manager.get_view_def(view_name, tx)?)
     }
 
+    fn get_view_defs(&self, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<Vec<(String, String)>> {
+        let view_manager = ViewManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        Ok(view_manager.get_view_defs(tx)?)
+    }
+
+    fn update_view_def(
+        &self,
+        view_name: &str,
+        view_def: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        let view_manager = ViewManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        Ok(view_manager.update_view_def(view_name, view_def, tx)?)
+    }
+
     fn get_table_stat(
         &self,
         table_name: &str,
+        budget: &MemoryBudget,
         tx: &Rc<RefCell<Transaction>>,
     ) -> AnyhowResult<HashMap<String, StatInfo>> {
         let stat_manager = StatManagerFactory::create(
             self.table_manager.as_ref(),
             Box::new(TableScanFactoryImpl::new()),
+            budget.clone(),
+            self.stat_cache.clone(),
         );
         stat_manager.get_table_stat(table_name, tx)
     }
+
+    fn new_query_budget(&self) -> MemoryBudget {
+        MemoryBudget::new(self.query_memory_limit)
+    }
+
+    fn invalidate_table_stat(&self, table_name: &str) {
+        self.stat_cache.invalidate(table_name);
+    }
+
+    fn forget_table_range(&self, table_name: &str) {
+        self.stat_cache.forget_range(table_name);
+    }
+
+    fn set_default_expression(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        expression: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        let default_value_manager = DefaultValueManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        default_value_manager.setup_if_not_exists(tx)?;
+        default_value_manager.set_default_expression(table_name, field_name, expression, tx)
+    }
+
+    fn get_default_expressions(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<HashMap<String, String>> {
+        let default_value_manager = DefaultValueManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        default_value_manager.get_default_expressions(table_name, tx)
+    }
+
+    fn update_default_expression(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        expression: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        let default_value_manager = DefaultValueManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        default_value_manager.update_default_expression(table_name, field_name, expression, tx)
+    }
+
+    fn rename_field(
+        &self,
+        table_name: &str,
+        old_field_name: &str,
+        new_field_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        self.table_manager
+            .rename_field(table_name, old_field_name, new_field_name, tx)?;
+        let default_value_manager = DefaultValueManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        default_value_manager.rename_field(table_name, old_field_name, new_field_name, tx)?;
+        let index_manager = IndexManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        Ok(index_manager.rename_field(table_name, old_field_name, new_field_name, tx)?)
+    }
+
+    fn rename_table(
+        &self,
+        table_name: &str,
+        new_table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        self.table_manager
+            .rename_table(table_name, new_table_name, tx)?;
+        let default_value_manager = DefaultValueManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        default_value_manager.rename_table(table_name, new_table_name, tx)?;
+        let external_table_manager = ExternalTableManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        external_table_manager.rename_table(table_name, new_table_name, tx)?;
+        let index_manager = IndexManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        index_manager.rename_table(table_name, new_table_name, tx)?;
+        // 統計情報は table 名ごとに保持しているので、どちらの名前のものも計算し直す
+        self.stat_cache.invalidate(table_name);
+        self.stat_cache.invalidate(new_table_name);
+        Ok(())
+    }
+
+    fn create_index(
+        &self,
+        index_info: &IndexInfo,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        let index_manager = IndexManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        index_manager.setup_if_not_exists(tx)?;
+        Ok(index_manager.create_index(index_info, tx)?)
+    }
+
+    fn get_index(
+        &self,
+        index_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Option<IndexInfo>> {
+        let index_manager = IndexManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        Ok(index_manager.get_index(index_name, tx)?)
+    }
+
+    fn get_index_info(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Vec<IndexInfo>> {
+        let index_manager = IndexManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        Ok(index_manager.get_index_info(table_name, tx)?)
+    }
+
+    fn rename_index(
+        &self,
+        index_name: &str,
+        new_index_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        let index_manager = IndexManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        Ok(index_manager.rename_index(index_name, new_index_name, tx)?)
+    }
+
+    fn create_external_table(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        path: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<()> {
+        let external_table_manager = ExternalTableManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        external_table_manager.setup_if_not_exists(tx)?;
+        Ok(external_table_manager.create_external_table(table_name, schema, path, tx)?)
+    }
+
+    fn get_external_table_path(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Option<String>> {
+        let external_table_manager = ExternalTableManagerFactory::create(
+            self.table_manager.as_ref(),
+            Box::new(TableScanFactoryImpl::new()),
+        );
+        Ok(external_table_manager.get_external_path(table_name, tx)?)
+    }
 }
 
 impl MetadataManagerImpl {
     pub fn new(table_manager: Arc<dyn TableManager>) -> AnyhowResult<Self> {
-        Ok(Self { table_manager })
+        Ok(Self {
+            table_manager,
+            access_list: None,
+            query_memory_limit: None,
+            stat_cache: Arc::new(StatCache::new()),
+        })
+    }
+
+    pub fn with_access_list(mut self, access_list: Arc<AccessList>) -> Self {
+        self.access_list = Some(access_list);
+        self
+    }
+
+    pub fn with_query_memory_limit(mut self, limit: Option<usize>) -> Self {
+        self.query_memory_limit = limit;
+        self
     }
 }
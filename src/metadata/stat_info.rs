@@ -1,13 +1,18 @@
+use crate::query::constant::Constant;
+
 /**
  * Table のそれぞれのカラムに対する統計情報を保持するための構造体
  *
  * 実装の都合上、必ずしも正確な値が返されるわけではないことに注意
  */
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct StatInfo {
     num_blocks: u64,
     num_records: u64,
     num_distinct_values: u64,
+    // カラムの値の最小値と最大値。record がない場合は None
+    min: Option<Constant>,
+    max: Option<Constant>,
 }
 
 impl StatInfo {
@@ -16,9 +21,24 @@ impl StatInfo {
             num_blocks,
             num_records,
             num_distinct_values,
+            min: None,
+            max: None,
         }
     }
 
+    /// 統計情報がない場合に代わりに使う見積もり (1 block, 1 record)
+    /// 小さめに見積もっておき、統計情報がなくても planner が plan を作れるようにする
+    pub fn missing() -> Self {
+        Self::new(1, 1, 1)
+    }
+
+    /// カラムの値の最小値と最大値を設定する
+    pub fn with_range(mut self, min: Constant, max: Constant) -> Self {
+        self.min = Some(min);
+        self.max = Some(max);
+        self
+    }
+
     /// table の保持する block 数を返す
     pub fn get_num_blocks(&self) -> u64 {
         self.num_blocks
@@ -33,4 +53,15 @@ impl StatInfo {
     pub fn get_num_distinct_values(&self) -> u64 {
         self.num_distinct_values
     }
+
+    /// 最小値と最大値を捨てる。record の数などの値はそのまま残す
+    pub fn clear_range(&mut self) {
+        self.min = None;
+        self.max = None;
+    }
+
+    /// カラムの値の (最小値, 最大値) を返す。分からない場合は None を返す
+    pub fn get_range(&self) -> Option<(&Constant, &Constant)> {
+        Some((self.min.as_ref()?, self.max.as_ref()?))
+    }
 }
@@ -2,7 +2,7 @@ use std::{
     cell::RefCell,
     collections::{HashMap, HashSet},
     rc::Rc,
-    sync::Mutex,
+    sync::{Arc, Mutex},
 };
 
 use anyhow::{anyhow, Result as AnyhowResult};
@@ -10,16 +10,12 @@ use dashmap::DashMap;
 use thiserror::Error;
 
 use crate::{
-    query::constant::Constant,
+    query::{constant::Constant, memory_budget::MemoryBudget, row::Row},
     record::{layout::Layout, table_scan_factory::TableScanFactory},
     tx::transaction::Transaction,
 };
 
-use super::{
-    constants::{TBLCAT_TABLE_NAME, TBLCAT_TBLNAME_FIELD},
-    stat_info::StatInfo,
-    table_manager::TableManager,
-};
+use super::{stat_info::StatInfo, table_manager::TableManager};
 
 pub trait StatManager {
     /// 指定されたテーブルの指定されたフィールドの統計情報を取得する
@@ -57,17 +53,52 @@ struct FieldId {
     field_name: String,
 }
 
+/**
+ * 計算済みの統計情報を保持する構造体
+ *
+ * StatManager を作り直しても統計情報を再計算しなくて済むように、StatManager の外で保持して共有する
+ * 大量の record が変更された table は invalidate で捨てておき、次に問い合わせがあった時に計算し直す
+ */
+#[derive(Default)]
+pub struct StatCache {
+    field_stats: DashMap<FieldId, StatInfo>,
+    num_calls: Mutex<u64>,
+}
+
+impl StatCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// table の統計情報を捨てる。次に問い合わせがあった時に計算し直される
+    pub fn invalidate(&self, table_name: &str) {
+        self.field_stats
+            .retain(|field_id, _| field_id.table_name != table_name);
+    }
+
+    /// table の各 field の最小値と最大値だけを捨てる
+    /// record の数などは多少ずれても見積もりが悪くなるだけだが、範囲がずれると結果が空だと誤って判断してしまうので、
+    /// 変更があった table の範囲はすぐに使わないようにする
+    pub fn forget_range(&self, table_name: &str) {
+        self.field_stats
+            .iter_mut()
+            .filter(|entry| entry.key().table_name == table_name)
+            .for_each(|mut entry| entry.value_mut().clear_range());
+    }
+}
+
 /**
  * 統計情報を管理するための構造体
  *
  * イベントを受け取って統計情報を更新するなどの実装方針も考えられるが、今回の実装では一定の回数問い合わせがあるたびにテーブルを full scan し直して
  * 統計情報を更新するような方針をとる
+ * full scan の間は field ごとのユニークな値をメモリ上に保持するので、その使用量は memory_budget で制限する
  */
 pub struct StatManagerImpl<'a> {
     table_manager: &'a dyn TableManager,
     table_scan_factory: Box<dyn TableScanFactory>,
-    field_stats: DashMap<FieldId, StatInfo>,
-    num_calls: Mutex<u64>,
+    cache: Arc<StatCache>,
+    memory_budget: MemoryBudget,
 }
 
 impl StatManager for StatManagerImpl<'_> {
@@ -79,6 +110,7 @@ impl StatManager for StatManagerImpl<'_> {
     ) -> AnyhowResult<StatInfo> {
         {
             let mut num_calls = self
+                .cache
                 .num_calls
                 .lock()
                 .map_err(|_| StatManagerError::Internal("Failed to lock mutex".to_string()))?;
@@ -92,24 +124,26 @@ impl StatManager for StatManagerImpl<'_> {
             table_name: table_name.to_string(),
             field_name: field_name.to_string(),
         };
-        match self.field_stats.get(&field_id) {
-            Some(stat_info) => Ok(*stat_info.value()),
+        match self.cache.field_stats.get(&field_id) {
+            Some(stat_info) => Ok(stat_info.value().clone()),
             None => {
                 // 統計情報が見つからない場合は再計算する
                 let table_layout = self.table_manager.get_layout(table_name, tx)?;
                 let table_stats = self.calc_table_stats(table_name, table_layout, &tx)?;
                 for (field_id, stat_info) in table_stats {
-                    self.field_stats.insert(field_id, stat_info);
+                    self.cache.field_stats.insert(field_id, stat_info);
                 }
                 // 再計算しても見つからない場合はエラーを返す
-                Ok(*self
+                Ok(self
+                    .cache
                     .field_stats
                     .get(&field_id)
                     .ok_or(anyhow!(StatManagerError::InvalidCall(format!(
                     "Failed to get stat info for field ({}, {}). Probably the field does not exist",
                     table_name, field_name
                 ))))?
-                    .value())
+                    .value()
+                    .clone())
             }
         }
     }
@@ -121,9 +155,9 @@ impl StatManager for StatManagerImpl<'_> {
         let layout = self.table_manager.get_layout(table_name, tx)?;
         let schema = layout.schema();
         let mut table_stats = HashMap::new();
-        for field in schema.fields() {
-            let stat_info = self.get_field_stat(table_name, &field, tx)?;
-            table_stats.insert(field, stat_info);
+        for field in schema.fields_iter() {
+            let stat_info = self.get_field_stat(table_name, field, tx)?;
+            table_stats.insert(field.to_string(), stat_info);
         }
         Ok(table_stats)
     }
@@ -137,25 +171,31 @@ impl<'a> StatManagerImpl<'a> {
         Self {
             table_manager,
             table_scan_factory,
-            field_stats: DashMap::new(),
-            num_calls: Mutex::new(0),
+            cache: Arc::new(StatCache::new()),
+            memory_budget: MemoryBudget::unlimited(),
         }
     }
 
+    /// 計算済みの統計情報を他の StatManager と共有する
+    pub fn with_cache(mut self, cache: Arc<StatCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn with_memory_budget(mut self, memory_budget: MemoryBudget) -> Self {
+        self.memory_budget = memory_budget;
+        self
+    }
+
     /// 統計情報を更新する
     fn refresh_statistics(&self, tx: Rc<RefCell<Transaction>>) -> AnyhowResult<()> {
-        self.field_stats.clear();
-        let mut tcat_scan = {
-            let tcat_layout = self.table_manager.get_layout(TBLCAT_TABLE_NAME, &tx)?;
-            self.table_scan_factory
-                .create(&tx, TBLCAT_TABLE_NAME, &tcat_layout)?
-        };
-        while tcat_scan.move_next()? {
-            let table_name = tcat_scan.get_string(TBLCAT_TBLNAME_FIELD)?;
+        self.cache.field_stats.clear();
+        // tblcat には tblcat 自身の layout が登録されていないので、table の一覧は table manager から受け取る
+        for table_name in self.table_manager.table_names(&tx)? {
             let table_layout = self.table_manager.get_layout(&table_name, &tx)?;
             let stats_for_table = self.calc_table_stats(&table_name, table_layout, &tx)?;
             for (field_id, stat_info) in stats_for_table {
-                self.field_stats.insert(field_id, stat_info);
+                self.cache.field_stats.insert(field_id, stat_info);
             }
         }
 
@@ -169,21 +209,12 @@ impl<'a> StatManagerImpl<'a> {
         table_layout: Layout,
         tx: &Rc<RefCell<Transaction>>,
     ) -> AnyhowResult<DashMap<FieldId, StatInfo>> {
-        let mut num_blocks = 0u64;
+        // partition ごとの、record がある最後の block の番号 (partition に分けていない table は partition 0 だけ)
+        let mut last_blocks: HashMap<usize, usize> = HashMap::new();
         let mut num_records = 0;
-        // 各フィールドのユニークな値を保持するための HashMap を作成
-        // 空の HashSet を持った状態で初期化
-        let mut field_to_values = {
-            let mut field_to_values = HashMap::new();
-            for field in table_layout.schema().fields() {
-                let field_id = FieldId {
-                    table_name: table_name.to_string(),
-                    field_name: field,
-                };
-                field_to_values.insert(field_id, HashSet::new());
-            }
-            field_to_values
-        };
+        // 各フィールドのユニークな値を、schema の field と同じ順番で保持する
+        let schema = table_layout.schema();
+        let mut field_values: Vec<HashSet<Constant>> = vec![HashSet::new(); schema.len()];
 
         let mut table_scan = {
             let table_layout = self.table_manager.get_layout(table_name, tx)?;
@@ -191,24 +222,44 @@ impl<'a> StatManagerImpl<'a> {
                 .create(tx, table_name, &table_layout)?
         };
 
-        while table_scan.move_next()? {
-            num_blocks = (table_scan.get_rid()?.block_number() + 1) as u64;
-            num_records += 1;
-            for field in table_layout.schema().fields() {
-                let constant = table_scan.get_val(&field)?;
-                let field_id = FieldId {
-                    table_name: table_name.to_string(),
-                    field_name: field,
-                };
-                let set = field_to_values.entry(field_id).or_default();
-                set.insert(constant);
+        // ユニークな値を保持するために reserve したメモリの量。集計が終わったら (失敗した場合も) 解放する
+        let mut reserved = 0;
+        // 全ての record を同じ row に読み込み、まだ集めていない値の場合だけ複製して保持する
+        let mut row = Row::new(schema.fields());
+        let result = (|| -> AnyhowResult<()> {
+            while table_scan.move_next()? {
+                let rid = table_scan.get_rid()?;
+                last_blocks.insert(rid.partition(), rid.block_number());
+                num_records += 1;
+                table_scan.read_into(&mut row)?;
+                for (constant, set) in row.values().iter().zip(field_values.iter_mut()) {
+                    if !set.contains(constant) {
+                        let size = MemoryBudget::constant_size(constant);
+                        self.memory_budget.reserve(size)?;
+                        reserved += size;
+                        set.insert(constant.clone());
+                    }
+                }
             }
-        }
+            Ok(())
+        })();
+        self.memory_budget.release(reserved);
+        result?;
+        let num_blocks = last_blocks.values().map(|last| (last + 1) as u64).sum();
 
         let dash_map = DashMap::new();
-        for (field_id, values) in field_to_values {
+        for (field, values) in schema.fields_iter().zip(field_values) {
+            let field_id = FieldId {
+                table_name: table_name.to_string(),
+                field_name: field.to_string(),
+            };
             let num_distinct_values = values.len() as u64;
-            let stat_info = StatInfo::new(num_blocks, num_records, num_distinct_values);
+            let mut stat_info = StatInfo::new(num_blocks, num_records, num_distinct_values);
+            // 同じ field の値は全て同じ型なので、そのまま大小を比べられる
+            // double も Constant の順序では全順序として比べるので、NaN があっても最小値と最大値が決まる
+            if let (Some(min), Some(max)) = (values.iter().min(), values.iter().max()) {
+                stat_info = stat_info.with_range(min.clone(), max.clone());
+            }
             dash_map.insert(field_id, stat_info);
         }
         Ok(dash_map)
@@ -221,8 +272,12 @@ impl StatManagerFactory {
     pub fn create<'a>(
         table_manager: &'a dyn TableManager,
         table_scan_factory: Box<dyn TableScanFactory>,
+        memory_budget: MemoryBudget,
+        cache: Arc<StatCache>,
     ) -> Box<dyn StatManager + 'a> {
-        let stat_manager = StatManagerImpl::new(table_manager, table_scan_factory);
+        let stat_manager = StatManagerImpl::new(table_manager, table_scan_factory)
+            .with_memory_budget(memory_budget)
+            .with_cache(cache);
         Box::new(stat_manager)
     }
 }
@@ -332,6 +387,10 @@ mod stat_manager_test {
             assert_eq!(stat_info.get_num_records(), 2);
             // 重複しているので 1 つしかない
             assert_eq!(stat_info.get_num_distinct_values(), 1);
+            assert_eq!(
+                stat_info.get_range(),
+                Some((&Constant::Int(1), &Constant::Int(1)))
+            );
         }
         {
             let result = stat_manager.get_field_stat("tbl", "B", &tx);
@@ -341,6 +400,13 @@ mod stat_manager_test {
             assert_eq!(stat_info.get_num_records(), 2);
             // こちらはバラバラの値が入っている
             assert_eq!(stat_info.get_num_distinct_values(), 2);
+            assert_eq!(
+                stat_info.get_range(),
+                Some((
+                    &Constant::String("string 1".to_string()),
+                    &Constant::String("string 2".to_string())
+                ))
+            );
         }
         {
             let result = stat_manager.get_field_stat("tbl", "C", &tx);
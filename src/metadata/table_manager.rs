@@ -1,54 +1,137 @@
 use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
 
+use dashmap::DashMap;
+#[cfg(test)]
 use mockall::automock;
 use thiserror::Error;
 
 use crate::{
+    buffer::buffer_manager::BufferManagerError,
+    file::{blockid::BlockId, file_manager::FileManagerError},
+    index::{
+        btree_index::BTreeIndex,
+        common::{index_layout, Index, IndexEntry, IndexError},
+    },
     metadata::constants::{
-        FCAT_FLDNAME_FIELD, FCAT_LENGTH_FIELD, FCAT_OFFSET_FIELD, FCAT_TBLNAME_FIELD,
-        FCAT_TYPE_FIELD, FLDCAT_TABLE_NAME, MAX_TABLE_NAME_LENGTH, TBLCAT_SLOTSIZE_FIELD,
-        TBLCAT_TABLE_NAME,
+        CATALOG_TABLE_NAMES, FCAT_FLDNAME_FIELD, FCAT_LENGTH_FIELD, FCAT_OFFSET_FIELD,
+        FCAT_TBLNAME_FIELD, FCAT_TYPE_FIELD, FLDCAT_INDEX_NAME, FLDCAT_TABLE_NAME,
+        MAX_PARTITION_COUNT, MAX_TABLE_NAME_LENGTH, PARTCAT_FLDNAME_FIELD, PARTCAT_LOWER_FIELD,
+        PARTCAT_PARTNO_FIELD, PARTCAT_SCHEME_FIELD, PARTCAT_TABLE_NAME, PARTCAT_TBLNAME_FIELD,
+        SYSTEM_INDEX_NAMES, TBLCAT_BLKSIZE_FIELD, TBLCAT_COMPRESS_FIELD, TBLCAT_INDEX_NAME,
+        TBLCAT_SLOTSIZE_FIELD, TBLCAT_TABLE_NAME, TEMP_TABLE_PREFIX,
+    },
+    metadata::identifier::{validate_identifier, IdentifierError, IdentifierKind},
+    query::{
+        constant::Constant,
+        scan::{ReadScanError, UpdateScan, UpdateScanError},
     },
-    query::{scan::ReadScanError, scan::UpdateScanError},
     record::{
-        layout::{Layout, LayoutError},
+        layout::{Layout, LayoutError, StorageOptions},
+        partition::{stable_hash, PartitionScheme, PartitionSpec},
+        record_page::{RecordPage, RecordPageError},
+        rid::Rid,
         schema::{FieldInfo, FieldType, Schema},
         table_scan_factory::{TableScanFactory, TableScanFactoryError},
     },
-    tx::transaction::Transaction,
+    tx::transaction::{Transaction, TransactionCopyError, TransactionSizeError},
 };
 
 use super::constants::MAX_FIELD_NAME_LENGTH;
 
-#[automock]
+#[cfg_attr(test, automock)]
 pub trait TableManager {
     /// table manager が table を管理するために必要なファイルがまだ作成されていない場合、作成する
+    /// tblcat と fldcat を table 名で探すための index もここで作成する
     /// このメソッドは何回呼んでも問題ない
     fn setup_if_not_exists(&self, tx: &Rc<RefCell<Transaction>>) -> Result<(), TableManagerError>;
-    // 新しい table を作成する
-    // Warning: すでに table が存在する場合、エラーを返すべきだが、その確認は特にしていない
+    /// table が存在するかどうかを返す。temp table の場合は実体の名前で指定する
+    fn table_exists(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<bool, TableManagerError>;
+    /// tblcat に登録されている table の名前を、登録された順に返す
+    /// tblcat, fldcat などの catalog 自身と temp table は含まない
+    fn table_names(&self, tx: &Rc<RefCell<Transaction>>) -> Result<Vec<String>, TableManagerError>;
+    /// 新しい table を作成する。すでに同じ名前の table が存在する場合はエラーを返す
     fn create_table(
         &self,
         table_name: &str,
         schema: Schema,
         tx: &Rc<RefCell<Transaction>>,
     ) -> Result<(), TableManagerError>;
+    /// block size や圧縮などの保存方法を指定して新しい table を作成する
+    /// storage が default の場合は create_table と同じ
+    fn create_table_with_storage(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        storage: StorageOptions,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError>;
+    /// field の値で record を partition に分けて保存する table を作成する
+    /// partition の設定は partcat に保存し、get_layout で返す layout に含める
+    fn create_partitioned_table(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        storage: StorageOptions,
+        partition: PartitionSpec,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError>;
     fn get_layout(
         &self,
         table_name: &str,
         tx: &Rc<RefCell<Transaction>>,
     ) -> Result<Layout, TableManagerError>;
+    /// 指定された transaction からのみ見える temp table を作成する
+    /// temp table は tblcat, fldcat には書き込まれず、実体は temp から始まる名前のファイルに保存される
+    fn create_temp_table(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError>;
+    /// table 名を、実際にファイルとして保存されている table 名に変換する
+    /// 指定された transaction が同名の temp table を作成している場合はその実体の名前を、そうでなければ table_name をそのまま返す
+    fn resolve_table_name(&self, table_name: &str, tx: &Rc<RefCell<Transaction>>) -> String;
+    /// table の field 名を変更する。record 中の field の位置は変わらない
+    fn rename_field(
+        &self,
+        table_name: &str,
+        old_field_name: &str,
+        new_field_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError>;
+    /// table の名前を変更する。tblcat, fldcat, partcat の record と、table のファイルの名前を変更する
+    /// temp table と catalog の名前は変更できない
+    fn rename_table(
+        &self,
+        table_name: &str,
+        new_table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError>;
 }
 
 /**
  * table の作成及び table の定義情報の取得を行うためのクラス
  *
- * 内部的には tblcat という table に table の一覧を保存し、fldcat という table に各 table の field の情報を保存している
+ * 内部的には tblcat という table に table の一覧 (slot size, block size を含む) を保存し、fldcat という table に各 table の field の情報を保存している
+ * temp table の layout はメモリ上にのみ保持し、tblcat, fldcat には保存しない
+ * partition に分けた table の分け方は、最初の partitioned table を作成した時に作る partcat という table に保存する
+ * tblcat と fldcat には table 名の hash 値の B-Tree index (system index) を張り、table ごとの record を全体を読まずに探す
+ * table 名をそのまま key にすると directory の 1 block に数個の entry しか入らないので、固定長の hash 値を key にして、見つけた record の table 名を確かめる
+ * system index が作成される前の database では、今まで通り tblcat と fldcat を先頭から読む
  */
 pub struct TableManagerImpl {
     tcat_layout: Layout,
     fcat_layout: Layout,
+    pcat_layout: Layout,
+    // system index の leaf の record の layout
+    sysidx_layout: Layout,
     table_scan_factory: Arc<dyn TableScanFactory>,
+    // temp table の実体の名前 -> layout。transaction が終わった時に、その transaction の temp table を取り除く
+    temp_tables: Arc<DashMap<String, Layout>>,
 }
 
 #[derive(Error, Debug)]
@@ -63,6 +146,22 @@ pub(crate) enum TableManagerError {
     UpdateScan(#[from] UpdateScanError),
     #[error("invalid call error: {0}")]
     InvalidCall(String),
+    #[error("table {0} already exists")]
+    AlreadyExists(String),
+    #[error("identifier error: {0}")]
+    Identifier(#[from] IdentifierError),
+    #[error("file manager error: {0}")]
+    FileManager(#[from] FileManagerError),
+    #[error("transaction error: {0}")]
+    Transaction(#[from] TransactionCopyError),
+    #[error("transaction size error: {0}")]
+    TransactionSize(#[from] TransactionSizeError),
+    #[error("record page error: {0}")]
+    RecordPage(#[from] RecordPageError),
+    #[error("buffer manager error: {0}")]
+    BufferManager(#[from] BufferManagerError),
+    #[error("index error: {0}")]
+    Index(#[from] IndexError),
     #[error("internal error: {0}")]
     Internal(String),
     // TODO: 治す
@@ -76,25 +175,101 @@ impl TableManager for TableManagerImpl {
         let mut tcat = self
             .table_scan_factory
             .create(tx, TBLCAT_TABLE_NAME, &self.tcat_layout)?;
+        let mut initialized = false;
         while tcat.move_next()? {
             if tcat.get_string(TBLCAT_TABLE_NAME)? == TBLCAT_TABLE_NAME {
-                return Ok(());
+                initialized = true;
+                break;
             }
         }
-        self.create_table(TBLCAT_TABLE_NAME, self.tcat_layout.schema().clone(), tx)?;
-        self.create_table(FLDCAT_TABLE_NAME, self.fcat_layout.schema().clone(), tx)?;
-        Ok(())
+        drop(tcat);
+        if !initialized {
+            self.create_table(TBLCAT_TABLE_NAME, self.tcat_layout.schema().clone(), tx)?;
+            self.create_table(FLDCAT_TABLE_NAME, self.fcat_layout.schema().clone(), tx)?;
+        }
+        self.setup_system_indexes(tx)
+    }
+
+    fn table_exists(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<bool, TableManagerError> {
+        if self.temp_tables.contains_key(table_name) {
+            return Ok(true);
+        }
+        let mut tcat = self
+            .table_scan_factory
+            .create(tx, TBLCAT_TABLE_NAME, &self.tcat_layout)?;
+        if let Some(rids) = self.find_catalog_rids(
+            TBLCAT_INDEX_NAME,
+            tcat.as_mut(),
+            TBLCAT_TABLE_NAME,
+            table_name,
+            tx,
+        )? {
+            return Ok(!rids.is_empty());
+        }
+        while tcat.move_next()? {
+            if tcat.get_string(TBLCAT_TABLE_NAME)? == table_name {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn table_names(&self, tx: &Rc<RefCell<Transaction>>) -> Result<Vec<String>, TableManagerError> {
+        let mut tcat = self
+            .table_scan_factory
+            .create(tx, TBLCAT_TABLE_NAME, &self.tcat_layout)?;
+        let mut table_names = vec![];
+        while tcat.move_next()? {
+            let table_name = tcat.get_string(TBLCAT_TABLE_NAME)?;
+            if !CATALOG_TABLE_NAMES.contains(&table_name.as_str()) {
+                table_names.push(table_name);
+            }
+        }
+        Ok(table_names)
     }
 
     /// 新しい table を作成する
-    /// Warning: すでに table が存在する場合、エラーを返すべきだが、その確認は特にしていない
     fn create_table(
         &self,
         table_name: &str,
         schema: Schema,
         tx: &Rc<RefCell<Transaction>>,
     ) -> Result<(), TableManagerError> {
+        self.create_table_with_storage(table_name, schema, StorageOptions::default(), tx)
+    }
+
+    fn create_table_with_storage(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        storage: StorageOptions,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError> {
+        Self::validate_names(table_name, &schema)?;
+        if self.table_exists(table_name, tx)? {
+            return Err(TableManagerError::AlreadyExists(table_name.to_string()));
+        }
         let layout = Layout::new(schema.clone())?;
+        if storage.compressed && !cfg!(feature = "compression") {
+            return Err(TableManagerError::InvalidCall(
+                "compressed tables require the compression feature".to_string(),
+            ));
+        }
+        if let Some(block_size) = storage.block_size {
+            // record は block をまたいで保存できないので、少なくとも 1 record は入る block size でないといけない
+            if layout.slot_size() >= block_size {
+                return Err(TableManagerError::InvalidCall(format!(
+                    "block size {} is too small for table {} (slot size: {})",
+                    block_size,
+                    table_name,
+                    layout.slot_size()
+                )));
+            }
+        }
 
         {
             let mut tcat =
@@ -103,14 +278,21 @@ impl TableManager for TableManagerImpl {
             tcat.insert()?;
             tcat.set_string(TBLCAT_TABLE_NAME, table_name)?;
             tcat.set_int(TBLCAT_SLOTSIZE_FIELD, layout.slot_size() as i32)?;
+            // block size を指定しない場合は 0 を保存する
+            tcat.set_int(TBLCAT_BLKSIZE_FIELD, storage.block_size.unwrap_or(0) as i32)?;
+            tcat.set_int(TBLCAT_COMPRESS_FIELD, storage.compressed as i32)?;
+            let rid = tcat.get_rid()?;
+            self.insert_catalog_entry(TBLCAT_INDEX_NAME, table_name, &rid, tx)?;
         }
 
         {
             let mut fcat =
                 self.table_scan_factory
                     .create(tx, FLDCAT_TABLE_NAME, &self.fcat_layout)?;
-            for field in &schema.fields() {
+            for field in schema.fields_iter() {
                 fcat.insert()?;
+                let rid = fcat.get_rid()?;
+                self.insert_catalog_entry(FLDCAT_INDEX_NAME, table_name, &rid, tx)?;
                 fcat.set_string(FCAT_TBLNAME_FIELD, table_name)?;
                 fcat.set_string(FCAT_FLDNAME_FIELD, field)?;
                 match schema.info(field) {
@@ -126,7 +308,11 @@ impl TableManager for TableManagerImpl {
                                 )))? as i32,
                         )?;
                         match info {
-                            FieldInfo::Integer => {
+                            FieldInfo::Integer
+                            | FieldInfo::Boolean
+                            | FieldInfo::BigInt
+                            | FieldInfo::Date
+                            | FieldInfo::Double => {
                                 fcat.set_int(FCAT_LENGTH_FIELD, 0)?;
                             }
                             FieldInfo::String(length) => {
@@ -143,6 +329,69 @@ impl TableManager for TableManagerImpl {
                 }
             }
         }
+        if storage != StorageOptions::default() {
+            self.persist_storage_catalog(tx)?;
+        }
+        Ok(())
+    }
+
+    fn create_partitioned_table(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        storage: StorageOptions,
+        partition: PartitionSpec,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError> {
+        match (schema.info(partition.field()), partition.scheme()) {
+            (None, _) => {
+                return Err(TableManagerError::InvalidCall(format!(
+                    "partition field {} not found in table {}",
+                    partition.field(),
+                    table_name
+                )))
+            }
+            (Some(FieldInfo::String(_)), PartitionScheme::Range(_)) => {
+                return Err(TableManagerError::InvalidCall(format!(
+                    "range partition field {} must be an int field",
+                    partition.field()
+                )))
+            }
+            _ => {}
+        }
+        if partition.partition_count() > MAX_PARTITION_COUNT {
+            return Err(TableManagerError::InvalidCall(format!(
+                "table {} has too many partitions (max {})",
+                table_name, MAX_PARTITION_COUNT
+            )));
+        }
+        self.create_table_with_storage(table_name, schema, storage, tx)?;
+        if !self.table_exists(PARTCAT_TABLE_NAME, tx)? {
+            self.create_table(PARTCAT_TABLE_NAME, self.pcat_layout.schema().clone(), tx)?;
+        }
+
+        let mut pcat = self
+            .table_scan_factory
+            .create(tx, PARTCAT_TABLE_NAME, &self.pcat_layout)?;
+        let (scheme, lowers) = match partition.scheme() {
+            PartitionScheme::Hash(count) => (0, vec![0; *count]),
+            PartitionScheme::Range(bounds) => {
+                let mut lowers = vec![i32::MIN];
+                lowers.extend(bounds);
+                (1, lowers)
+            }
+        };
+        for (partno, lower) in lowers.into_iter().enumerate() {
+            pcat.insert()?;
+            pcat.set_string(PARTCAT_TBLNAME_FIELD, table_name)?;
+            pcat.set_string(PARTCAT_FLDNAME_FIELD, partition.field())?;
+            pcat.set_int(PARTCAT_SCHEME_FIELD, scheme)?;
+            pcat.set_int(PARTCAT_PARTNO_FIELD, partno as i32)?;
+            pcat.set_int(PARTCAT_LOWER_FIELD, lower)?;
+        }
+        if storage != StorageOptions::default() {
+            self.persist_storage_catalog(tx)?;
+        }
         Ok(())
     }
 
@@ -152,12 +401,192 @@ impl TableManager for TableManagerImpl {
         table_name: &str,
         tx: &Rc<RefCell<Transaction>>,
     ) -> Result<Layout, TableManagerError> {
-        let slot_size = self.get_record_size(table_name, tx)?;
+        if let Some(layout) = self.temp_tables.get(table_name) {
+            return Ok(layout.value().clone());
+        }
+        let (slot_size, storage) = self.get_record_size_and_storage(table_name, tx)?;
         let (schema, offsets) = self.get_schema_and_offsets(table_name, tx)?;
 
-        Ok(Layout::new_from_existing_settings(
-            schema, offsets, slot_size,
-        ))
+        let layout =
+            Layout::new_from_existing_settings(schema, offsets, slot_size).with_storage(storage);
+        Ok(match self.get_partition_spec(table_name, tx)? {
+            Some(partition) => layout.with_partition(partition),
+            None => layout,
+        })
+    }
+
+    fn create_temp_table(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError> {
+        Self::validate_names(table_name, &schema)?;
+        let physical_name = Self::temp_table_name(table_name, tx);
+        if self.temp_tables.contains_key(&physical_name) {
+            return Err(TableManagerError::InvalidCall(format!(
+                "temp table {} already exists",
+                table_name
+            )));
+        }
+        let layout = Layout::new(schema)?;
+        // temp table のファイルは他の transaction から見えず、transaction が終われば消えるので、変更を log に記録しない
+        tx.borrow()
+            .register_temp_file(&format!("{}.tbl", physical_name));
+        let temp_tables = self.temp_tables.clone();
+        let table_name = physical_name.clone();
+        tx.borrow().on_complete(Box::new(move |_| {
+            temp_tables.remove(&table_name);
+        }));
+        self.temp_tables.insert(physical_name, layout);
+        Ok(())
+    }
+
+    fn resolve_table_name(&self, table_name: &str, tx: &Rc<RefCell<Transaction>>) -> String {
+        let physical_name = Self::temp_table_name(table_name, tx);
+        if self.temp_tables.contains_key(&physical_name) {
+            physical_name
+        } else {
+            table_name.to_string()
+        }
+    }
+
+    fn rename_field(
+        &self,
+        table_name: &str,
+        old_field_name: &str,
+        new_field_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError> {
+        validate_identifier(IdentifierKind::Field, new_field_name)?;
+        let layout = self.get_layout(table_name, tx)?;
+        if !layout.schema().has_field(old_field_name) {
+            return Err(TableManagerError::InvalidCall(format!(
+                "field {} not found in table {}",
+                old_field_name, table_name
+            )));
+        }
+        if layout.schema().has_field(new_field_name) {
+            return Err(TableManagerError::InvalidCall(format!(
+                "field {} already exists in table {}",
+                new_field_name, table_name
+            )));
+        }
+
+        // temp table の layout はメモリ上にしかないので、それを差し替える
+        if let Some(mut temp_layout) = self.temp_tables.get_mut(table_name) {
+            *temp_layout = Self::rename_field_in_layout(&layout, old_field_name, new_field_name);
+            return Ok(());
+        }
+
+        let mut fcat = self
+            .table_scan_factory
+            .create(tx, FLDCAT_TABLE_NAME, &self.fcat_layout)?;
+        while fcat.move_next()? {
+            if fcat.get_string(FCAT_TBLNAME_FIELD)? == table_name
+                && fcat.get_string(FCAT_FLDNAME_FIELD)? == old_field_name
+            {
+                fcat.set_string(FCAT_FLDNAME_FIELD, new_field_name)?;
+            }
+        }
+
+        // partition を決める field の名前も変更する
+        if layout
+            .partition()
+            .is_some_and(|partition| partition.field() == old_field_name)
+        {
+            let mut pcat =
+                self.table_scan_factory
+                    .create(tx, PARTCAT_TABLE_NAME, &self.pcat_layout)?;
+            while pcat.move_next()? {
+                if pcat.get_string(PARTCAT_TBLNAME_FIELD)? == table_name {
+                    pcat.set_string(PARTCAT_FLDNAME_FIELD, new_field_name)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn rename_table(
+        &self,
+        table_name: &str,
+        new_table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError> {
+        validate_identifier(IdentifierKind::Table, new_table_name)?;
+        if self.temp_tables.contains_key(table_name) || CATALOG_TABLE_NAMES.contains(&table_name) {
+            return Err(TableManagerError::InvalidCall(format!(
+                "table {} cannot be renamed",
+                table_name
+            )));
+        }
+        let layout = self.get_layout(table_name, tx)?;
+        if self.table_exists(new_table_name, tx)? {
+            return Err(TableManagerError::AlreadyExists(new_table_name.to_string()));
+        }
+
+        // partition に分けた table の場合は、partition ごとのファイルの名前を変更する
+        let table_names = match layout.partition() {
+            Some(partition) => (0..partition.partition_count())
+                .map(|p| {
+                    (
+                        PartitionSpec::partition_table_name(table_name, p),
+                        PartitionSpec::partition_table_name(new_table_name, p),
+                    )
+                })
+                .collect(),
+            None => vec![(table_name.to_string(), new_table_name.to_string())],
+        };
+        for (old_name, new_name) in table_names {
+            let filename = format!("{}.tbl", old_name);
+            let new_filename = format!("{}.tbl", new_name);
+            for filename in [&filename, &new_filename] {
+                if let Some(block_size) = layout.block_size() {
+                    tx.borrow().set_block_size(filename, block_size)?;
+                }
+                if layout.storage().compressed {
+                    tx.borrow().set_compressed(filename)?;
+                }
+            }
+            tx.borrow().rename_file(&filename, &new_filename)?;
+        }
+
+        let mut tcat = self
+            .table_scan_factory
+            .create(tx, TBLCAT_TABLE_NAME, &self.tcat_layout)?;
+        while tcat.move_next()? {
+            if tcat.get_string(TBLCAT_TABLE_NAME)? == table_name {
+                tcat.set_string(TBLCAT_TABLE_NAME, new_table_name)?;
+                let rid = tcat.get_rid()?;
+                self.delete_catalog_entry(TBLCAT_INDEX_NAME, table_name, &rid, tx)?;
+                self.insert_catalog_entry(TBLCAT_INDEX_NAME, new_table_name, &rid, tx)?;
+            }
+        }
+        let mut fcat = self
+            .table_scan_factory
+            .create(tx, FLDCAT_TABLE_NAME, &self.fcat_layout)?;
+        while fcat.move_next()? {
+            if fcat.get_string(FCAT_TBLNAME_FIELD)? == table_name {
+                fcat.set_string(FCAT_TBLNAME_FIELD, new_table_name)?;
+                let rid = fcat.get_rid()?;
+                self.delete_catalog_entry(FLDCAT_INDEX_NAME, table_name, &rid, tx)?;
+                self.insert_catalog_entry(FLDCAT_INDEX_NAME, new_table_name, &rid, tx)?;
+            }
+        }
+        if layout.partition().is_some() {
+            let mut pcat =
+                self.table_scan_factory
+                    .create(tx, PARTCAT_TABLE_NAME, &self.pcat_layout)?;
+            while pcat.move_next()? {
+                if pcat.get_string(PARTCAT_TBLNAME_FIELD)? == table_name {
+                    pcat.set_string(PARTCAT_TBLNAME_FIELD, new_table_name)?;
+                }
+            }
+        }
+        if layout.storage() != StorageOptions::default() {
+            self.persist_storage_catalog(tx)?;
+        }
+        Ok(())
     }
 }
 
@@ -166,6 +595,8 @@ impl TableManagerImpl {
         let mut tcat_schema = Schema::new();
         tcat_schema.add_field(TBLCAT_TABLE_NAME, FieldInfo::String(MAX_TABLE_NAME_LENGTH));
         tcat_schema.add_field(TBLCAT_SLOTSIZE_FIELD, FieldInfo::Integer);
+        tcat_schema.add_field(TBLCAT_BLKSIZE_FIELD, FieldInfo::Integer);
+        tcat_schema.add_field(TBLCAT_COMPRESS_FIELD, FieldInfo::Integer);
         let tcat_layout = Layout::new(tcat_schema)?;
 
         let mut fcat_schema = Schema::new();
@@ -176,32 +607,270 @@ impl TableManagerImpl {
         fcat_schema.add_field(FCAT_OFFSET_FIELD, FieldInfo::Integer);
         let fcat_layout = Layout::new(fcat_schema)?;
 
+        let mut pcat_schema = Schema::new();
+        pcat_schema.add_field(
+            PARTCAT_TBLNAME_FIELD,
+            FieldInfo::String(MAX_TABLE_NAME_LENGTH),
+        );
+        pcat_schema.add_field(
+            PARTCAT_FLDNAME_FIELD,
+            FieldInfo::String(MAX_FIELD_NAME_LENGTH),
+        );
+        pcat_schema.add_field(PARTCAT_SCHEME_FIELD, FieldInfo::Integer);
+        pcat_schema.add_field(PARTCAT_PARTNO_FIELD, FieldInfo::Integer);
+        pcat_schema.add_field(PARTCAT_LOWER_FIELD, FieldInfo::Integer);
+        let pcat_layout = Layout::new(pcat_schema)?;
+
+        let sysidx_layout = index_layout(FieldInfo::BigInt, &[])?;
+
         Ok(Self {
             tcat_layout,
             fcat_layout,
+            pcat_layout,
+            sysidx_layout,
             table_scan_factory,
+            temp_tables: Arc::new(DashMap::new()),
         })
     }
 
-    fn get_record_size(
+    /// catalog に登録された全ての table のファイル名と、そのファイルの保存方法を返す
+    /// partition に分けた table は、partition ごとのファイルをそれぞれ返す
+    /// 起動時に、recovery などで table のファイルを読み書きする前に FileManager に登録するために使う
+    pub fn table_file_storages(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<Vec<(String, StorageOptions)>, TableManagerError> {
+        let mut tables = vec![];
+        {
+            let mut tcat =
+                self.table_scan_factory
+                    .create(tx, TBLCAT_TABLE_NAME, &self.tcat_layout)?;
+            while tcat.move_next()? {
+                tables.push((
+                    tcat.get_string(TBLCAT_TABLE_NAME)?,
+                    Self::read_storage(tcat.as_mut())?,
+                ));
+            }
+        }
+        let mut file_storages = vec![];
+        for (table_name, storage) in tables {
+            match self.get_partition_spec(&table_name, tx)? {
+                Some(partition) => {
+                    for p in 0..partition.partition_count() {
+                        let partition_name = PartitionSpec::partition_table_name(&table_name, p);
+                        file_storages.push((format!("{}.tbl", partition_name), storage));
+                    }
+                }
+                None => file_storages.push((format!("{}.tbl", table_name), storage)),
+            }
+        }
+        Ok(file_storages)
+    }
+
+    /// block size と圧縮の設定を持つ前の形式の tblcat を、現在の形式に書き換える
+    /// 以前の形式の tblcat は table 名と slot size だけを持つので、全ての table を共通の block size で圧縮せずに保存していたものとする
+    /// 書き換えは log に記録するので、commit する前に失敗した場合は recovery で元の形式に戻る
+    pub fn upgrade_legacy_catalog(
         &self,
-        table_name: &str,
         tx: &Rc<RefCell<Transaction>>,
-    ) -> Result<usize, TableManagerError> {
+    ) -> Result<(), TableManagerError> {
+        let mut legacy_schema = Schema::new();
+        legacy_schema.add_field(TBLCAT_TABLE_NAME, FieldInfo::String(MAX_TABLE_NAME_LENGTH));
+        legacy_schema.add_field(TBLCAT_SLOTSIZE_FIELD, FieldInfo::Integer);
+        let legacy_layout = Layout::new(legacy_schema)?;
+        let mut tables = vec![];
+        {
+            let mut tcat = self
+                .table_scan_factory
+                .create(tx, TBLCAT_TABLE_NAME, &legacy_layout)?;
+            while tcat.move_next()? {
+                tables.push((
+                    tcat.get_string(TBLCAT_TABLE_NAME)?,
+                    tcat.get_int(TBLCAT_SLOTSIZE_FIELD)?,
+                ));
+            }
+        }
+        // slot の大きさが変わるので、全ての block を現在の形式の空の slot にしてから登録し直す
+        let filename = format!("{}.tbl", TBLCAT_TABLE_NAME);
+        let block_count = tx.borrow().size(&filename)?;
+        for blknum in 0..block_count {
+            RecordPage::new(
+                tx.clone(),
+                &BlockId::new(&filename, blknum),
+                &self.tcat_layout,
+            )
+            .format()?;
+        }
         let mut tcat = self
             .table_scan_factory
             .create(tx, TBLCAT_TABLE_NAME, &self.tcat_layout)?;
-        while tcat.move_next()? {
-            if tcat.get_string(TBLCAT_TABLE_NAME)? == table_name {
-                return Ok(tcat.get_int(TBLCAT_SLOTSIZE_FIELD)? as usize);
+        for (table_name, slot_size) in tables {
+            tcat.insert()?;
+            tcat.set_string(TBLCAT_TABLE_NAME, &table_name)?;
+            tcat.set_int(TBLCAT_SLOTSIZE_FIELD, slot_size)?;
+            tcat.set_int(TBLCAT_BLKSIZE_FIELD, 0)?;
+            tcat.set_int(TBLCAT_COMPRESS_FIELD, 0)?;
+        }
+        Ok(())
+    }
+
+    /// table ごとの保存方法を読むための catalog (tblcat と partcat) の変更を、commit を待たずに disk に書き込む
+    /// 起動時には recovery より前に disk 上の catalog から保存方法を読んで FileManager に登録する (table_file_storages を参照)
+    /// commit しただけでは変更は log にしか無いので、書き込んでおかないと recovery で table のファイルを正しく読み書きできない
+    /// commit されなかった変更が disk に残っても、その table のファイルを undo するときに必要な設定なので問題ない
+    fn persist_storage_catalog(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError> {
+        for table_name in [TBLCAT_TABLE_NAME, PARTCAT_TABLE_NAME] {
+            tx.borrow().flush_file(&format!("{}.tbl", table_name))?;
+        }
+        Ok(())
+    }
+
+    /// tblcat の scan が指している record から、table の保存方法を読む
+    fn read_storage(tcat: &mut dyn UpdateScan) -> Result<StorageOptions, TableManagerError> {
+        let block_size = match tcat.get_int(TBLCAT_BLKSIZE_FIELD)? {
+            0 => None,
+            block_size => Some(block_size as usize),
+        };
+        Ok(StorageOptions {
+            block_size,
+            compressed: tcat.get_int(TBLCAT_COMPRESS_FIELD)? != 0,
+        })
+    }
+
+    /// table 名と field 名が catalog に保存できるかを検証する
+    fn validate_names(table_name: &str, schema: &Schema) -> Result<(), IdentifierError> {
+        validate_identifier(IdentifierKind::Table, table_name)?;
+        for field in schema.fields_iter() {
+            validate_identifier(IdentifierKind::Field, field)?;
+        }
+        Ok(())
+    }
+
+    /// field 名を変更した layout を作る。field の順番や offset は変えない
+    fn rename_field_in_layout(
+        layout: &Layout,
+        old_field_name: &str,
+        new_field_name: &str,
+    ) -> Layout {
+        let rename = |field: &str| {
+            if field == old_field_name {
+                new_field_name.to_string()
+            } else {
+                field.to_string()
+            }
+        };
+        let mut schema = Schema::new();
+        let mut offsets = HashMap::new();
+        for (field, info) in layout.schema().infos() {
+            schema.add_field(&rename(field), info);
+            if let Some(offset) = layout.offset(field) {
+                offsets.insert(rename(field), offset);
             }
         }
+        Layout::new_from_existing_settings(schema, offsets, layout.slot_size())
+            .with_storage(layout.storage())
+    }
+
+    /// temp table の実体の名前を返す
+    /// transaction ごとに名前空間を分けるため、transaction 番号を名前に含める
+    fn temp_table_name(table_name: &str, tx: &Rc<RefCell<Transaction>>) -> String {
+        format!(
+            "{}{}_{}",
+            TEMP_TABLE_PREFIX,
+            tx.borrow().tx_num(),
+            table_name
+        )
+    }
+
+    /// table の slot size と保存方法を返す
+    fn get_record_size_and_storage(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(usize, StorageOptions), TableManagerError> {
+        let mut tcat = self
+            .table_scan_factory
+            .create(tx, TBLCAT_TABLE_NAME, &self.tcat_layout)?;
+        let found = match self.find_catalog_rids(
+            TBLCAT_INDEX_NAME,
+            tcat.as_mut(),
+            TBLCAT_TABLE_NAME,
+            table_name,
+            tx,
+        )? {
+            Some(rids) => match rids.first() {
+                Some(rid) => {
+                    tcat.move_to_rid(rid)?;
+                    true
+                }
+                None => false,
+            },
+            None => loop {
+                if !tcat.move_next()? {
+                    break false;
+                }
+                if tcat.get_string(TBLCAT_TABLE_NAME)? == table_name {
+                    break true;
+                }
+            },
+        };
+        if found {
+            let slot_size = tcat.get_int(TBLCAT_SLOTSIZE_FIELD)? as usize;
+            return Ok((slot_size, Self::read_storage(tcat.as_mut())?));
+        }
         Err(TableManagerError::InvalidCall(format!(
             "table {} not found",
             table_name
         )))
     }
 
+    /// partcat から table の partition の分け方を読む。partition に分けていない table の場合は None を返す
+    fn get_partition_spec(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<Option<PartitionSpec>, TableManagerError> {
+        // catalog 自身は partition に分けない。partcat を読む前に partcat の layout を取得しようとして再帰しないように、先に除く
+        if CATALOG_TABLE_NAMES.contains(&table_name)
+            || !self.table_exists(PARTCAT_TABLE_NAME, tx)?
+        {
+            return Ok(None);
+        }
+        let mut pcat = self
+            .table_scan_factory
+            .create(tx, PARTCAT_TABLE_NAME, &self.pcat_layout)?;
+        let mut field = None;
+        let mut is_range = false;
+        // partition の番号 -> 下限
+        let mut lowers = vec![];
+        while pcat.move_next()? {
+            if pcat.get_string(PARTCAT_TBLNAME_FIELD)? != table_name {
+                continue;
+            }
+            field = Some(pcat.get_string(PARTCAT_FLDNAME_FIELD)?);
+            is_range = pcat.get_int(PARTCAT_SCHEME_FIELD)? == 1;
+            lowers.push((
+                pcat.get_int(PARTCAT_PARTNO_FIELD)?,
+                pcat.get_int(PARTCAT_LOWER_FIELD)?,
+            ));
+        }
+        let Some(field) = field else {
+            return Ok(None);
+        };
+        let scheme = if is_range {
+            lowers.sort();
+            PartitionScheme::Range(lowers.into_iter().skip(1).map(|(_, lower)| lower).collect())
+        } else {
+            PartitionScheme::Hash(lowers.len())
+        };
+        let partition = PartitionSpec::new(&field, scheme)
+            .map_err(|e| TableManagerError::Internal(e.to_string()))?;
+        Ok(Some(partition))
+    }
+
     fn get_schema_and_offsets(
         &self,
         table_name: &str,
@@ -212,30 +881,200 @@ impl TableManagerImpl {
         let mut fcat = self
             .table_scan_factory
             .create(tx, FLDCAT_TABLE_NAME, &self.fcat_layout)?;
-        while fcat.move_next()? {
-            if fcat.get_string(FCAT_TBLNAME_FIELD)? == table_name {
-                let field_name = fcat.get_string(FCAT_FLDNAME_FIELD)?;
-                let field_type_i32 = fcat.get_int(FCAT_TYPE_FIELD)?;
-                let field_type = FieldType::from_i32(field_type_i32).map_err(|e| {
-                    TableManagerError::Internal(format!(
-                        "unexpected field type value: {}. error: {}",
-                        field_type_i32, e
-                    ))
-                })?;
-                let field_length = fcat.get_int(FCAT_LENGTH_FIELD)? as usize;
-                let field_offset = fcat.get_int(FCAT_OFFSET_FIELD)? as usize;
-                schema.add_field(
-                    &field_name,
-                    match field_type {
-                        FieldType::Integer => FieldInfo::Integer,
-                        FieldType::String => FieldInfo::String(field_length),
-                    },
-                );
-                offsets.insert(field_name, field_offset);
+        match self.find_catalog_rids(
+            FLDCAT_INDEX_NAME,
+            fcat.as_mut(),
+            FCAT_TBLNAME_FIELD,
+            table_name,
+            tx,
+        )? {
+            Some(rids) => {
+                for rid in rids {
+                    fcat.move_to_rid(&rid)?;
+                    Self::read_field(fcat.as_mut(), &mut schema, &mut offsets)?;
+                }
+            }
+            None => {
+                while fcat.move_next()? {
+                    if fcat.get_string(FCAT_TBLNAME_FIELD)? == table_name {
+                        Self::read_field(fcat.as_mut(), &mut schema, &mut offsets)?;
+                    }
+                }
             }
         }
         Ok((schema, offsets))
     }
+
+    /// fldcat の scan が指している record から field の情報を読み、schema と offsets に加える
+    fn read_field(
+        fcat: &mut dyn UpdateScan,
+        schema: &mut Schema,
+        offsets: &mut HashMap<String, usize>,
+    ) -> Result<(), TableManagerError> {
+        let field_name = fcat.get_string(FCAT_FLDNAME_FIELD)?;
+        let field_type_i32 = fcat.get_int(FCAT_TYPE_FIELD)?;
+        let field_type = FieldType::from_i32(field_type_i32).map_err(|e| {
+            TableManagerError::Internal(format!(
+                "unexpected field type value: {}. error: {}",
+                field_type_i32, e
+            ))
+        })?;
+        let field_length = fcat.get_int(FCAT_LENGTH_FIELD)? as usize;
+        let field_offset = fcat.get_int(FCAT_OFFSET_FIELD)? as usize;
+        schema.add_field(
+            &field_name,
+            match field_type {
+                FieldType::Integer => FieldInfo::Integer,
+                FieldType::String => FieldInfo::String(field_length),
+                FieldType::Boolean => FieldInfo::Boolean,
+                FieldType::BigInt => FieldInfo::BigInt,
+                FieldType::Date => FieldInfo::Date,
+                FieldType::Double => FieldInfo::Double,
+            },
+        );
+        offsets.insert(field_name, field_offset);
+        Ok(())
+    }
+
+    /// system index が全て作成済みかどうか
+    pub fn has_system_indexes(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<bool, TableManagerError> {
+        for index_name in SYSTEM_INDEX_NAMES {
+            if !self.has_system_index(index_name, tx)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// まだ作成されていない system index を、tblcat と fldcat の record から作成する
+    /// system index を持たない database で呼ぶ場合は、先に recovery を済ませておく
+    /// (recovery で取り消される record を index に登録してしまわないようにするため)
+    pub fn setup_system_indexes(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError> {
+        let system_indexes = [
+            (
+                TBLCAT_INDEX_NAME,
+                TBLCAT_TABLE_NAME,
+                &self.tcat_layout,
+                TBLCAT_TABLE_NAME,
+            ),
+            (
+                FLDCAT_INDEX_NAME,
+                FLDCAT_TABLE_NAME,
+                &self.fcat_layout,
+                FCAT_TBLNAME_FIELD,
+            ),
+        ];
+        for (index_name, catalog_name, layout, key_field) in system_indexes {
+            if self.has_system_index(index_name, tx)? {
+                continue;
+            }
+            let mut entries = vec![];
+            {
+                let mut ts = self.table_scan_factory.create(tx, catalog_name, layout)?;
+                while ts.move_next()? {
+                    entries.push(IndexEntry::new(
+                        Self::system_index_key(&ts.get_string(key_field)?),
+                        ts.get_rid()?,
+                    ));
+                }
+            }
+            entries.sort_by(|lhs, rhs| lhs.data_val().cmp(rhs.data_val()));
+            BTreeIndex::new(tx.clone(), index_name, &self.sysidx_layout)?.bulk_load(&entries)?;
+        }
+        Ok(())
+    }
+
+    // index_name の system index が作成済みかどうか
+    fn has_system_index(
+        &self,
+        index_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<bool, TableManagerError> {
+        Ok(tx.borrow().size(&format!("{}.leaf", index_name))? > 0)
+    }
+
+    // system index を開く。まだ作成されていない場合は None を返す
+    fn open_system_index(
+        &self,
+        index_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<Option<BTreeIndex>, TableManagerError> {
+        if !self.has_system_index(index_name, tx)? {
+            return Ok(None);
+        }
+        Ok(Some(BTreeIndex::new(
+            tx.clone(),
+            index_name,
+            &self.sysidx_layout,
+        )?))
+    }
+
+    // system index の key。catalog のファイルに残るので、実行環境によらず同じ値になる hash を使う
+    fn system_index_key(table_name: &str) -> Constant {
+        Constant::BigInt(stable_hash(&Constant::from(table_name)) as i64)
+    }
+
+    // system index を使って、catalog (scan で読む) のうち key_field が table_name の record の Rid をファイル上の順に返す
+    // hash 値が同じ別の table の record を除くために、scan を各 record に移動して table 名を確かめる
+    // 同じ table 名の record が index に並ぶ順番は決まっていないので、fldcat の field の順番を保つために並べ直す
+    // system index がまだ作成されていない場合は None を返す
+    fn find_catalog_rids(
+        &self,
+        index_name: &str,
+        scan: &mut dyn UpdateScan,
+        key_field: &str,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<Option<Vec<Rid>>, TableManagerError> {
+        let Some(mut index) = self.open_system_index(index_name, tx)? else {
+            return Ok(None);
+        };
+        index.before_first(&Self::system_index_key(table_name))?;
+        let mut rids = vec![];
+        while index.next()? {
+            let rid = index.get_data_rid()?;
+            scan.move_to_rid(&rid)?;
+            if scan.get_string(key_field)? == table_name {
+                rids.push(rid);
+            }
+        }
+        rids.sort_by_key(|rid| (rid.block_number(), rid.slot()));
+        Ok(Some(rids))
+    }
+
+    // catalog に追加した record を system index に登録する。system index がまだ無い場合は何もしない
+    fn insert_catalog_entry(
+        &self,
+        index_name: &str,
+        table_name: &str,
+        rid: &Rid,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError> {
+        if let Some(mut index) = self.open_system_index(index_name, tx)? {
+            index.insert_entry(&IndexEntry::new(Self::system_index_key(table_name), *rid))?;
+        }
+        Ok(())
+    }
+
+    // catalog の record の登録を system index から削除する。system index がまだ無い場合は何もしない
+    fn delete_catalog_entry(
+        &self,
+        index_name: &str,
+        table_name: &str,
+        rid: &Rid,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), TableManagerError> {
+        if let Some(mut index) = self.open_system_index(index_name, tx)? {
+            index.delete(&Self::system_index_key(table_name), rid)?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -305,12 +1144,397 @@ mod table_manager_test {
         table_manager.setup_if_not_exists(&tx).unwrap();
 
         let layout = setup_layout();
+        assert!(!table_manager.table_exists("test_table", &tx).unwrap());
         table_manager
             .create_table("test_table", layout.schema().clone(), &tx)
             .unwrap();
+        assert!(table_manager.table_exists("test_table", &tx).unwrap());
+        assert_eq!(
+            table_manager.table_names(&tx).unwrap(),
+            vec!["test_table".to_string()]
+        );
         let layout_from_manager = table_manager.get_layout("test_table", &tx).unwrap();
         assert_eq!(layout, layout_from_manager);
 
+        // 同じ名前の table は作成できない
+        assert!(matches!(
+            table_manager.create_table("test_table", layout.schema().clone(), &tx),
+            Err(TableManagerError::AlreadyExists(_))
+        ));
+        // catalog に保存できない名前の table も作成できない
+        assert!(matches!(
+            table_manager.create_table(&"t".repeat(33), layout.schema().clone(), &tx),
+            Err(TableManagerError::Identifier(_))
+        ));
+        assert!(matches!(
+            table_manager.create_table("select", layout.schema().clone(), &tx),
+            Err(TableManagerError::Identifier(_))
+        ));
+
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_create_table_with_block_size() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let table_scan_factory = Arc::new(TableScanFactoryImpl::new());
+
+        let table_manager = TableManagerImpl::new(table_scan_factory.clone()).unwrap();
+        table_manager.setup_if_not_exists(&tx).unwrap();
+
+        // slot size が 412 bytes なので、共通の block size (400) には収まらない
+        let mut schema = Schema::new();
+        schema.add_field("A", FieldInfo::Integer);
+        schema.add_field("B", FieldInfo::String(100));
+        assert!(table_manager
+            .create_table_with_storage(
+                "wide_table",
+                schema.clone(),
+                StorageOptions {
+                    block_size: Some(400),
+                    compressed: false,
+                },
+                &tx,
+            )
+            .is_err());
+        table_manager
+            .create_table_with_storage(
+                "wide_table",
+                schema.clone(),
+                StorageOptions {
+                    block_size: Some(1000),
+                    compressed: false,
+                },
+                &tx,
+            )
+            .unwrap();
+        let layout = table_manager.get_layout("wide_table", &tx).unwrap();
+        assert_eq!(layout.block_size(), Some(1000));
+        assert_eq!(layout.schema(), &schema);
+
+        // 1 block に 2 record ずつ入る
+        {
+            let mut scan = table_scan_factory
+                .create(&tx, "wide_table", &layout)
+                .unwrap();
+            for i in 0..5 {
+                scan.insert().unwrap();
+                scan.set_int("A", i).unwrap();
+                scan.set_string("B", &"x".repeat(100)).unwrap();
+            }
+        }
+        assert_eq!(tx.borrow().size("wide_table.tbl").unwrap(), 3);
+        {
+            let mut scan = table_scan_factory
+                .create_read_only(&tx, "wide_table", &layout)
+                .unwrap();
+            let mut values = vec![];
+            while scan.move_next().unwrap() {
+                values.push(scan.get_int("A").unwrap());
+                assert_eq!(scan.get_string("B").unwrap(), "x".repeat(100));
+            }
+            assert_eq!(values, vec![0, 1, 2, 3, 4]);
+        }
+        // block size を指定しない table は共通の block size を使う
+        assert_eq!(
+            table_manager
+                .get_layout(TBLCAT_TABLE_NAME, &tx)
+                .unwrap()
+                .block_size(),
+            None
+        );
+        // 起動時に FileManager に登録するために、全ての table のファイルの保存方法を読める
+        let file_storages = table_manager.table_file_storages(&tx).unwrap();
+        assert!(file_storages.contains(&(
+            "wide_table.tbl".to_string(),
+            StorageOptions {
+                block_size: Some(1000),
+                compressed: false,
+            }
+        )));
+        assert!(file_storages.contains(&("fldcat.tbl".to_string(), StorageOptions::default())));
+
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_upgrade_legacy_catalog() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let table_scan_factory = Arc::new(TableScanFactoryImpl::new());
+
+        let table_manager = TableManagerImpl::new(table_scan_factory.clone()).unwrap();
+        table_manager.setup_if_not_exists(&tx).unwrap();
+        let layout = setup_layout();
+        table_manager
+            .create_table("test_table", layout.schema().clone(), &tx)
+            .unwrap();
+
+        // tblcat を、block size と圧縮の設定を持たない以前の形式で書き直す
+        let tables: Vec<(&str, usize)> = [TBLCAT_TABLE_NAME, FLDCAT_TABLE_NAME, "test_table"]
+            .into_iter()
+            .map(|name| {
+                let slot_size = table_manager.get_layout(name, &tx).unwrap().slot_size();
+                (name, slot_size)
+            })
+            .collect();
+        let mut legacy_schema = Schema::new();
+        legacy_schema.add_field(TBLCAT_TABLE_NAME, FieldInfo::String(MAX_TABLE_NAME_LENGTH));
+        legacy_schema.add_field(TBLCAT_SLOTSIZE_FIELD, FieldInfo::Integer);
+        let legacy_layout = Layout::new(legacy_schema).unwrap();
+        let filename = format!("{}.tbl", TBLCAT_TABLE_NAME);
+        for blknum in 0..tx.borrow().size(&filename).unwrap() {
+            RecordPage::new(tx.clone(), &BlockId::new(&filename, blknum), &legacy_layout)
+                .format()
+                .unwrap();
+        }
+        {
+            let mut tcat = table_scan_factory
+                .create(&tx, TBLCAT_TABLE_NAME, &legacy_layout)
+                .unwrap();
+            for (table_name, slot_size) in &tables {
+                tcat.insert().unwrap();
+                tcat.set_string(TBLCAT_TABLE_NAME, table_name).unwrap();
+                tcat.set_int(TBLCAT_SLOTSIZE_FIELD, *slot_size as i32)
+                    .unwrap();
+            }
+        }
+
+        table_manager.upgrade_legacy_catalog(&tx).unwrap();
+        assert_eq!(table_manager.get_layout("test_table", &tx).unwrap(), layout);
+        assert_eq!(
+            table_manager.table_names(&tx).unwrap(),
+            vec!["test_table".to_string()]
+        );
+        // 以前の形式の table は、全て共通の block size で圧縮せずに保存されている
+        assert!(table_manager
+            .table_file_storages(&tx)
+            .unwrap()
+            .iter()
+            .all(|(_, storage)| *storage == StorageOptions::default()));
+
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn test_create_compressed_table() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let table_scan_factory = Arc::new(TableScanFactoryImpl::new());
+
+        let table_manager = TableManagerImpl::new(table_scan_factory.clone()).unwrap();
+        table_manager.setup_if_not_exists(&tx).unwrap();
+
+        let layout = setup_layout();
+        let storage = StorageOptions {
+            block_size: None,
+            compressed: true,
+        };
+        table_manager
+            .create_table_with_storage("compressed_table", layout.schema().clone(), storage, &tx)
+            .unwrap();
+        let layout = table_manager.get_layout("compressed_table", &tx).unwrap();
+        assert_eq!(layout.storage(), storage);
+
+        {
+            let mut scan = table_scan_factory
+                .create(&tx, "compressed_table", &layout)
+                .unwrap();
+            for i in 0..20 {
+                scan.insert().unwrap();
+                scan.set_int("A", i).unwrap();
+                scan.set_string("B", "aaaaaaaaa").unwrap();
+            }
+        }
+        // buffer の内容をファイルに書き出してから読み直す
+        tx.borrow_mut().commit().unwrap();
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let mut scan = table_scan_factory
+            .create_read_only(&tx, "compressed_table", &layout)
+            .unwrap();
+        let mut count = 0;
+        while scan.move_next().unwrap() {
+            assert_eq!(scan.get_int("A").unwrap(), count);
+            assert_eq!(scan.get_string("B").unwrap(), "aaaaaaaaa");
+            count += 1;
+        }
+        assert_eq!(count, 20);
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    #[cfg(not(feature = "compression"))]
+    fn test_create_compressed_table_without_feature() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let table_manager = TableManagerImpl::new(Arc::new(TableScanFactoryImpl::new())).unwrap();
+        table_manager.setup_if_not_exists(&tx).unwrap();
+
+        let storage = StorageOptions {
+            block_size: None,
+            compressed: true,
+        };
+        assert!(table_manager
+            .create_table_with_storage(
+                "compressed_table",
+                setup_layout().schema().clone(),
+                storage,
+                &tx
+            )
+            .is_err());
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_create_temp_table() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let other_tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let table_scan_factory = Arc::new(TableScanFactoryImpl::new());
+
+        let table_manager = TableManagerImpl::new(table_scan_factory).unwrap();
+        table_manager.setup_if_not_exists(&tx).unwrap();
+
+        let layout = setup_layout();
+        table_manager
+            .create_temp_table("test_table", layout.schema().clone(), &tx)
+            .unwrap();
+        // 同じ transaction からは temp から始まる実体の名前で見える
+        let physical_name = table_manager.resolve_table_name("test_table", &tx);
+        assert!(physical_name.starts_with(TEMP_TABLE_PREFIX));
+        assert_eq!(
+            table_manager.get_layout(&physical_name, &tx).unwrap(),
+            layout
+        );
+        // 同名の temp table は作れない
+        assert!(table_manager
+            .create_temp_table("test_table", layout.schema().clone(), &tx)
+            .is_err());
+        // 他の transaction からは見えない
+        assert_eq!(
+            table_manager.resolve_table_name("test_table", &other_tx),
+            "test_table"
+        );
+        // tblcat には登録されない
+        assert!(table_manager.get_layout("test_table", &tx).is_err());
+
+        tx.borrow_mut().commit().unwrap();
+        other_tx.borrow_mut().commit().unwrap();
+        // transaction が終わると temp table は取り除かれる
+        assert!(table_manager.temp_tables.is_empty());
+    }
+
+    #[test]
+    fn test_rename_field() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let table_scan_factory = Arc::new(TableScanFactoryImpl::new());
+
+        let table_manager = TableManagerImpl::new(table_scan_factory).unwrap();
+        table_manager.setup_if_not_exists(&tx).unwrap();
+
+        let layout = setup_layout();
+        table_manager
+            .create_table("test_table", layout.schema().clone(), &tx)
+            .unwrap();
+        table_manager
+            .rename_field("test_table", "A", "C", &tx)
+            .unwrap();
+        let renamed = table_manager.get_layout("test_table", &tx).unwrap();
+        assert!(!renamed.schema().has_field("A"));
+        assert_eq!(renamed.schema().info("C"), Some(FieldInfo::Integer));
+        // record 中の位置は変わらない
+        assert_eq!(renamed.offset("C"), layout.offset("A"));
+        assert_eq!(renamed.slot_size(), layout.slot_size());
+
+        // 存在しない field や、すでに存在する field 名への変更はできない
+        assert!(table_manager
+            .rename_field("test_table", "A", "D", &tx)
+            .is_err());
+        assert!(table_manager
+            .rename_field("test_table", "C", "B", &tx)
+            .is_err());
+
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_system_indexes() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let table_manager = TableManagerImpl::new(Arc::new(TableScanFactoryImpl::new())).unwrap();
+        let schema = |i: usize| {
+            let mut schema = Schema::new();
+            schema.add_field(&format!("a{}", i), FieldInfo::Integer);
+            schema.add_field("b", FieldInfo::String(10));
+            schema.add_field(&format!("c{}", i), FieldInfo::Boolean);
+            schema
+        };
+
+        // system index を作成する前は、catalog を先頭から読む
+        for i in 0..10 {
+            table_manager
+                .create_table(&format!("t{}", i), schema(i), &tx)
+                .unwrap();
+        }
+        assert!(!table_manager.has_system_indexes(&tx).unwrap());
+        assert_eq!(
+            table_manager
+                .get_layout("t3", &tx)
+                .unwrap()
+                .schema()
+                .fields(),
+            vec!["a3", "b", "c3"]
+        );
+
+        // setup で、すでにある catalog の record から system index を作成する
+        table_manager.setup_if_not_exists(&tx).unwrap();
+        assert!(table_manager.has_system_indexes(&tx).unwrap());
+        for i in 10..40 {
+            table_manager
+                .create_table(&format!("t{}", i), schema(i), &tx)
+                .unwrap();
+        }
+        assert!(tx.borrow().size("fldcatidx.leaf").unwrap() > 1);
+        for i in 0..40 {
+            let table_name = format!("t{}", i);
+            assert!(table_manager.table_exists(&table_name, &tx).unwrap());
+            // field の順番は作成した時の順番のまま
+            assert_eq!(
+                table_manager
+                    .get_layout(&table_name, &tx)
+                    .unwrap()
+                    .schema()
+                    .fields(),
+                vec![format!("a{}", i), "b".to_string(), format!("c{}", i)]
+            );
+        }
+        assert!(!table_manager.table_exists("t40", &tx).unwrap());
+        assert!(table_manager.get_layout("t40", &tx).is_err());
+
+        // table の名前を変更すると、system index の entry も変わる
+        table_manager.rename_table("t5", "renamed", &tx).unwrap();
+        assert!(!table_manager.table_exists("t5", &tx).unwrap());
+        assert_eq!(
+            table_manager
+                .get_layout("renamed", &tx)
+                .unwrap()
+                .schema()
+                .fields(),
+            vec!["a5", "b", "c5"]
+        );
+        assert_eq!(table_manager.table_names(&tx).unwrap().len(), 40);
+
         tx.borrow_mut().commit().unwrap();
     }
 }
@@ -1,9 +1,9 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
 use thiserror::Error;
 
 use crate::{
-    query::scan::{ReadScanError, UpdateScanError},
+    query::scan::{ReadScanError, UpdateScan, UpdateScanError},
     record::{
         schema::{FieldInfo, Schema},
         table_scan_factory::{TableScanFactory, TableScanFactoryError},
@@ -13,9 +13,10 @@ use crate::{
 
 use super::{
     constants::{
-        MAX_VIEWDEF_LENGTH, MAX_VIEW_NAME_LENGTH, VIEWCAT_TABLE_NAME, VIEWCAT_VIEW_DEF_FIELD,
-        VIEWCAT_VIEW_NAME_FIELD,
+        MAX_VIEW_NAME_LENGTH, VIEWCAT_SEQ_FIELD, VIEWCAT_TABLE_NAME, VIEWCAT_VIEW_DEF_FIELD,
+        VIEWCAT_VIEW_NAME_FIELD, VIEWDEF_CHUNK_LENGTH,
     },
+    identifier::{validate_identifier, IdentifierError, IdentifierKind},
     table_manager::{TableManager, TableManagerError},
 };
 
@@ -32,12 +33,26 @@ pub trait ViewManager {
         view_name: &str,
         tx: &Rc<RefCell<Transaction>>,
     ) -> Result<String, ViewManagerError>;
+    /// 全ての view の (view 名, 定義) を取得する
+    fn get_view_defs(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<Vec<(String, String)>, ViewManagerError>;
+    /// 既存の view の定義を置き換える
+    fn update_view_def(
+        &self,
+        view_name: &str,
+        view_def: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), ViewManagerError>;
 }
 
 /**
  * View の作成及び View の定義情報の取得を行うためのクラス
  *
  * 内部的には viewcat という table に View の定義情報を保存している
+ * 定義は VIEWDEF_CHUNK_LENGTH 文字ずつの断片に分け、断片ごとに (view 名, 断片の番号, 断片) の 1 record として保存するので、
+ * 定義の長さは viewcat の 1 slot の大きさに制限されない
  */
 pub struct ViewManagerImpl<'a> {
     table_manager: &'a dyn TableManager,
@@ -68,6 +83,8 @@ pub(crate) enum ViewManagerError {
     TableScanFactory(#[from] TableScanFactoryError),
     #[error("invalid call error: {0}")]
     InvalidCall(String),
+    #[error("identifier error: {0}")]
+    Identifier(#[from] IdentifierError),
     // TODO: 治す
     #[error("anyhow error: {0}")]
     Anyhow(#[from] anyhow::Error),
@@ -83,20 +100,64 @@ impl<'a> ViewManagerImpl<'a> {
             table_scan_factory,
         }
     }
+
+    // viewcat がすでに作成されているかどうか
+    fn is_set_up(&self, tx: &Rc<RefCell<Transaction>>) -> bool {
+        self.table_manager
+            .get_layout(VIEWCAT_TABLE_NAME, tx)
+            .is_ok()
+    }
+
+    // view の定義を断片に分けて viewcat に insert する
+    fn insert_view_def(
+        ts: &mut Box<dyn UpdateScan>,
+        view_name: &str,
+        view_def: &str,
+    ) -> Result<(), ViewManagerError> {
+        for (seq, chunk) in Self::split_view_def(view_def).iter().enumerate() {
+            ts.insert()?;
+            ts.set_string(VIEWCAT_VIEW_NAME_FIELD, view_name)?;
+            ts.set_int(VIEWCAT_SEQ_FIELD, seq as i32)?;
+            ts.set_string(VIEWCAT_VIEW_DEF_FIELD, chunk)?;
+        }
+        Ok(())
+    }
+
+    // view の定義を VIEWDEF_CHUNK_LENGTH 文字ずつの断片に分ける。空の定義も 1 つの断片として保存する
+    fn split_view_def(view_def: &str) -> Vec<String> {
+        let chars = view_def.chars().collect::<Vec<_>>();
+        if chars.is_empty() {
+            return vec![String::new()];
+        }
+        chars
+            .chunks(VIEWDEF_CHUNK_LENGTH)
+            .map(|chunk| chunk.iter().collect())
+            .collect()
+    }
+
+    // 断片を番号順に並べて、元の定義に戻す
+    fn join_view_def(mut chunks: Vec<(i32, String)>) -> String {
+        chunks.sort_by_key(|(seq, _)| *seq);
+        chunks.into_iter().map(|(_, chunk)| chunk).collect()
+    }
 }
 
 impl<'a> ViewManager for ViewManagerImpl<'a> {
     // view manager が view を管理するために必要なファイルがまだ作成されていない場合、作成する
     // このメソッドは何回呼んでも問題ない
     fn setup_if_not_exists(&self, tx: &Rc<RefCell<Transaction>>) -> Result<(), ViewManagerError> {
+        if self.is_set_up(tx) {
+            return Ok(());
+        }
         let mut schema = Schema::new();
         schema.add_field(
             VIEWCAT_VIEW_NAME_FIELD,
             FieldInfo::String(MAX_VIEW_NAME_LENGTH),
         );
+        schema.add_field(VIEWCAT_SEQ_FIELD, FieldInfo::Integer);
         schema.add_field(
             VIEWCAT_VIEW_DEF_FIELD,
-            FieldInfo::String(MAX_VIEWDEF_LENGTH),
+            FieldInfo::String(VIEWDEF_CHUNK_LENGTH),
         );
         self.table_manager
             .create_table(VIEWCAT_TABLE_NAME, schema, tx)?;
@@ -111,14 +172,12 @@ impl<'a> ViewManager for ViewManagerImpl<'a> {
         view_def: &str,
         tx: &Rc<RefCell<Transaction>>,
     ) -> Result<(), ViewManagerError> {
+        validate_identifier(IdentifierKind::View, view_name)?;
         let layout = self.table_manager.get_layout(VIEWCAT_TABLE_NAME, tx)?;
         let mut ts = self
             .table_scan_factory
             .create(tx, VIEWCAT_TABLE_NAME, &layout)?;
-        ts.insert()?;
-        ts.set_string(VIEWCAT_VIEW_NAME_FIELD, view_name)?;
-        ts.set_string(VIEWCAT_VIEW_DEF_FIELD, view_def)?;
-        Ok(())
+        Self::insert_view_def(&mut ts, view_name, view_def)
     }
 
     // view の定義情報を取得する
@@ -131,22 +190,97 @@ impl<'a> ViewManager for ViewManagerImpl<'a> {
         let mut ts = self
             .table_scan_factory
             .create(tx, VIEWCAT_TABLE_NAME, &layout)?;
+        let mut chunks = vec![];
         while ts.move_next()? {
             if ts.get_string(VIEWCAT_VIEW_NAME_FIELD)? == view_name {
-                return Ok(ts.get_string(VIEWCAT_VIEW_DEF_FIELD)?);
+                chunks.push((
+                    ts.get_int(VIEWCAT_SEQ_FIELD)?,
+                    ts.get_string(VIEWCAT_VIEW_DEF_FIELD)?,
+                ));
             }
         }
-        Err(ViewManagerError::InvalidCall(format!(
-            "view {} not found",
-            view_name
-        )))
+        if chunks.is_empty() {
+            return Err(ViewManagerError::InvalidCall(format!(
+                "view {} not found",
+                view_name
+            )));
+        }
+        Ok(Self::join_view_def(chunks))
+    }
+
+    // 全ての view の定義を取得する
+    // viewcat がまだ作成されていない場合は、view が一つもないものとみなす
+    fn get_view_defs(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<Vec<(String, String)>, ViewManagerError> {
+        let layout = match self.table_manager.get_layout(VIEWCAT_TABLE_NAME, tx) {
+            Ok(layout) => layout,
+            Err(_) => return Ok(vec![]),
+        };
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, VIEWCAT_TABLE_NAME, &layout)?;
+        // view は最初の断片が見つかった順に返す
+        let mut view_names = vec![];
+        let mut chunks: HashMap<String, Vec<(i32, String)>> = HashMap::new();
+        while ts.move_next()? {
+            let view_name = ts.get_string(VIEWCAT_VIEW_NAME_FIELD)?;
+            let chunk = (
+                ts.get_int(VIEWCAT_SEQ_FIELD)?,
+                ts.get_string(VIEWCAT_VIEW_DEF_FIELD)?,
+            );
+            match chunks.get_mut(&view_name) {
+                Some(view_chunks) => view_chunks.push(chunk),
+                None => {
+                    view_names.push(view_name.clone());
+                    chunks.insert(view_name, vec![chunk]);
+                }
+            }
+        }
+        Ok(view_names
+            .into_iter()
+            .map(|view_name| {
+                let view_def = Self::join_view_def(chunks.remove(&view_name).unwrap_or_default());
+                (view_name, view_def)
+            })
+            .collect())
+    }
+
+    // view の定義を置き換える
+    // 古い定義の断片を全て削除してから、新しい定義の断片を insert する
+    fn update_view_def(
+        &self,
+        view_name: &str,
+        view_def: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> Result<(), ViewManagerError> {
+        let layout = self.table_manager.get_layout(VIEWCAT_TABLE_NAME, tx)?;
+        let mut ts = self
+            .table_scan_factory
+            .create(tx, VIEWCAT_TABLE_NAME, &layout)?;
+        let mut found = false;
+        while ts.move_next()? {
+            if ts.get_string(VIEWCAT_VIEW_NAME_FIELD)? == view_name {
+                ts.delete()?;
+                found = true;
+            }
+        }
+        if !found {
+            return Err(ViewManagerError::InvalidCall(format!(
+                "view {} not found",
+                view_name
+            )));
+        }
+        ts.before_first()?;
+        Self::insert_view_def(&mut ts, view_name, view_def)
     }
 }
 
 #[cfg(test)]
 mod view_manager_test {
     use crate::{
-        metadata::table_manager::MockTableManager,
+        metadata::table_manager::{MockTableManager, TableManagerImpl},
         query::scan::{MockUpdateScan, UpdateScan},
         record::{
             layout::Layout,
@@ -178,23 +312,33 @@ mod view_manager_test {
         TransactionFactory::new(file_manager, log_manager, buffer_manager, lock_table)
     }
 
+    fn viewcat_layout() -> Layout {
+        let mut schema = Schema::new();
+        schema.add_field(
+            VIEWCAT_VIEW_NAME_FIELD,
+            FieldInfo::String(MAX_VIEW_NAME_LENGTH),
+        );
+        schema.add_field(VIEWCAT_SEQ_FIELD, FieldInfo::Integer);
+        schema.add_field(
+            VIEWCAT_VIEW_DEF_FIELD,
+            FieldInfo::String(VIEWDEF_CHUNK_LENGTH),
+        );
+        Layout::new(schema).unwrap()
+    }
+
     #[test]
     fn test_setup() {
         let dir = tempdir().unwrap();
         let factory = setup_factory(&dir);
 
-        // table manager が create_table を呼び出すことを確認
+        // viewcat がまだない場合に、table manager が create_table を呼び出すことを確認
         let table_manager = {
             let mut table_manager = MockTableManager::new();
-            let mut schema = Schema::new();
-            schema.add_field(
-                VIEWCAT_VIEW_NAME_FIELD,
-                FieldInfo::String(MAX_VIEW_NAME_LENGTH),
-            );
-            schema.add_field(
-                VIEWCAT_VIEW_DEF_FIELD,
-                FieldInfo::String(MAX_VIEWDEF_LENGTH),
-            );
+            let schema = viewcat_layout().schema().clone();
+            table_manager
+                .expect_get_layout()
+                .times(1)
+                .returning(|_, _| Err(TableManagerError::InvalidCall("not found".to_string())));
             table_manager
                 .expect_create_table()
                 .withf(move |actual_table, actual_schema, _actual_tx| {
@@ -223,19 +367,7 @@ mod view_manager_test {
             table_manager
                 .expect_get_layout()
                 .times(1)
-                .returning(|_, _| {
-                    let mut schema = Schema::new();
-                    schema.add_field(
-                        VIEWCAT_VIEW_NAME_FIELD,
-                        FieldInfo::String(MAX_VIEW_NAME_LENGTH),
-                    );
-                    schema.add_field(
-                        VIEWCAT_VIEW_DEF_FIELD,
-                        FieldInfo::String(MAX_VIEWDEF_LENGTH),
-                    );
-                    let layout = Layout::new(schema).unwrap();
-                    Ok(layout)
-                });
+                .returning(|_, _| Ok(viewcat_layout()));
             table_manager
         };
 
@@ -261,6 +393,11 @@ mod view_manager_test {
                                 .with(eq(VIEWCAT_VIEW_NAME_FIELD), eq("view1"))
                                 .times(1)
                                 .returning(|_, _| Ok(()));
+                            table_scan
+                                .expect_set_int()
+                                .with(eq(VIEWCAT_SEQ_FIELD), eq(0))
+                                .times(1)
+                                .returning(|_, _| Ok(()));
                             table_scan
                                 .expect_set_string()
                                 .with(eq(VIEWCAT_VIEW_DEF_FIELD), eq("select * from table1"))
@@ -293,19 +430,7 @@ mod view_manager_test {
             table_manager
                 .expect_get_layout()
                 .times(1)
-                .returning(|_, _| {
-                    let mut schema = Schema::new();
-                    schema.add_field(
-                        VIEWCAT_VIEW_NAME_FIELD,
-                        FieldInfo::String(MAX_VIEW_NAME_LENGTH),
-                    );
-                    schema.add_field(
-                        VIEWCAT_VIEW_DEF_FIELD,
-                        FieldInfo::String(MAX_VIEWDEF_LENGTH),
-                    );
-                    let layout = Layout::new(schema).unwrap();
-                    Ok(layout)
-                });
+                .returning(|_, _| Ok(viewcat_layout()));
             table_manager
         };
 
@@ -324,10 +449,11 @@ mod view_manager_test {
                 .returning(move |_, _, _| {
                     let table_scan = {
                         let mut table_scan = MockUpdateScan::new();
-                        table_scan
-                            .expect_move_next()
-                            .times(1)
-                            .returning(|| Ok(true));
+                        let mut remaining = 1;
+                        table_scan.expect_move_next().times(2).returning(move || {
+                            remaining -= 1;
+                            Ok(remaining >= 0)
+                        });
 
                         // record の中身は view1, select * from table1 とする
                         table_scan
@@ -335,6 +461,11 @@ mod view_manager_test {
                             .withf(move |field_name| field_name == VIEWCAT_VIEW_NAME_FIELD)
                             .times(1)
                             .returning(|_| Ok("view1".to_string()));
+                        table_scan
+                            .expect_get_int()
+                            .withf(move |field_name| field_name == VIEWCAT_SEQ_FIELD)
+                            .times(1)
+                            .returning(|_| Ok(0));
                         table_scan
                             .expect_get_string()
                             .withf(move |field_name| field_name == VIEWCAT_VIEW_DEF_FIELD)
@@ -353,4 +484,51 @@ mod view_manager_test {
         let def = view_manager.get_view_def("view1", &tx).unwrap();
         assert_eq!(def, "select * from table1");
     }
+
+    #[test]
+    fn test_long_view_def() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let table_manager = TableManagerImpl::new(Arc::new(TableScanFactoryImpl::new())).unwrap();
+        table_manager.setup_if_not_exists(&tx).unwrap();
+        let view_manager =
+            ViewManagerImpl::new(&table_manager, Box::new(TableScanFactoryImpl::new()));
+        view_manager.setup_if_not_exists(&tx).unwrap();
+        // 何回呼び出しても大丈夫
+        view_manager.setup_if_not_exists(&tx).unwrap();
+
+        // 1 record に収まらない長さの定義も、複数の断片に分けて保存される
+        let fields = (0..100)
+            .map(|i| format!("field{}", i))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let long_def = format!("select {} from table1", fields);
+        assert!(long_def.len() > 10 * VIEWDEF_CHUNK_LENGTH);
+        view_manager.create_view("view1", &long_def, &tx).unwrap();
+        view_manager
+            .create_view("view2", "select a from table2", &tx)
+            .unwrap();
+        assert_eq!(view_manager.get_view_def("view1", &tx).unwrap(), long_def);
+        assert_eq!(
+            view_manager.get_view_defs(&tx).unwrap(),
+            vec![
+                ("view1".to_string(), long_def.clone()),
+                ("view2".to_string(), "select a from table2".to_string()),
+            ]
+        );
+
+        // 短い定義に置き換えると、古い断片は残らない
+        view_manager
+            .update_view_def("view1", "select b from table1", &tx)
+            .unwrap();
+        assert_eq!(
+            view_manager.get_view_def("view1", &tx).unwrap(),
+            "select b from table1"
+        );
+        assert!(view_manager
+            .update_view_def("no_such_view", "select a from table1", &tx)
+            .is_err());
+        tx.borrow_mut().commit().unwrap();
+    }
 }
@@ -1,5 +1,6 @@
-mod constant;
+pub(crate) mod constant;
 pub mod content;
-mod lexer;
+pub(crate) mod keyword;
+pub(crate) mod lexer;
 pub mod parser;
 pub mod parser_factory;
@@ -1,4 +1,54 @@
-pub const KEYWORDS: [&str; 18] = [
-    "select", "from", "where", "and", "insert", "into", "values", "delete", "update", "set",
-    "create", "table", "int", "varchar", "view", "as", "index", "on",
+/**
+ * 予約語を、それを使う文法ごとにまとめたもの
+ *
+ * 新しい文法を追加する場合は、その文法で使う予約語の group をここに追加し、keyword::reserved_keywords で登録する
+ * 同じ予約語が複数の group に含まれていてもよい
+ */
+pub const QUERY_KEYWORDS: [&str; 8] = [
+    "select", "distinct", "from", "where", "and", "as", "of", "lsn",
 ];
+pub const GROUP_BY_KEYWORDS: [&str; 7] = ["group", "by", "count", "max", "min", "sum", "avg"];
+pub const ORDER_BY_KEYWORDS: [&str; 4] = ["order", "by", "asc", "desc"];
+pub const EXPRESSION_KEYWORDS: [&str; 8] = [
+    "case", "when", "then", "else", "end", "true", "false", "date",
+];
+pub const MODIFY_KEYWORDS: [&str; 7] = [
+    "insert", "into", "values", "delete", "update", "set", "where",
+];
+pub const CREATE_TABLE_KEYWORDS: [&str; 23] = [
+    "create",
+    "table",
+    "int",
+    "varchar",
+    "bigint",
+    "boolean",
+    "date",
+    "double",
+    "temp",
+    "default",
+    "external",
+    "location",
+    "blocksize",
+    "compressed",
+    "if",
+    "not",
+    "exists",
+    "partition",
+    "by",
+    "hash",
+    "range",
+    "partitions",
+    "values",
+];
+pub const CREATE_VIEW_KEYWORDS: [&str; 3] = ["create", "view", "as"];
+pub const CREATE_INDEX_KEYWORDS: [&str; 7] = [
+    "create", "index", "on", "unique", "using", "btree", "include",
+];
+pub const ALTER_TABLE_KEYWORDS: [&str; 6] = ["alter", "table", "index", "rename", "column", "to"];
+pub const CURSOR_KEYWORDS: [&str; 5] = ["declare", "cursor", "for", "fetch", "close"];
+pub const EXPLAIN_KEYWORDS: [&str; 2] = ["explain", "analyze"];
+pub const SHOW_KEYWORDS: [&str; 3] = ["show", "tables", "describe"];
+
+/// lexer が 1 つの token として読む、2 文字以上の演算子
+/// 1 文字の演算子は Token::Delimiter として読む
+pub const MULTI_CHAR_OPERATORS: [&str; 4] = [">=", "<=", "<>", "!="];
@@ -1,7 +1,11 @@
 pub mod create_index_data;
 pub mod create_table_data;
 pub mod create_view_data;
+pub mod cursor_data;
 pub mod delete_data;
 pub mod insert_data;
 pub mod query_data;
+pub mod rename_column_data;
+pub mod rename_index_data;
+pub mod rename_table_data;
 pub mod update_data;
@@ -1,7 +1,15 @@
+use crate::index::common::IndexType;
+
 pub struct CreateIndexData {
     index_name: String,
     table_name: String,
     field_name: String,
+    // unique index の場合は同じ key の record を 2 つ以上入れられない
+    unique: bool,
+    // using で指定した index の種類。省略した場合は B-Tree
+    index_type: IndexType,
+    // include で指定した、index の record に一緒に保存する field
+    included_fields: Vec<String>,
 }
 
 impl CreateIndexData {
@@ -10,9 +18,27 @@ impl CreateIndexData {
             index_name,
             table_name,
             field_name,
+            unique: false,
+            index_type: IndexType::default(),
+            included_fields: vec![],
         }
     }
 
+    pub fn with_unique(mut self, unique: bool) -> Self {
+        self.unique = unique;
+        self
+    }
+
+    pub fn with_index_type(mut self, index_type: IndexType) -> Self {
+        self.index_type = index_type;
+        self
+    }
+
+    pub fn with_included_fields(mut self, included_fields: Vec<String>) -> Self {
+        self.included_fields = included_fields;
+        self
+    }
+
     pub fn index_name(&self) -> &str {
         &self.index_name
     }
@@ -24,4 +50,16 @@ impl CreateIndexData {
     pub fn field_name(&self) -> &str {
         &self.field_name
     }
+
+    pub fn is_unique(&self) -> bool {
+        self.unique
+    }
+
+    pub fn index_type(&self) -> IndexType {
+        self.index_type
+    }
+
+    pub fn included_fields(&self) -> &[String] {
+        &self.included_fields
+    }
 }
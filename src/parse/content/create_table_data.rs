@@ -1,13 +1,53 @@
-use crate::record::schema::Schema;
+use std::collections::HashMap;
+
+use crate::{
+    plan::expression::Expression,
+    record::{layout::StorageOptions, partition::PartitionSpec, schema::Schema},
+};
 
 pub struct CreateTableData {
     table: String,
     schema: Schema,
+    is_temp: bool,
+    // field 名 -> default 値の式
+    defaults: HashMap<String, Expression>,
+    // external table の場合、データを持つファイルの path
+    external_path: Option<String>,
+    // blocksize 句, compressed 句で指定された table の保存方法
+    storage: StorageOptions,
+    // partition by 句で指定された、record を partition に分ける方法
+    partition: Option<PartitionSpec>,
+    // if not exists が指定された場合、同じ名前の table がすでにあれば何もしない
+    if_not_exists: bool,
 }
 
 impl CreateTableData {
-    pub fn new(table: String, schema: Schema) -> Self {
-        Self { table, schema }
+    pub fn new(
+        table: String,
+        schema: Schema,
+        is_temp: bool,
+        defaults: HashMap<String, Expression>,
+        external_path: Option<String>,
+        storage: StorageOptions,
+    ) -> Self {
+        Self {
+            table,
+            schema,
+            is_temp,
+            defaults,
+            external_path,
+            storage,
+            partition: None,
+            if_not_exists: false,
+        }
+    }
+    pub fn with_partition(mut self, partition: PartitionSpec) -> Self {
+        self.partition = Some(partition);
+        self
+    }
+    pub fn with_if_not_exists(mut self) -> Self {
+        self.if_not_exists = true;
+        self
     }
     pub fn get_table(&self) -> &String {
         &self.table
@@ -15,4 +55,28 @@ impl CreateTableData {
     pub fn get_schema(&self) -> &Schema {
         &self.schema
     }
+    /// create temp table 文で作成された table かどうか
+    pub fn is_temp(&self) -> bool {
+        self.is_temp
+    }
+    /// default 句が指定された field の、field 名 -> default 値の式 のマップ
+    pub fn get_defaults(&self) -> &HashMap<String, Expression> {
+        &self.defaults
+    }
+    /// create external table 文で作成された table の場合、そのデータを持つファイルの path
+    pub fn get_external_path(&self) -> Option<&String> {
+        self.external_path.as_ref()
+    }
+    /// blocksize 句, compressed 句で指定された table の保存方法
+    pub fn get_storage(&self) -> StorageOptions {
+        self.storage
+    }
+    /// partition by 句で指定された、record を partition に分ける方法
+    pub fn get_partition(&self) -> Option<&PartitionSpec> {
+        self.partition.as_ref()
+    }
+    /// create table if not exists 文かどうか
+    pub fn if_not_exists(&self) -> bool {
+        self.if_not_exists
+    }
 }
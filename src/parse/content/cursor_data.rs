@@ -0,0 +1,42 @@
+use super::query_data::QueryData;
+
+/**
+ * declare cursor ... for select ... 文の parse 結果を保持する構造体
+ */
+pub struct DeclareCursorData {
+    cursor: String,
+    query: QueryData,
+}
+
+impl DeclareCursorData {
+    pub fn new(cursor: String, query: QueryData) -> Self {
+        Self { cursor, query }
+    }
+    pub fn get_cursor(&self) -> &String {
+        &self.cursor
+    }
+    pub fn get_query(&self) -> &QueryData {
+        &self.query
+    }
+}
+
+/**
+ * fetch n from ... 文の parse 結果を保持する構造体
+ */
+pub struct FetchData {
+    cursor: String,
+    count: usize,
+}
+
+impl FetchData {
+    pub fn new(cursor: String, count: usize) -> Self {
+        Self { cursor, count }
+    }
+    pub fn get_cursor(&self) -> &String {
+        &self.cursor
+    }
+    /// 一度に取得する record の最大数
+    pub fn get_count(&self) -> usize {
+        self.count
+    }
+}
@@ -3,6 +3,7 @@ use crate::query::constant::Constant;
 /**
  * insert 文の parse 結果を保持する構造体
  * この時点では fields と values の対応関係は気にしていない。特に、fields と values の数が異なる場合にもエラーとしていない。
+ * column のリストが省略された場合 (insert into t values (...)) は fields が空になり、実行時に table の全 field で補われる
  */
 pub struct InsertData {
     table: String,
@@ -1,37 +1,245 @@
 use std::fmt;
 
-use crate::plan::predicate::ProductPredicate;
+use crate::{
+    plan::{aggregation::Aggregation, expression::Expression, predicate::ProductPredicate},
+    query::record_comparator::SortDirection,
+};
 
 pub struct QueryData {
+    // select する field の名前。式で指定された値は、その値につけた名前
     fields: Vec<String>,
+    // select する値のうち、式で指定されたものの名前と式
+    expressions: Vec<(String, Expression)>,
     tables: Vec<String>,
     predicate: ProductPredicate,
+    // group by で指定された field
+    group_fields: Vec<String>,
+    // select する値のうち、集約関数で指定されたものの名前と集約関数
+    aggregations: Vec<(String, Aggregation)>,
+    // select distinct が指定された場合、select した値が全て等しい record を 1 つにまとめる
+    is_distinct: bool,
+    // order by で指定された、select する値の名前と並べ替える向き
+    order_by: Vec<(String, SortDirection)>,
+    // as of lsn N が指定された場合、LSN が N の時点で commit 済だった table の内容を読む
+    as_of_lsn: Option<u64>,
 }
 
 impl QueryData {
     pub fn new(fields: Vec<String>, tables: Vec<String>, predicate: ProductPredicate) -> Self {
         Self {
             fields,
+            expressions: vec![],
             tables,
             predicate,
+            group_fields: vec![],
+            aggregations: vec![],
+            is_distinct: false,
+            order_by: vec![],
+            as_of_lsn: None,
         }
     }
+    pub fn with_expressions(mut self, expressions: Vec<(String, Expression)>) -> Self {
+        self.expressions = expressions;
+        self
+    }
+    pub fn with_group_by(
+        mut self,
+        group_fields: Vec<String>,
+        aggregations: Vec<(String, Aggregation)>,
+    ) -> Self {
+        self.group_fields = group_fields;
+        self.aggregations = aggregations;
+        self
+    }
+    pub fn with_distinct(mut self, is_distinct: bool) -> Self {
+        self.is_distinct = is_distinct;
+        self
+    }
+    pub fn with_order_by(mut self, order_by: Vec<(String, SortDirection)>) -> Self {
+        self.order_by = order_by;
+        self
+    }
+    pub fn with_as_of_lsn(mut self, lsn: u64) -> Self {
+        self.as_of_lsn = Some(lsn);
+        self
+    }
     pub fn get_fields(&self) -> &Vec<String> {
         &self.fields
     }
+    pub fn get_expressions(&self) -> &[(String, Expression)] {
+        &self.expressions
+    }
+    /// name が式で指定された値の名前であれば、その式を返す
+    pub fn get_expression(&self, name: &str) -> Option<&Expression> {
+        self.expressions
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, expression)| expression)
+    }
+    pub fn get_group_fields(&self) -> &[String] {
+        &self.group_fields
+    }
+    pub fn get_aggregations(&self) -> &[(String, Aggregation)] {
+        &self.aggregations
+    }
+    /// name が集約関数で指定された値の名前であれば、その集約関数を返す
+    pub fn get_aggregation(&self, name: &str) -> Option<&Aggregation> {
+        self.aggregations
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, aggregation)| aggregation)
+    }
+    pub fn is_distinct(&self) -> bool {
+        self.is_distinct
+    }
+    pub fn get_order_by(&self) -> &[(String, SortDirection)] {
+        &self.order_by
+    }
+    /// group by か集約関数を使い、record を group にまとめる query かどうか
+    pub fn is_grouped(&self) -> bool {
+        !self.group_fields.is_empty() || !self.aggregations.is_empty()
+    }
+    /// select する値を求めるために table から読む必要がある field を返す
+    pub fn referenced_fields(&self) -> Vec<String> {
+        let mut fields: Vec<String> = self
+            .fields
+            .iter()
+            .flat_map(
+                |field| match (self.get_expression(field), self.get_aggregation(field)) {
+                    (Some(expression), _) => expression.fields(),
+                    (None, Some(aggregation)) => vec![aggregation.field().to_string()],
+                    (None, None) => vec![field.clone()],
+                },
+            )
+            .collect();
+        fields.extend(self.group_fields.iter().cloned());
+        fields
+    }
     pub fn get_tables(&self) -> &Vec<String> {
         &self.tables
     }
     pub fn get_predicate(&self) -> &ProductPredicate {
         &self.predicate
     }
+    pub fn get_as_of_lsn(&self) -> Option<u64> {
+        self.as_of_lsn
+    }
+    /// table_name の field 名 old_name を new_name に置き換えた query を返す
+    /// table_name を参照していない query の場合は None を返す
+    /// field 名は table で修飾されないので、from 句の他の table にも old_name の field がある
+    /// (has_field が true を返す) 場合は、table_name の field を指しているとは限らず None を返す
+    pub fn rename_field(
+        &self,
+        table_name: &str,
+        old_name: &str,
+        new_name: &str,
+        has_field: impl Fn(&str) -> bool,
+    ) -> Option<Self> {
+        if !self.tables.iter().any(|table| table == table_name) {
+            return None;
+        }
+        if self
+            .tables
+            .iter()
+            .any(|table| table != table_name && has_field(table))
+        {
+            return None;
+        }
+        // 名前を省略した式や集約関数の値はそれ自体を名前にしているので、一緒に名前も変える
+        let rename =
+            |field: &String| match (self.get_expression(field), self.get_aggregation(field)) {
+                (Some(expression), _) if *field == expression.to_string() => {
+                    expression.rename_field(old_name, new_name).to_string()
+                }
+                (None, Some(aggregation)) if *field == aggregation.to_string() => {
+                    aggregation.rename_field(old_name, new_name).to_string()
+                }
+                (Some(_), _) | (None, Some(_)) => field.clone(),
+                (None, None) if field == old_name => new_name.to_string(),
+                (None, None) => field.clone(),
+            };
+        Some(Self {
+            fields: self.fields.iter().map(rename).collect(),
+            expressions: self
+                .expressions
+                .iter()
+                .map(|(field, expression)| {
+                    (rename(field), expression.rename_field(old_name, new_name))
+                })
+                .collect(),
+            tables: self.tables.clone(),
+            predicate: self.predicate.rename_field(old_name, new_name),
+            group_fields: self
+                .group_fields
+                .iter()
+                .map(|field| {
+                    if field == old_name {
+                        new_name.to_string()
+                    } else {
+                        field.clone()
+                    }
+                })
+                .collect(),
+            aggregations: self
+                .aggregations
+                .iter()
+                .map(|(field, aggregation)| {
+                    (rename(field), aggregation.rename_field(old_name, new_name))
+                })
+                .collect(),
+            is_distinct: self.is_distinct,
+            order_by: self
+                .order_by
+                .iter()
+                .map(|(field, direction)| (rename(field), *direction))
+                .collect(),
+            as_of_lsn: self.as_of_lsn,
+        })
+    }
+    /// 参照している table 名 table_name を new_name に置き換えた query を返す
+    /// table_name を参照していない query の場合は None を返す
+    pub fn rename_table(&self, table_name: &str, new_name: &str) -> Option<Self> {
+        if !self.tables.iter().any(|table| table == table_name) {
+            return None;
+        }
+        Some(Self {
+            fields: self.fields.clone(),
+            expressions: self.expressions.clone(),
+            tables: self
+                .tables
+                .iter()
+                .map(|table| {
+                    if table == table_name {
+                        new_name.to_string()
+                    } else {
+                        table.clone()
+                    }
+                })
+                .collect(),
+            predicate: self.predicate.clone(),
+            group_fields: self.group_fields.clone(),
+            aggregations: self.aggregations.clone(),
+            is_distinct: self.is_distinct,
+            order_by: self.order_by.clone(),
+            as_of_lsn: self.as_of_lsn,
+        })
+    }
 }
 
 impl fmt::Display for QueryData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut query = "select ".to_string();
+        if self.is_distinct {
+            query += "distinct ";
+        }
         for (i, field) in self.fields.iter().enumerate() {
-            query += field;
+            match (self.get_expression(field), self.get_aggregation(field)) {
+                (Some(expression), _) if *field == expression.to_string() => query += field,
+                (Some(expression), _) => query += &format!("{} as {}", expression, field),
+                (None, Some(aggregation)) if *field == aggregation.to_string() => query += field,
+                (None, Some(aggregation)) => query += &format!("{} as {}", aggregation, field),
+                (None, None) => query += field,
+            }
             if i != self.fields.len() - 1 {
                 query += ", ";
             }
@@ -48,6 +256,22 @@ impl fmt::Display for QueryData {
             query += " where ";
             query += &predicate_string
         }
+        if !self.group_fields.is_empty() {
+            query += " group by ";
+            query += &self.group_fields.join(", ");
+        }
+        if !self.order_by.is_empty() {
+            query += " order by ";
+            query += &self
+                .order_by
+                .iter()
+                .map(|(field, direction)| format!("{} {}", field, direction))
+                .collect::<Vec<_>>()
+                .join(", ");
+        }
+        if let Some(lsn) = self.as_of_lsn {
+            query += &format!(" as of lsn {}", lsn);
+        }
         write!(f, "{}", query)
     }
 }
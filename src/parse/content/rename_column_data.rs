@@ -0,0 +1,27 @@
+/**
+ * alter table ... rename column ... to ... 文の parse 結果を保持する構造体
+ */
+pub struct RenameColumnData {
+    table: String,
+    old_field: String,
+    new_field: String,
+}
+
+impl RenameColumnData {
+    pub fn new(table: String, old_field: String, new_field: String) -> Self {
+        Self {
+            table,
+            old_field,
+            new_field,
+        }
+    }
+    pub fn get_table(&self) -> &String {
+        &self.table
+    }
+    pub fn get_old_field(&self) -> &String {
+        &self.old_field
+    }
+    pub fn get_new_field(&self) -> &String {
+        &self.new_field
+    }
+}
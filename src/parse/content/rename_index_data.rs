@@ -0,0 +1,19 @@
+/**
+ * alter index ... rename to ... 文の parse 結果を保持する構造体
+ */
+pub struct RenameIndexData {
+    index: String,
+    new_index: String,
+}
+
+impl RenameIndexData {
+    pub fn new(index: String, new_index: String) -> Self {
+        Self { index, new_index }
+    }
+    pub fn get_index(&self) -> &String {
+        &self.index
+    }
+    pub fn get_new_index(&self) -> &String {
+        &self.new_index
+    }
+}
@@ -0,0 +1,19 @@
+/**
+ * alter table ... rename to ... 文の parse 結果を保持する構造体
+ */
+pub struct RenameTableData {
+    table: String,
+    new_table: String,
+}
+
+impl RenameTableData {
+    pub fn new(table: String, new_table: String) -> Self {
+        Self { table, new_table }
+    }
+    pub fn get_table(&self) -> &String {
+        &self.table
+    }
+    pub fn get_new_table(&self) -> &String {
+        &self.new_table
+    }
+}
@@ -0,0 +1,130 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+use crate::metadata::identifier::{validate_identifier, IdentifierKind};
+
+use super::constant::{
+    ALTER_TABLE_KEYWORDS, CREATE_INDEX_KEYWORDS, CREATE_TABLE_KEYWORDS, CREATE_VIEW_KEYWORDS,
+    CURSOR_KEYWORDS, EXPLAIN_KEYWORDS, EXPRESSION_KEYWORDS, GROUP_BY_KEYWORDS, MODIFY_KEYWORDS,
+    ORDER_BY_KEYWORDS, QUERY_KEYWORDS, SHOW_KEYWORDS,
+};
+
+/**
+ * 予約語の一覧
+ *
+ * 文法ごとに予約語の group を登録し、Lexer はここに登録された語を Token::Keyword として読む
+ * 予約語は table などの名前に使えないので、どの文法の予約語なのかも覚えておき、error の表示に使う
+ */
+#[derive(Debug, Default)]
+pub(crate) struct KeywordRegistry {
+    // 予約語と、その予約語を最初に登録した文法の名前
+    keywords: HashMap<String, &'static str>,
+}
+
+impl KeywordRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// syntax の文法で使う予約語を登録する
+    pub fn with_keywords(mut self, syntax: &'static str, keywords: &[&str]) -> Self {
+        for keyword in keywords {
+            self.keywords.entry(keyword.to_string()).or_insert(syntax);
+        }
+        self
+    }
+
+    pub fn contains(&self, word: &str) -> bool {
+        self.keywords.contains_key(word)
+    }
+
+    /// word を予約語として使う文法の名前を返す
+    pub fn syntax_of(&self, word: &str) -> Option<&'static str> {
+        self.keywords.get(word).copied()
+    }
+
+    /// Lexer に渡す予約語の集合を返す
+    pub fn keywords(&self) -> HashSet<String> {
+        self.keywords.keys().cloned().collect()
+    }
+
+    /// 予約語と同じ name の代わりに使える名前の候補を返す
+    pub fn suggest_identifiers(&self, kind: IdentifierKind, name: &str) -> Vec<String> {
+        [format!("{}_", name), format!("{}_{}", kind, name)]
+            .into_iter()
+            .filter(|candidate| !self.contains(candidate))
+            .filter(|candidate| validate_identifier(kind, candidate).is_ok())
+            .collect()
+    }
+}
+
+/// SQL の文法全体の予約語を返す
+pub(crate) fn reserved_keywords() -> &'static KeywordRegistry {
+    static KEYWORDS: OnceLock<KeywordRegistry> = OnceLock::new();
+    KEYWORDS.get_or_init(|| {
+        KeywordRegistry::new()
+            .with_keywords("select", &QUERY_KEYWORDS)
+            .with_keywords("group by", &GROUP_BY_KEYWORDS)
+            .with_keywords("order by", &ORDER_BY_KEYWORDS)
+            .with_keywords("case expression", &EXPRESSION_KEYWORDS)
+            .with_keywords("insert / update / delete", &MODIFY_KEYWORDS)
+            .with_keywords("create table", &CREATE_TABLE_KEYWORDS)
+            .with_keywords("create view", &CREATE_VIEW_KEYWORDS)
+            .with_keywords("create index", &CREATE_INDEX_KEYWORDS)
+            .with_keywords("alter table", &ALTER_TABLE_KEYWORDS)
+            .with_keywords("cursor", &CURSOR_KEYWORDS)
+            .with_keywords("explain analyze", &EXPLAIN_KEYWORDS)
+            .with_keywords("show / describe", &SHOW_KEYWORDS)
+    })
+}
+
+#[cfg(test)]
+mod keyword_test {
+    use super::*;
+
+    #[test]
+    fn test_reserved_keywords() {
+        let keywords = reserved_keywords();
+        // 複数の文法で使う予約語は 1 つにまとめる
+        assert_eq!(keywords.keywords().len(), 73);
+        assert!(keywords.contains("select"));
+        assert!(keywords.contains("describe"));
+        assert!(!keywords.contains("student"));
+        assert_eq!(keywords.syntax_of("where"), Some("select"));
+        assert_eq!(keywords.syntax_of("blocksize"), Some("create table"));
+        assert_eq!(keywords.syntax_of("include"), Some("create index"));
+        assert_eq!(keywords.syntax_of("student"), None);
+    }
+
+    #[test]
+    fn test_register_keywords() {
+        let keywords = KeywordRegistry::new()
+            .with_keywords("select", &["select", "from"])
+            .with_keywords("order by", &["order", "by"]);
+        assert_eq!(
+            keywords.keywords(),
+            ["select", "from", "order", "by"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect()
+        );
+        assert_eq!(keywords.syntax_of("by"), Some("order by"));
+    }
+
+    #[test]
+    fn test_suggest_identifiers() {
+        let keywords = reserved_keywords();
+        assert_eq!(
+            keywords.suggest_identifiers(IdentifierKind::Table, "select"),
+            vec!["select_", "table_select"]
+        );
+        // catalog に収まらない候補は返さない
+        let name = "a".repeat(31);
+        assert_eq!(
+            keywords.suggest_identifiers(IdentifierKind::Field, &name),
+            vec![format!("{}_", name)]
+        );
+    }
+}
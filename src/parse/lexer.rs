@@ -2,10 +2,14 @@ use anyhow::{anyhow, Result as AnyhowResult};
 use std::collections::HashSet;
 use thiserror::Error;
 
+use crate::metadata::identifier::{is_identifier_part, is_identifier_start};
+
+use super::constant::MULTI_CHAR_OPERATORS;
+
 /**
  * Parser で扱う token の種類
  */
-#[derive(Debug, Clone, Eq, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum Token {
     // 予約語
     Keyword(String),
@@ -13,10 +17,16 @@ pub enum Token {
     Id(String),
     // 区切り文字
     Delimiter(char),
+    // >= のような 2 文字以上の演算子
+    Operator(String),
     // 文字列リテラル
     StringConstant(String),
     // 数値リテラル
     IntConstant(i32),
+    // int に収まらない数値リテラル
+    BigIntConstant(i64),
+    // 小数点を含む数値リテラル
+    DoubleConstant(f64),
     #[default]
     None,
 }
@@ -91,6 +101,32 @@ impl Lexer {
         }
     }
 
+    /// int に収まらない数値の constant を読み進める
+    pub fn eat_bigint_constant(&mut self) -> AnyhowResult<i64> {
+        match self.token {
+            Token::BigIntConstant(val) => {
+                self.token = self.read_token()?;
+                Ok(val)
+            }
+            _ => Err(anyhow!(LexerError::UnexpectedToken(
+                "expected bigint constant".to_string()
+            ))),
+        }
+    }
+
+    /// 小数点を含む数値の constant を読み進める
+    pub fn eat_double_constant(&mut self) -> AnyhowResult<f64> {
+        match self.token {
+            Token::DoubleConstant(val) => {
+                self.token = self.read_token()?;
+                Ok(val)
+            }
+            _ => Err(anyhow!(LexerError::UnexpectedToken(
+                "expected double constant".to_string()
+            ))),
+        }
+    }
+
     /// string constant を読み進める
     pub fn eat_string_constant(&mut self) -> AnyhowResult<String> {
         match std::mem::take(&mut self.token) {
@@ -105,11 +141,16 @@ impl Lexer {
     }
 
     pub fn eat_id(&mut self) -> AnyhowResult<String> {
-        match std::mem::take(&mut self.token) {
+        match &self.token {
             Token::Id(val) => {
+                let val = val.clone();
                 self.token = self.read_token()?;
                 Ok(val)
             }
+            Token::Keyword(keyword) => Err(anyhow!(LexerError::UnexpectedToken(format!(
+                "expected identifier, but got reserved word {}",
+                keyword
+            )))),
             _ => Err(anyhow!(LexerError::UnexpectedToken(
                 "expected identifier".to_string()
             ))),
@@ -149,27 +190,46 @@ impl Lexer {
                     num.push('-');
                 }
                 num.push(c);
-                for c in chars.by_ref() {
-                    if c.is_numeric() {
+                while let Some(c) = chars.clone().next().filter(|c| c.is_numeric()) {
+                    num.push(c);
+                    chars.next();
+                }
+                // 小数点の後に数字が続く場合は double のリテラルとして読む
+                let mut fraction = chars.clone();
+                if fraction.next() == Some('.') && fraction.next().is_some_and(|c| c.is_numeric()) {
+                    chars.next();
+                    num.push('.');
+                    while let Some(c) = chars.clone().next().filter(|c| c.is_numeric()) {
                         num.push(c);
-                    } else {
-                        break;
+                        chars.next();
                     }
+                    self.position += num.len();
+                    let val: f64 = num.parse().map_err(|_| {
+                        anyhow!(LexerError::Internal(format!(
+                            "failed to parse string into double: {}",
+                            num
+                        )))
+                    })?;
+                    return Ok(Token::DoubleConstant(val));
                 }
                 self.position += num.len();
-                return Ok(Token::IntConstant(num.parse().map_err(|_| {
+                let val: i64 = num.parse().map_err(|_| {
                     anyhow!(LexerError::Internal(format!(
                         "failed to parse string into integer: {}",
                         num
                     )))
-                })?));
+                })?;
+                return Ok(match i32::try_from(val) {
+                    Ok(val) => Token::IntConstant(val),
+                    Err(_) => Token::BigIntConstant(val),
+                });
             }
 
-            if c.is_alphabetic() || c == '_' {
+            if is_identifier_start(c) {
                 let mut sval = String::new();
                 sval.push(c);
                 for c in chars.by_ref() {
-                    if c.is_alphanumeric() {
+                    if is_identifier_part(c) {
                         sval.push(c);
                     } else {
                         break;
@@ -182,6 +242,16 @@ impl Lexer {
                     Ok(Token::Id(sval))
                 };
             }
+            // 最も長く一致する演算子を優先する
+            let rest = &self.input[self.position..];
+            if let Some(op) = MULTI_CHAR_OPERATORS
+                .iter()
+                .filter(|op| rest.starts_with(*op))
+                .max_by_key(|op| op.len())
+            {
+                self.position += op.len();
+                return Ok(Token::Operator(op.to_string()));
+            }
             self.position += c.len_utf8();
             return Ok(Token::Delimiter(c));
         }
@@ -191,14 +261,14 @@ impl Lexer {
 
 #[cfg(test)]
 mod lexer_test {
-    use crate::parse::constant::KEYWORDS;
+    use crate::parse::keyword::reserved_keywords;
 
     use super::*;
     #[test]
     fn test_legal_input() {
         let mut lexer = Lexer::new(
             "select a from x, z where b = 3 and c = 'string'".to_string(),
-            KEYWORDS.iter().map(|&s| s.to_string()).collect(),
+            reserved_keywords().keywords(),
         )
         .unwrap();
         assert!(lexer.is_matched(Token::Keyword("select".to_string())));
@@ -250,11 +320,73 @@ mod lexer_test {
         assert!(lexer.is_matched(Token::None));
     }
 
+    #[test]
+    fn test_multi_char_operators() {
+        let mut lexer = Lexer::new(
+            "a>=1 and b <= 2 and c<>d and e != 'x' and f > g and h<i and j=!k".to_string(),
+            reserved_keywords().keywords(),
+        )
+        .unwrap();
+        let mut tokens = vec![];
+        while !lexer.is_matched(Token::None) {
+            let token = lexer.get_token().clone();
+            lexer.eat_exact(token.clone()).unwrap();
+            if !matches!(token, Token::Keyword(_)) {
+                tokens.push(token);
+            }
+        }
+        let id = |s: &str| Token::Id(s.to_string());
+        let op = |s: &str| Token::Operator(s.to_string());
+        assert_eq!(
+            tokens,
+            vec![
+                id("a"),
+                op(">="),
+                Token::IntConstant(1),
+                id("b"),
+                op("<="),
+                Token::IntConstant(2),
+                id("c"),
+                op("<>"),
+                id("d"),
+                id("e"),
+                op("!="),
+                Token::StringConstant("x".to_string()),
+                // 1 文字の演算子は今まで通り区切り文字になる
+                id("f"),
+                Token::Delimiter('>'),
+                id("g"),
+                id("h"),
+                Token::Delimiter('<'),
+                id("i"),
+                id("j"),
+                Token::Delimiter('='),
+                Token::Delimiter('!'),
+                id("k"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_double_constants() {
+        let mut lexer =
+            Lexer::new("1.5 0.25 7 3.x".to_string(), reserved_keywords().keywords()).unwrap();
+        assert_eq!(lexer.eat_double_constant().unwrap(), 1.5);
+        assert_eq!(lexer.eat_double_constant().unwrap(), 0.25);
+        assert!(lexer.eat_double_constant().is_err());
+        assert_eq!(lexer.eat_int_constant().unwrap(), 7);
+        // 小数点の後に数字が続かない場合は int と区切り文字として読む
+        assert_eq!(lexer.eat_int_constant().unwrap(), 3);
+        lexer.eat_exact(Token::Delimiter('.')).unwrap();
+        assert_eq!(lexer.eat_id().unwrap(), "x");
+        assert!(lexer.is_matched(Token::None));
+    }
+
     #[test]
     fn test_it_returns_error_if_unmatching_token() {
         let mut lexer = Lexer::new(
             "select a from x, z where b = 3".to_string(),
-            KEYWORDS.iter().map(|&s| s.to_string()).collect(),
+            reserved_keywords().keywords(),
         )
         .unwrap();
 
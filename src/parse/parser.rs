@@ -1,20 +1,39 @@
+use std::collections::HashMap;
+
 use crate::{
+    index::common::IndexType,
+    metadata::identifier::{validate_identifier, IdentifierError, IdentifierKind},
     plan::{
-        expression::Expression,
+        aggregation::{Aggregation, AggregationKind},
+        expression::{CaseExpression, Expression},
         predicate::ProductPredicate,
-        term::{EqualTerm, Term},
+        term::{ComparisonTerm, EqualTerm, Term},
+    },
+    query::{
+        constant::Constant, date::Date, record_comparator::SortDirection, term::ComparisonOperator,
+    },
+    record::{
+        layout::StorageOptions,
+        partition::{PartitionScheme, PartitionSpec},
+        schema::{FieldInfo, Schema},
     },
-    query::constant::Constant,
-    record::schema::{FieldInfo, Schema},
 };
 
 use super::{
-    constant::KEYWORDS,
     content::{
-        create_index_data::CreateIndexData, create_table_data::CreateTableData,
-        create_view_data::CreateViewData, delete_data::DeleteData, insert_data::InsertData,
-        query_data::QueryData, update_data::UpdateData,
+        create_index_data::CreateIndexData,
+        create_table_data::CreateTableData,
+        create_view_data::CreateViewData,
+        cursor_data::{DeclareCursorData, FetchData},
+        delete_data::DeleteData,
+        insert_data::InsertData,
+        query_data::QueryData,
+        rename_column_data::RenameColumnData,
+        rename_index_data::RenameIndexData,
+        rename_table_data::RenameTableData,
+        update_data::UpdateData,
     },
+    keyword::reserved_keywords,
     lexer::{Lexer, Token},
 };
 use anyhow::{anyhow, Result as AnyhowResult};
@@ -34,6 +53,8 @@ pub trait Parser {
     fn parse_expression(&mut self) -> AnyhowResult<Expression>;
     /// = で結ばれた term の取得
     fn parse_equal_term(&mut self) -> AnyhowResult<EqualTerm>;
+    /// =, <, >, <=, >=, <> (!=) のいずれかの比較演算子で結ばれた term の取得
+    fn parse_term(&mut self) -> AnyhowResult<Term>;
     /// and で結ばれた predicate の取得
     fn parse_predicate(&mut self) -> AnyhowResult<ProductPredicate>;
     /// select 文の取得
@@ -41,18 +62,29 @@ pub trait Parser {
     /// insert, delete, update, create table, create view, create index のいずれかの文の取得
     fn parse_update_command(&mut self) -> AnyhowResult<UpdateCommand>;
     /// insert 文の取得
+    /// column のリストが省略された場合、InsertData の fields は空になる
     fn parse_insert(&mut self) -> AnyhowResult<InsertData>;
     /// delete 文の取得
     fn parse_delete(&mut self) -> AnyhowResult<DeleteData>;
     /// update 文の取得
     fn parse_update(&mut self) -> AnyhowResult<UpdateData>;
-    /// create table 文の取得
+    /// create table 文 (create temp table 文, create external table 文を含む) の取得
     fn parse_create_table(&mut self) -> AnyhowResult<CreateTableData>;
     /// create view 文の取得
     fn parse_create_view(&mut self) -> AnyhowResult<CreateViewData>;
     /// create index 文の取得
     /// field としては一つしか許容していないことに注意
     fn parse_create_index(&mut self) -> AnyhowResult<CreateIndexData>;
+    /// alter table ... rename column ... to ... 文の取得
+    fn parse_rename_column(&mut self) -> AnyhowResult<RenameColumnData>;
+    /// alter table ... rename column ... to ..., alter table ... rename to ..., alter index ... rename to ... のいずれかの文の取得
+    fn parse_alter(&mut self) -> AnyhowResult<UpdateCommand>;
+    /// declare cursor, fetch, close のいずれかの文の取得
+    fn parse_cursor_command(&mut self) -> AnyhowResult<CursorCommand>;
+    /// explain analyze select ... 文の取得
+    fn parse_explain_analyze(&mut self) -> AnyhowResult<QueryData>;
+    /// show tables, describe のいずれかの文の取得
+    fn parse_show_command(&mut self) -> AnyhowResult<ShowCommand>;
 }
 
 #[derive(Error, Debug)]
@@ -74,6 +106,42 @@ pub enum UpdateCommand {
     CreateTable(CreateTableData),
     CreateView(CreateViewData),
     CreateIndex(CreateIndexData),
+    RenameColumn(RenameColumnData),
+    RenameTable(RenameTableData),
+    RenameIndex(RenameIndexData),
+}
+
+/// select 句に書かれた値
+enum SelectItem {
+    Expression(Expression),
+    /// count(sid) などの集約関数の値
+    Aggregation(Aggregation),
+}
+
+impl SelectItem {
+    /// as で名前を省略した場合の値の名前
+    fn default_name(&self) -> String {
+        match self {
+            SelectItem::Expression(expression) => expression.to_string(),
+            SelectItem::Aggregation(aggregation) => aggregation.to_string(),
+        }
+    }
+}
+
+pub enum CursorCommand {
+    /// declare cursor c for select ...
+    Declare(DeclareCursorData),
+    /// fetch n from c
+    Fetch(FetchData),
+    /// close c
+    Close(String),
+}
+
+pub enum ShowCommand {
+    /// show tables
+    Tables,
+    /// describe t
+    Describe(String),
 }
 
 impl Parser for ParserImpl {
@@ -83,10 +151,35 @@ impl Parser for ParserImpl {
                 let value = self.lexer.eat_int_constant()?;
                 Ok(Constant::Int(value))
             }
+            Token::BigIntConstant(_) => {
+                let value = self.lexer.eat_bigint_constant()?;
+                Ok(Constant::BigInt(value))
+            }
+            Token::DoubleConstant(_) => {
+                let value = self.lexer.eat_double_constant()?;
+                Ok(Constant::Double(value))
+            }
+            // date 'YYYY-MM-DD'
+            Token::Keyword(keyword) if keyword == "date" => {
+                self.lexer.eat_exact(Token::Keyword("date".to_string()))?;
+                let value = self.lexer.eat_string_constant()?;
+                let date = Date::parse(&value).ok_or_else(|| {
+                    anyhow!(ParserError::UnexpectedToken(format!(
+                        "invalid date literal '{}'. expected YYYY-MM-DD",
+                        value
+                    )))
+                })?;
+                Ok(Constant::Date(date))
+            }
             Token::StringConstant(_) => {
                 let value = self.lexer.eat_string_constant()?;
                 Ok(Constant::String(value))
             }
+            Token::Keyword(keyword) if keyword == "true" || keyword == "false" => {
+                let value = keyword == "true";
+                self.lexer.eat_exact(Token::Keyword(keyword.clone()))?;
+                Ok(Constant::Bool(value))
+            }
             _ => Err(anyhow!(ParserError::UnexpectedToken(
                 "expected constant".to_string()
             ))),
@@ -94,7 +187,16 @@ impl Parser for ParserImpl {
     }
     fn parse_expression(&mut self) -> AnyhowResult<Expression> {
         match &self.lexer.get_token() {
-            Token::IntConstant(_) | Token::StringConstant(_) => {
+            Token::IntConstant(_)
+            | Token::BigIntConstant(_)
+            | Token::DoubleConstant(_)
+            | Token::StringConstant(_) => {
+                let constant = self.parse_constant()?;
+                Ok(Expression::Constant(constant))
+            }
+            Token::Keyword(keyword)
+                if keyword == "true" || keyword == "false" || keyword == "date" =>
+            {
                 let constant = self.parse_constant()?;
                 Ok(Expression::Constant(constant))
             }
@@ -102,6 +204,9 @@ impl Parser for ParserImpl {
                 let field_name = self.lexer.eat_id()?;
                 Ok(Expression::Field(field_name))
             }
+            Token::Keyword(keyword) if keyword == "case" => {
+                Ok(Expression::Case(self.parse_case_expression()?))
+            }
             _ => Err(anyhow!(ParserError::UnexpectedToken(
                 "expected expression".to_string()
             ))),
@@ -113,30 +218,77 @@ impl Parser for ParserImpl {
         let rhs = self.parse_expression()?;
         Ok(EqualTerm::new(lhs, rhs))
     }
+    fn parse_term(&mut self) -> AnyhowResult<Term> {
+        let lhs = self.parse_expression()?;
+        self.parse_term_with_lhs(lhs)
+    }
     fn parse_predicate(&mut self) -> AnyhowResult<ProductPredicate> {
-        let mut terms: Vec<Term> = vec![Term::Equal(self.parse_equal_term()?)];
+        let mut terms: Vec<Term> = self.parse_terms()?;
         while self.lexer.is_matched(Token::Keyword("and".to_string())) {
             self.lexer.eat_exact(Token::Keyword("and".to_string()))?;
-            terms.push(Term::Equal(self.parse_equal_term()?));
+            terms.extend(self.parse_terms()?);
         }
         Ok(ProductPredicate::new(terms))
     }
     fn parse_query(&mut self) -> AnyhowResult<QueryData> {
         self.lexer.eat_exact(Token::Keyword("select".to_string()))?;
-        let fields = self.parse_id_list()?;
+        let is_distinct = self
+            .lexer
+            .is_matched(Token::Keyword("distinct".to_string()));
+        if is_distinct {
+            self.lexer
+                .eat_exact(Token::Keyword("distinct".to_string()))?;
+        }
+        let select_list = self.parse_select_list()?;
+        let fields = select_list.iter().map(|(name, _)| name.clone()).collect();
+        // field をそのまま select する値以外は、式または集約関数として覚えておく
+        let mut expressions = vec![];
+        let mut aggregations = vec![];
+        for (name, item) in select_list {
+            match item {
+                SelectItem::Expression(expression) if expression.as_field() == Some(&name) => {}
+                SelectItem::Expression(expression) => expressions.push((name, expression)),
+                SelectItem::Aggregation(aggregation) => aggregations.push((name, aggregation)),
+            }
+        }
         self.lexer.eat_exact(Token::Keyword("from".to_string()))?;
         let tables = self.parse_id_list()?;
-        if self.lexer.is_matched(Token::Keyword("where".to_string())) {
+        let predicate = if self.lexer.is_matched(Token::Keyword("where".to_string())) {
             self.lexer.eat_exact(Token::Keyword("where".to_string()))?;
-            let predicate = self.parse_predicate()?;
-            Ok(QueryData::new(fields, tables, predicate))
+            self.parse_predicate()?
         } else {
-            Ok(QueryData::new(
-                fields,
-                tables,
-                ProductPredicate::new(vec![]),
-            ))
+            ProductPredicate::new(vec![])
+        };
+        let group_fields = if self.lexer.is_matched(Token::Keyword("group".to_string())) {
+            self.lexer.eat_exact(Token::Keyword("group".to_string()))?;
+            self.lexer.eat_exact(Token::Keyword("by".to_string()))?;
+            self.parse_id_list()?
+        } else {
+            vec![]
+        };
+        let order_by = if self.lexer.is_matched(Token::Keyword("order".to_string())) {
+            self.lexer.eat_exact(Token::Keyword("order".to_string()))?;
+            self.lexer.eat_exact(Token::Keyword("by".to_string()))?;
+            self.parse_order_by_list()?
+        } else {
+            vec![]
+        };
+        let query_data = QueryData::new(fields, tables, predicate)
+            .with_expressions(expressions)
+            .with_group_by(group_fields, aggregations)
+            .with_distinct(is_distinct)
+            .with_order_by(order_by);
+        if self.lexer.is_matched(Token::Keyword("as".to_string())) {
+            self.lexer.eat_exact(Token::Keyword("as".to_string()))?;
+            self.lexer.eat_exact(Token::Keyword("of".to_string()))?;
+            self.lexer.eat_exact(Token::Keyword("lsn".to_string()))?;
+            let lsn = self.lexer.eat_int_constant()?;
+            let lsn = u64::try_from(lsn).map_err(|_| {
+                ParserError::UnexpectedToken(format!("lsn must not be negative: {}", lsn))
+            })?;
+            return Ok(query_data.with_as_of_lsn(lsn));
         }
+        Ok(query_data)
     }
     fn parse_update_command(&mut self) -> AnyhowResult<UpdateCommand> {
         if self.lexer.is_matched(Token::Keyword("insert".to_string())) {
@@ -147,20 +299,29 @@ impl Parser for ParserImpl {
             Ok(UpdateCommand::Update(self.parse_update()?))
         } else if self.lexer.is_matched(Token::Keyword("create".to_string())) {
             self.lexer.eat_exact(Token::Keyword("create".to_string()))?;
-            if self.lexer.is_matched(Token::Keyword("table".to_string())) {
+            if self.lexer.is_matched(Token::Keyword("table".to_string()))
+                || self.lexer.is_matched(Token::Keyword("temp".to_string()))
+                || self
+                    .lexer
+                    .is_matched(Token::Keyword("external".to_string()))
+            {
                 Ok(UpdateCommand::CreateTable(self._parse_create_table(true)?))
             } else if self.lexer.is_matched(Token::Keyword("view".to_string())) {
                 Ok(UpdateCommand::CreateView(self._parse_create_view(true)?))
-            } else if self.lexer.is_matched(Token::Keyword("index".to_string())) {
+            } else if self.lexer.is_matched(Token::Keyword("index".to_string()))
+                || self.lexer.is_matched(Token::Keyword("unique".to_string()))
+            {
                 Ok(UpdateCommand::CreateIndex(self._parse_create_index(true)?))
             } else {
                 Err(anyhow!(ParserError::UnexpectedToken(
                     "expected table, view, or index for create command".to_string()
                 )))
             }
+        } else if self.lexer.is_matched(Token::Keyword("alter".to_string())) {
+            self.parse_alter()
         } else {
             Err(anyhow!(ParserError::UnexpectedToken(
-                "expected insert, delete, update, or create for udpate command".to_string()
+                "expected insert, delete, update, create, or alter for udpate command".to_string()
             )))
         }
     }
@@ -168,9 +329,15 @@ impl Parser for ParserImpl {
         self.lexer.eat_exact(Token::Keyword("insert".to_string()))?;
         self.lexer.eat_exact(Token::Keyword("into".to_string()))?;
         let table_name = self.lexer.eat_id()?;
-        self.lexer.eat_exact(Token::Delimiter('('))?;
-        let fields = self.parse_id_list()?;
-        self.lexer.eat_exact(Token::Delimiter(')'))?;
+        // column のリストは省略可能。省略された場合は空のリストとする
+        let fields = if self.lexer.is_matched(Token::Delimiter('(')) {
+            self.lexer.eat_exact(Token::Delimiter('('))?;
+            let fields = self.parse_id_list()?;
+            self.lexer.eat_exact(Token::Delimiter(')'))?;
+            fields
+        } else {
+            vec![]
+        };
         self.lexer.eat_exact(Token::Keyword("values".to_string()))?;
         self.lexer.eat_exact(Token::Delimiter('('))?;
         let values = self.parse_constant_list()?;
@@ -213,13 +380,259 @@ impl Parser for ParserImpl {
     fn parse_create_index(&mut self) -> AnyhowResult<CreateIndexData> {
         self._parse_create_index(false)
     }
+    fn parse_rename_column(&mut self) -> AnyhowResult<RenameColumnData> {
+        match self.parse_alter()? {
+            UpdateCommand::RenameColumn(data) => Ok(data),
+            _ => Err(anyhow!(ParserError::UnexpectedToken(
+                "expected rename column".to_string()
+            ))),
+        }
+    }
+    fn parse_alter(&mut self) -> AnyhowResult<UpdateCommand> {
+        self.lexer.eat_exact(Token::Keyword("alter".to_string()))?;
+        if self.lexer.is_matched(Token::Keyword("index".to_string())) {
+            self.lexer.eat_exact(Token::Keyword("index".to_string()))?;
+            let index_name = self.lexer.eat_id()?;
+            self.lexer.eat_exact(Token::Keyword("rename".to_string()))?;
+            self.lexer.eat_exact(Token::Keyword("to".to_string()))?;
+            let new_index_name = self.eat_identifier(IdentifierKind::Index)?;
+            return Ok(UpdateCommand::RenameIndex(RenameIndexData::new(
+                index_name,
+                new_index_name,
+            )));
+        }
+        self.lexer.eat_exact(Token::Keyword("table".to_string()))?;
+        let table_name = self.lexer.eat_id()?;
+        self.lexer.eat_exact(Token::Keyword("rename".to_string()))?;
+        // column が続かない場合は table 自身の名前を変更する
+        if !self.lexer.is_matched(Token::Keyword("column".to_string())) {
+            self.lexer.eat_exact(Token::Keyword("to".to_string()))?;
+            let new_table_name = self.eat_identifier(IdentifierKind::Table)?;
+            return Ok(UpdateCommand::RenameTable(RenameTableData::new(
+                table_name,
+                new_table_name,
+            )));
+        }
+        self.lexer.eat_exact(Token::Keyword("column".to_string()))?;
+        let old_field = self.lexer.eat_id()?;
+        self.lexer.eat_exact(Token::Keyword("to".to_string()))?;
+        let new_field = self.eat_identifier(IdentifierKind::Field)?;
+        Ok(UpdateCommand::RenameColumn(RenameColumnData::new(
+            table_name, old_field, new_field,
+        )))
+    }
+    fn parse_cursor_command(&mut self) -> AnyhowResult<CursorCommand> {
+        if self.lexer.is_matched(Token::Keyword("declare".to_string())) {
+            self.lexer
+                .eat_exact(Token::Keyword("declare".to_string()))?;
+            self.lexer.eat_exact(Token::Keyword("cursor".to_string()))?;
+            let cursor = self.lexer.eat_id()?;
+            self.lexer.eat_exact(Token::Keyword("for".to_string()))?;
+            let query = self.parse_query()?;
+            Ok(CursorCommand::Declare(DeclareCursorData::new(
+                cursor, query,
+            )))
+        } else if self.lexer.is_matched(Token::Keyword("fetch".to_string())) {
+            self.lexer.eat_exact(Token::Keyword("fetch".to_string()))?;
+            let count = self.lexer.eat_int_constant()?;
+            if count <= 0 {
+                return Err(anyhow!(ParserError::UnexpectedToken(
+                    "fetch count must be positive".to_string()
+                )));
+            }
+            self.lexer.eat_exact(Token::Keyword("from".to_string()))?;
+            let cursor = self.lexer.eat_id()?;
+            Ok(CursorCommand::Fetch(FetchData::new(cursor, count as usize)))
+        } else if self.lexer.is_matched(Token::Keyword("close".to_string())) {
+            self.lexer.eat_exact(Token::Keyword("close".to_string()))?;
+            Ok(CursorCommand::Close(self.lexer.eat_id()?))
+        } else {
+            Err(anyhow!(ParserError::UnexpectedToken(
+                "expected declare, fetch, or close for cursor command".to_string()
+            )))
+        }
+    }
+    fn parse_explain_analyze(&mut self) -> AnyhowResult<QueryData> {
+        self.lexer
+            .eat_exact(Token::Keyword("explain".to_string()))?;
+        self.lexer
+            .eat_exact(Token::Keyword("analyze".to_string()))?;
+        self.parse_query()
+    }
+    fn parse_show_command(&mut self) -> AnyhowResult<ShowCommand> {
+        if self.lexer.is_matched(Token::Keyword("show".to_string())) {
+            self.lexer.eat_exact(Token::Keyword("show".to_string()))?;
+            self.lexer.eat_exact(Token::Keyword("tables".to_string()))?;
+            Ok(ShowCommand::Tables)
+        } else if self
+            .lexer
+            .is_matched(Token::Keyword("describe".to_string()))
+        {
+            self.lexer
+                .eat_exact(Token::Keyword("describe".to_string()))?;
+            Ok(ShowCommand::Describe(self.lexer.eat_id()?))
+        } else {
+            Err(anyhow!(ParserError::UnexpectedToken(
+                "expected show or describe".to_string()
+            )))
+        }
+    }
 }
 
 impl ParserImpl {
     pub fn new(input: String) -> AnyhowResult<ParserImpl> {
-        let lexer = Lexer::new(input, KEYWORDS.iter().map(|s| s.to_string()).collect())?;
+        let lexer = Lexer::new(input, reserved_keywords().keywords())?;
         Ok(ParserImpl { lexer })
     }
+    /// 新しく作成する table などの名前を読み進める。catalog に保存できない名前の場合は error を返す
+    fn eat_identifier(&mut self, kind: IdentifierKind) -> AnyhowResult<String> {
+        // 予約語は Token::Keyword として読まれるので、名前に使えない理由をここで説明する
+        if let Token::Keyword(keyword) = self.lexer.get_token() {
+            if let Some(err) = IdentifierError::reserved_word(kind, keyword) {
+                return Err(err.into());
+            }
+        }
+        let name = self.lexer.eat_id()?;
+        validate_identifier(kind, &name)?;
+        Ok(name)
+    }
+    /// = で結ばれた term を読み進める
+    /// (a, b) = (1, 'x') のような行値どうしの比較は、要素ごとの term (a = 1, b = 'x') に展開して返す
+    fn parse_terms(&mut self) -> AnyhowResult<Vec<Term>> {
+        if !self.lexer.is_matched(Token::Delimiter('(')) {
+            return Ok(vec![self.parse_term()?]);
+        }
+        let lhs = self.parse_row_value()?;
+        self.lexer.eat_exact(Token::Delimiter('='))?;
+        let rhs = self.parse_row_value()?;
+        let (lhs_len, rhs_len) = (lhs.len(), rhs.len());
+        let predicate = ProductPredicate::equal_rows(lhs, rhs).ok_or_else(|| {
+            ParserError::UnexpectedToken(format!(
+                "row values of different sizes cannot be compared: {} and {}",
+                lhs_len, rhs_len
+            ))
+        })?;
+        Ok(predicate.terms().to_vec())
+    }
+    /// select 句の値の並びを読み進め、値の名前と値の組を返す
+    /// 値は field 名の他に、式 (select a = 1 from t など) や集約関数 (select count(a) from t など) で指定でき、as で名前をつけられる
+    /// 名前を省略した値は、式や集約関数そのものを名前にする
+    fn parse_select_list(&mut self) -> AnyhowResult<Vec<(String, SelectItem)>> {
+        let mut select_list = vec![];
+        loop {
+            let item = match self.aggregation_kind() {
+                Some(kind) => SelectItem::Aggregation(self.parse_aggregation(kind)?),
+                None => SelectItem::Expression(self.parse_select_expression()?),
+            };
+            let name = if self.lexer.is_matched(Token::Keyword("as".to_string())) {
+                self.lexer.eat_exact(Token::Keyword("as".to_string()))?;
+                self.eat_identifier(IdentifierKind::Field)?
+            } else {
+                item.default_name()
+            };
+            select_list.push((name, item));
+            if !self.lexer.is_matched(Token::Delimiter(',')) {
+                break;
+            }
+            self.lexer.eat_exact(Token::Delimiter(','))?;
+        }
+        Ok(select_list)
+    }
+    /// cursor が集約関数の名前を指している場合は、その種類を返す
+    fn aggregation_kind(&self) -> Option<AggregationKind> {
+        match self.lexer.get_token() {
+            Token::Keyword(keyword) => AggregationKind::from_name(keyword),
+            _ => None,
+        }
+    }
+    /// count(a) などの集約関数の呼び出しを読み進める
+    fn parse_aggregation(&mut self, kind: AggregationKind) -> AnyhowResult<Aggregation> {
+        self.lexer
+            .eat_exact(Token::Keyword(kind.name().to_string()))?;
+        self.lexer.eat_exact(Token::Delimiter('('))?;
+        let field = self.lexer.eat_id()?;
+        self.lexer.eat_exact(Token::Delimiter(')'))?;
+        Ok(Aggregation::new(kind, &field))
+    }
+    /// select 句の値を読み進める。= で比較している場合は、比較の結果を真偽値とする式を返す
+    fn parse_select_expression(&mut self) -> AnyhowResult<Expression> {
+        let mut terms = if self.lexer.is_matched(Token::Delimiter('(')) {
+            self.parse_terms()?
+        } else {
+            let lhs = self.parse_expression()?;
+            if !self.lexer.is_matched(Token::Delimiter('=')) && self.comparison_operator().is_none()
+            {
+                return Ok(lhs);
+            }
+            vec![self.parse_term_with_lhs(lhs)?]
+        };
+        while self.lexer.is_matched(Token::Keyword("and".to_string())) {
+            self.lexer.eat_exact(Token::Keyword("and".to_string()))?;
+            terms.extend(self.parse_terms()?);
+        }
+        Ok(Expression::Predicate(ProductPredicate::new(terms)))
+    }
+    /// 現在の token が等号以外の比較演算子であれば、その演算子を返す
+    fn comparison_operator(&self) -> Option<ComparisonOperator> {
+        match self.lexer.get_token() {
+            Token::Delimiter('<') => Some(ComparisonOperator::Less),
+            Token::Delimiter('>') => Some(ComparisonOperator::Greater),
+            Token::Operator(op) => match op.as_str() {
+                "<=" => Some(ComparisonOperator::LessEqual),
+                ">=" => Some(ComparisonOperator::GreaterEqual),
+                "<>" | "!=" => Some(ComparisonOperator::NotEqual),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    /// 読み終えた左辺に続く、比較演算子と右辺を読み進める
+    fn parse_term_with_lhs(&mut self, lhs: Expression) -> AnyhowResult<Term> {
+        if self.lexer.is_matched(Token::Delimiter('=')) {
+            self.lexer.eat_exact(Token::Delimiter('='))?;
+            let rhs = self.parse_expression()?;
+            return Ok(Term::Equal(EqualTerm::new(lhs, rhs)));
+        }
+        let op = self.comparison_operator().ok_or_else(|| {
+            ParserError::UnexpectedToken(format!(
+                "expected comparison operator, but got {:?}",
+                self.lexer.get_token()
+            ))
+        })?;
+        let token = self.lexer.get_token().clone();
+        self.lexer.eat_exact(token)?;
+        let rhs = self.parse_expression()?;
+        Ok(Term::Comparison(ComparisonTerm::new(lhs, op, rhs)))
+    }
+    /// case when <predicate> then <expression> ... else <expression> end を読み進める
+    fn parse_case_expression(&mut self) -> AnyhowResult<CaseExpression> {
+        self.lexer.eat_exact(Token::Keyword("case".to_string()))?;
+        let mut branches = vec![];
+        loop {
+            self.lexer.eat_exact(Token::Keyword("when".to_string()))?;
+            let predicate = self.parse_predicate()?;
+            self.lexer.eat_exact(Token::Keyword("then".to_string()))?;
+            branches.push((predicate, self.parse_expression()?));
+            if !self.lexer.is_matched(Token::Keyword("when".to_string())) {
+                break;
+            }
+        }
+        self.lexer.eat_exact(Token::Keyword("else".to_string()))?;
+        let otherwise = self.parse_expression()?;
+        self.lexer.eat_exact(Token::Keyword("end".to_string()))?;
+        Ok(CaseExpression::new(branches, otherwise))
+    }
+    /// (expression, ...) の形の行値を読み進める
+    fn parse_row_value(&mut self) -> AnyhowResult<Vec<Expression>> {
+        self.lexer.eat_exact(Token::Delimiter('('))?;
+        let mut expressions = vec![self.parse_expression()?];
+        while self.lexer.is_matched(Token::Delimiter(',')) {
+            self.lexer.eat_exact(Token::Delimiter(','))?;
+            expressions.push(self.parse_expression()?);
+        }
+        self.lexer.eat_exact(Token::Delimiter(')'))?;
+        Ok(expressions)
+    }
     fn parse_id_list(&mut self) -> AnyhowResult<Vec<String>> {
         let mut fields = vec![self.lexer.eat_id()?];
         while self.lexer.is_matched(Token::Delimiter(',')) {
@@ -228,6 +641,27 @@ impl ParserImpl {
         }
         Ok(fields)
     }
+    /// order by の後の、field 名と並べ替える向きのリストを読み進める。向きを省略した場合は昇順とする
+    fn parse_order_by_list(&mut self) -> AnyhowResult<Vec<(String, SortDirection)>> {
+        let mut order_by = vec![];
+        loop {
+            let field = self.lexer.eat_id()?;
+            let direction = if self.lexer.is_matched(Token::Keyword("desc".to_string())) {
+                self.lexer.eat_exact(Token::Keyword("desc".to_string()))?;
+                SortDirection::Desc
+            } else {
+                if self.lexer.is_matched(Token::Keyword("asc".to_string())) {
+                    self.lexer.eat_exact(Token::Keyword("asc".to_string()))?;
+                }
+                SortDirection::Asc
+            };
+            order_by.push((field, direction));
+            if !self.lexer.is_matched(Token::Delimiter(',')) {
+                return Ok(order_by);
+            }
+            self.lexer.eat_exact(Token::Delimiter(','))?;
+        }
+    }
     fn parse_constant_list(&mut self) -> AnyhowResult<Vec<Constant>> {
         let mut values = vec![self.parse_constant()?];
         while self.lexer.is_matched(Token::Delimiter(',')) {
@@ -236,13 +670,13 @@ impl ParserImpl {
         }
         Ok(values)
     }
-    fn parse_field_definition(&mut self) -> AnyhowResult<Schema> {
-        let field_name = self.lexer.eat_id()?;
+    /// field の定義を parse する。default 句がある場合はその式も返す
+    fn parse_field_definition(&mut self) -> AnyhowResult<(Schema, Option<Expression>)> {
+        let field_name = self.eat_identifier(IdentifierKind::Field)?;
         let mut schema = Schema::new();
         if self.lexer.is_matched(Token::Keyword("int".to_string())) {
             self.lexer.eat_exact(Token::Keyword("int".to_string()))?;
             schema.add_field(&field_name, FieldInfo::Integer);
-            Ok(schema)
         } else if self.lexer.is_matched(Token::Keyword("varchar".to_string())) {
             self.lexer
                 .eat_exact(Token::Keyword("varchar".to_string()))?;
@@ -250,20 +684,50 @@ impl ParserImpl {
             let strlen = self.lexer.eat_int_constant()?;
             self.lexer.eat_exact(Token::Delimiter(')'))?;
             schema.add_field(&field_name, FieldInfo::String(strlen as usize));
-            Ok(schema)
+        } else if self.lexer.is_matched(Token::Keyword("bigint".to_string())) {
+            self.lexer.eat_exact(Token::Keyword("bigint".to_string()))?;
+            schema.add_field(&field_name, FieldInfo::BigInt);
+        } else if self.lexer.is_matched(Token::Keyword("boolean".to_string())) {
+            self.lexer
+                .eat_exact(Token::Keyword("boolean".to_string()))?;
+            schema.add_field(&field_name, FieldInfo::Boolean);
+        } else if self.lexer.is_matched(Token::Keyword("date".to_string())) {
+            self.lexer.eat_exact(Token::Keyword("date".to_string()))?;
+            schema.add_field(&field_name, FieldInfo::Date);
+        } else if self.lexer.is_matched(Token::Keyword("double".to_string())) {
+            self.lexer.eat_exact(Token::Keyword("double".to_string()))?;
+            schema.add_field(&field_name, FieldInfo::Double);
         } else {
-            Err(anyhow!(ParserError::UnexpectedToken(
-                "expected field type (int, varchar)".to_string()
-            )))
+            return Err(anyhow!(ParserError::UnexpectedToken(
+                "expected field type (int, varchar, bigint, boolean, date, double)".to_string()
+            )));
+        }
+        if self.lexer.is_matched(Token::Keyword("default".to_string())) {
+            self.lexer
+                .eat_exact(Token::Keyword("default".to_string()))?;
+            Ok((schema, Some(self.parse_expression()?)))
+        } else {
+            Ok((schema, None))
         }
     }
-    fn parse_field_definitions(&mut self) -> AnyhowResult<Schema> {
-        let mut schema = self.parse_field_definition()?;
-        if self.lexer.is_matched(Token::Delimiter(',')) {
+    /// field の定義の列を parse する。default 句は field 名 -> 式 のマップとして返す
+    fn parse_field_definitions(&mut self) -> AnyhowResult<(Schema, HashMap<String, Expression>)> {
+        let mut schema = Schema::new();
+        let mut defaults = HashMap::new();
+        loop {
+            let (field_schema, default) = self.parse_field_definition()?;
+            if let Some(default) = default {
+                for field in field_schema.fields_iter() {
+                    defaults.insert(field.to_string(), default.clone());
+                }
+            }
+            schema.add_all(&field_schema)?;
+            if !self.lexer.is_matched(Token::Delimiter(',')) {
+                break;
+            }
             self.lexer.eat_exact(Token::Delimiter(','))?;
-            schema.add_all(&self.parse_field_definitions()?)?;
         }
-        Ok(schema)
+        Ok((schema, defaults))
     }
     fn _parse_create_table(
         &mut self,
@@ -272,19 +736,110 @@ impl ParserImpl {
         if !is_create_token_eaten {
             self.lexer.eat_exact(Token::Keyword("create".to_string()))?;
         }
+        let is_temp = self.lexer.is_matched(Token::Keyword("temp".to_string()));
+        if is_temp {
+            self.lexer.eat_exact(Token::Keyword("temp".to_string()))?;
+        }
+        let is_external = !is_temp
+            && self
+                .lexer
+                .is_matched(Token::Keyword("external".to_string()));
+        if is_external {
+            self.lexer
+                .eat_exact(Token::Keyword("external".to_string()))?;
+        }
         self.lexer.eat_exact(Token::Keyword("table".to_string()))?;
-        let table = self.lexer.eat_id()?;
+        let if_not_exists = self.lexer.is_matched(Token::Keyword("if".to_string()));
+        if if_not_exists {
+            self.lexer.eat_exact(Token::Keyword("if".to_string()))?;
+            self.lexer.eat_exact(Token::Keyword("not".to_string()))?;
+            self.lexer.eat_exact(Token::Keyword("exists".to_string()))?;
+        }
+        let table = self.eat_identifier(IdentifierKind::Table)?;
         self.lexer.eat_exact(Token::Delimiter('('))?;
-        let schema = self.parse_field_definitions()?;
+        let (schema, defaults) = self.parse_field_definitions()?;
         self.lexer.eat_exact(Token::Delimiter(')'))?;
-        Ok(CreateTableData::new(table, schema))
+        // external table の場合は location 'path' でファイルを指定する
+        let external_path = if is_external {
+            self.lexer
+                .eat_exact(Token::Keyword("location".to_string()))?;
+            Some(self.lexer.eat_string_constant()?)
+        } else {
+            None
+        };
+        // 通常の table の場合は blocksize n で table の block size を、compressed で page の圧縮を指定できる
+        let mut storage = StorageOptions::default();
+        if !is_temp && !is_external {
+            if self
+                .lexer
+                .is_matched(Token::Keyword("blocksize".to_string()))
+            {
+                self.lexer
+                    .eat_exact(Token::Keyword("blocksize".to_string()))?;
+                storage.block_size = Some(self.lexer.eat_int_constant()? as usize);
+            }
+            if self
+                .lexer
+                .is_matched(Token::Keyword("compressed".to_string()))
+            {
+                self.lexer
+                    .eat_exact(Token::Keyword("compressed".to_string()))?;
+                storage.compressed = true;
+            }
+        }
+        let mut data =
+            CreateTableData::new(table, schema, is_temp, defaults, external_path, storage);
+        if !is_temp
+            && !is_external
+            && self
+                .lexer
+                .is_matched(Token::Keyword("partition".to_string()))
+        {
+            data = data.with_partition(self.parse_partition_spec()?);
+        }
+        Ok(if if_not_exists {
+            data.with_if_not_exists()
+        } else {
+            data
+        })
+    }
+    /// partition by hash(field) partitions n, または partition by range(field) values (b1, b2, ...) を parse する
+    fn parse_partition_spec(&mut self) -> AnyhowResult<PartitionSpec> {
+        self.lexer
+            .eat_exact(Token::Keyword("partition".to_string()))?;
+        self.lexer.eat_exact(Token::Keyword("by".to_string()))?;
+        let is_hash = self.lexer.is_matched(Token::Keyword("hash".to_string()));
+        if is_hash {
+            self.lexer.eat_exact(Token::Keyword("hash".to_string()))?;
+        } else {
+            self.lexer.eat_exact(Token::Keyword("range".to_string()))?;
+        }
+        self.lexer.eat_exact(Token::Delimiter('('))?;
+        let field = self.eat_identifier(IdentifierKind::Field)?;
+        self.lexer.eat_exact(Token::Delimiter(')'))?;
+        let scheme = if is_hash {
+            self.lexer
+                .eat_exact(Token::Keyword("partitions".to_string()))?;
+            PartitionScheme::Hash(self.lexer.eat_int_constant()?.max(0) as usize)
+        } else {
+            self.lexer.eat_exact(Token::Keyword("values".to_string()))?;
+            self.lexer.eat_exact(Token::Delimiter('('))?;
+            let mut bounds = vec![self.lexer.eat_int_constant()?];
+            while self.lexer.is_matched(Token::Delimiter(',')) {
+                self.lexer.eat_exact(Token::Delimiter(','))?;
+                bounds.push(self.lexer.eat_int_constant()?);
+            }
+            self.lexer.eat_exact(Token::Delimiter(')'))?;
+            PartitionScheme::Range(bounds)
+        };
+        Ok(PartitionSpec::new(&field, scheme)?)
     }
     fn _parse_create_view(&mut self, is_create_token_eaten: bool) -> AnyhowResult<CreateViewData> {
         if !is_create_token_eaten {
             self.lexer.eat_exact(Token::Keyword("create".to_string()))?;
         }
         self.lexer.eat_exact(Token::Keyword("view".to_string()))?;
-        let view_name = self.lexer.eat_id()?;
+        let view_name = self.eat_identifier(IdentifierKind::View)?;
         self.lexer.eat_exact(Token::Keyword("as".to_string()))?;
         let query = self.parse_query()?;
         Ok(CreateViewData::new(view_name, query))
@@ -296,20 +851,52 @@ impl ParserImpl {
         if !is_create_token_eaten {
             self.lexer.eat_exact(Token::Keyword("create".to_string()))?;
         }
+        let unique = self.lexer.is_matched(Token::Keyword("unique".to_string()));
+        if unique {
+            self.lexer.eat_exact(Token::Keyword("unique".to_string()))?;
+        }
         self.lexer.eat_exact(Token::Keyword("index".to_string()))?;
-        let index_name = self.lexer.eat_id()?;
+        let index_name = self.eat_identifier(IdentifierKind::Index)?;
         self.lexer.eat_exact(Token::Keyword("on".to_string()))?;
         let table_name = self.lexer.eat_id()?;
         self.lexer.eat_exact(Token::Delimiter('('))?;
         let field_name = self.lexer.eat_id()?;
         self.lexer.eat_exact(Token::Delimiter(')'))?;
-        Ok(CreateIndexData::new(index_name, table_name, field_name))
+        // using hash または using btree で index の種類を指定できる
+        let index_type = if self.lexer.is_matched(Token::Keyword("using".to_string())) {
+            self.lexer.eat_exact(Token::Keyword("using".to_string()))?;
+            if self.lexer.is_matched(Token::Keyword("hash".to_string())) {
+                self.lexer.eat_exact(Token::Keyword("hash".to_string()))?;
+                IndexType::Hash
+            } else {
+                self.lexer.eat_exact(Token::Keyword("btree".to_string()))?;
+                IndexType::BTree
+            }
+        } else {
+            IndexType::default()
+        };
+        // include (a, b) で、index の record に一緒に保存する field を指定できる
+        let included_fields = if self.lexer.is_matched(Token::Keyword("include".to_string())) {
+            self.lexer
+                .eat_exact(Token::Keyword("include".to_string()))?;
+            self.lexer.eat_exact(Token::Delimiter('('))?;
+            let fields = self.parse_id_list()?;
+            self.lexer.eat_exact(Token::Delimiter(')'))?;
+            fields
+        } else {
+            vec![]
+        };
+        Ok(CreateIndexData::new(index_name, table_name, field_name)
+            .with_unique(unique)
+            .with_index_type(index_type)
+            .with_included_fields(included_fields))
     }
 }
 
 #[cfg(test)]
 mod parser_test {
     use super::*;
+    use crate::metadata::identifier::IdentifierError;
     #[test]
     fn test_select_sentence() {
         let query = "select a from x, z where b = 3 and c = 'string'";
@@ -322,6 +909,210 @@ mod parser_test {
         );
         let predicate = query_data.get_predicate();
         assert_eq!(predicate.to_string(), "b = 3 and c = 'string'");
+        assert_eq!(query_data.get_as_of_lsn(), None);
+    }
+    #[test]
+    fn test_select_expressions() {
+        let query =
+            "select a, a = 1 as is_one, b = c and d = true, true, e as f from x where g = false";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let query_data = parser.parse_query().unwrap();
+        assert_eq!(
+            query_data.get_fields(),
+            &vec!["a", "is_one", "b = c and d = true", "true", "f"]
+        );
+        assert_eq!(query_data.get_expressions().len(), 4);
+        assert_eq!(
+            query_data.get_expression("true"),
+            Some(&Expression::Constant(Constant::Bool(true)))
+        );
+        assert_eq!(
+            query_data.get_expression("f"),
+            Some(&Expression::Field("e".to_string()))
+        );
+        assert_eq!(
+            query_data.referenced_fields(),
+            vec!["a", "a", "b", "c", "d", "e"]
+        );
+        assert_eq!(query_data.get_predicate().to_string(), "g = false");
+        // 式は view の定義として保存できるように、parse できる形で表示する
+        assert_eq!(query_data.to_string(), query);
+
+        let renamed = query_data.rename_field("x", "b", "bb", |_| false).unwrap();
+        assert_eq!(
+            renamed.to_string(),
+            "select a, a = 1 as is_one, bb = c and d = true, true, e as f from x where g = false"
+        );
+    }
+    #[test]
+    fn test_select_group_by() {
+        let query =
+            "select majorid, count(sid), max(gradyear) as latest from student where sid = 1 group by majorid";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let query_data = parser.parse_query().unwrap();
+        assert_eq!(
+            query_data.get_fields(),
+            &vec!["majorid", "count(sid)", "latest"]
+        );
+        assert!(query_data.get_expressions().is_empty());
+        assert_eq!(
+            query_data.get_aggregation("latest"),
+            Some(&Aggregation::new(AggregationKind::Max, "gradyear"))
+        );
+        assert_eq!(query_data.get_group_fields(), &["majorid".to_string()]);
+        assert!(query_data.is_grouped());
+        assert_eq!(
+            query_data.referenced_fields(),
+            vec!["majorid", "sid", "gradyear", "majorid"]
+        );
+        assert_eq!(query_data.to_string(), query);
+
+        let renamed = query_data
+            .rename_field("student", "sid", "id", |_| false)
+            .unwrap();
+        assert_eq!(
+            renamed.to_string(),
+            "select majorid, count(id), max(gradyear) as latest from student where id = 1 group by majorid"
+        );
+
+        // group by が無くても集約関数は使える
+        let query = "select sum(a), avg(a), min(b) from x";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let query_data = parser.parse_query().unwrap();
+        assert_eq!(query_data.get_aggregations().len(), 3);
+        assert!(query_data.get_group_fields().is_empty());
+
+        let query = "select count(a + 1) from x";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_query().is_err());
+    }
+    #[test]
+    fn test_select_order_by() {
+        let query = "select sname, gradyear from student where majorid = 10 order by gradyear desc, sname as of lsn 3";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let query_data = parser.parse_query().unwrap();
+        assert_eq!(
+            query_data.get_order_by(),
+            &[
+                ("gradyear".to_string(), SortDirection::Desc),
+                ("sname".to_string(), SortDirection::Asc),
+            ]
+        );
+        assert_eq!(query_data.get_as_of_lsn(), Some(3));
+        // 向きを省略した field も asc をつけて表示する
+        assert_eq!(
+            query_data.to_string(),
+            "select sname, gradyear from student where majorid = 10 order by gradyear desc, sname asc as of lsn 3"
+        );
+        let renamed = query_data
+            .rename_field("student", "gradyear", "year", |_| false)
+            .unwrap();
+        assert_eq!(
+            renamed.get_order_by()[0],
+            ("year".to_string(), SortDirection::Desc)
+        );
+
+        // group by の後に書く。集約関数の値は名前で指定する
+        let query = "select majorid, count(sid) as n from student group by majorid order by n desc";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let query_data = parser.parse_query().unwrap();
+        assert_eq!(
+            query_data.get_order_by(),
+            &[("n".to_string(), SortDirection::Desc)]
+        );
+
+        let query = "select a from x order by";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_query().is_err());
+    }
+    #[test]
+    fn test_select_distinct() {
+        let query = "select distinct majorid, gradyear from student order by majorid";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let query_data = parser.parse_query().unwrap();
+        assert!(query_data.is_distinct());
+        assert_eq!(
+            query_data.get_fields(),
+            &vec!["majorid".to_string(), "gradyear".to_string()]
+        );
+        assert_eq!(
+            query_data.to_string(),
+            "select distinct majorid, gradyear from student order by majorid asc"
+        );
+        assert!(query_data
+            .rename_table("student", "pupil")
+            .unwrap()
+            .is_distinct());
+
+        let query = "select majorid from student";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(!parser.parse_query().unwrap().is_distinct());
+
+        // distinct は予約語なので field 名には使えない
+        let query = "select distinct from student";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_query().is_err());
+    }
+    #[test]
+    fn test_row_value_predicate() {
+        let query = "select a from x where (a, b) = (1, 'x') and c = d";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let query_data = parser.parse_query().unwrap();
+        let predicate = query_data.get_predicate();
+        assert_eq!(predicate.to_string(), "a = 1 and b = 'x' and c = d");
+        assert_eq!(
+            predicate.equates_with_constant("b"),
+            Some(Constant::String("x".to_string()))
+        );
+
+        let query = "delete from x where (a, b) = (1)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_delete().is_err());
+    }
+    #[test]
+    fn test_comparison_predicate() {
+        let query = "select a, b < 10 as small from x where a >= 1 and b <> 'x' and 3 < c and d != e and f<=g";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let query_data = parser.parse_query().unwrap();
+        let predicate = query_data.get_predicate();
+        // != は <> として表示する
+        assert_eq!(
+            predicate.to_string(),
+            "a >= 1 and b <> 'x' and 3 < c and d <> e and f <= g"
+        );
+        assert_eq!(
+            predicate.terms()[2],
+            Term::Comparison(ComparisonTerm::new(
+                Expression::Constant(Constant::Int(3)),
+                ComparisonOperator::Less,
+                Expression::Field("c".to_string()),
+            ))
+        );
+        // 等号以外の条件は、constant と等しい条件として扱わない
+        assert_eq!(predicate.equates_with_constant("a"), None);
+        assert_eq!(
+            query_data.get_expression("small").unwrap().to_string(),
+            "b < 10"
+        );
+
+        let query = "select a from x where a > ";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_query().is_err());
+        let query = "select a from x where a !";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_query().is_err());
+    }
+    #[test]
+    fn test_select_sentence_as_of_lsn() {
+        let query = "select a from x where b = 3 as of lsn 12";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let query_data = parser.parse_query().unwrap();
+        assert_eq!(query_data.get_as_of_lsn(), Some(12));
+        assert_eq!(query_data.to_string(), query);
+
+        let query = "select a from x as of lsn -1";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_query().is_err());
     }
     #[test]
     fn test_insert_sentence() {
@@ -339,6 +1130,18 @@ mod parser_test {
         );
     }
     #[test]
+    fn test_insert_sentence_without_field_list() {
+        let query = "insert into x values (3, 'string')";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let insert_data = parser.parse_insert().unwrap();
+        assert_eq!(insert_data.get_table(), "x");
+        assert!(insert_data.get_fields().is_empty());
+        assert_eq!(
+            insert_data.get_values(),
+            &vec![Constant::Int(3), Constant::String("string".to_string())]
+        );
+    }
+    #[test]
     fn test_delete_sentence_with_where_phrase() {
         let query = "delete from x where a = 3 and b = 'string'";
         let mut parser = ParserImpl::new(query.to_string()).unwrap();
@@ -385,6 +1188,27 @@ mod parser_test {
         assert_eq!(predicate.to_string(), "");
     }
     #[test]
+    fn test_update_sentence_with_case_expression() {
+        let query =
+            "update x set a = case when b = 1 and c = d then 'one' when b = 2 then e else 'other' end where f = 3";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let update_data = parser.parse_update().unwrap();
+        assert_eq!(
+            update_data.get_new_value().to_string(),
+            "case when b = 1 and c = d then 'one' when b = 2 then e else 'other' end"
+        );
+        assert_eq!(
+            update_data.get_new_value().fields(),
+            vec!["b", "c", "d", "b", "e"]
+        );
+        assert_eq!(update_data.get_predicate().to_string(), "f = 3");
+
+        // else 句は省略できない
+        let query = "update x set a = case when b = 1 then 'one' end";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_update().is_err());
+    }
+    #[test]
     fn test_create_table() {
         let query = "create table x (a int, b varchar(10))";
         let mut parser = ParserImpl::new(query.to_string()).unwrap();
@@ -393,6 +1217,222 @@ mod parser_test {
         let schema = create_table_data.get_schema();
         assert_eq!(schema.info("a"), Some(FieldInfo::Integer));
         assert_eq!(schema.info("b"), Some(FieldInfo::String(10)));
+        assert!(!create_table_data.is_temp());
+        assert!(!create_table_data.if_not_exists());
+    }
+    #[test]
+    fn test_create_table_with_additional_types() {
+        let query = "create table x (a bigint, b boolean, c date, d double)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_table_data = parser.parse_create_table().unwrap();
+        let schema = create_table_data.get_schema();
+        assert_eq!(schema.info("a"), Some(FieldInfo::BigInt));
+        assert_eq!(schema.info("b"), Some(FieldInfo::Boolean));
+        assert_eq!(schema.info("c"), Some(FieldInfo::Date));
+        assert_eq!(schema.info("d"), Some(FieldInfo::Double));
+    }
+    #[test]
+    fn test_double_literals() {
+        let query = "insert into x values (1.5, 0.125, 3)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let insert_data = parser.parse_insert().unwrap();
+        assert_eq!(
+            insert_data.get_values(),
+            &vec![
+                Constant::Double(1.5),
+                Constant::Double(0.125),
+                Constant::Int(3)
+            ]
+        );
+
+        // 整数の値の double も、表示した形のまま double として読み直せる
+        let query = "select a from x where d >= 2.0";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let query_data = parser.parse_query().unwrap();
+        assert_eq!(query_data.get_predicate().to_string(), "d >= 2.0");
+    }
+    #[test]
+    fn test_bigint_and_date_literals() {
+        // int に収まらない数値は bigint の値になる
+        let query = "insert into x values (3, 3000000000, date '2024-02-29')";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let insert_data = parser.parse_insert().unwrap();
+        assert_eq!(
+            insert_data.get_values(),
+            &vec![
+                Constant::Int(3),
+                Constant::BigInt(3000000000),
+                Constant::Date(Date::parse("2024-02-29").unwrap())
+            ]
+        );
+
+        // date literal は表示した形のまま読み直せる
+        let query = "select a from x where d = date '2024-01-31'";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let query_data = parser.parse_query().unwrap();
+        assert_eq!(
+            query_data.get_predicate().to_string(),
+            "d = date '2024-01-31'"
+        );
+
+        let query = "insert into x values (date '2024-02-30')";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_insert().is_err());
+    }
+    #[test]
+    fn test_create_table_if_not_exists() {
+        let query = "create table if not exists x (a int)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_table_data = parser.parse_create_table().unwrap();
+        assert_eq!(create_table_data.get_table(), "x");
+        assert!(create_table_data.if_not_exists());
+
+        let query = "create temp table if not exists x (a int)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_table_data = parser.parse_create_table().unwrap();
+        assert!(create_table_data.is_temp());
+        assert!(create_table_data.if_not_exists());
+
+        // if だけでは table 名として読めない
+        let query = "create table if x (a int)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_create_table().is_err());
+    }
+    #[test]
+    fn test_create_table_with_invalid_name() {
+        // _ を含む名前も 1 つの識別子として読む
+        let query = "create table student_2024 (major_id int)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_table_data = parser.parse_create_table().unwrap();
+        assert_eq!(create_table_data.get_table(), "student_2024");
+        assert_eq!(create_table_data.get_schema().fields(), vec!["major_id"]);
+
+        // catalog に収まらない長さの名前は error になる
+        let query = format!("create table {} (a int)", "t".repeat(33));
+        let mut parser = ParserImpl::new(query).unwrap();
+        let err = parser.parse_create_table().err().unwrap();
+        assert!(err.downcast_ref::<IdentifierError>().is_some());
+        let query = format!("create table t ({} int)", "f".repeat(33));
+        let mut parser = ParserImpl::new(query).unwrap();
+        assert!(parser.parse_create_table().is_err());
+        let query = format!("create view {} as select a from t", "v".repeat(33));
+        let mut parser = ParserImpl::new(query).unwrap();
+        assert!(parser.parse_create_view().is_err());
+        let query = format!("create index {} on t (a)", "i".repeat(33));
+        let mut parser = ParserImpl::new(query).unwrap();
+        assert!(parser.parse_create_index().is_err());
+
+        // 予約語と同じ名前は、代わりの名前の候補と一緒に error になる
+        let query = "create table student (tables int)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let err = parser.parse_create_table().err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "field name tables conflicts with reserved word of show / describe (did you mean tables_ or field_tables?)"
+        );
+        let query = "create view select as select a from t";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let err = parser.parse_create_view().err().unwrap();
+        assert!(matches!(
+            err.downcast_ref::<IdentifierError>(),
+            Some(IdentifierError::ReservedWord { .. })
+        ));
+    }
+    #[test]
+    fn test_create_temp_table() {
+        let query = "create temp table x (a int)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_table_data = parser.parse_create_table().unwrap();
+        assert_eq!(create_table_data.get_table(), "x");
+        assert_eq!(
+            create_table_data.get_schema().info("a"),
+            Some(FieldInfo::Integer)
+        );
+        assert!(create_table_data.is_temp());
+    }
+    #[test]
+    fn test_create_table_with_block_size() {
+        let query = "create table x (a int, b varchar(100)) blocksize 1000";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_table_data = parser.parse_create_table().unwrap();
+        assert_eq!(create_table_data.get_table(), "x");
+        assert_eq!(create_table_data.get_storage().block_size, Some(1000));
+        assert!(!create_table_data.get_storage().compressed);
+
+        let query = "create table x (a int) blocksize 1000 compressed";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_table_data = parser.parse_create_table().unwrap();
+        assert_eq!(
+            create_table_data.get_storage(),
+            StorageOptions {
+                block_size: Some(1000),
+                compressed: true
+            }
+        );
+
+        let query = "create table x (a int)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_table_data = parser.parse_create_table().unwrap();
+        assert_eq!(create_table_data.get_storage(), StorageOptions::default());
+    }
+    #[test]
+    fn test_create_partitioned_table() {
+        let query = "create table x (a int, b varchar(10)) blocksize 1000 partition by hash(a) partitions 4";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_table_data = parser.parse_create_table().unwrap();
+        assert_eq!(create_table_data.get_storage().block_size, Some(1000));
+        let partition = create_table_data.get_partition().unwrap();
+        assert_eq!(partition.field(), "a");
+        assert_eq!(partition.scheme(), &PartitionScheme::Hash(4));
+
+        let query = "create table x (a int) partition by range(a) values (10, 20)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_table_data = parser.parse_create_table().unwrap();
+        assert_eq!(
+            create_table_data.get_partition().unwrap().scheme(),
+            &PartitionScheme::Range(vec![10, 20])
+        );
+
+        // 境界が昇順でない range partition は作れない
+        let query = "create table x (a int) partition by range(a) values (20, 10)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_create_table().is_err());
+    }
+    #[test]
+    fn test_create_external_table() {
+        let query = "create external table x (a int, b varchar(10)) location '/data/x.csv'";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_table_data = parser.parse_create_table().unwrap();
+        assert_eq!(create_table_data.get_table(), "x");
+        assert_eq!(create_table_data.get_schema().fields(), vec!["a", "b"]);
+        assert!(!create_table_data.is_temp());
+        assert_eq!(
+            create_table_data.get_external_path(),
+            Some(&"/data/x.csv".to_string())
+        );
+
+        // location がない場合はエラー
+        let query = "create external table x (a int)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_create_table().is_err());
+    }
+    #[test]
+    fn test_create_table_with_default() {
+        let query = "create table x (a int default 0, b varchar(10), c varchar(5) default 'none')";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_table_data = parser.parse_create_table().unwrap();
+        let schema = create_table_data.get_schema();
+        assert_eq!(schema.fields(), vec!["a", "b", "c"]);
+        let defaults = create_table_data.get_defaults();
+        assert_eq!(defaults.len(), 2);
+        assert_eq!(
+            defaults.get("a"),
+            Some(&Expression::Constant(Constant::Int(0)))
+        );
+        assert_eq!(
+            defaults.get("c"),
+            Some(&Expression::Constant(Constant::String("none".to_string())))
+        );
     }
     #[test]
     fn test_create_view() {
@@ -414,6 +1454,107 @@ mod parser_test {
         assert_eq!(create_index_data.index_name(), "x");
         assert_eq!(create_index_data.table_name(), "y");
         assert_eq!(create_index_data.field_name(), "a");
+        assert_eq!(create_index_data.index_type(), IndexType::BTree);
+
+        let query = "create index x on y (a) using hash";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_index_data = parser.parse_create_index().unwrap();
+        assert_eq!(create_index_data.index_type(), IndexType::Hash);
+
+        let query = "create index x on y (a) using btree";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_index_data = parser.parse_create_index().unwrap();
+        assert_eq!(create_index_data.index_type(), IndexType::BTree);
+
+        let query = "create index x on y (a) using bitmap";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_create_index().is_err());
+
+        let query = "create index x on y (a) using hash include (b, c)";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let create_index_data = parser.parse_create_index().unwrap();
+        assert_eq!(create_index_data.index_type(), IndexType::Hash);
+        assert_eq!(create_index_data.included_fields(), &["b", "c"]);
+
+        let query = "create index x on y (a) include ()";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_create_index().is_err());
+    }
+    #[test]
+    fn test_rename_column() {
+        let query = "alter table x rename column a to b";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let rename_column_data = parser.parse_rename_column().unwrap();
+        assert_eq!(rename_column_data.get_table(), "x");
+        assert_eq!(rename_column_data.get_old_field(), "a");
+        assert_eq!(rename_column_data.get_new_field(), "b");
+    }
+    #[test]
+    fn test_alter() {
+        let query = "alter table x rename to y";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        match parser.parse_alter().unwrap() {
+            UpdateCommand::RenameTable(data) => {
+                assert_eq!(data.get_table(), "x");
+                assert_eq!(data.get_new_table(), "y");
+            }
+            _ => panic!("expected rename table"),
+        }
+
+        let query = "alter index i rename to j";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        match parser.parse_alter().unwrap() {
+            UpdateCommand::RenameIndex(data) => {
+                assert_eq!(data.get_index(), "i");
+                assert_eq!(data.get_new_index(), "j");
+            }
+            _ => panic!("expected rename index"),
+        }
+
+        let query = "alter table x rename column a to b";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(matches!(
+            parser.parse_alter().unwrap(),
+            UpdateCommand::RenameColumn(_)
+        ));
+
+        // 予約語には変更できない
+        let query = "alter table x rename to select";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_alter().is_err());
+    }
+    #[test]
+    fn test_cursor_command() {
+        let query = "declare cursor c for select a from x where b = 3";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        match parser.parse_cursor_command().unwrap() {
+            CursorCommand::Declare(data) => {
+                assert_eq!(data.get_cursor(), "c");
+                assert_eq!(data.get_query().get_fields(), &vec!["a".to_string()]);
+            }
+            _ => panic!("expected declare"),
+        }
+
+        let query = "fetch 10 from c";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        match parser.parse_cursor_command().unwrap() {
+            CursorCommand::Fetch(data) => {
+                assert_eq!(data.get_cursor(), "c");
+                assert_eq!(data.get_count(), 10);
+            }
+            _ => panic!("expected fetch"),
+        }
+
+        let query = "close c";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(matches!(
+            parser.parse_cursor_command().unwrap(),
+            CursorCommand::Close(cursor) if cursor == "c"
+        ));
+
+        let query = "fetch 0 from c";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_cursor_command().is_err());
     }
     #[test]
     fn test_update_command() {
@@ -459,5 +1600,69 @@ mod parser_test {
             let update_command = parser.parse_update_command().unwrap();
             assert!(matches!(update_command, UpdateCommand::CreateIndex(_)));
         }
+        // create unique index
+        {
+            let query = "create unique index x on y (a)";
+            let mut parser = ParserImpl::new(query.to_string()).unwrap();
+            let update_command = parser.parse_update_command().unwrap();
+            let UpdateCommand::CreateIndex(data) = update_command else {
+                panic!("expected create index");
+            };
+            assert!(data.is_unique());
+            assert_eq!(data.field_name(), "a");
+        }
+        // alter table
+        {
+            let query = "alter table x rename column a to b";
+            let mut parser = ParserImpl::new(query.to_string()).unwrap();
+            let update_command = parser.parse_update_command().unwrap();
+            assert!(matches!(update_command, UpdateCommand::RenameColumn(_)));
+        }
+    }
+    #[test]
+    fn test_explain_analyze() {
+        let query = "explain analyze select a from x where b = 3";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let query_data = parser.parse_explain_analyze().unwrap();
+        assert_eq!(query_data.to_string(), "select a from x where b = 3");
+
+        // analyze のない explain は受け付けない
+        let query = "explain select a from x";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        assert!(parser.parse_explain_analyze().is_err());
+    }
+    #[test]
+    fn test_show_command() {
+        let mut parser = ParserImpl::new("show tables".to_string()).unwrap();
+        assert!(matches!(
+            parser.parse_show_command().unwrap(),
+            ShowCommand::Tables
+        ));
+
+        let mut parser = ParserImpl::new("describe student".to_string()).unwrap();
+        match parser.parse_show_command().unwrap() {
+            ShowCommand::Describe(table) => assert_eq!(table, "student"),
+            _ => panic!("expected describe"),
+        }
+
+        let mut parser = ParserImpl::new("show student".to_string()).unwrap();
+        assert!(parser.parse_show_command().is_err());
+    }
+    #[test]
+    fn test_rename_field_in_query() {
+        let query = "select a, b from x, y where a = c and b = 3";
+        let mut parser = ParserImpl::new(query.to_string()).unwrap();
+        let query_data = parser.parse_query().unwrap();
+        let renamed = query_data.rename_field("x", "a", "z", |_| false).unwrap();
+        assert_eq!(
+            renamed.to_string(),
+            "select z, b from x, y where z = c and b = 3"
+        );
+        // y にも a がある場合は、どちらの a を指しているか分からないので書き換えない
+        assert!(query_data
+            .rename_field("x", "a", "z", |table| table == "y")
+            .is_none());
+        // 参照していない table の場合は None
+        assert!(query_data.rename_field("w", "a", "z", |_| false).is_none());
     }
 }
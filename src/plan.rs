@@ -1,10 +1,22 @@
+pub mod aggregation;
+pub mod csv_plan;
+pub mod distinct_plan;
 pub mod expression;
+pub mod extend_plan;
+pub mod group_by_plan;
+pub mod index_join_plan;
+pub mod index_select_plan;
+pub mod instrumented_plan;
+pub mod merge_join_plan;
+pub mod never_plan;
 pub mod plan;
+pub mod plan_snapshot;
 pub mod plannable;
 pub mod predicate;
 pub mod product_plan;
 pub mod project_plan;
 pub mod reduction_factor;
 pub mod select_plan;
+pub mod sort_plan;
 pub mod table_plan;
 pub mod term;
@@ -0,0 +1,106 @@
+use std::fmt;
+
+use crate::{
+    query::aggregation_fn::{AggregationFn, AvgFn, CountFn, MaxFn, MinFn, SumFn},
+    record::schema::{FieldInfo, Schema},
+};
+
+/// 集約関数の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregationKind {
+    Count,
+    Max,
+    Min,
+    Sum,
+    Avg,
+}
+
+impl AggregationKind {
+    /// SQL での関数名から種類を返す。集約関数でない場合は None を返す
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "count" => Some(AggregationKind::Count),
+            "max" => Some(AggregationKind::Max),
+            "min" => Some(AggregationKind::Min),
+            "sum" => Some(AggregationKind::Sum),
+            "avg" => Some(AggregationKind::Avg),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AggregationKind::Count => "count",
+            AggregationKind::Max => "max",
+            AggregationKind::Min => "min",
+            AggregationKind::Sum => "sum",
+            AggregationKind::Avg => "avg",
+        }
+    }
+}
+
+/**
+ * select 句の count(sid) などの集約関数の呼び出しを表す
+ * 実際に値を集計するのは、create_fn で作成する query 以下の AggregationFn
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregation {
+    kind: AggregationKind,
+    field: String,
+}
+
+impl Aggregation {
+    pub fn new(kind: AggregationKind, field: &str) -> Self {
+        Self {
+            kind,
+            field: field.to_string(),
+        }
+    }
+
+    pub fn kind(&self) -> AggregationKind {
+        self.kind
+    }
+
+    /// 集計する field の名前
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    /// schema の record を集計した値の型を返す。集計する field が schema にない場合は None を返す
+    /// sum と avg は int の field しか集計できないが、ここでは検査しない
+    pub fn field_info(&self, schema: &Schema) -> Option<FieldInfo> {
+        let info = schema.info(&self.field)?;
+        match self.kind {
+            AggregationKind::Max | AggregationKind::Min => Some(info),
+            AggregationKind::Count | AggregationKind::Sum | AggregationKind::Avg => {
+                Some(FieldInfo::Integer)
+            }
+        }
+    }
+
+    /// field 名 old_name を new_name に置き換えた集約関数を返す
+    pub fn rename_field(&self, old_name: &str, new_name: &str) -> Self {
+        if self.field == old_name {
+            Self::new(self.kind, new_name)
+        } else {
+            self.clone()
+        }
+    }
+
+    /// 集計した値を name という field で出力する AggregationFn を作成する
+    pub fn create_fn(&self, name: &str) -> Box<dyn AggregationFn> {
+        match self.kind {
+            AggregationKind::Count => Box::new(CountFn::new(name, &self.field)),
+            AggregationKind::Max => Box::new(MaxFn::new(name, &self.field)),
+            AggregationKind::Min => Box::new(MinFn::new(name, &self.field)),
+            AggregationKind::Sum => Box::new(SumFn::new(name, &self.field)),
+            AggregationKind::Avg => Box::new(AvgFn::new(name, &self.field)),
+        }
+    }
+}
+
+impl fmt::Display for Aggregation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}({})", self.kind.name(), self.field)
+    }
+}
@@ -0,0 +1,257 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+use crate::{
+    query::{
+        constant::Constant,
+        csv_scan::CsvScan,
+        memory_budget::MemoryBudget,
+        scan::{ReadScan, UpdateScan},
+    },
+    record::schema::Schema,
+};
+
+use super::plan::{Plan, PlanError};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+
+/**
+ * 外部の CSV / TSV ファイルを table として扱うための plan
+ *
+ * 統計情報は catalog に保存されていないので、plan を作成する時にファイルを一度読んで計算する
+ * 計算の間は field ごとのユニークな値をメモリ上に保持するので、その使用量は query の memory budget に計上する
+ * 計算した統計情報は CsvStatCache に保持し、ファイルが変更されていなければ次の plan の作成でも使う
+ */
+pub struct CsvPlan {
+    table_name: String,
+    path: PathBuf,
+    schema: Schema,
+    num_blocks: u64,
+    num_records: u64,
+    num_distinct_values: HashMap<String, u64>,
+}
+
+/**
+ * 計算済みの external table の統計情報をファイルごとに保持する構造体
+ *
+ * ファイルの変更は log に残らないので、計算した時のファイルの更新時刻と大きさを一緒に覚えておき、
+ * どちらかが変わっていたら計算し直す
+ */
+#[derive(Default)]
+pub struct CsvStatCache {
+    stats: Mutex<HashMap<PathBuf, CsvStat>>,
+}
+
+#[derive(Clone)]
+struct CsvStat {
+    modified: SystemTime,
+    file_size: u64,
+    // 統計情報を計算した時の schema。schema が異なると値の parse の結果も変わるので、一致する場合だけ使う
+    schema: Schema,
+    num_records: u64,
+    num_distinct_values: HashMap<String, u64>,
+}
+
+impl CsvStatCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// path のファイルの統計情報を返す。ファイルが変更されている場合は None を返す
+    fn get(
+        &self,
+        path: &Path,
+        modified: SystemTime,
+        file_size: u64,
+        schema: &Schema,
+    ) -> Option<CsvStat> {
+        self.stats
+            .lock()
+            .unwrap()
+            .get(path)
+            .filter(|stat| {
+                stat.modified == modified && stat.file_size == file_size && stat.schema == *schema
+            })
+            .cloned()
+    }
+
+    fn insert(&self, path: PathBuf, stat: CsvStat) {
+        self.stats.lock().unwrap().insert(path, stat);
+    }
+}
+
+impl Plan for CsvPlan {
+    fn get_block_access_cost(&self) -> AnyhowResult<u64> {
+        Ok(self.num_blocks)
+    }
+    fn get_record_access_cost(&self) -> AnyhowResult<u64> {
+        Ok(self.num_records)
+    }
+    fn get_distinct_value_estimation(&self, field_name: &str) -> AnyhowResult<u64> {
+        Ok(*self.num_distinct_values.get(field_name).ok_or_else(|| {
+            PlanError::InvalidCall(format!(
+                "no distinct value estimation found for field {} in external table {}",
+                field_name, self.table_name
+            ))
+        })?)
+    }
+    fn get_schema(&self) -> &Schema {
+        &self.schema
+    }
+    fn ordering(&self) -> Vec<String> {
+        // ファイルの行の順番については何も仮定しない
+        vec![]
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        vec![]
+    }
+    fn get_value_range(&self, _field_name: &str) -> Option<(Constant, Constant)> {
+        // external table の統計情報は集めていない
+        None
+    }
+    fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>> {
+        Ok(Box::new(CsvScan::new(
+            self.path.clone(),
+            self.schema.clone(),
+        )?))
+    }
+    fn open_update_scan(&self) -> AnyhowResult<Box<dyn UpdateScan>> {
+        Err(anyhow!(PlanError::InvalidCall(format!(
+            "external table {} is read-only",
+            self.table_name
+        ))))
+    }
+}
+
+impl CsvPlan {
+    /// csv plan の初期化
+    /// block_size はファイルを block 単位で読んだとみなした時の block access cost の見積もりに使う
+    /// cache に変更されていないファイルの統計情報があればそれを使い、無ければファイルを読んで計算して cache に保存する
+    pub fn new(
+        table_name: String,
+        path: PathBuf,
+        schema: Schema,
+        block_size: usize,
+        budget: &MemoryBudget,
+        cache: &CsvStatCache,
+    ) -> AnyhowResult<CsvPlan> {
+        let metadata = std::fs::metadata(&path)?;
+        let file_size = metadata.len();
+        let modified = metadata.modified()?;
+        let num_blocks = file_size.div_ceil(block_size as u64);
+
+        let stat = match cache.get(&path, modified, file_size, &schema) {
+            Some(stat) => stat,
+            None => {
+                let (num_records, num_distinct_values) =
+                    Self::compute_stat(&path, &schema, budget)?;
+                let stat = CsvStat {
+                    modified,
+                    file_size,
+                    schema: schema.clone(),
+                    num_records,
+                    num_distinct_values,
+                };
+                cache.insert(path.clone(), stat.clone());
+                stat
+            }
+        };
+        Ok(CsvPlan {
+            table_name,
+            path,
+            schema,
+            num_blocks,
+            num_records: stat.num_records,
+            num_distinct_values: stat.num_distinct_values,
+        })
+    }
+
+    /// ファイルを読んで record の数と field ごとのユニークな値の数を計算する
+    fn compute_stat(
+        path: &Path,
+        schema: &Schema,
+        budget: &MemoryBudget,
+    ) -> AnyhowResult<(u64, HashMap<String, u64>)> {
+        let mut num_records = 0;
+        let mut values: HashMap<String, HashSet<_>> = schema
+            .fields()
+            .into_iter()
+            .map(|field| (field, HashSet::new()))
+            .collect();
+        let mut scan = CsvScan::new(path.to_path_buf(), schema.clone())?;
+        // ユニークな値を保持するために reserve したメモリの量。集計が終わったら (失敗した場合も) 解放する
+        let mut reserved = 0;
+        let result = (|| -> AnyhowResult<()> {
+            while scan.move_next()? {
+                num_records += 1;
+                for (field, set) in values.iter_mut() {
+                    let val = scan.get_val(field)?;
+                    if !set.contains(&val) {
+                        let size = MemoryBudget::constant_size(&val);
+                        budget.reserve(size)?;
+                        reserved += size;
+                        set.insert(val);
+                    }
+                }
+            }
+            Ok(())
+        })();
+        budget.release(reserved);
+        result?;
+        Ok((
+            num_records,
+            values
+                .into_iter()
+                .map(|(field, set)| (field, set.len() as u64))
+                .collect(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod csv_plan_test {
+    use super::*;
+    use crate::record::schema::FieldInfo;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_stat_cache() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("item.csv");
+        std::fs::write(&path, "1,a\n2,b\n2,c\n").unwrap();
+        let mut schema = Schema::new();
+        schema.add_field("id", FieldInfo::Integer);
+        schema.add_field("name", FieldInfo::String(10));
+        let cache = CsvStatCache::new();
+        let create = |budget: &MemoryBudget| {
+            CsvPlan::new(
+                "item".to_string(),
+                path.clone(),
+                schema.clone(),
+                400,
+                budget,
+                &cache,
+            )
+        };
+
+        let plan = create(&MemoryBudget::unlimited()).unwrap();
+        assert_eq!(plan.get_record_access_cost().unwrap(), 3);
+        assert_eq!(plan.get_distinct_value_estimation("id").unwrap(), 2);
+        assert_eq!(plan.get_distinct_value_estimation("name").unwrap(), 3);
+
+        // ファイルが変更されていなければ読み直さないので、memory budget を使わない
+        let plan = create(&MemoryBudget::new(Some(0))).unwrap();
+        assert_eq!(plan.get_record_access_cost().unwrap(), 3);
+
+        // ファイルが変更されたら計算し直す
+        std::fs::write(&path, "1,a\n2,b\n3,c\n4,d\n").unwrap();
+        assert!(create(&MemoryBudget::new(Some(0))).is_err());
+        let plan = create(&MemoryBudget::unlimited()).unwrap();
+        assert_eq!(plan.get_record_access_cost().unwrap(), 4);
+        assert_eq!(plan.get_distinct_value_estimation("id").unwrap(), 4);
+    }
+}
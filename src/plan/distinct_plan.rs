@@ -0,0 +1,209 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use crate::{
+    query::{
+        constant::Constant,
+        group_by_scan::GroupByScan,
+        scan::{ReadScan, UpdateScan},
+    },
+    record::schema::Schema,
+    tx::{temp_file_manager::TempFileManager, transaction::Transaction},
+};
+
+use super::{
+    plan::{Plan, PlanError},
+    sort_plan::SortPlan,
+};
+
+/**
+ * child の record のうち、全ての field の値が等しいものを 1 つにまとめる plan (select distinct)
+ *
+ * 同じ値の record が隣り合うように child を全ての field で並べ替え、集約関数の無い GroupByScan で読む
+ * child が既に全ての field で並んでいる場合は並べ替えない
+ */
+pub struct DistinctPlan {
+    // distinct_fields の順に並んだ child
+    child: Box<dyn Plan>,
+    // child の全ての field。child が並んでいる順に並べる
+    distinct_fields: Vec<String>,
+}
+
+impl Plan for DistinctPlan {
+    fn get_block_access_cost(&self) -> AnyhowResult<u64> {
+        self.child.get_block_access_cost()
+    }
+    fn get_record_access_cost(&self) -> AnyhowResult<u64> {
+        // 重複を除いた record の数は、field ごとの distinct value の積を child の record の数で抑えたもの
+        let records = self.child.get_record_access_cost()?;
+        let mut distinct_records: u64 = 1;
+        for field in &self.distinct_fields {
+            distinct_records =
+                distinct_records.saturating_mul(self.child.get_distinct_value_estimation(field)?);
+        }
+        Ok(distinct_records.min(records))
+    }
+    fn get_distinct_value_estimation(&self, field_name: &str) -> AnyhowResult<u64> {
+        self.child.get_distinct_value_estimation(field_name)
+    }
+    fn get_schema(&self) -> &Schema {
+        self.child.get_schema()
+    }
+    fn ordering(&self) -> Vec<String> {
+        self.distinct_fields.clone()
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        // field が 1 つの場合は、重複を除いたその値も重複しない
+        match self.distinct_fields.as_slice() {
+            [field] => vec![field.clone()],
+            _ => self.child.unique_fields(),
+        }
+    }
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        self.child.get_value_range(field_name)
+    }
+    fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>> {
+        let scan = self.child.open_read_scan()?;
+        Ok(Box::new(GroupByScan::new(
+            scan,
+            self.distinct_fields.clone(),
+            vec![],
+        )?))
+    }
+    fn open_update_scan(&self) -> AnyhowResult<Box<dyn UpdateScan>> {
+        Err(anyhow!(PlanError::InvalidCall(
+            "distinct plan does not support update scan".to_string()
+        )))
+    }
+}
+
+impl DistinctPlan {
+    pub fn new(
+        child: Box<dyn Plan>,
+        temp_file_manager: Arc<TempFileManager>,
+        tx: Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Self> {
+        let fields = child.get_schema().fields();
+        // child の ordering の先頭が全ての field からなる場合は、同じ値の record が既に隣り合っている
+        let ordering = child.ordering();
+        if ordering.len() >= fields.len()
+            && ordering[..fields.len()]
+                .iter()
+                .all(|field| fields.contains(field))
+        {
+            return Ok(Self {
+                child,
+                distinct_fields: ordering[..fields.len()].to_vec(),
+            });
+        }
+        let child = Box::new(SortPlan::new(child, fields.clone(), temp_file_manager, tx)?);
+        Ok(Self {
+            child,
+            distinct_fields: fields,
+        })
+    }
+}
+
+#[cfg(test)]
+mod distinct_plan_test {
+    use super::*;
+    use crate::{
+        buffer::buffer_manager::BufferManager,
+        file::file_manager::FileManager,
+        log::log_manager::LogManager,
+        plan::plan::MockPlan,
+        query::values_scan::ValuesScan,
+        record::schema::FieldInfo,
+        tx::{concurrency::lock_table::LockTable, transaction::TransactionFactory},
+    };
+    use tempfile::tempdir;
+
+    fn values_plan(rows: Vec<(i32, &'static str)>, ordering: Vec<String>) -> Box<dyn Plan> {
+        let mut schema = Schema::new();
+        schema.add_field("a", FieldInfo::Integer);
+        schema.add_field("b", FieldInfo::String(8));
+        let rows = rows
+            .into_iter()
+            .map(|(a, b)| vec![Constant::Int(a), Constant::from(b)])
+            .collect::<Vec<_>>();
+        let mut plan = MockPlan::new();
+        plan.expect_get_schema().return_const(schema);
+        plan.expect_ordering().returning(move || ordering.clone());
+        plan.expect_open_read_scan().returning(move || {
+            Ok(Box::new(ValuesScan::new(
+                vec!["a".to_string(), "b".to_string()],
+                rows.clone(),
+            )))
+        });
+        Box::new(plan)
+    }
+
+    fn collect(plan: &dyn Plan) -> Vec<(i32, String)> {
+        let mut scan = plan.open_read_scan().unwrap();
+        let mut rows = vec![];
+        while scan.move_next().unwrap() {
+            rows.push((scan.get_int("a").unwrap(), scan.get_string("b").unwrap()));
+        }
+        rows
+    }
+
+    #[test]
+    fn test_distinct() {
+        let dir = tempdir().unwrap();
+        let file_manager = Arc::new(FileManager::new(dir.path(), 400));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager.clone(),
+            log_manager.clone(),
+            8,
+            Some(10),
+        ));
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        let temp_file_manager = Arc::new(TempFileManager::new(
+            file_manager.clone(),
+            buffer_manager.clone(),
+        ));
+        let factory =
+            TransactionFactory::new(file_manager, log_manager, buffer_manager, lock_table);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+
+        // 同じ値の record が離れた位置にあっても、並べ替えてから 1 つにまとめる
+        let rows = vec![(2, "x"), (1, "y"), (2, "x"), (1, "z"), (1, "y")];
+        let plan = DistinctPlan::new(
+            values_plan(rows, vec![]),
+            temp_file_manager.clone(),
+            tx.clone(),
+        )
+        .unwrap();
+        assert_eq!(plan.get_schema().fields(), vec!["a", "b"]);
+        assert_eq!(plan.ordering(), vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(
+            collect(&plan),
+            vec![
+                (1, "y".to_string()),
+                (1, "z".to_string()),
+                (2, "x".to_string()),
+            ]
+        );
+
+        // 既に全ての field で並んでいる場合は、その順のまま重複を除く
+        let rows = vec![(1, "x"), (2, "x"), (2, "x"), (1, "y")];
+        let plan = DistinctPlan::new(
+            values_plan(rows, vec!["b".to_string(), "a".to_string()]),
+            temp_file_manager,
+            tx.clone(),
+        )
+        .unwrap();
+        assert_eq!(plan.ordering(), vec!["b".to_string(), "a".to_string()]);
+        assert_eq!(
+            collect(&plan),
+            vec![
+                (1, "x".to_string()),
+                (2, "x".to_string()),
+                (1, "y".to_string()),
+            ]
+        );
+        tx.borrow_mut().commit().unwrap();
+    }
+}
@@ -1,4 +1,12 @@
-use crate::query::{constant::Constant, expression::Expression as ExpressionForScan};
+use crate::{
+    query::{
+        constant::Constant,
+        expression::{CaseExpression as CaseExpressionForScan, Expression as ExpressionForScan},
+    },
+    record::schema::{FieldInfo, Schema},
+};
+
+use super::predicate::ProductPredicate;
 
 use std::fmt;
 
@@ -6,10 +14,23 @@ use std::fmt;
  * Select の where 句で用いられる条件で、A=B などの比較における A または B を表す
  * 同じ名前の struct が query 以下のパッケージにも存在するが、こちらは実行計画を立てるうえで使うことを意図されている
  */
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
     Constant(Constant),
     Field(String),
+    Case(CaseExpression),
+    /// predicate が満たされるかどうかを真偽値として返す式 (select a = 1 from t など)
+    Predicate(ProductPredicate),
+}
+
+/**
+ * case when <predicate> then <expression> ... else <expression> end を表す式
+ * 最初に満たされた when 句の式の値になり、どれも満たされない場合は else 句の式の値になる
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseExpression {
+    branches: Vec<(ProductPredicate, Expression)>,
+    otherwise: Box<Expression>,
 }
 
 impl Expression {
@@ -26,12 +47,125 @@ impl Expression {
             _ => None,
         }
     }
+    /// 式の中で参照されている field 名を返す
+    pub fn fields(&self) -> Vec<String> {
+        match self {
+            Expression::Constant(_) => vec![],
+            Expression::Field(field_name) => vec![field_name.clone()],
+            Expression::Case(case) => case.fields(),
+            Expression::Predicate(predicate) => predicate.fields(),
+        }
+    }
+    /// schema の record に対してこの式を評価した値の型を返す。参照する field が schema にない場合は None を返す
+    /// string の場合は、値になりうる string の最大の長さを返す
+    pub fn field_info(&self, schema: &Schema) -> Option<FieldInfo> {
+        match self {
+            Expression::Constant(Constant::Int(_)) => Some(FieldInfo::Integer),
+            Expression::Constant(Constant::String(val)) => {
+                Some(FieldInfo::String(val.chars().count()))
+            }
+            Expression::Constant(Constant::Bool(_)) => Some(FieldInfo::Boolean),
+            Expression::Constant(Constant::BigInt(_)) => Some(FieldInfo::BigInt),
+            Expression::Constant(Constant::Date(_)) => Some(FieldInfo::Date),
+            Expression::Constant(Constant::Double(_)) => Some(FieldInfo::Double),
+            Expression::Field(field_name) => schema.info(field_name),
+            Expression::Case(case) => case.field_info(schema),
+            Expression::Predicate(_) => Some(FieldInfo::Boolean),
+        }
+    }
+    /// field 名 old_name を new_name に置き換えた式を返す
+    pub fn rename_field(&self, old_name: &str, new_name: &str) -> Expression {
+        match self {
+            Expression::Field(field_name) if field_name == old_name => {
+                Expression::Field(new_name.to_string())
+            }
+            Expression::Case(case) => Expression::Case(case.rename_field(old_name, new_name)),
+            Expression::Predicate(predicate) => {
+                Expression::Predicate(predicate.rename_field(old_name, new_name))
+            }
+            _ => self.clone(),
+        }
+    }
     pub fn convert_for_scan(&self) -> ExpressionForScan {
         match self {
             Expression::Field(field_name) => ExpressionForScan::Field(field_name.clone()),
             Expression::Constant(constant) => ExpressionForScan::Constant(constant.clone()),
+            Expression::Case(case) => ExpressionForScan::Case(case.convert_for_scan()),
+            Expression::Predicate(predicate) => {
+                ExpressionForScan::Predicate(predicate.convert_for_scan())
+            }
+        }
+    }
+}
+
+impl CaseExpression {
+    pub fn new(branches: Vec<(ProductPredicate, Expression)>, otherwise: Expression) -> Self {
+        Self {
+            branches,
+            otherwise: Box::new(otherwise),
         }
     }
+
+    /// when 句の条件と、その条件が満たされた場合の式の組
+    pub fn branches(&self) -> &[(ProductPredicate, Expression)] {
+        &self.branches
+    }
+
+    /// どの when 句も満たされない場合の式
+    pub fn otherwise(&self) -> &Expression {
+        &self.otherwise
+    }
+
+    fn fields(&self) -> Vec<String> {
+        let mut fields = vec![];
+        for (predicate, expression) in &self.branches {
+            fields.extend(predicate.fields());
+            fields.extend(expression.fields());
+        }
+        fields.extend(self.otherwise.fields());
+        fields
+    }
+
+    fn field_info(&self, schema: &Schema) -> Option<FieldInfo> {
+        let mut infos = self
+            .branches
+            .iter()
+            .map(|(_, expression)| expression.field_info(schema))
+            .collect::<Option<Vec<_>>>()?;
+        infos.push(self.otherwise.field_info(schema)?);
+        // 型が揃っていることは validator で確認する。string の場合は最も長いものに合わせる
+        infos.into_iter().reduce(|lhs, rhs| match (lhs, rhs) {
+            (FieldInfo::String(lhs), FieldInfo::String(rhs)) => FieldInfo::String(lhs.max(rhs)),
+            (lhs, _) => lhs,
+        })
+    }
+
+    fn rename_field(&self, old_name: &str, new_name: &str) -> CaseExpression {
+        CaseExpression::new(
+            self.branches
+                .iter()
+                .map(|(predicate, expression)| {
+                    (
+                        predicate.rename_field(old_name, new_name),
+                        expression.rename_field(old_name, new_name),
+                    )
+                })
+                .collect(),
+            self.otherwise.rename_field(old_name, new_name),
+        )
+    }
+
+    fn convert_for_scan(&self) -> CaseExpressionForScan {
+        CaseExpressionForScan::new(
+            self.branches
+                .iter()
+                .map(|(predicate, expression)| {
+                    (predicate.convert_for_scan(), expression.convert_for_scan())
+                })
+                .collect(),
+            self.otherwise.convert_for_scan(),
+        )
+    }
 }
 
 impl fmt::Display for Expression {
@@ -39,6 +173,18 @@ impl fmt::Display for Expression {
         match self {
             Expression::Constant(constant) => write!(f, "{}", constant),
             Expression::Field(field_name) => write!(f, "{}", field_name),
+            Expression::Case(case) => write!(f, "{}", case),
+            Expression::Predicate(predicate) => write!(f, "{}", predicate),
+        }
+    }
+}
+
+impl fmt::Display for CaseExpression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "case")?;
+        for (predicate, expression) in &self.branches {
+            write!(f, " when {} then {}", predicate, expression)?;
         }
+        write!(f, " else {} end", self.otherwise)
     }
 }
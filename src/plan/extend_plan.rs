@@ -0,0 +1,116 @@
+use crate::{
+    query::{
+        constant::Constant,
+        expression::Expression as ExpressionForScan,
+        extend_scan::ExtendScan,
+        scan::{ReadScan, Scan, UpdateScan},
+    },
+    record::schema::Schema,
+};
+
+use super::{
+    expression::Expression,
+    plan::{Plan, PlanError},
+};
+
+use anyhow::Result as AnyhowResult;
+
+/**
+ * 子の plan の record に、式を評価した値を持つ field を追加する plan
+ */
+pub struct ExtendPlan {
+    child: Box<dyn Plan>,
+    expressions: Vec<(String, Expression)>,
+    schema: Schema,
+}
+
+impl Plan for ExtendPlan {
+    fn get_schema(&self) -> &Schema {
+        &self.schema
+    }
+    fn ordering(&self) -> Vec<String> {
+        self.child.ordering()
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        self.child.unique_fields()
+    }
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        match self.expression(field_name) {
+            Some(Expression::Field(field_name)) => self.child.get_value_range(field_name),
+            Some(Expression::Constant(constant)) => Some((constant.clone(), constant.clone())),
+            Some(_) => None,
+            None => self.child.get_value_range(field_name),
+        }
+    }
+    fn get_block_access_cost(&self) -> AnyhowResult<u64> {
+        self.child.get_block_access_cost()
+    }
+    fn get_record_access_cost(&self) -> AnyhowResult<u64> {
+        self.child.get_record_access_cost()
+    }
+    fn get_distinct_value_estimation(&self, field_name: &str) -> AnyhowResult<u64> {
+        match self.expression(field_name) {
+            Some(Expression::Field(field_name)) => {
+                self.child.get_distinct_value_estimation(field_name)
+            }
+            Some(Expression::Constant(_)) => Ok(1),
+            Some(Expression::Predicate(_)) => Ok(2),
+            // case 式は when 句の数だけ値を取りうる
+            Some(Expression::Case(case)) => Ok(case.branches().len() as u64 + 1),
+            None => self.child.get_distinct_value_estimation(field_name),
+        }
+    }
+    fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>> {
+        let scan = self.child.open_read_scan()?;
+        Ok(Box::new(ExtendScan::new(
+            Scan::ReadOnly(scan),
+            self.expressions_for_scan(),
+        )))
+    }
+    fn open_update_scan(&self) -> AnyhowResult<Box<dyn UpdateScan>> {
+        let scan = self.child.open_update_scan()?;
+        Ok(Box::new(ExtendScan::new(
+            Scan::Updatable(scan),
+            self.expressions_for_scan(),
+        )))
+    }
+}
+
+impl ExtendPlan {
+    pub fn new(
+        child: Box<dyn Plan>,
+        expressions: Vec<(String, Expression)>,
+    ) -> AnyhowResult<ExtendPlan> {
+        let mut schema = child.get_schema().clone();
+        for (field_name, expression) in &expressions {
+            if schema.has_field(field_name) {
+                return Err(
+                    PlanError::InvalidCall(format!("field {} already exists", field_name)).into(),
+                );
+            }
+            let info = expression.field_info(child.get_schema()).ok_or_else(|| {
+                PlanError::InvalidCall(format!("fields referenced by {} not found", expression))
+            })?;
+            schema.add_field(field_name, info);
+        }
+        Ok(ExtendPlan {
+            child,
+            expressions,
+            schema,
+        })
+    }
+
+    fn expression(&self, field_name: &str) -> Option<&Expression> {
+        self.expressions
+            .iter()
+            .find(|(name, _)| name == field_name)
+            .map(|(_, expression)| expression)
+    }
+
+    fn expressions_for_scan(&self) -> Vec<(String, ExpressionForScan)> {
+        self.expressions
+            .iter()
+            .map(|(name, expression)| (name.clone(), expression.convert_for_scan()))
+            .collect()
+    }
+}
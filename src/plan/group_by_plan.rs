@@ -0,0 +1,261 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use crate::{
+    query::{
+        constant::Constant,
+        group_by_scan::GroupByScan,
+        scan::{ReadScan, UpdateScan},
+    },
+    record::schema::Schema,
+    tx::{temp_file_manager::TempFileManager, transaction::Transaction},
+};
+
+use super::{
+    aggregation::{Aggregation, AggregationKind},
+    plan::{Plan, PlanError},
+    sort_plan::SortPlan,
+};
+
+/**
+ * child の record を group_fields の値ごとにまとめ、group ごとに集約関数の値を求める plan
+ *
+ * child が group_fields の順に並んでいない場合は、SortPlan で並べ替えてから GroupByScan で読む
+ * 出力する record は group_fields と、集約関数の値につけた名前の field を持つ
+ */
+pub struct GroupByPlan {
+    // group_fields の順に並んだ child
+    child: Box<dyn Plan>,
+    group_fields: Vec<String>,
+    // 集約関数の値につけた名前と、集約関数
+    aggregations: Vec<(String, Aggregation)>,
+    schema: Schema,
+}
+
+impl Plan for GroupByPlan {
+    fn get_block_access_cost(&self) -> AnyhowResult<u64> {
+        self.child.get_block_access_cost()
+    }
+    fn get_record_access_cost(&self) -> AnyhowResult<u64> {
+        // group の数は、group_fields の distinct value の積を child の record の数で抑えたもの
+        let records = self.child.get_record_access_cost()?;
+        let mut groups: u64 = 1;
+        for field in &self.group_fields {
+            groups = groups.saturating_mul(self.child.get_distinct_value_estimation(field)?);
+        }
+        Ok(groups.min(records))
+    }
+    fn get_distinct_value_estimation(&self, field_name: &str) -> AnyhowResult<u64> {
+        if self.group_fields.iter().any(|field| field == field_name) {
+            self.child.get_distinct_value_estimation(field_name)
+        } else {
+            self.get_record_access_cost()
+        }
+    }
+    fn get_schema(&self) -> &Schema {
+        &self.schema
+    }
+    fn ordering(&self) -> Vec<String> {
+        self.group_fields.clone()
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        // group_fields の値の組は重複しないので、group_fields が 1 つの場合はその値も重複しない
+        match self.group_fields.as_slice() {
+            [field] => vec![field.clone()],
+            _ => vec![],
+        }
+    }
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        if self.group_fields.iter().any(|field| field == field_name) {
+            return self.child.get_value_range(field_name);
+        }
+        // 最大値と最小値は集計する field の値のどれかになる
+        let (_, aggregation) = self
+            .aggregations
+            .iter()
+            .find(|(name, _)| name == field_name)?;
+        match aggregation.kind() {
+            AggregationKind::Max | AggregationKind::Min => {
+                self.child.get_value_range(aggregation.field())
+            }
+            AggregationKind::Count | AggregationKind::Sum | AggregationKind::Avg => None,
+        }
+    }
+    fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>> {
+        let scan = self.child.open_read_scan()?;
+        let aggregation_fns = self
+            .aggregations
+            .iter()
+            .map(|(name, aggregation)| aggregation.create_fn(name))
+            .collect();
+        Ok(Box::new(GroupByScan::new(
+            scan,
+            self.group_fields.clone(),
+            aggregation_fns,
+        )?))
+    }
+    fn open_update_scan(&self) -> AnyhowResult<Box<dyn UpdateScan>> {
+        Err(anyhow!(PlanError::InvalidCall(
+            "group by plan does not support update scan".to_string()
+        )))
+    }
+}
+
+impl GroupByPlan {
+    pub fn new(
+        child: Box<dyn Plan>,
+        group_fields: Vec<String>,
+        aggregations: Vec<(String, Aggregation)>,
+        temp_file_manager: Arc<TempFileManager>,
+        tx: Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Self> {
+        let mut schema = Schema::new();
+        for field in &group_fields {
+            if !child.get_schema().has_field(field) {
+                return Err(PlanError::InvalidCall(format!("field {} not found", field)).into());
+            }
+            schema.add(field, child.get_schema())?;
+        }
+        for (name, aggregation) in &aggregations {
+            if schema.has_field(name) {
+                return Err(
+                    PlanError::InvalidCall(format!("field {} already exists", name)).into(),
+                );
+            }
+            let info = aggregation.field_info(child.get_schema()).ok_or_else(|| {
+                PlanError::InvalidCall(format!("field {} not found", aggregation.field()))
+            })?;
+            schema.add_field(name, info);
+        }
+        // child が既に group_fields の順に並んでいる場合は並べ替えない
+        let child: Box<dyn Plan> = if child.ordering().starts_with(&group_fields) {
+            child
+        } else {
+            Box::new(SortPlan::new(
+                child,
+                group_fields.clone(),
+                temp_file_manager,
+                tx,
+            )?)
+        };
+        Ok(Self {
+            child,
+            group_fields,
+            aggregations,
+            schema,
+        })
+    }
+}
+
+#[cfg(test)]
+mod group_by_plan_test {
+    use super::*;
+    use crate::{
+        buffer::buffer_manager::BufferManager,
+        file::file_manager::FileManager,
+        log::log_manager::LogManager,
+        plan::plan::MockPlan,
+        query::values_scan::ValuesScan,
+        record::schema::FieldInfo,
+        tx::{concurrency::lock_table::LockTable, transaction::TransactionFactory},
+    };
+    use tempfile::tempdir;
+
+    fn values_plan(rows: Vec<(i32, &'static str)>) -> Box<dyn Plan> {
+        let mut schema = Schema::new();
+        schema.add_field("a", FieldInfo::Integer);
+        schema.add_field("b", FieldInfo::String(8));
+        let rows = rows
+            .into_iter()
+            .map(|(a, b)| vec![Constant::Int(a), Constant::from(b)])
+            .collect::<Vec<_>>();
+        let mut plan = MockPlan::new();
+        plan.expect_get_schema().return_const(schema);
+        plan.expect_ordering().returning(Vec::new);
+        plan.expect_open_read_scan().returning(move || {
+            Ok(Box::new(ValuesScan::new(
+                vec!["a".to_string(), "b".to_string()],
+                rows.clone(),
+            )))
+        });
+        Box::new(plan)
+    }
+
+    #[test]
+    fn test_group_by() {
+        let dir = tempdir().unwrap();
+        let file_manager = Arc::new(FileManager::new(dir.path(), 400));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager.clone(),
+            log_manager.clone(),
+            8,
+            Some(10),
+        ));
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        let temp_file_manager = Arc::new(TempFileManager::new(
+            file_manager.clone(),
+            buffer_manager.clone(),
+        ));
+        let factory =
+            TransactionFactory::new(file_manager, log_manager, buffer_manager, lock_table);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+
+        // 同じ値の record が離れた位置にあっても、並べ替えてから 1 つの group にまとめる
+        let rows = vec![(2, "x"), (1, "y"), (2, "z"), (3, "w"), (1, "v"), (2, "u")];
+        let plan = GroupByPlan::new(
+            values_plan(rows),
+            vec!["a".to_string()],
+            vec![
+                (
+                    "n".to_string(),
+                    Aggregation::new(AggregationKind::Count, "b"),
+                ),
+                (
+                    "min(b)".to_string(),
+                    Aggregation::new(AggregationKind::Min, "b"),
+                ),
+            ],
+            temp_file_manager.clone(),
+            tx.clone(),
+        )
+        .unwrap();
+        assert_eq!(plan.get_schema().info("n"), Some(FieldInfo::Integer));
+        assert_eq!(plan.get_schema().info("min(b)"), Some(FieldInfo::String(8)));
+        assert_eq!(plan.ordering(), vec!["a".to_string()]);
+
+        let mut scan = plan.open_read_scan().unwrap();
+        let mut rows = vec![];
+        while scan.move_next().unwrap() {
+            rows.push((
+                scan.get_int("a").unwrap(),
+                scan.get_int("n").unwrap(),
+                scan.get_string("min(b)").unwrap(),
+            ));
+        }
+        assert_eq!(
+            rows,
+            vec![
+                (1, 2, "v".to_string()),
+                (2, 3, "u".to_string()),
+                (3, 1, "w".to_string()),
+            ]
+        );
+        drop(scan);
+
+        // 存在しない field は集計できない
+        assert!(GroupByPlan::new(
+            values_plan(vec![]),
+            vec![],
+            vec![(
+                "sum(c)".to_string(),
+                Aggregation::new(AggregationKind::Sum, "c"),
+            )],
+            temp_file_manager,
+            tx.clone(),
+        )
+        .is_err());
+        tx.borrow_mut().commit().unwrap();
+    }
+}
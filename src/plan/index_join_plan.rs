@@ -0,0 +1,140 @@
+use std::{cell::RefCell, rc::Rc};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use crate::{
+    metadata::index_manager::IndexInfo,
+    query::{
+        constant::Constant,
+        index_join_scan::IndexJoinScan,
+        scan::{ReadScan, UpdateScan},
+    },
+    record::schema::{FieldInfo, Schema},
+    tx::transaction::Transaction,
+};
+
+use super::{
+    plan::{Plan, PlanError},
+    table_plan::TablePlan,
+};
+
+/**
+ * lhs の各 record の join_field の値で rhs の table の index を検索し、値が等しい record どうしを組み合わせる plan
+ *
+ * product と異なり、lhs の record ごとに rhs の table を全て読み直す必要がない
+ */
+pub struct IndexJoinPlan {
+    lhs: Box<dyn Plan>,
+    rhs: TablePlan,
+    index_info: IndexInfo,
+    join_field: String,
+    schema: Schema,
+    tx: Rc<RefCell<Transaction>>,
+}
+
+impl Plan for IndexJoinPlan {
+    fn get_block_access_cost(&self) -> AnyhowResult<u64> {
+        // lhs の record ごとに、index の検索と見つかった record ごとに 1 block 読むとみなす
+        let search_cost = self.index_info.search_cost(
+            self.rhs.get_record_access_cost()?,
+            self.rhs.layout().schema(),
+            self.tx.borrow().block_size(),
+        )?;
+        Ok(self.lhs.get_block_access_cost()?
+            + self.lhs.get_record_access_cost()? * (search_cost + self.matches_per_record()?))
+    }
+    fn get_record_access_cost(&self) -> AnyhowResult<u64> {
+        Ok(self.lhs.get_record_access_cost()? * self.matches_per_record()?)
+    }
+    fn get_distinct_value_estimation(&self, field_name: &str) -> AnyhowResult<u64> {
+        if self.lhs.get_schema().has_field(field_name) {
+            self.lhs.get_distinct_value_estimation(field_name)
+        } else {
+            self.rhs.get_distinct_value_estimation(field_name)
+        }
+    }
+    fn get_schema(&self) -> &Schema {
+        &self.schema
+    }
+    fn ordering(&self) -> Vec<String> {
+        // lhs の 1 record ごとに rhs の record を探すので、lhs の並び順は保たれる
+        self.lhs.ordering()
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        // lhs の各 record は一致する rhs の record の数だけ繰り返し出力されるので、一意性は保証できない
+        vec![]
+    }
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        if self.lhs.get_schema().has_field(field_name) {
+            self.lhs.get_value_range(field_name)
+        } else {
+            self.rhs.get_value_range(field_name)
+        }
+    }
+    fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>> {
+        let lhs = self.lhs.open_read_scan()?;
+        // Rid で record に移動するために、read でも update scan を開く
+        let rhs = self.rhs.open_update_scan()?;
+        let key_info = self.key_info()?;
+        let index = self.index_info.open(&self.tx, self.rhs.layout().schema())?;
+        Ok(Box::new(IndexJoinScan::new(
+            lhs,
+            index,
+            &self.join_field,
+            key_info.get_type(),
+            rhs,
+        )?))
+    }
+    fn open_update_scan(&self) -> AnyhowResult<Box<dyn UpdateScan>> {
+        Err(anyhow!(PlanError::InvalidCall(
+            "index join plan does not support update scan".to_string()
+        )))
+    }
+}
+
+impl IndexJoinPlan {
+    /// rhs は index_info の index を張った table を読む plan で、join_field は lhs の field
+    pub fn new(
+        lhs: Box<dyn Plan>,
+        rhs: TablePlan,
+        index_info: IndexInfo,
+        join_field: &str,
+        tx: Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Self> {
+        let mut schema = Schema::new();
+        schema.add_all(lhs.get_schema())?;
+        schema.add_all(rhs.get_schema())?;
+        Ok(Self {
+            lhs,
+            rhs,
+            index_info,
+            join_field: join_field.to_string(),
+            schema,
+            tx,
+        })
+    }
+
+    // lhs の 1 record に対して index で見つかる rhs の record の数の見積もり
+    fn matches_per_record(&self) -> AnyhowResult<u64> {
+        let distinct_values = self
+            .rhs
+            .get_distinct_value_estimation(self.index_info.field_name())?
+            .max(1);
+        Ok(self.rhs.get_record_access_cost()?.div_ceil(distinct_values))
+    }
+
+    // index を張った field の型
+    fn key_info(&self) -> AnyhowResult<FieldInfo> {
+        self.rhs
+            .layout()
+            .schema()
+            .info(self.index_info.field_name())
+            .ok_or_else(|| {
+                anyhow!(PlanError::Internal(format!(
+                    "field {} not found in table {}",
+                    self.index_info.field_name(),
+                    self.index_info.table_name()
+                )))
+            })
+    }
+}
@@ -0,0 +1,208 @@
+use std::{cell::RefCell, ops::Bound, rc::Rc};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use crate::{
+    index::common::IndexSearch,
+    metadata::index_manager::IndexInfo,
+    query::{
+        constant::Constant,
+        index_only_scan::IndexOnlyScan,
+        index_select_scan::IndexSelectScan,
+        scan::{ReadScan, UpdateScan},
+        term::ComparisonOperator,
+    },
+    record::schema::Schema,
+    tx::transaction::Transaction,
+};
+
+use super::{
+    expression::Expression,
+    plan::{Plan, PlanError},
+    plannable::Plannable,
+    reduction_factor::ReductionFactor,
+    table_plan::TablePlan,
+    term::ComparisonTerm,
+};
+
+/**
+ * index を使って、field の値が search の条件 (val と等しい、または範囲に入る) を満たす record だけを table から読む plan
+ *
+ * table を全て読む代わりに、index の検索と、見つかった record のある block だけを読む
+ */
+pub struct IndexSelectPlan {
+    child: TablePlan,
+    index_info: IndexInfo,
+    search: IndexSearch,
+    // 読む field が全て index の record に含まれていて、table の block を読まない場合は true
+    covering: bool,
+    tx: Rc<RefCell<Transaction>>,
+}
+
+impl Plan for IndexSelectPlan {
+    fn get_block_access_cost(&self) -> AnyhowResult<u64> {
+        let block_size = self.tx.borrow().block_size();
+        let search_cost = self.index_info.search_cost(
+            self.child.get_record_access_cost()?,
+            self.child.layout().schema(),
+            block_size,
+        )?;
+        if self.covering {
+            // 見つかった record は index の leaf に並んでいるので、leaf の block を読む分だけ増える
+            let records_per_block = self
+                .index_info
+                .records_per_block(self.child.layout().schema(), block_size)?;
+            return Ok(search_cost + self.get_record_access_cost()?.div_ceil(records_per_block));
+        }
+        // index の検索に加えて、見つかった record ごとに 1 block 読むとみなす
+        Ok(search_cost + self.get_record_access_cost()?)
+    }
+    fn get_record_access_cost(&self) -> AnyhowResult<u64> {
+        let records = self.child.get_record_access_cost()?;
+        match &self.search {
+            IndexSearch::Equal(_) => {
+                let distinct_values = self
+                    .child
+                    .get_distinct_value_estimation(self.index_info.field_name())?
+                    .max(1);
+                Ok(records / distinct_values)
+            }
+            // 範囲の両端の条件で絞られる割合は、select の条件と同じように見積もる
+            IndexSearch::Range(_, _) => Ok(match self.range_reduction_factor()? {
+                ReductionFactor::Constant(c) => (records as f64 / c) as u64,
+                ReductionFactor::Infinity() => 0,
+            }),
+        }
+    }
+    fn get_distinct_value_estimation(&self, field_name: &str) -> AnyhowResult<u64> {
+        if field_name != self.index_info.field_name() {
+            return self.child.get_distinct_value_estimation(field_name);
+        }
+        match &self.search {
+            IndexSearch::Equal(_) => Ok(1),
+            IndexSearch::Range(_, _) => Ok(self
+                .child
+                .get_distinct_value_estimation(field_name)?
+                .min(self.get_record_access_cost()?.max(1))),
+        }
+    }
+    fn get_schema(&self) -> &Schema {
+        self.child.get_schema()
+    }
+    fn ordering(&self) -> Vec<String> {
+        match &self.search {
+            // B-Tree の範囲の検索は、値の順に record を返す
+            IndexSearch::Range(_, _) => vec![self.index_info.field_name().to_string()],
+            // 等しい値の record を返す順番は決まっていない
+            IndexSearch::Equal(_) => vec![],
+        }
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        self.child.unique_fields()
+    }
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        if field_name != self.index_info.field_name() {
+            return self.child.get_value_range(field_name);
+        }
+        match &self.search {
+            IndexSearch::Equal(val) => Some((val.clone(), val.clone())),
+            // 範囲の端が決まっていない側は、table 全体の値の範囲で補う
+            IndexSearch::Range(lo, hi) => {
+                let child_range = self.child.get_value_range(field_name);
+                let lo = match lo {
+                    Bound::Included(val) | Bound::Excluded(val) => val.clone(),
+                    Bound::Unbounded => child_range.as_ref()?.0.clone(),
+                };
+                let hi = match hi {
+                    Bound::Included(val) | Bound::Excluded(val) => val.clone(),
+                    Bound::Unbounded => child_range?.1,
+                };
+                Some((lo, hi))
+            }
+        }
+    }
+    fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>> {
+        if self.covering {
+            let index = self
+                .index_info
+                .open(&self.tx, self.child.layout().schema())?;
+            return Ok(Box::new(IndexOnlyScan::new(
+                index,
+                self.search.clone(),
+                self.index_info.field_name().to_string(),
+                self.index_info.included_fields().to_vec(),
+            )?));
+        }
+        // Rid で record に移動するために、read でも update scan を開く
+        let table_scan = self.child.open_update_scan()?;
+        let index = self
+            .index_info
+            .open(&self.tx, self.child.layout().schema())?;
+        Ok(Box::new(IndexSelectScan::new(
+            table_scan,
+            index,
+            self.search.clone(),
+        )?))
+    }
+    fn open_update_scan(&self) -> AnyhowResult<Box<dyn UpdateScan>> {
+        Err(anyhow!(PlanError::InvalidCall(
+            "index select plan does not support update scan".to_string()
+        )))
+    }
+}
+
+impl IndexSelectPlan {
+    /// child は index_info の index を張った table を読む plan
+    pub fn new(
+        child: TablePlan,
+        index_info: IndexInfo,
+        search: IndexSearch,
+        tx: Rc<RefCell<Transaction>>,
+    ) -> Self {
+        Self {
+            child,
+            index_info,
+            search,
+            covering: false,
+            tx,
+        }
+    }
+
+    /// 読む field が全て index の record に含まれている場合に、table の block を読まずに index だけを読むようにする
+    pub fn with_covering(mut self) -> AnyhowResult<Self> {
+        if !self.index_info.covers(&self.child.get_schema().fields()) {
+            return Err(anyhow!(PlanError::InvalidCall(format!(
+                "index {} does not cover the fields of table {}",
+                self.index_info.index_name(),
+                self.index_info.table_name()
+            ))));
+        }
+        self.covering = true;
+        Ok(self)
+    }
+
+    // 範囲の両端を field と定数の比較の term とみなして、record が絞られる割合を見積もる
+    fn range_reduction_factor(&self) -> AnyhowResult<ReductionFactor> {
+        let IndexSearch::Range(lo, hi) = &self.search else {
+            return Ok(ReductionFactor::Constant(1.0));
+        };
+        let field = || Expression::Field(self.index_info.field_name().to_string());
+        let mut terms = vec![];
+        match lo {
+            Bound::Included(val) => terms.push((ComparisonOperator::GreaterEqual, val)),
+            Bound::Excluded(val) => terms.push((ComparisonOperator::Greater, val)),
+            Bound::Unbounded => {}
+        }
+        match hi {
+            Bound::Included(val) => terms.push((ComparisonOperator::LessEqual, val)),
+            Bound::Excluded(val) => terms.push((ComparisonOperator::Less, val)),
+            Bound::Unbounded => {}
+        }
+        let mut reduction_factor = ReductionFactor::Constant(1.0);
+        for (op, val) in terms {
+            let term = ComparisonTerm::new(field(), op, Expression::Constant(val.clone()));
+            reduction_factor *= term.reduction_factor(&self.child)?;
+        }
+        Ok(reduction_factor)
+    }
+}
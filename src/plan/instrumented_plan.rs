@@ -0,0 +1,213 @@
+use std::{cell::Cell, cmp::Ordering, fmt, rc::Rc};
+
+use anyhow::Result as AnyhowResult;
+
+use crate::{
+    query::{
+        constant::Constant,
+        row::Row,
+        scan::{ReadScan, UpdateScan},
+    },
+    record::schema::Schema,
+};
+
+use super::{plan::Plan, plan_snapshot::PlanSnapshot};
+
+/// InstrumentedPlan から開いた scan が実際に返した record の数と、自分で block を読んだ回数
+#[derive(Debug, Default)]
+struct PlanStats {
+    records: Cell<u64>,
+    block_accesses: Cell<u64>,
+}
+
+/**
+ * explain analyze の結果の plan tree の 1 node
+ *
+ * plan の見積もり (estimated) と、実際に実行した時の値 (actual) を並べて保持する
+ * actual の値は、対応する InstrumentedPlan から開いた scan を読み進めるたびに更新される
+ * 見積もりは scan を 1 回読み切った時の値だが、actual は product の内側の scan のように何度も読み直された分を全て足した値になる
+ */
+pub struct ExplainNode {
+    label: String,
+    estimated_records: u64,
+    estimated_blocks: u64,
+    stats: Rc<PlanStats>,
+    children: Vec<ExplainNode>,
+}
+
+impl ExplainNode {
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn children(&self) -> &[ExplainNode] {
+        &self.children
+    }
+
+    pub fn estimated_records(&self) -> u64 {
+        self.estimated_records
+    }
+
+    pub fn estimated_blocks(&self) -> u64 {
+        self.estimated_blocks
+    }
+
+    pub fn actual_records(&self) -> u64 {
+        self.stats.records.get()
+    }
+
+    /// 実際に block を読んだ回数
+    /// cost の見積もりと同じく、子の plan が読んだ block も含める
+    pub fn actual_blocks(&self) -> u64 {
+        self.stats.block_accesses.get()
+            + self
+                .children
+                .iter()
+                .map(|child| child.actual_blocks())
+                .sum::<u64>()
+    }
+
+    /// 実際に実行した値を除いた、plan tree の形と見積もりだけを返す
+    pub fn snapshot(&self) -> PlanSnapshot {
+        PlanSnapshot::new(
+            self.label.clone(),
+            self.estimated_records,
+            self.estimated_blocks,
+            self.children.iter().map(|child| child.snapshot()).collect(),
+        )
+    }
+
+    fn fmt_with_indent(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(
+            f,
+            "{}{} (estimated: records={}, blocks={}) (actual: records={}, blocks={})",
+            "  ".repeat(depth),
+            self.label,
+            self.estimated_records,
+            self.estimated_blocks,
+            self.actual_records(),
+            self.actual_blocks()
+        )?;
+        for child in &self.children {
+            child.fmt_with_indent(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ExplainNode {
+    /// 1 行に 1 node ずつ、子の node を字下げして表示する
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with_indent(f, 0)
+    }
+}
+
+/**
+ * 他の Plan を包み、その Plan から開いた scan が実際に返した record の数と読んだ block の数を数える Plan
+ *
+ * 見積もりなどは包んだ Plan のものをそのまま返すので、plan tree のどこに挟んでも結果は変わらない
+ */
+pub struct InstrumentedPlan {
+    child: Box<dyn Plan>,
+    stats: Rc<PlanStats>,
+}
+
+impl Plan for InstrumentedPlan {
+    fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>> {
+        Ok(Box::new(CountingScan {
+            scan: self.child.open_read_scan()?,
+            stats: self.stats.clone(),
+            reported_block_accesses: 0,
+        }))
+    }
+    fn open_update_scan(&self) -> AnyhowResult<Box<dyn UpdateScan>> {
+        // 数えるのは読み出しだけなので、update scan はそのまま返す
+        self.child.open_update_scan()
+    }
+    fn get_block_access_cost(&self) -> AnyhowResult<u64> {
+        self.child.get_block_access_cost()
+    }
+    fn get_record_access_cost(&self) -> AnyhowResult<u64> {
+        self.child.get_record_access_cost()
+    }
+    fn get_distinct_value_estimation(&self, field_name: &str) -> AnyhowResult<u64> {
+        self.child.get_distinct_value_estimation(field_name)
+    }
+    fn get_schema(&self) -> &Schema {
+        self.child.get_schema()
+    }
+    fn ordering(&self) -> Vec<String> {
+        self.child.ordering()
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        self.child.unique_fields()
+    }
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        self.child.get_value_range(field_name)
+    }
+}
+
+impl InstrumentedPlan {
+    /// plan を包み、その結果を記録する ExplainNode と一緒に返す
+    /// children には plan の子の plan (包んだもの) の ExplainNode を渡す
+    pub fn wrap(
+        plan: Box<dyn Plan>,
+        label: String,
+        children: Vec<ExplainNode>,
+    ) -> AnyhowResult<(Box<dyn Plan>, ExplainNode)> {
+        let stats = Rc::new(PlanStats::default());
+        let node = ExplainNode {
+            label,
+            estimated_records: plan.get_record_access_cost()?,
+            estimated_blocks: plan.get_block_access_cost()?,
+            stats: stats.clone(),
+            children,
+        };
+        Ok((Box::new(InstrumentedPlan { child: plan, stats }), node))
+    }
+}
+
+struct CountingScan {
+    scan: Box<dyn ReadScan>,
+    stats: Rc<PlanStats>,
+    // 包んだ scan の block_accesses のうち、すでに stats に足したもの
+    reported_block_accesses: u64,
+}
+
+impl CountingScan {
+    fn report_block_accesses(&mut self) {
+        let block_accesses = self.scan.block_accesses();
+        self.stats
+            .block_accesses
+            .set(self.stats.block_accesses.get() + block_accesses - self.reported_block_accesses);
+        self.reported_block_accesses = block_accesses;
+    }
+}
+
+impl ReadScan for CountingScan {
+    fn before_first(&mut self) -> AnyhowResult<()> {
+        self.scan.before_first()?;
+        self.report_block_accesses();
+        Ok(())
+    }
+    fn move_next(&mut self) -> AnyhowResult<bool> {
+        let found = self.scan.move_next()?;
+        if found {
+            self.stats.records.set(self.stats.records.get() + 1);
+        }
+        self.report_block_accesses();
+        Ok(found)
+    }
+    fn get_val(&self, field_name: &str) -> AnyhowResult<Constant> {
+        self.scan.get_val(field_name)
+    }
+    fn has_field(&self, field_name: &str) -> bool {
+        self.scan.has_field(field_name)
+    }
+    fn compare_val(&self, field_name: &str, val: &Constant) -> AnyhowResult<Option<Ordering>> {
+        self.scan.compare_val(field_name, val)
+    }
+    fn read_into(&self, row: &mut Row) -> AnyhowResult<()> {
+        self.scan.read_into(row)
+    }
+}
@@ -0,0 +1,219 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use crate::{
+    query::{
+        constant::Constant,
+        merge_join_scan::MergeJoinScan,
+        scan::{ReadScan, UpdateScan},
+    },
+    record::schema::Schema,
+    tx::{temp_file_manager::TempFileManager, transaction::Transaction},
+};
+
+use super::{
+    plan::{Plan, PlanError},
+    sort_plan::SortPlan,
+};
+
+/**
+ * p1 の field1 と p2 の field2 の値が等しい record どうしを組み合わせる plan
+ *
+ * 両側をそれぞれ join する field で並べ替えてから先頭から同時に読むので、
+ * product のように p1 の record ごとに p2 を全て読み直す必要がない
+ */
+pub struct MergeJoinPlan {
+    sp1: SortPlan,
+    sp2: SortPlan,
+    field1: String,
+    field2: String,
+    schema: Schema,
+}
+
+impl Plan for MergeJoinPlan {
+    fn get_block_access_cost(&self) -> AnyhowResult<u64> {
+        // 両側を並べ替えて 1 回ずつ読む。同じ値の record を読み直す分は含めない
+        Ok(self.sp1.get_block_access_cost()? + self.sp2.get_block_access_cost()?)
+    }
+    fn get_record_access_cost(&self) -> AnyhowResult<u64> {
+        let distinct_values = self
+            .sp1
+            .get_distinct_value_estimation(&self.field1)?
+            .max(self.sp2.get_distinct_value_estimation(&self.field2)?)
+            .max(1);
+        Ok(
+            self.sp1.get_record_access_cost()? * self.sp2.get_record_access_cost()?
+                / distinct_values,
+        )
+    }
+    fn get_distinct_value_estimation(&self, field_name: &str) -> AnyhowResult<u64> {
+        if self.sp1.get_schema().has_field(field_name) {
+            self.sp1.get_distinct_value_estimation(field_name)
+        } else {
+            self.sp2.get_distinct_value_estimation(field_name)
+        }
+    }
+    fn get_schema(&self) -> &Schema {
+        &self.schema
+    }
+    fn ordering(&self) -> Vec<String> {
+        self.sp1.ordering()
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        // 同じ値を持つ record は、相手側の同じ値の record の数だけ繰り返し出力される
+        vec![]
+    }
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        if self.sp1.get_schema().has_field(field_name) {
+            self.sp1.get_value_range(field_name)
+        } else {
+            self.sp2.get_value_range(field_name)
+        }
+    }
+    fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>> {
+        let s1 = self.sp1.open_read_scan()?;
+        let s2 = self.sp2.open_sort_scan()?;
+        Ok(Box::new(MergeJoinScan::new(
+            s1,
+            s2,
+            &self.field1,
+            &self.field2,
+        )?))
+    }
+    fn open_update_scan(&self) -> AnyhowResult<Box<dyn UpdateScan>> {
+        Err(anyhow!(PlanError::InvalidCall(
+            "merge join plan does not support update scan".to_string()
+        )))
+    }
+}
+
+impl MergeJoinPlan {
+    /// p1 と p2 を merge join する時の block access の cost の見積もり
+    pub fn estimate_block_access_cost(
+        p1: &dyn Plan,
+        p2: &dyn Plan,
+        block_size: usize,
+    ) -> AnyhowResult<u64> {
+        Ok(SortPlan::estimate_block_access_cost(p1, block_size)?
+            + SortPlan::estimate_block_access_cost(p2, block_size)?)
+    }
+
+    /// field1 は p1 の field で、field2 は p2 の field
+    pub fn new(
+        p1: Box<dyn Plan>,
+        p2: Box<dyn Plan>,
+        field1: &str,
+        field2: &str,
+        temp_file_manager: Arc<TempFileManager>,
+        tx: Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Self> {
+        let mut schema = Schema::new();
+        schema.add_all(p1.get_schema())?;
+        schema.add_all(p2.get_schema())?;
+        let sp1 = SortPlan::new(
+            p1,
+            vec![field1.to_string()],
+            temp_file_manager.clone(),
+            tx.clone(),
+        )?;
+        let sp2 = SortPlan::new(p2, vec![field2.to_string()], temp_file_manager, tx)?;
+        Ok(Self {
+            sp1,
+            sp2,
+            field1: field1.to_string(),
+            field2: field2.to_string(),
+            schema,
+        })
+    }
+}
+
+#[cfg(test)]
+mod merge_join_plan_test {
+    use super::*;
+    use crate::{
+        buffer::buffer_manager::BufferManager,
+        file::file_manager::FileManager,
+        log::log_manager::LogManager,
+        plan::plan::MockPlan,
+        query::values_scan::ValuesScan,
+        record::schema::FieldInfo,
+        tx::{concurrency::lock_table::LockTable, transaction::TransactionFactory},
+    };
+    use tempfile::tempdir;
+
+    fn values_plan(field_names: [&'static str; 2], rows: Vec<(i32, i32)>) -> Box<dyn Plan> {
+        let mut schema = Schema::new();
+        for field_name in field_names {
+            schema.add_field(field_name, FieldInfo::Integer);
+        }
+        let rows = rows
+            .into_iter()
+            .map(|(key, id)| vec![Constant::Int(key), Constant::Int(id)])
+            .collect::<Vec<_>>();
+        let mut plan = MockPlan::new();
+        plan.expect_get_schema().return_const(schema);
+        plan.expect_open_read_scan().returning(move || {
+            Ok(Box::new(ValuesScan::new(
+                field_names.iter().map(|name| name.to_string()).collect(),
+                rows.clone(),
+            )))
+        });
+        Box::new(plan)
+    }
+
+    #[test]
+    fn test_merge_join() {
+        let dir = tempdir().unwrap();
+        let file_manager = Arc::new(FileManager::new(dir.path(), 400));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager.clone(),
+            log_manager.clone(),
+            8,
+            Some(10),
+        ));
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        let temp_file_manager = Arc::new(TempFileManager::new(
+            file_manager.clone(),
+            buffer_manager.clone(),
+        ));
+        let factory =
+            TransactionFactory::new(file_manager, log_manager, buffer_manager, lock_table);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+
+        // 両側に同じ値の record が複数あり、片側にしか無い値もある
+        let lhs = vec![(3, 0), (1, 1), (2, 2), (3, 3), (5, 4), (1, 5)];
+        let rhs = vec![(1, 10), (3, 11), (4, 12), (3, 13), (1, 14), (0, 15)];
+        let plan = MergeJoinPlan::new(
+            values_plan(["a", "x"], lhs.clone()),
+            values_plan(["b", "y"], rhs.clone()),
+            "a",
+            "b",
+            temp_file_manager,
+            tx.clone(),
+        )
+        .unwrap();
+
+        let mut scan = plan.open_read_scan().unwrap();
+        let mut rows = vec![];
+        while scan.move_next().unwrap() {
+            assert_eq!(scan.get_int("a").unwrap(), scan.get_int("b").unwrap());
+            rows.push((scan.get_int("x").unwrap(), scan.get_int("y").unwrap()));
+        }
+        rows.sort();
+        let mut expected = lhs
+            .iter()
+            .flat_map(|&(a, x)| {
+                rhs.iter()
+                    .filter(move |&&(b, _)| a == b)
+                    .map(move |&(_, y)| (x, y))
+            })
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(rows.len(), 8);
+        assert_eq!(rows, expected);
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use crate::{
+    query::{
+        constant::Constant,
+        empty_scan::EmptyScan,
+        scan::{ReadScan, UpdateScan},
+    },
+    record::schema::Schema,
+};
+
+use super::plan::{Plan, PlanError};
+
+/**
+ * 結果が空になることがわかっている Plan
+ *
+ * 元の Plan は schema を返すためだけに保持し、scan は開かない
+ */
+pub struct NeverPlan {
+    child: Box<dyn Plan>,
+}
+
+impl Plan for NeverPlan {
+    fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>> {
+        Ok(Box::new(EmptyScan::new(self.child.get_schema().fields())))
+    }
+    fn open_update_scan(&self) -> AnyhowResult<Box<dyn UpdateScan>> {
+        Err(anyhow!(PlanError::InvalidCall(
+            "NeverPlan does not support update".to_string()
+        )))
+    }
+    fn get_block_access_cost(&self) -> AnyhowResult<u64> {
+        Ok(0)
+    }
+    fn get_record_access_cost(&self) -> AnyhowResult<u64> {
+        Ok(0)
+    }
+    fn get_distinct_value_estimation(&self, _field_name: &str) -> AnyhowResult<u64> {
+        Ok(0)
+    }
+    fn get_schema(&self) -> &Schema {
+        self.child.get_schema()
+    }
+    fn ordering(&self) -> Vec<String> {
+        vec![]
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        // record が無いので、どの field も値が重複しない
+        self.child.get_schema().fields()
+    }
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        self.child.get_value_range(field_name)
+    }
+}
+
+impl NeverPlan {
+    /// child と同じ schema を持ち、record を 1 つも返さない Plan を作成する
+    pub fn new(child: Box<dyn Plan>) -> Self {
+        Self { child }
+    }
+}
+
+#[cfg(test)]
+mod never_plan_test {
+    use super::*;
+    use crate::{plan::plan::MockPlan, record::schema::FieldInfo};
+
+    #[test]
+    fn test_never_plan() {
+        let mut schema = Schema::new();
+        schema.add_field("a", FieldInfo::Integer);
+        let mut child = MockPlan::new();
+        child.expect_get_schema().return_const(schema);
+        // child の scan は開かない
+        child.expect_open_read_scan().never();
+
+        let plan = NeverPlan::new(Box::new(child));
+        assert_eq!(plan.get_record_access_cost().unwrap(), 0);
+        assert_eq!(plan.get_block_access_cost().unwrap(), 0);
+        let mut scan = plan.open_read_scan().unwrap();
+        scan.before_first().unwrap();
+        assert!(!scan.move_next().unwrap());
+        assert!(scan.has_field("a"));
+        assert!(scan.get_val("a").is_err());
+    }
+}
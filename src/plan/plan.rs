@@ -1,9 +1,13 @@
 use anyhow::Result as AnyhowResult;
+#[cfg(test)]
 use mockall::automock;
 use thiserror::Error;
 
 use crate::{
-    query::scan::{ReadScan, UpdateScan},
+    query::{
+        constant::Constant,
+        scan::{ReadScan, UpdateScan},
+    },
     record::schema::Schema,
 };
 
@@ -19,7 +23,7 @@ pub enum PlanError {
  * SQL の query tree の cost を計算するオブジェクトが実装する trait
  * Scan と対応関係を持つので、Scan の実装により cost が変わった場合には、こちらの cost 見積もりも変更する必要がある可能性がある
  */
-#[automock]
+#[cfg_attr(test, automock)]
 pub trait Plan {
     /// Plan から ReadScan オブジェクトを作成する
     fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>>;
@@ -34,4 +38,12 @@ pub trait Plan {
     fn get_distinct_value_estimation(&self, field_name: &str) -> AnyhowResult<u64>;
     /// Plan が持つ schema を返す
     fn get_schema(&self) -> &Schema;
+    /// scan が出力する record の並び順を返す
+    /// record は返り値の先頭の field から順に昇順に並んでいることが保証される。並び順が保証されない場合は空を返す
+    fn ordering(&self) -> Vec<String>;
+    /// scan が出力する record の中で、値が重複しないことが保証されている field を返す
+    fn unique_fields(&self) -> Vec<String>;
+    /// field の値の (最小値, 最大値) の見積もりを返す。分からない場合は None を返す
+    /// 統計情報から求めるので、最後に統計情報を計算した後に変更された値は範囲に含まれていない可能性がある
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)>;
 }
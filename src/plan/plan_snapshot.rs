@@ -0,0 +1,346 @@
+use std::{fmt, iter::Peekable, str::Chars};
+
+use thiserror::Error;
+
+/**
+ * plan tree の形と見積もりを、実行結果を含めずに保存したもの
+ *
+ * ExplainNode::snapshot や Executor::plan_snapshot で作成する
+ * to_json で 1 行の JSON に変換し、from_json で元に戻せるので、
+ * plan をファイルに保存しておいて、planner の変更で plan が変わっていないかを比べるのに使える
+ *
+ * JSON の形式は {"label":"...","estimated_records":N,"estimated_blocks":N,"children":[...]}
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanSnapshot {
+    label: String,
+    estimated_records: u64,
+    estimated_blocks: u64,
+    children: Vec<PlanSnapshot>,
+}
+
+#[derive(Error, Debug)]
+pub enum PlanSnapshotError {
+    #[error("invalid plan snapshot at {position}: {message}")]
+    Invalid { position: usize, message: String },
+}
+
+impl PlanSnapshot {
+    pub fn new(
+        label: String,
+        estimated_records: u64,
+        estimated_blocks: u64,
+        children: Vec<PlanSnapshot>,
+    ) -> Self {
+        Self {
+            label,
+            estimated_records,
+            estimated_blocks,
+            children,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn estimated_records(&self) -> u64 {
+        self.estimated_records
+    }
+
+    pub fn estimated_blocks(&self) -> u64 {
+        self.estimated_blocks
+    }
+
+    pub fn children(&self) -> &[PlanSnapshot] {
+        &self.children
+    }
+
+    /// 見積もりを除いた、plan tree の形だけが等しいかどうか
+    /// 統計情報によって変わる見積もりを無視して、planner が選んだ plan を比べたい場合に使う
+    pub fn same_shape(&self, other: &PlanSnapshot) -> bool {
+        self.label == other.label
+            && self.children.len() == other.children.len()
+            && self
+                .children
+                .iter()
+                .zip(&other.children)
+                .all(|(lhs, rhs)| lhs.same_shape(rhs))
+    }
+
+    /// 空白を含まない 1 行の JSON に変換する
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        self.write_json(&mut json);
+        json
+    }
+
+    /// to_json で変換した JSON から復元する
+    pub fn from_json(json: &str) -> Result<Self, PlanSnapshotError> {
+        let mut reader = JsonReader {
+            chars: json.chars().peekable(),
+            position: 0,
+        };
+        let snapshot = reader.read_snapshot()?;
+        reader.skip_whitespace();
+        if reader.chars.peek().is_some() {
+            return Err(reader.error("unexpected trailing characters"));
+        }
+        Ok(snapshot)
+    }
+
+    fn write_json(&self, json: &mut String) {
+        json.push_str("{\"label\":");
+        write_json_string(json, &self.label);
+        json.push_str(&format!(
+            ",\"estimated_records\":{},\"estimated_blocks\":{},\"children\":[",
+            self.estimated_records, self.estimated_blocks
+        ));
+        for (i, child) in self.children.iter().enumerate() {
+            if i != 0 {
+                json.push(',');
+            }
+            child.write_json(json);
+        }
+        json.push_str("]}");
+    }
+
+    fn fmt_with_indent(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(
+            f,
+            "{}{} (estimated: records={}, blocks={})",
+            "  ".repeat(depth),
+            self.label,
+            self.estimated_records,
+            self.estimated_blocks
+        )?;
+        for child in &self.children {
+            child.fmt_with_indent(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PlanSnapshot {
+    /// ExplainNode と同じく、1 行に 1 node ずつ、子の node を字下げして表示する
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with_indent(f, 0)
+    }
+}
+
+fn write_json_string(json: &mut String, val: &str) {
+    json.push('"');
+    for c in val.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+// PlanSnapshot の JSON だけを読む reader
+struct JsonReader<'a> {
+    chars: Peekable<Chars<'a>>,
+    // 読み進めた文字数。error の位置の表示に使う
+    position: usize,
+}
+
+impl JsonReader<'_> {
+    fn error(&self, message: &str) -> PlanSnapshotError {
+        PlanSnapshotError::Invalid {
+            position: self.position,
+            message: message.to_string(),
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.position += 1;
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), PlanSnapshotError> {
+        self.skip_whitespace();
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            _ => Err(self.error(&format!("expected '{}'", expected))),
+        }
+    }
+
+    // 次の文字が c であれば読み進めて true を返す
+    fn consume(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&c) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn read_snapshot(&mut self) -> Result<PlanSnapshot, PlanSnapshotError> {
+        self.expect('{')?;
+        let mut label = None;
+        let mut estimated_records = None;
+        let mut estimated_blocks = None;
+        let mut children = None;
+        if !self.consume('}') {
+            loop {
+                let key = self.read_string()?;
+                self.expect(':')?;
+                match key.as_str() {
+                    "label" => label = Some(self.read_string()?),
+                    "estimated_records" => estimated_records = Some(self.read_u64()?),
+                    "estimated_blocks" => estimated_blocks = Some(self.read_u64()?),
+                    "children" => children = Some(self.read_children()?),
+                    _ => return Err(self.error(&format!("unknown key {}", key))),
+                }
+                if self.consume('}') {
+                    break;
+                }
+                self.expect(',')?;
+            }
+        }
+        match (label, estimated_records, estimated_blocks, children) {
+            (Some(label), Some(estimated_records), Some(estimated_blocks), Some(children)) => Ok(
+                PlanSnapshot::new(label, estimated_records, estimated_blocks, children),
+            ),
+            _ => Err(self.error("missing key")),
+        }
+    }
+
+    fn read_children(&mut self) -> Result<Vec<PlanSnapshot>, PlanSnapshotError> {
+        self.expect('[')?;
+        let mut children = vec![];
+        if self.consume(']') {
+            return Ok(children);
+        }
+        loop {
+            children.push(self.read_snapshot()?);
+            if self.consume(']') {
+                return Ok(children);
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn read_string(&mut self) -> Result<String, PlanSnapshotError> {
+        self.expect('"')?;
+        let mut val = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(val),
+                Some('\\') => match self.next() {
+                    Some('"') => val.push('"'),
+                    Some('\\') => val.push('\\'),
+                    Some('/') => val.push('/'),
+                    Some('n') => val.push('\n'),
+                    Some('t') => val.push('\t'),
+                    Some('u') => {
+                        let code: String = (0..4).filter_map(|_| self.next()).collect();
+                        let c = u32::from_str_radix(&code, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| self.error("invalid unicode escape"))?;
+                        val.push(c);
+                    }
+                    _ => return Err(self.error("invalid escape")),
+                },
+                Some(c) => val.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn read_u64(&mut self) -> Result<u64, PlanSnapshotError> {
+        self.skip_whitespace();
+        let mut digits = String::new();
+        while let Some(c) = self.chars.peek().copied().filter(char::is_ascii_digit) {
+            digits.push(c);
+            self.next();
+        }
+        digits
+            .parse()
+            .map_err(|_| self.error("expected unsigned integer"))
+    }
+}
+
+#[cfg(test)]
+mod plan_snapshot_test {
+    use super::*;
+
+    fn snapshot() -> PlanSnapshot {
+        PlanSnapshot::new(
+            "ProjectPlan(sname)".to_string(),
+            3,
+            10,
+            vec![PlanSnapshot::new(
+                "SelectPlan(sname = 'a\"b\\c')".to_string(),
+                3,
+                10,
+                vec![PlanSnapshot::new(
+                    "TablePlan(student)".to_string(),
+                    9,
+                    10,
+                    vec![],
+                )],
+            )],
+        )
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let snapshot = snapshot();
+        let json = snapshot.to_json();
+        assert_eq!(
+            json,
+            r#"{"label":"ProjectPlan(sname)","estimated_records":3,"estimated_blocks":10,"children":[{"label":"SelectPlan(sname = 'a\"b\\c')","estimated_records":3,"estimated_blocks":10,"children":[{"label":"TablePlan(student)","estimated_records":9,"estimated_blocks":10,"children":[]}]}]}"#
+        );
+        assert_eq!(PlanSnapshot::from_json(&json).unwrap(), snapshot);
+
+        // 空白や key の順番が違っても読める
+        let json = r#" { "children" : [ ], "estimated_blocks" : 2, "label" : "xA", "estimated_records" : 1 } "#;
+        assert_eq!(
+            PlanSnapshot::from_json(json).unwrap(),
+            PlanSnapshot::new("xA".to_string(), 1, 2, vec![])
+        );
+    }
+
+    #[test]
+    fn test_same_shape() {
+        let snapshot = snapshot();
+        // 見積もりだけが違う場合は同じ形とみなす
+        let json = snapshot
+            .to_json()
+            .replace("\"estimated_records\":9", "\"estimated_records\":90");
+        let other = PlanSnapshot::from_json(&json).unwrap();
+        assert_ne!(snapshot, other);
+        assert!(snapshot.same_shape(&other));
+        assert!(!snapshot.same_shape(&snapshot.children()[0]));
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        for json in [
+            "",
+            "{",
+            r#"{"label":"x","estimated_records":1,"estimated_blocks":2}"#,
+            r#"{"label":"x","estimated_records":-1,"estimated_blocks":2,"children":[]}"#,
+            r#"{"label":"x","estimated_records":1,"estimated_blocks":2,"children":[],"cost":3}"#,
+            r#"{"label":"x","estimated_records":1,"estimated_blocks":2,"children":[]} {}"#,
+        ] {
+            assert!(PlanSnapshot::from_json(json).is_err(), "{}", json);
+        }
+    }
+}
@@ -3,9 +3,10 @@ use crate::plan::plan::Plan;
 use super::reduction_factor::ReductionFactor;
 
 use anyhow::Result as AnyhowResult;
+#[cfg(test)]
 use mockall::automock;
 
-#[automock]
+#[cfg_attr(test, automock)]
 pub trait Plannable {
     // この predicate が満たされるときに、どれだけ scan の結果が絞られるかの推定値を返す
     fn reduction_factor(&self, plan: &dyn Plan) -> AnyhowResult<ReductionFactor>;
@@ -1,13 +1,22 @@
-use crate::{plan::plan::Plan, query::constant::Constant};
+use crate::{
+    plan::plan::Plan,
+    query::{coercion::coerce, constant::Constant, term::ComparisonOperator},
+    record::schema::FieldType,
+};
 
-use super::{plannable::Plannable, reduction_factor::ReductionFactor, term::Term};
+use super::{
+    expression::Expression,
+    plannable::Plannable,
+    reduction_factor::ReductionFactor,
+    term::{EqualTerm, Term},
+};
 use crate::query::predicate::{
     Predicate as PredicateForScan, ProductPredicate as ProductPredicateForScan,
 };
 
 use anyhow::Result as AnyhowResult;
 
-use std::fmt;
+use std::{fmt, ops::Bound};
 
 /**
  * Select の where 句で用いられる条件を表す (A=B AND C<B など)
@@ -18,7 +27,7 @@ pub enum Predicate {
 }
 
 /// 複数の term の論理積を表す predicate
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct ProductPredicate {
     terms: Vec<Term>,
 }
@@ -54,13 +63,30 @@ impl ProductPredicate {
     pub fn new(terms: Vec<Term>) -> Self {
         Self { terms }
     }
+    /// lhs = rhs の条件一つだけからなる predicate を作る
+    pub fn equal(lhs: Expression, rhs: Expression) -> Self {
+        Self::new(vec![Term::Equal(EqualTerm::new(lhs, rhs))])
+    }
+    /// (a, b) = (1, 'x') のような行値どうしの比較を、要素ごとの等号条件の論理積 (a = 1 and b = 'x') に展開する
+    /// 要素の数が一致しない場合や、要素がない場合は None を返す
+    pub fn equal_rows(lhs: Vec<Expression>, rhs: Vec<Expression>) -> Option<Self> {
+        if lhs.is_empty() || lhs.len() != rhs.len() {
+            return None;
+        }
+        Some(Self::new(
+            lhs.into_iter()
+                .zip(rhs)
+                .map(|(lhs, rhs)| Term::Equal(EqualTerm::new(lhs, rhs)))
+                .collect(),
+        ))
+    }
     /// 引数で与えた field と対になっている (等号条件のついている) constant の値を返す
     pub fn equates_with_constant(&self, field_name: &str) -> Option<Constant> {
         for term in &self.terms {
-            // Term に EqualTerm しかないので if let で match する必要がない
-            let Term::Equal(equal_term) = term;
-            if let Some(constant) = equal_term.equates_with_constant(field_name) {
-                return Some(constant);
+            if let Term::Equal(equal_term) = term {
+                if let Some(constant) = equal_term.equates_with_constant(field_name) {
+                    return Some(constant);
+                }
             }
         }
         None
@@ -69,15 +95,102 @@ impl ProductPredicate {
     /// 引数で与えた field と対になっている (等号条件のついている) field の値を返す
     pub fn equates_with_field(&self, field_name: &str) -> Option<String> {
         for term in &self.terms {
-            // Term に EqualTerm しかないので if let で match する必要がない
-            let Term::Equal(equal_term) = term;
-            if let Some(field) = equal_term.equates_with_field(field_name) {
-                return Some(field);
+            if let Term::Equal(equal_term) = term {
+                if let Some(field) = equal_term.equates_with_field(field_name) {
+                    return Some(field);
+                }
             }
         }
         None
     }
 
+    /// 引数で与えた field の値を、定数との大小比較の条件で制限した範囲を返す
+    /// 定数は to 型に変換してから比べる。to 型に変換できない定数との比較と <> の条件は範囲に含めない
+    /// 範囲を制限する条件が無い場合は None を返す
+    pub fn range_of_field(
+        &self,
+        field_name: &str,
+        to: FieldType,
+    ) -> Option<(Bound<Constant>, Bound<Constant>)> {
+        let mut lo = Bound::Unbounded;
+        let mut hi = Bound::Unbounded;
+        for term in &self.terms {
+            let Term::Comparison(comparison_term) = term else {
+                continue;
+            };
+            let Some((op, constant)) = comparison_term.compares_with_constant(field_name) else {
+                continue;
+            };
+            let Some(constant) = coerce(&constant, to) else {
+                continue;
+            };
+            match op {
+                ComparisonOperator::Greater => tighten_lower(&mut lo, Bound::Excluded(constant)),
+                ComparisonOperator::GreaterEqual => {
+                    tighten_lower(&mut lo, Bound::Included(constant))
+                }
+                ComparisonOperator::Less => tighten_upper(&mut hi, Bound::Excluded(constant)),
+                ComparisonOperator::LessEqual => tighten_upper(&mut hi, Bound::Included(constant)),
+                ComparisonOperator::NotEqual => {}
+            }
+        }
+        if lo == Bound::Unbounded && hi == Bound::Unbounded {
+            None
+        } else {
+            Some((lo, hi))
+        }
+    }
+
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+
+    /// 常に真になる term と重複した term を取り除いた predicate を返す
+    /// 常に偽になる term がある場合は、predicate 全体が偽になるので、その term だけからなる predicate を返す
+    pub fn simplify(&self) -> ProductPredicate {
+        let mut terms: Vec<Term> = vec![];
+        for term in &self.terms {
+            match term.evaluate_constant() {
+                Some(true) => continue,
+                Some(false) => return ProductPredicate::new(vec![term.clone()]),
+                None => {}
+            }
+            if !terms.iter().any(|t| t.is_equivalent(term)) {
+                terms.push(term.clone());
+            }
+        }
+        ProductPredicate::new(terms)
+    }
+
+    /// 常に偽になる term を含むかどうか
+    pub fn is_contradiction(&self) -> bool {
+        self.terms
+            .iter()
+            .any(|term| term.evaluate_constant() == Some(false))
+    }
+
+    /// この predicate と other の論理積を返す
+    pub fn conjoin(&self, other: &ProductPredicate) -> ProductPredicate {
+        let mut terms = self.terms.clone();
+        terms.extend(other.terms.iter().cloned());
+        ProductPredicate::new(terms)
+    }
+
+    /// predicate の中で参照されている field 名を返す
+    pub fn fields(&self) -> Vec<String> {
+        self.terms.iter().flat_map(|term| term.fields()).collect()
+    }
+
+    /// field 名 old_name を new_name に置き換えた predicate を返す
+    pub fn rename_field(&self, old_name: &str, new_name: &str) -> ProductPredicate {
+        ProductPredicate::new(
+            self.terms
+                .iter()
+                .map(|term| term.rename_field(old_name, new_name))
+                .collect(),
+        )
+    }
+
     /// scan をする際に必要な Predicate に変換する
     pub fn convert_for_scan(&self) -> ProductPredicateForScan {
         ProductPredicateForScan::new(
@@ -89,6 +202,32 @@ impl ProductPredicate {
     }
 }
 
+// 下限 lo を、new の方が狭い場合は new に置き換える
+fn tighten_lower(lo: &mut Bound<Constant>, new: Bound<Constant>) {
+    let is_tighter = match (&*lo, &new) {
+        (Bound::Unbounded, _) => true,
+        (Bound::Included(old), Bound::Included(val) | Bound::Excluded(val)) => val >= old,
+        (Bound::Excluded(old), Bound::Included(val) | Bound::Excluded(val)) => val > old,
+        (_, Bound::Unbounded) => false,
+    };
+    if is_tighter {
+        *lo = new;
+    }
+}
+
+// 上限 hi を、new の方が狭い場合は new に置き換える
+fn tighten_upper(hi: &mut Bound<Constant>, new: Bound<Constant>) {
+    let is_tighter = match (&*hi, &new) {
+        (Bound::Unbounded, _) => true,
+        (Bound::Included(old), Bound::Included(val) | Bound::Excluded(val)) => val <= old,
+        (Bound::Excluded(old), Bound::Included(val) | Bound::Excluded(val)) => val < old,
+        (_, Bound::Unbounded) => false,
+    };
+    if is_tighter {
+        *hi = new;
+    }
+}
+
 impl fmt::Display for ProductPredicate {
     /// SQL の where 句のように表示する
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
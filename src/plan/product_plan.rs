@@ -1,5 +1,6 @@
 use crate::{
     query::{
+        constant::Constant,
         product_scan::ProductScan,
         scan::{ReadScan, UpdateScan},
     },
@@ -35,6 +36,21 @@ impl Plan for ProductPlan {
     fn get_schema(&self) -> &Schema {
         &self.schema
     }
+    fn ordering(&self) -> Vec<String> {
+        // p1 の 1 record ごとに p2 の全 record を走査するので、p1 の並び順は保たれる
+        self.p1.ordering()
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        // p1 の各 record は p2 の record の数だけ繰り返し出力されるので、一意性は保証できない
+        vec![]
+    }
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        if self.p1.get_schema().has_field(field_name) {
+            self.p1.get_value_range(field_name)
+        } else {
+            self.p2.get_value_range(field_name)
+        }
+    }
     fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>> {
         let s1 = self.p1.open_read_scan()?;
         let s2 = self.p2.open_read_scan()?;
@@ -128,4 +144,18 @@ mod product_plan_test {
             .get_distinct_value_estimation("field3")
             .is_err());
     }
+    #[test]
+    fn ordering_and_unique_fields_test() {
+        let mut p1 = MockPlan::new();
+        p1.expect_get_schema().return_const(Schema::new());
+        p1.expect_ordering()
+            .returning(|| vec!["field1".to_string()]);
+        p1.expect_unique_fields()
+            .returning(|| vec!["field1".to_string()]);
+        let p2 = setup_plan(40, 2000, 50, "field2".to_string());
+        let product_plan = ProductPlan::new(Box::new(p1), p2).unwrap();
+        // 外側の plan の並び順は保たれるが、一意性は保たれない
+        assert_eq!(product_plan.ordering(), vec!["field1".to_string()]);
+        assert!(product_plan.unique_fields().is_empty());
+    }
 }
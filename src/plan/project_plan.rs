@@ -1,5 +1,6 @@
 use crate::{
     query::{
+        constant::Constant,
         project_scan::ProjectScan,
         scan::{ReadScan, Scan, UpdateScan},
     },
@@ -19,6 +20,24 @@ impl Plan for ProjectPlan {
     fn get_schema(&self) -> &Schema {
         &self.schema
     }
+    fn ordering(&self) -> Vec<String> {
+        // projection で落とされた field 以降の並び順は外からは意味を持たない
+        self.child
+            .ordering()
+            .into_iter()
+            .take_while(|field| self.schema.has_field(field))
+            .collect()
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        self.child
+            .unique_fields()
+            .into_iter()
+            .filter(|field| self.schema.has_field(field))
+            .collect()
+    }
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        self.child.get_value_range(field_name)
+    }
     fn get_block_access_cost(&self) -> AnyhowResult<u64> {
         self.child.get_block_access_cost()
     }
@@ -32,14 +51,14 @@ impl Plan for ProjectPlan {
         let scan = self.child.open_read_scan()?;
         Ok(Box::new(ProjectScan::new(
             Scan::ReadOnly(scan),
-            self.schema.fields().iter().cloned().collect(),
+            self.schema.fields_iter().map(str::to_string).collect(),
         )?))
     }
     fn open_update_scan(&self) -> AnyhowResult<Box<dyn UpdateScan>> {
         let scan = self.child.open_update_scan()?;
         Ok(Box::new(ProjectScan::new(
             Scan::Updatable(scan),
-            self.schema.fields().iter().cloned().collect(),
+            self.schema.fields_iter().map(str::to_string).collect(),
         )?))
     }
 }
@@ -62,3 +81,31 @@ impl ProjectPlan {
         Ok(ProjectPlan { child, schema })
     }
 }
+
+#[cfg(test)]
+mod project_plan_test {
+    use crate::{plan::plan::MockPlan, record::schema::FieldInfo};
+
+    use super::*;
+
+    #[test]
+    fn ordering_and_unique_fields_test() {
+        let mut child = MockPlan::new();
+        let mut schema = Schema::new();
+        schema.add_field("a", FieldInfo::Integer);
+        schema.add_field("b", FieldInfo::Integer);
+        schema.add_field("c", FieldInfo::Integer);
+        child.expect_get_schema().return_const(schema);
+        child
+            .expect_ordering()
+            .returning(|| vec!["a".to_string(), "b".to_string(), "c".to_string()]);
+        child
+            .expect_unique_fields()
+            .returning(|| vec!["b".to_string(), "c".to_string()]);
+        let plan =
+            ProjectPlan::new(Box::new(child), vec!["a".to_string(), "c".to_string()]).unwrap();
+        // b が projection で落とされるので、並び順として意味があるのは a までになる
+        assert_eq!(plan.ordering(), vec!["a".to_string()]);
+        assert_eq!(plan.unique_fields(), vec!["c".to_string()]);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
     query::{
+        constant::Constant,
         scan::{ReadScan, Scan},
         select_scan::SelectScan,
     },
@@ -51,6 +52,16 @@ impl Plan for SelectPlan {
     fn get_schema(&self) -> &Schema {
         self.child.get_schema()
     }
+    fn ordering(&self) -> Vec<String> {
+        // record を間引くだけなので、並び順と一意性は元の Plan のものが保たれる
+        self.child.ordering()
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        self.child.unique_fields()
+    }
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        self.child.get_value_range(field_name)
+    }
     fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>> {
         let scan = self.child.open_read_scan()?;
         Ok(Box::new(SelectScan::new(
@@ -80,9 +91,9 @@ mod select_plan_test {
             expression::Expression,
             plan::MockPlan,
             predicate::ProductPredicate,
-            term::{EqualTerm, Term},
+            term::{ComparisonTerm, EqualTerm, Term},
         },
-        query::constant::Constant,
+        query::{constant::Constant, term::ComparisonOperator},
         record::schema::FieldInfo,
     };
 
@@ -262,4 +273,88 @@ mod select_plan_test {
             20
         );
     }
+    #[test]
+    fn record_access_cost_test_for_range_condition() {
+        let cost = |lhs: Expression, op: ComparisonOperator, rhs: Expression| {
+            let mut p = MockPlan::new();
+            p.expect_get_record_access_cost().returning(|| Ok(1000));
+            p.expect_get_distinct_value_estimation()
+                .returning(|_| Ok(10));
+            // field1 の値は 1 から 100 の範囲にあり、field3 の値は 0.0 から 10.0 の範囲にある
+            // field2 の範囲は分からない
+            p.expect_get_value_range()
+                .returning(|field_name| match field_name {
+                    "field1" => Some((Constant::Int(1), Constant::Int(100))),
+                    "field3" => Some((Constant::Double(0.0), Constant::Double(10.0))),
+                    _ => None,
+                });
+            let predicate = Predicate::Product(ProductPredicate::new(vec![Term::Comparison(
+                ComparisonTerm::new(lhs, op, rhs),
+            )]));
+            SelectPlan::new(Box::new(p), Box::new(predicate))
+                .get_record_access_cost()
+                .unwrap()
+        };
+        let field = |name: &str| Expression::Field(name.to_string());
+        let int = |val: i32| Expression::Constant(Constant::Int(val));
+
+        // 値が範囲に一様に分布しているとみなす
+        assert_eq!(
+            cost(field("field1"), ComparisonOperator::Less, int(26)),
+            250
+        );
+        assert_eq!(
+            cost(field("field1"), ComparisonOperator::GreaterEqual, int(76)),
+            250
+        );
+        // 左右を入れ替えても同じ見積もりになる
+        assert_eq!(
+            cost(int(76), ComparisonOperator::LessEqual, field("field1")),
+            250
+        );
+        // 範囲全体を含む条件は絞り込まず、範囲外の条件を満たす record は無い
+        assert_eq!(
+            cost(field("field1"), ComparisonOperator::LessEqual, int(200)),
+            1000
+        );
+        assert_eq!(
+            cost(field("field1"), ComparisonOperator::Greater, int(100)),
+            0
+        );
+        // 範囲が分からない場合は 1/3 に絞られるとみなす
+        assert_eq!(cost(field("field2"), ComparisonOperator::Less, int(5)), 333);
+        assert_eq!(
+            cost(field("field1"), ComparisonOperator::Less, field("field2")),
+            333
+        );
+        // <> は 1 つの値だけを除く
+        assert_eq!(
+            cost(field("field2"), ComparisonOperator::NotEqual, int(5)),
+            900
+        );
+
+        // double の範囲は連続した値の区間として扱う
+        let double = |val: f64| Expression::Constant(Constant::Double(val));
+        assert_eq!(
+            cost(field("field3"), ComparisonOperator::Less, double(2.5)),
+            250
+        );
+        assert_eq!(
+            cost(field("field3"), ComparisonOperator::LessEqual, int(5)),
+            500
+        );
+        // 区間の端の値だけに一致する条件でも、distinct value 1 つ分は残る
+        assert_eq!(
+            cost(
+                field("field3"),
+                ComparisonOperator::GreaterEqual,
+                double(10.0)
+            ),
+            100
+        );
+        assert_eq!(
+            cost(field("field3"), ComparisonOperator::Greater, double(10.0)),
+            0
+        );
+    }
 }
@@ -0,0 +1,324 @@
+use std::{cell::RefCell, cmp::Ordering, rc::Rc, sync::Arc};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use crate::{
+    query::{
+        constant::Constant,
+        record_comparator::{RecordComparator, SortDirection},
+        scan::{ReadScan, UpdateScan},
+        sort_scan::SortScan,
+    },
+    record::{
+        layout::Layout,
+        schema::Schema,
+        table_scan_factory::{TableScanFactory, TableScanFactoryImpl},
+    },
+    tx::{temp_file_manager::TempFileManager, transaction::Transaction},
+};
+
+use super::plan::{Plan, PlanError};
+
+/**
+ * child の record を sort_keys の順に並べ替える plan
+ *
+ * sort_keys の field ごとに昇順か降順かを指定できる
+ * scan を開く時に、child の record を整列済みの区間 (run) ごとに temp table へ書き出し、
+ * run が 2 つ以下になるまで 2 つずつ merge した新しい temp table を作る (外部 merge sort)
+ * 最後に残った run は SortScan が merge しながら読むので、メモリ上に保持する record は run ごとに 1 つだけ
+ * temp table のファイルは transaction が終わった時に TempFileManager が削除する
+ */
+pub struct SortPlan {
+    child: Box<dyn Plan>,
+    sort_keys: Vec<(String, SortDirection)>,
+    layout: Layout,
+    temp_file_manager: Arc<TempFileManager>,
+    tx: Rc<RefCell<Transaction>>,
+}
+
+impl Plan for SortPlan {
+    fn get_block_access_cost(&self) -> AnyhowResult<u64> {
+        Self::estimate_block_access_cost(self.child.as_ref(), self.tx.borrow().block_size())
+    }
+    fn get_record_access_cost(&self) -> AnyhowResult<u64> {
+        self.child.get_record_access_cost()
+    }
+    fn get_distinct_value_estimation(&self, field_name: &str) -> AnyhowResult<u64> {
+        self.child.get_distinct_value_estimation(field_name)
+    }
+    fn get_schema(&self) -> &Schema {
+        self.child.get_schema()
+    }
+    fn ordering(&self) -> Vec<String> {
+        // ordering は昇順に並んでいる field を表すので、降順の field より後ろは含めない
+        self.sort_keys
+            .iter()
+            .take_while(|(_, direction)| *direction == SortDirection::Asc)
+            .map(|(field, _)| field.clone())
+            .collect()
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        self.child.unique_fields()
+    }
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        self.child.get_value_range(field_name)
+    }
+    fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>> {
+        Ok(Box::new(self.open_sort_scan()?))
+    }
+    fn open_update_scan(&self) -> AnyhowResult<Box<dyn UpdateScan>> {
+        Err(anyhow!(PlanError::InvalidCall(
+            "sort plan does not support update scan".to_string()
+        )))
+    }
+}
+
+impl SortPlan {
+    /// child の record を sort_fields の昇順に並べ替える
+    pub fn new(
+        child: Box<dyn Plan>,
+        sort_fields: Vec<String>,
+        temp_file_manager: Arc<TempFileManager>,
+        tx: Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Self> {
+        let sort_keys = sort_fields
+            .into_iter()
+            .map(|field| (field, SortDirection::Asc))
+            .collect();
+        Self::with_directions(child, sort_keys, temp_file_manager, tx)
+    }
+
+    /// child の record を、field ごとに指定した向きで並べ替える
+    pub fn with_directions(
+        child: Box<dyn Plan>,
+        sort_keys: Vec<(String, SortDirection)>,
+        temp_file_manager: Arc<TempFileManager>,
+        tx: Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Self> {
+        for (field, _) in &sort_keys {
+            if !child.get_schema().has_field(field) {
+                return Err(PlanError::InvalidCall(format!("field {} not found", field)).into());
+            }
+        }
+        let layout = Layout::new(child.get_schema().clone())?;
+        Ok(Self {
+            child,
+            sort_keys,
+            layout,
+            temp_file_manager,
+            tx,
+        })
+    }
+
+    /// child の record を並べ替え、位置を保存して戻せる SortScan として開く
+    pub fn open_sort_scan(&self) -> AnyhowResult<SortScan> {
+        let mut runs = self.split_into_runs()?;
+        while runs.len() > 2 {
+            runs = self.merge_runs(runs)?;
+        }
+        let runs = runs
+            .iter()
+            .map(|run| self.open_run(run))
+            .collect::<AnyhowResult<Vec<_>>>()?;
+        SortScan::new(runs, self.comparator())
+    }
+
+    fn comparator(&self) -> RecordComparator {
+        RecordComparator::new(self.sort_keys.clone())
+    }
+
+    /// child を並べ替える SortPlan の block access の cost の見積もり
+    /// child を 1 回読み、run を temp table に書き出して最後に読み直すとみなす
+    /// merge の pass の回数は child の並び方によるので、見積もりには含めない
+    pub fn estimate_block_access_cost(child: &dyn Plan, block_size: usize) -> AnyhowResult<u64> {
+        let layout = Layout::new(child.get_schema().clone())?;
+        let records_per_block = layout.slots_per_block(block_size).max(1) as u64;
+        let temp_blocks = child.get_record_access_cost()?.div_ceil(records_per_block);
+        Ok(child.get_block_access_cost()? + 2 * temp_blocks)
+    }
+
+    // child を先頭から読み、整列済みの区間ごとに temp table へ書き出して、その名前を返す
+    // child に record が無い場合も、空の run を 1 つ返す
+    fn split_into_runs(&self) -> AnyhowResult<Vec<String>> {
+        let comparator = self.comparator();
+        let mut src = self.child.open_read_scan()?;
+        src.before_first()?;
+        let mut runs = vec![self.new_run()?];
+        let mut dst = self.open_run(&runs[0])?;
+        let mut is_empty = true;
+        while src.move_next()? {
+            // 直前に書き出した record より小さい record から、新しい run を始める
+            if !is_empty && comparator.compare(src.as_ref(), dst.as_ref())? == Ordering::Less {
+                runs.push(self.new_run()?);
+                dst = self.open_run(runs.last().unwrap())?;
+            }
+            self.copy(src.as_ref(), dst.as_mut())?;
+            is_empty = false;
+        }
+        Ok(runs)
+    }
+
+    // run を 2 つずつ merge し、merge した後の run の名前を返す
+    fn merge_runs(&self, runs: Vec<String>) -> AnyhowResult<Vec<String>> {
+        let comparator = self.comparator();
+        let mut merged = vec![];
+        for pair in runs.chunks(2) {
+            let [run1, run2] = pair else {
+                merged.push(pair[0].clone());
+                continue;
+            };
+            let (mut s1, mut s2) = (self.open_run(run1)?, self.open_run(run2)?);
+            let result = self.new_run()?;
+            let mut dst = self.open_run(&result)?;
+            let mut has_more1 = s1.move_next()?;
+            let mut has_more2 = s2.move_next()?;
+            while has_more1 || has_more2 {
+                let take_first = has_more1
+                    && (!has_more2
+                        || comparator.compare(s1.as_ref(), s2.as_ref())? != Ordering::Greater);
+                if take_first {
+                    self.copy(s1.as_ref(), dst.as_mut())?;
+                    has_more1 = s1.move_next()?;
+                } else {
+                    self.copy(s2.as_ref(), dst.as_mut())?;
+                    has_more2 = s2.move_next()?;
+                }
+            }
+            merged.push(result);
+        }
+        Ok(merged)
+    }
+
+    fn new_run(&self) -> AnyhowResult<String> {
+        Ok(self.temp_file_manager.next_table_name(&self.tx.borrow())?)
+    }
+
+    fn open_run(&self, run: &str) -> AnyhowResult<Box<dyn UpdateScan>> {
+        let mut scan = TableScanFactoryImpl::new().create(&self.tx, run, &self.layout)?;
+        scan.before_first()?;
+        Ok(scan)
+    }
+
+    // src が指している record を dst の末尾に追加する
+    fn copy(&self, src: &dyn ReadScan, dst: &mut dyn UpdateScan) -> AnyhowResult<()> {
+        dst.insert()?;
+        for field in self.layout.schema().fields_iter() {
+            dst.set_val(field, &src.get_val(field)?)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod sort_plan_test {
+    use super::*;
+    use crate::{
+        buffer::buffer_manager::BufferManager,
+        file::file_manager::FileManager,
+        log::log_manager::LogManager,
+        plan::plan::MockPlan,
+        query::values_scan::ValuesScan,
+        record::schema::FieldInfo,
+        tx::{concurrency::lock_table::LockTable, transaction::TransactionFactory},
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_sort() {
+        let dir = tempdir().unwrap();
+        let file_manager = Arc::new(FileManager::new(dir.path(), 400));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager.clone(),
+            log_manager.clone(),
+            8,
+            Some(10),
+        ));
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        let temp_file_manager = Arc::new(TempFileManager::new(
+            file_manager.clone(),
+            buffer_manager.clone(),
+        ));
+        let factory =
+            TransactionFactory::new(file_manager, log_manager, buffer_manager, lock_table);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+
+        // 整列済みの区間が何度も途切れるように並べた record
+        let keys = (0..100).map(|i| (i * 37) % 100).collect::<Vec<i32>>();
+        let child = || -> Box<dyn Plan> {
+            let mut schema = Schema::new();
+            schema.add_field("a", FieldInfo::Integer);
+            schema.add_field("b", FieldInfo::String(8));
+            let rows = keys
+                .iter()
+                .map(|&key| vec![Constant::Int(key % 10), Constant::from(format!("v{}", key))])
+                .collect::<Vec<_>>();
+            let mut plan = MockPlan::new();
+            plan.expect_get_schema().return_const(schema);
+            plan.expect_open_read_scan().returning(move || {
+                Ok(Box::new(ValuesScan::new(
+                    vec!["a".to_string(), "b".to_string()],
+                    rows.clone(),
+                )))
+            });
+            Box::new(plan)
+        };
+        let plan = SortPlan::new(
+            child(),
+            vec!["a".to_string(), "b".to_string()],
+            temp_file_manager.clone(),
+            tx.clone(),
+        )
+        .unwrap();
+        assert_eq!(plan.ordering(), vec!["a".to_string(), "b".to_string()]);
+
+        let mut scan = plan.open_read_scan().unwrap();
+        let mut rows = vec![];
+        while scan.move_next().unwrap() {
+            rows.push((scan.get_int("a").unwrap(), scan.get_string("b").unwrap()));
+        }
+        let mut expected = keys
+            .iter()
+            .map(|&key| (key % 10, format!("v{}", key)))
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(rows, expected);
+        drop(scan);
+
+        // a の降順、a が等しい場合は b の昇順に並べる
+        let plan = SortPlan::with_directions(
+            child(),
+            vec![
+                ("a".to_string(), SortDirection::Desc),
+                ("b".to_string(), SortDirection::Asc),
+            ],
+            temp_file_manager.clone(),
+            tx.clone(),
+        )
+        .unwrap();
+        assert!(plan.ordering().is_empty());
+        let mut scan = plan.open_read_scan().unwrap();
+        let mut rows = vec![];
+        while scan.move_next().unwrap() {
+            rows.push((scan.get_int("a").unwrap(), scan.get_string("b").unwrap()));
+        }
+        expected.sort_by(|(a1, b1), (a2, b2)| a2.cmp(a1).then(b1.cmp(b2)));
+        assert_eq!(rows, expected);
+        drop(scan);
+
+        // 存在しない field では並べ替えられない
+        assert!(SortPlan::new(
+            child(),
+            vec!["c".to_string()],
+            temp_file_manager.clone(),
+            tx.clone()
+        )
+        .is_err());
+
+        // run は temp table に書き出され、transaction が終わると削除される
+        let txnum = tx.borrow().tx_num();
+        assert!(temp_file_manager.table_names(txnum).unwrap().len() > 2);
+        tx.borrow_mut().commit().unwrap();
+        assert!(temp_file_manager.table_names(txnum).unwrap().is_empty());
+    }
+}
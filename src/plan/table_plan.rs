@@ -4,72 +4,89 @@ use anyhow::Result as AnyhowResult;
 
 use crate::{
     metadata::{metadata_manager::MetadataManager, stat_info::StatInfo},
-    query::scan::ReadScan,
+    query::{coercion::coerce, constant::Constant, memory_budget::MemoryBudget, scan::ReadScan},
     record::{
         layout::Layout,
+        partition::PartitionSpec,
         schema::Schema,
         table_scan_factory::{TableScanFactory, TableScanFactoryImpl},
     },
-    tx::transaction::Transaction,
+    tx::{temp_file_manager::TempFileManager, transaction::Transaction},
 };
 
-use super::plan::{Plan, PlanError};
+use super::{
+    plan::{Plan, PlanError},
+    predicate::ProductPredicate,
+};
 
 pub struct TablePlan {
     table_name: String,
     layout: Layout,
+    // 設定されている場合、read scan ではこの layout に含まれる field だけを読む
+    projected_layout: Option<Layout>,
     stat_info: HashMap<String, StatInfo>,
     tx: Rc<RefCell<Transaction>>,
 }
 
 impl Plan for TablePlan {
     fn get_block_access_cost(&self) -> AnyhowResult<u64> {
-        Ok(self
-            .stat_info
-            .values()
-            // 最初にあった値を取り出す
-            .find(|_| true)
-            .map(|stat| stat.get_num_blocks())
-            .ok_or_else(|| {
-                PlanError::InvalidCall(format!(
-                    "no block access cost found for table {}",
-                    self.table_name
-                ))
-            })?)
+        let num_blocks = self.table_stat().get_num_blocks();
+        // partition の一部だけを読む場合は、block が partition に均等に分かれているとみなす
+        Ok(match self.layout.partition() {
+            Some(partition) => {
+                num_blocks * partition.selected_partitions().len() as u64
+                    / partition.partition_count() as u64
+            }
+            None => num_blocks,
+        })
     }
     fn get_record_access_cost(&self) -> AnyhowResult<u64> {
-        Ok(self
-            .stat_info
-            .values()
-            // 最初にあった値を取り出す
-            .find(|_| true)
-            .map(|stat| stat.get_num_records())
-            .ok_or_else(|| {
-                PlanError::InvalidCall(format!(
-                    "no record access cost found for table {}",
-                    self.table_name
-                ))
-            })?)
+        Ok(self.table_stat().get_num_records())
     }
     fn get_distinct_value_estimation(&self, field_name: &str) -> AnyhowResult<u64> {
-        Ok(self
-            .stat_info
-            .get(field_name)
-            .map(|stat| stat.get_num_distinct_values())
-            .ok_or_else(|| {
-                PlanError::InvalidCall(format!(
-                    "no distinct value estimation found for field {} in table {}",
-                    field_name, self.table_name
-                ))
-            })?)
+        if let Some(stat) = self.stat_info.get(field_name) {
+            return Ok(stat.get_num_distinct_values());
+        }
+        if !self.layout.schema().has_field(field_name) {
+            return Err(PlanError::InvalidCall(format!(
+                "no distinct value estimation found for field {} in table {}",
+                field_name, self.table_name
+            ))
+            .into());
+        }
+        Ok(StatInfo::missing().get_num_distinct_values())
     }
     fn get_schema(&self) -> &Schema {
-        self.layout.schema()
+        match &self.projected_layout {
+            Some(projected_layout) => projected_layout.schema(),
+            None => self.layout.schema(),
+        }
+    }
+    fn ordering(&self) -> Vec<String> {
+        // record は空いている slot に挿入されるので、並び順は保証されない
+        vec![]
+    }
+    fn unique_fields(&self) -> Vec<String> {
+        // 現状では table に一意性の制約を付ける方法がない
+        vec![]
+    }
+    fn get_value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        let (min, max) = self.stat_info.get(field_name)?.get_range()?;
+        Some((min.clone(), max.clone()))
     }
     fn open_read_scan(&self) -> AnyhowResult<Box<dyn ReadScan>> {
         let table_scan_factory = TableScanFactoryImpl::new();
-        let table_scan =
-            table_scan_factory.create_read_only(&self.tx, &self.table_name, &self.layout)?;
+        let table_scan = match &self.projected_layout {
+            Some(projected_layout) => table_scan_factory.create_read_only_with_projection(
+                &self.tx,
+                &self.table_name,
+                &self.layout,
+                &projected_layout.schema().fields(),
+            )?,
+            None => {
+                table_scan_factory.create_read_only(&self.tx, &self.table_name, &self.layout)?
+            }
+        };
         Ok(table_scan)
     }
     fn open_update_scan(&self) -> AnyhowResult<Box<dyn crate::query::scan::UpdateScan>> {
@@ -82,18 +99,220 @@ impl Plan for TablePlan {
 impl TablePlan {
     /// table plan の初期化
     /// transaction は metadata manager の内容にアクセスするために必要
+    /// 統計情報を計算する場合は、その間にメモリ上に保持する値を budget に計上する
+    /// table_name が temp table を指す場合、その実体の table を読み書きする
     pub fn new(
         table_name: String,
         metadata_manager: &dyn MetadataManager,
+        budget: &MemoryBudget,
         tx: Rc<RefCell<Transaction>>,
     ) -> AnyhowResult<TablePlan> {
+        let table_name = metadata_manager.resolve_table_name(&table_name, &tx);
         let layout = metadata_manager.get_layout(&table_name, &tx)?;
-        let stat_info = metadata_manager.get_table_stat(&table_name, &tx)?;
+        let stat_info = metadata_manager.get_table_stat(&table_name, budget, &tx)?;
         Ok(TablePlan {
             table_name,
             layout,
+            projected_layout: None,
             stat_info,
             tx,
         })
     }
+
+    /// LSN が lsn の時点で commit 済だった table の内容を temp table のファイルに再現し、それを読む table plan を作成する
+    /// 統計情報は現在の table のものを使う
+    /// 再現した temp table のファイルは、temp_file_manager によって transaction の終了時に削除される
+    pub fn as_of(
+        table_name: String,
+        lsn: u64,
+        metadata_manager: &dyn MetadataManager,
+        temp_file_manager: &TempFileManager,
+        budget: &MemoryBudget,
+        tx: Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<TablePlan> {
+        let mut plan = TablePlan::new(table_name, metadata_manager, budget, tx)?;
+        let snapshot_name = temp_file_manager.next_table_name(&plan.tx.borrow())?;
+        // partition に分けた table の場合は、partition ごとのファイルをそれぞれ再現する
+        let table_names = match plan.layout.partition() {
+            Some(partition) => (0..partition.partition_count())
+                .map(|p| {
+                    (
+                        PartitionSpec::partition_table_name(&plan.table_name, p),
+                        PartitionSpec::partition_table_name(&snapshot_name, p),
+                    )
+                })
+                .collect(),
+            None => vec![(plan.table_name.clone(), snapshot_name.clone())],
+        };
+        {
+            let tx = plan.tx.borrow();
+            for (table_name, snapshot_table_name) in table_names {
+                if snapshot_table_name != snapshot_name {
+                    temp_file_manager.register_table(&tx, &snapshot_table_name)?;
+                }
+                let filename = format!("{}.tbl", table_name);
+                let snapshot_filename = format!("{}.tbl", snapshot_table_name);
+                // 再現先のファイルは元の table と同じ layout で読むので、block size や圧縮の設定も揃える
+                for filename in [&filename, &snapshot_filename] {
+                    if let Some(block_size) = plan.layout.block_size() {
+                        tx.set_block_size(filename, block_size)?;
+                    }
+                    if plan.layout.storage().compressed {
+                        tx.set_compressed(filename)?;
+                    }
+                }
+                tx.copy_file_as_of(&filename, &snapshot_filename, lsn)?;
+            }
+        }
+        plan.table_name = snapshot_name;
+        // 統計情報の範囲は現在の値から求めたもので、過去の時点の値を含むとは限らない
+        plan.stat_info.values_mut().for_each(StatInfo::clear_range);
+        Ok(plan)
+    }
+
+    /// predicate を満たす record をまとめて削除し、削除した record の数を返す
+    pub fn delete_where(&self, predicate: &ProductPredicate) -> AnyhowResult<u64> {
+        let table_scan_factory = TableScanFactoryImpl::new();
+        let Some(partition) = self.layout.partition() else {
+            let mut table_scan =
+                table_scan_factory.create_internal(&self.tx, &self.table_name, &self.layout)?;
+            return table_scan.delete_where(&predicate.convert_for_scan(), &predicate.fields());
+        };
+        let layout = self.layout.without_partition();
+        let mut delete_count = 0;
+        for p in partition.selected_partitions() {
+            let partition_name = PartitionSpec::partition_table_name(&self.table_name, p);
+            let mut table_scan =
+                table_scan_factory.create_internal(&self.tx, &partition_name, &layout)?;
+            delete_count +=
+                table_scan.delete_where(&predicate.convert_for_scan(), &predicate.fields())?;
+        }
+        Ok(delete_count)
+    }
+
+    /// partition に分けた table で、predicate が partition を決める field の値を定数に限定している場合、その値の record がある partition だけを読む
+    pub fn with_partition_pruning(mut self, predicate: &ProductPredicate) -> TablePlan {
+        let Some(partition) = self.layout.partition() else {
+            return self;
+        };
+        // 保存する時と同じ値で partition を決めるように、値を field の型に変換してから使う
+        // (int の定数と bigint の field を比べる場合など)
+        let field_type = self.layout.schema().info(partition.field());
+        if let Some(val) = predicate
+            .equates_with_constant(partition.field())
+            .and_then(|val| match field_type {
+                Some(info) => coerce(&val, info.get_type()),
+                None => Some(val),
+            })
+        {
+            let pruned = partition.clone().prune(&val);
+            self.layout = self.layout.clone().with_partition(pruned.clone());
+            self.projected_layout = self
+                .projected_layout
+                .map(|layout| layout.with_partition(pruned));
+        }
+        self
+    }
+
+    /// table の全ての field の統計情報があるかどうか
+    /// false の場合、統計情報がない部分の見積もりには StatInfo::missing の値を使っていて、当てにならない
+    pub fn has_stats(&self) -> bool {
+        self.layout
+            .schema()
+            .fields_iter()
+            .all(|field| self.stat_info.contains_key(field))
+    }
+
+    // table 全体の block 数と record 数を持つ統計情報。どの field の統計情報も同じ値を持つ
+    fn table_stat(&self) -> StatInfo {
+        self.stat_info
+            .values()
+            // 最初にあった値を取り出す
+            .find(|_| true)
+            .cloned()
+            .unwrap_or_else(StatInfo::missing)
+    }
+
+    /// table の layout を返す。with_projection で読む field を絞っていても、全ての field を含む
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// partition に分けた table の場合、読む partition の番号を返す
+    pub fn selected_partitions(&self) -> Option<Vec<usize>> {
+        self.layout
+            .partition()
+            .map(|partition| partition.selected_partitions())
+    }
+
+    /// read scan で読む field を fields に含まれるものだけに絞る
+    /// update scan は常に全ての field を扱う
+    pub fn with_projection(mut self, fields: &[String]) -> TablePlan {
+        self.projected_layout = Some(self.layout.project(fields));
+        self
+    }
+}
+
+#[cfg(test)]
+mod table_plan_test {
+    use super::*;
+    use crate::{
+        buffer::buffer_manager::BufferManager,
+        file::file_manager::FileManager,
+        log::log_manager::LogManager,
+        record::schema::FieldInfo,
+        tx::{concurrency::lock_table::LockTable, transaction::TransactionFactory},
+    };
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_missing_stats() {
+        let dir = tempdir().unwrap();
+        let file_manager = Arc::new(FileManager::new(dir.path(), 400));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager.clone(),
+            log_manager.clone(),
+            8,
+            Some(10),
+        ));
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        let factory =
+            TransactionFactory::new(file_manager, log_manager, buffer_manager, lock_table);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+
+        let mut schema = Schema::new();
+        schema.add_field("a", FieldInfo::Integer);
+        schema.add_field("b", FieldInfo::String(8));
+        let mut plan = TablePlan {
+            table_name: "tbl".to_string(),
+            layout: Layout::new(schema).unwrap(),
+            projected_layout: None,
+            stat_info: HashMap::new(),
+            tx: tx.clone(),
+        };
+        // 統計情報がなくても、仮の見積もりを返す
+        assert!(!plan.has_stats());
+        assert_eq!(plan.get_block_access_cost().unwrap(), 1);
+        assert_eq!(plan.get_record_access_cost().unwrap(), 1);
+        assert_eq!(plan.get_distinct_value_estimation("a").unwrap(), 1);
+        assert_eq!(plan.get_value_range("a"), None);
+        // table に存在しない field は error になる
+        assert!(plan.get_distinct_value_estimation("c").is_err());
+
+        // 一部の field の統計情報しかない場合は、table 全体の値はその field のものを使う
+        plan.stat_info
+            .insert("a".to_string(), StatInfo::new(3, 30, 10));
+        assert!(!plan.has_stats());
+        assert_eq!(plan.get_block_access_cost().unwrap(), 3);
+        assert_eq!(plan.get_record_access_cost().unwrap(), 30);
+        assert_eq!(plan.get_distinct_value_estimation("a").unwrap(), 10);
+        assert_eq!(plan.get_distinct_value_estimation("b").unwrap(), 1);
+
+        plan.stat_info
+            .insert("b".to_string(), StatInfo::new(3, 30, 5));
+        assert!(plan.has_stats());
+        tx.borrow_mut().commit().unwrap();
+    }
 }
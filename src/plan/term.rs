@@ -4,8 +4,12 @@ use crate::plan::plan::Plan;
 use std::cmp::max;
 
 use crate::query::{
+    coercion,
     constant::Constant,
-    term::{EqualTerm as EqualTermForScan, Term as TermForScan},
+    term::{
+        ComparisonOperator, ComparisonTerm as ComparisonTermForScan, EqualTerm as EqualTermForScan,
+        Term as TermForScan,
+    },
 };
 
 use anyhow::Result as AnyhowResult;
@@ -16,7 +20,7 @@ use std::fmt;
  * Select の where 句で A = B の条件を表す term
  * 同じ名前の struct が query 以下のパッケージにも存在するが、こちらは実行計画を立てるうえで使うことを意図されている
  */
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct EqualTerm {
     lhs: Expression,
     rhs: Expression,
@@ -26,15 +30,28 @@ pub struct EqualTerm {
  * Select の where 句で用いられる条件のうちの一つを表す (A=B, A<B など)
  * 同じ名前の struct が query 以下のパッケージにも存在するが、こちらは実行計画を立てるうえで使うことを意図されている
  */
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Term {
     Equal(EqualTerm),
+    Comparison(ComparisonTerm),
+}
+
+/**
+ * Select の where 句で A < B, A <> B のように、等号以外の比較演算子で比べる条件を表す term
+ * 同じ名前の struct が query 以下のパッケージにも存在するが、こちらは実行計画を立てるうえで使うことを意図されている
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparisonTerm {
+    lhs: Expression,
+    op: ComparisonOperator,
+    rhs: Expression,
 }
 
 impl Plannable for Term {
     fn reduction_factor(&self, plan: &dyn Plan) -> AnyhowResult<ReductionFactor> {
         match self {
             Term::Equal(equal_term) => equal_term.reduction_factor(plan),
+            Term::Comparison(comparison_term) => comparison_term.reduction_factor(plan),
         }
     }
 }
@@ -43,14 +60,55 @@ impl fmt::Display for Term {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Term::Equal(equal_term) => write!(f, "{}", equal_term),
+            Term::Comparison(comparison_term) => write!(f, "{}", comparison_term),
         }
     }
 }
 
 impl Term {
+    /// 比較している 2 つの式を返す
+    pub fn operands(&self) -> (&Expression, &Expression) {
+        match self {
+            Term::Equal(equal_term) => (equal_term.lhs(), equal_term.rhs()),
+            Term::Comparison(comparison_term) => (comparison_term.lhs(), comparison_term.rhs()),
+        }
+    }
+    /// term の中で参照されている field 名を返す
+    pub fn fields(&self) -> Vec<String> {
+        let (lhs, rhs) = self.operands();
+        [lhs, rhs]
+            .into_iter()
+            .flat_map(|expression| expression.fields())
+            .collect()
+    }
+    /// field 名 old_name を new_name に置き換えた term を返す
+    pub fn rename_field(&self, old_name: &str, new_name: &str) -> Term {
+        match self {
+            Term::Equal(equal_term) => Term::Equal(equal_term.rename_field(old_name, new_name)),
+            Term::Comparison(comparison_term) => {
+                Term::Comparison(comparison_term.rename_field(old_name, new_name))
+            }
+        }
+    }
     pub fn convert_for_scan(&self) -> Box<dyn TermForScan> {
         match self {
             Term::Equal(equal_term) => Box::new(equal_term.convert_for_scan()),
+            Term::Comparison(comparison_term) => Box::new(comparison_term.convert_for_scan()),
+        }
+    }
+    /// field を参照しない term の場合、その真偽値を返す。field を参照する場合は None を返す
+    pub fn evaluate_constant(&self) -> Option<bool> {
+        match self {
+            Term::Equal(equal_term) => equal_term.evaluate_constant(),
+            Term::Comparison(comparison_term) => comparison_term.evaluate_constant(),
+        }
+    }
+    /// other と同じ条件を表す term かどうか
+    pub fn is_equivalent(&self, other: &Term) -> bool {
+        match (self, other) {
+            (Term::Equal(lhs), Term::Equal(rhs)) => lhs.is_equivalent(rhs),
+            (Term::Comparison(lhs), Term::Comparison(rhs)) => lhs.is_equivalent(rhs),
+            _ => false,
         }
     }
 }
@@ -71,12 +129,14 @@ impl Plannable for EqualTerm {
                 ReductionFactor::Constant(plan.get_distinct_value_estimation(right_field)? as f64)
             }
             (Expression::Constant(lhs), Expression::Constant(rhs)) => {
-                if lhs == rhs {
+                if coercion::equals(lhs, rhs) {
                     ReductionFactor::Constant(1.0)
                 } else {
                     ReductionFactor::Infinity()
                 }
             }
+            // case 式などの値の分布は分からないので、絞り込まないものとして見積もる
+            _ => ReductionFactor::Constant(1.0),
         })
     }
 }
@@ -87,6 +147,14 @@ impl EqualTerm {
     }
 
     /// 引数で与えた field と対になっている (等号条件のついている) constant の値を返す
+    pub fn lhs(&self) -> &Expression {
+        &self.lhs
+    }
+
+    pub fn rhs(&self) -> &Expression {
+        &self.rhs
+    }
+
     pub fn equates_with_constant(&self, field_name: &str) -> Option<Constant> {
         if let Expression::Field(name) = &self.lhs {
             if name == field_name {
@@ -116,9 +184,41 @@ impl EqualTerm {
         None
     }
 
+    /// term の中で参照されている field 名を返す
+    pub fn fields(&self) -> Vec<String> {
+        [&self.lhs, &self.rhs]
+            .into_iter()
+            .flat_map(|expression| expression.fields())
+            .collect()
+    }
+
+    /// field 名 old_name を new_name に置き換えた term を返す
+    pub fn rename_field(&self, old_name: &str, new_name: &str) -> EqualTerm {
+        EqualTerm::new(
+            self.lhs.rename_field(old_name, new_name),
+            self.rhs.rename_field(old_name, new_name),
+        )
+    }
+
     pub fn convert_for_scan(&self) -> EqualTermForScan {
         EqualTermForScan::new(self.lhs.convert_for_scan(), self.rhs.convert_for_scan())
     }
+
+    /// 両辺が constant の場合、その 2 つが等しいかどうかを返す。field を参照する場合は None を返す
+    pub fn evaluate_constant(&self) -> Option<bool> {
+        match (&self.lhs, &self.rhs) {
+            (Expression::Constant(lhs), Expression::Constant(rhs)) => {
+                Some(coercion::equals(lhs, rhs))
+            }
+            _ => None,
+        }
+    }
+
+    /// 等号の左右を入れ替えたものも同じ条件として扱う
+    pub fn is_equivalent(&self, other: &EqualTerm) -> bool {
+        (self.lhs == other.lhs && self.rhs == other.rhs)
+            || (self.lhs == other.rhs && self.rhs == other.lhs)
+    }
 }
 
 impl fmt::Display for EqualTerm {
@@ -126,3 +226,206 @@ impl fmt::Display for EqualTerm {
         write!(f, "{} = {}", self.lhs, self.rhs)
     }
 }
+
+impl Plannable for ComparisonTerm {
+    fn reduction_factor(&self, plan: &dyn Plan) -> AnyhowResult<ReductionFactor> {
+        // 定数を右辺に揃える
+        let (field, op, constant) = match (&self.lhs, &self.rhs) {
+            (Expression::Field(field), Expression::Constant(constant)) => {
+                (field, self.op, constant)
+            }
+            (Expression::Constant(constant), Expression::Field(field)) => {
+                (field, self.op.swapped(), constant)
+            }
+            (Expression::Constant(_), Expression::Constant(_)) => {
+                return Ok(match self.evaluate_constant() {
+                    Some(true) => ReductionFactor::Constant(1.0),
+                    _ => ReductionFactor::Infinity(),
+                });
+            }
+            // field どうしの比較や case 式などの値の分布は分からないので、既定の割合で絞り込むものとして見積もる
+            _ => {
+                return Ok(match self.op {
+                    ComparisonOperator::NotEqual => ReductionFactor::Constant(1.0),
+                    _ => ReductionFactor::Constant(Self::DEFAULT_RANGE_REDUCTION_FACTOR),
+                })
+            }
+        };
+        if op == ComparisonOperator::NotEqual {
+            // 1 つの値だけを除くので、distinct value が d なら d / (d - 1) に絞られる
+            let distinct_values = plan.get_distinct_value_estimation(field)? as f64;
+            return Ok(if distinct_values > 1.0 {
+                ReductionFactor::Constant(distinct_values / (distinct_values - 1.0))
+            } else {
+                ReductionFactor::Constant(1.0)
+            });
+        }
+        // 値の範囲が分かる数値の field は、値が範囲に一様に分布しているとみなして、条件を満たす値の割合を求める
+        let Some((min, max)) = plan.get_value_range(field) else {
+            return Ok(ReductionFactor::Constant(
+                Self::DEFAULT_RANGE_REDUCTION_FACTOR,
+            ));
+        };
+        let (min, max, val) = match (
+            Self::integer_value(&min),
+            Self::integer_value(&max),
+            Self::integer_value(constant),
+        ) {
+            (Some(min), Some(max), Some(val)) => (min, max, val),
+            // double の値を含む場合は、範囲を連続した値の区間として扱う
+            _ => {
+                return Ok(
+                    match (
+                        Self::real_value(&min),
+                        Self::real_value(&max),
+                        Self::real_value(constant),
+                    ) {
+                        (Some(min), Some(max), Some(val)) => {
+                            let distinct_values = plan.get_distinct_value_estimation(field)? as f64;
+                            Self::real_reduction_factor(op, min, max, val, distinct_values)
+                        }
+                        _ => ReductionFactor::Constant(Self::DEFAULT_RANGE_REDUCTION_FACTOR),
+                    },
+                )
+            }
+        };
+        let total = max - min + 1;
+        let matched = match op {
+            ComparisonOperator::Less => val - min,
+            ComparisonOperator::LessEqual => val - min + 1,
+            ComparisonOperator::Greater => max - val,
+            ComparisonOperator::GreaterEqual => max - val + 1,
+            ComparisonOperator::NotEqual => unreachable!("<> is handled above"),
+        }
+        .clamp(0, total);
+        Ok(if total <= 0 {
+            ReductionFactor::Constant(1.0)
+        } else if matched == 0 {
+            ReductionFactor::Infinity()
+        } else {
+            ReductionFactor::Constant(total as f64 / matched as f64)
+        })
+    }
+}
+
+impl ComparisonTerm {
+    /// 値の分布が分からない範囲の条件で、record が絞られる割合の既定値 (1/3 に絞られるとみなす)
+    const DEFAULT_RANGE_REDUCTION_FACTOR: f64 = 3.0;
+
+    // 範囲の見積もりで整数として扱う値 (int と bigint)。bigint の範囲の幅も溢れないように i128 にする
+    fn integer_value(val: &Constant) -> Option<i128> {
+        match val {
+            Constant::Int(val) => Some(*val as i128),
+            Constant::BigInt(val) => Some(*val as i128),
+            _ => None,
+        }
+    }
+
+    // 範囲の見積もりで実数として扱う値 (int, bigint と double)
+    fn real_value(val: &Constant) -> Option<f64> {
+        match val {
+            Constant::Double(val) => Some(*val),
+            _ => Self::integer_value(val).map(|val| val as f64),
+        }
+    }
+
+    // [min, max] の区間に一様に分布している値のうち、val と比べて条件を満たすものの割合から絞られる割合を求める
+    // 区間の端の値だけが条件を満たす場合も、少なくとも distinct value 1 つ分の record は残るとみなす
+    fn real_reduction_factor(
+        op: ComparisonOperator,
+        min: f64,
+        max: f64,
+        val: f64,
+        distinct_values: f64,
+    ) -> ReductionFactor {
+        let (satisfiable, matched) = match op {
+            ComparisonOperator::Less => (val > min, val - min),
+            ComparisonOperator::LessEqual => (val >= min, val - min),
+            ComparisonOperator::Greater => (val < max, max - val),
+            ComparisonOperator::GreaterEqual => (val <= max, max - val),
+            ComparisonOperator::NotEqual => unreachable!("<> is handled above"),
+        };
+        if !satisfiable {
+            return ReductionFactor::Infinity();
+        }
+        if max <= min {
+            return ReductionFactor::Constant(1.0);
+        }
+        let fraction = (matched / (max - min))
+            .clamp(0.0, 1.0)
+            .max(1.0 / distinct_values.max(1.0));
+        ReductionFactor::Constant(1.0 / fraction)
+    }
+
+    pub fn new(lhs: Expression, op: ComparisonOperator, rhs: Expression) -> Self {
+        Self { lhs, op, rhs }
+    }
+
+    pub fn lhs(&self) -> &Expression {
+        &self.lhs
+    }
+
+    pub fn op(&self) -> ComparisonOperator {
+        self.op
+    }
+
+    pub fn rhs(&self) -> &Expression {
+        &self.rhs
+    }
+
+    /// field 名 old_name を new_name に置き換えた term を返す
+    pub fn rename_field(&self, old_name: &str, new_name: &str) -> ComparisonTerm {
+        ComparisonTerm::new(
+            self.lhs.rename_field(old_name, new_name),
+            self.op,
+            self.rhs.rename_field(old_name, new_name),
+        )
+    }
+
+    pub fn convert_for_scan(&self) -> ComparisonTermForScan {
+        ComparisonTermForScan::new(
+            self.lhs.convert_for_scan(),
+            self.op,
+            self.rhs.convert_for_scan(),
+        )
+    }
+
+    /// 両辺が constant の場合、比較した結果を返す。field を参照する場合は None を返す
+    /// 比較できない型の値どうしの場合は false を返す
+    pub fn evaluate_constant(&self) -> Option<bool> {
+        match (&self.lhs, &self.rhs) {
+            (Expression::Constant(lhs), Expression::Constant(rhs)) => {
+                Some(coercion::compare(lhs, rhs).is_some_and(|ordering| self.op.matches(ordering)))
+            }
+            _ => None,
+        }
+    }
+
+    /// field_name と定数を比べる term の場合は、field_name を左辺に揃えた時の演算子と定数を返す
+    pub fn compares_with_constant(
+        &self,
+        field_name: &str,
+    ) -> Option<(ComparisonOperator, Constant)> {
+        match (&self.lhs, &self.rhs) {
+            (Expression::Field(field), Expression::Constant(constant)) if field == field_name => {
+                Some((self.op, constant.clone()))
+            }
+            (Expression::Constant(constant), Expression::Field(field)) if field == field_name => {
+                Some((self.op.swapped(), constant.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// a < b と b > a のように、左右と演算子を入れ替えたものも同じ条件として扱う
+    pub fn is_equivalent(&self, other: &ComparisonTerm) -> bool {
+        (self.lhs == other.lhs && self.op == other.op && self.rhs == other.rhs)
+            || (self.lhs == other.rhs && self.op == other.op.swapped() && self.rhs == other.lhs)
+    }
+}
+
+impl fmt::Display for ComparisonTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.lhs, self.op, self.rhs)
+    }
+}
@@ -1,2 +1,6 @@
 pub mod basic_query_planner;
+pub mod logical_plan;
+pub mod query_builder;
 pub mod query_planner;
+pub mod rewrite;
+pub mod row_policy;
@@ -1,65 +1,73 @@
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use std::{cell::RefCell, path::PathBuf, rc::Rc, sync::Arc};
 
-use anyhow::Result as AnyhowResult;
+use anyhow::{anyhow, Result as AnyhowResult};
 
 use crate::{
-    metadata::metadata_manager::MetadataManager,
+    index::common::IndexSearch,
+    metadata::{index_manager::IndexInfo, metadata_manager::MetadataManager},
     parse::{content::query_data::QueryData, parser_factory::ParserFactory},
     plan::{
-        plan::Plan, predicate::Predicate, product_plan::ProductPlan, project_plan::ProjectPlan,
-        select_plan::SelectPlan, table_plan::TablePlan,
+        csv_plan::{CsvPlan, CsvStatCache},
+        index_select_plan::IndexSelectPlan,
+        instrumented_plan::ExplainNode,
+        plan::{Plan, PlanError},
+        predicate::ProductPredicate,
+        table_plan::TablePlan,
     },
-    tx::transaction::Transaction,
+    query::{coercion::coerce, memory_budget::MemoryBudget},
+    tx::{temp_file_manager::TempFileManager, transaction::Transaction},
 };
 
-use super::query_planner::QueryPlanner;
+use super::{
+    logical_plan::LogicalPlan,
+    query_builder::{Query, QueryBuilderError, QueryNode},
+    query_planner::QueryPlanner,
+    rewrite::Rewriter,
+    row_policy::RowPolicy,
+};
 
 pub struct BasicQueryPalanner {
     mdm: Arc<dyn MetadataManager>,
     parser_factory: ParserFactory,
+    // 組み立てた plan tree に適用する rule
+    rewriter: Rewriter,
+    // merge join で並べ替えた record を保存する temp table を管理する。None の場合は merge join を使わない
+    temp_file_manager: Option<Arc<TempFileManager>>,
+    // external table のファイルから計算した統計情報
+    csv_stats: CsvStatCache,
 }
 
 impl QueryPlanner for BasicQueryPalanner {
     fn create_plan(
         &self,
         data: &QueryData,
+        row_policy: &RowPolicy,
+        budget: &MemoryBudget,
         tx: &Rc<RefCell<Transaction>>,
     ) -> AnyhowResult<Box<dyn Plan>> {
-        let mut plans = {
-            // Step 1: product でまとめる前に table の集合として plan の集合を取得 (view がある場合は、それが一つのテーブルとみなされている)
-            let mut plans = vec![];
-            for table in data.get_tables() {
-                if let Ok(view_def) = self.mdm.get_view_def(table, tx) {
-                    let mut parser = self.parser_factory.create(view_def)?;
-                    let view_data = parser.parse_query()?;
-                    plans.push(self.create_plan(&view_data, tx)?);
-                } else {
-                    plans.push(Box::new(TablePlan::new(
-                        table.clone(),
-                        self.mdm.as_ref(),
-                        tx.clone(),
-                    )?) as Box<dyn Plan>);
-                }
-            }
-            plans
-        };
-        // Step 2: product でまとめる
-        let mut plan = {
-            let mut plan = plans.remove(0);
-            for p in plans {
-                plan = Box::new(ProductPlan::new(plan, p)?) as Box<dyn Plan>;
-            }
-            plan
-        };
-        // Step 3: predicate を適用
-        plan = Box::new(SelectPlan::new(
-            plan,
-            Box::new(Predicate::Product(data.get_predicate().clone())),
-        )) as Box<dyn Plan>;
-        // Step 4: projection を適用
-        plan = Box::new(ProjectPlan::new(plan, data.get_fields().clone())?) as Box<dyn Plan>;
+        let plan = self.build_plan(data, row_policy, budget, tx)?;
+        self.rewriter.rewrite(plan)?.into_plan()
+    }
+
+    fn create_instrumented_plan(
+        &self,
+        data: &QueryData,
+        row_policy: &RowPolicy,
+        budget: &MemoryBudget,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<(Box<dyn Plan>, ExplainNode)> {
+        let plan = self.build_plan(data, row_policy, budget, tx)?;
+        self.rewriter.rewrite(plan)?.into_instrumented_plan()
+    }
 
-        Ok(plan)
+    fn create_plan_for_query(
+        &self,
+        query: &Query,
+        budget: &MemoryBudget,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Box<dyn Plan>> {
+        let plan = self.build_query_plan(query, budget, tx)?;
+        self.rewriter.rewrite(plan)?.into_plan()
     }
 }
 
@@ -68,6 +76,374 @@ impl BasicQueryPalanner {
         BasicQueryPalanner {
             mdm,
             parser_factory,
+            rewriter: Rewriter::default(),
+            temp_file_manager: None,
+            csv_stats: CsvStatCache::new(),
+        }
+    }
+
+    pub fn with_temp_file_manager(mut self, temp_file_manager: Arc<TempFileManager>) -> Self {
+        self.temp_file_manager = Some(temp_file_manager);
+        self
+    }
+
+    /// query をそのまま product / select / project で組み立てた plan tree を作成する
+    /// view の query は、その view を読む葉の node の代わりに展開する
+    fn build_plan(
+        &self,
+        data: &QueryData,
+        row_policy: &RowPolicy,
+        budget: &MemoryBudget,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<LogicalPlan> {
+        // row policy は view ではなく、実際に読む table に対して適用する (view の中の table には再帰の中で適用される)
+        let predicate = {
+            let tables = data
+                .get_tables()
+                .iter()
+                .filter(|table| self.mdm.get_view_def(table, tx).is_err())
+                .cloned()
+                .collect::<Vec<_>>();
+            row_policy.restrict(data.get_predicate(), &tables)
+        };
+        // 各 table から読む必要があるのは、select する field と predicate で参照する field だけ
+        let needed_fields = {
+            let mut fields = data.referenced_fields();
+            fields.extend(predicate.fields());
+            fields
+        };
+        // Step 1: table ごとの plan を取得する (view がある場合は、それが一つのテーブルとみなされている)
+        // Step 2: product でまとめる。それまでの table の field と等しいという条件を持つ field に index が張られた table は、
+        // product の代わりに index join でまとめる。index が無い場合は、merge join の方が安ければ merge join でまとめる
+        let (first, rest) = data
+            .get_tables()
+            .split_first()
+            .ok_or_else(|| anyhow!("query has no table"))?;
+        let mut plan = self.build_table_plan(
+            first,
+            data.get_as_of_lsn(),
+            &predicate,
+            Some(&needed_fields),
+            row_policy,
+            budget,
+            tx,
+        )?;
+        for table in rest {
+            // index は現在の table の record を指しているので、過去の時点を読む場合は使わない
+            let join_index = match data.get_as_of_lsn() {
+                Some(_) => None,
+                None => self.find_join_index(table, &plan.fields(), &predicate, tx)?,
+            };
+            plan = match join_index {
+                Some((index_info, join_field)) => {
+                    let rhs =
+                        TablePlan::new(table.to_string(), self.mdm.as_ref(), budget, tx.clone())?
+                            .with_projection(&needed_fields);
+                    LogicalPlan::index_join(plan, rhs, index_info, join_field, tx.clone())
+                }
+                None => {
+                    let rhs = self.build_table_plan(
+                        table,
+                        data.get_as_of_lsn(),
+                        &predicate,
+                        Some(&needed_fields),
+                        row_policy,
+                        budget,
+                        tx,
+                    )?;
+                    self.join(plan, rhs, &predicate, tx)
+                }
+            };
+        }
+        // Step 3: predicate を適用
+        let plan = LogicalPlan::select(plan, predicate);
+        // Step 4: group by や集約関数を使う場合は、group ごとに record をまとめる
+        // 並べ替える record を小さくするために、group にまとめる前に必要な field だけを残す
+        let plan = if data.is_grouped() {
+            let temp_file_manager = self.required_temp_file_manager("group by")?;
+            let mut grouped_fields = data.get_group_fields().to_vec();
+            for (_, aggregation) in data.get_aggregations() {
+                if !grouped_fields
+                    .iter()
+                    .any(|field| field == aggregation.field())
+                {
+                    grouped_fields.push(aggregation.field().to_string());
+                }
+            }
+            LogicalPlan::group_by(
+                LogicalPlan::project(plan, grouped_fields),
+                data.get_group_fields().to_vec(),
+                data.get_aggregations().to_vec(),
+                temp_file_manager,
+                tx.clone(),
+            )
+        } else {
+            plan
+        };
+        // Step 5: select する値を式で指定している場合は、その値を field として追加する
+        let plan = if data.get_expressions().is_empty() {
+            plan
+        } else {
+            LogicalPlan::extend(plan, data.get_expressions().to_vec())
+        };
+        // Step 6: projection を適用
+        let plan = LogicalPlan::project(plan, data.get_fields().clone());
+        // Step 7: select distinct が指定されている場合は、select した値が等しい record を 1 つにまとめる
+        let plan = if data.is_distinct() {
+            LogicalPlan::distinct(
+                plan,
+                self.required_temp_file_manager("distinct")?,
+                tx.clone(),
+            )
+        } else {
+            plan
+        };
+        // Step 8: order by が指定されている場合は、select した値で並べ替える
+        if data.get_order_by().is_empty() {
+            return Ok(plan);
+        }
+        Ok(LogicalPlan::sort(
+            plan,
+            data.get_order_by().to_vec(),
+            self.required_temp_file_manager("order by")?,
+            tx.clone(),
+        ))
+    }
+
+    /// temp table に record を書き出す operator のための TempFileManager を返す。設定されていない場合は error を返す
+    fn required_temp_file_manager(&self, operator: &str) -> AnyhowResult<Arc<TempFileManager>> {
+        self.temp_file_manager.clone().ok_or_else(|| {
+            anyhow!(PlanError::InvalidCall(format!(
+                "{} is not available without temp file manager",
+                operator
+            )))
+        })
+    }
+
+    /// table を読む plan tree を作成する。view の場合は view の query を展開した plan tree になる
+    /// predicate は partition の pruning に、needed_fields は読む field を絞るのに使う (None の場合は全ての field を読む)
+    fn build_table_plan(
+        &self,
+        table: &str,
+        as_of_lsn: Option<u64>,
+        predicate: &ProductPredicate,
+        needed_fields: Option<&[String]>,
+        row_policy: &RowPolicy,
+        budget: &MemoryBudget,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<LogicalPlan> {
+        if let Ok(view_def) = self.mdm.get_view_def(table, tx) {
+            let mut parser = self.parser_factory.create(view_def)?;
+            let mut view_data = parser.parse_query()?;
+            // view の中で時点が指定されていない場合は、外側の query の時点で読む
+            if let (Some(lsn), None) = (as_of_lsn, view_data.get_as_of_lsn()) {
+                view_data = view_data.with_as_of_lsn(lsn);
+            }
+            return self.build_plan(&view_data, row_policy, budget, tx);
+        }
+        if let Some(path) = self.mdm.get_external_table_path(table, tx)? {
+            // external table は catalog の schema に従ってファイルを直接読む
+            // ファイルの変更は log に残らないので、as of lsn が指定されていても現在の内容を読む
+            let schema = self.mdm.get_layout(table, tx)?.schema().clone();
+            let block_size = tx.borrow().block_size();
+            let plan = CsvPlan::new(
+                table.to_string(),
+                PathBuf::from(path),
+                schema,
+                block_size,
+                budget,
+                &self.csv_stats,
+            )?;
+            return Ok(LogicalPlan::leaf(
+                Box::new(plan),
+                format!("CsvPlan({})", table),
+            ));
+        }
+        let plan = match as_of_lsn {
+            Some(lsn) => TablePlan::as_of(
+                table.to_string(),
+                lsn,
+                self.mdm.as_ref(),
+                self.required_temp_file_manager("as of lsn")?.as_ref(),
+                budget,
+                tx.clone(),
+            )?,
+            None => TablePlan::new(table.to_string(), self.mdm.as_ref(), budget, tx.clone())?,
+        };
+        // partition に分けた table は、predicate で読む必要がないと分かる partition を読まない
+        let plan = plan.with_partition_pruning(predicate);
+        let mut label = match plan.selected_partitions() {
+            Some(partitions) => format!("TablePlan({}, partitions {:?})", table, partitions),
+            None => format!("TablePlan({})", table),
+        };
+        // 統計情報がなく仮の見積もりを使っている場合は、explain で分かるように印を付ける
+        if !plan.has_stats() {
+            label.insert_str(label.len() - 1, ", no stats");
+        }
+        let plan = match needed_fields {
+            Some(fields) => plan.with_projection(fields),
+            None => plan,
+        };
+        // index は現在の table の record を指しているので、過去の時点を読む場合は使わない
+        if as_of_lsn.is_none() {
+            let index_infos = self.mdm.get_index_info(table, tx)?;
+            // predicate が index を張った field を定数に限定している場合は、index で record を探す
+            // 型が異なる値は index を検索できないので、table を全て読む
+            let equal_search = index_infos.iter().find_map(|index_info| {
+                let key_info = plan.layout().schema().info(index_info.field_name())?;
+                let val = predicate
+                    .equates_with_constant(index_info.field_name())
+                    .and_then(|val| coerce(&val, key_info.get_type()))?;
+                Some((index_info.clone(), IndexSearch::Equal(val)))
+            });
+            // 定数と等しいという条件が無い場合は、定数との大小比較で範囲を限定している field の B-Tree index を使う
+            let search = equal_search.or_else(|| {
+                index_infos.iter().find_map(|index_info| {
+                    if !index_info.index_type().supports_range() {
+                        return None;
+                    }
+                    let key_info = plan.layout().schema().info(index_info.field_name())?;
+                    let (lo, hi) =
+                        predicate.range_of_field(index_info.field_name(), key_info.get_type())?;
+                    Some((index_info.clone(), IndexSearch::Range(lo, hi)))
+                })
+            });
+            if let Some((index_info, search)) = search {
+                // 読む field が全て index の record に含まれている場合は、table の block を読まずに index だけを読む
+                let covering =
+                    needed_fields.is_some() && index_info.covers(&plan.get_schema().fields());
+                let name = if covering {
+                    "IndexOnlyPlan"
+                } else {
+                    "IndexSelectPlan"
+                };
+                let label = match &search {
+                    IndexSearch::Equal(val) => {
+                        format!("{}({}, {} = {})", name, table, index_info.index_name(), val)
+                    }
+                    IndexSearch::Range(_, _) => format!(
+                        "{}({}, {}, {})",
+                        name,
+                        table,
+                        index_info.index_name(),
+                        search.display(index_info.field_name())
+                    ),
+                };
+                let plan = IndexSelectPlan::new(plan, index_info, search, tx.clone());
+                let plan = if covering {
+                    plan.with_covering()?
+                } else {
+                    plan
+                };
+                return Ok(LogicalPlan::leaf(Box::new(plan), label));
+            }
         }
+        Ok(LogicalPlan::leaf(Box::new(plan), label))
+    }
+
+    /// table に張られた index のうち、lhs_fields のいずれかと等しいという条件を predicate が持つ field の index を探す
+    /// 見つかった場合は index と、その field と等しい lhs の field を返す
+    /// 定数と等しいという条件で index を使える table は、index select で読む方が良いので None を返す
+    fn find_join_index(
+        &self,
+        table: &str,
+        lhs_fields: &[String],
+        predicate: &ProductPredicate,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Option<(IndexInfo, String)>> {
+        if self.mdm.get_view_def(table, tx).is_ok()
+            || self.mdm.get_external_table_path(table, tx)?.is_some()
+        {
+            return Ok(None);
+        }
+        let index_infos = self.mdm.get_index_info(table, tx)?;
+        if index_infos.iter().any(|index_info| {
+            predicate
+                .equates_with_constant(index_info.field_name())
+                .is_some()
+        }) {
+            return Ok(None);
+        }
+        Ok(index_infos.into_iter().find_map(|index_info| {
+            let join_field = predicate
+                .equates_with_field(index_info.field_name())
+                .filter(|field| lhs_fields.contains(field))?;
+            Some((index_info, join_field))
+        }))
+    }
+
+    /// rhs の field とそれまでの table の field が等しいという条件を predicate が持つ場合は merge join で、
+    /// それ以外の場合は product でまとめる
+    fn join(
+        &self,
+        lhs: LogicalPlan,
+        rhs: LogicalPlan,
+        predicate: &ProductPredicate,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> LogicalPlan {
+        let Some(temp_file_manager) = &self.temp_file_manager else {
+            return LogicalPlan::product(lhs, rhs);
+        };
+        let lhs_fields = lhs.fields();
+        let join_fields = rhs.fields().into_iter().find_map(|rhs_field| {
+            let lhs_field = predicate
+                .equates_with_field(&rhs_field)
+                .filter(|field| lhs_fields.contains(field))?;
+            Some((lhs_field, rhs_field))
+        });
+        match join_fields {
+            Some((lhs_field, rhs_field)) => LogicalPlan::merge_join(
+                lhs,
+                rhs,
+                lhs_field,
+                rhs_field,
+                temp_file_manager.clone(),
+                tx.clone(),
+            ),
+            None => LogicalPlan::product(lhs, rhs),
+        }
+    }
+
+    /// builder で組み立てた query をそのまま plan tree に変換する
+    /// filter で参照する field が存在しない場合は error を返す (project や extend は Plan を作る時に確認される)
+    fn build_query_plan(
+        &self,
+        query: &Query,
+        budget: &MemoryBudget,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<LogicalPlan> {
+        Ok(match query.node() {
+            QueryNode::Table(table) => self.build_table_plan(
+                table,
+                None,
+                &ProductPredicate::new(vec![]),
+                None,
+                &RowPolicy::new(),
+                budget,
+                tx,
+            )?,
+            QueryNode::Filter(child, predicate) => {
+                let plan = self.build_query_plan(child, budget, tx)?;
+                let fields = plan.fields();
+                if let Some(field) = predicate.fields().into_iter().find(|f| !fields.contains(f)) {
+                    return Err(anyhow!(QueryBuilderError::FieldNotFound {
+                        field,
+                        query: child.to_string(),
+                    }));
+                }
+                LogicalPlan::select(plan, predicate.clone())
+            }
+            QueryNode::Project(child, fields) => {
+                LogicalPlan::project(self.build_query_plan(child, budget, tx)?, fields.clone())
+            }
+            QueryNode::Product(lhs, rhs) => LogicalPlan::product(
+                self.build_query_plan(lhs, budget, tx)?,
+                self.build_query_plan(rhs, budget, tx)?,
+            ),
+            QueryNode::Extend(child, expressions) => LogicalPlan::extend(
+                self.build_query_plan(child, budget, tx)?,
+                expressions.clone(),
+            ),
+        })
     }
 }
@@ -0,0 +1,561 @@
+use std::{cell::RefCell, rc::Rc, sync::Arc};
+
+use anyhow::Result as AnyhowResult;
+
+use crate::{
+    metadata::index_manager::IndexInfo,
+    plan::{
+        aggregation::{Aggregation, AggregationKind},
+        distinct_plan::DistinctPlan,
+        expression::Expression,
+        extend_plan::ExtendPlan,
+        group_by_plan::GroupByPlan,
+        index_join_plan::IndexJoinPlan,
+        instrumented_plan::{ExplainNode, InstrumentedPlan},
+        merge_join_plan::MergeJoinPlan,
+        never_plan::NeverPlan,
+        plan::Plan,
+        predicate::{Predicate, ProductPredicate},
+        product_plan::ProductPlan,
+        project_plan::ProjectPlan,
+        select_plan::SelectPlan,
+        sort_plan::SortPlan,
+        table_plan::TablePlan,
+    },
+    query::{constant::Constant, record_comparator::SortDirection},
+    tx::{temp_file_manager::TempFileManager, transaction::Transaction},
+};
+
+/**
+ * planner が rewrite rule を適用するための plan tree
+ *
+ * table や external table を読む葉の node は作成済みの Plan をそのまま持ち、
+ * product / select / project の組み立て方だけを rule で書き換えられるようにしている
+ * 書き換えが終わったら into_plan で Plan の tree に変換する
+ */
+pub enum LogicalPlan {
+    /// table などを読む Plan。label は explain analyze で表示する名前
+    Leaf {
+        plan: Box<dyn Plan>,
+        label: String,
+    },
+    Product(Box<LogicalPlan>, Box<LogicalPlan>),
+    /// lhs の各 record の join_field の値で、rhs の table に張った index を検索して組み合わせる
+    IndexJoin {
+        lhs: Box<LogicalPlan>,
+        rhs: Box<TablePlan>,
+        index_info: IndexInfo,
+        join_field: String,
+        tx: Rc<RefCell<Transaction>>,
+    },
+    /// lhs の lhs_field と rhs の rhs_field の値が等しい record どうしを組み合わせる
+    /// Plan に変換する時に、merge join と product のうち block access の cost の見積もりが小さい方を選ぶ
+    MergeJoin {
+        lhs: Box<LogicalPlan>,
+        rhs: Box<LogicalPlan>,
+        lhs_field: String,
+        rhs_field: String,
+        temp_file_manager: Arc<TempFileManager>,
+        tx: Rc<RefCell<Transaction>>,
+    },
+    Select(Box<LogicalPlan>, ProductPredicate),
+    Project(Box<LogicalPlan>, Vec<String>),
+    /// 式を評価した値を、指定した名前の field として追加する
+    Extend(Box<LogicalPlan>, Vec<(String, Expression)>),
+    /// group_fields の値ごとに record をまとめ、集約関数の値を指定した名前の field として出力する
+    GroupBy {
+        child: Box<LogicalPlan>,
+        group_fields: Vec<String>,
+        aggregations: Vec<(String, Aggregation)>,
+        temp_file_manager: Arc<TempFileManager>,
+        tx: Rc<RefCell<Transaction>>,
+    },
+    /// 全ての field の値が等しい record を 1 つにまとめる
+    Distinct {
+        child: Box<LogicalPlan>,
+        temp_file_manager: Arc<TempFileManager>,
+        tx: Rc<RefCell<Transaction>>,
+    },
+    /// order by で指定された field と向きの順に record を並べ替える
+    Sort {
+        child: Box<LogicalPlan>,
+        sort_keys: Vec<(String, SortDirection)>,
+        temp_file_manager: Arc<TempFileManager>,
+        tx: Rc<RefCell<Transaction>>,
+    },
+    /// 結果が空になることがわかっている plan。中の plan は schema を決めるためだけに使い、実行はしない
+    Empty(Box<LogicalPlan>),
+}
+
+impl LogicalPlan {
+    pub fn leaf(plan: Box<dyn Plan>, label: String) -> Self {
+        LogicalPlan::Leaf { plan, label }
+    }
+
+    pub fn product(lhs: LogicalPlan, rhs: LogicalPlan) -> Self {
+        LogicalPlan::Product(Box::new(lhs), Box::new(rhs))
+    }
+
+    pub fn index_join(
+        lhs: LogicalPlan,
+        rhs: TablePlan,
+        index_info: IndexInfo,
+        join_field: String,
+        tx: Rc<RefCell<Transaction>>,
+    ) -> Self {
+        LogicalPlan::IndexJoin {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+            index_info,
+            join_field,
+            tx,
+        }
+    }
+
+    pub fn merge_join(
+        lhs: LogicalPlan,
+        rhs: LogicalPlan,
+        lhs_field: String,
+        rhs_field: String,
+        temp_file_manager: Arc<TempFileManager>,
+        tx: Rc<RefCell<Transaction>>,
+    ) -> Self {
+        LogicalPlan::MergeJoin {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+            lhs_field,
+            rhs_field,
+            temp_file_manager,
+            tx,
+        }
+    }
+
+    pub fn select(child: LogicalPlan, predicate: ProductPredicate) -> Self {
+        LogicalPlan::Select(Box::new(child), predicate)
+    }
+
+    pub fn project(child: LogicalPlan, fields: Vec<String>) -> Self {
+        LogicalPlan::Project(Box::new(child), fields)
+    }
+
+    pub fn extend(child: LogicalPlan, expressions: Vec<(String, Expression)>) -> Self {
+        LogicalPlan::Extend(Box::new(child), expressions)
+    }
+
+    pub fn group_by(
+        child: LogicalPlan,
+        group_fields: Vec<String>,
+        aggregations: Vec<(String, Aggregation)>,
+        temp_file_manager: Arc<TempFileManager>,
+        tx: Rc<RefCell<Transaction>>,
+    ) -> Self {
+        LogicalPlan::GroupBy {
+            child: Box::new(child),
+            group_fields,
+            aggregations,
+            temp_file_manager,
+            tx,
+        }
+    }
+
+    pub fn distinct(
+        child: LogicalPlan,
+        temp_file_manager: Arc<TempFileManager>,
+        tx: Rc<RefCell<Transaction>>,
+    ) -> Self {
+        LogicalPlan::Distinct {
+            child: Box::new(child),
+            temp_file_manager,
+            tx,
+        }
+    }
+
+    pub fn sort(
+        child: LogicalPlan,
+        sort_keys: Vec<(String, SortDirection)>,
+        temp_file_manager: Arc<TempFileManager>,
+        tx: Rc<RefCell<Transaction>>,
+    ) -> Self {
+        LogicalPlan::Sort {
+            child: Box::new(child),
+            sort_keys,
+            temp_file_manager,
+            tx,
+        }
+    }
+
+    pub fn empty(child: LogicalPlan) -> Self {
+        LogicalPlan::Empty(Box::new(child))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, LogicalPlan::Empty(_))
+    }
+
+    /// この node が出力する record の field を返す
+    pub fn fields(&self) -> Vec<String> {
+        match self {
+            LogicalPlan::Leaf { plan, .. } => plan.get_schema().fields(),
+            LogicalPlan::Product(lhs, rhs) | LogicalPlan::MergeJoin { lhs, rhs, .. } => {
+                let mut fields = lhs.fields();
+                fields.extend(rhs.fields());
+                fields
+            }
+            LogicalPlan::IndexJoin { lhs, rhs, .. } => {
+                let mut fields = lhs.fields();
+                fields.extend(rhs.get_schema().fields());
+                fields
+            }
+            LogicalPlan::Select(child, _)
+            | LogicalPlan::Distinct { child, .. }
+            | LogicalPlan::Sort { child, .. } => child.fields(),
+            LogicalPlan::Project(_, fields) => fields.clone(),
+            LogicalPlan::Extend(child, expressions) => {
+                let mut fields = child.fields();
+                fields.extend(expressions.iter().map(|(name, _)| name.clone()));
+                fields
+            }
+            LogicalPlan::GroupBy {
+                group_fields,
+                aggregations,
+                ..
+            } => {
+                let mut fields = group_fields.clone();
+                fields.extend(aggregations.iter().map(|(name, _)| name.clone()));
+                fields
+            }
+            LogicalPlan::Empty(child) => child.fields(),
+        }
+    }
+
+    /// field の値の (最小値, 最大値) の見積もりを返す。分からない場合は None を返す
+    pub fn value_range(&self, field_name: &str) -> Option<(Constant, Constant)> {
+        match self {
+            LogicalPlan::Leaf { plan, .. } => plan.get_value_range(field_name),
+            LogicalPlan::Product(lhs, rhs) | LogicalPlan::MergeJoin { lhs, rhs, .. } => {
+                if lhs.fields().iter().any(|field| field == field_name) {
+                    lhs.value_range(field_name)
+                } else {
+                    rhs.value_range(field_name)
+                }
+            }
+            LogicalPlan::IndexJoin { lhs, rhs, .. } => {
+                if lhs.fields().iter().any(|field| field == field_name) {
+                    lhs.value_range(field_name)
+                } else {
+                    rhs.get_value_range(field_name)
+                }
+            }
+            // select と project と distinct は値を絞り込むだけで、sort は並べ替えるだけなので、子の範囲に含まれる
+            LogicalPlan::Select(child, _)
+            | LogicalPlan::Project(child, _)
+            | LogicalPlan::Distinct { child, .. }
+            | LogicalPlan::Sort { child, .. }
+            | LogicalPlan::Empty(child) => child.value_range(field_name),
+            LogicalPlan::Extend(child, expressions) => {
+                match expressions.iter().find(|(name, _)| name == field_name) {
+                    Some((_, Expression::Field(field_name))) => child.value_range(field_name),
+                    Some((_, Expression::Constant(constant))) => {
+                        Some((constant.clone(), constant.clone()))
+                    }
+                    Some(_) => None,
+                    None => child.value_range(field_name),
+                }
+            }
+            LogicalPlan::GroupBy {
+                child,
+                group_fields,
+                aggregations,
+                ..
+            } => {
+                if group_fields.iter().any(|field| field == field_name) {
+                    return child.value_range(field_name);
+                }
+                // 最大値と最小値は集計する field の値のどれかになる
+                let (_, aggregation) = aggregations.iter().find(|(name, _)| name == field_name)?;
+                match aggregation.kind() {
+                    AggregationKind::Max | AggregationKind::Min => {
+                        child.value_range(aggregation.field())
+                    }
+                    AggregationKind::Count | AggregationKind::Sum | AggregationKind::Avg => None,
+                }
+            }
+        }
+    }
+
+    /// Plan の tree に変換する
+    pub fn into_plan(self) -> AnyhowResult<Box<dyn Plan>> {
+        Ok(match self {
+            LogicalPlan::Leaf { plan, .. } => plan,
+            LogicalPlan::Product(lhs, rhs) => {
+                Box::new(ProductPlan::new(lhs.into_plan()?, rhs.into_plan()?)?)
+            }
+            LogicalPlan::IndexJoin {
+                lhs,
+                rhs,
+                index_info,
+                join_field,
+                tx,
+            } => Box::new(IndexJoinPlan::new(
+                lhs.into_plan()?,
+                *rhs,
+                index_info,
+                &join_field,
+                tx,
+            )?),
+            LogicalPlan::MergeJoin {
+                lhs,
+                rhs,
+                lhs_field,
+                rhs_field,
+                temp_file_manager,
+                tx,
+            } => {
+                join_plan(
+                    lhs.into_plan()?,
+                    rhs.into_plan()?,
+                    &lhs_field,
+                    &rhs_field,
+                    temp_file_manager,
+                    tx,
+                )?
+                .0
+            }
+            LogicalPlan::Select(child, predicate) => Box::new(SelectPlan::new(
+                child.into_plan()?,
+                Box::new(Predicate::Product(predicate)),
+            )),
+            LogicalPlan::Project(child, fields) => {
+                Box::new(ProjectPlan::new(child.into_plan()?, fields)?)
+            }
+            LogicalPlan::Extend(child, expressions) => {
+                Box::new(ExtendPlan::new(child.into_plan()?, expressions)?)
+            }
+            LogicalPlan::GroupBy {
+                child,
+                group_fields,
+                aggregations,
+                temp_file_manager,
+                tx,
+            } => Box::new(GroupByPlan::new(
+                child.into_plan()?,
+                group_fields,
+                aggregations,
+                temp_file_manager,
+                tx,
+            )?),
+            LogicalPlan::Distinct {
+                child,
+                temp_file_manager,
+                tx,
+            } => Box::new(DistinctPlan::new(
+                child.into_plan()?,
+                temp_file_manager,
+                tx,
+            )?),
+            LogicalPlan::Sort {
+                child,
+                sort_keys,
+                temp_file_manager,
+                tx,
+            } => Box::new(SortPlan::with_directions(
+                child.into_plan()?,
+                sort_keys,
+                temp_file_manager,
+                tx,
+            )?),
+            LogicalPlan::Empty(child) => Box::new(NeverPlan::new(child.into_plan()?)),
+        })
+    }
+
+    /// 各 node を InstrumentedPlan で包んだ Plan の tree に変換し、同じ形の ExplainNode の tree と一緒に返す
+    pub fn into_instrumented_plan(self) -> AnyhowResult<(Box<dyn Plan>, ExplainNode)> {
+        match self {
+            LogicalPlan::Leaf { plan, label } => InstrumentedPlan::wrap(plan, label, vec![]),
+            LogicalPlan::Product(lhs, rhs) => {
+                let (lhs, lhs_node) = lhs.into_instrumented_plan()?;
+                let (rhs, rhs_node) = rhs.into_instrumented_plan()?;
+                InstrumentedPlan::wrap(
+                    Box::new(ProductPlan::new(lhs, rhs)?),
+                    "ProductPlan".to_string(),
+                    vec![lhs_node, rhs_node],
+                )
+            }
+            // rhs の table は index で見つかった record だけを読むので、この node の中で数える
+            LogicalPlan::IndexJoin {
+                lhs,
+                rhs,
+                index_info,
+                join_field,
+                tx,
+            } => {
+                let label = format!(
+                    "IndexJoinPlan({}, {} = {})",
+                    index_info.table_name(),
+                    index_info.index_name(),
+                    join_field
+                );
+                let (lhs, lhs_node) = lhs.into_instrumented_plan()?;
+                InstrumentedPlan::wrap(
+                    Box::new(IndexJoinPlan::new(lhs, *rhs, index_info, &join_field, tx)?),
+                    label,
+                    vec![lhs_node],
+                )
+            }
+            LogicalPlan::MergeJoin {
+                lhs,
+                rhs,
+                lhs_field,
+                rhs_field,
+                temp_file_manager,
+                tx,
+            } => {
+                let (lhs, lhs_node) = lhs.into_instrumented_plan()?;
+                let (rhs, rhs_node) = rhs.into_instrumented_plan()?;
+                let (plan, label) =
+                    join_plan(lhs, rhs, &lhs_field, &rhs_field, temp_file_manager, tx)?;
+                InstrumentedPlan::wrap(plan, label, vec![lhs_node, rhs_node])
+            }
+            LogicalPlan::Select(child, predicate) => {
+                let label = format!("SelectPlan({})", predicate);
+                let (child, child_node) = child.into_instrumented_plan()?;
+                InstrumentedPlan::wrap(
+                    Box::new(SelectPlan::new(
+                        child,
+                        Box::new(Predicate::Product(predicate)),
+                    )),
+                    label,
+                    vec![child_node],
+                )
+            }
+            LogicalPlan::Project(child, fields) => {
+                let label = format!("ProjectPlan({})", fields.join(", "));
+                let (child, child_node) = child.into_instrumented_plan()?;
+                InstrumentedPlan::wrap(
+                    Box::new(ProjectPlan::new(child, fields)?),
+                    label,
+                    vec![child_node],
+                )
+            }
+            LogicalPlan::Extend(child, expressions) => {
+                let label = format!(
+                    "ExtendPlan({})",
+                    expressions
+                        .iter()
+                        .map(|(name, expression)| format!("{} as {}", expression, name))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                let (child, child_node) = child.into_instrumented_plan()?;
+                InstrumentedPlan::wrap(
+                    Box::new(ExtendPlan::new(child, expressions)?),
+                    label,
+                    vec![child_node],
+                )
+            }
+            // group_fields の順に並べ替える場合は、その分もこの node の中で数える
+            LogicalPlan::GroupBy {
+                child,
+                group_fields,
+                aggregations,
+                temp_file_manager,
+                tx,
+            } => {
+                let label = format!(
+                    "GroupByPlan({}; {})",
+                    group_fields.join(", "),
+                    aggregations
+                        .iter()
+                        .map(|(name, aggregation)| format!("{} as {}", aggregation, name))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                let (child, child_node) = child.into_instrumented_plan()?;
+                InstrumentedPlan::wrap(
+                    Box::new(GroupByPlan::new(
+                        child,
+                        group_fields,
+                        aggregations,
+                        temp_file_manager,
+                        tx,
+                    )?),
+                    label,
+                    vec![child_node],
+                )
+            }
+            // 並べ替える場合は、その分もこの node の中で数える
+            LogicalPlan::Distinct {
+                child,
+                temp_file_manager,
+                tx,
+            } => {
+                let (child, child_node) = child.into_instrumented_plan()?;
+                InstrumentedPlan::wrap(
+                    Box::new(DistinctPlan::new(child, temp_file_manager, tx)?),
+                    "DistinctPlan".to_string(),
+                    vec![child_node],
+                )
+            }
+            LogicalPlan::Sort {
+                child,
+                sort_keys,
+                temp_file_manager,
+                tx,
+            } => {
+                let label = format!(
+                    "SortPlan({})",
+                    sort_keys
+                        .iter()
+                        .map(|(field, direction)| format!("{} {}", field, direction))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+                let (child, child_node) = child.into_instrumented_plan()?;
+                InstrumentedPlan::wrap(
+                    Box::new(SortPlan::with_directions(
+                        child,
+                        sort_keys,
+                        temp_file_manager,
+                        tx,
+                    )?),
+                    label,
+                    vec![child_node],
+                )
+            }
+            // 中の plan は実行しないので数えない
+            LogicalPlan::Empty(child) => InstrumentedPlan::wrap(
+                Box::new(NeverPlan::new(child.into_plan()?)),
+                "NeverPlan".to_string(),
+                vec![],
+            ),
+        }
+    }
+}
+
+/// lhs_field と rhs_field の値が等しい record どうしを組み合わせる plan を、explain analyze で表示する名前と一緒に返す
+/// merge join の block access の cost の見積もりが product より小さい場合だけ merge join を使う
+/// product の場合は、join の条件は上の select で評価される
+fn join_plan(
+    lhs: Box<dyn Plan>,
+    rhs: Box<dyn Plan>,
+    lhs_field: &str,
+    rhs_field: &str,
+    temp_file_manager: Arc<TempFileManager>,
+    tx: Rc<RefCell<Transaction>>,
+) -> AnyhowResult<(Box<dyn Plan>, String)> {
+    let block_size = tx.borrow().block_size();
+    let merge_join_cost =
+        MergeJoinPlan::estimate_block_access_cost(lhs.as_ref(), rhs.as_ref(), block_size)?;
+    let product_cost = lhs.get_block_access_cost()?
+        + lhs.get_record_access_cost()? * rhs.get_block_access_cost()?;
+    if merge_join_cost < product_cost {
+        let label = format!("MergeJoinPlan({} = {})", lhs_field, rhs_field);
+        let plan = MergeJoinPlan::new(lhs, rhs, lhs_field, rhs_field, temp_file_manager, tx)?;
+        Ok((Box::new(plan), label))
+    } else {
+        Ok((
+            Box::new(ProductPlan::new(lhs, rhs)?),
+            "ProductPlan".to_string(),
+        ))
+    }
+}
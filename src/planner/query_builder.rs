@@ -0,0 +1,163 @@
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::plan::{expression::Expression, predicate::ProductPredicate};
+
+#[derive(Error, Debug)]
+pub enum QueryBuilderError {
+    #[error("field {field} not found in {query}")]
+    FieldNotFound { field: String, query: String },
+}
+
+/**
+ * SQL の文字列を組み立てずに、Rust のコードから query を組み立てるための builder
+ *
+ * Query::table("student")
+ *     .join(
+ *         Query::table("dept"),
+ *         ProductPredicate::equal(Expression::Field("majorid".into()), Expression::Field("did".into())),
+ *     )
+ *     .filter(ProductPredicate::equal(Expression::Field("gradyear".into()), Expression::Constant(2020.into())))
+ *     .project(&["sname", "dname"])
+ *
+ * のように、table から始めて操作を重ねていく
+ * 組み立てた Query は QueryPlanner::create_plan_for_query で parse した query と同じように plan に変換され、
+ * select の push down などの rule も同じように適用される
+ * field の存在の確認は plan に変換する時に行う
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    node: QueryNode,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryNode {
+    /// table (view や external table も含む) の全ての record を読む
+    Table(String),
+    Filter(Box<Query>, ProductPredicate),
+    Project(Box<Query>, Vec<String>),
+    Product(Box<Query>, Box<Query>),
+    /// 式を評価した値を、指定した名前の field として追加する
+    Extend(Box<Query>, Vec<(String, Expression)>),
+}
+
+impl Query {
+    pub fn table(table_name: &str) -> Self {
+        Self {
+            node: QueryNode::Table(table_name.to_string()),
+        }
+    }
+
+    /// predicate を満たす record だけに絞り込む
+    pub fn filter(self, predicate: ProductPredicate) -> Self {
+        Self {
+            node: QueryNode::Filter(Box::new(self), predicate),
+        }
+    }
+
+    /// 指定した field だけを返すようにする
+    pub fn project(self, fields: &[&str]) -> Self {
+        Self {
+            node: QueryNode::Project(
+                Box::new(self),
+                fields.iter().map(|field| field.to_string()).collect(),
+            ),
+        }
+    }
+
+    /// other の record との全ての組み合わせを返す
+    pub fn product(self, other: Query) -> Self {
+        Self {
+            node: QueryNode::Product(Box::new(self), Box::new(other)),
+        }
+    }
+
+    /// other の record との組み合わせのうち、on を満たすものを返す
+    pub fn join(self, other: Query, on: ProductPredicate) -> Self {
+        self.product(other).filter(on)
+    }
+
+    /// expression を評価した値を name という field として追加する
+    pub fn extend(self, name: &str, expression: Expression) -> Self {
+        match self.node {
+            // 続けて extend した場合は一つの node にまとめる
+            QueryNode::Extend(child, mut expressions) => {
+                expressions.push((name.to_string(), expression));
+                Self {
+                    node: QueryNode::Extend(child, expressions),
+                }
+            }
+            node => Self {
+                node: QueryNode::Extend(
+                    Box::new(Self { node }),
+                    vec![(name.to_string(), expression)],
+                ),
+            },
+        }
+    }
+
+    pub fn node(&self) -> &QueryNode {
+        &self.node
+    }
+}
+
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.node {
+            QueryNode::Table(table_name) => write!(f, "table({})", table_name),
+            QueryNode::Filter(child, predicate) => write!(f, "{}.filter({})", child, predicate),
+            QueryNode::Project(child, fields) => {
+                write!(f, "{}.project({})", child, fields.join(", "))
+            }
+            QueryNode::Product(lhs, rhs) => write!(f, "{}.product({})", lhs, rhs),
+            QueryNode::Extend(child, expressions) => write!(
+                f,
+                "{}.extend({})",
+                child,
+                expressions
+                    .iter()
+                    .map(|(name, expression)| format!("{} as {}", expression, name))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod query_builder_test {
+    use crate::query::constant::Constant;
+
+    use super::*;
+
+    #[test]
+    fn test_build_query() {
+        let query = Query::table("student")
+            .join(
+                Query::table("dept"),
+                ProductPredicate::equal(
+                    Expression::Field("majorid".to_string()),
+                    Expression::Field("did".to_string()),
+                ),
+            )
+            .filter(ProductPredicate::equal(
+                Expression::Field("gradyear".to_string()),
+                Expression::Constant(Constant::from(2020)),
+            ))
+            .extend("graduated", Expression::Constant(Constant::from(true)))
+            .extend("one", Expression::Constant(Constant::from(1)))
+            .project(&["sname", "dname", "graduated"]);
+        assert_eq!(
+            query.to_string(),
+            "table(student).product(table(dept)).filter(majorid = did).filter(gradyear = 2020).extend(true as graduated, 1 as one).project(sname, dname, graduated)"
+        );
+        let QueryNode::Project(child, fields) = query.node() else {
+            panic!("project should be the outermost node");
+        };
+        assert_eq!(fields, &vec!["sname", "dname", "graduated"]);
+        assert!(
+            matches!(child.node(), QueryNode::Extend(_, expressions) if expressions.len() == 2)
+        );
+    }
+}
@@ -3,13 +3,43 @@ use std::{cell::RefCell, rc::Rc};
 use anyhow::Result as AnyhowResult;
 
 use crate::{
-    parse::content::query_data::QueryData, plan::plan::Plan, tx::transaction::Transaction,
+    parse::content::query_data::QueryData,
+    plan::{instrumented_plan::ExplainNode, plan::Plan},
+    query::memory_budget::MemoryBudget,
+    tx::transaction::Transaction,
 };
 
+use super::{query_builder::Query, row_policy::RowPolicy};
+
+/**
+ * query の plan を作成する
+ *
+ * budget は 1 つの query の中で、plan の作成 (統計情報の収集など) と実行でメモリ上に値を保持する operator が共有する
+ */
 pub trait QueryPlanner {
+    /// query の plan を作成する
+    /// query が (view を通して間接的に読むものも含めて) 読む table に row_policy の predicate が設定されている場合、それも条件に加える
     fn create_plan(
         &self,
         data: &QueryData,
+        row_policy: &RowPolicy,
+        budget: &MemoryBudget,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Box<dyn Plan>>;
+    /// create_plan と同じ plan を、各 plan が実際に返した record の数と読んだ block の数を数えるようにして作成する
+    /// 数えた値は、一緒に返す ExplainNode の tree から plan の見積もりと並べて参照できる
+    fn create_instrumented_plan(
+        &self,
+        data: &QueryData,
+        row_policy: &RowPolicy,
+        budget: &MemoryBudget,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<(Box<dyn Plan>, ExplainNode)>;
+    /// builder で組み立てた query の plan を作成する
+    fn create_plan_for_query(
+        &self,
+        query: &Query,
+        budget: &MemoryBudget,
         tx: &Rc<RefCell<Transaction>>,
     ) -> AnyhowResult<Box<dyn Plan>>;
 }
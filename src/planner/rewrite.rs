@@ -0,0 +1,263 @@
+pub mod empty_result;
+pub mod predicate_pushdown;
+pub mod predicate_simplification;
+pub mod projection_pruning;
+
+use anyhow::{Context, Result as AnyhowResult};
+
+use super::logical_plan::LogicalPlan;
+
+/// rule を適用した結果
+pub enum Rewrite {
+    /// plan を書き換えた
+    Changed(LogicalPlan),
+    /// rule が適用できなかったので、受け取った plan をそのまま返す
+    Unchanged(LogicalPlan),
+}
+
+/**
+ * plan tree の書き換え rule が実装する trait
+ *
+ * rule は受け取った node を root とする部分木だけを見て書き換える。子の node への適用は Rewriter が行う
+ * 書き換えた後の plan は、書き換える前の plan と同じ record を出力しなければならない
+ */
+pub trait RewriteRule {
+    /// rule の名前。rule の適用に失敗した時の error に含める
+    fn name(&self) -> &str;
+    /// plan の root の node に rule を適用する
+    fn apply(&self, plan: LogicalPlan) -> AnyhowResult<Rewrite>;
+}
+
+/**
+ * rule の集合を、plan tree が変化しなくなるまで繰り返し適用する
+ *
+ * 1 回の pass では tree を葉から順に辿り、各 node に全ての rule を登録された順に適用する
+ * 互いに書き戻し合う rule があっても止まるように、pass の回数には上限を設ける
+ */
+pub struct Rewriter {
+    rules: Vec<Box<dyn RewriteRule>>,
+    max_passes: usize,
+}
+
+impl Default for Rewriter {
+    fn default() -> Self {
+        // 条件を単純にしてから押し下げる
+        Self::new(vec![
+            Box::new(predicate_simplification::PredicateSimplification),
+            Box::new(predicate_pushdown::PredicatePushdown),
+            Box::new(projection_pruning::ProjectionPruning),
+            Box::new(empty_result::EmptyResult),
+        ])
+    }
+}
+
+impl Rewriter {
+    const DEFAULT_MAX_PASSES: usize = 16;
+
+    pub fn new(rules: Vec<Box<dyn RewriteRule>>) -> Self {
+        Self {
+            rules,
+            max_passes: Self::DEFAULT_MAX_PASSES,
+        }
+    }
+
+    /// plan が変化しなくなるか、pass の回数が上限に達するまで rule を適用する
+    pub fn rewrite(&self, mut plan: LogicalPlan) -> AnyhowResult<LogicalPlan> {
+        for _ in 0..self.max_passes {
+            let (rewritten, changed) = self.rewrite_once(plan)?;
+            plan = rewritten;
+            if !changed {
+                break;
+            }
+        }
+        Ok(plan)
+    }
+
+    /// 葉から順に各 node へ rule を 1 回ずつ適用する。どこかが書き換わった場合は true も返す
+    fn rewrite_once(&self, plan: LogicalPlan) -> AnyhowResult<(LogicalPlan, bool)> {
+        let (mut plan, mut changed) = match plan {
+            // 空の plan の中は実行しないので書き換えない
+            LogicalPlan::Leaf { .. } | LogicalPlan::Empty(_) => (plan, false),
+            LogicalPlan::Product(lhs, rhs) => {
+                let (lhs, lhs_changed) = self.rewrite_once(*lhs)?;
+                let (rhs, rhs_changed) = self.rewrite_once(*rhs)?;
+                (LogicalPlan::product(lhs, rhs), lhs_changed || rhs_changed)
+            }
+            LogicalPlan::IndexJoin {
+                lhs,
+                rhs,
+                index_info,
+                join_field,
+                tx,
+            } => {
+                let (lhs, lhs_changed) = self.rewrite_once(*lhs)?;
+                (
+                    LogicalPlan::index_join(lhs, *rhs, index_info, join_field, tx),
+                    lhs_changed,
+                )
+            }
+            LogicalPlan::MergeJoin {
+                lhs,
+                rhs,
+                lhs_field,
+                rhs_field,
+                temp_file_manager,
+                tx,
+            } => {
+                let (lhs, lhs_changed) = self.rewrite_once(*lhs)?;
+                let (rhs, rhs_changed) = self.rewrite_once(*rhs)?;
+                (
+                    LogicalPlan::merge_join(lhs, rhs, lhs_field, rhs_field, temp_file_manager, tx),
+                    lhs_changed || rhs_changed,
+                )
+            }
+            LogicalPlan::Select(child, predicate) => {
+                let (child, child_changed) = self.rewrite_once(*child)?;
+                (LogicalPlan::select(child, predicate), child_changed)
+            }
+            LogicalPlan::Project(child, fields) => {
+                let (child, child_changed) = self.rewrite_once(*child)?;
+                (LogicalPlan::project(child, fields), child_changed)
+            }
+            LogicalPlan::Extend(child, expressions) => {
+                let (child, child_changed) = self.rewrite_once(*child)?;
+                (LogicalPlan::extend(child, expressions), child_changed)
+            }
+            LogicalPlan::GroupBy {
+                child,
+                group_fields,
+                aggregations,
+                temp_file_manager,
+                tx,
+            } => {
+                let (child, child_changed) = self.rewrite_once(*child)?;
+                (
+                    LogicalPlan::group_by(child, group_fields, aggregations, temp_file_manager, tx),
+                    child_changed,
+                )
+            }
+            LogicalPlan::Distinct {
+                child,
+                temp_file_manager,
+                tx,
+            } => {
+                let (child, child_changed) = self.rewrite_once(*child)?;
+                (
+                    LogicalPlan::distinct(child, temp_file_manager, tx),
+                    child_changed,
+                )
+            }
+            LogicalPlan::Sort {
+                child,
+                sort_keys,
+                temp_file_manager,
+                tx,
+            } => {
+                let (child, child_changed) = self.rewrite_once(*child)?;
+                (
+                    LogicalPlan::sort(child, sort_keys, temp_file_manager, tx),
+                    child_changed,
+                )
+            }
+        };
+        for rule in &self.rules {
+            let rewrite = rule
+                .apply(plan)
+                .with_context(|| format!("failed to apply rewrite rule {}", rule.name()))?;
+            plan = match rewrite {
+                Rewrite::Changed(plan) => {
+                    changed = true;
+                    plan
+                }
+                Rewrite::Unchanged(plan) => plan,
+            };
+        }
+        Ok((plan, changed))
+    }
+}
+
+#[cfg(test)]
+mod rewrite_test {
+    use super::*;
+    use crate::{
+        plan::plan::MockPlan,
+        record::schema::{FieldInfo, Schema},
+    };
+
+    fn leaf(name: &str) -> LogicalPlan {
+        let mut schema = Schema::new();
+        schema.add_field(name, FieldInfo::Integer);
+        let mut plan = MockPlan::new();
+        plan.expect_get_schema().return_const(schema);
+        LogicalPlan::leaf(Box::new(plan), name.to_string())
+    }
+
+    /// Project を 1 つずつ剥がしていく rule
+    struct RemoveProject;
+
+    impl RewriteRule for RemoveProject {
+        fn name(&self) -> &str {
+            "remove_project"
+        }
+        fn apply(&self, plan: LogicalPlan) -> AnyhowResult<Rewrite> {
+            Ok(match plan {
+                LogicalPlan::Project(child, _) => Rewrite::Changed(*child),
+                plan => Rewrite::Unchanged(plan),
+            })
+        }
+    }
+
+    #[test]
+    fn test_rewrite_until_fixed_point() {
+        // project(project(project(a)))
+        let plan = LogicalPlan::project(
+            LogicalPlan::project(
+                LogicalPlan::project(leaf("a"), vec!["a".to_string()]),
+                vec!["a".to_string()],
+            ),
+            vec!["a".to_string()],
+        );
+        let rewriter = Rewriter::new(vec![Box::new(RemoveProject)]);
+        let plan = rewriter.rewrite(plan).unwrap();
+        assert!(matches!(plan, LogicalPlan::Leaf { .. }));
+        assert_eq!(plan.fields(), vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn test_max_passes() {
+        let plan = LogicalPlan::product(
+            LogicalPlan::project(leaf("a"), vec!["a".to_string()]),
+            LogicalPlan::project(leaf("b"), vec!["b".to_string()]),
+        );
+        // pass の上限が 0 の場合は何も書き換えない
+        let rewriter = Rewriter {
+            rules: vec![Box::new(RemoveProject)],
+            max_passes: 0,
+        };
+        let plan = rewriter.rewrite(plan).unwrap();
+        let LogicalPlan::Product(lhs, rhs) = plan else {
+            panic!("expected product");
+        };
+        assert!(matches!(*lhs, LogicalPlan::Project(..)));
+        assert!(matches!(*rhs, LogicalPlan::Project(..)));
+    }
+
+    struct FailingRule;
+
+    impl RewriteRule for FailingRule {
+        fn name(&self) -> &str {
+            "failing"
+        }
+        fn apply(&self, _plan: LogicalPlan) -> AnyhowResult<Rewrite> {
+            Err(anyhow::anyhow!("broken"))
+        }
+    }
+
+    #[test]
+    fn test_error_contains_rule_name() {
+        let rewriter = Rewriter::new(vec![Box::new(FailingRule)]);
+        let err = rewriter.rewrite(leaf("a")).err().unwrap();
+        assert_eq!(err.to_string(), "failed to apply rewrite rule failing");
+        assert_eq!(err.root_cause().to_string(), "broken");
+    }
+}
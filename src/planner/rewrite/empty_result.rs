@@ -0,0 +1,188 @@
+use std::cmp::Ordering;
+
+use anyhow::Result as AnyhowResult;
+
+use crate::{
+    plan::predicate::ProductPredicate, planner::logical_plan::LogicalPlan, query::coercion,
+};
+
+use super::{Rewrite, RewriteRule};
+
+/**
+ * 結果が空になることがわかる plan を、何も読まない空の plan に置き換える rule
+ *
+ * 常に偽の条件を持つ select と、空の plan を子に持つ product / select / project を空の plan にする
+ * field = 定数 の条件で、定数が統計情報から分かる field の値の範囲 [min, max] の外にある select も空の plan にする
+ * 空の plan は葉から根に向かって伝わっていくので、query 全体が空になる場合は table を一切読まずに済む
+ */
+pub struct EmptyResult;
+
+impl EmptyResult {
+    // predicate が field と範囲外の定数が等しいことを要求しているかどうか
+    fn is_out_of_range(child: &LogicalPlan, predicate: &ProductPredicate) -> bool {
+        predicate.fields().iter().any(|field| {
+            let Some(val) = predicate.equates_with_constant(field) else {
+                return false;
+            };
+            let Some((min, max)) = child.value_range(field) else {
+                return false;
+            };
+            coercion::compare(&val, &min) == Some(Ordering::Less)
+                || coercion::compare(&val, &max) == Some(Ordering::Greater)
+        })
+    }
+}
+
+impl RewriteRule for EmptyResult {
+    fn name(&self) -> &str {
+        "empty_result"
+    }
+
+    fn apply(&self, plan: LogicalPlan) -> AnyhowResult<Rewrite> {
+        let is_empty = match &plan {
+            LogicalPlan::Leaf { .. } | LogicalPlan::Empty(_) => false,
+            LogicalPlan::Product(lhs, rhs) | LogicalPlan::MergeJoin { lhs, rhs, .. } => {
+                lhs.is_empty() || rhs.is_empty()
+            }
+            LogicalPlan::IndexJoin { lhs, .. } => lhs.is_empty(),
+            LogicalPlan::Select(child, predicate) => {
+                child.is_empty()
+                    || predicate.is_contradiction()
+                    || Self::is_out_of_range(child, predicate)
+            }
+            // record が無ければ group も無い
+            LogicalPlan::Project(child, _)
+            | LogicalPlan::Extend(child, _)
+            | LogicalPlan::GroupBy { child, .. }
+            | LogicalPlan::Distinct { child, .. }
+            | LogicalPlan::Sort { child, .. } => child.is_empty(),
+        };
+        Ok(if is_empty {
+            Rewrite::Changed(LogicalPlan::empty(plan))
+        } else {
+            Rewrite::Unchanged(plan)
+        })
+    }
+}
+
+#[cfg(test)]
+mod empty_result_test {
+    use super::*;
+    use crate::{
+        parse::parser::{Parser, ParserImpl},
+        plan::{expression::Expression, plan::MockPlan},
+        planner::rewrite::Rewriter,
+        query::constant::Constant,
+        record::schema::{FieldInfo, Schema},
+    };
+
+    fn parse_predicate(predicate: &str) -> ProductPredicate {
+        ParserImpl::new(predicate.to_string())
+            .unwrap()
+            .parse_predicate()
+            .unwrap()
+    }
+
+    fn leaf(field: &str) -> LogicalPlan {
+        leaf_with_range(field, None)
+    }
+
+    // 統計情報から field の値の範囲が分かっている葉
+    fn leaf_with_range(field: &str, range: Option<(i32, i32)>) -> LogicalPlan {
+        let mut schema = Schema::new();
+        schema.add_field(field, FieldInfo::Integer);
+        let mut plan = MockPlan::new();
+        plan.expect_get_schema().return_const(schema);
+        plan.expect_get_value_range()
+            .return_const(range.map(|(min, max)| (Constant::Int(min), Constant::Int(max))));
+        // 空の plan の中の plan は scan を開かない
+        plan.expect_open_read_scan().never();
+        LogicalPlan::leaf(Box::new(plan), field.to_string())
+    }
+
+    #[test]
+    fn test_contradiction_makes_whole_query_empty() {
+        let predicate = ParserImpl::new("a = 1 and 1 = 2".to_string())
+            .unwrap()
+            .parse_predicate()
+            .unwrap();
+        // select a, b from (select from a where a = 1 and 1 = 2), b
+        let plan = LogicalPlan::project(
+            LogicalPlan::product(LogicalPlan::select(leaf("a"), predicate), leaf("b")),
+            vec!["a".to_string(), "b".to_string()],
+        );
+        let plan = Rewriter::default().rewrite(plan).unwrap();
+        assert!(plan.is_empty());
+        assert_eq!(plan.fields(), vec!["a".to_string(), "b".to_string()]);
+
+        let plan = plan.into_plan().unwrap();
+        assert_eq!(plan.get_record_access_cost().unwrap(), 0);
+        let mut scan = plan.open_read_scan().unwrap();
+        scan.before_first().unwrap();
+        assert!(!scan.move_next().unwrap());
+    }
+
+    #[test]
+    fn test_satisfiable_predicate_is_kept() {
+        let predicate = ParserImpl::new("a = 1".to_string())
+            .unwrap()
+            .parse_predicate()
+            .unwrap();
+        let plan = LogicalPlan::select(leaf("a"), predicate);
+        assert!(matches!(
+            EmptyResult.apply(plan).unwrap(),
+            Rewrite::Unchanged(_)
+        ));
+    }
+
+    #[test]
+    fn test_out_of_range_constant_makes_select_empty() {
+        // 範囲の外の値
+        for predicate in ["a = 0", "a = 11", "11 = a"] {
+            let plan = LogicalPlan::select(
+                leaf_with_range("a", Some((1, 10))),
+                parse_predicate(predicate),
+            );
+            assert!(
+                matches!(EmptyResult.apply(plan).unwrap(), Rewrite::Changed(plan) if plan.is_empty()),
+                "{}",
+                predicate
+            );
+        }
+        // 範囲の中の値は、table に実際にあるかどうか分からないので残す
+        for predicate in ["a = 1", "a = 5", "a = 10"] {
+            let plan = LogicalPlan::select(
+                leaf_with_range("a", Some((1, 10))),
+                parse_predicate(predicate),
+            );
+            assert!(
+                matches!(EmptyResult.apply(plan).unwrap(), Rewrite::Unchanged(_)),
+                "{}",
+                predicate
+            );
+        }
+        // 範囲が分からない場合も残す
+        let plan = LogicalPlan::select(leaf("a"), parse_predicate("a = 100"));
+        assert!(matches!(
+            EmptyResult.apply(plan).unwrap(),
+            Rewrite::Unchanged(_)
+        ));
+    }
+
+    #[test]
+    fn test_range_through_product_and_extend() {
+        // select from (extend a as c from a), b where c = 20
+        let plan = LogicalPlan::select(
+            LogicalPlan::product(
+                LogicalPlan::extend(
+                    leaf_with_range("a", Some((1, 10))),
+                    vec![("c".to_string(), Expression::Field("a".to_string()))],
+                ),
+                leaf_with_range("b", Some((100, 200))),
+            ),
+            parse_predicate("c = 20 and b = 150"),
+        );
+        let plan = Rewriter::default().rewrite(plan).unwrap();
+        assert!(plan.is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+use anyhow::Result as AnyhowResult;
+
+use crate::{
+    plan::{predicate::ProductPredicate, term::Term},
+    planner::logical_plan::LogicalPlan,
+};
+
+use super::{Rewrite, RewriteRule};
+
+/**
+ * product の上にある select の条件のうち、片側の field だけで評価できる term をその側の select に移す rule
+ *
+ * product の内側の plan は外側の record の数だけ読み直されるので、先に record を絞っておくと読む block の数が減る
+ * どちらの側の field も参照する term は product の上に残す
+ * index join の上にある select は、lhs の field だけで評価できる term を lhs に移す
+ * merge join の上にある select は、product と同じように両側に移す
+ */
+pub struct PredicatePushdown;
+
+impl RewriteRule for PredicatePushdown {
+    fn name(&self) -> &str {
+        "predicate_pushdown"
+    }
+
+    fn apply(&self, plan: LogicalPlan) -> AnyhowResult<Rewrite> {
+        let LogicalPlan::Select(child, predicate) = plan else {
+            return Ok(Rewrite::Unchanged(plan));
+        };
+        // product と merge join は、移した後に同じ種類の node で組み立て直す
+        type Join = Box<dyn FnOnce(LogicalPlan, LogicalPlan) -> LogicalPlan>;
+        let (lhs, rhs, join): (_, _, Join) = match *child {
+            LogicalPlan::Product(lhs, rhs) => (lhs, rhs, Box::new(LogicalPlan::product)),
+            LogicalPlan::MergeJoin {
+                lhs,
+                rhs,
+                lhs_field,
+                rhs_field,
+                temp_file_manager,
+                tx,
+            } => (
+                lhs,
+                rhs,
+                Box::new(move |lhs, rhs| {
+                    LogicalPlan::merge_join(lhs, rhs, lhs_field, rhs_field, temp_file_manager, tx)
+                }),
+            ),
+            LogicalPlan::IndexJoin {
+                lhs,
+                rhs,
+                index_info,
+                join_field,
+                tx,
+            } => {
+                // index join の rhs は index で読む table なので、lhs 側にだけ移す
+                let lhs_fields = lhs.fields();
+                let (lhs_terms, rest): (Vec<_>, Vec<_>) =
+                    predicate.terms().iter().cloned().partition(|term| {
+                        term.fields().iter().all(|field| lhs_fields.contains(field))
+                    });
+                if lhs_terms.is_empty() {
+                    return Ok(Rewrite::Unchanged(LogicalPlan::select(
+                        LogicalPlan::index_join(*lhs, *rhs, index_info, join_field, tx),
+                        predicate,
+                    )));
+                }
+                let lhs = LogicalPlan::select(*lhs, ProductPredicate::new(lhs_terms));
+                let join = LogicalPlan::index_join(lhs, *rhs, index_info, join_field, tx);
+                return Ok(Rewrite::Changed(if rest.is_empty() {
+                    join
+                } else {
+                    LogicalPlan::select(join, ProductPredicate::new(rest))
+                }));
+            }
+            child => return Ok(Rewrite::Unchanged(LogicalPlan::select(child, predicate))),
+        };
+        let (lhs_fields, rhs_fields) = (lhs.fields(), rhs.fields());
+        let covers = |fields: &[String], term: &Term| {
+            term.fields().iter().all(|field| fields.contains(field))
+        };
+        let (mut lhs_terms, mut rhs_terms, mut rest) = (vec![], vec![], vec![]);
+        for term in predicate.terms() {
+            if covers(&lhs_fields, term) {
+                lhs_terms.push(term.clone());
+            } else if covers(&rhs_fields, term) {
+                rhs_terms.push(term.clone());
+            } else {
+                rest.push(term.clone());
+            }
+        }
+        if lhs_terms.is_empty() && rhs_terms.is_empty() {
+            return Ok(Rewrite::Unchanged(LogicalPlan::select(
+                join(*lhs, *rhs),
+                predicate,
+            )));
+        }
+        let push = |plan: LogicalPlan, terms: Vec<Term>| {
+            if terms.is_empty() {
+                plan
+            } else {
+                LogicalPlan::select(plan, ProductPredicate::new(terms))
+            }
+        };
+        let product = join(push(*lhs, lhs_terms), push(*rhs, rhs_terms));
+        Ok(Rewrite::Changed(push(product, rest)))
+    }
+}
+
+#[cfg(test)]
+mod predicate_pushdown_test {
+    use super::*;
+    use crate::{
+        parse::parser::{Parser, ParserImpl},
+        plan::plan::MockPlan,
+        planner::rewrite::Rewriter,
+        record::schema::{FieldInfo, Schema},
+    };
+
+    fn leaf(fields: &[&str]) -> LogicalPlan {
+        let mut schema = Schema::new();
+        for field in fields {
+            schema.add_field(field, FieldInfo::Integer);
+        }
+        let mut plan = MockPlan::new();
+        plan.expect_get_schema().return_const(schema);
+        LogicalPlan::leaf(Box::new(plan), fields.join(","))
+    }
+
+    fn predicate(predicate: &str) -> ProductPredicate {
+        ParserImpl::new(predicate.to_string())
+            .unwrap()
+            .parse_predicate()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_push_down_to_both_sides() {
+        let plan = LogicalPlan::select(
+            LogicalPlan::product(leaf(&["a", "b"]), leaf(&["c"])),
+            predicate("a = 1 and b = c and c = 2"),
+        );
+        let Rewrite::Changed(plan) = PredicatePushdown.apply(plan).unwrap() else {
+            panic!("expected the predicate to be pushed down");
+        };
+        // 両側の field を参照する term だけが product の上に残る
+        let LogicalPlan::Select(product, rest) = plan else {
+            panic!("expected select");
+        };
+        assert_eq!(rest.to_string(), "b = c");
+        let LogicalPlan::Product(lhs, rhs) = *product else {
+            panic!("expected product");
+        };
+        assert!(matches!(*lhs, LogicalPlan::Select(_, ref p) if p.to_string() == "a = 1"));
+        assert!(matches!(*rhs, LogicalPlan::Select(_, ref p) if p.to_string() == "c = 2"));
+    }
+
+    #[test]
+    fn test_push_down_through_nested_products() {
+        // (x, y), z の product に対して、x と y だけを参照する term は内側の product の上まで下がる
+        let plan = LogicalPlan::select(
+            LogicalPlan::product(
+                LogicalPlan::product(leaf(&["x"]), leaf(&["y"])),
+                leaf(&["z"]),
+            ),
+            predicate("x = y and y = z"),
+        );
+        let plan = Rewriter::default().rewrite(plan).unwrap();
+        let LogicalPlan::Select(product, rest) = plan else {
+            panic!("expected select");
+        };
+        assert_eq!(rest.to_string(), "y = z");
+        let LogicalPlan::Product(lhs, _) = *product else {
+            panic!("expected product");
+        };
+        assert!(matches!(*lhs, LogicalPlan::Select(_, ref p) if p.to_string() == "x = y"));
+
+        // 両側を参照する term しかない場合は書き換えない
+        let plan = LogicalPlan::select(
+            LogicalPlan::product(leaf(&["x"]), leaf(&["y"])),
+            predicate("x = y"),
+        );
+        assert!(matches!(
+            PredicatePushdown.apply(plan).unwrap(),
+            Rewrite::Unchanged(_)
+        ));
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::Result as AnyhowResult;
+
+use crate::planner::logical_plan::LogicalPlan;
+
+use super::{Rewrite, RewriteRule};
+
+/**
+ * select の条件を単純にする rule
+ *
+ * 1 = 1 のような常に真の term と重複した term を取り除き、条件が無くなった select は取り除く
+ * 1 = 2 のような常に偽の term がある場合は、条件をその term だけにする
+ */
+pub struct PredicateSimplification;
+
+impl RewriteRule for PredicateSimplification {
+    fn name(&self) -> &str {
+        "predicate_simplification"
+    }
+
+    fn apply(&self, plan: LogicalPlan) -> AnyhowResult<Rewrite> {
+        let LogicalPlan::Select(child, predicate) = plan else {
+            return Ok(Rewrite::Unchanged(plan));
+        };
+        let simplified = predicate.simplify();
+        Ok(if simplified.terms().is_empty() {
+            Rewrite::Changed(*child)
+        } else if simplified.terms().len() == predicate.terms().len() {
+            // simplify は term を取り除くだけなので、数が同じなら何も変わっていない
+            Rewrite::Unchanged(LogicalPlan::Select(child, predicate))
+        } else {
+            Rewrite::Changed(LogicalPlan::Select(child, simplified))
+        })
+    }
+}
+
+#[cfg(test)]
+mod predicate_simplification_test {
+    use super::*;
+    use crate::{
+        parse::parser::{Parser, ParserImpl},
+        plan::{plan::MockPlan, predicate::ProductPredicate},
+        record::schema::{FieldInfo, Schema},
+    };
+
+    fn leaf() -> LogicalPlan {
+        let mut schema = Schema::new();
+        schema.add_field("a", FieldInfo::Integer);
+        schema.add_field("b", FieldInfo::Integer);
+        let mut plan = MockPlan::new();
+        plan.expect_get_schema().return_const(schema);
+        LogicalPlan::leaf(Box::new(plan), "leaf".to_string())
+    }
+
+    fn predicate(predicate: &str) -> ProductPredicate {
+        ParserImpl::new(predicate.to_string())
+            .unwrap()
+            .parse_predicate()
+            .unwrap()
+    }
+
+    fn simplify(predicate_str: &str) -> Rewrite {
+        let plan = LogicalPlan::select(leaf(), predicate(predicate_str));
+        PredicateSimplification.apply(plan).unwrap()
+    }
+
+    #[test]
+    fn test_simplify() {
+        // 常に真の term と重複した term (左右を入れ替えたものも含む) を取り除く
+        let Rewrite::Changed(LogicalPlan::Select(_, simplified)) =
+            simplify("a = 1 and 1 = 1 and b = a and a = b and 1 = a")
+        else {
+            panic!("expected simplified select");
+        };
+        assert_eq!(simplified.to_string(), "a = 1 and b = a");
+
+        // 条件が全て常に真なら select ごと取り除く
+        assert!(matches!(
+            simplify("1 = 1 and 'x' = 'x'"),
+            Rewrite::Changed(LogicalPlan::Leaf { .. })
+        ));
+
+        // 常に偽の term があれば、その term だけを残す
+        let Rewrite::Changed(LogicalPlan::Select(_, simplified)) =
+            simplify("a = 1 and 1 = 2 and b = 3")
+        else {
+            panic!("expected simplified select");
+        };
+        assert_eq!(simplified.to_string(), "1 = 2");
+        assert!(simplified.is_contradiction());
+
+        // これ以上単純にできない場合は書き換えない
+        assert!(matches!(simplify("a = 1 and b = 2"), Rewrite::Unchanged(_)));
+        assert!(matches!(simplify("1 = 2"), Rewrite::Unchanged(_)));
+    }
+}
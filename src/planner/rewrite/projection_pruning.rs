@@ -0,0 +1,176 @@
+use anyhow::Result as AnyhowResult;
+
+use crate::planner::logical_plan::LogicalPlan;
+
+use super::{Rewrite, RewriteRule};
+
+/**
+ * project で必要な field だけを、product (merge join を含む) の下まで押し下げる rule
+ *
+ * select の条件で使い終わった field などを product の手前で落とすことで、product の中を流れる record を小さくする
+ * 1 回の適用では 1 段だけ押し下げ、その下の段は Rewriter が次の pass で同じ rule を適用することで処理される
+ */
+pub struct ProjectionPruning;
+
+impl RewriteRule for ProjectionPruning {
+    fn name(&self) -> &str {
+        "projection_pruning"
+    }
+
+    fn apply(&self, plan: LogicalPlan) -> AnyhowResult<Rewrite> {
+        let LogicalPlan::Project(child, fields) = plan else {
+            return Ok(Rewrite::Unchanged(plan));
+        };
+        Ok(match *child {
+            // project が重なっている場合は外側の field だけを残す
+            LogicalPlan::Project(grandchild, _) => {
+                Rewrite::Changed(LogicalPlan::project(*grandchild, fields))
+            }
+            LogicalPlan::Product(lhs, rhs) => match (prune(*lhs, &fields), prune(*rhs, &fields)) {
+                ((lhs, false), (rhs, false)) => {
+                    unchanged_or_redundant(LogicalPlan::product(lhs, rhs), fields)
+                }
+                ((lhs, _), (rhs, _)) => {
+                    Rewrite::Changed(LogicalPlan::project(LogicalPlan::product(lhs, rhs), fields))
+                }
+            },
+            LogicalPlan::MergeJoin {
+                lhs,
+                rhs,
+                lhs_field,
+                rhs_field,
+                temp_file_manager,
+                tx,
+            } => {
+                // merge join の中では、join する field も必要になる
+                let mut needed = fields.clone();
+                needed.extend([lhs_field.clone(), rhs_field.clone()]);
+                let ((lhs, lhs_pruned), (rhs, rhs_pruned)) =
+                    (prune(*lhs, &needed), prune(*rhs, &needed));
+                let join =
+                    LogicalPlan::merge_join(lhs, rhs, lhs_field, rhs_field, temp_file_manager, tx);
+                if lhs_pruned || rhs_pruned {
+                    Rewrite::Changed(LogicalPlan::project(join, fields))
+                } else {
+                    unchanged_or_redundant(join, fields)
+                }
+            }
+            LogicalPlan::Select(grandchild, predicate) => {
+                // select の下では、条件で参照する field も必要になる
+                let mut needed = fields.clone();
+                needed.extend(predicate.fields());
+                match prune(*grandchild, &needed) {
+                    (grandchild, false) => {
+                        unchanged_or_redundant(LogicalPlan::select(grandchild, predicate), fields)
+                    }
+                    (grandchild, true) => Rewrite::Changed(LogicalPlan::project(
+                        LogicalPlan::select(grandchild, predicate),
+                        fields,
+                    )),
+                }
+            }
+            child => unchanged_or_redundant(child, fields),
+        })
+    }
+}
+
+/// plan が needed に含まれない field を出力する場合は、needed の field だけを残す project を上に置く
+/// 書き換えた場合は true も返す
+fn prune(plan: LogicalPlan, needed: &[String]) -> (LogicalPlan, bool) {
+    let fields = plan.fields();
+    if fields.iter().all(|field| needed.contains(field)) {
+        return (plan, false);
+    }
+    let kept = fields
+        .into_iter()
+        .filter(|field| needed.contains(field))
+        .collect();
+    (LogicalPlan::project(plan, kept), true)
+}
+
+/// child が fields をそのままの順で出力する場合は、何もしない project を取り除く
+fn unchanged_or_redundant(child: LogicalPlan, fields: Vec<String>) -> Rewrite {
+    if child.fields() == fields {
+        Rewrite::Changed(child)
+    } else {
+        Rewrite::Unchanged(LogicalPlan::project(child, fields))
+    }
+}
+
+#[cfg(test)]
+mod projection_pruning_test {
+    use super::*;
+    use crate::{
+        parse::parser::{Parser, ParserImpl},
+        plan::plan::MockPlan,
+        planner::rewrite::Rewriter,
+        record::schema::{FieldInfo, Schema},
+    };
+
+    fn leaf(fields: &[&str]) -> LogicalPlan {
+        let mut schema = Schema::new();
+        for field in fields {
+            schema.add_field(field, FieldInfo::Integer);
+        }
+        let mut plan = MockPlan::new();
+        plan.expect_get_schema().return_const(schema);
+        LogicalPlan::leaf(Box::new(plan), fields.join(","))
+    }
+
+    fn fields(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|field| field.to_string()).collect()
+    }
+
+    #[test]
+    fn test_prune_below_product() {
+        // select a from (a, b, c), (d, e) where b = d
+        let predicate = ParserImpl::new("b = d".to_string())
+            .unwrap()
+            .parse_predicate()
+            .unwrap();
+        let plan = LogicalPlan::project(
+            LogicalPlan::select(
+                LogicalPlan::product(leaf(&["a", "b", "c"]), leaf(&["d", "e"])),
+                predicate,
+            ),
+            fields(&["a"]),
+        );
+        let plan = Rewriter::new(vec![Box::new(ProjectionPruning)])
+            .rewrite(plan)
+            .unwrap();
+
+        let LogicalPlan::Project(select, top_fields) = plan else {
+            panic!("expected project");
+        };
+        assert_eq!(top_fields, fields(&["a"]));
+        let LogicalPlan::Select(product, _) = *select else {
+            panic!("expected select");
+        };
+        // product の下では、select の条件と出力に使う field だけが残る
+        assert_eq!(product.fields(), fields(&["a", "b", "d"]));
+        let LogicalPlan::Product(lhs, rhs) = *product else {
+            panic!("expected product");
+        };
+        assert!(matches!(*lhs, LogicalPlan::Project(_, ref f) if *f == fields(&["a", "b"])));
+        assert!(matches!(*rhs, LogicalPlan::Project(_, ref f) if *f == fields(&["d"])));
+    }
+
+    #[test]
+    fn test_remove_redundant_project() {
+        let plan = LogicalPlan::project(
+            LogicalPlan::project(leaf(&["a", "b"]), fields(&["a", "b"])),
+            fields(&["a", "b"]),
+        );
+        let plan = Rewriter::new(vec![Box::new(ProjectionPruning)])
+            .rewrite(plan)
+            .unwrap();
+        assert!(matches!(plan, LogicalPlan::Leaf { .. }));
+
+        // 並び順を変える project は残す
+        let plan = LogicalPlan::project(leaf(&["a", "b"]), fields(&["b", "a"]));
+        assert!(matches!(
+            ProjectionPruning.apply(plan).unwrap(),
+            Rewrite::Unchanged(_)
+        ));
+    }
+}
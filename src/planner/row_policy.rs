@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+
+use crate::plan::predicate::ProductPredicate;
+
+/**
+ * table ごとの row-level security の設定
+ *
+ * table に predicate を設定すると、その table を読み書きする文の where 句に predicate が AND される
+ * session ごとに設定することで、利用者ごとに読み書きできる record を制限する (multi-tenant のデータ分離など) ために使う
+ * predicate 中の field は、設定した table の field を参照している必要がある
+ */
+#[derive(Debug, Clone, Default)]
+pub struct RowPolicy {
+    // table 名 -> predicate
+    predicates: HashMap<String, ProductPredicate>,
+}
+
+impl RowPolicy {
+    pub fn new() -> RowPolicy {
+        RowPolicy {
+            predicates: HashMap::new(),
+        }
+    }
+
+    /// table の predicate を設定する。すでに設定されている場合は置き換える
+    pub fn set_predicate(&mut self, table_name: &str, predicate: ProductPredicate) {
+        self.predicates.insert(table_name.to_string(), predicate);
+    }
+
+    /// table の predicate を削除し、設定されていた predicate を返す
+    pub fn remove_predicate(&mut self, table_name: &str) -> Option<ProductPredicate> {
+        self.predicates.remove(table_name)
+    }
+
+    pub fn predicate(&self, table_name: &str) -> Option<&ProductPredicate> {
+        self.predicates.get(table_name)
+    }
+
+    /// predicate に、tables に設定されている predicate を AND したものを返す
+    pub fn restrict(&self, predicate: &ProductPredicate, tables: &[String]) -> ProductPredicate {
+        tables
+            .iter()
+            .filter_map(|table_name| self.predicates.get(table_name))
+            .fold(predicate.clone(), |predicate, policy| {
+                predicate.conjoin(policy)
+            })
+    }
+}
+
+#[cfg(test)]
+mod row_policy_test {
+    use super::RowPolicy;
+    use crate::{
+        plan::{
+            expression::Expression,
+            predicate::ProductPredicate,
+            term::{EqualTerm, Term},
+        },
+        query::constant::Constant,
+    };
+
+    fn equal(field_name: &str, val: i32) -> ProductPredicate {
+        ProductPredicate::new(vec![Term::Equal(EqualTerm::new(
+            Expression::Field(field_name.to_string()),
+            Expression::Constant(Constant::Int(val)),
+        ))])
+    }
+
+    #[test]
+    fn test_restrict() {
+        let mut row_policy = RowPolicy::new();
+        row_policy.set_predicate("student", equal("tenant", 1));
+        row_policy.set_predicate("dept", equal("dtenant", 1));
+
+        let predicate = equal("sid", 3);
+        let tables = vec!["student".to_string(), "course".to_string()];
+        assert_eq!(
+            row_policy.restrict(&predicate, &tables).to_string(),
+            "sid = 3 and tenant = 1"
+        );
+
+        row_policy.remove_predicate("student");
+        assert_eq!(
+            row_policy.restrict(&predicate, &tables).to_string(),
+            "sid = 3"
+        );
+    }
+}
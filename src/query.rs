@@ -1,8 +1,24 @@
+pub mod aggregation_fn;
+pub mod coercion;
 pub mod constant;
+pub mod csv_scan;
+pub mod date;
+pub mod empty_scan;
 pub mod expression;
+pub mod extend_scan;
+pub mod group_by_scan;
+pub mod index_join_scan;
+pub mod index_only_scan;
+pub mod index_select_scan;
+pub mod memory_budget;
+pub mod merge_join_scan;
 pub mod predicate;
 pub mod product_scan;
 pub mod project_scan;
+pub mod record_comparator;
+pub mod row;
 pub mod scan;
 pub mod select_scan;
+pub mod sort_scan;
 pub mod term;
+pub mod values_scan;
@@ -0,0 +1,338 @@
+use anyhow::Result as AnyhowResult;
+use thiserror::Error;
+
+use super::{constant::Constant, scan::ReadScan};
+
+#[derive(Error, Debug)]
+pub enum AggregationFnError {
+    #[error("[aggregation] invalid call : {0}")]
+    InvalidCall(String),
+    #[error("[aggregation] type mismatch : {0}")]
+    TypeMismatch(String),
+    #[error("[aggregation] overflow : {0}")]
+    Overflow(String),
+}
+
+/**
+ * group ごとに record の値を集計する関数が実装する trait
+ *
+ * group の最初の record で process_first を、残りの record で process_next を呼び、最後に value で集計した値を取得する
+ */
+pub trait AggregationFn {
+    /// group の最初の record の値で集計をやり直す
+    fn process_first(&mut self, scan: &dyn ReadScan) -> AnyhowResult<()>;
+    /// group の 2 番目以降の record の値を集計に加える
+    fn process_next(&mut self, scan: &dyn ReadScan) -> AnyhowResult<()>;
+    /// 集計した値を出力する field の名前
+    fn field_name(&self) -> &str;
+    /// 集計した値。process_first を呼ぶ前は error を返す
+    fn value(&self) -> AnyhowResult<Constant>;
+}
+
+fn not_processed(field_name: &str) -> AggregationFnError {
+    AggregationFnError::InvalidCall(format!(
+        "no record has been aggregated for {}. you need to call process_first first",
+        field_name
+    ))
+}
+
+/// group の record の数を数える
+pub struct CountFn {
+    name: String,
+    field: String,
+    count: Option<i32>,
+}
+
+impl CountFn {
+    pub fn new(name: &str, field: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            field: field.to_string(),
+            count: None,
+        }
+    }
+}
+
+impl AggregationFn for CountFn {
+    fn process_first(&mut self, scan: &dyn ReadScan) -> AnyhowResult<()> {
+        // field が存在することだけを確かめる
+        scan.get_val(&self.field)?;
+        self.count = Some(1);
+        Ok(())
+    }
+    fn process_next(&mut self, scan: &dyn ReadScan) -> AnyhowResult<()> {
+        scan.get_val(&self.field)?;
+        let count = self.count.ok_or_else(|| not_processed(&self.name))?;
+        self.count = Some(count.checked_add(1).ok_or_else(|| {
+            AggregationFnError::Overflow(format!("{} exceeds the range of int", self.name))
+        })?);
+        Ok(())
+    }
+    fn field_name(&self) -> &str {
+        &self.name
+    }
+    fn value(&self) -> AnyhowResult<Constant> {
+        Ok(Constant::Int(
+            self.count.ok_or_else(|| not_processed(&self.name))?,
+        ))
+    }
+}
+
+/// group の中で field の最大値を求める
+pub struct MaxFn {
+    name: String,
+    field: String,
+    val: Option<Constant>,
+}
+
+impl MaxFn {
+    pub fn new(name: &str, field: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            field: field.to_string(),
+            val: None,
+        }
+    }
+}
+
+impl AggregationFn for MaxFn {
+    fn process_first(&mut self, scan: &dyn ReadScan) -> AnyhowResult<()> {
+        self.val = Some(scan.get_val(&self.field)?);
+        Ok(())
+    }
+    fn process_next(&mut self, scan: &dyn ReadScan) -> AnyhowResult<()> {
+        let val = scan.get_val(&self.field)?;
+        // 同じ field の値はすべて同じ型なので、Constant の順序で比較できる
+        if self.val.as_ref().is_none_or(|max| val > *max) {
+            self.val = Some(val);
+        }
+        Ok(())
+    }
+    fn field_name(&self) -> &str {
+        &self.name
+    }
+    fn value(&self) -> AnyhowResult<Constant> {
+        Ok(self.val.clone().ok_or_else(|| not_processed(&self.name))?)
+    }
+}
+
+/// group の中で field の最小値を求める
+pub struct MinFn {
+    name: String,
+    field: String,
+    val: Option<Constant>,
+}
+
+impl MinFn {
+    pub fn new(name: &str, field: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            field: field.to_string(),
+            val: None,
+        }
+    }
+}
+
+impl AggregationFn for MinFn {
+    fn process_first(&mut self, scan: &dyn ReadScan) -> AnyhowResult<()> {
+        self.val = Some(scan.get_val(&self.field)?);
+        Ok(())
+    }
+    fn process_next(&mut self, scan: &dyn ReadScan) -> AnyhowResult<()> {
+        let val = scan.get_val(&self.field)?;
+        if self.val.as_ref().is_none_or(|min| val < *min) {
+            self.val = Some(val);
+        }
+        Ok(())
+    }
+    fn field_name(&self) -> &str {
+        &self.name
+    }
+    fn value(&self) -> AnyhowResult<Constant> {
+        Ok(self.val.clone().ok_or_else(|| not_processed(&self.name))?)
+    }
+}
+
+// int の field の値を読む。sum と avg は int の field しか集計できない
+fn get_int(scan: &dyn ReadScan, field: &str) -> AnyhowResult<i64> {
+    match scan.get_val(field)? {
+        Constant::Int(val) => Ok(val as i64),
+        val => Err(AggregationFnError::TypeMismatch(format!(
+            "cannot aggregate {} of field {}. expected int",
+            val, field
+        ))
+        .into()),
+    }
+}
+
+/// group の中で field の値の合計を求める
+/// 途中の合計は i64 で持ち、最後の値が int の範囲を超える場合は error を返す
+pub struct SumFn {
+    name: String,
+    field: String,
+    sum: Option<i64>,
+}
+
+impl SumFn {
+    pub fn new(name: &str, field: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            field: field.to_string(),
+            sum: None,
+        }
+    }
+}
+
+impl AggregationFn for SumFn {
+    fn process_first(&mut self, scan: &dyn ReadScan) -> AnyhowResult<()> {
+        self.sum = Some(get_int(scan, &self.field)?);
+        Ok(())
+    }
+    fn process_next(&mut self, scan: &dyn ReadScan) -> AnyhowResult<()> {
+        let sum = self.sum.ok_or_else(|| not_processed(&self.name))?;
+        self.sum = Some(sum + get_int(scan, &self.field)?);
+        Ok(())
+    }
+    fn field_name(&self) -> &str {
+        &self.name
+    }
+    fn value(&self) -> AnyhowResult<Constant> {
+        let sum = self.sum.ok_or_else(|| not_processed(&self.name))?;
+        let sum = i32::try_from(sum).map_err(|_| {
+            AggregationFnError::Overflow(format!("{} exceeds the range of int", self.name))
+        })?;
+        Ok(Constant::Int(sum))
+    }
+}
+
+/// group の中で field の値の平均を求める
+/// 値は int なので、小数点以下は 0 の方向に切り捨てる
+pub struct AvgFn {
+    name: String,
+    field: String,
+    // (合計, record の数)
+    state: Option<(i64, i64)>,
+}
+
+impl AvgFn {
+    pub fn new(name: &str, field: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            field: field.to_string(),
+            state: None,
+        }
+    }
+}
+
+impl AggregationFn for AvgFn {
+    fn process_first(&mut self, scan: &dyn ReadScan) -> AnyhowResult<()> {
+        self.state = Some((get_int(scan, &self.field)?, 1));
+        Ok(())
+    }
+    fn process_next(&mut self, scan: &dyn ReadScan) -> AnyhowResult<()> {
+        let (sum, count) = self.state.ok_or_else(|| not_processed(&self.name))?;
+        self.state = Some((sum + get_int(scan, &self.field)?, count + 1));
+        Ok(())
+    }
+    fn field_name(&self) -> &str {
+        &self.name
+    }
+    fn value(&self) -> AnyhowResult<Constant> {
+        let (sum, count) = self.state.ok_or_else(|| not_processed(&self.name))?;
+        // int の値の平均は int の範囲に収まる
+        Ok(Constant::Int((sum / count) as i32))
+    }
+}
+
+#[cfg(test)]
+mod aggregation_fn_test {
+    use super::*;
+    use crate::query::values_scan::ValuesScan;
+
+    fn aggregate(aggregation_fn: &mut dyn AggregationFn, rows: Vec<Constant>) -> Constant {
+        let rows = rows.into_iter().map(|val| vec![val]).collect();
+        let mut scan = ValuesScan::new(vec!["a".to_string()], rows);
+        scan.before_first().unwrap();
+        assert!(scan.move_next().unwrap());
+        aggregation_fn.process_first(&scan).unwrap();
+        while scan.move_next().unwrap() {
+            aggregation_fn.process_next(&scan).unwrap();
+        }
+        aggregation_fn.value().unwrap()
+    }
+
+    #[test]
+    fn test_aggregation_fns() {
+        let ints = || vec![Constant::Int(3), Constant::Int(-4), Constant::Int(8)];
+        assert_eq!(
+            aggregate(&mut CountFn::new("c", "a"), ints()),
+            Constant::Int(3)
+        );
+        assert_eq!(
+            aggregate(&mut MaxFn::new("c", "a"), ints()),
+            Constant::Int(8)
+        );
+        assert_eq!(
+            aggregate(&mut MinFn::new("c", "a"), ints()),
+            Constant::Int(-4)
+        );
+        assert_eq!(
+            aggregate(&mut SumFn::new("c", "a"), ints()),
+            Constant::Int(7)
+        );
+        assert_eq!(
+            aggregate(&mut AvgFn::new("c", "a"), ints()),
+            Constant::Int(2)
+        );
+
+        let strings = || vec![Constant::from("joe"), Constant::from("amy")];
+        assert_eq!(
+            aggregate(&mut MaxFn::new("c", "a"), strings()),
+            Constant::from("joe")
+        );
+        assert_eq!(
+            aggregate(&mut MinFn::new("c", "a"), strings()),
+            Constant::from("amy")
+        );
+
+        // 途中の合計が int の範囲を超えても、最後の値が範囲内であれば求められる
+        let large = vec![
+            Constant::Int(i32::MAX),
+            Constant::Int(i32::MAX),
+            Constant::Int(-i32::MAX),
+        ];
+        assert_eq!(
+            aggregate(&mut SumFn::new("c", "a"), large.clone()),
+            Constant::Int(i32::MAX)
+        );
+        assert_eq!(
+            aggregate(&mut AvgFn::new("c", "a"), large),
+            Constant::Int(i32::MAX / 3)
+        );
+    }
+
+    #[test]
+    fn test_aggregation_errors() {
+        // process_first を呼ぶ前は値が無い
+        assert!(CountFn::new("c", "a").value().is_err());
+        assert!(MaxFn::new("c", "a").value().is_err());
+
+        let mut scan = ValuesScan::new(
+            vec!["a".to_string()],
+            vec![vec![Constant::Int(i32::MAX)], vec![Constant::Int(1)]],
+        );
+        scan.before_first().unwrap();
+        scan.move_next().unwrap();
+        let mut sum = SumFn::new("c", "a");
+        sum.process_first(&scan).unwrap();
+        scan.move_next().unwrap();
+        sum.process_next(&scan).unwrap();
+        assert!(sum.value().is_err());
+
+        // string の値は合計できない
+        let mut scan = ValuesScan::new(vec!["a".to_string()], vec![vec![Constant::from("x")]]);
+        scan.before_first().unwrap();
+        scan.move_next().unwrap();
+        assert!(SumFn::new("c", "a").process_first(&scan).is_err());
+    }
+}
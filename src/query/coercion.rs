@@ -0,0 +1,210 @@
+use std::cmp::Ordering;
+
+use crate::record::schema::FieldType;
+
+use super::constant::Constant;
+
+/*
+ * 型の異なる値を比較・代入する際の、暗黙の型変換の規則
+ *
+ * 数値型は int → bigint → double の順に表せる値の範囲が広くなり、狭い型の値は広い型に暗黙に変換できる
+ * 数値型以外の型の値は、同じ型の値としか比較・代入できない
+ *
+ * | 変換元 \ 変換先 | int | bigint | double | varchar | boolean | date |
+ * |-----------------|-----|--------|--------|---------|---------|------|
+ * | int             | ○   | ○      | ○      | ×       | ×       | ×    |
+ * | bigint          | ×   | ○      | ○      | ×       | ×       | ×    |
+ * | double          | ×   | ×      | ○      | ×       | ×       | ×    |
+ * | varchar         | ×   | ×      | ×      | ○       | ×       | ×    |
+ * | boolean         | ×   | ×      | ×      | ×       | ○       | ×    |
+ * | date            | ×   | ×      | ×      | ×       | ×       | ○    |
+ *
+ * 比較では、両辺をどちらの値も変換できる型 (共通の型) に変換してから比べる
+ * bigint から double への変換では、絶対値が 2^53 を超える値は最も近い double に丸められる
+ */
+
+/// 数値型の広さの順位。数値型でない場合は None を返す
+fn numeric_rank(field_type: FieldType) -> Option<u8> {
+    match field_type {
+        FieldType::Integer => Some(0),
+        FieldType::BigInt => Some(1),
+        FieldType::Double => Some(2),
+        FieldType::String | FieldType::Boolean | FieldType::Date => None,
+    }
+}
+
+/// from 型の値を to 型の field に代入 (insert / update) できるかどうか
+pub fn can_assign(from: FieldType, to: FieldType) -> bool {
+    if from == to {
+        return true;
+    }
+    match (numeric_rank(from), numeric_rank(to)) {
+        (Some(from), Some(to)) => from <= to,
+        _ => false,
+    }
+}
+
+/// lhs 型の値と rhs 型の値を比較する際に、両辺を変換する型を返す。比較できない場合は None を返す
+pub fn common_type(lhs: FieldType, rhs: FieldType) -> Option<FieldType> {
+    if can_assign(lhs, rhs) {
+        Some(rhs)
+    } else if can_assign(rhs, lhs) {
+        Some(lhs)
+    } else {
+        None
+    }
+}
+
+/// 値の型を返す
+pub fn type_of(val: &Constant) -> FieldType {
+    match val {
+        Constant::Int(_) => FieldType::Integer,
+        Constant::String(_) => FieldType::String,
+        Constant::Bool(_) => FieldType::Boolean,
+        Constant::BigInt(_) => FieldType::BigInt,
+        Constant::Date(_) => FieldType::Date,
+        Constant::Double(_) => FieldType::Double,
+    }
+}
+
+/// val を to 型の値に変換する。変換できない場合は None を返す
+pub fn coerce(val: &Constant, to: FieldType) -> Option<Constant> {
+    if !can_assign(type_of(val), to) {
+        return None;
+    }
+    match (val, to) {
+        (Constant::Int(val), FieldType::BigInt) => Some(Constant::BigInt(*val as i64)),
+        (Constant::Int(val), FieldType::Double) => Some(Constant::Double(*val as f64)),
+        (Constant::BigInt(val), FieldType::Double) => Some(Constant::Double(*val as f64)),
+        _ => Some(val.clone()),
+    }
+}
+
+/// 2 つの値を共通の型に変換した上で、等しいかどうかを返す。比較できない型の場合は false を返す
+pub fn equals(lhs: &Constant, rhs: &Constant) -> bool {
+    let Some(to) = common_type(type_of(lhs), type_of(rhs)) else {
+        return false;
+    };
+    match (coerce(lhs, to), coerce(rhs, to)) {
+        (Some(lhs), Some(rhs)) => lhs == rhs,
+        _ => false,
+    }
+}
+
+/// 2 つの値を共通の型に変換した上で、大小を比較する。比較できない型の場合は None を返す
+pub fn compare(lhs: &Constant, rhs: &Constant) -> Option<Ordering> {
+    let to = common_type(type_of(lhs), type_of(rhs))?;
+    coerce(lhs, to)?.partial_cmp(&coerce(rhs, to)?)
+}
+
+#[cfg(test)]
+mod coercion_test {
+    use super::*;
+    use crate::query::date::Date;
+
+    #[test]
+    fn test_coercion_matrix() {
+        let types = [
+            FieldType::Integer,
+            FieldType::String,
+            FieldType::Boolean,
+            FieldType::BigInt,
+            FieldType::Date,
+            FieldType::Double,
+        ];
+        let numeric = [FieldType::Integer, FieldType::BigInt, FieldType::Double];
+        for from in types {
+            for to in types {
+                // 数値型の間では狭い型から広い型にだけ変換でき、それ以外は同じ型の間でだけ変換できる
+                let rank = |t| numeric.iter().position(|n| *n == t);
+                let expected = from == to
+                    || matches!((rank(from), rank(to)), (Some(from), Some(to)) if from < to);
+                assert_eq!(can_assign(from, to), expected);
+            }
+        }
+        assert_eq!(
+            common_type(FieldType::Integer, FieldType::Integer),
+            Some(FieldType::Integer)
+        );
+        assert_eq!(
+            common_type(FieldType::Integer, FieldType::BigInt),
+            Some(FieldType::BigInt)
+        );
+        assert_eq!(
+            common_type(FieldType::BigInt, FieldType::Integer),
+            Some(FieldType::BigInt)
+        );
+        assert_eq!(
+            common_type(FieldType::Double, FieldType::BigInt),
+            Some(FieldType::Double)
+        );
+        assert_eq!(common_type(FieldType::Integer, FieldType::String), None);
+    }
+
+    #[test]
+    fn test_coerce_and_equals() {
+        assert_eq!(
+            coerce(&Constant::Int(1), FieldType::Integer),
+            Some(Constant::Int(1))
+        );
+        assert_eq!(coerce(&Constant::Int(1), FieldType::String), None);
+        assert!(equals(&Constant::Int(1), &Constant::Int(1)));
+        assert!(!equals(&Constant::Int(1), &Constant::Int(2)));
+        // 比較できない型の値は等しくない
+        assert!(!equals(
+            &Constant::Int(1),
+            &Constant::String("1".to_string())
+        ));
+        assert!(!equals(&Constant::Bool(true), &Constant::Int(1)));
+
+        // int は bigint に広げてから比べる
+        assert_eq!(
+            coerce(&Constant::Int(-1), FieldType::BigInt),
+            Some(Constant::BigInt(-1))
+        );
+        assert_eq!(coerce(&Constant::BigInt(1), FieldType::Integer), None);
+        assert!(equals(&Constant::Int(3), &Constant::BigInt(3)));
+        // int と bigint は double に広げてから比べる
+        assert_eq!(
+            coerce(&Constant::Int(2), FieldType::Double),
+            Some(Constant::Double(2.0))
+        );
+        assert_eq!(coerce(&Constant::Double(2.0), FieldType::Integer), None);
+        assert!(equals(&Constant::Double(3.0), &Constant::BigInt(3)));
+        assert!(!equals(&Constant::Double(3.5), &Constant::Int(3)));
+        assert!(!equals(
+            &Constant::Date(Date::from_days(3)),
+            &Constant::Int(3)
+        ));
+    }
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(
+            compare(&Constant::Int(1), &Constant::Int(2)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(compare(&"b".into(), &"a".into()), Some(Ordering::Greater));
+        assert_eq!(
+            compare(&Constant::Bool(false), &Constant::Bool(false)),
+            Some(Ordering::Equal)
+        );
+        assert_eq!(
+            compare(&Constant::BigInt(1 << 40), &Constant::Int(i32::MAX)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare(
+                &Constant::Date(Date::parse("2024-01-01").unwrap()),
+                &Constant::Date(Date::parse("2023-12-31").unwrap())
+            ),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            compare(&Constant::Int(3), &Constant::Double(2.5)),
+            Some(Ordering::Greater)
+        );
+        // 比較できない型の値は大小も比べられない
+        assert_eq!(compare(&Constant::Int(1), &"1".into()), None);
+    }
+}
@@ -1,9 +1,27 @@
-use std::fmt;
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+};
 
-#[derive(Debug, Clone, Eq, PartialEq, PartialOrd, Hash)]
+use super::date::Date;
+
+/**
+ * 値
+ *
+ * HashSet の key や group by の key に使えるように、Double を含めて Eq と Hash を実装する
+ * そのために Double どうしは f64 の == ではなく、0.0 と -0.0 を等しいものとした上で bit 列で比べる
+ * (NaN も自分自身と等しくなる)
+ * 異なる variant の値は variant の順に並べる。型の異なる値を比べる場合は coercion::compare を使う
+ */
+#[derive(Debug, Clone)]
 pub enum Constant {
     Int(i32),
     String(String),
+    Bool(bool),
+    BigInt(i64),
+    Date(Date),
+    Double(f64),
 }
 
 impl Constant {
@@ -20,6 +38,119 @@ impl Constant {
             _ => None,
         }
     }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Constant::Bool(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_bigint(&self) -> Option<i64> {
+        match self {
+            Constant::BigInt(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_date(&self) -> Option<Date> {
+        match self {
+            Constant::Date(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    pub fn as_double(&self) -> Option<f64> {
+        match self {
+            Constant::Double(val) => Some(*val),
+            _ => None,
+        }
+    }
+
+    /// 値を文字列 val に置き換える
+    /// 元の値が string の場合はその String の buffer を使い回すので、容量が足りていれば allocation が起きない
+    pub fn set_string(&mut self, val: &str) {
+        match self {
+            Constant::String(buf) => {
+                buf.clear();
+                buf.push_str(val);
+            }
+            _ => *self = Constant::String(val.to_string()),
+        }
+    }
+}
+
+impl Constant {
+    // 異なる variant の値を並べる順番
+    fn variant_index(&self) -> u8 {
+        match self {
+            Constant::Int(_) => 0,
+            Constant::String(_) => 1,
+            Constant::Bool(_) => 2,
+            Constant::BigInt(_) => 3,
+            Constant::Date(_) => 4,
+            Constant::Double(_) => 5,
+        }
+    }
+}
+
+// -0.0 を 0.0 にそろえた bit 列。Double の比較と hash に使う
+fn double_bits(val: f64) -> u64 {
+    if val == 0.0 {
+        0
+    } else {
+        val.to_bits()
+    }
+}
+
+fn compare_double(lhs: f64, rhs: f64) -> Ordering {
+    if double_bits(lhs) == double_bits(rhs) {
+        Ordering::Equal
+    } else {
+        lhs.total_cmp(&rhs)
+    }
+}
+
+impl PartialEq for Constant {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Constant {}
+
+impl PartialOrd for Constant {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Constant {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Constant::Int(lhs), Constant::Int(rhs)) => lhs.cmp(rhs),
+            (Constant::String(lhs), Constant::String(rhs)) => lhs.cmp(rhs),
+            (Constant::Bool(lhs), Constant::Bool(rhs)) => lhs.cmp(rhs),
+            (Constant::BigInt(lhs), Constant::BigInt(rhs)) => lhs.cmp(rhs),
+            (Constant::Date(lhs), Constant::Date(rhs)) => lhs.cmp(rhs),
+            (Constant::Double(lhs), Constant::Double(rhs)) => compare_double(*lhs, *rhs),
+            _ => self.variant_index().cmp(&other.variant_index()),
+        }
+    }
+}
+
+impl Hash for Constant {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.variant_index().hash(state);
+        match self {
+            Constant::Int(val) => val.hash(state),
+            Constant::String(val) => val.hash(state),
+            Constant::Bool(val) => val.hash(state),
+            Constant::BigInt(val) => val.hash(state),
+            Constant::Date(val) => val.hash(state),
+            Constant::Double(val) => double_bits(*val).hash(state),
+        }
+    }
 }
 
 impl fmt::Display for Constant {
@@ -27,6 +158,81 @@ impl fmt::Display for Constant {
         match self {
             Constant::Int(val) => write!(f, "{}", val),
             Constant::String(val) => write!(f, "'{}'", val),
+            Constant::Bool(val) => write!(f, "{}", val),
+            Constant::BigInt(val) => write!(f, "{}", val),
+            // parser が date literal として読み直せる形式で表示する
+            Constant::Date(val) => write!(f, "date '{}'", val),
+            // 整数の値も parser が double として読み直せるように、小数点を付けて表示する
+            Constant::Double(val) if val.is_finite() && val.fract() == 0.0 => {
+                write!(f, "{:.1}", val)
+            }
+            Constant::Double(val) => write!(f, "{}", val),
         }
     }
 }
+
+impl From<i32> for Constant {
+    fn from(val: i32) -> Self {
+        Constant::Int(val)
+    }
+}
+
+impl From<&str> for Constant {
+    fn from(val: &str) -> Self {
+        Constant::String(val.to_string())
+    }
+}
+
+impl From<String> for Constant {
+    fn from(val: String) -> Self {
+        Constant::String(val)
+    }
+}
+
+impl From<i64> for Constant {
+    fn from(val: i64) -> Self {
+        Constant::BigInt(val)
+    }
+}
+
+impl From<Date> for Constant {
+    fn from(val: Date) -> Self {
+        Constant::Date(val)
+    }
+}
+
+impl From<f64> for Constant {
+    fn from(val: f64) -> Self {
+        Constant::Double(val)
+    }
+}
+
+impl From<bool> for Constant {
+    fn from(val: bool) -> Self {
+        Constant::Bool(val)
+    }
+}
+
+#[cfg(test)]
+mod constant_test {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn test_double_eq_and_hash() {
+        assert_eq!(Constant::Double(0.0), Constant::Double(-0.0));
+        assert_eq!(Constant::Double(f64::NAN), Constant::Double(f64::NAN));
+        assert_ne!(Constant::Double(1.0), Constant::Int(1));
+        let set: HashSet<Constant> = [0.0, -0.0, 1.5, 1.5]
+            .into_iter()
+            .map(Constant::Double)
+            .collect();
+        assert_eq!(set.len(), 2);
+
+        assert!(Constant::Double(-1.5) < Constant::Double(0.0));
+        assert!(Constant::Double(2.0) < Constant::Double(f64::INFINITY));
+        assert_eq!(Constant::Double(3.0).to_string(), "3.0");
+        assert_eq!(Constant::Double(0.25).to_string(), "0.25");
+    }
+}
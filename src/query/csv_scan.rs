@@ -0,0 +1,235 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Lines},
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+use thiserror::Error;
+
+use crate::record::schema::{FieldInfo, Schema};
+
+use super::{
+    constant::Constant,
+    date::Date,
+    scan::{ReadScan, ReadScanError},
+};
+
+/**
+ * 外部の CSV / TSV ファイルを table として読み込むための read-only な scan
+ *
+ * ファイルの各行が一つの record に対応し、各列は schema の field の順番に対応する
+ * 拡張子が .tsv の場合は tab 区切り、それ以外の場合は comma 区切りとして扱う
+ * quote などの CSV の細かい仕様には対応していない
+ */
+pub struct CsvScan {
+    path: PathBuf,
+    schema: Schema,
+    delimiter: char,
+    lines: Option<Lines<BufReader<File>>>,
+    // 現在 cursor が指している record。schema の field の順番に並んでいる
+    current: Option<Vec<Constant>>,
+}
+
+#[derive(Error, Debug)]
+pub enum CsvScanError {
+    #[error("[csv scan] parse error at {path}: {message}")]
+    Parse { path: String, message: String },
+}
+
+impl CsvScan {
+    pub fn new(path: PathBuf, schema: Schema) -> AnyhowResult<Self> {
+        let delimiter = Self::delimiter_for(&path);
+        let mut scan = Self {
+            path,
+            schema,
+            delimiter,
+            lines: None,
+            current: None,
+        };
+        scan.before_first()?;
+        Ok(scan)
+    }
+
+    /// ファイルの拡張子から区切り文字を決める
+    pub fn delimiter_for(path: &std::path::Path) -> char {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("tsv") => '\t',
+            _ => ',',
+        }
+    }
+
+    /// 1 行を schema に従って parse する
+    pub fn parse_line(
+        line: &str,
+        schema: &Schema,
+        delimiter: char,
+    ) -> Result<Vec<Constant>, String> {
+        let columns: Vec<&str> = line.split(delimiter).collect();
+        if columns.len() != schema.len() {
+            return Err(format!(
+                "expected {} columns but found {} in line '{}'",
+                schema.len(),
+                columns.len(),
+                line
+            ));
+        }
+        schema
+            .infos()
+            .zip(columns)
+            .map(|((field, info), column)| match info {
+                FieldInfo::Integer => column
+                    .trim()
+                    .parse::<i32>()
+                    .map(Constant::Int)
+                    .map_err(|e| format!("invalid int value '{}' for {}: {}", column, field, e)),
+                FieldInfo::String(_) => Ok(Constant::String(column.to_string())),
+                FieldInfo::Boolean => {
+                    column
+                        .trim()
+                        .parse::<bool>()
+                        .map(Constant::Bool)
+                        .map_err(|e| {
+                            format!("invalid boolean value '{}' for {}: {}", column, field, e)
+                        })
+                }
+                FieldInfo::BigInt => column
+                    .trim()
+                    .parse::<i64>()
+                    .map(Constant::BigInt)
+                    .map_err(|e| format!("invalid bigint value '{}' for {}: {}", column, field, e)),
+                FieldInfo::Date => Date::parse(column.trim())
+                    .map(Constant::Date)
+                    .ok_or_else(|| format!("invalid date value '{}' for {}", column, field)),
+                // NaN は比較や index の key に使えないので、読み込まない
+                FieldInfo::Double => column
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|e| e.to_string())
+                    .and_then(|val| {
+                        if val.is_nan() {
+                            Err("NaN is not supported".to_string())
+                        } else {
+                            Ok(Constant::Double(val))
+                        }
+                    })
+                    .map_err(|e| format!("invalid double value '{}' for {}: {}", column, field, e)),
+            })
+            .collect()
+    }
+}
+
+impl ReadScan for CsvScan {
+    fn before_first(&mut self) -> AnyhowResult<()> {
+        let file = File::open(&self.path)?;
+        self.lines = Some(BufReader::new(file).lines());
+        self.current = None;
+        Ok(())
+    }
+
+    fn move_next(&mut self) -> AnyhowResult<bool> {
+        let lines = self
+            .lines
+            .as_mut()
+            .ok_or(anyhow!(ReadScanError::InvalidCall(
+                "before_first must be called before move_next".to_string()
+            )))?;
+        for line in lines.by_ref() {
+            let line = line?;
+            // 空行は読み飛ばす
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record =
+                Self::parse_line(&line, &self.schema, self.delimiter).map_err(|message| {
+                    CsvScanError::Parse {
+                        path: self.path.display().to_string(),
+                        message,
+                    }
+                })?;
+            self.current = Some(record);
+            return Ok(true);
+        }
+        self.current = None;
+        Ok(false)
+    }
+
+    fn get_val(&self, field_name: &str) -> AnyhowResult<Constant> {
+        let record = self
+            .current
+            .as_ref()
+            .ok_or(anyhow!(ReadScanError::InvalidCall(
+                "no record is specified for the csv scan. you need to call move_next first"
+                    .to_string()
+            )))?;
+        let index = self
+            .schema
+            .fields_iter()
+            .position(|field| field == field_name)
+            .ok_or(anyhow!(ReadScanError::InvalidCall(format!(
+                "field {} not found for the csv scan",
+                field_name
+            ))))?;
+        Ok(record[index].clone())
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.schema.has_field(field_name)
+    }
+}
+
+#[cfg(test)]
+mod csv_scan_test {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn setup_schema() -> Schema {
+        let mut schema = Schema::new();
+        schema.add_field("id", FieldInfo::Integer);
+        schema.add_field("name", FieldInfo::String(10));
+        schema
+    }
+
+    #[test]
+    fn test_read_csv() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("data.csv");
+        {
+            let mut file = File::create(&path).unwrap();
+            writeln!(file, "1,joe").unwrap();
+            writeln!(file).unwrap();
+            writeln!(file, "2,amy").unwrap();
+        }
+        let mut scan = CsvScan::new(path, setup_schema()).unwrap();
+        // 2 周しても同じ結果が得られる
+        for _ in 0..2 {
+            scan.before_first().unwrap();
+            assert!(scan.move_next().unwrap());
+            assert_eq!(scan.get_int("id").unwrap(), 1);
+            assert_eq!(scan.get_string("name").unwrap(), "joe");
+            assert!(scan.move_next().unwrap());
+            assert_eq!(scan.get_int("id").unwrap(), 2);
+            assert_eq!(scan.get_string("name").unwrap(), "amy");
+            assert!(!scan.move_next().unwrap());
+        }
+        assert!(scan.has_field("id"));
+        assert!(!scan.has_field("age"));
+    }
+
+    #[test]
+    fn test_read_tsv_with_invalid_line() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("data.tsv");
+        {
+            let mut file = File::create(&path).unwrap();
+            writeln!(file, "1\tjoe, jr.").unwrap();
+            writeln!(file, "x\tamy").unwrap();
+        }
+        let mut scan = CsvScan::new(path, setup_schema()).unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("name").unwrap(), "joe, jr.");
+        // int として parse できない行はエラーになる
+        assert!(scan.move_next().is_err());
+    }
+}
@@ -0,0 +1,129 @@
+use std::fmt;
+
+/**
+ * 日付 (date 型の値)
+ *
+ * 1970-01-01 からの日数で表し、disk には int と同じく 4 byte で保存する
+ * 文字列としては YYYY-MM-DD の形式で読み書きする
+ */
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Hash)]
+pub struct Date(i32);
+
+impl Date {
+    /// 1970-01-01 からの日数から作成する
+    pub fn from_days(days: i32) -> Self {
+        Date(days)
+    }
+
+    /// 年月日から作成する。存在しない日付の場合は None を返す
+    pub fn from_ymd(year: i32, month: u32, day: u32) -> Option<Self> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return None;
+        }
+        // 3 月始まりの年で数えると、閏日が年の最後に来るので計算が簡単になる
+        let year = if month <= 2 { year - 1 } else { year } as i64;
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month_from_march = (month as i64 + 9) % 12;
+        let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        i32::try_from(era * 146097 + day_of_era - 719468)
+            .ok()
+            .map(Date)
+    }
+
+    /// YYYY-MM-DD の形式の文字列を読む。形式が違う場合や存在しない日付の場合は None を返す
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut parts = s.splitn(3, '-');
+        let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if !all_digits(year) || !all_digits(month) || !all_digits(day) {
+            return None;
+        }
+        Self::from_ymd(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?)
+    }
+
+    /// 1970-01-01 からの日数
+    pub fn days(&self) -> i32 {
+        self.0
+    }
+
+    /// (年, 月, 日) を返す
+    pub fn ymd(&self) -> (i32, u32, u32) {
+        // from_ymd の逆の計算
+        let days = self.0 as i64 + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_from_march = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+        let month = if month_from_march < 10 {
+            month_from_march + 3
+        } else {
+            month_from_march - 9
+        } as u32;
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        (year as i32, month, day)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+#[cfg(test)]
+mod date_test {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!(Date::parse("1970-01-01"), Some(Date::from_days(0)));
+        assert_eq!(Date::parse("1970-01-02"), Some(Date::from_days(1)));
+        assert_eq!(Date::parse("1969-12-31"), Some(Date::from_days(-1)));
+        assert_eq!(Date::parse("2000-03-01"), Some(Date::from_days(11017)));
+        for s in [
+            "1970-01-01",
+            "2000-02-29",
+            "2024-12-31",
+            "1900-03-01",
+            "0001-01-01",
+        ] {
+            assert_eq!(Date::parse(s).unwrap().to_string(), s);
+        }
+        assert!(Date::parse("2024-01-01") < Date::parse("2024-01-02"));
+    }
+
+    #[test]
+    fn test_invalid_date() {
+        for s in [
+            "",
+            "2024",
+            "2024-1-01",
+            "2024-13-01",
+            "2024-00-10",
+            "2023-02-29",
+            "1900-02-29",
+            "2024-04-31",
+            "2024-01-1x",
+        ] {
+            assert_eq!(Date::parse(s), None, "{}", s);
+        }
+    }
+}
@@ -0,0 +1,42 @@
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use super::{
+    constant::Constant,
+    scan::{ReadScan, ReadScanError},
+};
+
+/**
+ * record を 1 つも返さない scan
+ *
+ * 結果が空になることが plan の時点でわかっている場合に使い、table などは一切読まない
+ */
+pub struct EmptyScan {
+    fields: Vec<String>,
+}
+
+impl ReadScan for EmptyScan {
+    fn before_first(&mut self) -> AnyhowResult<()> {
+        Ok(())
+    }
+
+    fn move_next(&mut self) -> AnyhowResult<bool> {
+        Ok(false)
+    }
+
+    fn get_val(&self, field_name: &str) -> AnyhowResult<Constant> {
+        Err(anyhow!(ReadScanError::InvalidCall(format!(
+            "empty scan has no record to read field {}",
+            field_name
+        ))))
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.fields.iter().any(|field| field == field_name)
+    }
+}
+
+impl EmptyScan {
+    pub fn new(fields: Vec<String>) -> Self {
+        Self { fields }
+    }
+}
@@ -1,13 +1,28 @@
 use crate::record::schema::Schema;
 
-use super::{constant::Constant, scan::ReadScan};
+use super::{
+    constant::Constant,
+    predicate::{Predicate, ProductPredicate},
+    scan::ReadScan,
+};
 
 use anyhow::Result as AnyhowResult;
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone)]
 pub enum Expression {
     Constant(Constant),
     Field(String),
+    Case(CaseExpression),
+    Predicate(ProductPredicate),
+}
+
+/**
+ * case when <predicate> then <expression> ... else <expression> end を表す式
+ */
+#[derive(Debug, Clone)]
+pub struct CaseExpression {
+    branches: Vec<(ProductPredicate, Expression)>,
+    otherwise: Box<Expression>,
 }
 
 /**
@@ -18,6 +33,10 @@ impl Expression {
         match self {
             Expression::Constant(constant) => Ok(constant.clone()),
             Expression::Field(field_name) => scan.get_val(field_name),
+            Expression::Case(case) => case.eval(scan),
+            Expression::Predicate(predicate) => {
+                Ok(Constant::Bool(predicate.is_satisfied_by(scan)?))
+            }
         }
     }
 
@@ -26,6 +45,33 @@ impl Expression {
         match self {
             Expression::Constant(_) => true,
             Expression::Field(field_name) => schema.has_field(field_name),
+            Expression::Case(case) => case.can_apply(schema),
+            Expression::Predicate(predicate) => predicate.can_apply(schema),
         }
     }
 }
+
+impl CaseExpression {
+    pub fn new(branches: Vec<(ProductPredicate, Expression)>, otherwise: Expression) -> Self {
+        Self {
+            branches,
+            otherwise: Box::new(otherwise),
+        }
+    }
+
+    /// 最初に満たされた when 句の式を評価する。どれも満たされない場合は else 句の式を評価する
+    fn eval(&self, scan: &dyn ReadScan) -> AnyhowResult<Constant> {
+        for (predicate, expression) in &self.branches {
+            if predicate.is_satisfied_by(scan)? {
+                return expression.eval(scan);
+            }
+        }
+        self.otherwise.eval(scan)
+    }
+
+    fn can_apply(&self, schema: &Schema) -> bool {
+        self.branches.iter().all(|(predicate, expression)| {
+            predicate.can_apply(schema) && expression.can_apply(schema)
+        }) && self.otherwise.can_apply(schema)
+    }
+}
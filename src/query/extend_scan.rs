@@ -0,0 +1,181 @@
+use anyhow::{anyhow, Result as AnyhowResult};
+use thiserror::Error;
+
+use crate::record::rid::Rid;
+
+use super::{
+    constant::Constant,
+    expression::Expression,
+    scan::{ReadScan, Scan, UpdateScan},
+};
+
+/**
+ * 元の scan の record に、式を評価した値を持つ field を追加する scan
+ * select a = 1 from t のように、select する値を式で指定した場合に使う
+ */
+pub struct ExtendScan {
+    scan: Scan,
+    // 追加する field の名前と、その値を計算する式
+    expressions: Vec<(String, Expression)>,
+}
+
+#[derive(Error, Debug)]
+pub enum ExtendScanError {
+    #[error("[extend scan] invalid call : {0}")]
+    InvalidCall(String),
+}
+
+impl ExtendScan {
+    pub fn new(scan: Scan, expressions: Vec<(String, Expression)>) -> Self {
+        Self { scan, expressions }
+    }
+
+    fn inner(&self) -> &dyn ReadScan {
+        match self.scan {
+            Scan::ReadOnly(ref scan) => scan.as_ref(),
+            Scan::Updatable(ref scan) => scan.as_ref(),
+        }
+    }
+
+    fn expression(&self, field_name: &str) -> Option<&Expression> {
+        self.expressions
+            .iter()
+            .find(|(name, _)| name == field_name)
+            .map(|(_, expression)| expression)
+    }
+}
+
+impl ReadScan for ExtendScan {
+    fn before_first(&mut self) -> AnyhowResult<()> {
+        match self.scan {
+            Scan::ReadOnly(ref mut scan) => scan.before_first(),
+            Scan::Updatable(ref mut scan) => scan.before_first(),
+        }
+    }
+
+    fn move_next(&mut self) -> AnyhowResult<bool> {
+        match self.scan {
+            Scan::ReadOnly(ref mut scan) => scan.move_next(),
+            Scan::Updatable(ref mut scan) => scan.move_next(),
+        }
+    }
+
+    fn get_val(&self, field_name: &str) -> AnyhowResult<Constant> {
+        match self.expression(field_name) {
+            Some(expression) => expression.eval(self.inner()),
+            None => self.inner().get_val(field_name),
+        }
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.expression(field_name).is_some() || self.inner().has_field(field_name)
+    }
+}
+
+impl UpdateScan for ExtendScan {
+    fn set_val(&self, field_name: &str, val: &Constant) -> AnyhowResult<()> {
+        if self.expression(field_name).is_some() {
+            return Err(anyhow!(ExtendScanError::InvalidCall(format!(
+                "field {} is computed from an expression and cannot be updated",
+                field_name
+            ))));
+        }
+        match self.scan {
+            Scan::ReadOnly(_) => Err(anyhow!(ExtendScanError::InvalidCall(
+                "set_val called on read-only scan".to_string()
+            ))),
+            Scan::Updatable(ref scan) => scan.set_val(field_name, val),
+        }
+    }
+
+    fn insert(&mut self) -> AnyhowResult<()> {
+        match self.scan {
+            Scan::ReadOnly(_) => Err(anyhow!(ExtendScanError::InvalidCall(
+                "insert called on read-only scan".to_string()
+            ))),
+            Scan::Updatable(ref mut scan) => scan.insert(),
+        }
+    }
+
+    fn delete(&mut self) -> AnyhowResult<()> {
+        match self.scan {
+            Scan::ReadOnly(_) => Err(anyhow!(ExtendScanError::InvalidCall(
+                "delete called on read-only scan".to_string()
+            ))),
+            Scan::Updatable(ref mut scan) => scan.delete(),
+        }
+    }
+
+    fn move_to_rid(&mut self, rid: &Rid) -> AnyhowResult<()> {
+        match self.scan {
+            Scan::ReadOnly(_) => Err(anyhow!(ExtendScanError::InvalidCall(
+                "move_to_rid called on read-only scan".to_string()
+            ))),
+            Scan::Updatable(ref mut scan) => scan.move_to_rid(rid),
+        }
+    }
+
+    fn get_rid(&self) -> AnyhowResult<Rid> {
+        match self.scan {
+            Scan::ReadOnly(_) => Err(anyhow!(ExtendScanError::InvalidCall(
+                "get_rid called on read-only scan".to_string()
+            ))),
+            Scan::Updatable(ref scan) => scan.get_rid(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod extend_scan_test {
+    use mockall::predicate::eq;
+
+    use super::*;
+    use crate::query::{
+        predicate::ProductPredicate,
+        scan::{MockReadScan, MockUpdateScan},
+        term::EqualTerm,
+    };
+
+    #[test]
+    fn test_get_val_evaluates_expression() {
+        let scan = {
+            let mut scan = MockReadScan::new();
+            scan.expect_has_field().with(eq("a")).returning(|_| true);
+            scan.expect_get_val()
+                .with(eq("a"))
+                .returning(|_| Ok(Constant::Int(1)));
+            // field と定数を比べる条件は、値を取り出さずに compare_val で比べる
+            scan.expect_compare_val()
+                .with(eq("a"), eq(Constant::Int(1)))
+                .returning(|_, _| Ok(Some(std::cmp::Ordering::Equal)));
+            scan
+        };
+        let predicate = ProductPredicate::new(vec![Box::new(EqualTerm::new(
+            Expression::Field("a".to_string()),
+            Expression::Constant(Constant::Int(1)),
+        ))]);
+        let extend_scan = ExtendScan::new(
+            Scan::ReadOnly(Box::new(scan)),
+            vec![("is_one".to_string(), Expression::Predicate(predicate))],
+        );
+        assert!(extend_scan.has_field("is_one"));
+        assert!(extend_scan.has_field("a"));
+        assert_eq!(extend_scan.get_val("is_one").unwrap(), Constant::Bool(true));
+        assert_eq!(extend_scan.get_val("a").unwrap(), Constant::Int(1));
+    }
+
+    #[test]
+    fn test_set_val_fails_for_computed_field() {
+        let scan = {
+            let mut scan = MockUpdateScan::new();
+            scan.expect_set_val().times(1).returning(|_, _| Ok(()));
+            scan
+        };
+        let extend_scan = ExtendScan::new(
+            Scan::Updatable(Box::new(scan)),
+            vec![("t".to_string(), Expression::Constant(Constant::Bool(true)))],
+        );
+        assert!(extend_scan.set_val("t", &Constant::Bool(false)).is_err());
+        assert!(extend_scan.set_val("a", &Constant::Int(2)).is_ok());
+    }
+}
@@ -0,0 +1,180 @@
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use super::{
+    aggregation_fn::AggregationFn,
+    constant::Constant,
+    scan::{ReadScan, ReadScanError},
+};
+
+/**
+ * group_fields の値が等しい record をまとめて 1 つの record にし、group ごとに集約関数の値を求める scan
+ *
+ * scan は group_fields の順に並んでいなければならない。同じ値の record が続く間を 1 つの group とみなす
+ * 出力する record は group_fields の値と、各集約関数の値を持つ
+ * group_fields が空の場合は、全ての record を 1 つの group にまとめる (record が無い場合は何も出力しない)
+ */
+pub struct GroupByScan {
+    scan: Box<dyn ReadScan>,
+    group_fields: Vec<String>,
+    aggregation_fns: Vec<Box<dyn AggregationFn>>,
+    // 今いる group の group_fields の値。move_next を呼ぶまでは None
+    group_val: Option<Vec<Constant>>,
+    // scan に、まだ group にまとめていない record が残っているかどうか
+    more_groups: bool,
+}
+
+impl ReadScan for GroupByScan {
+    fn before_first(&mut self) -> AnyhowResult<()> {
+        self.scan.before_first()?;
+        self.group_val = None;
+        self.more_groups = self.scan.move_next()?;
+        Ok(())
+    }
+
+    fn move_next(&mut self) -> AnyhowResult<bool> {
+        if !self.more_groups {
+            self.group_val = None;
+            return Ok(false);
+        }
+        for aggregation_fn in self.aggregation_fns.iter_mut() {
+            aggregation_fn.process_first(self.scan.as_ref())?;
+        }
+        let group_val = self.current_group_val()?;
+        loop {
+            self.more_groups = self.scan.move_next()?;
+            if !self.more_groups || self.current_group_val()? != group_val {
+                break;
+            }
+            for aggregation_fn in self.aggregation_fns.iter_mut() {
+                aggregation_fn.process_next(self.scan.as_ref())?;
+            }
+        }
+        self.group_val = Some(group_val);
+        Ok(true)
+    }
+
+    fn get_val(&self, field_name: &str) -> AnyhowResult<Constant> {
+        let group_val = self.group_val.as_ref().ok_or_else(|| {
+            anyhow!(ReadScanError::InvalidCall(
+                "no group is specified. you need to call move_next first".to_string()
+            ))
+        })?;
+        if let Some(index) = self.group_fields.iter().position(|f| f == field_name) {
+            return Ok(group_val[index].clone());
+        }
+        match self
+            .aggregation_fns
+            .iter()
+            .find(|aggregation_fn| aggregation_fn.field_name() == field_name)
+        {
+            Some(aggregation_fn) => aggregation_fn.value(),
+            None => Err(anyhow!(ReadScanError::InvalidCall(format!(
+                "field {} not found",
+                field_name
+            )))),
+        }
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.group_fields.iter().any(|field| field == field_name)
+            || self
+                .aggregation_fns
+                .iter()
+                .any(|aggregation_fn| aggregation_fn.field_name() == field_name)
+    }
+}
+
+impl GroupByScan {
+    /// scan は group_fields の順に並んでいる必要がある
+    pub fn new(
+        scan: Box<dyn ReadScan>,
+        group_fields: Vec<String>,
+        aggregation_fns: Vec<Box<dyn AggregationFn>>,
+    ) -> AnyhowResult<Self> {
+        let mut scan = Self {
+            scan,
+            group_fields,
+            aggregation_fns,
+            group_val: None,
+            more_groups: false,
+        };
+        scan.before_first()?;
+        Ok(scan)
+    }
+
+    // scan の今の record の group_fields の値
+    fn current_group_val(&self) -> AnyhowResult<Vec<Constant>> {
+        self.group_fields
+            .iter()
+            .map(|field| self.scan.get_val(field))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod group_by_scan_test {
+    use super::*;
+    use crate::query::{
+        aggregation_fn::{CountFn, MaxFn},
+        values_scan::ValuesScan,
+    };
+
+    fn values_scan(rows: Vec<(i32, i32)>) -> Box<dyn ReadScan> {
+        Box::new(ValuesScan::new(
+            vec!["a".to_string(), "b".to_string()],
+            rows.into_iter()
+                .map(|(a, b)| vec![Constant::Int(a), Constant::Int(b)])
+                .collect(),
+        ))
+    }
+
+    fn aggregation_fns() -> Vec<Box<dyn AggregationFn>> {
+        vec![
+            Box::new(CountFn::new("count(b)", "b")),
+            Box::new(MaxFn::new("max(b)", "b")),
+        ]
+    }
+
+    fn collect(scan: &mut GroupByScan, fields: &[&str]) -> Vec<Vec<i32>> {
+        let mut rows = vec![];
+        while scan.move_next().unwrap() {
+            rows.push(
+                fields
+                    .iter()
+                    .map(|field| scan.get_int(field).unwrap())
+                    .collect(),
+            );
+        }
+        rows
+    }
+
+    #[test]
+    fn test_group_by() {
+        let rows = vec![(1, 5), (1, 7), (2, 3), (3, 4), (3, 9), (3, 1)];
+        let mut scan =
+            GroupByScan::new(values_scan(rows), vec!["a".to_string()], aggregation_fns()).unwrap();
+        assert!(scan.has_field("a") && scan.has_field("max(b)") && !scan.has_field("b"));
+        let fields = ["a", "count(b)", "max(b)"];
+        let expected = vec![vec![1, 2, 7], vec![2, 1, 3], vec![3, 3, 9]];
+        assert_eq!(collect(&mut scan, &fields), expected);
+
+        // 先頭から読み直せる
+        scan.before_first().unwrap();
+        assert_eq!(collect(&mut scan, &fields), expected);
+        assert!(scan.get_val("a").is_err());
+    }
+
+    #[test]
+    fn test_group_by_without_group_fields() {
+        let rows = vec![(1, 5), (2, 7), (1, 3)];
+        let mut scan = GroupByScan::new(values_scan(rows), vec![], aggregation_fns()).unwrap();
+        assert_eq!(
+            collect(&mut scan, &["count(b)", "max(b)"]),
+            vec![vec![3, 7]]
+        );
+
+        // record が無い場合は group も無い
+        let mut scan = GroupByScan::new(values_scan(vec![]), vec![], aggregation_fns()).unwrap();
+        assert!(!scan.move_next().unwrap());
+    }
+}
@@ -0,0 +1,170 @@
+use anyhow::Result as AnyhowResult;
+
+use crate::{index::common::Index, record::schema::FieldType};
+
+use super::{
+    coercion::coerce,
+    constant::Constant,
+    scan::{ReadScan, UpdateScan},
+};
+
+/**
+ * lhs の各 record について、join_field の値を index で検索し、一致する rhs の table の record と組み合わせる scan
+ *
+ * rhs の table を lhs の record ごとに全て読み直す代わりに、index が返した Rid の record だけを読む
+ */
+pub struct IndexJoinScan {
+    lhs: Box<dyn ReadScan>,
+    index: Box<dyn Index>,
+    join_field: String,
+    // index を張った field の型。lhs の値はこの型に変換してから index を検索する
+    key_type: FieldType,
+    rhs: Box<dyn UpdateScan>,
+    // lhs の現在の record の値で index を検索中かどうか
+    // lhs を読み終えた場合や、値を index の型に変換できない場合は false
+    is_probing: bool,
+}
+
+impl ReadScan for IndexJoinScan {
+    fn before_first(&mut self) -> AnyhowResult<()> {
+        self.lhs.before_first()?;
+        self.is_probing = false;
+        if self.lhs.move_next()? {
+            self.reset_index()?;
+        }
+        Ok(())
+    }
+
+    fn move_next(&mut self) -> AnyhowResult<bool> {
+        loop {
+            if self.is_probing && self.index.next()? {
+                let rid = self.index.get_data_rid()?;
+                self.rhs.move_to_rid(&rid)?;
+                return Ok(true);
+            }
+            if !self.lhs.move_next()? {
+                self.is_probing = false;
+                return Ok(false);
+            }
+            self.reset_index()?;
+        }
+    }
+
+    fn get_val(&self, field_name: &str) -> AnyhowResult<Constant> {
+        if self.rhs.has_field(field_name) {
+            self.rhs.get_val(field_name)
+        } else {
+            self.lhs.get_val(field_name)
+        }
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.lhs.has_field(field_name) || self.rhs.has_field(field_name)
+    }
+
+    fn block_accesses(&self) -> u64 {
+        self.rhs.block_accesses()
+    }
+}
+
+impl IndexJoinScan {
+    /// rhs は index を張った table の scan で、key_type はその index を張った field の型
+    pub fn new(
+        lhs: Box<dyn ReadScan>,
+        index: Box<dyn Index>,
+        join_field: &str,
+        key_type: FieldType,
+        rhs: Box<dyn UpdateScan>,
+    ) -> AnyhowResult<Self> {
+        let mut scan = Self {
+            lhs,
+            index,
+            join_field: join_field.to_string(),
+            key_type,
+            rhs,
+            is_probing: false,
+        };
+        scan.before_first()?;
+        Ok(scan)
+    }
+
+    // lhs の現在の record の値で index の検索を始める
+    fn reset_index(&mut self) -> AnyhowResult<()> {
+        let val = self.lhs.get_val(&self.join_field)?;
+        // 型が異なる値は index を張った field のどの値とも等しくならない
+        self.is_probing = match coerce(&val, self.key_type) {
+            Some(key) => {
+                self.index.before_first(&key)?;
+                true
+            }
+            None => false,
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod index_join_scan_test {
+    use mockall::predicate::eq;
+
+    use crate::{
+        index::common::MockIndex,
+        query::scan::{MockReadScan, MockUpdateScan},
+        record::rid::Rid,
+    };
+
+    use super::*;
+
+    #[test]
+    fn move_next_test() {
+        // lhs は a = 1, 2, 3 の 3 つの record を持つ
+        let lhs = {
+            let mut scan = MockReadScan::new();
+            scan.expect_before_first().times(1).returning(|| Ok(()));
+            let mut count = 0;
+            scan.expect_move_next().times(4).returning(move || {
+                count += 1;
+                Ok(count <= 3)
+            });
+            let mut val = 0;
+            scan.expect_get_val().with(eq("a")).returning(move |_| {
+                val += 1;
+                Ok(Constant::Int(val))
+            });
+            scan.expect_has_field().returning(|field| field == "a");
+            Box::new(scan)
+        };
+        // index では a = 1 と a = 3 の record が 1 つずつ見つかり、a = 2 の record は見つからない
+        let index = {
+            let mut index = MockIndex::new();
+            index.expect_before_first().times(3).returning(|_| Ok(()));
+            let mut results = vec![true, false, false, true, false].into_iter();
+            index
+                .expect_next()
+                .times(5)
+                .returning(move || Ok(results.next().unwrap()));
+            let mut block = 0;
+            index.expect_get_data_rid().times(2).returning(move || {
+                block += 1;
+                Ok(Rid::new(block, Some(0)))
+            });
+            Box::new(index)
+        };
+        let rhs = {
+            let mut scan = MockUpdateScan::new();
+            scan.expect_move_to_rid().times(2).returning(|_| Ok(()));
+            scan.expect_has_field().returning(|field| field == "b");
+            scan.expect_get_val()
+                .with(eq("b"))
+                .returning(|_| Ok(Constant::from("rhs")));
+            Box::new(scan)
+        };
+
+        let mut scan = IndexJoinScan::new(lhs, index, "a", FieldType::Integer, rhs).unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_val("b").unwrap(), Constant::from("rhs"));
+        assert!(scan.move_next().unwrap());
+        assert!(!scan.move_next().unwrap());
+        assert!(scan.has_field("a") && scan.has_field("b"));
+    }
+}
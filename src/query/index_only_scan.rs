@@ -0,0 +1,118 @@
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use crate::index::common::{Index, IndexSearch};
+
+use super::{
+    constant::Constant,
+    scan::{ReadScan, ReadScanError},
+};
+
+/**
+ * index の record だけを読んで、field の値が search の条件を満たす record の値を返す scan
+ *
+ * index を張った field と、index に含めた (include で指定した) field の値を index の record から読むので、table の block を読まない
+ * それ以外の field の値は読めない
+ */
+pub struct IndexOnlyScan {
+    index: Box<dyn Index>,
+    search: IndexSearch,
+    key_field: String,
+    included_fields: Vec<String>,
+}
+
+impl ReadScan for IndexOnlyScan {
+    fn before_first(&mut self) -> AnyhowResult<()> {
+        self.search.before_first(self.index.as_mut())?;
+        Ok(())
+    }
+
+    fn move_next(&mut self) -> AnyhowResult<bool> {
+        Ok(self.index.next()?)
+    }
+
+    fn get_val(&self, field_name: &str) -> AnyhowResult<Constant> {
+        if field_name == self.key_field {
+            return Ok(self.index.get_data_val()?);
+        }
+        let pos = self
+            .included_fields
+            .iter()
+            .position(|field| field == field_name)
+            .ok_or_else(|| {
+                anyhow!(ReadScanError::InvalidCall(format!(
+                    "field {} is not included in the index",
+                    field_name
+                )))
+            })?;
+        Ok(self.index.get_included_val(pos)?)
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        field_name == self.key_field || self.included_fields.iter().any(|field| field == field_name)
+    }
+}
+
+impl IndexOnlyScan {
+    /// key_field は index を張った field、included_fields は index に含めた field を index の record の順に並べたもの
+    pub fn new(
+        index: Box<dyn Index>,
+        search: IndexSearch,
+        key_field: String,
+        included_fields: Vec<String>,
+    ) -> AnyhowResult<Self> {
+        let mut scan = Self {
+            index,
+            search,
+            key_field,
+            included_fields,
+        };
+        scan.before_first()?;
+        Ok(scan)
+    }
+}
+
+#[cfg(test)]
+mod index_only_scan_test {
+    use crate::index::common::MockIndex;
+
+    use super::*;
+
+    #[test]
+    fn get_val_test() {
+        let mut index = MockIndex::new();
+        index
+            .expect_before_first()
+            .withf(|val| *val == Constant::Int(10))
+            .times(1)
+            .returning(|_| Ok(()));
+        let mut count = 0;
+        index.expect_next().times(2).returning(move || {
+            count += 1;
+            Ok(count <= 1)
+        });
+        index
+            .expect_get_data_val()
+            .returning(|| Ok(Constant::Int(10)));
+        index
+            .expect_get_included_val()
+            .withf(|pos| *pos == 1)
+            .returning(|_| Ok(Constant::from("bob")));
+        // table scan を使わずに、index の record から値を読む
+        index.expect_get_data_rid().never();
+
+        let mut scan = IndexOnlyScan::new(
+            Box::new(index),
+            IndexSearch::Equal(Constant::Int(10)),
+            "id".to_string(),
+            vec!["age".to_string(), "name".to_string()],
+        )
+        .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_val("id").unwrap(), Constant::Int(10));
+        assert_eq!(scan.get_val("name").unwrap(), Constant::from("bob"));
+        assert!(scan.has_field("age"));
+        assert!(!scan.has_field("dept"));
+        assert!(scan.get_val("dept").is_err());
+        assert!(!scan.move_next().unwrap());
+    }
+}
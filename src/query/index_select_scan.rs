@@ -0,0 +1,179 @@
+use std::cmp::Ordering;
+
+use anyhow::Result as AnyhowResult;
+
+use crate::index::common::{Index, IndexSearch};
+
+use super::{
+    constant::Constant,
+    row::Row,
+    scan::{ReadScan, UpdateScan},
+};
+
+/**
+ * index を使って、field の値が search の条件 (val と等しい、または範囲に入る) を満たす record だけを読む scan
+ *
+ * table を先頭から読む代わりに index から条件を満たす record の Rid を探し、table scan をその record に移動させる
+ */
+pub struct IndexSelectScan {
+    table_scan: Box<dyn UpdateScan>,
+    index: Box<dyn Index>,
+    search: IndexSearch,
+}
+
+impl ReadScan for IndexSelectScan {
+    fn before_first(&mut self) -> AnyhowResult<()> {
+        self.search.before_first(self.index.as_mut())?;
+        Ok(())
+    }
+
+    fn move_next(&mut self) -> AnyhowResult<bool> {
+        if !self.index.next()? {
+            return Ok(false);
+        }
+        let rid = self.index.get_data_rid()?;
+        self.table_scan.move_to_rid(&rid)?;
+        Ok(true)
+    }
+
+    fn get_val(&self, field_name: &str) -> AnyhowResult<Constant> {
+        self.table_scan.get_val(field_name)
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.table_scan.has_field(field_name)
+    }
+
+    fn compare_val(&self, field_name: &str, val: &Constant) -> AnyhowResult<Option<Ordering>> {
+        self.table_scan.compare_val(field_name, val)
+    }
+
+    fn read_into(&self, row: &mut Row) -> AnyhowResult<()> {
+        self.table_scan.read_into(row)
+    }
+
+    fn block_accesses(&self) -> u64 {
+        self.table_scan.block_accesses()
+    }
+}
+
+impl IndexSelectScan {
+    /// table_scan は index を張った table の scan
+    pub fn new(
+        table_scan: Box<dyn UpdateScan>,
+        index: Box<dyn Index>,
+        search: IndexSearch,
+    ) -> AnyhowResult<Self> {
+        let mut scan = Self {
+            table_scan,
+            index,
+            search,
+        };
+        scan.before_first()?;
+        Ok(scan)
+    }
+}
+
+#[cfg(test)]
+mod index_select_scan_test {
+    use std::ops::Bound;
+
+    use crate::{index::common::MockIndex, query::scan::MockUpdateScan, record::rid::Rid};
+
+    use super::*;
+
+    #[test]
+    fn move_next_test() {
+        // index は 2 つの record を返す
+        let index = {
+            let mut index = MockIndex::new();
+            index
+                .expect_before_first()
+                .withf(|val| *val == Constant::Int(10))
+                .times(1)
+                .returning(|_| Ok(()));
+            let mut count = 0;
+            index.expect_next().times(3).returning(move || {
+                count += 1;
+                Ok(count <= 2)
+            });
+            let mut block = 0;
+            index.expect_get_data_rid().times(2).returning(move || {
+                block += 1;
+                Ok(Rid::new(block, Some(0)))
+            });
+            Box::new(index)
+        };
+        // table scan は index が返した record に順に移動する
+        let table_scan = {
+            let mut table_scan = MockUpdateScan::new();
+            table_scan
+                .expect_move_to_rid()
+                .withf(|rid| *rid == Rid::new(1, Some(0)))
+                .times(1)
+                .returning(|_| Ok(()));
+            table_scan
+                .expect_move_to_rid()
+                .withf(|rid| *rid == Rid::new(2, Some(0)))
+                .times(1)
+                .returning(|_| Ok(()));
+            table_scan
+                .expect_get_val()
+                .times(2)
+                .returning(|_| Ok(Constant::Int(10)));
+            Box::new(table_scan)
+        };
+
+        let mut scan =
+            IndexSelectScan::new(table_scan, index, IndexSearch::Equal(Constant::Int(10))).unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_val("a").unwrap(), Constant::Int(10));
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_val("a").unwrap(), Constant::Int(10));
+        assert!(!scan.move_next().unwrap());
+    }
+
+    #[test]
+    fn range_search_test() {
+        // 範囲で検索する場合は before_first_range で index を開く
+        let index = {
+            let mut index = MockIndex::new();
+            index
+                .expect_before_first_range()
+                .withf(|lo, hi| {
+                    *lo == Bound::Excluded(Constant::Int(10))
+                        && *hi == Bound::Included(Constant::Int(20))
+                })
+                .times(1)
+                .returning(|_, _| Ok(()));
+            index.expect_before_first().times(0);
+            let mut count = 0;
+            index.expect_next().times(2).returning(move || {
+                count += 1;
+                Ok(count <= 1)
+            });
+            index
+                .expect_get_data_rid()
+                .times(1)
+                .returning(|| Ok(Rid::new(3, Some(1))));
+            Box::new(index)
+        };
+        let table_scan = {
+            let mut table_scan = MockUpdateScan::new();
+            table_scan
+                .expect_move_to_rid()
+                .withf(|rid| *rid == Rid::new(3, Some(1)))
+                .times(1)
+                .returning(|_| Ok(()));
+            Box::new(table_scan)
+        };
+
+        let search = IndexSearch::Range(
+            Bound::Excluded(Constant::Int(10)),
+            Bound::Included(Constant::Int(20)),
+        );
+        let mut scan = IndexSelectScan::new(table_scan, index, search).unwrap();
+        assert!(scan.move_next().unwrap());
+        assert!(!scan.move_next().unwrap());
+    }
+}
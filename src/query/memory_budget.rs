@@ -0,0 +1,101 @@
+use std::{cell::Cell, mem::size_of, rc::Rc};
+
+use thiserror::Error;
+
+use super::constant::Constant;
+
+#[derive(Error, Debug)]
+pub enum MemoryBudgetError {
+    #[error(
+        "memory limit exceeded: {requested} bytes requested, {used} of {limit} bytes already used"
+    )]
+    LimitExceeded {
+        requested: usize,
+        used: usize,
+        limit: usize,
+    },
+}
+
+/**
+ * 1 つの query の中で、メモリ上に値を保持する operator (統計情報の収集など) が使うメモリの量を管理する
+ *
+ * clone したものは同じ使用量を共有するので、同じ query の中の複数の operator に渡して合計の使用量を制限できる
+ * limit が None の場合は制限しない
+ */
+#[derive(Debug, Clone)]
+pub struct MemoryBudget {
+    limit: Option<usize>,
+    used: Rc<Cell<usize>>,
+}
+
+impl MemoryBudget {
+    pub fn new(limit: Option<usize>) -> Self {
+        Self {
+            limit,
+            used: Rc::new(Cell::new(0)),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(None)
+    }
+
+    /// bytes だけメモリを使うことを記録する。limit を超える場合は何も記録せずに error を返す
+    pub fn reserve(&self, bytes: usize) -> Result<(), MemoryBudgetError> {
+        let used = self.used.get();
+        let new_used = used.saturating_add(bytes);
+        if let Some(limit) = self.limit {
+            if new_used > limit {
+                return Err(MemoryBudgetError::LimitExceeded {
+                    requested: bytes,
+                    used,
+                    limit,
+                });
+            }
+        }
+        self.used.set(new_used);
+        Ok(())
+    }
+
+    /// reserve したメモリを解放したことを記録する
+    pub fn release(&self, bytes: usize) {
+        self.used.set(self.used.get().saturating_sub(bytes));
+    }
+
+    /// constant をメモリ上に保持する時に使うおおよその byte 数
+    pub fn constant_size(constant: &Constant) -> usize {
+        match constant {
+            Constant::Int(_)
+            | Constant::Bool(_)
+            | Constant::BigInt(_)
+            | Constant::Date(_)
+            | Constant::Double(_) => size_of::<Constant>(),
+            Constant::String(s) => size_of::<Constant>() + s.capacity(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod memory_budget_test {
+    use super::*;
+
+    #[test]
+    fn test_reserve_and_release() {
+        let budget = MemoryBudget::new(Some(100));
+        let shared = budget.clone();
+        assert!(budget.reserve(60).is_ok());
+        // clone したものとは使用量を共有する
+        let err = shared.reserve(50).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "memory limit exceeded: 50 bytes requested, 60 of 100 bytes already used"
+        );
+        // 失敗した reserve は記録されない
+        assert!(shared.reserve(40).is_ok());
+        shared.release(100);
+        assert!(budget.reserve(100).is_ok());
+
+        let unlimited = MemoryBudget::unlimited();
+        assert!(unlimited.reserve(usize::MAX).is_ok());
+    }
+}
@@ -0,0 +1,101 @@
+use std::cmp::Ordering;
+
+use anyhow::Result as AnyhowResult;
+
+use super::{
+    coercion,
+    constant::Constant,
+    scan::ReadScan,
+    sort_scan::{SortPosition, SortScan},
+};
+
+/**
+ * field1 の順に並んだ s1 と field2 の順に並んだ s2 を先頭から同時に読み、field1 と field2 の値が等しい record どうしを組み合わせる scan
+ *
+ * s1 で同じ値の record が続く場合は、s2 の同じ値の record を先頭から読み直す
+ * そのために s2 は位置を保存して戻せる SortScan でなければならない
+ */
+pub struct MergeJoinScan {
+    s1: Box<dyn ReadScan>,
+    s2: SortScan,
+    field1: String,
+    field2: String,
+    // 直前に組み合わせた値と、s2 でその値を持つ最初の record の位置
+    join: Option<(Constant, SortPosition)>,
+}
+
+impl ReadScan for MergeJoinScan {
+    fn before_first(&mut self) -> AnyhowResult<()> {
+        self.s1.before_first()?;
+        self.s2.before_first()?;
+        self.join = None;
+        Ok(())
+    }
+
+    fn move_next(&mut self) -> AnyhowResult<bool> {
+        // s2 の次の record も同じ値なら、s1 の現在の record と組み合わせる
+        let mut has_more2 = self.s2.move_next()?;
+        if has_more2 && self.equals_join_val(&self.s2.get_val(&self.field2)?) {
+            return Ok(true);
+        }
+        // s1 の次の record も同じ値なら、s2 の同じ値の record を最初から組み合わせ直す
+        let mut has_more1 = self.s1.move_next()?;
+        if has_more1 && self.equals_join_val(&self.s1.get_val(&self.field1)?) {
+            if let Some((_, position)) = &self.join {
+                self.s2.restore_position(position)?;
+            }
+            return Ok(true);
+        }
+        while has_more1 && has_more2 {
+            let val1 = self.s1.get_val(&self.field1)?;
+            let val2 = self.s2.get_val(&self.field2)?;
+            match coercion::compare(&val1, &val2) {
+                Some(Ordering::Equal) => {
+                    self.join = Some((val2, self.s2.save_position()?));
+                    return Ok(true);
+                }
+                Some(Ordering::Greater) => has_more2 = self.s2.move_next()?,
+                // 比較できない値はどの値とも組み合わせないので読み飛ばす
+                Some(Ordering::Less) | None => has_more1 = self.s1.move_next()?,
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_val(&self, field_name: &str) -> AnyhowResult<Constant> {
+        if self.s1.has_field(field_name) {
+            self.s1.get_val(field_name)
+        } else {
+            self.s2.get_val(field_name)
+        }
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.s1.has_field(field_name) || self.s2.has_field(field_name)
+    }
+}
+
+impl MergeJoinScan {
+    pub fn new(
+        s1: Box<dyn ReadScan>,
+        s2: SortScan,
+        field1: &str,
+        field2: &str,
+    ) -> AnyhowResult<Self> {
+        let mut scan = Self {
+            s1,
+            s2,
+            field1: field1.to_string(),
+            field2: field2.to_string(),
+            join: None,
+        };
+        scan.before_first()?;
+        Ok(scan)
+    }
+
+    fn equals_join_val(&self, val: &Constant) -> bool {
+        self.join
+            .as_ref()
+            .is_some_and(|(join_val, _)| coercion::compare(join_val, val) == Some(Ordering::Equal))
+    }
+}
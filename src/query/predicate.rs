@@ -1,14 +1,18 @@
 use crate::record::schema::Schema;
 
-use super::{scan::Scan, term::Term};
+use super::{
+    scan::{ReadScan, Scan},
+    term::Term,
+};
 
 use anyhow::Result as AnyhowResult;
+#[cfg(test)]
 use mockall::automock;
 
 /**
  * Select の where 句で用いられる条件を表す (A=B AND C<B など)
  */
-#[automock]
+#[cfg_attr(test, automock)]
 pub trait Predicate {
     /// この predicate が満たされるかどうかを判定する
     fn is_satisfied(&self, scan: &Scan) -> AnyhowResult<bool>;
@@ -45,6 +49,17 @@ impl ProductPredicate {
         Self { terms }
     }
 
+    /// scan の現在の record でこの predicate が満たされるかどうかを判定する
+    pub fn is_satisfied_by(&self, scan: &dyn ReadScan) -> AnyhowResult<bool> {
+        for term in &self.terms {
+            if !term.is_satisfied_by(scan)? {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
     /// schema に適用可能な term のみを残した predicate を返す
     pub fn select_sub_pred(&self, schema: &Schema) -> Self {
         let terms = self
@@ -7,6 +7,7 @@ use crate::record::rid::Rid;
 
 use super::{
     constant::Constant,
+    row::Row,
     scan::{ReadScan, ReadScanError, Scan, UpdateScan},
 };
 
@@ -57,6 +58,23 @@ impl ReadScan for ProjectScan {
         // ここでは field_list に含まれているかどうかを返すだけで良い
         self.field_list.contains(field_name)
     }
+
+    fn read_into(&self, row: &mut Row) -> AnyhowResult<()> {
+        if let Some(field_name) = row
+            .fields()
+            .iter()
+            .find(|field| !self.field_list.contains(*field))
+        {
+            return Err(anyhow!(ReadScanError::InvalidCall(format!(
+                "field {} not found for the project scan. It expects one of {:?}",
+                field_name, self.field_list
+            ))));
+        }
+        match self.scan {
+            Scan::ReadOnly(ref scan) => scan.read_into(row),
+            Scan::Updatable(ref scan) => scan.read_into(row),
+        }
+    }
 }
 
 impl UpdateScan for ProjectScan {
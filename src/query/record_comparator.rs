@@ -0,0 +1,54 @@
+use std::{cmp::Ordering, fmt};
+
+use anyhow::Result as AnyhowResult;
+
+use super::{coercion, scan::ReadScan};
+
+/// record を並べ替える向き
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+impl fmt::Display for SortDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortDirection::Asc => write!(f, "asc"),
+            SortDirection::Desc => write!(f, "desc"),
+        }
+    }
+}
+
+/**
+ * 2 つの scan が指している record を、指定した field の値で比較する
+ *
+ * fields の先頭の field から順に比べ、値が等しい場合は次の field で比べる
+ * 降順を指定した field は、値の大小を逆にして比べる
+ * 型が異なるなどで比較できない値は等しいとみなす
+ */
+#[derive(Clone, Debug)]
+pub struct RecordComparator {
+    keys: Vec<(String, SortDirection)>,
+}
+
+impl RecordComparator {
+    pub fn new(keys: Vec<(String, SortDirection)>) -> Self {
+        Self { keys }
+    }
+
+    pub fn compare(&self, s1: &dyn ReadScan, s2: &dyn ReadScan) -> AnyhowResult<Ordering> {
+        for (field, direction) in &self.keys {
+            let ordering = coercion::compare(&s1.get_val(field)?, &s2.get_val(field)?)
+                .unwrap_or(Ordering::Equal);
+            let ordering = match direction {
+                SortDirection::Asc => ordering,
+                SortDirection::Desc => ordering.reverse(),
+            };
+            if ordering != Ordering::Equal {
+                return Ok(ordering);
+            }
+        }
+        Ok(Ordering::Equal)
+    }
+}
@@ -0,0 +1,96 @@
+use anyhow::Result as AnyhowResult;
+
+use super::constant::Constant;
+
+/**
+ * scan から 1 record 分の値を読み込むための buffer
+ *
+ * ReadScan::read_into に渡すと、fields の値が fields と同じ順に values に入る
+ * 同じ Row を使い回して record を読み進めると、前の record の値の String を再利用するので、
+ * 統計情報の集計のように全ての record を読む処理で、record ごと field ごとの allocation を避けられる
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    fields: Vec<String>,
+    // 最後に読み込んだ値。まだ一度も読み込んでいない場合は空
+    values: Vec<Constant>,
+}
+
+impl Row {
+    pub fn new(fields: Vec<String>) -> Self {
+        Self {
+            fields,
+            values: vec![],
+        }
+    }
+
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    /// 最後に読み込んだ値を fields と同じ順に返す。まだ読み込んでいない場合は空
+    pub fn values(&self) -> &[Constant] {
+        &self.values
+    }
+
+    /// 最後に読み込んだ、field_name の値
+    pub fn get(&self, field_name: &str) -> Option<&Constant> {
+        self.fields
+            .iter()
+            .position(|field| field == field_name)
+            .and_then(|i| self.values.get(i))
+    }
+
+    /// 各 field について、前回読み込んだ値を read で書き換える
+    /// 初めて読み込む場合は仮の値が渡されるので、read は必ず値を書き込まなければならない
+    /// read が error を返した場合、values にはそれまでに読み込んだ値と前回の値が混ざって残る
+    pub fn fill(
+        &mut self,
+        mut read: impl FnMut(&str, &mut Constant) -> AnyhowResult<()>,
+    ) -> AnyhowResult<()> {
+        if self.values.len() != self.fields.len() {
+            self.values = vec![Constant::Int(0); self.fields.len()];
+        }
+        for (field, value) in self.fields.iter().zip(self.values.iter_mut()) {
+            read(field, value)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod row_test {
+    use super::*;
+
+    #[test]
+    fn test_fill() {
+        let mut row = Row::new(vec!["a".to_string(), "b".to_string()]);
+        assert!(row.values().is_empty());
+        assert_eq!(row.get("a"), None);
+
+        row.fill(|field, value| {
+            match field {
+                "a" => *value = Constant::Int(1),
+                _ => value.set_string("first"),
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(row.values(), &[Constant::Int(1), Constant::from("first")]);
+        let ptr = row.get("b").unwrap().as_string().unwrap().as_ptr();
+
+        // 同じ長さ以下の文字列であれば、前の String の buffer に書き込む
+        row.fill(|field, value| {
+            match field {
+                "a" => *value = Constant::Int(2),
+                _ => value.set_string("next"),
+            }
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(row.get("a"), Some(&Constant::Int(2)));
+        assert_eq!(row.get("b"), Some(&Constant::from("next")));
+        assert_eq!(row.get("b").unwrap().as_string().unwrap().as_ptr(), ptr);
+        assert_eq!(row.get("c"), None);
+    }
+}
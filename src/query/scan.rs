@@ -1,9 +1,11 @@
 use crate::record::rid::Rid;
 
-use super::constant::Constant;
+use super::{coercion, constant::Constant, row::Row};
 
 use anyhow::Result as AnyhowResult;
+#[cfg(test)]
 use mockall::{automock, mock};
+use std::cmp::Ordering;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -14,7 +16,7 @@ pub enum ReadScanError {
     InvalidCall(String),
 }
 
-#[automock]
+#[cfg_attr(test, automock)]
 pub trait ReadScan {
     /// table scan の cursor を先頭に移動する
     fn before_first(&mut self) -> AnyhowResult<()>;
@@ -44,7 +46,40 @@ pub trait ReadScan {
         }?)
     }
 
+    fn get_bool(&self, field_name: &str) -> AnyhowResult<bool> {
+        Ok(match self.get_val(field_name)? {
+            Constant::Bool(val) => Ok(val),
+            _ => Err(ReadScanError::InvalidCall(format!(
+                "field type mismatch: {}. expected boolean",
+                field_name
+            ))),
+        }?)
+    }
+
     fn has_field(&self, field_name: &str) -> bool;
+
+    /// 今いる slot の field の値を、coercion の規則に従って val と比べる。比較できない型の場合は None を返す
+    /// table scan は string の値を String として取り出さずに page の上で比べるので、条件の評価で allocation が起きない
+    /// 他の scan の値を返すだけの scan は、その scan の compare_val をそのまま呼ぶ
+    fn compare_val(&self, field_name: &str, val: &Constant) -> AnyhowResult<Option<Ordering>> {
+        Ok(coercion::compare(&self.get_val(field_name)?, val))
+    }
+
+    /// 今いる slot の、row の全ての field の値を row に読み込む
+    /// table scan は string の値を row が持つ String に直接書き込むので、同じ row を使い回せば record ごとの allocation が起きない
+    /// 他の scan の値を返すだけの scan は、その scan の read_into をそのまま呼ぶ
+    fn read_into(&self, row: &mut Row) -> AnyhowResult<()> {
+        row.fill(|field_name, value| {
+            *value = self.get_val(field_name)?;
+            Ok(())
+        })
+    }
+
+    /// この scan が自分で block を読んだ回数を返す
+    /// 他の scan を通して record を読む scan は、その scan が読んだ分を含めずに 0 を返す
+    fn block_accesses(&self) -> u64 {
+        0
+    }
 }
 
 #[derive(Error, Debug)]
@@ -79,6 +114,7 @@ pub enum Scan {
     Updatable(Box<dyn UpdateScan>),
 }
 
+#[cfg(test)]
 mock! {
     pub UpdateScan {}
     impl ReadScan for UpdateScan {
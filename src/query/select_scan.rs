@@ -1,3 +1,5 @@
+use std::cmp::Ordering;
+
 use anyhow::{anyhow, Result as AnyhowResult};
 use thiserror::Error;
 
@@ -6,6 +8,7 @@ use crate::record::rid::Rid;
 use super::{
     constant::Constant,
     predicate::Predicate,
+    row::Row,
     scan::{ReadScan, Scan, UpdateScan},
 };
 
@@ -56,6 +59,20 @@ impl ReadScan for SelectScan {
             Scan::Updatable(ref scan) => scan.has_field(field_name),
         }
     }
+
+    fn compare_val(&self, field_name: &str, val: &Constant) -> AnyhowResult<Option<Ordering>> {
+        match self.scan {
+            Scan::ReadOnly(ref scan) => scan.compare_val(field_name, val),
+            Scan::Updatable(ref scan) => scan.compare_val(field_name, val),
+        }
+    }
+
+    fn read_into(&self, row: &mut Row) -> AnyhowResult<()> {
+        match self.scan {
+            Scan::ReadOnly(ref scan) => scan.read_into(row),
+            Scan::Updatable(ref scan) => scan.read_into(row),
+        }
+    }
 }
 
 impl UpdateScan for SelectScan {
@@ -0,0 +1,165 @@
+use std::cmp::Ordering;
+
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use crate::record::rid::Rid;
+
+use super::{
+    constant::Constant,
+    record_comparator::RecordComparator,
+    scan::{ReadScan, ReadScanError, UpdateScan},
+};
+
+/**
+ * 並び順に従って整列済みの run (temp table) を最大 2 つ受け取り、それらを merge しながら読む scan
+ *
+ * SortPlan が run の数を 2 つ以下になるまで merge してから作成する
+ * merge join で同じ値の record を読み直せるように、現在の位置を保存して戻す機能を持つ
+ */
+pub struct SortScan {
+    s1: Box<dyn UpdateScan>,
+    s2: Option<Box<dyn UpdateScan>>,
+    comparator: RecordComparator,
+    // 現在の record を指している run。move_next を呼ぶ前や、読み終えた後は None
+    current: Option<Run>,
+    has_more1: bool,
+    has_more2: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Run {
+    First,
+    Second,
+}
+
+/// save_position で保存した SortScan の位置
+/// 各 run の現在の record の Rid (読み終えた run は None) と、現在の record を指している run を持つ
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SortPosition {
+    rids: Vec<Option<Rid>>,
+    current: Option<Run>,
+}
+
+impl ReadScan for SortScan {
+    fn before_first(&mut self) -> AnyhowResult<()> {
+        self.current = None;
+        self.s1.before_first()?;
+        self.has_more1 = self.s1.move_next()?;
+        if let Some(s2) = self.s2.as_mut() {
+            s2.before_first()?;
+            self.has_more2 = s2.move_next()?;
+        }
+        Ok(())
+    }
+
+    fn move_next(&mut self) -> AnyhowResult<bool> {
+        // 直前に返した record の run だけを進める
+        match self.current {
+            Some(Run::First) => self.has_more1 = self.s1.move_next()?,
+            Some(Run::Second) => {
+                if let Some(s2) = self.s2.as_mut() {
+                    self.has_more2 = s2.move_next()?;
+                }
+            }
+            None => {}
+        }
+        self.current = match (self.has_more1, self.has_more2, self.s2.as_deref()) {
+            (false, false, _) => None,
+            (true, false, _) | (true, true, None) => Some(Run::First),
+            (false, true, _) => Some(Run::Second),
+            (true, true, Some(s2)) => {
+                if self.comparator.compare(self.s1.as_ref(), s2)? == Ordering::Greater {
+                    Some(Run::Second)
+                } else {
+                    Some(Run::First)
+                }
+            }
+        };
+        Ok(self.current.is_some())
+    }
+
+    fn get_val(&self, field_name: &str) -> AnyhowResult<Constant> {
+        self.current_scan().get_val(field_name)
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.s1.has_field(field_name)
+    }
+
+    fn block_accesses(&self) -> u64 {
+        self.s1.block_accesses() + self.s2.as_ref().map_or(0, |s2| s2.block_accesses())
+    }
+}
+
+impl SortScan {
+    /// runs は 1 つか 2 つの、comparator の順に整列済みの run の scan
+    pub fn new(runs: Vec<Box<dyn UpdateScan>>, comparator: RecordComparator) -> AnyhowResult<Self> {
+        if runs.is_empty() || runs.len() > 2 {
+            return Err(anyhow!(ReadScanError::InvalidCall(format!(
+                "sort scan takes one or two runs, but {} runs are given",
+                runs.len()
+            ))));
+        }
+        let mut runs = runs.into_iter();
+        let (s1, s2) = (runs.next().unwrap(), runs.next());
+        let mut scan = Self {
+            s1,
+            s2,
+            comparator,
+            current: None,
+            has_more1: false,
+            has_more2: false,
+        };
+        scan.before_first()?;
+        Ok(scan)
+    }
+
+    /// 現在の位置を返す。restore_position で、この位置から読み直せる
+    pub fn save_position(&self) -> AnyhowResult<SortPosition> {
+        let mut rids = vec![self.position(self.s1.as_ref(), self.has_more1)?];
+        if let Some(s2) = self.s2.as_deref() {
+            rids.push(self.position(s2, self.has_more2)?);
+        }
+        Ok(SortPosition {
+            rids,
+            current: self.current,
+        })
+    }
+
+    /// save_position で保存した位置に戻る。戻った後は、保存した時に指していた record を指している
+    pub fn restore_position(&mut self, position: &SortPosition) -> AnyhowResult<()> {
+        self.has_more1 = Self::move_to(self.s1.as_mut(), position.rids.first().copied().flatten())?;
+        if let Some(s2) = self.s2.as_mut() {
+            self.has_more2 = Self::move_to(s2.as_mut(), position.rids.get(1).copied().flatten())?;
+        }
+        self.current = position.current;
+        Ok(())
+    }
+
+    fn current_scan(&self) -> &dyn UpdateScan {
+        match (self.current, self.s2.as_deref()) {
+            (Some(Run::Second), Some(s2)) => s2,
+            _ => self.s1.as_ref(),
+        }
+    }
+
+    // run を読み終えている場合は None
+    fn position(&self, scan: &dyn UpdateScan, has_more: bool) -> AnyhowResult<Option<Rid>> {
+        Ok(if has_more {
+            Some(scan.get_rid()?)
+        } else {
+            None
+        })
+    }
+
+    // 位置が None の run は読み終えた状態に戻す。読み終えた run は二度と進めないので、scan は動かさなくてよい
+    fn move_to(scan: &mut dyn UpdateScan, position: Option<Rid>) -> AnyhowResult<bool> {
+        match position {
+            Some(rid) => {
+                scan.move_to_rid(&rid)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
@@ -1,8 +1,12 @@
 use crate::record::schema::Schema;
 
-use std::fmt;
+use std::{cmp::Ordering, fmt};
 
-use super::{constant::Constant, expression::Expression, scan::Scan};
+use super::{
+    coercion,
+    expression::Expression,
+    scan::{ReadScan, Scan},
+};
 
 use anyhow::Result as AnyhowResult;
 use dyn_clone::DynClone;
@@ -12,7 +16,14 @@ use dyn_clone::DynClone;
  */
 pub trait Term: fmt::Debug + DynClone {
     /// この term が満たされるかどうかを判定する
-    fn is_satisfied(&self, scan: &Scan) -> AnyhowResult<bool>;
+    fn is_satisfied(&self, scan: &Scan) -> AnyhowResult<bool> {
+        match scan {
+            Scan::ReadOnly(ref scan) => self.is_satisfied_by(scan.as_ref()),
+            Scan::Updatable(ref scan) => self.is_satisfied_by(scan.as_ref()),
+        }
+    }
+    /// scan の現在の record でこの term が満たされるかどうかを判定する
+    fn is_satisfied_by(&self, scan: &dyn ReadScan) -> AnyhowResult<bool>;
     /// この term が schema に適用可能かどうかを判定する
     fn can_apply(&self, schema: &Schema) -> bool;
 }
@@ -27,11 +38,8 @@ pub struct EqualTerm {
 }
 
 impl Term for EqualTerm {
-    fn is_satisfied(&self, scan: &Scan) -> AnyhowResult<bool> {
-        let lhs_val = eval_expr(&self.lhs, scan)?;
-        let rhs_val = eval_expr(&self.rhs, scan)?;
-
-        Ok(lhs_val == rhs_val)
+    fn is_satisfied_by(&self, scan: &dyn ReadScan) -> AnyhowResult<bool> {
+        Ok(compare_expressions(&self.lhs, &self.rhs, scan)? == Some(Ordering::Equal))
     }
 
     fn can_apply(&self, schema: &Schema) -> bool {
@@ -45,9 +53,139 @@ impl EqualTerm {
     }
 }
 
-fn eval_expr(expr: &Expression, scan: &Scan) -> AnyhowResult<Constant> {
-    match scan {
-        Scan::ReadOnly(ref scan) => expr.eval(scan.as_ref()),
-        Scan::Updatable(ref scan) => expr.eval(scan.as_ref()),
+/**
+ * 等号以外の比較演算子
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparisonOperator {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    NotEqual,
+}
+
+impl ComparisonOperator {
+    /// lhs と rhs を比較した結果が ordering のとき、lhs <op> rhs が満たされるかどうか
+    pub fn matches(&self, ordering: Ordering) -> bool {
+        match self {
+            ComparisonOperator::Less => ordering == Ordering::Less,
+            ComparisonOperator::LessEqual => ordering != Ordering::Greater,
+            ComparisonOperator::Greater => ordering == Ordering::Greater,
+            ComparisonOperator::GreaterEqual => ordering != Ordering::Less,
+            ComparisonOperator::NotEqual => ordering != Ordering::Equal,
+        }
+    }
+    /// 左右を入れ替えても同じ条件になる演算子を返す (a < b と b > a など)
+    pub fn swapped(&self) -> Self {
+        match self {
+            ComparisonOperator::Less => ComparisonOperator::Greater,
+            ComparisonOperator::LessEqual => ComparisonOperator::GreaterEqual,
+            ComparisonOperator::Greater => ComparisonOperator::Less,
+            ComparisonOperator::GreaterEqual => ComparisonOperator::LessEqual,
+            ComparisonOperator::NotEqual => ComparisonOperator::NotEqual,
+        }
+    }
+}
+
+impl fmt::Display for ComparisonOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            ComparisonOperator::Less => "<",
+            ComparisonOperator::LessEqual => "<=",
+            ComparisonOperator::Greater => ">",
+            ComparisonOperator::GreaterEqual => ">=",
+            ComparisonOperator::NotEqual => "<>",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+/**
+ * A < B のような、等号以外の比較演算子で比べる条件を表す term
+ * 比較できない型の値どうしの場合は満たされない
+ */
+#[derive(Debug, Clone)]
+pub struct ComparisonTerm {
+    lhs: Expression,
+    op: ComparisonOperator,
+    rhs: Expression,
+}
+
+impl Term for ComparisonTerm {
+    fn is_satisfied_by(&self, scan: &dyn ReadScan) -> AnyhowResult<bool> {
+        Ok(compare_expressions(&self.lhs, &self.rhs, scan)?
+            .is_some_and(|ordering| self.op.matches(ordering)))
+    }
+
+    fn can_apply(&self, schema: &Schema) -> bool {
+        self.lhs.can_apply(schema) && self.rhs.can_apply(schema)
+    }
+}
+
+impl ComparisonTerm {
+    pub fn new(lhs: Expression, op: ComparisonOperator, rhs: Expression) -> Self {
+        Self { lhs, op, rhs }
+    }
+}
+
+/// scan の現在の record で lhs と rhs を評価し、大小を比べる。比較できない型の場合は None を返す
+/// field と定数を比べる場合は、field の値を取り出さずに scan の compare_val で比べる
+fn compare_expressions(
+    lhs: &Expression,
+    rhs: &Expression,
+    scan: &dyn ReadScan,
+) -> AnyhowResult<Option<Ordering>> {
+    match (lhs, rhs) {
+        (Expression::Field(field_name), Expression::Constant(val)) => {
+            scan.compare_val(field_name, val)
+        }
+        (Expression::Constant(val), Expression::Field(field_name)) => {
+            Ok(scan.compare_val(field_name, val)?.map(Ordering::reverse))
+        }
+        _ => Ok(coercion::compare(&lhs.eval(scan)?, &rhs.eval(scan)?)),
+    }
+}
+
+#[cfg(test)]
+mod term_test {
+    use super::*;
+    use crate::query::{constant::Constant, values_scan::ValuesScan};
+
+    #[test]
+    fn test_comparison_term() {
+        let mut scan = ValuesScan::new(
+            vec!["a".to_string(), "b".to_string()],
+            vec![vec![Constant::Int(1), Constant::from("x")]],
+        );
+        scan.move_next().unwrap();
+        let field = |name: &str| Expression::Field(name.to_string());
+        let int = |val: i32| Expression::Constant(Constant::Int(val));
+        let satisfied = |lhs: Expression, op: ComparisonOperator, rhs: Expression| {
+            ComparisonTerm::new(lhs, op, rhs)
+                .is_satisfied_by(&scan)
+                .unwrap()
+        };
+        assert!(satisfied(field("a"), ComparisonOperator::Less, int(2)));
+        assert!(!satisfied(field("a"), ComparisonOperator::Less, int(1)));
+        assert!(satisfied(field("a"), ComparisonOperator::LessEqual, int(1)));
+        assert!(satisfied(int(2), ComparisonOperator::Greater, field("a")));
+        assert!(!satisfied(
+            field("a"),
+            ComparisonOperator::GreaterEqual,
+            int(2)
+        ));
+        assert!(satisfied(field("a"), ComparisonOperator::NotEqual, int(2)));
+        assert!(satisfied(
+            field("b"),
+            ComparisonOperator::Less,
+            Expression::Constant(Constant::from("y"))
+        ));
+        // 比較できない型の値どうしは、<> でも満たされない
+        assert!(!satisfied(
+            field("a"),
+            ComparisonOperator::NotEqual,
+            field("b")
+        ));
     }
 }
@@ -0,0 +1,96 @@
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use super::{
+    constant::Constant,
+    scan::{ReadScan, ReadScanError},
+};
+
+/**
+ * メモリ上に用意した record の列を先頭から順に返す scan
+ *
+ * show tables, describe のように、結果を catalog から組み立てて返す文で使う
+ */
+pub struct ValuesScan {
+    fields: Vec<String>,
+    rows: Vec<Vec<Constant>>,
+    // 今いる record の位置。before_first の直後は None
+    current: Option<usize>,
+}
+
+impl ReadScan for ValuesScan {
+    fn before_first(&mut self) -> AnyhowResult<()> {
+        self.current = None;
+        Ok(())
+    }
+
+    fn move_next(&mut self) -> AnyhowResult<bool> {
+        let next = self.current.map_or(0, |current| current + 1);
+        self.current = Some(next.min(self.rows.len()));
+        Ok(next < self.rows.len())
+    }
+
+    fn get_val(&self, field_name: &str) -> AnyhowResult<Constant> {
+        let index = self
+            .fields
+            .iter()
+            .position(|field| field == field_name)
+            .ok_or_else(|| {
+                anyhow!(ReadScanError::InvalidCall(format!(
+                    "field {} not found",
+                    field_name
+                )))
+            })?;
+        let row = self
+            .current
+            .and_then(|current| self.rows.get(current))
+            .ok_or_else(|| {
+                anyhow!(ReadScanError::InvalidCall(
+                    "no record is specified. you need to call move_next first".to_string()
+                ))
+            })?;
+        Ok(row[index].clone())
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.fields.iter().any(|field| field == field_name)
+    }
+}
+
+impl ValuesScan {
+    /// rows の各 record は fields と同じ順に値を持つ
+    pub fn new(fields: Vec<String>, rows: Vec<Vec<Constant>>) -> Self {
+        Self {
+            fields,
+            rows,
+            current: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod values_scan_test {
+    use super::*;
+
+    #[test]
+    fn test_values_scan() {
+        let mut scan = ValuesScan::new(
+            vec!["a".to_string(), "b".to_string()],
+            vec![
+                vec![Constant::Int(1), Constant::String("one".to_string())],
+                vec![Constant::Int(2), Constant::String("two".to_string())],
+            ],
+        );
+        assert!(scan.get_int("a").is_err());
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_int("a").unwrap(), 1);
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("b").unwrap(), "two");
+        assert!(scan.get_val("c").is_err());
+        assert!(!scan.move_next().unwrap());
+        assert!(!scan.move_next().unwrap());
+
+        scan.before_first().unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("b").unwrap(), "one");
+    }
+}
@@ -1,4 +1,7 @@
+pub mod dump;
 pub mod layout;
+pub mod partition;
+pub mod partitioned_scan;
 pub mod record_page;
 pub mod rid;
 pub mod schema;
@@ -0,0 +1,148 @@
+use std::{cell::RefCell, fmt::Write, rc::Rc};
+
+use crate::{file::blockid::BlockId, query::date::Date, tx::transaction::Transaction};
+
+use super::{
+    layout::Layout,
+    record_page::{RecordPage, RecordPageError, RecordPageFlag},
+    schema::FieldInfo,
+};
+
+/**
+ * block の中身を layout に従って解釈し、slot ごとの flag と field の値を人が読める形で返す
+ *
+ * 壊れた block や layout の変更を調べるための関数なので、flag が不正な slot も field の値を読んで表示する
+ * 値を読めなかった field は、読めなかった理由を値の代わりに表示する
+ */
+pub fn dump_block(
+    tx: &Rc<RefCell<Transaction>>,
+    block: &BlockId,
+    layout: &Layout,
+) -> Result<String, RecordPageError> {
+    let record_page = RecordPage::new(tx.clone(), block, layout);
+    let slot_count = record_page.slot_count()?;
+    let mut dump = format!(
+        "{} slot size: {}, slots: {}\n",
+        block,
+        layout.slot_size(),
+        slot_count
+    );
+    for slot in 0..slot_count {
+        let raw_flag = record_page.raw_flag(slot)?;
+        let flag = match RecordPageFlag::from_i32(raw_flag) {
+            Some(RecordPageFlag::Empty) => {
+                let _ = writeln!(dump, "  slot {} @{}: empty", slot, layout.slot_offset(slot));
+                continue;
+            }
+            Some(RecordPageFlag::Used) => "used".to_string(),
+            None => format!("invalid flag {}", raw_flag),
+        };
+        let fields = layout
+            .schema()
+            .infos()
+            .map(|(field, info)| dump_field(&record_page, slot, field, info))
+            .collect::<Result<Vec<_>, _>>()?;
+        let _ = writeln!(
+            dump,
+            "  slot {} @{}: {} {{ {} }}",
+            slot,
+            layout.slot_offset(slot),
+            flag,
+            fields.join(", ")
+        );
+    }
+    Ok(dump)
+}
+
+/// field 名, block の中での位置, 値を "name@offset=value" の形で返す
+fn dump_field(
+    record_page: &RecordPage,
+    slot: usize,
+    field: &str,
+    info: FieldInfo,
+) -> Result<String, RecordPageError> {
+    let offset = record_page.field_offset(slot, field)?;
+    let value = match info {
+        FieldInfo::Integer => record_page.get_int(slot, field).map(|val| val.to_string()),
+        FieldInfo::Boolean => record_page
+            .get_int(slot, field)
+            .map(|val| (val != 0).to_string()),
+        FieldInfo::String(_) => record_page
+            .get_string(slot, field)
+            .map(|val| format!("'{}'", val)),
+        FieldInfo::BigInt => record_page
+            .get_bigint(slot, field)
+            .map(|val| val.to_string()),
+        FieldInfo::Date => record_page
+            .get_int(slot, field)
+            .map(|val| Date::from_days(val).to_string()),
+        FieldInfo::Double => record_page
+            .get_double(slot, field)
+            .map(|val| val.to_string()),
+    };
+    let value = value.unwrap_or_else(|err| format!("<error: {}>", err));
+    Ok(format!("{}@{}={}", field, offset, value))
+}
+
+#[cfg(test)]
+mod dump_test {
+    use super::*;
+    use crate::{
+        buffer::buffer_manager::BufferManager,
+        file::file_manager::FileManager,
+        log::log_manager::LogManager,
+        record::schema::Schema,
+        tx::{concurrency::lock_table::LockTable, transaction::TransactionFactory},
+    };
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_dump_block() {
+        let dir = tempdir().unwrap();
+        let file_manager = Arc::new(FileManager::new(dir.path(), 100));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager.clone(),
+            log_manager.clone(),
+            8,
+            Some(10),
+        ));
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        let factory =
+            TransactionFactory::new(file_manager, log_manager, buffer_manager, lock_table);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+
+        let mut schema = Schema::new();
+        schema.add_field("A", FieldInfo::Integer);
+        schema.add_field("B", FieldInfo::String(3));
+        let layout = Layout::new(schema).unwrap();
+        let block = tx.borrow().append("testtbl.tbl").unwrap();
+        {
+            let mut record_page = RecordPage::new(tx.clone(), &block, &layout);
+            record_page.format().unwrap();
+            let slot = record_page.insert_after(None).unwrap().unwrap();
+            record_page.set_int(slot, "A", 7).unwrap();
+            record_page.set_string(slot, "B", "abc").unwrap();
+            // 2 つ目の slot には不正な flag を書き込む
+            tx.borrow()
+                .set_int(&block, layout.slot_size(), 9, false)
+                .unwrap();
+        }
+
+        let dump = dump_block(&tx, &block, &layout).unwrap();
+        let lines = dump.lines().collect::<Vec<_>>();
+        // slot size は flag (4) + A (4) + B (4 + 3 * 4) = 24 なので、100 byte の block には 4 slot 入る
+        assert_eq!(
+            lines,
+            vec![
+                "[file testtbl.tbl, block 0] slot size: 24, slots: 4",
+                "  slot 0 @0: used { A@4=7, B@8='abc' }",
+                "  slot 1 @24: invalid flag 9 { A@28=0, B@32='' }",
+                "  slot 2 @48: empty",
+                "  slot 3 @72: empty",
+            ]
+        );
+        tx.borrow_mut().commit().unwrap();
+    }
+}
@@ -1,13 +1,58 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
 use thiserror::Error;
 
 use crate::{constants::INTEGER_BYTE_LEN, file::page::Page};
 
-use super::schema::{FieldInfo, Schema};
+use super::{
+    partition::PartitionSpec,
+    schema::{FieldInfo, Schema},
+};
+
+/**
+ * table のファイルをどのように保存するかの設定
+ */
+#[derive(Copy, Clone, Default, Eq, PartialEq, Debug)]
+pub struct StorageOptions {
+    /// table のファイルの block size。None の場合は FileManager 共通の block size を使う
+    pub block_size: Option<usize>,
+    /// page を圧縮して保存するかどうか (compression feature が必要)
+    pub compressed: bool,
+}
+
+/**
+ * slot の中の、field の値以外の領域の配置
+ *
+ * slot は header (flag と、将来追加する null bitmap), field の値, padding の順に並ぶ
+ * 位置はどれも slot の先頭からの byte 数
+ */
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct SlotHeader {
+    /// slot が使用中かどうかを示す flag の位置
+    pub flag_offset: usize,
+    /// null bitmap の位置。null を保存しない layout では None
+    pub null_bitmap_offset: Option<usize>,
+    /// header の大きさ。最初の field はこの位置から始まる
+    pub header_size: usize,
+    /// 最後の field の後ろにある、どの field にも使われていない byte 数
+    pub padding: usize,
+}
 
 /**
  * table のレコードがどのように保存されているのかを示す構造体
+ *
+ * slot の中は次のように並ぶ (位置は slot の先頭からの byte 数)
+ *
+ * | 位置              | 大きさ       | 内容                                  |
+ * |-------------------|--------------|---------------------------------------|
+ * | 0                 | 4            | flag (RecordPageFlag)                 |
+ * | 4 以降            | field ごと   | field の値を schema の順に並べたもの  |
+ * | 最後の field の後 | padding      | どの field にも使わない領域           |
+ *
+ * 各 field は Layout::alignment_of の倍数の位置から始め、足りない分は直前に padding を入れる
+ * slot size も slot の中の alignment の最大値の倍数に切り上げるので、block の中のどの slot でも同じように揃う
+ * 今の型はどれも 4 byte 単位で読み書きし、大きさも 4 byte の倍数なので、field の間に padding は入らない
+ * (alignment の規則を入れる前に作られた table とも同じ配置になる)
  */
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct Layout {
@@ -16,20 +61,33 @@ pub struct Layout {
     offsets: HashMap<String, usize>,
     // 1 つの record が何バイトで保存されているかを示す
     slot_size: usize,
+    // slot の中の、field 以外の領域の配置
+    slot_header: SlotHeader,
+    // table のファイルの保存方法
+    storage: StorageOptions,
+    // partition に分けた table の場合、その分け方
+    partition: Option<PartitionSpec>,
 }
 
 #[derive(Error, Debug)]
-pub(crate) enum LayoutError {
+pub enum LayoutError {
     #[error("invalid call error: {0}")]
     InvalidCallError(String),
+    #[error("inconsistent layout: {0}")]
+    Inconsistent(String),
 }
 
 impl Layout {
     pub fn new(schema: Schema) -> Result<Layout, LayoutError> {
         let mut offsets = HashMap::new();
         let mut pos = INTEGER_BYTE_LEN;
-        for field in &schema.fields() {
-            offsets.insert(field.clone(), pos);
+        // flag は int なので、slot の先頭も int と同じく揃える
+        let mut slot_alignment = INTEGER_BYTE_LEN;
+        for (field, info) in schema.infos() {
+            let alignment = Self::alignment_of(info);
+            pos = pos.next_multiple_of(alignment);
+            slot_alignment = slot_alignment.max(alignment);
+            offsets.insert(field.to_string(), pos);
             match Self::length_in_bytes(&schema, field) {
                 Some(len) => pos += len,
                 None => {
@@ -40,10 +98,19 @@ impl Layout {
                 }
             }
         }
+        let slot_size = pos.next_multiple_of(slot_alignment);
         Ok(Layout {
             schema,
             offsets,
-            slot_size: pos,
+            slot_size,
+            slot_header: SlotHeader {
+                flag_offset: 0,
+                null_bitmap_offset: None,
+                header_size: INTEGER_BYTE_LEN,
+                padding: slot_size - pos,
+            },
+            storage: StorageOptions::default(),
+            partition: None,
         })
     }
 
@@ -52,10 +119,73 @@ impl Layout {
         offsets: HashMap<String, usize>,
         slot_size: usize,
     ) -> Layout {
+        // catalog には slot size と各 field の offset しか保存していないので、最後の field の後ろを padding とする
+        let data_end = schema
+            .fields_iter()
+            .filter_map(|field| Some(offsets.get(field)? + Self::length_in_bytes(&schema, field)?))
+            .max()
+            .unwrap_or(INTEGER_BYTE_LEN);
+        let slot_header = SlotHeader {
+            flag_offset: 0,
+            null_bitmap_offset: None,
+            header_size: INTEGER_BYTE_LEN,
+            padding: slot_size.saturating_sub(data_end),
+        };
         Layout {
             schema,
             offsets,
             slot_size,
+            slot_header,
+            storage: StorageOptions::default(),
+            partition: None,
+        }
+    }
+
+    /// table のファイルの保存方法を指定した layout を返す
+    pub fn with_storage(mut self, storage: StorageOptions) -> Layout {
+        self.storage = storage;
+        self
+    }
+
+    /// partition に分けた table の layout を返す
+    pub fn with_partition(mut self, partition: PartitionSpec) -> Layout {
+        self.partition = Some(partition);
+        self
+    }
+
+    /// partition に分けた table の場合、その分け方を返す
+    pub fn partition(&self) -> Option<&PartitionSpec> {
+        self.partition.as_ref()
+    }
+
+    /// partition の設定を除いた layout を返す
+    /// partition の実体のファイルは、通常の table と同じようにこの layout で読み書きする
+    pub fn without_partition(&self) -> Layout {
+        let mut layout = self.clone();
+        layout.partition = None;
+        layout
+    }
+
+    /// fields に含まれる field だけを持つ layout を返す
+    /// slot size や各 field の offset は元の layout と同じなので、同じ table のファイルをそのまま読める
+    /// fields のうち、この layout に存在しない field は無視する
+    pub fn project(&self, fields: &[String]) -> Layout {
+        let mut schema = Schema::new();
+        let mut offsets = HashMap::new();
+        for (field, info) in self.schema.infos() {
+            if !fields.iter().any(|f| f == field) {
+                continue;
+            }
+            if let Some(offset) = self.offset(field) {
+                schema.add_field(field, info);
+                offsets.insert(field.to_string(), offset);
+            }
+        }
+        let layout = Layout::new_from_existing_settings(schema, offsets, self.slot_size)
+            .with_storage(self.storage);
+        match &self.partition {
+            Some(partition) => layout.with_partition(partition.clone()),
+            None => layout,
         }
     }
 
@@ -71,15 +201,140 @@ impl Layout {
         self.slot_size
     }
 
+    pub fn slot_header(&self) -> SlotHeader {
+        self.slot_header
+    }
+
+    /// block の先頭から、slot の先頭までの byte 数
+    pub fn slot_offset(&self, slot: usize) -> usize {
+        slot * self.slot_size
+    }
+
+    /// block の先頭から、slot の flag までの byte 数
+    pub fn flag_offset(&self, slot: usize) -> usize {
+        self.slot_offset(slot) + self.slot_header.flag_offset
+    }
+
+    /// block の先頭から、slot の field の値までの byte 数。field が無い場合は None を返す
+    pub fn field_offset(&self, slot: usize, field_name: &str) -> Option<usize> {
+        Some(self.slot_offset(slot) + self.offset(field_name)?)
+    }
+
+    /// block_size の block に入る slot の数
+    /// slot は block の最後の byte を使わないように並べる
+    pub fn slots_per_block(&self, block_size: usize) -> usize {
+        block_size.saturating_sub(1) / self.slot_size
+    }
+
+    pub fn storage(&self) -> StorageOptions {
+        self.storage
+    }
+
+    pub fn block_size(&self) -> Option<usize> {
+        self.storage.block_size
+    }
+
+    /// field の値を置く位置の alignment (byte 数)
+    /// bigint と double も int 2 つとして読み書きするので、どの型も int と同じ 4 byte に揃える
+    /// string は先頭の長さを int として読むので、同じく 4 byte に揃える
+    pub fn alignment_of(info: FieldInfo) -> usize {
+        match info {
+            FieldInfo::Integer
+            | FieldInfo::Boolean
+            | FieldInfo::Date
+            | FieldInfo::BigInt
+            | FieldInfo::Double
+            | FieldInfo::String(_) => INTEGER_BYTE_LEN,
+        }
+    }
+
+    /// catalog から読んだ layout で、全ての field が header の後ろから slot の中に収まり、互いに重ならないことを確認する
+    /// field の位置が alignment に揃っていることも確認する
+    pub(crate) fn validate(&self) -> Result<(), LayoutError> {
+        let mut ranges = vec![];
+        for (field, info) in self.schema.infos() {
+            let offset = self.offset(field).ok_or_else(|| {
+                LayoutError::Inconsistent(format!("field {} has no offset", field))
+            })?;
+            if !offset.is_multiple_of(Self::alignment_of(info)) {
+                return Err(LayoutError::Inconsistent(format!(
+                    "field {} at offset {} is not aligned to {} bytes",
+                    field,
+                    offset,
+                    Self::alignment_of(info)
+                )));
+            }
+            let length = Self::length_in_bytes(&self.schema, field).unwrap_or_default();
+            ranges.push((offset, offset + length, field));
+        }
+        ranges.sort();
+        let mut end_of_previous = self.slot_header.header_size;
+        for (start, end, field) in ranges {
+            if start < end_of_previous {
+                return Err(LayoutError::Inconsistent(format!(
+                    "field {} at offset {} overlaps the previous field or the slot header",
+                    field, start
+                )));
+            }
+            end_of_previous = end;
+        }
+        if end_of_previous > self.slot_size {
+            return Err(LayoutError::Inconsistent(format!(
+                "fields end at offset {} beyond the slot size {}",
+                end_of_previous, self.slot_size
+            )));
+        }
+        Ok(())
+    }
+
     fn length_in_bytes(schema: &Schema, field_name: &str) -> Option<usize> {
         match schema.info(field_name) {
-            Some(FieldInfo::Integer) => Some(INTEGER_BYTE_LEN),
+            Some(FieldInfo::Integer) | Some(FieldInfo::Boolean) | Some(FieldInfo::Date) => {
+                Some(INTEGER_BYTE_LEN)
+            }
+            Some(FieldInfo::BigInt) | Some(FieldInfo::Double) => Some(2 * INTEGER_BYTE_LEN),
             Some(FieldInfo::String(size)) => Some(Page::max_length(size)),
             None => None,
         }
     }
 }
 
+impl fmt::Display for Layout {
+    /// slot の中の配置を、位置の順に 1 行に 1 つずつ表示する
+    /// field の間の padding も 1 行として表示するので、binary format を変えた時に配置を確かめるのに使える
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "slot size: {}, header: {}, padding: {}",
+            self.slot_size, self.slot_header.header_size, self.slot_header.padding
+        )?;
+        writeln!(
+            f,
+            "  @{} flag ({})",
+            self.slot_header.flag_offset, INTEGER_BYTE_LEN
+        )?;
+        let mut fields = self
+            .schema
+            .infos()
+            .filter_map(|(field, info)| Some((self.offset(field)?, field, info)))
+            .collect::<Vec<_>>();
+        fields.sort_by_key(|(offset, field, _)| (*offset, *field));
+        let mut pos = self.slot_header.header_size;
+        for (offset, field, info) in fields {
+            if offset > pos {
+                writeln!(f, "  @{} padding ({})", pos, offset - pos)?;
+            }
+            let length = Self::length_in_bytes(&self.schema, field).unwrap_or_default();
+            writeln!(f, "  @{} {} {} ({})", offset, field, info, length)?;
+            pos = pos.max(offset + length);
+        }
+        if self.slot_size > pos {
+            writeln!(f, "  @{} padding ({})", pos, self.slot_size - pos)?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod layout_test {
     use super::*;
@@ -95,4 +350,124 @@ mod layout_test {
         assert_eq!(layout.offset("id"), Some(4));
         assert_eq!(layout.offset("name"), Some(8));
     }
+
+    #[test]
+    fn test_slot_offsets() {
+        let mut schema = Schema::new();
+        schema.add_field("id", FieldInfo::Integer);
+        schema.add_field("name", FieldInfo::String(2));
+        let layout = Layout::new(schema.clone()).unwrap();
+        assert_eq!(layout.slot_size(), 4 + 4 + 12);
+        assert_eq!(
+            layout.slot_header(),
+            SlotHeader {
+                flag_offset: 0,
+                null_bitmap_offset: None,
+                header_size: 4,
+                padding: 0,
+            }
+        );
+        assert_eq!(layout.slot_offset(2), 40);
+        assert_eq!(layout.flag_offset(2), 40);
+        assert_eq!(layout.field_offset(2, "name"), Some(48));
+        assert_eq!(layout.field_offset(2, "age"), None);
+        // 最後の slot が block の最後の byte までを使う場合は入らない
+        assert_eq!(layout.slots_per_block(100), 4);
+        assert_eq!(layout.slots_per_block(101), 5);
+        assert_eq!(layout.slots_per_block(0), 0);
+
+        // catalog から復元した layout では、最後の field の後ろが padding になる
+        let offsets = HashMap::from([("id".to_string(), 4), ("name".to_string(), 8)]);
+        let restored = Layout::new_from_existing_settings(schema, offsets, 24);
+        assert_eq!(restored.slot_header().padding, 4);
+        assert_eq!(restored.slot_header().header_size, 4);
+    }
+
+    #[test]
+    fn test_alignment() {
+        let mut schema = Schema::new();
+        schema.add_field("a", FieldInfo::Boolean);
+        schema.add_field("b", FieldInfo::BigInt);
+        schema.add_field("c", FieldInfo::String(1));
+        schema.add_field("d", FieldInfo::Double);
+        schema.add_field("e", FieldInfo::Date);
+        let layout = Layout::new(schema.clone()).unwrap();
+        // どの型も 4 byte の倍数の大きさなので、詰めて並べても揃っている
+        let offsets = schema
+            .fields_iter()
+            .map(|field| layout.offset(field).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, vec![4, 8, 16, 24, 32]);
+        assert!(schema.infos().all(|(field, info)| layout
+            .offset(field)
+            .unwrap()
+            .is_multiple_of(Layout::alignment_of(info))));
+        assert_eq!(layout.slot_size(), 36);
+        assert_eq!(layout.slot_header().padding, 0);
+        assert!(layout.validate().is_ok());
+    }
+
+    #[test]
+    fn test_display() {
+        let mut schema = Schema::new();
+        schema.add_field("id", FieldInfo::Integer);
+        schema.add_field("name", FieldInfo::String(2));
+        assert_eq!(
+            Layout::new(schema.clone()).unwrap().to_string(),
+            "slot size: 20, header: 4, padding: 0\n  @0 flag (4)\n  @4 id int (4)\n  @8 name varchar(2) (12)\n"
+        );
+
+        // field の間と最後の field の後ろの padding も表示する
+        let offsets = HashMap::from([("id".to_string(), 4), ("name".to_string(), 12)]);
+        let layout = Layout::new_from_existing_settings(schema, offsets, 28);
+        assert_eq!(
+            layout.to_string(),
+            "slot size: 28, header: 4, padding: 4\n  @0 flag (4)\n  @4 id int (4)\n  @8 padding (4)\n  @12 name varchar(2) (12)\n  @24 padding (4)\n"
+        );
+    }
+
+    #[test]
+    fn test_project() {
+        let mut schema = Schema::new();
+        schema.add_field("id", FieldInfo::Integer);
+        schema.add_field("name", FieldInfo::String(10));
+        schema.add_field("age", FieldInfo::Integer);
+
+        let layout = Layout::new(schema).unwrap();
+        let projected = layout.project(&["age".to_string(), "unknown".to_string()]);
+        assert_eq!(projected.schema().fields(), vec!["age".to_string()]);
+        assert_eq!(projected.offset("age"), layout.offset("age"));
+        assert_eq!(projected.offset("name"), None);
+        assert_eq!(projected.slot_size(), layout.slot_size());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut schema = Schema::new();
+        schema.add_field("id", FieldInfo::Integer);
+        schema.add_field("age", FieldInfo::Integer);
+        assert!(Layout::new(schema.clone()).unwrap().validate().is_ok());
+
+        // 重なっている field
+        let offsets = HashMap::from([("id".to_string(), 4), ("age".to_string(), 6)]);
+        let layout = Layout::new_from_existing_settings(schema.clone(), offsets, 12);
+        assert!(matches!(
+            layout.validate(),
+            Err(LayoutError::Inconsistent(_))
+        ));
+        // slot に収まらない field
+        let offsets = HashMap::from([("id".to_string(), 4), ("age".to_string(), 8)]);
+        let layout = Layout::new_from_existing_settings(schema.clone(), offsets, 10);
+        assert!(matches!(
+            layout.validate(),
+            Err(LayoutError::Inconsistent(_))
+        ));
+        // 重なっていなくても、alignment に揃っていない field
+        let offsets = HashMap::from([("id".to_string(), 4), ("age".to_string(), 10)]);
+        let layout = Layout::new_from_existing_settings(schema, offsets, 16);
+        assert!(matches!(
+            layout.validate(),
+            Err(LayoutError::Inconsistent(_))
+        ));
+    }
 }
@@ -0,0 +1,196 @@
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::query::constant::Constant;
+
+/**
+ * partition の分け方
+ */
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum PartitionScheme {
+    /// field の値の hash で、指定した数の partition に分ける
+    Hash(usize),
+    /// 2 番目以降の partition の下限の値の列 (昇順)
+    /// partition i には bounds[i - 1] 以上 bounds[i] 未満の値が入る。最初の partition に下限はなく、最後の partition に上限はない
+    Range(Vec<i32>),
+}
+
+/**
+ * table をどの field の値で partition に分けるかの設定
+ *
+ * partition に分けた table の record は、partition ごとに別のファイルに保存される
+ * catalog に保存される設定の他に、query で読む必要がある partition の集合 (pruning の結果) を持つ
+ */
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct PartitionSpec {
+    field: String,
+    scheme: PartitionScheme,
+    // 読む partition の番号 (昇順)。None の場合はすべての partition を読む
+    selected: Option<Vec<usize>>,
+}
+
+#[derive(Error, Debug)]
+pub enum PartitionError {
+    #[error("invalid partition spec: {0}")]
+    InvalidSpec(String),
+}
+
+impl PartitionSpec {
+    pub fn new(field: &str, scheme: PartitionScheme) -> Result<PartitionSpec, PartitionError> {
+        match &scheme {
+            PartitionScheme::Hash(0) => {
+                return Err(PartitionError::InvalidSpec(
+                    "the number of hash partitions must be positive".to_string(),
+                ))
+            }
+            PartitionScheme::Range(bounds) if bounds.windows(2).any(|pair| pair[0] >= pair[1]) => {
+                return Err(PartitionError::InvalidSpec(
+                    "range partition bounds must be strictly increasing".to_string(),
+                ))
+            }
+            _ => {}
+        }
+        Ok(PartitionSpec {
+            field: field.to_string(),
+            scheme,
+            selected: None,
+        })
+    }
+
+    /// partition を決める field の名前
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn scheme(&self) -> &PartitionScheme {
+        &self.scheme
+    }
+
+    /// partition の数
+    pub fn partition_count(&self) -> usize {
+        match &self.scheme {
+            PartitionScheme::Hash(count) => *count,
+            PartitionScheme::Range(bounds) => bounds.len() + 1,
+        }
+    }
+
+    /// partition を決める field の値が val である record を保存する partition の番号を返す
+    pub fn partition_of(&self, val: &Constant) -> usize {
+        match &self.scheme {
+            PartitionScheme::Hash(count) => (stable_hash(val) % *count as u64) as usize,
+            PartitionScheme::Range(bounds) => match val {
+                Constant::Int(val) => bounds.partition_point(|bound| bound <= val),
+                // range partition は int の field にしか作れないので、ここには来ない
+                _ => 0,
+            },
+        }
+    }
+
+    /// 読む partition の番号を昇順で返す
+    pub fn selected_partitions(&self) -> Vec<usize> {
+        match &self.selected {
+            Some(selected) => selected.clone(),
+            None => (0..self.partition_count()).collect(),
+        }
+    }
+
+    /// field の値が val である record だけを読めばよい場合に、その record がある partition だけを読む設定を返す
+    pub fn prune(mut self, val: &Constant) -> PartitionSpec {
+        let partition = self.partition_of(val);
+        let selected = self
+            .selected_partitions()
+            .into_iter()
+            .filter(|p| *p == partition)
+            .collect();
+        self.selected = Some(selected);
+        self
+    }
+
+    /// partition に分けた table の、partition の実体の table 名を返す
+    /// table 名には "." を使えないので、通常の table と名前が重なることはない
+    pub fn partition_table_name(table_name: &str, partition: usize) -> String {
+        format!("{}.p{}", table_name, partition)
+    }
+}
+
+impl fmt::Display for PartitionSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.scheme {
+            PartitionScheme::Hash(count) => {
+                write!(f, "partition by hash({}) partitions {}", self.field, count)
+            }
+            PartitionScheme::Range(bounds) => {
+                let bounds = bounds
+                    .iter()
+                    .map(|bound| bound.to_string())
+                    .collect::<Vec<_>>();
+                write!(
+                    f,
+                    "partition by range({}) values ({})",
+                    self.field,
+                    bounds.join(", ")
+                )
+            }
+        }
+    }
+}
+
+// 保存先の partition はファイルに残るので、実行環境によらず同じ値になる hash (FNV-1a) を使う
+pub(crate) fn stable_hash(val: &Constant) -> u64 {
+    let bytes = match val {
+        Constant::Int(val) => val.to_be_bytes().to_vec(),
+        Constant::String(val) => val.as_bytes().to_vec(),
+        Constant::Bool(val) => vec![*val as u8],
+        Constant::BigInt(val) => val.to_be_bytes().to_vec(),
+        Constant::Date(val) => val.days().to_be_bytes().to_vec(),
+        // 0.0 と -0.0 は等しい値なので、同じ partition に入れる
+        Constant::Double(val) if *val == 0.0 => 0u64.to_be_bytes().to_vec(),
+        Constant::Double(val) => val.to_bits().to_be_bytes().to_vec(),
+    };
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod partition_test {
+    use super::*;
+
+    #[test]
+    fn test_hash_partition() {
+        let spec = PartitionSpec::new("a", PartitionScheme::Hash(4)).unwrap();
+        assert_eq!(spec.partition_count(), 4);
+        for i in 0..100 {
+            let partition = spec.partition_of(&Constant::Int(i));
+            assert!(partition < 4);
+            // 同じ値は常に同じ partition に入る
+            assert_eq!(spec.partition_of(&Constant::Int(i)), partition);
+        }
+        let pruned = spec.clone().prune(&Constant::Int(7));
+        assert_eq!(
+            pruned.selected_partitions(),
+            vec![spec.partition_of(&Constant::Int(7))]
+        );
+        assert!(PartitionSpec::new("a", PartitionScheme::Hash(0)).is_err());
+    }
+
+    #[test]
+    fn test_range_partition() {
+        let spec = PartitionSpec::new("a", PartitionScheme::Range(vec![10, 20])).unwrap();
+        assert_eq!(spec.partition_count(), 3);
+        assert_eq!(spec.partition_of(&Constant::Int(-5)), 0);
+        assert_eq!(spec.partition_of(&Constant::Int(9)), 0);
+        assert_eq!(spec.partition_of(&Constant::Int(10)), 1);
+        assert_eq!(spec.partition_of(&Constant::Int(19)), 1);
+        assert_eq!(spec.partition_of(&Constant::Int(20)), 2);
+        assert_eq!(
+            spec.to_string(),
+            "partition by range(a) values (10, 20)".to_string()
+        );
+        // 一度 pruning した後に別の値で pruning すると、どの partition も読まなくてよい
+        let pruned = spec.prune(&Constant::Int(15)).prune(&Constant::Int(25));
+        assert!(pruned.selected_partitions().is_empty());
+        assert!(PartitionSpec::new("a", PartitionScheme::Range(vec![20, 10])).is_err());
+    }
+}
@@ -0,0 +1,355 @@
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    rc::Rc,
+};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+
+use crate::{
+    query::{
+        constant::Constant,
+        row::Row,
+        scan::{ReadScan, ReadScanError, UpdateScan, UpdateScanError},
+    },
+    tx::transaction::Transaction,
+};
+
+use super::{
+    layout::Layout,
+    partition::PartitionSpec,
+    rid::Rid,
+    schema::FieldInfo,
+    table_scan_factory::{TableScanFactory, TableScanFactoryImpl},
+};
+
+/**
+ * partition に分けた table を、読む partition の scan を順番につないで読むための read-only な scan
+ */
+pub struct PartitionedScan {
+    layout: Layout,
+    // 読む partition ごとの scan
+    scans: Vec<Box<dyn ReadScan>>,
+    // 今読んでいる scan の位置
+    current: usize,
+}
+
+impl PartitionedScan {
+    pub fn new(layout: Layout, scans: Vec<Box<dyn ReadScan>>) -> PartitionedScan {
+        PartitionedScan {
+            layout,
+            scans,
+            current: 0,
+        }
+    }
+}
+
+impl ReadScan for PartitionedScan {
+    fn before_first(&mut self) -> AnyhowResult<()> {
+        self.current = 0;
+        for scan in self.scans.iter_mut() {
+            scan.before_first()?;
+        }
+        Ok(())
+    }
+
+    fn move_next(&mut self) -> AnyhowResult<bool> {
+        while let Some(scan) = self.scans.get_mut(self.current) {
+            if scan.move_next()? {
+                return Ok(true);
+            }
+            self.current += 1;
+        }
+        Ok(false)
+    }
+
+    fn get_val(&self, field_name: &str) -> AnyhowResult<Constant> {
+        match self.scans.get(self.current) {
+            Some(scan) => scan.get_val(field_name),
+            None => Err(anyhow!(ReadScanError::InvalidCall(
+                "no record is specified for the partitioned scan".to_string()
+            ))),
+        }
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.layout.schema().has_field(field_name)
+    }
+
+    fn compare_val(&self, field_name: &str, val: &Constant) -> AnyhowResult<Option<Ordering>> {
+        match self.scans.get(self.current) {
+            Some(scan) => scan.compare_val(field_name, val),
+            None => Err(anyhow!(ReadScanError::InvalidCall(
+                "no record is specified for the partitioned scan".to_string()
+            ))),
+        }
+    }
+
+    fn read_into(&self, row: &mut Row) -> AnyhowResult<()> {
+        match self.scans.get(self.current) {
+            Some(scan) => scan.read_into(row),
+            None => Err(anyhow!(ReadScanError::InvalidCall(
+                "no record is specified for the partitioned scan".to_string()
+            ))),
+        }
+    }
+
+    fn block_accesses(&self) -> u64 {
+        self.scans.iter().map(|scan| scan.block_accesses()).sum()
+    }
+}
+
+/**
+ * partition に分けた table の record を読み書きするための scan
+ *
+ * 走査は読む partition の scan を順番につないで行う
+ * insert した record や、partition を決める field を書き換えた record は、値に応じた partition に保存する
+ * 走査中の scan の cursor を動かさないように、partition への書き込みは partition ごとに別に開いた scan で行う
+ */
+pub struct PartitionedUpdateScan {
+    tx: Rc<RefCell<Transaction>>,
+    table_name: String,
+    // partition の実体のファイルを読み書きする layout (partition の設定を含まない)
+    layout: Layout,
+    spec: PartitionSpec,
+    // 読む partition の番号と、その partition を走査する scan
+    scans: RefCell<Vec<(usize, Box<dyn UpdateScan>)>>,
+    // 今走査している scan の位置
+    current: usize,
+    // 書き込みに使う、partition ごとの scan
+    writers: RefCell<HashMap<usize, Box<dyn UpdateScan>>>,
+    // 現在の record が書き込み用の scan の方にある場合、その partition の番号
+    writer_cursor: Cell<Option<usize>>,
+    // この scan が書き込んだ record。走査中に同じ record を二度読まないように飛ばす
+    written: RefCell<HashSet<Rid>>,
+}
+
+impl PartitionedUpdateScan {
+    /// table_name の partition のうち、spec で選ばれている partition を走査する scan を作成する
+    pub fn new(
+        tx: &Rc<RefCell<Transaction>>,
+        table_name: &str,
+        layout: &Layout,
+        spec: PartitionSpec,
+    ) -> AnyhowResult<PartitionedUpdateScan> {
+        let layout = layout.without_partition();
+        let table_scan_factory = TableScanFactoryImpl::new();
+        let mut scans = vec![];
+        for partition in spec.selected_partitions() {
+            let partition_name = PartitionSpec::partition_table_name(table_name, partition);
+            scans.push((
+                partition,
+                table_scan_factory.create(tx, &partition_name, &layout)?,
+            ));
+        }
+        Ok(PartitionedUpdateScan {
+            tx: tx.clone(),
+            table_name: table_name.to_string(),
+            layout,
+            spec,
+            scans: RefCell::new(scans),
+            current: 0,
+            writers: RefCell::new(HashMap::new()),
+            writer_cursor: Cell::new(None),
+            written: RefCell::new(HashSet::new()),
+        })
+    }
+
+    // 現在の record がある partition の番号
+    fn current_partition(&self) -> Option<usize> {
+        self.writer_cursor.get().or_else(|| {
+            self.scans
+                .borrow()
+                .get(self.current)
+                .map(|(partition, _)| *partition)
+        })
+    }
+
+    // 現在の record を指している scan に対して f を呼ぶ
+    fn with_current<T>(
+        &self,
+        f: impl FnOnce(&mut dyn UpdateScan) -> AnyhowResult<T>,
+    ) -> AnyhowResult<T> {
+        match self.writer_cursor.get() {
+            Some(partition) => {
+                let mut writers = self.writers.borrow_mut();
+                let scan = writers.get_mut(&partition).ok_or_else(|| {
+                    UpdateScanError::Internal(format!(
+                        "no scan is opened for partition {} of table {}",
+                        partition, self.table_name
+                    ))
+                })?;
+                f(scan.as_mut())
+            }
+            None => {
+                let mut scans = self.scans.borrow_mut();
+                let (_, scan) = scans.get_mut(self.current).ok_or_else(|| {
+                    UpdateScanError::InvalidCall(
+                        "no record is specified for the partitioned scan".to_string(),
+                    )
+                })?;
+                f(scan.as_mut())
+            }
+        }
+    }
+
+    // partition に書き込む scan を必要なら開き、その scan に対して f を呼ぶ
+    fn with_writer<T>(
+        &self,
+        partition: usize,
+        f: impl FnOnce(&mut dyn UpdateScan) -> AnyhowResult<T>,
+    ) -> AnyhowResult<T> {
+        let mut writers = self.writers.borrow_mut();
+        let scan = match writers.entry(partition) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let partition_name =
+                    PartitionSpec::partition_table_name(&self.table_name, partition);
+                entry.insert(TableScanFactoryImpl::new().create(
+                    &self.tx,
+                    &partition_name,
+                    &self.layout,
+                )?)
+            }
+        };
+        f(scan.as_mut())
+    }
+
+    // partition に新しい record を挿入し、それを現在の record にする
+    fn insert_into(&self, partition: usize) -> AnyhowResult<()> {
+        let rid = self.with_writer(partition, |scan| {
+            scan.insert()?;
+            scan.get_rid()
+        })?;
+        self.written
+            .borrow_mut()
+            .insert(rid.with_partition(partition));
+        self.writer_cursor.set(Some(partition));
+        Ok(())
+    }
+
+    // 現在の record を、partition を決める field の値を val にした上で partition に移す
+    fn move_to_partition(&self, partition: usize, val: &Constant) -> AnyhowResult<()> {
+        let mut values = vec![];
+        for field in self.layout.schema().fields_iter() {
+            let value = if field == self.spec.field() {
+                val.clone()
+            } else {
+                self.with_current(|scan| scan.get_val(field))?
+            };
+            values.push((field, value));
+        }
+        self.with_current(|scan| scan.delete())?;
+        self.insert_into(partition)?;
+        self.with_writer(partition, |scan| {
+            for (field, value) in &values {
+                scan.set_val(field, value)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl ReadScan for PartitionedUpdateScan {
+    fn before_first(&mut self) -> AnyhowResult<()> {
+        self.current = 0;
+        self.writer_cursor.set(None);
+        self.written.borrow_mut().clear();
+        for (_, scan) in self.scans.borrow_mut().iter_mut() {
+            scan.before_first()?;
+        }
+        Ok(())
+    }
+
+    fn move_next(&mut self) -> AnyhowResult<bool> {
+        self.writer_cursor.set(None);
+        let mut scans = self.scans.borrow_mut();
+        while let Some((partition, scan)) = scans.get_mut(self.current) {
+            if !scan.move_next()? {
+                self.current += 1;
+                continue;
+            }
+            let rid = scan.get_rid()?.with_partition(*partition);
+            if !self.written.borrow().contains(&rid) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_val(&self, field_name: &str) -> AnyhowResult<Constant> {
+        self.with_current(|scan| scan.get_val(field_name))
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.layout.schema().has_field(field_name)
+    }
+
+    fn compare_val(&self, field_name: &str, val: &Constant) -> AnyhowResult<Option<Ordering>> {
+        self.with_current(|scan| scan.compare_val(field_name, val))
+    }
+
+    fn read_into(&self, row: &mut Row) -> AnyhowResult<()> {
+        self.with_current(|scan| scan.read_into(row))
+    }
+
+    fn block_accesses(&self) -> u64 {
+        let scans = self
+            .scans
+            .borrow()
+            .iter()
+            .map(|(_, scan)| scan.block_accesses())
+            .sum::<u64>();
+        let writers = self
+            .writers
+            .borrow()
+            .values()
+            .map(|scan| scan.block_accesses())
+            .sum::<u64>();
+        scans + writers
+    }
+}
+
+impl UpdateScan for PartitionedUpdateScan {
+    fn set_val(&self, field_name: &str, val: &Constant) -> AnyhowResult<()> {
+        if field_name == self.spec.field() {
+            let partition = self.spec.partition_of(val);
+            if self.current_partition().is_some_and(|p| p != partition) {
+                return self.move_to_partition(partition, val);
+            }
+        }
+        self.with_current(|scan| scan.set_val(field_name, val))
+    }
+
+    /// partition を決める field の値はまだ分からないので、その field の初期値 (0 または空文字列) の partition に挿入する
+    /// その field に値を設定した時に、値に応じた partition に移す
+    fn insert(&mut self) -> AnyhowResult<()> {
+        let initial = match self.layout.schema().info(self.spec.field()) {
+            Some(FieldInfo::String(_)) => Constant::String(String::new()),
+            _ => Constant::Int(0),
+        };
+        self.insert_into(self.spec.partition_of(&initial))
+    }
+
+    fn delete(&mut self) -> AnyhowResult<()> {
+        self.with_current(|scan| scan.delete())
+    }
+
+    fn move_to_rid(&mut self, rid: &Rid) -> AnyhowResult<()> {
+        self.with_writer(rid.partition(), |scan| scan.move_to_rid(rid))?;
+        self.writer_cursor.set(Some(rid.partition()));
+        Ok(())
+    }
+
+    fn get_rid(&self) -> AnyhowResult<Rid> {
+        let partition = self.current_partition().ok_or_else(|| {
+            UpdateScanError::InvalidCall(
+                "no record is specified for the partitioned scan".to_string(),
+            )
+        })?;
+        Ok(self
+            .with_current(|scan| scan.get_rid())?
+            .with_partition(partition))
+    }
+}
@@ -1,5 +1,9 @@
 use crate::{
-    file::blockid::BlockId,
+    file::{
+        blockid::BlockId,
+        file_manager::FileManagerError,
+        page::{Page, PageError},
+    },
     tx::transaction::{Transaction, TransactionGetError, TransactionSetError},
 };
 
@@ -14,6 +18,8 @@ use std::{cell::RefCell, rc::Rc};
  * ある block の中で、layout に従った record を取得・操作するための構造体
  *
  * フィールドの長さは固定長で、Unspanned (page をまたいで record を保存することがない) と仮定している
+ *
+ * new_read_only で作成した場合は、block を pin せずに作成時点の block の内容の写しを読む。この場合は record を変更できない
  */
 pub struct RecordPage {
     // record を取得する主体となっている transaction
@@ -21,6 +27,8 @@ pub struct RecordPage {
     // 参照している block
     block: BlockId,
     layout: Layout,
+    // read-only な場合に読む block の内容の写し
+    snapshot: Option<Page>,
 }
 
 #[derive(Eq, PartialEq, Debug, Clone, Copy)]
@@ -41,12 +49,20 @@ pub(crate) enum RecordPageError {
     TransactionGet(#[from] TransactionGetError),
     #[error("transaction set error: {0}")]
     TransactionSet(#[from] TransactionSetError),
+    #[error("file manager error: {0}")]
+    FileManager(#[from] FileManagerError),
+    #[error("page error: {0}")]
+    Page(#[from] PageError),
+    #[error("utf8 error: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
 }
 
 impl Drop for RecordPage {
     fn drop(&mut self) {
         // new で pin した block を unpin する
-        self.tx.borrow_mut().unpin(&self.block).unwrap();
+        if self.snapshot.is_none() {
+            self.tx.borrow().unpin(&self.block).unwrap();
+        }
     }
 }
 
@@ -56,26 +72,99 @@ impl RecordPage {
             tx,
             block: block.clone(),
             layout: layout.clone(),
+            snapshot: None,
         };
-        record_page.tx.borrow_mut().pin(block).unwrap();
+        record_page.tx.borrow().pin(block).unwrap();
         record_page
     }
 
+    /// block を pin せずに、block の内容の写しを読む read-only な RecordPage を作成する
+    /// 写しを取る時に block の slock を取るので、他の transaction の未 commit の変更は読まない
+    pub fn new_read_only(
+        tx: Rc<RefCell<Transaction>>,
+        block: &BlockId,
+        layout: &Layout,
+    ) -> Result<Self, RecordPageError> {
+        let snapshot = tx.borrow().read_block_copy(block)?;
+        Ok(RecordPage {
+            tx,
+            block: block.clone(),
+            layout: layout.clone(),
+            snapshot: Some(snapshot),
+        })
+    }
+
     pub fn get_int(&self, slot: usize, field_name: &str) -> Result<i32, RecordPageError> {
         let offset = self.offset(slot, field_name)?;
-        Ok(self.tx.borrow_mut().get_int(&self.block, offset)?)
+        self.read_int(offset)
+    }
+
+    pub fn get_bigint(&self, slot: usize, field_name: &str) -> Result<i64, RecordPageError> {
+        let offset = self.offset(slot, field_name)?;
+        match &self.snapshot {
+            Some(page) => Ok(page.try_get_i64(offset)?),
+            None => Ok(self.tx.borrow().get_bigint(&self.block, offset)?),
+        }
+    }
+
+    pub fn get_double(&self, slot: usize, field_name: &str) -> Result<f64, RecordPageError> {
+        let offset = self.offset(slot, field_name)?;
+        match &self.snapshot {
+            Some(page) => Ok(page.try_get_f64(offset)?),
+            None => Ok(self.tx.borrow().get_double(&self.block, offset)?),
+        }
     }
 
     pub fn get_string(&self, slot: usize, field_name: &str) -> Result<String, RecordPageError> {
+        self.read_str(slot, field_name, str::to_string)
+    }
+
+    /// slot の string の field を、String を作らずに f に渡して読む
+    pub fn read_str<R>(
+        &self,
+        slot: usize,
+        field_name: &str,
+        f: impl FnOnce(&str) -> R,
+    ) -> Result<R, RecordPageError> {
         let offset = self.offset(slot, field_name)?;
-        Ok(self.tx.borrow_mut().get_string(&self.block, offset)?)
+        match &self.snapshot {
+            Some(page) => Ok(f(page.get_str(offset)?)),
+            None => Ok(self.tx.borrow().read_str(&self.block, offset, f)?),
+        }
     }
 
     pub fn set_int(&self, slot: usize, field_name: &str, val: i32) -> Result<(), RecordPageError> {
+        self.check_writable()?;
+        let offset = self.offset(slot, field_name)?;
+        self.tx.borrow().set_int(&self.block, offset, val, true)?;
+        Ok(())
+    }
+
+    pub fn set_bigint(
+        &self,
+        slot: usize,
+        field_name: &str,
+        val: i64,
+    ) -> Result<(), RecordPageError> {
+        self.check_writable()?;
         let offset = self.offset(slot, field_name)?;
         self.tx
-            .borrow_mut()
-            .set_int(&self.block, offset, val, true)?;
+            .borrow()
+            .set_bigint(&self.block, offset, val, true)?;
+        Ok(())
+    }
+
+    pub fn set_double(
+        &self,
+        slot: usize,
+        field_name: &str,
+        val: f64,
+    ) -> Result<(), RecordPageError> {
+        self.check_writable()?;
+        let offset = self.offset(slot, field_name)?;
+        self.tx
+            .borrow()
+            .set_double(&self.block, offset, val, true)?;
         Ok(())
     }
 
@@ -86,6 +175,7 @@ impl RecordPage {
         field_name: &str,
         val: &str,
     ) -> Result<(), RecordPageError> {
+        self.check_writable()?;
         // string の長さが schema で設定された長さを超えていないかチェック
         match self.layout.schema().info(field_name) {
             Some(FieldInfo::String(len)) => {
@@ -104,7 +194,7 @@ impl RecordPage {
         }
         let offset = self.offset(slot, field_name)?;
         self.tx
-            .borrow_mut()
+            .borrow()
             .set_string(&self.block, offset, val, true)?;
         Ok(())
     }
@@ -116,28 +206,37 @@ impl RecordPage {
 
     // block の状態を初期化する。ここで施した変更は log には保存しない
     pub fn format(&self) -> Result<(), RecordPageError> {
+        self.check_writable()?;
         let mut slot = 0;
-        while self.is_valid_slot(slot) {
-            self.tx.borrow_mut().set_int(
+        while self.is_valid_slot(slot)? {
+            self.tx.borrow().set_int(
                 &self.block,
                 self.root_offset(slot),
                 RecordPageFlag::Empty as i32,
                 true,
             )?;
             let schema = self.layout.schema();
-            for field in schema.fields() {
-                let offset = self.offset(slot, &field)?;
-                match schema.info(&field) {
-                    Some(crate::record::schema::FieldInfo::Integer) => {
-                        self.tx.borrow_mut().set_int(&self.block, offset, 0, false)?;
+            for (field, info) in schema.infos() {
+                let offset = self.offset(slot, field)?;
+                match info {
+                    crate::record::schema::FieldInfo::Integer
+                    | crate::record::schema::FieldInfo::Boolean
+                    | crate::record::schema::FieldInfo::Date => {
+                        self.tx.borrow().set_int(&self.block, offset, 0, false)?;
+                    }
+                    crate::record::schema::FieldInfo::BigInt => {
+                        self.tx.borrow().set_bigint(&self.block, offset, 0, false)?;
+                    }
+                    crate::record::schema::FieldInfo::Double => {
+                        self.tx
+                            .borrow()
+                            .set_double(&self.block, offset, 0.0, false)?;
                     }
-                    Some(crate::record::schema::FieldInfo::String(_)) => {
-                        self.tx.borrow_mut().set_string(&self.block, offset, "", false)?;
+                    crate::record::schema::FieldInfo::String(_) => {
+                        self.tx
+                            .borrow()
+                            .set_string(&self.block, offset, "", false)?;
                     }
-                    None => return Err(RecordPageError::InvalidCall(
-                        "field not found. It might be because the layout configuration was not correct."
-                            .to_string(),
-                    )),
                 }
             }
             slot += 1;
@@ -169,6 +268,23 @@ impl RecordPage {
         &self.block
     }
 
+    /// この block に入る slot の数を返す
+    pub fn slot_count(&self) -> Result<usize, RecordPageError> {
+        // table ごとに block size が異なりうるので、block のファイルの block size を使う
+        let block_size = self.tx.borrow().block_size_of(self.block.file_name())?;
+        Ok(self.layout.slots_per_block(block_size))
+    }
+
+    /// slot の flag を、RecordPageFlag として解釈せずにそのまま返す
+    pub fn raw_flag(&self, slot: usize) -> Result<i32, RecordPageError> {
+        self.read_int(self.root_offset(slot))
+    }
+
+    /// slot の field が保存されている、block の先頭からの byte 単位の位置を返す
+    pub fn field_offset(&self, slot: usize, field_name: &str) -> Result<usize, RecordPageError> {
+        self.offset(slot, field_name)
+    }
+
     fn search_after(
         &mut self,
         slot: Option<usize>,
@@ -178,11 +294,8 @@ impl RecordPage {
             Some(slot) => slot + 1,
             None => 0,
         };
-        while self.is_valid_slot(next_slot) {
-            let flag = self
-                .tx
-                .borrow_mut()
-                .get_int(&self.block, self.root_offset(next_slot))?;
+        while self.is_valid_slot(next_slot)? {
+            let flag = self.read_int(self.root_offset(next_slot))?;
             let flag = RecordPageFlag::from_i32(flag).ok_or(RecordPageError::Internal(format!(
                 "invalid flag found. slot: {}, flag: {}",
                 next_slot, flag
@@ -196,28 +309,42 @@ impl RecordPage {
     }
 
     fn set_flag(&mut self, slot: usize, flag: RecordPageFlag) -> Result<(), RecordPageError> {
-        let offset = slot * self.layout.slot_size();
+        self.check_writable()?;
+        let offset = self.root_offset(slot);
         self.tx
-            .borrow_mut()
+            .borrow()
             .set_int(&self.block, offset, flag as i32, true)?;
         Ok(())
     }
 
+    fn read_int(&self, offset: usize) -> Result<i32, RecordPageError> {
+        match &self.snapshot {
+            Some(page) => Ok(page.try_get_int(offset)?),
+            None => Ok(self.tx.borrow().get_int(&self.block, offset)?),
+        }
+    }
+
+    fn check_writable(&self) -> Result<(), RecordPageError> {
+        if self.snapshot.is_some() {
+            return Err(RecordPageError::InvalidCall(
+                "record page is read-only".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
     fn offset(&self, slot: usize, field_name: &str) -> Result<usize, RecordPageError> {
-        Ok(slot * self.layout.slot_size()
-            + self
-                .layout
-                .offset(field_name)
-                .ok_or(RecordPageError::InvalidCall("field not found".to_string()))?)
+        self.layout
+            .field_offset(slot, field_name)
+            .ok_or(RecordPageError::InvalidCall("field not found".to_string()))
     }
 
     fn root_offset(&self, slot: usize) -> usize {
-        slot * self.layout.slot_size()
+        self.layout.flag_offset(slot)
     }
 
-    fn is_valid_slot(&self, slot: usize) -> bool {
-        let block_size = self.tx.borrow_mut().block_size();
-        return self.root_offset(slot + 1) < block_size;
+    fn is_valid_slot(&self, slot: usize) -> Result<bool, RecordPageError> {
+        Ok(slot < self.slot_count()?)
     }
 }
 
@@ -323,7 +450,7 @@ mod record_page_test {
         }
 
         // drop で unpin されているので、再び unpin しようとすると error になる
-        assert!(tx.borrow_mut().unpin(&block).is_err());
+        assert!(tx.borrow().unpin(&block).is_err());
         tx.borrow_mut().commit().unwrap();
     }
 
@@ -5,11 +5,27 @@
 pub struct Rid {
     blk_num: usize,
     slot: Option<usize>,
+    // partition に分けた table の場合、record がある partition の番号。それ以外の table では 0
+    partition: usize,
 }
 
 impl Rid {
     pub fn new(blk_num: usize, slot: Option<usize>) -> Self {
-        Rid { blk_num, slot }
+        Rid {
+            blk_num,
+            slot,
+            partition: 0,
+        }
+    }
+
+    /// partition に分けた table の、partition の番号を指定した record id を返す
+    pub fn with_partition(mut self, partition: usize) -> Self {
+        self.partition = partition;
+        self
+    }
+
+    pub fn partition(&self) -> usize {
+        self.partition
     }
 
     pub fn block_number(&self) -> usize {
@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
 use thiserror::Error;
 
@@ -47,16 +47,38 @@ impl Schema {
 
     // schema に指定したものをすべて追加する
     pub fn add_all(&mut self, schema: &Schema) -> Result<(), SchemaError> {
-        for field in &schema.fields {
-            self.add(field, schema)?;
+        for (field, info) in schema.infos() {
+            self.add_field(field, info);
         }
         Ok(())
     }
 
+    /// field 名を追加した順に返す。Vec が必要な場合以外は fields_iter を使う
     pub fn fields(&self) -> Vec<String> {
         self.fields.clone()
     }
 
+    /// field 名を追加した順に、clone せずに返す
+    pub fn fields_iter(&self) -> impl Iterator<Item = &str> + '_ {
+        self.fields.iter().map(|field| field.as_str())
+    }
+
+    /// field 名とその FieldInfo を、追加した順に返す
+    pub fn infos(&self) -> impl Iterator<Item = (&str, FieldInfo)> + '_ {
+        self.fields
+            .iter()
+            .map(|field| (field.as_str(), self.info[field]))
+    }
+
+    /// field の数
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+
     pub fn has_field(&self, field_name: &str) -> bool {
         self.info.contains_key(field_name)
     }
@@ -70,12 +92,24 @@ impl Schema {
 pub enum FieldInfo {
     Integer,
     String(usize),
+    /// 真偽値。Integer と同じく 4 byte の int (0 または 1) として保存する
+    Boolean,
+    /// 8 byte の整数。big endian で保存する
+    BigInt,
+    /// 日付。1970-01-01 からの日数を 4 byte の int として保存する
+    Date,
+    /// 8 byte の浮動小数点数。f64 の bit 列を BigInt と同じく big endian で保存する
+    Double,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum FieldType {
     Integer = 0,
     String = 1,
+    Boolean = 2,
+    BigInt = 3,
+    Date = 4,
+    Double = 5,
 }
 
 #[derive(Error, Debug)]
@@ -89,6 +123,24 @@ impl FieldInfo {
         match self {
             FieldInfo::Integer => FieldType::Integer,
             FieldInfo::String(_) => FieldType::String,
+            FieldInfo::Boolean => FieldType::Boolean,
+            FieldInfo::BigInt => FieldType::BigInt,
+            FieldInfo::Date => FieldType::Date,
+            FieldInfo::Double => FieldType::Double,
+        }
+    }
+}
+
+impl fmt::Display for FieldInfo {
+    /// create table で field の型を書く時と同じ形で表示する
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldInfo::Integer => write!(f, "int"),
+            FieldInfo::String(length) => write!(f, "varchar({})", length),
+            FieldInfo::Boolean => write!(f, "boolean"),
+            FieldInfo::BigInt => write!(f, "bigint"),
+            FieldInfo::Date => write!(f, "date"),
+            FieldInfo::Double => write!(f, "double"),
         }
     }
 }
@@ -98,6 +150,10 @@ impl FieldType {
         match value {
             0 => Ok(FieldType::Integer),
             1 => Ok(FieldType::String),
+            2 => Ok(FieldType::Boolean),
+            3 => Ok(FieldType::BigInt),
+            4 => Ok(FieldType::Date),
+            5 => Ok(FieldType::Double),
             _ => Err(FieldTypeError::InvalidCall(format!(
                 "invalid value: {}",
                 value
@@ -131,4 +187,20 @@ mod schema_test {
         assert_eq!(schema.info("c"), Some(FieldInfo::Integer));
         assert_eq!(schema.info("d"), Some(FieldInfo::String(20)));
     }
+
+    #[test]
+    fn test_iterate_fields() {
+        let mut schema = Schema::new();
+        assert!(schema.is_empty());
+        schema.add_field("b", FieldInfo::String(10));
+        schema.add_field("a", FieldInfo::Integer);
+
+        // 追加した順に返す
+        assert_eq!(schema.len(), 2);
+        assert_eq!(schema.fields_iter().collect::<Vec<_>>(), vec!["b", "a"]);
+        assert_eq!(
+            schema.infos().collect::<Vec<_>>(),
+            vec![("b", FieldInfo::String(10)), ("a", FieldInfo::Integer)]
+        );
+    }
 }
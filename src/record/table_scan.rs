@@ -1,8 +1,12 @@
 use crate::{
     file::blockid::BlockId,
     query::{
+        coercion,
         constant::Constant,
-        scan::{ReadScan, ReadScanError, UpdateScan, UpdateScanError},
+        date::Date,
+        predicate::Predicate,
+        row::Row,
+        scan::{ReadScan, ReadScanError, Scan, UpdateScan, UpdateScanError},
     },
     tx::{
         buffer_list::BufferListError,
@@ -20,7 +24,7 @@ use super::{
 use anyhow::{anyhow, Result as AnyhowResult};
 use thiserror::Error;
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
 
 /**
  * table の record を取得・操作するための構造体
@@ -28,6 +32,12 @@ use std::{cell::RefCell, rc::Rc};
  * move_next など、record の指し示す位置 (cursor) を移動させるメソッドを呼んだあとに get_val, insert などのメソッドを呼ぶことで table の record を操作できる
  *
  * フィールドの長さは固定長で、Unspanned (page をまたいで record を保存することがない) と仮定している
+ *
+ * move_next で読む block は、scan の作成時か最後に before_first を呼んだ時点で table にあった block だけである
+ * 走査の途中で他の scan が追加した block (format の途中のものも含む) は、次に before_first を呼ぶまで読まない
+ * この scan 自身の insert で追加した block は読む対象に含める
+ *
+ * read_only な scan は block を pin せずに、block に移動した時点の内容の写しを読む (RecordPage::new_read_only を参照)
  */
 pub struct TableScanImpl {
     // TableScanFactory に見せるために pub(crate) にしている
@@ -39,6 +49,12 @@ pub struct TableScanImpl {
     pub(crate) record_page: RecordPage,
     pub(crate) filename: String,
     pub(crate) current_slot: Option<usize>,
+    // move_next で読む block の数。走査を始めた時点の table の block の数
+    pub(crate) block_count: usize,
+    // この scan が block を移動した回数
+    pub(crate) block_accesses: u64,
+    // true の場合は block の写しを読み、record を変更しない
+    pub(crate) read_only: bool,
 }
 
 #[derive(Error, Debug)]
@@ -54,8 +70,9 @@ pub(crate) enum TableScanError {
 impl ReadScan for TableScanImpl {
     /// table scan の cursor を先頭に移動する
     fn before_first(&mut self) -> AnyhowResult<()> {
+        self.block_count = self.tx.borrow().size(&self.filename)?;
         let block = BlockId::new(&self.filename, 0);
-        self.move_to_block(&block);
+        self.move_to_block(&block)?;
         Ok(())
     }
 
@@ -63,12 +80,11 @@ impl ReadScan for TableScanImpl {
     fn move_next(&mut self) -> AnyhowResult<bool> {
         self.current_slot = self.record_page.next_after(self.current_slot)?;
         while self.current_slot.is_none() {
-            if self.is_at_last_block()? {
+            let Some(next_block_num) = self.next_block_number()? else {
                 return Ok(false);
-            }
-            let next_block_num = self.record_page.block().number() + 1;
+            };
             let block = BlockId::new(&self.filename, next_block_num);
-            self.move_to_block(&block);
+            self.move_to_block(&block)?;
             self.current_slot = self.record_page.next_after(None)?;
         }
         Ok(true)
@@ -96,12 +112,62 @@ impl ReadScan for TableScanImpl {
                 let val = self.record_page.get_string(slot, field_name)?;
                 Ok(Constant::String(val))
             }
+            Some(FieldInfo::Boolean) => {
+                let val = self.record_page.get_int(slot, field_name)?;
+                Ok(Constant::Bool(val != 0))
+            }
+            Some(FieldInfo::BigInt) => {
+                let val = self.record_page.get_bigint(slot, field_name)?;
+                Ok(Constant::BigInt(val))
+            }
+            Some(FieldInfo::Date) => {
+                let val = self.record_page.get_int(slot, field_name)?;
+                Ok(Constant::Date(Date::from_days(val)))
+            }
+            Some(FieldInfo::Double) => {
+                let val = self.record_page.get_double(slot, field_name)?;
+                Ok(Constant::Double(val))
+            }
         }
     }
 
     fn has_field(&self, field_name: &str) -> bool {
         self.layout.schema().has_field(field_name)
     }
+
+    fn compare_val(&self, field_name: &str, val: &Constant) -> AnyhowResult<Option<Ordering>> {
+        // string どうしは page の上の文字列と直接比べて、String を作らない
+        if let (Some(FieldInfo::String(_)), Constant::String(val), Some(slot)) = (
+            self.layout.schema().info(field_name),
+            val,
+            self.current_slot,
+        ) {
+            let ordering = self
+                .record_page
+                .read_str(slot, field_name, |s| s.cmp(val.as_str()))?;
+            return Ok(Some(ordering));
+        }
+        Ok(coercion::compare(&self.get_val(field_name)?, val))
+    }
+
+    fn read_into(&self, row: &mut Row) -> AnyhowResult<()> {
+        row.fill(|field_name, value| {
+            // string は page の上の文字列を row の String に直接書き込む
+            if let (Some(FieldInfo::String(_)), Some(slot)) =
+                (self.layout.schema().info(field_name), self.current_slot)
+            {
+                self.record_page
+                    .read_str(slot, field_name, |s| value.set_string(s))?;
+            } else {
+                *value = self.get_val(field_name)?;
+            }
+            Ok(())
+        })
+    }
+
+    fn block_accesses(&self) -> u64 {
+        self.block_accesses
+    }
 }
 
 impl UpdateScan for TableScanImpl {
@@ -139,6 +205,50 @@ impl UpdateScan for TableScanImpl {
                 self.record_page.set_string(slot, field_name, val)?;
                 Ok(())
             }
+            Some(FieldInfo::Boolean) => {
+                let val = match val {
+                    Constant::Bool(val) => Ok(*val),
+                    _ => Err(UpdateScanError::InvalidCall(format!(
+                        "field type mismatch (expected boolean): {}.",
+                        field_name
+                    ))),
+                }?;
+                self.record_page.set_int(slot, field_name, val as i32)?;
+                Ok(())
+            }
+            Some(FieldInfo::BigInt) => {
+                let val = match val {
+                    Constant::BigInt(val) => Ok(*val),
+                    _ => Err(UpdateScanError::InvalidCall(format!(
+                        "field type mismatch (expected bigint): {}.",
+                        field_name
+                    ))),
+                }?;
+                self.record_page.set_bigint(slot, field_name, val)?;
+                Ok(())
+            }
+            Some(FieldInfo::Date) => {
+                let val = match val {
+                    Constant::Date(val) => Ok(*val),
+                    _ => Err(UpdateScanError::InvalidCall(format!(
+                        "field type mismatch (expected date): {}.",
+                        field_name
+                    ))),
+                }?;
+                self.record_page.set_int(slot, field_name, val.days())?;
+                Ok(())
+            }
+            Some(FieldInfo::Double) => {
+                let val = match val {
+                    Constant::Double(val) => Ok(*val),
+                    _ => Err(UpdateScanError::InvalidCall(format!(
+                        "field type mismatch (expected double): {}.",
+                        field_name
+                    ))),
+                }?;
+                self.record_page.set_double(slot, field_name, val)?;
+                Ok(())
+            }
         }?)
     }
 
@@ -146,12 +256,14 @@ impl UpdateScan for TableScanImpl {
     fn insert(&mut self) -> AnyhowResult<()> {
         self.current_slot = self.record_page.insert_after(self.current_slot)?;
         while self.current_slot.is_none() {
-            if self.is_at_last_block()? {
+            // insert では他の scan が追加した block の空きも使うので、table の今の block の数を見る
+            self.block_count = self.tx.borrow().size(&self.filename)?;
+            if self.is_at_last_block() {
                 self.move_to_new_block()?;
             } else {
                 let next_block_num = self.record_page.block().number() + 1;
                 let block = BlockId::new(&self.filename, next_block_num);
-                self.move_to_block(&block);
+                self.move_to_block(&block)?;
             }
             self.current_slot = self.record_page.insert_after(None)?;
         }
@@ -173,7 +285,7 @@ impl UpdateScan for TableScanImpl {
 
     fn move_to_rid(&mut self, rid: &Rid) -> AnyhowResult<()> {
         let block = BlockId::new(&self.filename, rid.block_number());
-        self.record_page = RecordPage::new(self.tx.clone(), &block, &self.layout);
+        self.record_page = self.open_record_page(&block)?;
         self.current_slot = rid.slot();
         Ok(())
     }
@@ -187,22 +299,98 @@ impl UpdateScan for TableScanImpl {
 }
 
 impl TableScanImpl {
-    fn move_to_block(&mut self, block: &BlockId) {
-        self.record_page = RecordPage::new(self.tx.clone(), block, &self.layout);
+    /// predicate を満たす record をまとめて削除し、削除した record の数を返す
+    /// fields には predicate が参照する field を渡す。predicate の評価ではこれらの field だけを読み、
+    /// 満たした record は SelectScan を通さずに、その block の slot flag を直接書き換えて削除する
+    pub(crate) fn delete_where(
+        &mut self,
+        predicate: &dyn Predicate,
+        fields: &[String],
+    ) -> AnyhowResult<u64> {
+        let layout = self.layout.project(fields);
+        let block = BlockId::new(&self.filename, 0);
+        // reader は predicate を評価するだけなので、block を pin せずに写しを読む
+        let mut reader = Scan::Updatable(Box::new(TableScanImpl {
+            tx: self.tx.clone(),
+            record_page: RecordPage::new_read_only(self.tx.clone(), &block, &layout)?,
+            layout,
+            filename: self.filename.clone(),
+            current_slot: None,
+            block_count: self.block_count,
+            block_accesses: 0,
+            read_only: true,
+        }));
+        self.move_to_block(&block)?;
+        let mut delete_count = 0;
+        loop {
+            let Scan::Updatable(scan) = &mut reader else {
+                unreachable!("reader is always updatable");
+            };
+            if !scan.move_next()? {
+                break;
+            }
+            if !predicate.is_satisfied(&reader)? {
+                continue;
+            }
+            let Scan::Updatable(scan) = &reader else {
+                unreachable!("reader is always updatable");
+            };
+            let rid = scan.get_rid()?;
+            // 削除は reader と同じ block を pin した自分の RecordPage で行う。block が変わった時だけ pin し直す
+            if self.record_page.block().number() != rid.block_number() {
+                let block = BlockId::new(&self.filename, rid.block_number());
+                self.move_to_block(&block)?;
+            }
+            if let Some(slot) = rid.slot() {
+                self.record_page.delete(slot)?;
+                delete_count += 1;
+            }
+        }
+        Ok(delete_count)
+    }
+
+    fn move_to_block(&mut self, block: &BlockId) -> Result<(), TableScanError> {
+        self.record_page = self.open_record_page(block)?;
         self.current_slot = None;
+        self.block_accesses += 1;
+        Ok(())
+    }
+
+    fn open_record_page(&self, block: &BlockId) -> Result<RecordPage, TableScanError> {
+        if self.read_only {
+            return Ok(RecordPage::new_read_only(
+                self.tx.clone(),
+                block,
+                &self.layout,
+            )?);
+        }
+        Ok(RecordPage::new(self.tx.clone(), block, &self.layout))
     }
 
     fn move_to_new_block(&mut self) -> AnyhowResult<(), TableScanError> {
-        let block = self.tx.borrow_mut().append(&self.filename)?;
-        self.move_to_block(&block);
+        let block = self.tx.borrow().append(&self.filename)?;
+        self.move_to_block(&block)?;
         self.record_page.format()?;
+        self.block_count = block.number() + 1;
         Ok(())
     }
 
-    // table を走査していき、すでに最後の block まで到達していれば true を返す
-    fn is_at_last_block(&self) -> AnyhowResult<bool, TableScanError> {
-        let block_num = self.record_page.block().number();
-        Ok(block_num == self.tx.borrow_mut().size(&self.filename)? - 1)
+    /// move_next で次に読む block の番号を返す。file の末尾まで読み終わった場合は None を返す
+    /// 走査を始めた時点の最後の block を読み終えたら、phantom を防ぐために file の末尾の lock を取る
+    /// lock を取るまでに他の transaction が commit した block は続けて読むが、この transaction が append した block は読まない
+    fn next_block_number(&mut self) -> Result<Option<usize>, TableScanError> {
+        let next = self.record_page.block().number() + 1;
+        if next < self.block_count {
+            return Ok(Some(next));
+        }
+        let tx = self.tx.borrow();
+        let length = tx.lock_end_of_file(&self.filename)?;
+        Ok((next..length).find(|number| !tx.is_appended(&BlockId::new(&self.filename, *number))))
+    }
+
+    // 読む対象の最後の block まで到達していれば true を返す
+    fn is_at_last_block(&self) -> bool {
+        self.record_page.block().number() + 1 >= self.block_count
     }
 }
 
@@ -222,12 +410,25 @@ impl ReadScan for Box<TableScanImpl> {
     fn has_field(&self, field_name: &str) -> bool {
         self.as_ref().has_field(field_name)
     }
+
+    fn compare_val(&self, field_name: &str, val: &Constant) -> AnyhowResult<Option<Ordering>> {
+        self.as_ref().compare_val(field_name, val)
+    }
+
+    fn read_into(&self, row: &mut Row) -> AnyhowResult<()> {
+        self.as_ref().read_into(row)
+    }
+
+    fn block_accesses(&self) -> u64 {
+        self.as_ref().block_accesses()
+    }
 }
 
 #[cfg(test)]
 mod table_scan_test {
     use crate::file::file_manager::FileManager;
     use crate::log::log_manager::LogManager;
+    use crate::query::{expression::Expression, predicate::ProductPredicate, term::EqualTerm};
     use crate::record::table_scan_factory::{TableScanFactory, TableScanFactoryImpl};
     use crate::tx::concurrency::lock_table::LockTable;
     use crate::tx::transaction::TransactionFactory;
@@ -314,7 +515,284 @@ mod table_scan_test {
             }
             assert_eq!(count, 25);
         }
+        // scan を drop すると、scan が pin していた block はすべて unpin される
+        assert!(tx.borrow().pinned_blocks().is_empty());
+
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_compare_val() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let layout = setup_layout();
+        let table_scan_factory = TableScanFactoryImpl::new();
+        {
+            let mut table_scan = table_scan_factory.create(&tx, "testtbl", &layout).unwrap();
+            table_scan.insert().unwrap();
+            table_scan.set_val("A", &Constant::Int(5)).unwrap();
+            table_scan.set_val("B", &Constant::from("bob")).unwrap();
+
+            // string は page の上の値と直接比べる
+            for (val, expected) in [
+                ("bob", Ordering::Equal),
+                ("alice", Ordering::Greater),
+                ("carol", Ordering::Less),
+                ("bobby", Ordering::Less),
+            ] {
+                assert_eq!(
+                    table_scan.compare_val("B", &Constant::from(val)).unwrap(),
+                    Some(expected)
+                );
+            }
+            assert_eq!(
+                table_scan.compare_val("A", &Constant::Int(3)).unwrap(),
+                Some(Ordering::Greater)
+            );
+            // 比較できない型の値とは比べられない
+            assert_eq!(
+                table_scan.compare_val("B", &Constant::Int(3)).unwrap(),
+                None
+            );
+            assert!(table_scan.compare_val("C", &Constant::Int(3)).is_err());
+        }
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_read_into() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let layout = setup_layout();
+        let table_scan_factory = TableScanFactoryImpl::new();
+        {
+            let mut table_scan = table_scan_factory.create(&tx, "testtbl", &layout).unwrap();
+            for (a, b) in [(1, "alice"), (2, "bob"), (3, "carol")] {
+                table_scan.insert().unwrap();
+                table_scan.set_val("A", &Constant::Int(a)).unwrap();
+                table_scan.set_val("B", &Constant::from(b)).unwrap();
+            }
+
+            table_scan.before_first().unwrap();
+            let mut row = Row::new(vec!["B".to_string(), "A".to_string()]);
+            let mut rows = vec![];
+            let mut buffers = vec![];
+            while table_scan.move_next().unwrap() {
+                table_scan.read_into(&mut row).unwrap();
+                rows.push(row.values().to_vec());
+                buffers.push(row.get("B").unwrap().as_string().unwrap().as_ptr());
+            }
+            assert_eq!(
+                rows,
+                vec![
+                    vec![Constant::from("alice"), Constant::Int(1)],
+                    vec![Constant::from("bob"), Constant::Int(2)],
+                    vec![Constant::from("carol"), Constant::Int(3)],
+                ]
+            );
+            // 全ての record で、同じ String の buffer に読み込む
+            assert!(buffers.iter().all(|ptr| *ptr == buffers[0]));
+
+            let mut row = Row::new(vec!["C".to_string()]);
+            assert!(table_scan.read_into(&mut row).is_err());
+        }
+        tx.borrow_mut().commit().unwrap();
+    }
 
+    #[test]
+    fn test_scan_with_projection() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let layout = setup_layout();
+        let table_scan_factory = TableScanFactoryImpl::new();
+        {
+            let mut table_scan = table_scan_factory.create(&tx, "testtbl", &layout).unwrap();
+            table_scan.insert().unwrap();
+            table_scan.set_val("A", &Constant::Int(1)).unwrap();
+            table_scan
+                .set_val("B", &Constant::String("one".to_string()))
+                .unwrap();
+        }
+        let mut table_scan = table_scan_factory
+            .create_read_only_with_projection(&tx, "testtbl", &layout, &["A".to_string()])
+            .unwrap();
+        assert!(table_scan.move_next().unwrap());
+        assert_eq!(table_scan.get_val("A").unwrap(), Constant::Int(1));
+        // projection に含まれない field は scan から見えない
+        assert!(!table_scan.has_field("B"));
+        assert!(table_scan.get_val("B").is_err());
+        assert!(!table_scan.move_next().unwrap());
+        drop(table_scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_read_only_scan_does_not_pin_blocks() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let layout = setup_layout();
+        let table_scan_factory = TableScanFactoryImpl::new();
+        // 複数の block にまたがるように record を入れる
+        {
+            let mut table_scan = table_scan_factory.create(&tx, "testtbl", &layout).unwrap();
+            for i in 0..50 {
+                table_scan.insert().unwrap();
+                table_scan.set_val("A", &Constant::Int(i)).unwrap();
+                table_scan
+                    .set_val("B", &Constant::String(format!("test{}", i)))
+                    .unwrap();
+            }
+        }
+
+        let mut table_scan = table_scan_factory
+            .create_read_only(&tx, "testtbl", &layout)
+            .unwrap();
+        for i in 0..50 {
+            assert!(table_scan.move_next().unwrap());
+            // block の写しを読むので、走査中も block は pin されない
+            assert!(tx.borrow().pinned_blocks().is_empty());
+            assert_eq!(table_scan.get_val("A").unwrap(), Constant::Int(i));
+            assert_eq!(
+                table_scan.get_val("B").unwrap(),
+                Constant::String(format!("test{}", i))
+            );
+        }
+        assert!(!table_scan.move_next().unwrap());
+        assert!(table_scan.block_accesses() > 1);
+        drop(table_scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_delete_where() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let layout = setup_layout();
+        let table_scan_factory = TableScanFactoryImpl::new();
+        // 複数の block にまたがるように record を入れる
+        {
+            let mut table_scan = table_scan_factory.create(&tx, "testtbl", &layout).unwrap();
+            for i in 0..50 {
+                table_scan.insert().unwrap();
+                table_scan.set_val("A", &Constant::Int(i % 5)).unwrap();
+                table_scan
+                    .set_val("B", &Constant::String(format!("test{}", i)))
+                    .unwrap();
+            }
+        }
+        let predicate = ProductPredicate::new(vec![Box::new(EqualTerm::new(
+            Expression::Field("A".to_string()),
+            Expression::Constant(Constant::Int(3)),
+        ))]);
+        let mut table_scan = table_scan_factory
+            .create_internal(&tx, "testtbl", &layout)
+            .unwrap();
+        let count = table_scan
+            .delete_where(&predicate, &["A".to_string()])
+            .unwrap();
+        assert_eq!(count, 10);
+        drop(table_scan);
+
+        let mut table_scan = table_scan_factory
+            .create_read_only(&tx, "testtbl", &layout)
+            .unwrap();
+        let mut remaining = 0;
+        while table_scan.move_next().unwrap() {
+            assert_ne!(table_scan.get_int("A").unwrap(), 3);
+            remaining += 1;
+        }
+        assert_eq!(remaining, 40);
+        drop(table_scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_scan_ignores_blocks_appended_during_scan() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let layout = setup_layout();
+        let table_scan_factory = TableScanFactoryImpl::new();
+        let filename = "testtbl.tbl";
+        // 追加する record が既存の block の空きに入らないように、2 block をちょうど埋める
+        let initial = 2 * (tx.borrow().block_size() / layout.slot_size());
+        {
+            let mut table_scan = table_scan_factory.create(&tx, "testtbl", &layout).unwrap();
+            for i in 0..initial as i32 {
+                table_scan.insert().unwrap();
+                table_scan.set_val("A", &Constant::Int(i)).unwrap();
+            }
+        }
+        let block_count = tx.borrow().size(filename).unwrap();
+        assert_eq!(block_count, 2);
+
+        let mut reader = table_scan_factory
+            .create_read_only(&tx, "testtbl", &layout)
+            .unwrap();
+        let mut writer = table_scan_factory.create(&tx, "testtbl", &layout).unwrap();
+        let mut count = 0;
+        while reader.move_next().unwrap() {
+            // 走査の途中で別の scan が record を追加し、block が増えていく
+            writer.insert().unwrap();
+            writer.set_val("A", &Constant::Int(100 + count)).unwrap();
+            count += 1;
+        }
+        // 走査を始めた時点の record だけを読む
+        assert_eq!(count as usize, initial);
+        assert!(tx.borrow().size(filename).unwrap() > block_count);
+
+        // before_first をやり直すと、追加された block も読む
+        reader.before_first().unwrap();
+        let mut count = 0;
+        while reader.move_next().unwrap() {
+            count += 1;
+        }
+        assert_eq!(count, 2 * initial);
+        drop(reader);
+        drop(writer);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_nested_scan_while_tx_borrowed() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let tx = Rc::new(RefCell::new(factory.create().unwrap()));
+        let layout = setup_layout();
+        let table_scan_factory = TableScanFactoryImpl::new();
+        {
+            let mut table_scan = table_scan_factory.create(&tx, "testtbl", &layout).unwrap();
+            for i in 0..3 {
+                table_scan.insert().unwrap();
+                table_scan.set_val("A", &Constant::Int(i)).unwrap();
+            }
+        }
+        {
+            let mut outer = table_scan_factory
+                .create_read_only(&tx, "testtbl", &layout)
+                .unwrap();
+            // 外側で transaction を借用したまま、同じ transaction で別の scan を読んでも panic しない
+            let guard = tx.borrow();
+            let mut count = 0;
+            while outer.move_next().unwrap() {
+                let a = outer.get_int("A").unwrap();
+                let mut inner = table_scan_factory
+                    .create_read_only(&tx, "testtbl", &layout)
+                    .unwrap();
+                while inner.move_next().unwrap() {
+                    if inner.get_int("A").unwrap() == a {
+                        count += 1;
+                    }
+                }
+            }
+            assert_eq!(count, 3);
+            drop(guard);
+        }
         tx.borrow_mut().commit().unwrap();
     }
 }
@@ -1,18 +1,22 @@
 use crate::file::blockid::BlockId;
+use crate::file::file_manager::FileManagerError;
 use crate::query::scan::{ReadScan, UpdateScan};
 use crate::tx::transaction::{Transaction, TransactionSizeError};
 
 use super::layout::Layout;
+use super::partition::PartitionSpec;
+use super::partitioned_scan::{PartitionedScan, PartitionedUpdateScan};
 
 use super::record_page::{RecordPage, RecordPageError};
 use super::table_scan::TableScanImpl;
+#[cfg(test)]
 use mockall::automock;
 use std::{cell::RefCell, rc::Rc};
 use thiserror::Error;
 
 /// table scan を作成するための factory
 /// application 中に何個あっても問題ない
-#[automock]
+#[cfg_attr(test, automock)]
 pub trait TableScanFactory {
     /// table scan を作成する
     fn create(
@@ -29,6 +33,15 @@ pub trait TableScanFactory {
         tblname: &str,
         layout: &Layout,
     ) -> Result<Box<dyn ReadScan>, TableScanFactoryError>;
+    /// fields に含まれる field だけを読む read-only な table scan を作成する
+    /// それ以外の field は scan からは見えず、値の decode も行われない
+    fn create_read_only_with_projection(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+        tblname: &str,
+        layout: &Layout,
+        fields: &[String],
+    ) -> Result<Box<dyn ReadScan>, TableScanFactoryError>;
 }
 
 pub struct TableScanFactoryImpl;
@@ -39,6 +52,10 @@ pub(crate) enum TableScanFactoryError {
     TransactionSize(#[from] TransactionSizeError),
     #[error("record page error: {0}")]
     RecordPage(#[from] RecordPageError),
+    #[error("file manager error: {0}")]
+    FileManager(#[from] FileManagerError),
+    #[error("partitioned scan error: {0}")]
+    Partition(anyhow::Error),
 }
 
 impl TableScanFactoryImpl {
@@ -54,6 +71,11 @@ impl TableScanFactory for TableScanFactoryImpl {
         tblname: &str,
         layout: &Layout,
     ) -> Result<Box<dyn UpdateScan>, TableScanFactoryError> {
+        if let Some(spec) = layout.partition() {
+            let scan = PartitionedUpdateScan::new(tx, tblname, layout, spec.clone())
+                .map_err(TableScanFactoryError::Partition)?;
+            return Ok(Box::new(scan));
+        }
         Ok(Box::new(self.create_internal(tx, tblname, layout)?))
     }
     fn create_read_only(
@@ -62,27 +84,95 @@ impl TableScanFactory for TableScanFactoryImpl {
         tblname: &str,
         layout: &Layout,
     ) -> Result<Box<dyn ReadScan>, TableScanFactoryError> {
-        Ok(Box::new(self.create_internal(tx, tblname, layout)?))
+        if layout.partition().is_some() {
+            return self.create_partitioned_read_only(tx, tblname, layout);
+        }
+        Ok(Box::new(self.open(tx, tblname, layout, true)?))
+    }
+    fn create_read_only_with_projection(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+        tblname: &str,
+        layout: &Layout,
+        fields: &[String],
+    ) -> Result<Box<dyn ReadScan>, TableScanFactoryError> {
+        if layout.partition().is_some() {
+            return self.create_partitioned_read_only(tx, tblname, &layout.project(fields));
+        }
+        Ok(Box::new(self.open(
+            tx,
+            tblname,
+            &layout.project(fields),
+            true,
+        )?))
     }
 }
 
 impl TableScanFactoryImpl {
+    /// partition に分けた table の、layout で選ばれている partition を順に読む scan を作成する
+    fn create_partitioned_read_only(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+        tblname: &str,
+        layout: &Layout,
+    ) -> Result<Box<dyn ReadScan>, TableScanFactoryError> {
+        let partition_layout = layout.without_partition();
+        let mut scans: Vec<Box<dyn ReadScan>> = vec![];
+        for partition in layout
+            .partition()
+            .map(|spec| spec.selected_partitions())
+            .unwrap_or_default()
+        {
+            let partition_name = PartitionSpec::partition_table_name(tblname, partition);
+            scans.push(Box::new(self.open(
+                tx,
+                &partition_name,
+                &partition_layout,
+                true,
+            )?));
+        }
+        Ok(Box::new(PartitionedScan::new(partition_layout, scans)))
+    }
+
     /// TableScanImpl を作成する
-    fn create_internal(
+    /// TableScanImpl にしかない操作 (delete_where など) を使う場合は、trait を通さずにこちらを使う
+    pub(crate) fn create_internal(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+        tblname: &str,
+        layout: &Layout,
+    ) -> Result<TableScanImpl, TableScanFactoryError> {
+        self.open(tx, tblname, layout, false)
+    }
+
+    // read_only な場合は、block を pin せずに写しを読む TableScanImpl を作成する
+    fn open(
         &self,
         tx: &Rc<RefCell<Transaction>>,
         tblname: &str,
         layout: &Layout,
+        read_only: bool,
     ) -> Result<TableScanImpl, TableScanFactoryError> {
         let filename = format!("{}.tbl", tblname);
-        let record_page = if tx.borrow_mut().size(&filename)? == 0 {
-            let block = tx.borrow_mut().append(&filename)?;
-            let record_page = RecordPage::new(tx.clone(), &block, layout);
-            record_page.format()?;
-            record_page
+        // block size や圧縮が table ごとに指定されている場合は、ファイルを読み書きする前に FileManager に登録する
+        if let Some(block_size) = layout.block_size() {
+            tx.borrow().set_block_size(&filename, block_size)?;
+        }
+        if layout.storage().compressed {
+            tx.borrow().set_compressed(&filename)?;
+        }
+        let mut block_count = tx.borrow().size(&filename)?;
+        let block = if block_count == 0 {
+            let block = tx.borrow().append(&filename)?;
+            RecordPage::new(tx.clone(), &block, layout).format()?;
+            block_count = 1;
+            block
+        } else {
+            BlockId::new(&filename, 0)
+        };
+        let record_page = if read_only {
+            RecordPage::new_read_only(tx.clone(), &block, layout)?
         } else {
-            let block = BlockId::new(&filename, 0);
-
             RecordPage::new(tx.clone(), &block, layout)
         };
         Ok(TableScanImpl {
@@ -91,6 +181,9 @@ impl TableScanFactoryImpl {
             record_page,
             filename,
             current_slot: None,
+            block_count,
+            block_accesses: 0,
+            read_only,
         })
     }
 }
@@ -1 +1,3 @@
+pub mod control_file;
+pub mod kv_table;
 pub mod simpledb;
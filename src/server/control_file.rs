@@ -0,0 +1,287 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+/**
+ * database 全体に関わる設定と状態を保存する control file の内容
+ *
+ * block size のように、作成した時の値で読まなければならない設定を disk 上に残しておくために使う
+ * 起動時に読み込み、起動時と終了時に書き込む
+ * ファイルは db directory 内に `key=value` の形式で 1 行 1 項目ずつ保存する (暗号化はされない)
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlFile {
+    /// control file と、それが表す database のファイルの形式の version
+    pub format_version: u32,
+    /// database を作成した時の block size
+    pub block_size: usize,
+    /// 最後に書き込まれた checkpoint record の LSN
+    /// checkpoint を書き込んだことがない場合は 0
+    /// 起動時に LogManager に設定し、recovery はこの LSN より前の log record を読まない
+    pub checkpoint_lsn: u64,
+    /// 次に作成する transaction の番号
+    pub next_txnum: u32,
+}
+
+#[derive(Error, Debug)]
+pub enum ControlFileError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid control file : {0}")]
+    Invalid(String),
+    #[error("unsupported format version {0}. expected {expected}", expected = ControlFile::FORMAT_VERSION)]
+    UnsupportedVersion(u32),
+    #[error("block size mismatch: the database was created with block size {stored}, but is opened with {requested}")]
+    BlockSizeMismatch { stored: usize, requested: usize },
+    #[error("the database has no control file and its files do not match block size {0}")]
+    UnknownBlockSize(usize),
+}
+
+impl ControlFile {
+    /// この実装が読み書きする format の version
+    pub const FORMAT_VERSION: u32 = 1;
+
+    /// 新しく作成する database の control file の内容
+    pub fn new(block_size: usize) -> Self {
+        Self {
+            format_version: Self::FORMAT_VERSION,
+            block_size,
+            checkpoint_lsn: 0,
+            next_txnum: 1,
+        }
+    }
+
+    /// path に保存された control file を読み込む。ファイルが存在しない場合は None を返す
+    /// この実装が扱えない version の場合は error を返す
+    pub fn load(path: &Path) -> Result<Option<Self>, ControlFileError> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut format_version = None;
+        let mut block_size = None;
+        let mut checkpoint_lsn = None;
+        let mut next_txnum = None;
+        for line in content.lines().filter(|line| !line.is_empty()) {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| ControlFileError::Invalid(format!("malformed line: {}", line)))?;
+            match key {
+                "format_version" => format_version = Some(parse_value(key, value)?),
+                "block_size" => block_size = Some(parse_value(key, value)?),
+                "checkpoint_lsn" => checkpoint_lsn = Some(parse_value(key, value)?),
+                "next_txnum" => next_txnum = Some(parse_value(key, value)?),
+                _ => {
+                    return Err(ControlFileError::Invalid(format!("unknown key: {}", key)));
+                }
+            }
+        }
+        // version が違う場合は他の項目の意味も違いうるので、先に確かめる
+        let format_version = required("format_version", format_version)?;
+        if format_version != Self::FORMAT_VERSION {
+            return Err(ControlFileError::UnsupportedVersion(format_version));
+        }
+        Ok(Some(Self {
+            format_version,
+            block_size: required("block_size", block_size)?,
+            checkpoint_lsn: required("checkpoint_lsn", checkpoint_lsn)?,
+            next_txnum: required("next_txnum", next_txnum)?,
+        }))
+    }
+
+    /// block_size で database を開いてよいかどうかを確かめる
+    /// 作成した時と異なる block size で読むと block の境界がずれてデータを正しく読めないので、error を返す
+    pub fn check_block_size(&self, block_size: usize) -> Result<(), ControlFileError> {
+        if self.block_size != block_size {
+            return Err(ControlFileError::BlockSizeMismatch {
+                stored: self.block_size,
+                requested: block_size,
+            });
+        }
+        Ok(())
+    }
+
+    /// control file を持たない既存の database を block_size で開いてよいかどうかを、ファイルの中身から確かめる
+    ///
+    /// log_file と data_files の長さが 1 block がファイル上で占めるバイト数 (stride) の倍数であることを確かめる
+    /// 暗号化されていない場合は、さらに log の各 block で、先頭にある境界の位置から並ぶ log record がちょうど block の末尾で終わることを確かめる
+    /// (extent として確保しただけの 0 埋めの block は読み飛ばす)
+    /// 作成時の block size はわからないので、一致しない場合は UnknownBlockSize を返す
+    pub fn probe_block_size(
+        db_directory: &Path,
+        log_file: &str,
+        data_files: &[String],
+        block_size: usize,
+        stride: usize,
+    ) -> Result<(), ControlFileError> {
+        let mismatch = || ControlFileError::UnknownBlockSize(block_size);
+        for filename in data_files.iter().map(String::as_str).chain([log_file]) {
+            match fs::metadata(db_directory.join(filename)) {
+                Ok(metadata) if metadata.len() % stride as u64 != 0 => return Err(mismatch()),
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        // 暗号化されている場合は block の中身を読めない
+        if stride != block_size {
+            return Ok(());
+        }
+        let log = match fs::read(db_directory.join(log_file)) {
+            Ok(log) => log,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let read_int = |block: &[u8], pos: usize| {
+            block
+                .get(pos..pos + 4)
+                .map(|bytes| i32::from_be_bytes(bytes.try_into().unwrap()))
+        };
+        for block in log.chunks(block_size) {
+            if block.iter().all(|&b| b == 0) {
+                continue;
+            }
+            let mut pos = read_int(block, 0).ok_or_else(mismatch)?;
+            if pos < 4 {
+                return Err(mismatch());
+            }
+            // log record は [長さ][中身] の形で、境界の位置から block の末尾まで隙間なく並んでいる
+            // 中身が空の log record は無いので、長さが 0 の場合も block size が合っていない
+            while (pos as usize) < block_size {
+                let len = read_int(block, pos as usize).ok_or_else(mismatch)?;
+                if len <= 0 {
+                    return Err(mismatch());
+                }
+                pos = pos.checked_add(4 + len).ok_or_else(mismatch)?;
+            }
+            if pos as usize != block_size {
+                return Err(mismatch());
+            }
+        }
+        Ok(())
+    }
+
+    /// path に保存する
+    /// 書き込みの途中で crash しても以前の内容が残るように、一時ファイルに書いてから置き換える
+    pub fn save(&self, path: &Path) -> Result<(), ControlFileError> {
+        let content = format!(
+            "format_version={}\nblock_size={}\ncheckpoint_lsn={}\nnext_txnum={}\n",
+            self.format_version, self.block_size, self.checkpoint_lsn, self.next_txnum
+        );
+        let tmp_path = Self::tmp_path(path);
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    fn tmp_path(path: &Path) -> PathBuf {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        PathBuf::from(tmp_path)
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, ControlFileError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| ControlFileError::Invalid(format!("invalid value of {}: {}", key, value)))
+}
+
+fn required<T>(key: &str, value: Option<T>) -> Result<T, ControlFileError> {
+    value.ok_or_else(|| ControlFileError::Invalid(format!("{} is missing", key)))
+}
+
+#[cfg(test)]
+mod control_file_test {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("db.control");
+        assert_eq!(ControlFile::load(&path).unwrap(), None);
+
+        let control_file = ControlFile {
+            checkpoint_lsn: 12,
+            next_txnum: 34,
+            ..ControlFile::new(400)
+        };
+        control_file.save(&path).unwrap();
+        assert_eq!(ControlFile::load(&path).unwrap(), Some(control_file));
+        // 一時ファイルは残らない
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_check_block_size() {
+        let control_file = ControlFile::new(400);
+        assert!(control_file.check_block_size(400).is_ok());
+        assert!(matches!(
+            control_file.check_block_size(800),
+            Err(ControlFileError::BlockSizeMismatch {
+                stored: 400,
+                requested: 800
+            })
+        ));
+    }
+
+    #[test]
+    fn test_probe_block_size() {
+        let dir = tempdir().unwrap();
+        // 末尾に長さ 4 の log record が 1 つある log block を 2 つと、extent として確保しただけの block を書き込む
+        let mut log = vec![0u8; 1200];
+        for start in [0, 400] {
+            log[start..start + 4].copy_from_slice(&392i32.to_be_bytes());
+            log[start + 392..start + 396].copy_from_slice(&4i32.to_be_bytes());
+        }
+        fs::write(dir.path().join("db.log"), log).unwrap();
+        fs::write(dir.path().join("tblcat.tbl"), vec![0u8; 800]).unwrap();
+        let data_files = vec!["tblcat.tbl".to_string(), "fldcat.tbl".to_string()];
+
+        assert!(ControlFile::probe_block_size(dir.path(), "db.log", &data_files, 400, 400).is_ok());
+        // ファイルの長さは 200 の倍数だが、log record が 200 byte の block の末尾で終わらない
+        assert!(matches!(
+            ControlFile::probe_block_size(dir.path(), "db.log", &data_files, 200, 200),
+            Err(ControlFileError::UnknownBlockSize(200))
+        ));
+        // ファイルの長さが block size の倍数でない
+        assert!(matches!(
+            ControlFile::probe_block_size(dir.path(), "db.log", &data_files, 300, 300),
+            Err(ControlFileError::UnknownBlockSize(300))
+        ));
+    }
+
+    #[test]
+    fn test_load_invalid_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("db.control");
+
+        fs::write(&path, "format_version=2\nblock_size=400\n").unwrap();
+        assert!(matches!(
+            ControlFile::load(&path),
+            Err(ControlFileError::UnsupportedVersion(2))
+        ));
+
+        fs::write(&path, "format_version=1\nblock_size=400\n").unwrap();
+        assert!(matches!(
+            ControlFile::load(&path),
+            Err(ControlFileError::Invalid(_))
+        ));
+
+        fs::write(
+            &path,
+            "format_version=1\nblock_size=abc\ncheckpoint_lsn=0\nnext_txnum=1\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            ControlFile::load(&path),
+            Err(ControlFileError::Invalid(_))
+        ));
+    }
+}
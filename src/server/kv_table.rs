@@ -0,0 +1,505 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ops::{Bound, RangeBounds},
+    rc::Rc,
+};
+
+use anyhow::{anyhow, Result as AnyhowResult};
+use thiserror::Error;
+
+use crate::{
+    error::SimpleDbResult,
+    exec::executor::{Executor, ExecutorError},
+    index::common::Index,
+    parse::{
+        content::{delete_data::DeleteData, insert_data::InsertData, update_data::UpdateData},
+        parser::UpdateCommand,
+    },
+    plan::{
+        expression::Expression,
+        predicate::ProductPredicate,
+        term::{EqualTerm, Term},
+    },
+    planner::row_policy::RowPolicy,
+    query::{
+        constant::Constant,
+        scan::{ReadScan, UpdateScan},
+    },
+    record::{
+        layout::Layout,
+        schema::FieldInfo,
+        table_scan_factory::{TableScanFactory, TableScanFactoryImpl},
+    },
+    tx::transaction::Transaction,
+};
+
+/// KvTable で読み書きする 1 record 分の値 (field 名 -> 値)
+pub type KvRow = HashMap<String, Constant>;
+
+/**
+ * SQL の解析や plan の作成を通さずに、table を key-value store のように読み書きするための API
+ *
+ * table の最初の field を key として扱う
+ * 読み書きは通常の table scan と同じく transaction の中で行われるので、SQL からの読み書きと混在させてもよい
+ * key の field に index が張られている場合は index で record を探し、ない場合は table の先頭からの scan で探す
+ * put / delete は insert, update, delete 文と同じ処理で実行するので、default 値や index、row policy も SQL と同様に扱われる
+ */
+pub struct KvTable<'a> {
+    // 利用者が指定した table 名。row policy や insert などの文にはこの名前を使う
+    table_name: String,
+    // catalog 上の実体の table 名
+    physical_name: String,
+    layout: Layout,
+    key_field: String,
+    executor: &'a Executor,
+    row_policy: RowPolicy,
+}
+
+#[derive(Error, Debug)]
+pub enum KvTableError {
+    #[error("[kv table] invalid call : {0}")]
+    InvalidCall(String),
+    #[error("[kv table] table {0} is read-only")]
+    ReadOnlyTable(String),
+}
+
+impl<'a> KvTable<'a> {
+    /// row_policy で読み書きできる record に制限した KvTable を開く
+    pub(crate) fn open(
+        table_name: &str,
+        executor: &'a Executor,
+        row_policy: RowPolicy,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<KvTable<'a>> {
+        let metadata_manager = executor.metadata_manager();
+        // external table は読み込み専用なので、put / delete ができない
+        if metadata_manager
+            .get_external_table_path(table_name, tx)?
+            .is_some()
+        {
+            return Err(anyhow!(KvTableError::ReadOnlyTable(table_name.to_string())));
+        }
+        let physical_name = metadata_manager.resolve_table_name(table_name, tx);
+        let layout = metadata_manager.get_layout(&physical_name, tx)?;
+        let key_field = layout
+            .schema()
+            .fields_iter()
+            .next()
+            .map(str::to_string)
+            .ok_or_else(|| {
+                anyhow!(KvTableError::InvalidCall(format!(
+                    "table {} has no field",
+                    table_name
+                )))
+            })?;
+        Ok(KvTable {
+            table_name: table_name.to_string(),
+            physical_name,
+            layout,
+            key_field,
+            executor,
+            row_policy,
+        })
+    }
+
+    /// key として扱う field の名前
+    pub fn key_field(&self) -> &str {
+        &self.key_field
+    }
+
+    /// key を持つ record を返す。存在しない場合や、row policy で読めない場合は None を返す
+    pub fn get(
+        &self,
+        key: &Constant,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<Option<KvRow>> {
+        Ok(self.get_internal(key, tx)?)
+    }
+
+    /// row の key を持つ record があれば row の値で更新し、なければ新しく挿入する
+    /// row に含まれない field は、更新の場合は元の値のまま、挿入の場合は default 値 (なければ 0 や空文字列) になる
+    /// 挿入した場合は true を返す
+    pub fn put(&self, row: &KvRow, tx: &Rc<RefCell<Transaction>>) -> SimpleDbResult<bool> {
+        Ok(self.put_internal(row, tx)?)
+    }
+
+    /// key を持つ record を削除する。削除した場合は true を返す
+    pub fn delete(&self, key: &Constant, tx: &Rc<RefCell<Transaction>>) -> SimpleDbResult<bool> {
+        Ok(self.delete_internal(key, tx)?)
+    }
+
+    /// key が range に含まれ、row policy で読める record を key の昇順で返す
+    pub fn scan<R: RangeBounds<Constant>>(
+        &self,
+        range: R,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<Vec<KvRow>> {
+        Ok(self.scan_internal(range, tx)?)
+    }
+
+    fn get_internal(
+        &self,
+        key: &Constant,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Option<KvRow>> {
+        self.check_key(key)?;
+        let Some(scan) = self.find(key, true, tx)? else {
+            return Ok(None);
+        };
+        Ok(Some(self.read_row(scan.as_ref())?))
+    }
+
+    fn put_internal(&self, row: &KvRow, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<bool> {
+        let key = row.get(&self.key_field).ok_or_else(|| {
+            anyhow!(KvTableError::InvalidCall(format!(
+                "key field {} is not specified",
+                self.key_field
+            )))
+        })?;
+        self.check_key(key)?;
+        if let Some(field) = row
+            .keys()
+            .find(|field| !self.layout.schema().has_field(field))
+        {
+            return Err(anyhow!(KvTableError::InvalidCall(format!(
+                "field {} not found in table {}",
+                field, self.table_name
+            ))));
+        }
+        // field ごとの update の途中で error が起きた場合に、それまでの更新も元に戻せるよう savepoint を作っておく
+        let savepoint = tx.borrow().savepoint()?;
+        self.write_row(row, key, tx).or_else(|err| {
+            tx.borrow().rollback_to_savepoint(&savepoint)?;
+            Err(err)
+        })
+    }
+
+    /// put_internal の本体。error を返した場合、一部の field だけが更新されたままになっていることがある
+    fn write_row(
+        &self,
+        row: &KvRow,
+        key: &Constant,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<bool> {
+        // row policy で読めない record も含めて探す。読めない record と同じ key の record を挿入しないようにするため
+        if self.find(key, false, tx)?.is_none() {
+            let (fields, values) = row
+                .iter()
+                .map(|(field, val)| (field.clone(), val.clone()))
+                .unzip();
+            let data = InsertData::new(self.table_name.clone(), fields, values);
+            self.executor
+                .exec_update_data(UpdateCommand::Insert(data), &self.row_policy, tx)?;
+            return Ok(true);
+        }
+        for (field, val) in row {
+            if *field == self.key_field {
+                continue;
+            }
+            let data = UpdateData::new(
+                self.table_name.clone(),
+                field.clone(),
+                Expression::Constant(val.clone()),
+                self.key_predicate(key),
+            );
+            let count = self.executor.exec_update_data(
+                UpdateCommand::Update(data),
+                &self.row_policy,
+                tx,
+            )?;
+            // record はあるが、row policy で読めないために更新されなかった
+            if count == 0 {
+                return Err(anyhow!(ExecutorError::RowPolicyViolation(
+                    self.table_name.clone()
+                )));
+            }
+        }
+        Ok(false)
+    }
+
+    fn delete_internal(&self, key: &Constant, tx: &Rc<RefCell<Transaction>>) -> AnyhowResult<bool> {
+        self.check_key(key)?;
+        let data = DeleteData::new(self.table_name.clone(), self.key_predicate(key));
+        let count =
+            self.executor
+                .exec_update_data(UpdateCommand::Delete(data), &self.row_policy, tx)?;
+        Ok(count > 0)
+    }
+
+    fn scan_internal<R: RangeBounds<Constant>>(
+        &self,
+        range: R,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Vec<KvRow>> {
+        for bound in [range.start_bound(), range.end_bound()] {
+            if let Bound::Included(key) | Bound::Excluded(key) = bound {
+                self.check_key(key)?;
+            }
+        }
+        let mut scan =
+            TableScanFactoryImpl::new().create_read_only(tx, &self.physical_name, &self.layout)?;
+        scan.before_first()?;
+        let mut rows = Vec::new();
+        while scan.move_next()? {
+            if range.contains(&scan.get_val(&self.key_field)?) && self.is_visible(scan.as_ref())? {
+                rows.push(self.read_row(scan.as_ref())?);
+            }
+        }
+        // key の型は check_key で揃えているので、比較できない組み合わせは出てこない
+        rows.sort_by(|a, b| a[&self.key_field].partial_cmp(&b[&self.key_field]).unwrap());
+        Ok(rows)
+    }
+
+    /// key を持つ record まで cursor を進めた scan を返す。見つからなかった場合は None を返す
+    /// restricted が true の場合は、row policy で読めない record は見つからなかったものとして扱う
+    fn find(
+        &self,
+        key: &Constant,
+        restricted: bool,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Option<Box<dyn UpdateScan>>> {
+        let mut scan = TableScanFactoryImpl::new().create(tx, &self.physical_name, &self.layout)?;
+        let found = match self.open_key_index(tx)? {
+            Some(mut index) => {
+                index.before_first(key)?;
+                let mut found = false;
+                while !found && index.next()? {
+                    scan.move_to_rid(&index.get_data_rid()?)?;
+                    found = !restricted || self.is_visible(scan.as_ref())?;
+                }
+                found
+            }
+            None => {
+                scan.before_first()?;
+                let mut found = false;
+                while !found && scan.move_next()? {
+                    found = scan.get_val(&self.key_field)? == *key
+                        && (!restricted || self.is_visible(scan.as_ref())?);
+                }
+                found
+            }
+        };
+        Ok(found.then_some(scan))
+    }
+
+    /// key の field に張られた index があれば開く
+    fn open_key_index(
+        &self,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> AnyhowResult<Option<Box<dyn Index>>> {
+        let index_info = self
+            .executor
+            .metadata_manager()
+            .get_index_info(&self.physical_name, tx)?
+            .into_iter()
+            .find(|index_info| index_info.field_name() == self.key_field);
+        Ok(match index_info {
+            Some(index_info) => Some(index_info.open(tx, self.layout.schema())?),
+            None => None,
+        })
+    }
+
+    /// scan が指している record を、row policy で読めるかどうか
+    fn is_visible(&self, scan: &dyn ReadScan) -> AnyhowResult<bool> {
+        match self.row_policy.predicate(&self.table_name) {
+            Some(predicate) => predicate.convert_for_scan().is_satisfied_by(scan),
+            None => Ok(true),
+        }
+    }
+
+    /// key の field が key と等しいことを表す predicate
+    fn key_predicate(&self, key: &Constant) -> ProductPredicate {
+        ProductPredicate::new(vec![Term::Equal(EqualTerm::new(
+            Expression::Field(self.key_field.clone()),
+            Expression::Constant(key.clone()),
+        ))])
+    }
+
+    fn read_row<S: ReadScan + ?Sized>(&self, scan: &S) -> AnyhowResult<KvRow> {
+        let mut row = KvRow::new();
+        for field in self.layout.schema().fields_iter() {
+            let val = scan.get_val(field)?;
+            row.insert(field.to_string(), val);
+        }
+        Ok(row)
+    }
+
+    fn check_key(&self, key: &Constant) -> Result<(), KvTableError> {
+        match (self.layout.schema().info(&self.key_field), key) {
+            (Some(FieldInfo::Integer), Constant::Int(_))
+            | (Some(FieldInfo::String(_)), Constant::String(_)) => Ok(()),
+            _ => Err(KvTableError::InvalidCall(format!(
+                "key {} does not match the type of key field {}",
+                key, self.key_field
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod kv_table_test {
+    use tempfile::tempdir;
+
+    use super::KvRow;
+    use crate::{error::ErrorCategory, query::constant::Constant, server::simpledb::SimpleDB};
+
+    fn row(sid: i32, sname: &str) -> KvRow {
+        KvRow::from([
+            ("sid".to_string(), Constant::Int(sid)),
+            ("sname".to_string(), Constant::String(sname.to_string())),
+        ])
+    }
+
+    #[test]
+    fn test_put_get_delete_scan() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        let tx = db.new_tx().unwrap();
+        db.executor()
+            .exec_update_command("create table student (sid int, sname varchar(10))", &tx)
+            .unwrap();
+
+        let table = db.table("student", &tx).unwrap();
+        assert_eq!(table.key_field(), "sid");
+        for (sid, sname) in [(3, "max"), (1, "joe"), (2, "amy"), (4, "sue")] {
+            assert!(table.put(&row(sid, sname), &tx).unwrap());
+        }
+        // 同じ key の put は更新になる
+        assert!(!table.put(&row(2, "bob"), &tx).unwrap());
+        assert_eq!(
+            table.get(&Constant::Int(2), &tx).unwrap(),
+            Some(row(2, "bob"))
+        );
+        assert_eq!(table.get(&Constant::Int(5), &tx).unwrap(), None);
+
+        assert!(table.delete(&Constant::Int(3), &tx).unwrap());
+        assert!(!table.delete(&Constant::Int(3), &tx).unwrap());
+
+        let rows = table
+            .scan(Constant::Int(2)..=Constant::Int(4), &tx)
+            .unwrap();
+        assert_eq!(rows, vec![row(2, "bob"), row(4, "sue")]);
+        assert_eq!(table.scan(.., &tx).unwrap().len(), 3);
+
+        // KvTable で書き込んだ record は SQL からも見える
+        let mut scan = db
+            .executor()
+            .exec_query("select sname from student where sid = 1", &tx)
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("sname").unwrap(), "joe");
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_index_and_defaults() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        let tx = db.new_tx().unwrap();
+        db.executor()
+            .exec_update_command(
+                "create table student (sid int, sname varchar(10), majorid int default 20)",
+                &tx,
+            )
+            .unwrap();
+        db.executor()
+            .exec_update_command("create unique index sid_idx on student (sid)", &tx)
+            .unwrap();
+
+        let table = db.table("student", &tx).unwrap();
+        for (sid, sname) in [(1, "joe"), (2, "amy")] {
+            assert!(table.put(&row(sid, sname), &tx).unwrap());
+        }
+        assert!(!table.put(&row(2, "bob"), &tx).unwrap());
+        // row に含まれない field には default 値が入る
+        assert_eq!(
+            table.get(&Constant::Int(2), &tx).unwrap().unwrap()["majorid"],
+            Constant::Int(20)
+        );
+
+        // put で書き込んだ record は index にも登録されているので、index を使う select でも見える
+        let mut scan = db
+            .executor()
+            .exec_query("select sname from student where sid = 2", &tx)
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("sname").unwrap(), "bob");
+        assert!(!scan.move_next().unwrap());
+        drop(scan);
+
+        assert!(table.delete(&Constant::Int(1), &tx).unwrap());
+        assert_eq!(table.get(&Constant::Int(1), &tx).unwrap(), None);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_row_policy() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        let tx = db.new_tx().unwrap();
+        db.executor()
+            .exec_update_command(
+                "create table student (sid int, sname varchar(10), majorid int)",
+                &tx,
+            )
+            .unwrap();
+        db.executor()
+            .exec_update_command(
+                "insert into student (sid, sname, majorid) values (1, 'joe', 20)",
+                &tx,
+            )
+            .unwrap();
+
+        let mut session = db.new_session();
+        session.set_row_policy("student", "majorid = 10").unwrap();
+        let table = session.table("student", &tx).unwrap();
+        let mut amy = row(2, "amy");
+        amy.insert("majorid".to_string(), Constant::Int(10));
+        assert!(table.put(&amy, &tx).unwrap());
+        // row policy で読めない record は見えず、更新も削除もできない
+        assert_eq!(table.get(&Constant::Int(1), &tx).unwrap(), None);
+        assert_eq!(table.scan(.., &tx).unwrap(), vec![amy]);
+        let err = table.put(&row(1, "bob"), &tx).unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Constraint);
+        assert!(!table.delete(&Constant::Int(1), &tx).unwrap());
+        // row policy を満たさない record は挿入できない
+        let mut sue = row(3, "sue");
+        sue.insert("majorid".to_string(), Constant::Int(20));
+        assert!(table.put(&sue, &tx).is_err());
+
+        assert_eq!(
+            db.table("student", &tx)
+                .unwrap()
+                .get(&Constant::Int(1), &tx)
+                .unwrap(),
+            Some(KvRow::from([
+                ("sid".to_string(), Constant::Int(1)),
+                ("sname".to_string(), Constant::String("joe".to_string())),
+                ("majorid".to_string(), Constant::Int(20)),
+            ]))
+        );
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        let tx = db.new_tx().unwrap();
+        db.executor()
+            .exec_update_command("create table student (sid int, sname varchar(10))", &tx)
+            .unwrap();
+
+        let table = db.table("student", &tx).unwrap();
+        let err = table
+            .get(&Constant::String("1".to_string()), &tx)
+            .unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Plan);
+        let only_name = KvRow::from([("sname".to_string(), Constant::String("joe".to_string()))]);
+        assert!(table.put(&only_name, &tx).is_err());
+        assert!(db.table("unknown", &tx).is_err());
+        tx.borrow_mut().commit().unwrap();
+    }
+}
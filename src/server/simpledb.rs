@@ -1,87 +1,320 @@
 use std::cell::RefCell;
 use std::sync::Arc;
-use std::{path::Path, rc::Rc};
+use std::time::Duration;
+use std::{
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
 use anyhow::Result as AnyhowResult;
 
+use super::{control_file::ControlFile, kv_table::KvTable};
 use crate::{
-    buffer::buffer_manager::BufferManager,
-    exec::executor::Executor,
-    file::file_manager::FileManager,
+    buffer::buffer_manager::{BufferManager, BufferPoolKind, BufferPoolSizes},
+    error::SimpleDbResult,
+    exec::{
+        executor::{Executor, IndexBuildProgressCallback},
+        session::Session,
+    },
+    file::{
+        blockid::BlockId,
+        encryption::{ENCRYPTION_KEY_LEN, ENCRYPTION_OVERHEAD},
+        file_manager::FileManager,
+    },
+    index::btree_index::BTreeIndex,
     log::log_manager::LogManager,
     metadata::{
+        access_list::AccessList,
+        constants::{CATALOG_TABLE_NAMES, SYSTEM_INDEX_NAMES, TBLCAT_TABLE_NAME},
         metadata_manager::{MetadataManager, MetadataManagerImpl},
-        table_manager::TableManagerImpl,
+        table_manager::{TableManager, TableManagerImpl},
     },
     parse::parser_factory::ParserFactory,
-    planner::basic_query_planner::BasicQueryPalanner,
-    record::table_scan_factory::TableScanFactoryImpl,
+    planner::{basic_query_planner::BasicQueryPalanner, row_policy::RowPolicy},
+    record::{partition::PartitionSpec, table_scan_factory::TableScanFactoryImpl},
     tx::{
         concurrency::lock_table::LockTable,
+        temp_file_manager::TempFileManager,
         transaction::{Transaction, TransactionFactory},
     },
 };
 
+/// SimpleDB を起動する時の設定
+#[derive(Clone)]
+pub struct SimpleDBConfig {
+    pub block_size: usize,
+    /// buffer pool の buffer の数 (catalog / index 用の buffer を除く)
+    pub buffer_size: usize,
+    /// catalog table と、その system index 専用の buffer の数。0 の場合は catalog table も buffer_size の buffer を使う
+    /// planning 中の catalog の読み込みが、実行中の scan が pin している buffer の解放を待って止まらないように、既定では専用の buffer を確保する
+    pub catalog_buffer_size: usize,
+    /// index 専用の buffer の数。0 の場合は index も buffer_size の buffer を使う
+    pub index_buffer_size: usize,
+    /// table のファイルなどを伸ばす時に一度に確保する block の数
+    pub extent_size: usize,
+    /// block を暗号化して保存する場合の鍵 (encryption feature が必要)
+    pub encryption_key: Option<[u8; ENCRYPTION_KEY_LEN]>,
+    /// 起動時に catalog table と前回最近使われていた table の先頭の block を buffer pool に読み込んでおくか
+    pub warm_up: bool,
+    /// 1 つの query の中でメモリ上に値を保持する operator (統計情報の収集など) が使えるメモリの上限 (byte)
+    /// 超えた場合は "memory limit exceeded" の error になる。None の場合は制限しない
+    pub query_memory_limit: Option<usize>,
+    /// 1 つの transaction が同じ table の block の lock をこの数だけ取ったら、table のファイル単位の lock にまとめる
+    /// None の場合はまとめない
+    pub lock_escalation_threshold: Option<usize>,
+    /// 1 つの transaction でこの数より多くの record を変更した table は、commit 時に統計情報を捨てて次の planning で計算し直す
+    pub stats_invalidation_threshold: u64,
+    /// 最後の操作からこの時間以上 commit も rollback もされていない transaction を abort する
+    /// None の場合は abort しない
+    pub idle_transaction_timeout: Option<Duration>,
+    /// create index で table の record を index に登録する処理の進捗を受け取る callback
+    pub index_build_progress: Option<IndexBuildProgressCallback>,
+}
+
+impl Default for SimpleDBConfig {
+    fn default() -> Self {
+        Self {
+            block_size: SimpleDB::BLOCK_SIZE,
+            buffer_size: SimpleDB::BUFFER_SIZE,
+            catalog_buffer_size: SimpleDB::CATALOG_BUFFER_SIZE,
+            index_buffer_size: 0,
+            extent_size: SimpleDB::EXTENT_SIZE,
+            encryption_key: None,
+            warm_up: false,
+            query_memory_limit: None,
+            lock_escalation_threshold: None,
+            stats_invalidation_threshold: 0,
+            idle_transaction_timeout: None,
+            index_build_progress: None,
+        }
+    }
+}
+
 pub struct SimpleDB {
     file_manager: Arc<FileManager>,
     log_manager: Arc<LogManager>,
     buffer_manager: Arc<BufferManager>,
-    transaction_factory: TransactionFactory,
+    transaction_factory: Arc<TransactionFactory>,
+    temp_file_manager: Arc<TempFileManager>,
+    table_manager: Arc<dyn TableManager>,
     metadata_manager: Arc<dyn MetadataManager>,
+    // warm-up が有効な場合のみ、最近使われた table を記録する
+    access_list: Option<Arc<AccessList>>,
+    control_file_path: PathBuf,
+    // 起動時に読み込んだ (なければ作成した) control file の内容
+    control_file: ControlFile,
     executor: Executor,
 }
 
 impl SimpleDB {
     const BLOCK_SIZE: usize = 400;
     const BUFFER_SIZE: usize = 8;
+    // catalog table は 1 つずつ開いてすぐに閉じるので、少しの buffer で足りる
+    // tblcat と fldcat の system index の directory と leaf (分割中は新しい block も) を同時に pin する分を加えている
+    const CATALOG_BUFFER_SIZE: usize = 6;
+    const EXTENT_SIZE: usize = 8;
     const LOG_FILE: &'static str = "simpledb.log";
     const LOCK_TABLE_MAX_WAITING_TIME_MS: u64 = 100;
+    const ACCESS_LIST_FILE: &'static str = "simpledb.access";
+    const ACCESS_LIST_CAPACITY: usize = 16;
+    const CONTROL_FILE: &'static str = "simpledb.control";
+    // warm-up で table ごとに読み込む block の数
+    const WARM_UP_BLOCKS_PER_TABLE: usize = 2;
+
+    pub fn with_params(
+        dir_name: &str,
+        block_size: usize,
+        buff_size: usize,
+    ) -> SimpleDbResult<Self> {
+        Self::with_config(
+            dir_name,
+            SimpleDBConfig {
+                block_size,
+                buffer_size: buff_size,
+                ..Default::default()
+            },
+        )
+    }
+
+    pub fn with_config(dir_name: &str, config: SimpleDBConfig) -> SimpleDbResult<Self> {
+        Ok(Self::open(dir_name, config)?)
+    }
 
-    pub fn with_params(dir_name: &str, block_size: usize, buff_size: usize) -> AnyhowResult<Self> {
-        let file_manager = Arc::new(FileManager::new(Path::new(dir_name), block_size));
+    fn open(dir_name: &str, config: SimpleDBConfig) -> AnyhowResult<Self> {
+        // 既存の database の場合は、ファイルを読む前に作成時と同じ block size で開いているかを確かめる
+        // control file が無い場合は、既存のファイルが block size に合っているかを確かめる
+        let control_file_path = Path::new(dir_name).join(SimpleDB::CONTROL_FILE);
+        // control file を持たない database は、table ごとの保存方法を tblcat に持つ前の形式で作られている
+        let mut is_legacy = false;
+        let mut is_new = false;
+        let control_file = match ControlFile::load(&control_file_path)? {
+            Some(control_file) => {
+                control_file.check_block_size(config.block_size)?;
+                control_file
+            }
+            None => {
+                let stride = match config.encryption_key {
+                    Some(_) => config.block_size + ENCRYPTION_OVERHEAD,
+                    None => config.block_size,
+                };
+                let catalog_files: Vec<String> = CATALOG_TABLE_NAMES
+                    .iter()
+                    .map(|table_name| format!("{}.tbl", table_name))
+                    .collect();
+                ControlFile::probe_block_size(
+                    Path::new(dir_name),
+                    SimpleDB::LOG_FILE,
+                    &catalog_files,
+                    config.block_size,
+                    stride,
+                )?;
+                is_legacy = Path::new(dir_name)
+                    .join(format!("{}.tbl", TBLCAT_TABLE_NAME))
+                    .exists();
+                is_new = !is_legacy;
+                ControlFile::new(config.block_size)
+            }
+        };
+        let mut file_manager = FileManager::new(Path::new(dir_name), config.block_size)
+            .with_extent_size(config.extent_size);
+        if let Some(key) = &config.encryption_key {
+            file_manager = file_manager.with_encryption_key(key)?;
+        }
+        let file_manager = Arc::new(file_manager);
         let log_manager = Arc::new(LogManager::new(file_manager.clone(), SimpleDB::LOG_FILE)?);
-        let buffer_manager = Arc::new(BufferManager::new(
+        // recovery で、前回までに書き込まれた checkpoint より前の log を読まないようにする
+        log_manager.set_last_checkpoint_lsn(control_file.checkpoint_lsn)?;
+        let buffer_manager = Arc::new(BufferManager::with_pools(
             file_manager.clone(),
             log_manager.clone(),
-            buff_size,
+            BufferPoolSizes {
+                data: config.buffer_size,
+                catalog: config.catalog_buffer_size,
+                index: config.index_buffer_size,
+            },
             None,
         ));
+        for table_name in CATALOG_TABLE_NAMES {
+            buffer_manager
+                .register_file(&format!("{}.tbl", table_name), BufferPoolKind::Catalog)?;
+        }
+        // catalog の system index も、catalog table と同じく planning 中に読むので catalog 用の buffer を使う
+        for index_name in SYSTEM_INDEX_NAMES {
+            for filename in BTreeIndex::file_names(index_name) {
+                buffer_manager.register_file(&filename, BufferPoolKind::Catalog)?;
+            }
+        }
         let table_manager = Arc::new(TableManagerImpl::new(
             Arc::new(TableScanFactoryImpl::new()),
         )?);
-        let metadata_manager = Arc::new(MetadataManagerImpl::new(table_manager)?);
-        let lock_table = Arc::new(LockTable::new(Some(
-            SimpleDB::LOCK_TABLE_MAX_WAITING_TIME_MS,
-        )));
-        let transaction_factory = TransactionFactory::new(
+        let access_list = if config.warm_up {
+            // 暗号化が有効な場合に table 名が平文で残らないように、FileManager を通して保存する
+            Some(Arc::new(AccessList::load(
+                file_manager.clone(),
+                SimpleDB::ACCESS_LIST_FILE,
+                SimpleDB::ACCESS_LIST_CAPACITY,
+            )?))
+        } else {
+            None
+        };
+        let mut metadata_manager = MetadataManagerImpl::new(table_manager.clone())?
+            .with_query_memory_limit(config.query_memory_limit);
+        if let Some(access_list) = &access_list {
+            metadata_manager = metadata_manager.with_access_list(access_list.clone());
+        }
+        let metadata_manager = Arc::new(metadata_manager);
+        let mut lock_table = LockTable::new(Some(SimpleDB::LOCK_TABLE_MAX_WAITING_TIME_MS));
+        if let Some(threshold) = config.lock_escalation_threshold {
+            lock_table = lock_table.with_escalation_threshold(threshold);
+        }
+        let lock_table = Arc::new(lock_table);
+        // 以前の起動で使った transaction の番号と重ならないように、log に残っている番号の続きから振る
+        let mut transaction_factory = TransactionFactory::new(
             file_manager.clone(),
             log_manager.clone(),
             buffer_manager.clone(),
             lock_table,
-        );
+        )
+        .with_tx_num_from_log()?;
+        // log が切り詰められていても、前回の終了時に記録した番号より前には戻らない
+        transaction_factory = transaction_factory.with_min_next_tx_num(control_file.next_txnum);
+        if let Some(timeout) = config.idle_transaction_timeout {
+            transaction_factory = transaction_factory.with_idle_reaper(timeout);
+        }
+        let transaction_factory = Arc::new(transaction_factory);
+        if is_legacy {
+            // 以前の形式の database は table ごとの block size を使わないので、先に recovery を済ませてから catalog を書き換える
+            let tx = Rc::new(RefCell::new(transaction_factory.create()?));
+            tx.borrow_mut().recover()?;
+            table_manager.upgrade_legacy_catalog(&tx)?;
+            tx.borrow_mut().commit()?;
+        }
+        // recovery などで table のファイルを読み書きする前に、table ごとの block size と圧縮の設定を FileManager に登録しておく
+        {
+            let tx = Rc::new(RefCell::new(transaction_factory.create()?));
+            for (filename, storage) in table_manager.table_file_storages(&tx)? {
+                if let Some(block_size) = storage.block_size {
+                    file_manager.set_block_size(&filename, block_size)?;
+                }
+                if storage.compressed {
+                    file_manager.set_compressed(&filename)?;
+                }
+            }
+            // tblcat と fldcat の system index が無い場合は作成する
+            // 既存の database では、recovery で取り消される catalog の record を index に登録しないように、先に recovery を済ませる
+            // (以前の形式の database は、catalog を書き換える前に recovery を済ませている)
+            if !table_manager.has_system_indexes(&tx)? {
+                if !is_new && !is_legacy {
+                    tx.borrow_mut().recover()?;
+                }
+                table_manager.setup_system_indexes(&tx)?;
+            }
+            tx.borrow_mut().commit()?;
+        }
+        let temp_file_manager = Arc::new(TempFileManager::new(
+            file_manager.clone(),
+            buffer_manager.clone(),
+        ));
 
-        let query_planner = BasicQueryPalanner::new(metadata_manager.clone(), ParserFactory::new());
-        let executor = Executor::new(
+        let query_planner = BasicQueryPalanner::new(metadata_manager.clone(), ParserFactory::new())
+            .with_temp_file_manager(temp_file_manager.clone());
+        let mut executor = Executor::new(
             Box::new(query_planner),
             ParserFactory::new(),
             metadata_manager.clone(),
-        );
+        )
+        .with_transaction_factory(transaction_factory.clone())
+        .with_stats_invalidation_threshold(config.stats_invalidation_threshold);
+        if let Some(progress) = config.index_build_progress.clone() {
+            executor = executor.with_index_build_progress(move |p| progress(p));
+        }
 
-        Ok(Self {
+        let db = Self {
             file_manager,
             log_manager,
             buffer_manager,
+            table_manager,
             metadata_manager,
+            access_list,
+            control_file_path,
+            control_file,
             executor,
             transaction_factory,
-        })
+            temp_file_manager,
+        };
+        // 作成したばかりの database でも、block size などの設定が disk 上に残るようにする
+        db.save_control_file()?;
+        if config.warm_up {
+            db.warm_up()?;
+        }
+        Ok(db)
     }
 
-    pub fn new(dir_name: &str) -> AnyhowResult<Self> {
+    pub fn new(dir_name: &str) -> SimpleDbResult<Self> {
         Self::with_params(dir_name, SimpleDB::BLOCK_SIZE, SimpleDB::BUFFER_SIZE)
     }
 
-    pub fn new_tx(&self) -> AnyhowResult<Rc<RefCell<Transaction>>> {
+    pub fn new_tx(&self) -> SimpleDbResult<Rc<RefCell<Transaction>>> {
         Ok(Rc::new(RefCell::new(self.transaction_factory.create()?)))
     }
 
@@ -93,6 +326,25 @@ impl SimpleDB {
         &self.executor
     }
 
+    /// table を SQL を通さずに key-value store として読み書きするための KvTable を返す
+    pub fn table(
+        &self,
+        table_name: &str,
+        tx: &Rc<RefCell<Transaction>>,
+    ) -> SimpleDbResult<KvTable<'_>> {
+        Ok(KvTable::open(
+            table_name,
+            &self.executor,
+            RowPolicy::new(),
+            tx,
+        )?)
+    }
+
+    /// cursor などの client ごとの状態を持つ session を作成する
+    pub fn new_session(&self) -> Session<'_> {
+        Session::new(&self.executor, ParserFactory::new())
+    }
+
     pub fn log_manager(&self) -> Arc<LogManager> {
         self.log_manager.clone()
     }
@@ -100,13 +352,143 @@ impl SimpleDB {
     pub fn buffer_manager(&self) -> Arc<BufferManager> {
         self.buffer_manager.clone()
     }
+
+    pub fn temp_file_manager(&self) -> Arc<TempFileManager> {
+        self.temp_file_manager.clone()
+    }
+
+    /// catalog table と、前回の起動時に最近使われていた table の先頭の block を buffer pool に読み込んでおく
+    /// 読み込んだ block は unpin しておくので、buffer pool が足りなくなれば通常通り置き換えられる
+    /// 読み込んだ block の数を返す
+    pub fn warm_up(&self) -> SimpleDbResult<usize> {
+        Ok(self.warm_up_tables()?)
+    }
+
+    fn warm_up_tables(&self) -> AnyhowResult<usize> {
+        let tables = match &self.access_list {
+            Some(access_list) => access_list.tables()?,
+            None => Vec::new(),
+        };
+        let tx = self.new_tx()?;
+        // buffer pool の大きさを超えて読み込むと、先に読み込んだ block が追い出されてしまう
+        let limit = self.buffer_manager.available()?;
+        let mut warmed = 0;
+        for table_name in CATALOG_TABLE_NAMES {
+            warmed += Self::warm_up_file(&tx, &format!("{}.tbl", table_name), limit - warmed)?;
+        }
+        for table_name in tables {
+            // 前回の起動後に削除された table や external table は読み込まない
+            let Ok(layout) = self.table_manager.get_layout(&table_name, &tx) else {
+                continue;
+            };
+            if self
+                .metadata_manager
+                .get_external_table_path(&table_name, &tx)?
+                .is_some()
+            {
+                continue;
+            }
+            // partition に分けた table の record は partition ごとのファイルにある
+            let filenames = match layout.partition() {
+                Some(partition) => (0..partition.partition_count())
+                    .map(|p| {
+                        format!(
+                            "{}.tbl",
+                            PartitionSpec::partition_table_name(&table_name, p)
+                        )
+                    })
+                    .collect(),
+                None => vec![format!("{}.tbl", table_name)],
+            };
+            for filename in filenames {
+                if let Some(block_size) = layout.block_size() {
+                    tx.borrow().set_block_size(&filename, block_size)?;
+                }
+                if layout.storage().compressed {
+                    tx.borrow().set_compressed(&filename)?;
+                }
+                warmed += Self::warm_up_file(&tx, &filename, limit - warmed)?;
+            }
+        }
+        tx.borrow_mut().commit()?;
+        Ok(warmed)
+    }
+
+    /// ファイルの先頭から最大 WARM_UP_BLOCKS_PER_TABLE 個 (かつ limit 個) の block を pin して unpin する
+    fn warm_up_file(
+        tx: &Rc<RefCell<Transaction>>,
+        filename: &str,
+        limit: usize,
+    ) -> AnyhowResult<usize> {
+        let size = tx.borrow().size(filename)?;
+        let count = size.min(SimpleDB::WARM_UP_BLOCKS_PER_TABLE).min(limit);
+        for blknum in 0..count {
+            let block = BlockId::new(filename, blknum);
+            tx.borrow().pin(&block)?;
+            tx.borrow().unpin(&block)?;
+        }
+        Ok(count)
+    }
+
+    /// 最近使われた table の一覧をファイルに保存する。warm-up が無効な場合は何もしない
+    pub fn save_access_list(&self) -> SimpleDbResult<()> {
+        if let Some(access_list) = &self.access_list {
+            access_list.save()?;
+        }
+        Ok(())
+    }
+
+    /// 現在の状態で control file を更新する
+    pub fn save_control_file(&self) -> SimpleDbResult<()> {
+        let control_file = ControlFile {
+            checkpoint_lsn: self.log_manager.last_checkpoint_lsn()?,
+            next_txnum: self.transaction_factory.next_tx_num(),
+            ..self.control_file.clone()
+        };
+        control_file.save(&self.control_file_path)?;
+        Ok(())
+    }
+}
+
+impl Drop for SimpleDB {
+    fn drop(&mut self) {
+        // drop 中にはエラーを返せないので、保存に失敗した場合は次回の warm-up が効かないだけとする
+        let _ = self.save_access_list();
+        let _ = self.save_control_file();
+    }
 }
 
 #[cfg(test)]
 mod simpledb_integration_test {
+    use std::{
+        cell::RefCell,
+        rc::Rc,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
     use tempfile::tempdir;
 
-    use super::SimpleDB;
+    use super::{ControlFile, SimpleDB, SimpleDBConfig};
+    use crate::{
+        buffer::buffer_manager::BufferPoolKind,
+        error::ErrorCategory,
+        exec::{
+            executor::{IndexBuildProgress, QueryResult},
+            retry_policy::RetryPolicy,
+            session::{CursorResult, Session},
+        },
+        file::file_manager::FileManager,
+        index::{btree_index::BTreeIndex, common::IndexType},
+        metadata::{
+            access_list::AccessList, constants::SYSTEM_INDEX_NAMES, index_manager::IndexInfo,
+        },
+        plan::{expression::Expression, plan_snapshot::PlanSnapshot, predicate::ProductPredicate},
+        planner::query_builder::Query,
+        query::{constant::Constant, memory_budget::MemoryBudget},
+        server::kv_table::KvRow,
+        tx::transaction::Transaction,
+    };
 
     fn setup(db: &SimpleDB) {
         // table 定義用の transaction
@@ -263,4 +645,2937 @@ mod simpledb_integration_test {
         drop(scan);
         tx.borrow_mut().commit().unwrap();
     }
+
+    #[test]
+    fn test_temp_table_is_visible_only_to_creating_transaction() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        executor
+            .exec_update_command("create temp table tmp (a int, b varchar(10))", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("insert into tmp (a, b) values (1, 'one')", &tx)
+            .unwrap();
+        let mut scan = executor.exec_query("select a, b from tmp", &tx).unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_int("a").unwrap(), 1);
+        assert_eq!(scan.get_string("b").unwrap(), "one");
+        assert!(!scan.move_next().unwrap());
+        drop(scan);
+        // temp table の実体は temp から始まるファイルに保存される
+        assert!(std::fs::read_dir(dir.path()).unwrap().any(|entry| entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with("temp")));
+        tx.borrow_mut().commit().unwrap();
+        // transaction が終わると temp table のファイルは削除される
+        assert!(!std::fs::read_dir(dir.path()).unwrap().any(|entry| entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with("temp")));
+
+        // 他の transaction からは見えない
+        let other_tx = db.new_tx().unwrap();
+        assert!(executor.exec_query("select a from tmp", &other_tx).is_err());
+        other_tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_insert_with_default_values() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let db = super::SimpleDB::new(dir_name).unwrap();
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        executor
+            .exec_update_command(
+                "create table item (id int, qty int default 10, label varchar(10) default 'none')",
+                &tx,
+            )
+            .unwrap();
+        executor
+            .exec_update_command("insert into item (id) values (1)", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("insert into item (id, label) values (2, 'two')", &tx)
+            .unwrap();
+        tx.borrow_mut().commit().unwrap();
+
+        let tx = db.new_tx().unwrap();
+        let mut scan = executor
+            .exec_query("select id, qty, label from item", &tx)
+            .unwrap();
+        let mut result = Vec::new();
+        while scan.move_next().unwrap() {
+            result.push((
+                scan.get_int("id").unwrap(),
+                scan.get_int("qty").unwrap(),
+                scan.get_string("label").unwrap(),
+            ));
+        }
+        assert_eq!(
+            result,
+            vec![(1, 10, "none".to_string()), (2, 10, "two".to_string())]
+        );
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+
+        // 型の合わない default 値や、存在しない field を参照する default 値を持つ table は作成できない
+        let tx = db.new_tx().unwrap();
+        for cmd in [
+            "create table bad (id int, qty int default 'ten')",
+            "create table bad (id int, qty int default missing)",
+        ] {
+            let err = executor.exec_update_command(cmd, &tx).unwrap_err();
+            assert_eq!(err.category(), ErrorCategory::Plan);
+        }
+        assert!(!db.metadata_manager().table_exists("bad", &tx).unwrap());
+
+        // 検証する前に catalog に保存された default 値の評価に失敗しても、書きかけの record は残らない
+        executor
+            .exec_update_command("create table legacy (id int, code int)", &tx)
+            .unwrap();
+        db.metadata_manager()
+            .set_default_expression("legacy", "code", "missing", &tx)
+            .unwrap();
+        assert!(executor
+            .exec_update_command("insert into legacy (id) values (1)", &tx)
+            .is_err());
+        let mut scan = executor.exec_query("select id from legacy", &tx).unwrap();
+        assert!(!scan.move_next().unwrap());
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_insert_without_field_list() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        executor
+            .exec_update_command("insert into dept values (40, 'physics')", &tx)
+            .unwrap();
+        // 値の数が field の数と一致しない場合はエラー
+        assert!(executor
+            .exec_update_command("insert into dept values (50)", &tx)
+            .is_err());
+        let mut scan = executor
+            .exec_query("select dname from dept where did = 40", &tx)
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("dname").unwrap(), "physics");
+        assert!(!scan.move_next().unwrap());
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_cursor() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let mut session = db.new_session();
+        assert_eq!(
+            session
+                .exec_cursor_command("declare cursor c for select sid, sname from student", &tx)
+                .unwrap(),
+            CursorResult::Declared
+        );
+        // 同じ名前の cursor は作れない
+        assert!(session
+            .exec_cursor_command("declare cursor c for select sid from student", &tx)
+            .is_err());
+
+        // 9 件の record を 4 件ずつ取得する
+        let mut fetched_counts = vec![];
+        let mut sids = vec![];
+        for _ in 0..4 {
+            match session.exec_cursor_command("fetch 4 from c", &tx).unwrap() {
+                CursorResult::Fetched { fields, rows } => {
+                    assert_eq!(fields, vec!["sid", "sname"]);
+                    fetched_counts.push(rows.len());
+                    for row in rows {
+                        sids.push(row[0].clone());
+                    }
+                }
+                _ => panic!("expected fetched result"),
+            }
+        }
+        assert_eq!(fetched_counts, vec![4, 4, 1, 0]);
+        assert_eq!(sids, (1..=9).map(Constant::Int).collect::<Vec<_>>());
+
+        assert_eq!(
+            session.exec_cursor_command("close c", &tx).unwrap(),
+            CursorResult::Closed
+        );
+        assert!(session.exec_cursor_command("fetch 1 from c", &tx).is_err());
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_index_buffer_pool() {
+        let dir = tempdir().unwrap();
+        let config = SimpleDBConfig {
+            index_buffer_size: 4,
+            ..Default::default()
+        };
+        let db = SimpleDB::with_config(dir.path().to_str().unwrap(), config).unwrap();
+        let tx = db.new_tx().unwrap();
+        db.executor()
+            .exec_update_command("create table student (sid int, sname varchar(10))", &tx)
+            .unwrap();
+        db.executor()
+            .exec_update_command("create index sid_idx on student (sid)", &tx)
+            .unwrap();
+        tx.borrow_mut().commit().unwrap();
+
+        // 作成した index のファイルは index 用の buffer を使う
+        let buffer_manager = db.buffer_manager();
+        for filename in ["sid_idx.leaf", "sid_idx.dir"] {
+            assert_eq!(
+                buffer_manager.pool_of(filename).unwrap(),
+                BufferPoolKind::Index
+            );
+        }
+        assert_eq!(
+            buffer_manager.pool_of("student.tbl").unwrap(),
+            BufferPoolKind::Data
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "encryption")]
+    fn test_encrypted_database() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let config = SimpleDBConfig {
+            encryption_key: Some([1u8; 32]),
+            warm_up: true,
+            ..Default::default()
+        };
+        {
+            let db = SimpleDB::with_config(dir_name, config.clone()).unwrap();
+            setup(&db);
+            // commit では buffer は書き出されないので、明示的に disk へ書き出しておく
+            db.buffer_manager().flush_all().unwrap();
+        }
+        // table のファイルにも log file にも平文は現れない
+        for file_name in ["dept.tbl", "simpledb.log"] {
+            let bytes = std::fs::read(dir.path().join(file_name)).unwrap();
+            assert!(!bytes.windows(7).any(|w| w == b"compsci"));
+        }
+        // 最近使われた table の一覧にも table 名は平文で現れない
+        let bytes = std::fs::read(dir.path().join(SimpleDB::ACCESS_LIST_FILE)).unwrap();
+        assert!(!bytes.is_empty());
+        assert!(!bytes.windows(7).any(|w| w == b"student"));
+
+        {
+            let db = SimpleDB::with_config(dir_name, config).unwrap();
+            let tx = db.new_tx().unwrap();
+            let mut scan = db
+                .executor()
+                .exec_query("select dname from dept where did = 10", &tx)
+                .unwrap();
+            assert!(scan.move_next().unwrap());
+            assert_eq!(scan.get_string("dname").unwrap(), "compsci");
+            drop(scan);
+            tx.borrow_mut().commit().unwrap();
+        }
+
+        // 違う鍵では読めない
+        let wrong_config = SimpleDBConfig {
+            encryption_key: Some([2u8; 32]),
+            ..Default::default()
+        };
+        let err = SimpleDB::with_config(dir_name, wrong_config).err().unwrap();
+        assert_eq!(err.category(), ErrorCategory::Corruption);
+    }
+
+    #[test]
+    #[cfg(not(feature = "encryption"))]
+    fn test_encryption_without_feature() {
+        let dir = tempdir().unwrap();
+        let config = SimpleDBConfig {
+            encryption_key: Some([1u8; 32]),
+            ..Default::default()
+        };
+        assert!(SimpleDB::with_config(dir.path().to_str().unwrap(), config).is_err());
+    }
+
+    #[test]
+    fn test_stats_invalidation_on_commit() {
+        let dir = tempdir().unwrap();
+        let config = SimpleDBConfig {
+            stats_invalidation_threshold: 5,
+            ..Default::default()
+        };
+        let db = SimpleDB::with_config(dir.path().to_str().unwrap(), config).unwrap();
+        let executor = db.executor();
+        let num_records = |db: &SimpleDB| {
+            let tx = db.new_tx().unwrap();
+            let stats = db
+                .metadata_manager()
+                .get_table_stat("t", &MemoryBudget::unlimited(), &tx)
+                .unwrap();
+            tx.borrow_mut().commit().unwrap();
+            stats["a"].get_num_records()
+        };
+        let insert = |count: usize, commit: bool| {
+            let tx = db.new_tx().unwrap();
+            for i in 0..count {
+                executor
+                    .exec_update_command(&format!("insert into t (a) values ({})", i), &tx)
+                    .unwrap();
+            }
+            if commit {
+                tx.borrow_mut().commit().unwrap();
+            } else {
+                tx.borrow_mut().rollback().unwrap();
+            }
+        };
+
+        let tx = db.new_tx().unwrap();
+        executor
+            .exec_update_command("create table t (a int)", &tx)
+            .unwrap();
+        tx.borrow_mut().commit().unwrap();
+        assert_eq!(num_records(&db), 0);
+
+        // 閾値以下の変更では統計情報は計算し直されない
+        insert(5, true);
+        assert_eq!(num_records(&db), 0);
+        // rollback した transaction の変更は数えない
+        insert(6, false);
+        assert_eq!(num_records(&db), 0);
+        // 閾値を超えて変更した transaction が commit されると、次に取得した時に計算し直される
+        insert(6, true);
+        assert_eq!(num_records(&db), 11);
+
+        // update や delete も変更した record として数える
+        let tx = db.new_tx().unwrap();
+        assert_eq!(
+            executor
+                .exec_update_command("delete from t where a = 0", &tx)
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            executor
+                .exec_update_command("update t set a = 10 where a = 1", &tx)
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            executor
+                .exec_update_command("delete from t where a = 2", &tx)
+                .unwrap(),
+            2
+        );
+        tx.borrow_mut().commit().unwrap();
+        assert_eq!(num_records(&db), 7);
+    }
+
+    #[test]
+    fn test_explain_analyze() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let root = db
+            .executor()
+            .exec_explain_analyze(
+                "explain analyze select sname, dname from student, dept where majorid = did",
+                &tx,
+            )
+            .unwrap();
+        // ProjectPlan -> SelectPlan -> MergeJoinPlan -> (TablePlan(student), TablePlan(dept))
+        assert_eq!(root.label(), "ProjectPlan(sname, dname)");
+        assert_eq!(root.actual_records(), 9);
+        let select = &root.children()[0];
+        assert_eq!(select.actual_records(), 9);
+        let join = &select.children()[0];
+        assert_eq!(join.label(), "MergeJoinPlan(majorid = did)");
+        assert_eq!(join.estimated_records(), 9);
+        assert_eq!(join.actual_records(), 9);
+        let [student, dept] = join.children() else {
+            panic!("merge join plan should have two children");
+        };
+        assert_eq!(student.label(), "TablePlan(student)");
+        assert_eq!(student.actual_records(), 9);
+        // product と異なり、dept は並べ替える時に 1 回だけ読まれる
+        assert_eq!(dept.estimated_records(), 3);
+        assert_eq!(dept.actual_records(), 3);
+        assert_eq!(
+            root.actual_blocks(),
+            student.actual_blocks() + dept.actual_blocks()
+        );
+        assert_eq!(root.to_string().lines().count(), 5);
+
+        // dept だけを参照する条件は join の内側に移され、join が出力する record が減る
+        let root = db
+            .executor()
+            .exec_explain_analyze(
+                "explain analyze select sname, dname from student, dept where majorid = did and did = 10",
+                &tx,
+            )
+            .unwrap();
+        assert_eq!(root.actual_records(), 3);
+        let select = &root.children()[0];
+        assert_eq!(select.label(), "SelectPlan(majorid = did)");
+        let join = &select.children()[0];
+        assert_eq!(join.actual_records(), 3);
+        assert_eq!(join.children()[1].label(), "SelectPlan(did = 10)");
+
+        // dname は dept の中で条件に使ったら不要になるので、join に渡る前に落とされる
+        let root = db
+            .executor()
+            .exec_explain_analyze(
+                "explain analyze select sname from student, dept where majorid = did and dname = 'math'",
+                &tx,
+            )
+            .unwrap();
+        assert_eq!(root.actual_records(), 4);
+        let join = &root.children()[0].children()[0];
+        assert_eq!(join.children()[1].label(), "ProjectPlan(did)");
+
+        // 常に偽になる条件の query は table を読まない
+        let root = db
+            .executor()
+            .exec_explain_analyze(
+                "explain analyze select sname from student, dept where majorid = did and 1 = 2",
+                &tx,
+            )
+            .unwrap();
+        assert_eq!(root.label(), "NeverPlan");
+        assert_eq!(root.actual_records(), 0);
+        assert_eq!(root.actual_blocks(), 0);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_plan_snapshot() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let query = "select sname, dname from student, dept where majorid = did";
+        let snapshot = db.executor().plan_snapshot(query, &tx).unwrap();
+        // planner が選ぶ plan が変わった場合に気付けるように、JSON 全体を固定しておく
+        assert_eq!(
+            snapshot.to_json(),
+            r#"{"label":"ProjectPlan(sname, dname)","estimated_records":3,"estimated_blocks":9,"children":[{"label":"SelectPlan(majorid = did)","estimated_records":3,"estimated_blocks":9,"children":[{"label":"MergeJoinPlan(majorid = did)","estimated_records":9,"estimated_blocks":9,"children":[{"label":"TablePlan(student)","estimated_records":9,"estimated_blocks":2,"children":[]},{"label":"TablePlan(dept)","estimated_records":3,"estimated_blocks":1,"children":[]}]}]}]}"#
+        );
+        assert_eq!(
+            PlanSnapshot::from_json(&snapshot.to_json()).unwrap(),
+            snapshot
+        );
+
+        // 実行した結果から作った snapshot も、実行せずに作ったものと同じになる
+        let root = db
+            .executor()
+            .exec_explain_analyze(&format!("explain analyze {}", query), &tx)
+            .unwrap();
+        assert_eq!(root.snapshot(), snapshot);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_range_pruning() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+        let executor = db.executor();
+        let count = |query: &str, tx: &Rc<RefCell<Transaction>>| {
+            let mut scan = executor.exec_query(query, tx).unwrap();
+            scan.before_first().unwrap();
+            let mut count = 0;
+            while scan.move_next().unwrap() {
+                count += 1;
+            }
+            count
+        };
+
+        let tx = db.new_tx().unwrap();
+        let stats = db
+            .metadata_manager()
+            .get_table_stat("student", &MemoryBudget::unlimited(), &tx)
+            .unwrap();
+        assert_eq!(
+            stats["sid"].get_range(),
+            Some((&Constant::Int(1), &Constant::Int(9)))
+        );
+        // 統計情報の範囲の外の値との等号条件を持つ query は table を読まない
+        let root = executor
+            .exec_explain_analyze(
+                "explain analyze select sname from student where sid = 100",
+                &tx,
+            )
+            .unwrap();
+        assert_eq!(root.label(), "NeverPlan");
+        assert_eq!(root.actual_blocks(), 0);
+        let root = executor
+            .exec_explain_analyze(
+                "explain analyze select sname from student where sid = 5",
+                &tx,
+            )
+            .unwrap();
+        assert_ne!(root.label(), "NeverPlan");
+        assert_eq!(root.actual_records(), 1);
+
+        // 範囲の外の値を挿入した transaction の中でも、挿入した record が見える
+        executor
+            .exec_update_command(
+                "insert into student (sid, sname, gradyear, majorid) values (100, 'tom', 2023, 10)",
+                &tx,
+            )
+            .unwrap();
+        assert_eq!(count("select sname from student where sid = 100", &tx), 1);
+        tx.borrow_mut().commit().unwrap();
+
+        // 統計情報を計算し直さない程度の変更でも、commit した後に見える
+        let tx = db.new_tx().unwrap();
+        assert_eq!(count("select sname from student where sid = 100", &tx), 1);
+        tx.borrow_mut().commit().unwrap();
+
+        // KvTable で書き込んだ値も見える
+        let tx = db.new_tx().unwrap();
+        db.metadata_manager().invalidate_table_stat("student");
+        let stats = db
+            .metadata_manager()
+            .get_table_stat("student", &MemoryBudget::unlimited(), &tx)
+            .unwrap();
+        assert_eq!(
+            stats["sid"].get_range(),
+            Some((&Constant::Int(1), &Constant::Int(100)))
+        );
+        let table = db.table("student", &tx).unwrap();
+        table
+            .put(
+                &KvRow::from([
+                    ("sid".to_string(), Constant::Int(200)),
+                    ("sname".to_string(), Constant::String("ann".to_string())),
+                ]),
+                &tx,
+            )
+            .unwrap();
+        assert_eq!(count("select sname from student where sid = 200", &tx), 1);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_query_memory_limit() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        {
+            let db = SimpleDB::new(dir_name).unwrap();
+            setup(&db);
+            db.buffer_manager().flush_all().unwrap();
+        }
+        let config = SimpleDBConfig {
+            query_memory_limit: Some(64),
+            ..Default::default()
+        };
+        let db = SimpleDB::with_config(dir_name, config).unwrap();
+
+        // student の統計情報を集める時にユニークな値を保持しきれない
+        let tx = db.new_tx().unwrap();
+        let err = db
+            .executor()
+            .exec_query("select sname from student", &tx)
+            .err()
+            .unwrap();
+        assert_eq!(err.category(), ErrorCategory::Constraint);
+        assert!(format!("{:#}", err).contains("memory limit exceeded"));
+        tx.borrow_mut().rollback().unwrap();
+
+        // external table の統計情報を集める時に保持する値も、同じ制限に計上する
+        let csv_path = dir.path().join("course.csv");
+        std::fs::write(&csv_path, "1,db,10\n2,calculus,20\n3,algebra,20\n").unwrap();
+        let tx = db.new_tx().unwrap();
+        db.executor()
+            .exec_update_command(
+                &format!(
+                    "create external table course (cid int, title varchar(10), deptid int) location '{}'",
+                    csv_path.to_str().unwrap()
+                ),
+                &tx,
+            )
+            .unwrap();
+        let err = db
+            .executor()
+            .exec_query("select title from course", &tx)
+            .err()
+            .unwrap();
+        assert!(format!("{:#}", err).contains("memory limit exceeded"));
+        tx.borrow_mut().rollback().unwrap();
+    }
+
+    #[test]
+    fn test_pin_cache_across_statements() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+        let catalog_buffers = || {
+            db.buffer_manager()
+                .available_in(BufferPoolKind::Catalog)
+                .unwrap()
+        };
+
+        let tx = db.new_tx().unwrap();
+        let run_query = || {
+            let mut scan = db
+                .executor()
+                .exec_query("select dname from dept where did = 10", &tx)
+                .unwrap();
+            assert!(scan.move_next().unwrap());
+        };
+        run_query();
+        // statement が終わっても、catalog の block は transaction の pin cache が pin したまま保持している
+        assert!(tx.borrow().pinned_blocks().is_empty());
+        assert!(catalog_buffers() < SimpleDB::CATALOG_BUFFER_SIZE);
+        let hits = tx.borrow().pin_cache_hits();
+
+        // 次の statement では、catalog の block を buffer manager を経由せずに pin する
+        run_query();
+        assert!(tx.borrow().pin_cache_hits() > hits);
+        assert!(tx.borrow().pinned_blocks().is_empty());
+
+        // commit すると pin cache が保持していた buffer も返す
+        tx.borrow_mut().commit().unwrap();
+        assert_eq!(catalog_buffers(), SimpleDB::CATALOG_BUFFER_SIZE);
+    }
+
+    #[test]
+    fn test_warm_up() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let config = SimpleDBConfig {
+            warm_up: true,
+            ..Default::default()
+        };
+        {
+            let db = SimpleDB::with_config(dir_name, config.clone()).unwrap();
+            setup(&db);
+            let tx = db.new_tx().unwrap();
+            let mut scan = db
+                .executor()
+                .exec_query("select dname from dept where did = 10", &tx)
+                .unwrap();
+            assert!(scan.move_next().unwrap());
+            drop(scan);
+            tx.borrow_mut().commit().unwrap();
+            db.buffer_manager().flush_all().unwrap();
+        }
+        // 前回最後に使われた table が一覧の先頭に保存されている
+        let access_list = AccessList::load(
+            Arc::new(FileManager::new(dir.path(), SimpleDB::BLOCK_SIZE)),
+            SimpleDB::ACCESS_LIST_FILE,
+            SimpleDB::ACCESS_LIST_CAPACITY,
+        )
+        .unwrap()
+        .tables()
+        .unwrap();
+        assert_eq!(access_list.first().map(|t| t.as_str()), Some("dept"));
+        assert!(access_list.iter().any(|t| t == "student"));
+
+        let db = SimpleDB::with_config(dir_name, config).unwrap();
+        // catalog table (tblcat, fldcat) と dept, student の block が読み込まれる
+        let warmed = db.warm_up().unwrap();
+        assert!(warmed >= 4);
+        assert!(warmed <= SimpleDB::BUFFER_SIZE);
+        assert_eq!(
+            db.buffer_manager().available().unwrap(),
+            SimpleDB::BUFFER_SIZE + SimpleDB::CATALOG_BUFFER_SIZE
+        );
+        let tx = db.new_tx().unwrap();
+        let mut scan = db
+            .executor()
+            .exec_query("select sname from student where sid = 1", &tx)
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("sname").unwrap(), "joe");
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_table_with_block_size() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        {
+            let db = super::SimpleDB::new(dir_name).unwrap();
+            setup(&db);
+            let tx = db.new_tx().unwrap();
+            let executor = db.executor();
+            // 1 record が共通の block size (400) より大きい table
+            executor
+                .exec_update_command(
+                    "create table memo (mid int, body varchar(120)) blocksize 1024",
+                    &tx,
+                )
+                .unwrap();
+            for i in 0..5 {
+                executor
+                    .exec_update_command(
+                        &format!("insert into memo (mid, body) values ({}, 'memo{}')", i, i),
+                        &tx,
+                    )
+                    .unwrap();
+            }
+            tx.borrow_mut().commit().unwrap();
+            db.buffer_manager().flush_all().unwrap();
+        }
+        // 再起動しても catalog に保存された block size で読める
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        let mut scan = executor
+            .exec_query(
+                "select body, dname from memo, dept where mid = 3 and did = 10",
+                &tx,
+            )
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("body").unwrap(), "memo3");
+        assert_eq!(scan.get_string("dname").unwrap(), "compsci");
+        assert!(!scan.move_next().unwrap());
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_recover_table_with_block_size() {
+        assert_recovers_table_with_storage("blocksize 1024");
+    }
+
+    #[test]
+    #[cfg(feature = "compression")]
+    fn test_recover_compressed_table() {
+        assert_recovers_table_with_storage("compressed");
+    }
+
+    // storage で保存方法を指定した table を、commit していない変更を disk に書き出した状態から recovery できることを確かめる
+    fn assert_recovers_table_with_storage(storage: &str) {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        {
+            let db = super::SimpleDB::new(dir_name).unwrap();
+            let tx = db.new_tx().unwrap();
+            let executor = db.executor();
+            executor
+                .exec_update_command(
+                    &format!("create table memo (mid int, body varchar(20)) {}", storage),
+                    &tx,
+                )
+                .unwrap();
+            for i in 0..5 {
+                executor
+                    .exec_update_command(
+                        &format!("insert into memo (mid, body) values ({}, 'memo{}')", i, i),
+                        &tx,
+                    )
+                    .unwrap();
+            }
+            tx.borrow_mut().commit().unwrap();
+        }
+        {
+            // recovery の checkpoint より前の insert は redo されないので、
+            // 次の recovery は disk 上の block を読んで undo だけを行う
+            let db = super::SimpleDB::new(dir_name).unwrap();
+            db.new_tx().unwrap().borrow_mut().recover().unwrap();
+
+            // commit していない変更を disk に書き出した状態で落ちる
+            let tx = db.new_tx().unwrap();
+            let executor = db.executor();
+            executor
+                .exec_update_command("update memo set body = 'changed'", &tx)
+                .unwrap();
+            db.buffer_manager().flush_all().unwrap();
+        }
+        // recovery でも catalog に保存された保存方法で table のファイルを読み書きする
+        {
+            let db = super::SimpleDB::new(dir_name).unwrap();
+            db.new_tx().unwrap().borrow_mut().recover().unwrap();
+        }
+        // buffer に残った内容ではなく、recovery が書き込んだファイルの内容を読む
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mut scan = db
+            .executor()
+            .exec_query("select mid, body from memo", &tx)
+            .unwrap();
+        let mut count = 0;
+        while scan.move_next().unwrap() {
+            let mid = scan.get_int("mid").unwrap();
+            assert_eq!(scan.get_string("body").unwrap(), format!("memo{}", mid));
+            count += 1;
+        }
+        assert_eq!(count, 5);
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_external_table() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        setup(&db);
+        let csv_path = dir.path().join("course.csv");
+        std::fs::write(&csv_path, "1,db,10\n2,calculus,20\n3,algebra,20\n").unwrap();
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        executor
+            .exec_update_command(
+                &format!(
+                    "create external table course (cid int, title varchar(10), deptid int) location '{}'",
+                    csv_path.to_str().unwrap()
+                ),
+                &tx,
+            )
+            .unwrap();
+        // 通常の table と join できる
+        let mut scan = executor
+            .exec_query(
+                "select title, dname from course, dept where deptid = did and did = 20",
+                &tx,
+            )
+            .unwrap();
+        let mut titles = vec![];
+        while scan.move_next().unwrap() {
+            assert_eq!(scan.get_string("dname").unwrap(), "math");
+            titles.push(scan.get_string("title").unwrap());
+        }
+        titles.sort();
+        assert_eq!(titles, vec!["algebra", "calculus"]);
+        drop(scan);
+        // external table は read-only
+        let err = executor
+            .exec_update_command("insert into course values (4, 'physics', 30)", &tx)
+            .unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Constraint);
+        let err = executor
+            .exec_update_command("delete from course where cid = 1", &tx)
+            .unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Constraint);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_error_category() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        let err = executor.exec_query("select from dept", &tx).err().unwrap();
+        assert_eq!(err.category(), ErrorCategory::Parse);
+        let err = executor
+            .exec_query("select a from nosuchtable", &tx)
+            .err()
+            .unwrap();
+        assert_eq!(err.category(), ErrorCategory::Plan);
+        // 元の error は source として辿れる
+        assert!(std::error::Error::source(&err).is_some());
+        let err = executor
+            .exec_update_command("insert into dept (did) values (1, 'x')", &tx)
+            .unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Plan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_create_table_if_not_exists() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        // すでにある table を作成しようとするとエラーになる
+        let err = executor
+            .exec_update_command("create table dept (did int)", &tx)
+            .unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Plan);
+        // if not exists を指定した場合は何もしない
+        executor
+            .exec_update_command("create table if not exists dept (did int)", &tx)
+            .unwrap();
+        let mut scan = executor.exec_query("select dname from dept", &tx).unwrap();
+        assert!(scan.move_next().unwrap());
+        drop(scan);
+
+        // 存在しない場合は作成する
+        executor
+            .exec_update_command("create table if not exists club (cid int)", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("insert into club (cid) values (1)", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("create temp table if not exists tmp (a int)", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("create temp table if not exists tmp (a int)", &tx)
+            .unwrap();
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_show_tables_and_describe() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        let mut scan = executor.exec_show_command("show tables", &tx).unwrap();
+        let mut tables = vec![];
+        while scan.move_next().unwrap() {
+            tables.push(scan.get_string("table_name").unwrap());
+        }
+        drop(scan);
+        // catalog の table は含まない
+        assert_eq!(tables, vec!["student", "dept"]);
+
+        let mut scan = executor.exec_show_command("describe dept", &tx).unwrap();
+        let mut fields = vec![];
+        while scan.move_next().unwrap() {
+            fields.push((
+                scan.get_string("field_name").unwrap(),
+                scan.get_string("type").unwrap(),
+                scan.get_int("length").unwrap(),
+                scan.get_int("offset").unwrap(),
+            ));
+        }
+        drop(scan);
+        assert_eq!(
+            fields,
+            vec![
+                ("did".to_string(), "int".to_string(), 0, 4),
+                ("dname".to_string(), "varchar".to_string(), 10, 8),
+            ]
+        );
+
+        let err = executor
+            .exec_show_command("describe nosuchtable", &tx)
+            .err()
+            .unwrap();
+        assert_eq!(err.category(), ErrorCategory::Plan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_execute_returns_result_by_statement_kind() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        assert!(matches!(
+            executor
+                .execute("create table t (a int, b varchar(5))", &tx)
+                .unwrap(),
+            QueryResult::Ddl
+        ));
+        assert!(matches!(
+            executor
+                .execute("insert into t (a, b) values (1, 'x')", &tx)
+                .unwrap(),
+            QueryResult::Count(1)
+        ));
+        assert!(matches!(
+            executor
+                .execute("update student set gradyear = 2023 where majorid = 10", &tx)
+                .unwrap(),
+            QueryResult::Count(3)
+        ));
+
+        match executor
+            .execute("select sname from student where sid = 1", &tx)
+            .unwrap()
+        {
+            QueryResult::Rows { fields, mut scan } => {
+                assert_eq!(fields, vec!["sname".to_string()]);
+                assert!(scan.move_next().unwrap());
+                assert_eq!(scan.get_string("sname").unwrap(), "joe");
+                assert!(!scan.move_next().unwrap());
+            }
+            _ => panic!("select must return rows"),
+        }
+        match executor.execute("describe t", &tx).unwrap() {
+            QueryResult::Rows { fields, mut scan } => {
+                assert_eq!(fields, vec!["field_name", "type", "length", "offset"]);
+                assert!(scan.move_next().unwrap());
+                assert_eq!(scan.get_string("field_name").unwrap(), "a");
+            }
+            _ => panic!("describe must return rows"),
+        }
+        assert!(matches!(
+            executor
+                .execute("explain analyze select a from t", &tx)
+                .unwrap(),
+            QueryResult::Explain(_)
+        ));
+        // cursor は session を通して実行する必要がある
+        assert!(executor.execute("close c", &tx).is_err());
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_nested_transaction_scopes() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+        let executor = db.executor();
+        let count_depts = |tx: &Rc<RefCell<Transaction>>| {
+            let mut scan = executor.exec_query("select did from dept", tx).unwrap();
+            let mut count = 0;
+            while scan.move_next().unwrap() {
+                count += 1;
+            }
+            count
+        };
+
+        let outer = executor.begin_scope(None).unwrap();
+        assert!(!outer.is_nested());
+        let before = count_depts(outer.tx());
+        executor
+            .exec_update_command(
+                "insert into dept (did, dname) values (40, 'art')",
+                outer.tx(),
+            )
+            .unwrap();
+
+        // 入れ子の scope を rollback すると、その scope の変更だけが取り消される
+        let inner = executor.begin_scope(Some(outer.tx())).unwrap();
+        assert!(inner.is_nested());
+        executor
+            .exec_update_command(
+                "insert into dept (did, dname) values (50, 'law')",
+                inner.tx(),
+            )
+            .unwrap();
+        inner.rollback().unwrap();
+        assert_eq!(count_depts(outer.tx()), before + 1);
+
+        // 入れ子の scope の commit は、親の commit で確定する
+        let inner = executor.begin_scope(Some(outer.tx())).unwrap();
+        executor
+            .exec_update_command(
+                "insert into dept (did, dname) values (60, 'med')",
+                inner.tx(),
+            )
+            .unwrap();
+        inner.commit().unwrap();
+        // commit も rollback もせずに drop した scope は rollback される
+        {
+            let inner = executor.begin_scope(Some(outer.tx())).unwrap();
+            executor
+                .exec_update_command("delete from dept", inner.tx())
+                .unwrap();
+        }
+        outer.commit().unwrap();
+
+        let tx = db.new_tx().unwrap();
+        assert_eq!(count_depts(&tx), before + 2);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_exec_with_retry() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+        let executor = db.executor();
+        let policy = RetryPolicy {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(1),
+        };
+
+        // 競合がなければそのまま commit される
+        let count = executor
+            .exec_with_retry("update dept set dname = 'cs' where did = 10", &policy)
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // 他の transaction が xlock を持ち続けている間は、再試行しても lock の timeout になる
+        let tx = db.new_tx().unwrap();
+        executor
+            .exec_update_command("insert into dept (did, dname) values (40, 'art')", &tx)
+            .unwrap();
+        let err = executor
+            .exec_with_retry("update dept set dname = 'x' where did = 20", &policy)
+            .unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Lock);
+        assert!(err.is_retryable());
+        tx.borrow_mut().commit().unwrap();
+
+        // 再試行できない error はそのまま返す
+        let err = executor
+            .exec_with_retry("update nosuchtable set a = 1", &policy)
+            .unwrap_err();
+        assert!(!err.is_retryable());
+
+        let tx = db.new_tx().unwrap();
+        let mut scan = executor
+            .exec_query("select dname from dept where did = 10", &tx)
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("dname").unwrap(), "cs");
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_delete() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        let count = executor
+            .exec_update_command("delete from student where majorid = 20", &tx)
+            .unwrap();
+        assert_eq!(count, 4);
+        let mut scan = executor.exec_query("select sid from student", &tx).unwrap();
+        let mut sids = vec![];
+        while scan.move_next().unwrap() {
+            sids.push(scan.get_int("sid").unwrap());
+        }
+        assert_eq!(sids, vec![1, 3, 5, 7, 9]);
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_select_expressions() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        let mut scan = executor
+            .exec_query(
+                "select sid, majorid = did and did = 10 as in_cs, case when gradyear = 2020 then 'old' else 'new' end as generation, true from student, dept where dname = 'compsci' and sid = 2",
+                &tx,
+            )
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_int("sid").unwrap(), 2);
+        assert!(!scan.get_bool("in_cs").unwrap());
+        assert_eq!(scan.get_string("generation").unwrap(), "old");
+        assert!(scan.get_bool("true").unwrap());
+        // select しなかった field は読めない
+        assert!(scan.get_val("majorid").is_err());
+        assert!(!scan.move_next().unwrap());
+        drop(scan);
+
+        // 式を含む view も、定義を保存して読み直せる
+        executor
+            .exec_update_command(
+                "create view graduate as select sid, gradyear = 2020 as graduated from student",
+                &tx,
+            )
+            .unwrap();
+        let mut scan = executor
+            .exec_query("select sid from graduate where graduated = true", &tx)
+            .unwrap();
+        let mut graduates = vec![];
+        while scan.move_next().unwrap() {
+            graduates.push(scan.get_int("sid").unwrap());
+        }
+        assert_eq!(graduates, vec![2, 5, 6]);
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_built_query() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        let query = Query::table("student")
+            .join(
+                Query::table("dept"),
+                ProductPredicate::equal(
+                    Expression::Field("majorid".to_string()),
+                    Expression::Field("did".to_string()),
+                ),
+            )
+            .filter(ProductPredicate::equal(
+                Expression::Field("dname".to_string()),
+                Expression::Constant(Constant::from("math")),
+            ))
+            .extend("old", Expression::Constant(Constant::from(true)))
+            .project(&["sname", "old"]);
+        let mut scan = executor.exec_built_query(&query, &tx).unwrap();
+        let mut names = vec![];
+        while scan.move_next().unwrap() {
+            assert!(scan.get_bool("old").unwrap());
+            assert!(scan.get_val("dname").is_err());
+            names.push(scan.get_string("sname").unwrap());
+        }
+        assert_eq!(names, vec!["amy", "sue", "kim", "pat"]);
+        drop(scan);
+
+        // 存在しない field を参照すると error になる
+        let query = Query::table("student").filter(ProductPredicate::equal(
+            Expression::Field("dname".to_string()),
+            Expression::Constant(Constant::from("math")),
+        ));
+        assert!(executor.exec_built_query(&query, &tx).is_err());
+        let query = Query::table("student").project(&["dname"]);
+        assert!(executor.exec_built_query(&query, &tx).is_err());
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_update_with_case_expression() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        let count = executor
+            .exec_update_command(
+                "update student set sname = case when majorid = 10 then 'cs' when majorid = 20 then 'math' else sname end where gradyear = 2020",
+                &tx,
+            )
+            .unwrap();
+        assert_eq!(count, 3);
+        let mut scan = executor
+            .exec_query("select sid, sname from student", &tx)
+            .unwrap();
+        let mut names = vec![];
+        while scan.move_next().unwrap() {
+            names.push((
+                scan.get_int("sid").unwrap(),
+                scan.get_string("sname").unwrap(),
+            ));
+        }
+        assert_eq!(
+            names,
+            vec![
+                (1, "joe".to_string()),
+                (2, "math".to_string()),
+                (3, "max".to_string()),
+                (4, "sue".to_string()),
+                (5, "bob".to_string()),
+                (6, "math".to_string()),
+                (7, "art".to_string()),
+                (8, "pat".to_string()),
+                (9, "lee".to_string()),
+            ]
+        );
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_partitioned_table() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        executor
+            .exec_update_command(
+                "create table orders (oid int, region varchar(8)) partition by hash(oid) partitions 4",
+                &tx,
+            )
+            .unwrap();
+        for oid in 0..20 {
+            executor
+                .exec_update_command(
+                    &format!(
+                        "insert into orders (oid, region) values ({}, 'r{}')",
+                        oid, oid
+                    ),
+                    &tx,
+                )
+                .unwrap();
+        }
+        // record は partition ごとのファイルに分かれて保存される
+        let file_manager = db.file_manager.clone();
+        let partitions_with_records = (0..4)
+            .filter(|p| file_manager.length(&format!("orders.p{}.tbl", p)).unwrap() > 0)
+            .count();
+        assert!(partitions_with_records > 1);
+
+        let read_oids = |query: &str| {
+            let mut scan = executor.exec_query(query, &tx).unwrap();
+            let mut oids = vec![];
+            while scan.move_next().unwrap() {
+                oids.push(scan.get_int("oid").unwrap());
+            }
+            oids.sort();
+            oids
+        };
+        assert_eq!(
+            read_oids("select oid from orders"),
+            (0..20).collect::<Vec<_>>()
+        );
+        assert_eq!(read_oids("select oid from orders where oid = 7"), vec![7]);
+
+        // partition を決める field が定数と等しい条件では、その値の partition だけを読む
+        let root = executor
+            .exec_explain_analyze("explain analyze select oid from orders where oid = 7", &tx)
+            .unwrap();
+        // SelectPlan -> TablePlan
+        let table = &root.children()[0];
+        assert!(table.label().starts_with("TablePlan(orders, partitions ["));
+        assert_eq!(table.label().matches(',').count(), 1);
+        assert!(table.actual_records() < 20);
+
+        // partition を決める field を更新すると、record は別の partition に移り、二重に更新されない
+        let count = executor
+            .exec_update_command("update orders set oid = 100 where oid = 3", &tx)
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(
+            read_oids("select oid from orders where oid = 100"),
+            vec![100]
+        );
+        let mut scan = executor
+            .exec_query("select region from orders where oid = 100", &tx)
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("region").unwrap(), "r3");
+        drop(scan);
+
+        let count = executor
+            .exec_update_command("delete from orders where oid = 100", &tx)
+            .unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(read_oids("select oid from orders").len(), 19);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_range_partitioned_table() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        executor
+            .exec_update_command(
+                "create table events (day int, name varchar(8)) partition by range(day) values (10, 20)",
+                &tx,
+            )
+            .unwrap();
+        for day in [1, 5, 12, 25] {
+            executor
+                .exec_update_command(
+                    &format!(
+                        "insert into events (day, name) values ({}, 'e{}')",
+                        day, day
+                    ),
+                    &tx,
+                )
+                .unwrap();
+        }
+        let file_manager = db.file_manager.clone();
+        let lengths = (0..3)
+            .map(|p| file_manager.length(&format!("events.p{}.tbl", p)).unwrap())
+            .collect::<Vec<_>>();
+        assert!(lengths.iter().all(|length| *length > 0));
+        // partition の設定は catalog に保存され、partition の実体は table の一覧に出ない
+        let mut scan = executor.exec_show_command("show tables", &tx).unwrap();
+        let mut tables = vec![];
+        while scan.move_next().unwrap() {
+            tables.push(scan.get_string("table_name").unwrap());
+        }
+        assert_eq!(tables, vec!["events".to_string()]);
+        drop(scan);
+
+        let mut scan = executor
+            .exec_query("select name from events where day = 12", &tx)
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("name").unwrap(), "e12");
+        assert!(!scan.move_next().unwrap());
+        drop(scan);
+
+        // range partition は int の field にしか作れない
+        assert!(executor
+            .exec_update_command(
+                "create table bad (name varchar(8)) partition by range(name) values (1)",
+                &tx,
+            )
+            .is_err());
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_rename_column() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        executor
+            .exec_update_command("alter table dept rename column dname to deptname", &tx)
+            .unwrap();
+        assert!(executor.exec_query("select dname from dept", &tx).is_err());
+        let mut scan = executor
+            .exec_query("select deptname from dept where did = 20", &tx)
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("deptname").unwrap(), "math");
+        drop(scan);
+
+        // view の中の field は、from 句の他の table に同じ名前の field が無い場合だけ書き換える
+        executor
+            .exec_update_command("create table office (did int, room varchar(10))", &tx)
+            .unwrap();
+        executor
+            .exec_update_command(
+                "create view majors as select sname, deptname from student, dept where majorid = did",
+                &tx,
+            )
+            .unwrap();
+        executor
+            .exec_update_command(
+                "create view rooms as select room from office, dept where did = 20",
+                &tx,
+            )
+            .unwrap();
+        executor
+            .exec_update_command("alter table dept rename column did to deptid", &tx)
+            .unwrap();
+        let metadata_manager = db.metadata_manager();
+        assert_eq!(
+            metadata_manager.get_view_def("majors", &tx).unwrap(),
+            "select sname, deptname from student, dept where majorid = deptid"
+        );
+        assert_eq!(
+            metadata_manager.get_view_def("rooms", &tx).unwrap(),
+            "select room from office, dept where did = 20"
+        );
+
+        // default 値の式の中の field も書き換える
+        executor
+            .exec_update_command("create table item (id int, code int default id)", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("alter table item rename column id to item_id", &tx)
+            .unwrap();
+        assert_eq!(
+            metadata_manager
+                .get_default_expressions("item", &tx)
+                .unwrap()
+                .get("code"),
+            Some(&"item_id".to_string())
+        );
+        executor
+            .exec_update_command("insert into item (item_id) values (3)", &tx)
+            .unwrap();
+        let mut scan = executor.exec_query("select code from item", &tx).unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_int("code").unwrap(), 3);
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_group_by() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        let read_ints = |query: &str, fields: &[&str]| {
+            let mut scan = executor.exec_query(query, &tx).unwrap();
+            let mut rows = vec![];
+            while scan.move_next().unwrap() {
+                rows.push(
+                    fields
+                        .iter()
+                        .map(|field| scan.get_int(field).unwrap())
+                        .collect::<Vec<_>>(),
+                );
+            }
+            rows
+        };
+
+        // group は group by の field の順に出力される
+        assert_eq!(
+            read_ints(
+                "select majorid, count(sid), max(gradyear) from student group by majorid",
+                &["majorid", "count(sid)", "max(gradyear)"]
+            ),
+            vec![vec![10, 3, 2022], vec![20, 4, 2022], vec![30, 2, 2021]]
+        );
+        // group by が無い場合は全ての record を 1 つの group にまとめる
+        assert_eq!(
+            read_ints(
+                "select min(gradyear) as first, sum(sid), avg(gradyear) from student",
+                &["first", "sum(sid)", "avg(gradyear)"]
+            ),
+            vec![vec![2019, 45, 2020]]
+        );
+        assert!(read_ints(
+            "select majorid, count(sid) from student where sid = 100 group by majorid",
+            &["majorid"]
+        )
+        .is_empty());
+
+        // join した結果もまとめられる
+        let mut scan = executor
+            .exec_query(
+                "select dname, count(sid) as n from student, dept where majorid = did group by dname",
+                &tx,
+            )
+            .unwrap();
+        let mut rows = vec![];
+        while scan.move_next().unwrap() {
+            rows.push((
+                scan.get_string("dname").unwrap(),
+                scan.get_int("n").unwrap(),
+            ));
+        }
+        assert_eq!(
+            rows,
+            vec![
+                ("compsci".to_string(), 3),
+                ("drama".to_string(), 2),
+                ("math".to_string(), 4)
+            ]
+        );
+        drop(scan);
+
+        // group by を使う view も読める
+        executor
+            .exec_update_command(
+                "create view major_count as select majorid, count(sid) as n from student group by majorid",
+                &tx,
+            )
+            .unwrap();
+        assert_eq!(
+            read_ints("select n from major_count where majorid = 20", &["n"]),
+            vec![vec![4]]
+        );
+
+        // group by に無い field は select できない
+        assert!(executor
+            .exec_query(
+                "select sname, count(sid) from student group by majorid",
+                &tx
+            )
+            .is_err());
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_order_by() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        let read_ints = |query: &str, fields: &[&str]| {
+            let mut scan = executor.exec_query(query, &tx).unwrap();
+            let mut rows = vec![];
+            while scan.move_next().unwrap() {
+                rows.push(
+                    fields
+                        .iter()
+                        .map(|field| scan.get_int(field).unwrap())
+                        .collect::<Vec<_>>(),
+                );
+            }
+            rows
+        };
+
+        // gradyear の降順、同じ gradyear の中では sid の昇順に並べる
+        assert_eq!(
+            read_ints(
+                "select sid, gradyear from student order by gradyear desc, sid",
+                &["sid"]
+            ),
+            vec![
+                vec![3],
+                vec![4],
+                vec![1],
+                vec![7],
+                vec![9],
+                vec![2],
+                vec![5],
+                vec![6],
+                vec![8]
+            ]
+        );
+        // 集約関数の値は、つけた名前で並べ替える
+        assert_eq!(
+            read_ints(
+                "select majorid, count(sid) as n from student group by majorid order by n desc",
+                &["majorid", "n"]
+            ),
+            vec![vec![20, 4], vec![10, 3], vec![30, 2]]
+        );
+
+        // order by を使う view も読める
+        executor
+            .exec_update_command(
+                "create view compsci as select sname from student where majorid = 10 order by sname",
+                &tx,
+            )
+            .unwrap();
+        let mut scan = executor
+            .exec_query("select sname from compsci", &tx)
+            .unwrap();
+        let mut names = vec![];
+        while scan.move_next().unwrap() {
+            names.push(scan.get_string("sname").unwrap());
+        }
+        assert_eq!(names, vec!["joe", "lee", "max"]);
+        drop(scan);
+
+        // select していない field では並べ替えられない
+        let err = executor
+            .exec_query("select sname from student order by sid", &tx)
+            .err()
+            .unwrap();
+        assert_eq!(err.category(), ErrorCategory::Plan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        let read_ints = |query: &str| {
+            let mut scan = executor.exec_query(query, &tx).unwrap();
+            let mut sids = vec![];
+            while scan.move_next().unwrap() {
+                sids.push(scan.get_int("sid").unwrap());
+            }
+            sids
+        };
+
+        assert_eq!(
+            read_ints("select sid from student where gradyear >= 2021 and majorid <> 10"),
+            vec![4, 7]
+        );
+        assert_eq!(
+            read_ints("select sid from student where 2020 > gradyear"),
+            vec![8]
+        );
+        assert_eq!(
+            read_ints("select sid from student where sname < 'bob' and sid != 2"),
+            vec![7]
+        );
+        assert_eq!(
+            read_ints("select sid from student where sid > 3 and sid <= 5"),
+            vec![4, 5]
+        );
+        // 常に偽になる条件では何も読まない
+        assert!(read_ints("select sid from student where 1 > 2").is_empty());
+
+        // update / delete の条件にも使える
+        assert_eq!(
+            executor
+                .exec_update_command("update student set majorid = 40 where gradyear < 2020", &tx)
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            read_ints("select sid from student where majorid >= 40"),
+            vec![8]
+        );
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_distinct() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        let read_ints = |query: &str, fields: &[&str]| {
+            let mut scan = executor.exec_query(query, &tx).unwrap();
+            let mut rows = vec![];
+            while scan.move_next().unwrap() {
+                rows.push(
+                    fields
+                        .iter()
+                        .map(|field| scan.get_int(field).unwrap())
+                        .collect::<Vec<_>>(),
+                );
+            }
+            rows
+        };
+
+        assert_eq!(
+            read_ints("select distinct majorid from student", &["majorid"]),
+            vec![vec![10], vec![20], vec![30]]
+        );
+        // select した値の組が全て等しい record だけをまとめ、その後で並べ替える
+        assert_eq!(
+            read_ints(
+                "select distinct majorid, gradyear from student order by gradyear desc, majorid",
+                &["majorid", "gradyear"]
+            ),
+            vec![
+                vec![10, 2022],
+                vec![20, 2022],
+                vec![10, 2021],
+                vec![30, 2021],
+                vec![20, 2020],
+                vec![30, 2020],
+                vec![20, 2019]
+            ]
+        );
+        // 集約関数の値の重複も除ける
+        assert_eq!(
+            read_ints(
+                "select distinct count(sid) as n from student group by gradyear",
+                &["n"]
+            ),
+            vec![vec![1], vec![2], vec![3]]
+        );
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_catalog_buffers_are_not_starved_by_data_pins() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let config = SimpleDBConfig {
+            buffer_size: 2,
+            ..Default::default()
+        };
+        {
+            let db = SimpleDB::with_config(dir_name, config.clone()).unwrap();
+            setup(&db);
+            db.buffer_manager().flush_all().unwrap();
+        }
+        // 起動し直して、catalog の内容がメモリ上に残っていない状態にする
+        let db = SimpleDB::with_config(dir_name, config).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mut student = db
+            .executor()
+            .exec_query("select sname from student", &tx)
+            .unwrap();
+        let mut dept = db
+            .executor()
+            .exec_query("select dname from dept", &tx)
+            .unwrap();
+        assert!(student.move_next().unwrap());
+        assert!(dept.move_next().unwrap());
+
+        // data 用の buffer が全て pin されていても、catalog table は専用の buffer で読める
+        let metadata_manager = db.metadata_manager();
+        let layout = metadata_manager.get_layout("student", &tx).unwrap();
+        assert!(layout.schema().has_field("majorid"));
+        assert!(metadata_manager
+            .get_index_info("student", &tx)
+            .unwrap()
+            .is_empty());
+        assert!(metadata_manager.get_view_defs(&tx).unwrap().is_empty());
+        drop(student);
+        drop(dept);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_system_indexes_built_at_startup() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        {
+            let db = SimpleDB::new(dir_name).unwrap();
+            setup(&db);
+            // commit されずに終了した table の作成は、起動時の recovery で取り消される
+            let tx = db.new_tx().unwrap();
+            db.executor()
+                .exec_update_command("create table unfinished (a int)", &tx)
+                .unwrap();
+            db.buffer_manager().flush_all().unwrap();
+        }
+        // system index を持たない database として起動し直す
+        for index_name in SYSTEM_INDEX_NAMES {
+            for filename in BTreeIndex::file_names(index_name) {
+                std::fs::remove_file(dir.path().join(filename)).unwrap();
+            }
+        }
+
+        let db = SimpleDB::new(dir_name).unwrap();
+        for index_name in SYSTEM_INDEX_NAMES {
+            assert!(dir.path().join(format!("{}.leaf", index_name)).exists());
+        }
+        let tx = db.new_tx().unwrap();
+        let metadata_manager = db.metadata_manager();
+        assert!(metadata_manager
+            .get_layout("student", &tx)
+            .unwrap()
+            .schema()
+            .has_field("majorid"));
+        assert!(metadata_manager.get_layout("unfinished", &tx).is_err());
+        let mut scan = db
+            .executor()
+            .exec_query("select sname from student where sid = 1", &tx)
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("sname").unwrap(), "joe");
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_control_file() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let path = dir.path().join(SimpleDB::CONTROL_FILE);
+        let last_txnum = {
+            let db = SimpleDB::with_params(dir_name, 400, SimpleDB::BUFFER_SIZE).unwrap();
+            // 作成した時点で control file が書き込まれる
+            // 起動時に catalog を読む transaction が番号を 1 つ使う
+            assert_eq!(
+                ControlFile::load(&path).unwrap(),
+                Some(ControlFile {
+                    next_txnum: 2,
+                    ..ControlFile::new(400)
+                })
+            );
+            setup(&db);
+            let tx = db.new_tx().unwrap();
+            let txnum = tx.borrow().tx_num();
+            tx.borrow_mut().commit().unwrap();
+            txnum
+        };
+        let control_file = ControlFile::load(&path).unwrap().unwrap();
+        assert_eq!(control_file.block_size, 400);
+        assert_eq!(control_file.next_txnum, last_txnum + 1);
+
+        // log が失われても、transaction の番号は control file に記録された番号の続きから振られる
+        // (起動時に catalog を読む transaction が last_txnum + 1 を使う)
+        std::fs::remove_file(dir.path().join(SimpleDB::LOG_FILE)).unwrap();
+        let db = SimpleDB::with_params(dir_name, 400, SimpleDB::BUFFER_SIZE).unwrap();
+        let tx = db.new_tx().unwrap();
+        assert_eq!(tx.borrow().tx_num(), last_txnum + 2);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_recover_with_control_file_checkpoint() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let path = dir.path().join(SimpleDB::CONTROL_FILE);
+        let checkpoint_lsn = {
+            let db = SimpleDB::with_params(dir_name, 400, SimpleDB::BUFFER_SIZE).unwrap();
+            setup(&db);
+            let tx = db.new_tx().unwrap();
+            tx.borrow_mut().recover().unwrap();
+            tx.borrow_mut().commit().unwrap();
+            db.log_manager().last_checkpoint_lsn().unwrap()
+        };
+        assert!(checkpoint_lsn > 0);
+        assert_eq!(
+            ControlFile::load(&path).unwrap().unwrap().checkpoint_lsn,
+            checkpoint_lsn
+        );
+
+        // 開き直すと control file に記録された checkpoint から recovery を行う
+        {
+            let db = SimpleDB::with_params(dir_name, 400, SimpleDB::BUFFER_SIZE).unwrap();
+            assert_eq!(
+                db.log_manager().last_checkpoint_lsn().unwrap(),
+                checkpoint_lsn
+            );
+            let tx = db.new_tx().unwrap();
+            tx.borrow_mut().recover().unwrap();
+            tx.borrow_mut().commit().unwrap();
+        }
+
+        // log が失われて checkpoint が見つからない場合は recovery できない
+        std::fs::remove_file(dir.path().join(SimpleDB::LOG_FILE)).unwrap();
+        let db = SimpleDB::with_params(dir_name, 400, SimpleDB::BUFFER_SIZE).unwrap();
+        let tx = db.new_tx().unwrap();
+        let err = tx.borrow_mut().recover().unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Corruption);
+    }
+
+    #[test]
+    fn test_block_size_mismatch() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        {
+            let db = SimpleDB::new(dir_name).unwrap();
+            setup(&db);
+            db.buffer_manager().flush_all().unwrap();
+        }
+        // 作成した時と異なる block size では開けない
+        let err = SimpleDB::with_params(dir_name, 800, SimpleDB::BUFFER_SIZE)
+            .err()
+            .unwrap();
+        assert_eq!(err.category(), ErrorCategory::Corruption);
+        assert!(err
+            .to_string()
+            .contains("the database was created with block size 400, but is opened with 800"));
+
+        // control file が無くても、ファイルの中身が block size に合わなければ開けない
+        let control_file = std::fs::read(dir.path().join(SimpleDB::CONTROL_FILE)).unwrap();
+        std::fs::remove_file(dir.path().join(SimpleDB::CONTROL_FILE)).unwrap();
+        let err = SimpleDB::with_params(dir_name, 800, SimpleDB::BUFFER_SIZE)
+            .err()
+            .unwrap();
+        assert_eq!(err.category(), ErrorCategory::Corruption);
+        assert!(err.to_string().contains(
+            "the database has no control file and its files do not match block size 800"
+        ));
+        std::fs::write(dir.path().join(SimpleDB::CONTROL_FILE), control_file).unwrap();
+
+        // 同じ block size なら、それまでのデータをそのまま読める
+        let db = SimpleDB::with_params(dir_name, 400, SimpleDB::BUFFER_SIZE).unwrap();
+        let tx = db.new_tx().unwrap();
+        let mut scan = db
+            .executor()
+            .exec_query("select dname from dept where did = 20", &tx)
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("dname").unwrap(), "math");
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_rename_table_and_index() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        {
+            let db = super::SimpleDB::new(dir_name).unwrap();
+            setup(&db);
+            let tx = db.new_tx().unwrap();
+            let executor = db.executor();
+            executor
+                .exec_update_command("create index did_idx on dept (did)", &tx)
+                .unwrap();
+            executor
+                .exec_update_command(
+                    "create view math as select dname from dept where did = 20",
+                    &tx,
+                )
+                .unwrap();
+            tx.borrow_mut().commit().unwrap();
+
+            // rollback すると元の名前に戻る
+            let tx = db.new_tx().unwrap();
+            executor
+                .exec_update_command("alter table dept rename to department", &tx)
+                .unwrap();
+            tx.borrow_mut().rollback().unwrap();
+            let tx = db.new_tx().unwrap();
+            assert!(executor
+                .exec_query("select did from department", &tx)
+                .is_err());
+            let mut scan = executor.exec_query("select did from dept", &tx).unwrap();
+            let mut count = 0;
+            while scan.move_next().unwrap() {
+                count += 1;
+            }
+            assert_eq!(count, 3);
+            drop(scan);
+
+            // 既に存在する table 名や catalog には変更できない
+            assert!(executor
+                .exec_update_command("alter table dept rename to student", &tx)
+                .is_err());
+            assert!(executor
+                .exec_update_command("alter table dept rename to math", &tx)
+                .is_err());
+            assert!(executor
+                .exec_update_command("alter table tblcat rename to x", &tx)
+                .is_err());
+
+            executor
+                .exec_update_command("alter table dept rename to department", &tx)
+                .unwrap();
+            executor
+                .exec_update_command("alter index did_idx rename to department_did_idx", &tx)
+                .unwrap();
+            assert!(executor
+                .exec_update_command("alter index did_idx rename to x", &tx)
+                .is_err());
+            tx.borrow_mut().commit().unwrap();
+        }
+
+        // 再起動しても新しい名前で data, index, view が読める
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        assert!(executor.exec_query("select did from dept", &tx).is_err());
+        let mut scan = executor
+            .exec_query("select dname from department where did = 30", &tx)
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("dname").unwrap(), "drama");
+        assert!(!scan.move_next().unwrap());
+        drop(scan);
+        let mut scan = executor.exec_query("select dname from math", &tx).unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_string("dname").unwrap(), "math");
+        drop(scan);
+        assert_eq!(
+            db.metadata_manager()
+                .get_index_info("department", &tx)
+                .unwrap(),
+            vec![IndexInfo::new(
+                "department_did_idx",
+                "department",
+                "did",
+                false
+            )]
+        );
+        assert_eq!(
+            db.metadata_manager().get_index("did_idx", &tx).unwrap(),
+            None
+        );
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_create_index() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        {
+            let db = super::SimpleDB::new(dir_name).unwrap();
+            setup(&db);
+            let tx = db.new_tx().unwrap();
+            let executor = db.executor();
+            executor
+                .exec_update_command("create index sid_idx on student (sid)", &tx)
+                .unwrap();
+            executor
+                .exec_update_command(
+                    "create unique index dname_idx on dept (dname) using hash",
+                    &tx,
+                )
+                .unwrap();
+            // 同じ名前の index や存在しない field への index は作成できない
+            assert!(executor
+                .exec_update_command("create index sid_idx on dept (did)", &tx)
+                .is_err());
+            assert!(executor
+                .exec_update_command("create index x_idx on student (x)", &tx)
+                .is_err());
+            tx.borrow_mut().commit().unwrap();
+        }
+
+        // 再起動しても index の情報は残っている
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        let tx = db.new_tx().unwrap();
+        assert_eq!(
+            db.metadata_manager()
+                .get_index_info("student", &tx)
+                .unwrap(),
+            vec![IndexInfo::new("sid_idx", "student", "sid", false)]
+        );
+        assert_eq!(
+            db.metadata_manager().get_index("dname_idx", &tx).unwrap(),
+            Some(
+                IndexInfo::new("dname_idx", "dept", "dname", true).with_index_type(IndexType::Hash)
+            )
+        );
+        // idxcat は catalog なので、table の一覧には含まれない
+        assert!(!db
+            .metadata_manager()
+            .get_table_names(&tx)
+            .unwrap()
+            .contains(&"idxcat".to_string()));
+
+        // field 名を変更すると、index の対象の field も変わる
+        db.executor()
+            .exec_update_command("alter table dept rename column dname to deptname", &tx)
+            .unwrap();
+        assert_eq!(
+            db.metadata_manager().get_index_info("dept", &tx).unwrap(),
+            vec![IndexInfo::new("dname_idx", "dept", "deptname", true)
+                .with_index_type(IndexType::Hash)]
+        );
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_create_index_on_existing_records() {
+        let dir = tempdir().unwrap();
+        let reports = Arc::new(Mutex::new(vec![]));
+        let config = SimpleDBConfig {
+            index_build_progress: Some({
+                let reports = reports.clone();
+                Arc::new(move |progress: &IndexBuildProgress| {
+                    reports.lock().unwrap().push(progress.clone())
+                })
+            }),
+            ..Default::default()
+        };
+        let db = SimpleDB::with_config(dir.path().to_str().unwrap(), config).unwrap();
+        let executor = db.executor();
+        let tx = db.new_tx().unwrap();
+        executor
+            .exec_update_command("create table item (iid int, grp int)", &tx)
+            .unwrap();
+        for i in 0..300 {
+            executor
+                .exec_update_command(
+                    &format!(
+                        "insert into item (iid, grp) values ({}, {})",
+                        i,
+                        (i * 7) % 20
+                    ),
+                    &tx,
+                )
+                .unwrap();
+        }
+        tx.borrow_mut().commit().unwrap();
+
+        // 重複した値がある field には unique な index を作れず、idxcat にも登録されない
+        let tx = db.new_tx().unwrap();
+        assert!(executor
+            .exec_update_command("create unique index grp_idx on item (grp)", &tx)
+            .is_err());
+        tx.borrow_mut().rollback().unwrap();
+        // rollback した create index と同じ名前の index を、もう一度作り直せる
+        let tx = db.new_tx().unwrap();
+        executor
+            .exec_update_command("create index grp_idx on item (grp)", &tx)
+            .unwrap();
+        tx.borrow_mut().rollback().unwrap();
+        reports.lock().unwrap().clear();
+
+        let tx = db.new_tx().unwrap();
+        executor
+            .exec_update_command("create index grp_idx on item (grp)", &tx)
+            .unwrap();
+        tx.borrow_mut().commit().unwrap();
+
+        // record を 1 つ読むたびと、index に登録し終えた時に進捗が報告される
+        let reports = reports.lock().unwrap().clone();
+        assert_eq!(reports.len(), 301);
+        assert_eq!(
+            reports[0],
+            IndexBuildProgress {
+                index_name: "grp_idx".to_string(),
+                records_scanned: 1,
+                entries_loaded: 0,
+            }
+        );
+        assert_eq!(
+            reports.last(),
+            Some(&IndexBuildProgress {
+                index_name: "grp_idx".to_string(),
+                records_scanned: 300,
+                entries_loaded: 300,
+            })
+        );
+
+        // まとめて作った index で、すでにあった record を検索できる
+        let tx = db.new_tx().unwrap();
+        for grp in [0, 7, 19] {
+            let mut scan = executor
+                .exec_query(&format!("select iid from item where grp = {}", grp), &tx)
+                .unwrap();
+            let mut iids = vec![];
+            while scan.move_next().unwrap() {
+                iids.push(scan.get_int("iid").unwrap());
+            }
+            iids.sort();
+            let expected: Vec<i32> = (0..300).filter(|i| (i * 7) % 20 == grp).collect();
+            assert_eq!(iids, expected);
+        }
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_index_select() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        // すでにある record も index に登録される
+        executor
+            .exec_update_command("create index major_idx on student (majorid)", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("create index sname_idx on student (sname) using hash", &tx)
+            .unwrap();
+
+        let read_sids = |cmd: &str| {
+            let mut scan = executor.exec_query(cmd, &tx).unwrap();
+            let mut sids = vec![];
+            while scan.move_next().unwrap() {
+                sids.push(scan.get_int("sid").unwrap());
+            }
+            sids.sort();
+            sids
+        };
+        assert_eq!(
+            read_sids("select sid from student where majorid = 20"),
+            vec![2, 4, 6, 8]
+        );
+        assert_eq!(
+            read_sids("select sid from student where majorid = 20 and gradyear = 2020"),
+            vec![2, 6]
+        );
+        assert_eq!(
+            read_sids("select sid from student where sname = 'max'"),
+            vec![3]
+        );
+        assert!(read_sids("select sid from student where majorid = 40").is_empty());
+
+        // index を張った field を定数に限定している場合は、table を全て読む代わりに index を使う
+        let root = executor
+            .exec_explain_analyze(
+                "explain analyze select sid from student where majorid = 10",
+                &tx,
+            )
+            .unwrap();
+        let leaf = &root.children()[0].children()[0];
+        assert_eq!(leaf.label(), "IndexSelectPlan(student, major_idx = 10)");
+        assert_eq!(leaf.actual_records(), 3);
+        // index を張っていない field の条件では使わない
+        let root = executor
+            .exec_explain_analyze(
+                "explain analyze select sid from student where gradyear = 2020",
+                &tx,
+            )
+            .unwrap();
+        assert_eq!(
+            root.children()[0].children()[0].label(),
+            "TablePlan(student)"
+        );
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_index_range_select() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        executor
+            .exec_update_command("create index year_idx on student (gradyear)", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("create index sname_idx on student (sname) using hash", &tx)
+            .unwrap();
+
+        let read_sids = |cmd: &str| {
+            let mut scan = executor.exec_query(cmd, &tx).unwrap();
+            let mut sids = vec![];
+            while scan.move_next().unwrap() {
+                sids.push(scan.get_int("sid").unwrap());
+            }
+            sids.sort();
+            sids
+        };
+        assert_eq!(
+            read_sids("select sid from student where gradyear > 2019 and gradyear <= 2021"),
+            vec![1, 2, 5, 6, 7, 9]
+        );
+        assert_eq!(
+            read_sids("select sid from student where 2021 < gradyear"),
+            vec![3, 4]
+        );
+        assert_eq!(
+            read_sids("select sid from student where gradyear >= 2020 and gradyear < 2020"),
+            Vec::<i32>::new()
+        );
+        // 範囲の条件に加えて、他の条件も適用される
+        assert_eq!(
+            read_sids("select sid from student where gradyear >= 2021 and majorid = 10"),
+            vec![1, 3, 9]
+        );
+
+        // 定数との大小比較の条件では、B-Tree の index を範囲で検索する
+        let root = executor
+            .exec_explain_analyze(
+                "explain analyze select sid from student where gradyear > 2019 and gradyear <= 2021",
+                &tx,
+            )
+            .unwrap();
+        let leaf = &root.children()[0].children()[0];
+        assert_eq!(
+            leaf.label(),
+            "IndexSelectPlan(student, year_idx, gradyear > 2019 and gradyear <= 2021)"
+        );
+        assert_eq!(leaf.actual_records(), 6);
+        // hash index は範囲では検索できないので、table を全て読む
+        let root = executor
+            .exec_explain_analyze(
+                "explain analyze select sid from student where sname > 'm'",
+                &tx,
+            )
+            .unwrap();
+        assert_eq!(
+            root.children()[0].children()[0].label(),
+            "TablePlan(student)"
+        );
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_index_only_scan() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        executor
+            .exec_update_command(
+                "create index year_idx on student (gradyear) include (sid, sname)",
+                &tx,
+            )
+            .unwrap();
+
+        let read_sids = |cmd: &str| {
+            let mut scan = executor.exec_query(cmd, &tx).unwrap();
+            let mut sids = vec![];
+            while scan.move_next().unwrap() {
+                sids.push(scan.get_int("sid").unwrap());
+            }
+            sids.sort();
+            sids
+        };
+        // 読む field が全て index に含まれている場合は、table を読まずに index だけを読む
+        let query = "select sid, sname from student where gradyear = 2020";
+        assert_eq!(read_sids(query), vec![2, 5, 6]);
+        let root = executor
+            .exec_explain_analyze(&format!("explain analyze {}", query), &tx)
+            .unwrap();
+        let leaf = &root.children()[0].children()[0];
+        assert_eq!(leaf.label(), "IndexOnlyPlan(student, year_idx = 2020)");
+        assert_eq!(leaf.actual_records(), 3);
+        assert_eq!(
+            read_sids("select sid from student where gradyear > 2021"),
+            vec![3, 4]
+        );
+
+        // include した field を更新すると、index の値も更新される
+        executor
+            .exec_update_command("update student set sid = 50 where sid = 5", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("update student set gradyear = 2020 where sid = 8", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("delete from student where sid = 2", &tx)
+            .unwrap();
+        assert_eq!(read_sids(query), vec![6, 8, 50]);
+
+        // index に含まれない field を読む場合は、table の record も読む
+        let root = executor
+            .exec_explain_analyze(
+                "explain analyze select sid, majorid from student where gradyear = 2020",
+                &tx,
+            )
+            .unwrap();
+        assert_eq!(
+            root.children()[0].children()[0].label(),
+            "IndexSelectPlan(student, year_idx = 2020)"
+        );
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_index_maintenance() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        executor
+            .exec_update_command("create index major_idx on student (majorid)", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("create index sname_idx on student (sname) using hash", &tx)
+            .unwrap();
+
+        let read_sids = |cmd: &str| {
+            let mut scan = executor.exec_query(cmd, &tx).unwrap();
+            let mut sids = vec![];
+            while scan.move_next().unwrap() {
+                sids.push(scan.get_int("sid").unwrap());
+            }
+            sids.sort();
+            sids
+        };
+
+        // 追加した record は、全ての index から見つかる
+        executor
+            .exec_update_command(
+                "insert into student (sid, sname, gradyear, majorid) values (10, 'ann', 2023, 30)",
+                &tx,
+            )
+            .unwrap();
+        assert_eq!(
+            read_sids("select sid from student where majorid = 30"),
+            vec![5, 7, 10]
+        );
+        assert_eq!(
+            read_sids("select sid from student where sname = 'ann'"),
+            vec![10]
+        );
+
+        // 値を変更した record は、新しい値でだけ見つかる
+        executor
+            .exec_update_command("update student set majorid = 10 where sid = 10", &tx)
+            .unwrap();
+        assert_eq!(
+            read_sids("select sid from student where majorid = 30"),
+            vec![5, 7]
+        );
+        assert_eq!(
+            read_sids("select sid from student where majorid = 10"),
+            vec![1, 3, 9, 10]
+        );
+
+        // 削除した record は、どの index からも見つからない
+        assert_eq!(
+            executor
+                .exec_update_command("delete from student where gradyear = 2020", &tx)
+                .unwrap(),
+            3
+        );
+        assert_eq!(
+            read_sids("select sid from student where majorid = 20"),
+            vec![4, 8]
+        );
+        assert!(read_sids("select sid from student where sname = 'amy'").is_empty());
+        assert_eq!(
+            read_sids("select sid from student where sname = 'sue'"),
+            vec![4]
+        );
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_unique_index() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        // すでに値が重複している field には unique な index を作れない
+        let err = executor
+            .exec_update_command("create unique index major_idx on student (majorid)", &tx)
+            .unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Constraint);
+        tx.borrow_mut().rollback().unwrap();
+
+        let tx = db.new_tx().unwrap();
+        executor
+            .exec_update_command("create unique index sid_idx on student (sid)", &tx)
+            .unwrap();
+        executor
+            .exec_update_command(
+                "create unique index sname_idx on student (sname) using hash",
+                &tx,
+            )
+            .unwrap();
+        let count = |cmd: &str| {
+            let mut scan = executor.exec_query(cmd, &tx).unwrap();
+            let mut count = 0;
+            while scan.move_next().unwrap() {
+                count += 1;
+            }
+            count
+        };
+
+        // 重複する値の insert は失敗し、record も index の entry も残らない
+        let err = executor
+            .exec_update_command(
+                "insert into student (sid, sname, gradyear, majorid) values (3, 'ann', 2023, 30)",
+                &tx,
+            )
+            .unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Constraint);
+        assert_eq!(count("select sid from student where sname = 'ann'"), 0);
+        assert_eq!(count("select sid from student where sid = 3"), 1);
+        executor
+            .exec_update_command(
+                "insert into student (sid, sname, gradyear, majorid) values (10, 'ann', 2023, 30)",
+                &tx,
+            )
+            .unwrap();
+
+        // 他の record と同じ値への update は失敗するが、自分と同じ値への update はできる
+        let err = executor
+            .exec_update_command("update student set sid = 1 where sid = 10", &tx)
+            .unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Constraint);
+        assert_eq!(count("select sid from student where sid = 10"), 1);
+        executor
+            .exec_update_command("update student set sid = 10 where sid = 10", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("update student set sid = 11 where sid = 10", &tx)
+            .unwrap();
+        assert_eq!(count("select sid from student where sid = 11"), 1);
+        assert_eq!(count("select sid from student where sid = 10"), 0);
+
+        // 途中の record で失敗した update は、それまでに更新した record も元に戻す
+        let err = executor
+            .exec_update_command("update student set sid = 12 where sid > 8", &tx)
+            .unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Constraint);
+        assert_eq!(count("select sid from student where sid = 9"), 1);
+        assert_eq!(count("select sid from student where sid = 11"), 1);
+        assert_eq!(count("select sid from student where sid = 12"), 0);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_index_join() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new(dir.path().to_str().unwrap()).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        executor
+            .exec_update_command("create index major_idx on student (majorid)", &tx)
+            .unwrap();
+
+        let read_rows = |cmd: &str| {
+            let mut scan = executor.exec_query(cmd, &tx).unwrap();
+            let mut rows = vec![];
+            while scan.move_next().unwrap() {
+                rows.push((
+                    scan.get_string("sname").unwrap(),
+                    scan.get_string("dname").unwrap(),
+                ));
+            }
+            rows.sort();
+            rows
+        };
+        let expected = vec![
+            ("amy".to_string(), "math".to_string()),
+            ("bob".to_string(), "drama".to_string()),
+            ("kim".to_string(), "math".to_string()),
+        ];
+        assert_eq!(
+            read_rows(
+                "select sname, dname from dept, student where did = majorid and gradyear = 2020"
+            ),
+            expected
+        );
+        // index の無い table が後ろにある場合は product で組み合わせる
+        assert_eq!(
+            read_rows(
+                "select sname, dname from student, dept where did = majorid and gradyear = 2020"
+            ),
+            expected
+        );
+
+        // dept の各 record の did で、student の majorid の index を検索する
+        let root = executor
+            .exec_explain_analyze(
+                "explain analyze select sname, dname from dept, student where did = majorid",
+                &tx,
+            )
+            .unwrap();
+        let join = &root.children()[0].children()[0];
+        assert_eq!(join.label(), "IndexJoinPlan(student, major_idx = did)");
+        assert_eq!(join.actual_records(), 9);
+        assert_eq!(join.children()[0].label(), "TablePlan(dept)");
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_row_policy() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        setup(&db);
+
+        let tx = db.new_tx().unwrap();
+        let mut session = db.new_session();
+        session.set_row_policy("student", "majorid = 10").unwrap();
+
+        let read_sids = |session: &Session, cmd: &str| {
+            let mut scan = session.exec_query(cmd, &tx).unwrap();
+            let mut sids = vec![];
+            while scan.move_next().unwrap() {
+                sids.push(scan.get_int("sid").unwrap());
+            }
+            sids
+        };
+        assert_eq!(
+            read_sids(&session, "select sid from student"),
+            vec![1, 3, 9]
+        );
+        assert_eq!(
+            read_sids(
+                &session,
+                "select sid from student, dept where majorid = did"
+            ),
+            vec![1, 3, 9]
+        );
+
+        // 更新によって record を row policy の外に移すことはできない
+        assert!(session
+            .exec_update_command("update student set majorid = 20", &tx)
+            .is_err());
+        assert_eq!(
+            read_sids(&session, "select sid from student"),
+            vec![1, 3, 9]
+        );
+        // row policy の外の record は更新・削除されない
+        assert_eq!(
+            session
+                .exec_update_command("update student set gradyear = 2000", &tx)
+                .unwrap(),
+            3
+        );
+        assert_eq!(
+            session
+                .exec_update_command("delete from student where gradyear = 2000", &tx)
+                .unwrap(),
+            3
+        );
+        // row policy を満たさない record は挿入できない
+        let err = session
+            .exec_update_command(
+                "insert into student (sid, sname, gradyear, majorid) values (10, 'ann', 2023, 20)",
+                &tx,
+            )
+            .unwrap_err();
+        assert_eq!(err.category(), ErrorCategory::Constraint);
+
+        session.remove_row_policy("student");
+        assert_eq!(
+            read_sids(&session, "select sid from student where majorid = 20"),
+            vec![2, 4, 6, 8]
+        );
+        tx.borrow_mut().rollback().unwrap();
+    }
+
+    #[test]
+    fn test_query_as_of_lsn() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        setup(&db);
+        let lsn_after_setup = db.log_manager().latest_lsn().unwrap();
+
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        executor
+            .exec_update_command("update student set gradyear = 2000 where sid = 1", &tx)
+            .unwrap();
+        executor
+            .exec_update_command("delete from student where majorid = 20", &tx)
+            .unwrap();
+        tx.borrow_mut().commit().unwrap();
+        let lsn_after_update = db.log_manager().latest_lsn().unwrap();
+
+        // commit していない変更は、どの時点の query からも見えない
+        let tx = db.new_tx().unwrap();
+        executor
+            .exec_update_command(
+                "insert into student (sid, sname, gradyear, majorid) values (10, 'ann', 2023, 10)",
+                &tx,
+            )
+            .unwrap();
+        let read = |cmd: &str| {
+            let mut scan = executor.exec_query(cmd, &tx).unwrap();
+            let mut rows = vec![];
+            while scan.move_next().unwrap() {
+                rows.push((
+                    scan.get_int("sid").unwrap(),
+                    scan.get_int("gradyear").unwrap(),
+                ));
+            }
+            rows.sort();
+            rows
+        };
+        let rows = read(&format!(
+            "select sid, gradyear from student as of lsn {}",
+            lsn_after_setup
+        ));
+        assert_eq!(rows.len(), 9);
+        assert_eq!(rows[0], (1, 2021));
+        let rows = read(&format!(
+            "select sid, gradyear from student where majorid = 10 as of lsn {}",
+            lsn_after_update
+        ));
+        assert_eq!(rows, vec![(1, 2000), (3, 2022), (9, 2021)]);
+        // 現在の内容は変わっていない
+        let rows = read("select sid, gradyear from student where majorid = 10");
+        assert_eq!(rows, vec![(1, 2000), (3, 2022), (9, 2021), (10, 2023)]);
+        tx.borrow_mut().rollback().unwrap();
+        // 過去の内容を再現した temp table のファイルは transaction の終了時に削除される
+        let temp_files = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|entry| {
+                let name = entry.as_ref().unwrap().file_name();
+                name.to_str().unwrap().starts_with("temp")
+            })
+            .count();
+        assert_eq!(temp_files, 0);
+        drop(db);
+
+        // LSN は起動をまたいでも変わらないので、前回の起動で得た LSN の時点の内容も読める
+        let db = super::SimpleDB::new(dir_name).unwrap();
+        // 終了時に buffer は書き出されないので、commit 済の変更を recovery で disk に反映しておく
+        db.new_tx().unwrap().borrow_mut().recover().unwrap();
+        assert!(db.log_manager().latest_lsn().unwrap() >= lsn_after_update);
+        let tx = db.new_tx().unwrap();
+        let mut scan = db
+            .executor()
+            .exec_query(
+                &format!(
+                    "select sid, gradyear from student where sid = 1 as of lsn {}",
+                    lsn_after_setup
+                ),
+                &tx,
+            )
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_int("gradyear").unwrap(), 2021);
+        drop(scan);
+        tx.borrow_mut().commit().unwrap();
+    }
+
+    #[test]
+    fn test_additional_column_types() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        {
+            let db = SimpleDB::new(dir_name).unwrap();
+            let tx = db.new_tx().unwrap();
+            let executor = db.executor();
+            executor
+                .exec_update_command(
+                    "create table event (eid int, views bigint, public boolean, day date)",
+                    &tx,
+                )
+                .unwrap();
+            executor
+                .exec_update_command("create index views_idx on event (views)", &tx)
+                .unwrap();
+            for values in [
+                "1, 5000000000, true, date '2024-02-29'",
+                "2, 9000000000, false, date '1969-12-31'",
+                // int の値は bigint の field にそのまま入れられる
+                "3, 7, true, date '2024-03-01'",
+            ] {
+                executor
+                    .exec_update_command(&format!("insert into event values ({})", values), &tx)
+                    .unwrap();
+            }
+            tx.borrow_mut().commit().unwrap();
+            db.buffer_manager().flush_all().unwrap();
+        }
+
+        // 作り直した database でも、catalog に保存した型で読める
+        let db = SimpleDB::new(dir_name).unwrap();
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        let read_eids = |query: &str| {
+            let mut scan = executor.exec_query(query, &tx).unwrap();
+            let mut eids = vec![];
+            while scan.move_next().unwrap() {
+                eids.push(scan.get_int("eid").unwrap());
+            }
+            eids
+        };
+        let mut scan = executor
+            .exec_query("select views, public, day from event where eid = 1", &tx)
+            .unwrap();
+        assert!(scan.move_next().unwrap());
+        assert_eq!(scan.get_val("views").unwrap(), Constant::BigInt(5000000000));
+        assert!(scan.get_bool("public").unwrap());
+        assert_eq!(
+            scan.get_val("day").unwrap().to_string(),
+            "date '2024-02-29'"
+        );
+        drop(scan);
+
+        // index を使った検索でも、int の定数を bigint に変換して探す
+        assert_eq!(read_eids("select eid from event where views = 7"), vec![3]);
+        assert_eq!(
+            read_eids("select eid from event where views = 9000000000"),
+            vec![2]
+        );
+        // views の範囲を index で検索するので、views の順に返る
+        assert_eq!(
+            read_eids("select eid from event where views > 0 and public = true"),
+            vec![3, 1]
+        );
+        assert_eq!(
+            read_eids("select eid from event where day < date '2024-03-01'"),
+            vec![1, 2]
+        );
+
+        let mut scan = executor.exec_show_command("describe event", &tx).unwrap();
+        let mut types = vec![];
+        while scan.move_next().unwrap() {
+            types.push(scan.get_string("type").unwrap());
+        }
+        assert_eq!(types, vec!["int", "bigint", "boolean", "date"]);
+
+        // 型の合わない値は代入できない
+        assert!(executor
+            .exec_update_command("insert into event values (4, 1, true, '2024-01-01')", &tx)
+            .is_err());
+        tx.borrow_mut().rollback().unwrap();
+    }
+
+    #[test]
+    fn test_double_columns() {
+        let dir = tempdir().unwrap();
+        let dir_name = dir.path().to_str().unwrap();
+        {
+            let db = SimpleDB::new(dir_name).unwrap();
+            let tx = db.new_tx().unwrap();
+            let executor = db.executor();
+            executor
+                .exec_update_command("create table measure (mid int, amount double)", &tx)
+                .unwrap();
+            executor
+                .exec_update_command("create index amount_idx on measure (amount)", &tx)
+                .unwrap();
+            for values in [
+                "1, 1.5",
+                "2, 0.25",
+                // int と bigint の値は double の field にそのまま入れられる
+                "3, 3",
+                "4, 5000000000",
+            ] {
+                executor
+                    .exec_update_command(&format!("insert into measure values ({})", values), &tx)
+                    .unwrap();
+            }
+            tx.borrow_mut().commit().unwrap();
+            db.buffer_manager().flush_all().unwrap();
+        }
+
+        // 作り直した database でも、catalog に保存した型で読める
+        let db = SimpleDB::new(dir_name).unwrap();
+        let tx = db.new_tx().unwrap();
+        let executor = db.executor();
+        let read_mids = |query: &str| {
+            let mut scan = executor.exec_query(query, &tx).unwrap();
+            let mut mids = vec![];
+            while scan.move_next().unwrap() {
+                mids.push(scan.get_int("mid").unwrap());
+            }
+            mids
+        };
+        let mut scan = executor
+            .exec_query("select mid, amount from measure", &tx)
+            .unwrap();
+        let mut amounts = vec![];
+        while scan.move_next().unwrap() {
+            amounts.push(scan.get_val("amount").unwrap());
+        }
+        assert_eq!(
+            amounts,
+            vec![
+                Constant::Double(1.5),
+                Constant::Double(0.25),
+                Constant::Double(3.0),
+                Constant::Double(5000000000.0)
+            ]
+        );
+        drop(scan);
+
+        // index を使った検索でも、int の定数を double に変換して探す
+        assert_eq!(
+            read_mids("select mid from measure where amount = 3"),
+            vec![3]
+        );
+        assert_eq!(
+            read_mids("select mid from measure where amount = 0.25"),
+            vec![2]
+        );
+        assert_eq!(
+            read_mids("select mid from measure where amount < 2 and amount >= 0.5"),
+            vec![1]
+        );
+
+        executor
+            .exec_update_command("update measure set amount = 2.75 where mid = 1", &tx)
+            .unwrap();
+        assert_eq!(
+            read_mids("select mid from measure where amount > 2.5 and amount < 3.5"),
+            vec![1, 3]
+        );
+
+        let mut scan = executor.exec_show_command("describe measure", &tx).unwrap();
+        let mut types = vec![];
+        while scan.move_next().unwrap() {
+            types.push(scan.get_string("type").unwrap());
+        }
+        assert_eq!(types, vec!["int", "double"]);
+
+        // double の値は int の field には代入できない
+        let count_rows = || {
+            let mut scan = executor.exec_query("select mid from measure", &tx).unwrap();
+            let mut count = 0;
+            while scan.move_next().unwrap() {
+                count += 1;
+            }
+            count
+        };
+        let before = count_rows();
+        assert!(executor
+            .exec_update_command("insert into measure values (1.5, 1.5)", &tx)
+            .is_err());
+        // 一部の field だけ代入できる場合も、書きかけの record は残らない
+        assert!(executor
+            .exec_update_command("insert into measure (mid, amount) values (7, 'seven')", &tx)
+            .is_err());
+        assert_eq!(count_rows(), before);
+        tx.borrow_mut().rollback().unwrap();
+    }
 }
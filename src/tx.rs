@@ -1,4 +1,7 @@
 pub mod buffer_list;
 pub mod concurrency;
+pub mod idle_reaper;
 pub mod log;
+pub mod recovery;
+pub mod temp_file_manager;
 pub mod transaction;
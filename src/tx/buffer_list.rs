@@ -1,9 +1,9 @@
-use std::collections::{hash_map::Entry, HashMap};
+use std::collections::{hash_map::Entry, HashMap, VecDeque};
 use std::sync::{Arc, Mutex};
 
 use thiserror::Error;
 
-use crate::buffer::buffer_manager::BufferManagerError;
+use crate::buffer::buffer_manager::{BufferManagerError, BufferPoolKind};
 use crate::{
     buffer::{buffer::Buffer, buffer_manager::BufferManager},
     file::blockid::BlockId,
@@ -14,6 +14,10 @@ use crate::{
  *
  * 書き込みするための lock を取得するなど、並行実行性の担保は transaction 側で行うので、ここでは考えなくて良い
  * transaction は一つの thread 内でしか動かないので、thread 間での競合は考慮しなくて良い
+ *
+ * pin cache を有効にした場合、全ての pin が外れた catalog の block もしばらくは buffer manager 上で pin したまま保持しておき、
+ * 同じ block が再度 pin されたときに buffer manager を経由せずに済むようにする (planning のたびに catalog の block を何度も pin するケースを想定)
+ * catalog 以外の block は pin し直されることが少なく、保持すると他の transaction が使える buffer が減るだけなので cache しない
  */
 pub(crate) struct BufferList {
     // 保持している buffer のリスト
@@ -21,6 +25,69 @@ pub(crate) struct BufferList {
     // pin している block のリスト
     pins: Vec<BlockId>,
     buffer_manager: Arc<BufferManager>,
+    // pin cache。None の場合は cache を使わない
+    pin_cache: Option<PinCache>,
+}
+
+/**
+ * transaction からは unpin されたが、buffer manager 上ではまだ pin している buffer を保持する LRU cache
+ *
+ * cache に入っている buffer は、それぞれ buffer manager 上でちょうど 1 回 pin されている
+ */
+struct PinCache {
+    capacity: usize,
+    // 後ろにあるものほど最近使われた
+    entries: VecDeque<(BlockId, Arc<Mutex<Buffer>>)>,
+    // cache にある buffer を使って、buffer manager を経由せずに pin した回数
+    hits: usize,
+}
+
+impl PinCache {
+    fn new(capacity: usize) -> PinCache {
+        PinCache {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+            hits: 0,
+        }
+    }
+
+    fn contains(&self, block: &BlockId) -> bool {
+        self.entries.iter().any(|(b, _)| b == block)
+    }
+
+    // block に対応する buffer を cache から取り出す
+    fn take(&mut self, block: &BlockId) -> Option<Arc<Mutex<Buffer>>> {
+        let pos = self.entries.iter().position(|(b, _)| b == block)?;
+        self.hits += 1;
+        self.entries.remove(pos).map(|(_, buffer)| buffer)
+    }
+
+    // buffer を cache に入れる。容量を超えた場合は最も長い間使われていない buffer を返す
+    fn put(
+        &mut self,
+        block: BlockId,
+        buffer: Arc<Mutex<Buffer>>,
+    ) -> Option<(BlockId, Arc<Mutex<Buffer>>)> {
+        self.entries.push_back((block, buffer));
+        if self.entries.len() > self.capacity {
+            self.entries.pop_front()
+        } else {
+            None
+        }
+    }
+
+    fn pop_lru(&mut self) -> Option<(BlockId, Arc<Mutex<Buffer>>)> {
+        self.entries.pop_front()
+    }
+
+    // is_target を満たす block のうち、最も長い間使われていないものを cache から取り出す
+    fn pop_lru_matching<F: Fn(&BlockId) -> bool>(
+        &mut self,
+        is_target: F,
+    ) -> Option<(BlockId, Arc<Mutex<Buffer>>)> {
+        let pos = self.entries.iter().position(|(b, _)| is_target(b))?;
+        self.entries.remove(pos)
+    }
 }
 
 #[derive(Error, Debug)]
@@ -39,6 +106,15 @@ impl BufferList {
             buffers: HashMap::new(),
             pins: Vec::new(),
             buffer_manager,
+            pin_cache: None,
+        }
+    }
+
+    /// capacity 個までの catalog の block を保持する pin cache を有効にした BufferList を作成する
+    pub fn with_pin_cache(buffer_manager: Arc<BufferManager>, capacity: usize) -> BufferList {
+        BufferList {
+            pin_cache: Some(PinCache::new(capacity)),
+            ..BufferList::new(buffer_manager)
         }
     }
 
@@ -46,13 +122,44 @@ impl BufferList {
         self.buffers.get(block).cloned()
     }
 
+    /// block を pin している回数を返す。pin していない場合は 0 を返す
+    /// pin cache が保持しているだけの block は pin されているとはみなさない
+    pub fn pin_count(&self, block: &BlockId) -> usize {
+        self.pins.iter().filter(|b| *b == block).count()
+    }
+
+    /// pin cache にある buffer を使って、buffer manager を経由せずに pin した回数を返す
+    pub fn pin_cache_hits(&self) -> usize {
+        self.pin_cache.as_ref().map_or(0, |cache| cache.hits)
+    }
+
+    /// pin している block と、それぞれの pin 数を返す
+    pub fn pinned_blocks(&self) -> HashMap<BlockId, usize> {
+        let mut pinned_blocks = HashMap::new();
+        for block in &self.pins {
+            *pinned_blocks.entry(block.clone()).or_insert(0) += 1;
+        }
+        pinned_blocks
+    }
+
     /**
      * 指定された block を pin する
      *
      * Note: すでに pin されていた block であっても、再度 pin するような挙動をするので、unpin では必ず pin した回数分だけ unpin する必要がある
      */
     pub fn pin(&mut self, block: &BlockId) -> Result<Arc<Mutex<Buffer>>, BufferManagerError> {
-        let buffer = self.buffer_manager.pin(block)?;
+        let buffer = match self.pin_cache.as_mut().and_then(|cache| cache.take(block)) {
+            // cache にある場合は、cache が保持していた pin をそのまま使う
+            Some(buffer) => buffer,
+            None => {
+                // block を割り当てる用途の buffer に空きがない場合、cache が保持している同じ用途の buffer を手放してから pin する
+                let kind = self.buffer_manager.pool_of(block.file_name())?;
+                if self.buffer_manager.available_in(kind)? == 0 {
+                    self.release_cached(kind, 1)?;
+                }
+                self.buffer_manager.pin(block)?
+            }
+        };
         self.buffers
             .entry(block.clone())
             .or_insert_with(|| buffer.clone());
@@ -61,6 +168,35 @@ impl BufferList {
         Ok(buffer)
     }
 
+    /**
+     * filename の start 番目から count 個の連続した block をまとめて pin し、pin した block を返す
+     *
+     * BufferManager::pin_range と同じく、全ての block を pin できない場合は何も pin しない
+     * pin した block は、それぞれ unpin する必要がある
+     */
+    pub fn pin_range(
+        &mut self,
+        filename: &str,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<BlockId>, BufferListError> {
+        // cache が保持している buffer の分だけ空きが足りない場合があるので、先に同じ用途の buffer を手放しておく
+        let kind = self.buffer_manager.pool_of(filename)?;
+        let available = self.buffer_manager.available_in(kind)?;
+        if available < count {
+            self.release_cached(kind, count - available)?;
+        }
+        let buffers = self.buffer_manager.pin_range(filename, start, count)?;
+        let mut blocks = Vec::with_capacity(buffers.len());
+        for (number, buffer) in (start..).zip(buffers) {
+            let block = BlockId::new(filename, number);
+            self.buffers.entry(block.clone()).or_insert(buffer);
+            self.pins.push(block.clone());
+            blocks.push(block);
+        }
+        Ok(blocks)
+    }
+
     /**
      * 指定された block を unpin する
      *
@@ -70,8 +206,17 @@ impl BufferList {
         let entry = self.buffers.entry(block.clone());
         match entry {
             Entry::Occupied(occupied) => {
-                let buffer = occupied.get();
-                self.buffer_manager.unpin(buffer.clone())?;
+                let buffer = occupied.get().clone();
+                let is_catalog = self.pin_cache.is_some()
+                    && self.buffer_manager.pool_of(block.file_name())? == BufferPoolKind::Catalog;
+                match &mut self.pin_cache {
+                    Some(cache) if is_catalog && !cache.contains(block) => {
+                        if let Some((_, evicted)) = cache.put(block.clone(), buffer) {
+                            self.buffer_manager.unpin(evicted)?;
+                        }
+                    }
+                    _ => self.buffer_manager.unpin(buffer)?,
+                }
 
                 match self.pins.iter().position(|b| b == block) {
                     Some(pos) => {
@@ -94,26 +239,79 @@ impl BufferList {
         }
     }
 
+    /**
+     * pin している全ての block を unpin する
+     *
+     * 途中で error が起きても残りの block の unpin を続け、最後に最初の error を返す
+     * いずれの場合も呼び出し後は何も pin していない状態になるので、何度呼び出しても二重に unpin されることはない
+     */
     pub fn unpin_all(&mut self) -> Result<(), BufferListError> {
-        for block in &self.pins {
-            match self.buffers.get(block) {
-                Some(buffer) => {
-                    self.buffer_manager.unpin(buffer.clone())?;
-                }
-                None => {
-                    return Err(BufferListError::InvalidState(format!(
-                        "block {} is not pinned",
-                        block
-                    )));
-                }
+        let mut first_error = None;
+        for block in std::mem::take(&mut self.pins) {
+            let result = match self.buffers.get(&block) {
+                Some(buffer) => self
+                    .buffer_manager
+                    .unpin(buffer.clone())
+                    .map_err(BufferListError::from),
+                None => Err(BufferListError::InvalidState(format!(
+                    "block {} is not pinned",
+                    block
+                ))),
+            };
+            if let Err(err) = result {
+                first_error.get_or_insert(err);
             }
         }
-
         self.buffers.clear();
-        self.pins.clear();
+        if let Err(err) = self.clear_pin_cache() {
+            first_error.get_or_insert(err);
+        }
 
+        match first_error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    // pin cache が保持している kind 用の buffer を、長い間使われていないものから最大 count 個 buffer manager に返す
+    // 他の用途の buffer を返しても kind 用の buffer の空きは増えないので、手放さずに残しておく
+    fn release_cached(
+        &mut self,
+        kind: BufferPoolKind,
+        count: usize,
+    ) -> Result<(), BufferManagerError> {
+        let Some(cache) = self.pin_cache.as_mut() else {
+            return Ok(());
+        };
+        let buffer_manager = &self.buffer_manager;
+        for _ in 0..count {
+            let Some((_, buffer)) = cache.pop_lru_matching(|block| {
+                buffer_manager
+                    .pool_of(block.file_name())
+                    .is_ok_and(|k| k == kind)
+            }) else {
+                break;
+            };
+            buffer_manager.unpin(buffer)?;
+        }
         Ok(())
     }
+
+    // pin cache が保持している buffer を全て buffer manager に返す
+    // 途中で error が起きても残りの buffer は返し、最初の error を返す
+    fn clear_pin_cache(&mut self) -> Result<(), BufferListError> {
+        let mut result = Ok(());
+        if let Some(cache) = self.pin_cache.as_mut() {
+            while let Some((_, buffer)) = cache.pop_lru() {
+                if let Err(err) = self.buffer_manager.unpin(buffer) {
+                    if result.is_ok() {
+                        result = Err(err.into());
+                    }
+                }
+            }
+        }
+        result
+    }
 }
 
 #[cfg(test)]
@@ -123,14 +321,39 @@ mod buffer_list_test {
     use super::*;
     use tempfile::tempdir;
 
+    use crate::buffer::buffer_manager::BufferPoolSizes;
     use crate::file::file_manager::FileManager;
     use crate::log::log_manager::LogManager;
 
+    fn setup_buffer_manager(dir_path: &path::Path) -> Arc<BufferManager> {
+        let file_manager = Arc::new(FileManager::new(dir_path, 400));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        Arc::new(BufferManager::new(file_manager, log_manager, 3, Some(10)))
+    }
+
     fn setup_buffer_list(dir_path: &path::Path) -> BufferList {
+        BufferList::new(setup_buffer_manager(dir_path))
+    }
+
+    // pin cache は catalog の block だけを保持するので、testfile を catalog 用の 3 つの buffer に割り当てる
+    fn setup_catalog_buffer_manager(dir_path: &path::Path) -> Arc<BufferManager> {
         let file_manager = Arc::new(FileManager::new(dir_path, 400));
         let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
-        let buffer_manager = Arc::new(BufferManager::new(file_manager, log_manager, 3, Some(10)));
-        BufferList::new(buffer_manager)
+        let sizes = BufferPoolSizes {
+            data: 0,
+            catalog: 3,
+            index: 0,
+        };
+        let buffer_manager = Arc::new(BufferManager::with_pools(
+            file_manager,
+            log_manager,
+            sizes,
+            Some(10),
+        ));
+        buffer_manager
+            .register_file("testfile", BufferPoolKind::Catalog)
+            .unwrap();
+        buffer_manager
     }
 
     #[test]
@@ -158,5 +381,152 @@ mod buffer_list_test {
         buffer_list.pin(&block).unwrap();
 
         assert!(buffer_list.unpin_all().is_ok());
+        // 2 回目の unpin_all は何もしない
+        assert!(buffer_list.unpin_all().is_ok());
+        assert_eq!(buffer_list.buffer_manager.available().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_pin_range_uses_pin_cache_buffers() {
+        let dir = tempdir().unwrap();
+        let mut buffer_list =
+            BufferList::with_pin_cache(setup_catalog_buffer_manager(dir.path()), 2);
+        let block0 = BlockId::new("testfile", 0);
+        let block5 = BlockId::new("testfile", 5);
+
+        // cache に 2 つの buffer が残っている
+        buffer_list.pin(&block0).unwrap();
+        buffer_list.pin(&block5).unwrap();
+        buffer_list.unpin(&block0).unwrap();
+        buffer_list.unpin(&block5).unwrap();
+        assert_eq!(buffer_list.buffer_manager.available().unwrap(), 1);
+
+        // cache の buffer を手放せば 3 つの block をまとめて pin できる
+        let blocks = buffer_list.pin_range("testfile", 0, 3).unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(buffer_list.pin_count(&block0), 1);
+        assert_eq!(buffer_list.buffer_manager.available().unwrap(), 0);
+        // それ以上は pin できず、何も pin されない
+        assert!(buffer_list.pin_range("otherfile", 0, 1).is_err());
+        for block in &blocks {
+            buffer_list.unpin(block).unwrap();
+        }
+        assert!(buffer_list.pinned_blocks().is_empty());
+    }
+
+    #[test]
+    fn test_pin_count() {
+        let dir = tempdir().unwrap();
+        let mut buffer_list = setup_buffer_list(dir.path());
+        let block0 = BlockId::new("testfile", 0);
+        let block1 = BlockId::new("testfile", 1);
+
+        buffer_list.pin(&block0).unwrap();
+        buffer_list.pin(&block0).unwrap();
+        buffer_list.pin(&block1).unwrap();
+        assert_eq!(buffer_list.pin_count(&block0), 2);
+        assert_eq!(buffer_list.pin_count(&BlockId::new("testfile", 2)), 0);
+        assert_eq!(
+            buffer_list.pinned_blocks(),
+            HashMap::from([(block0.clone(), 2), (block1.clone(), 1)])
+        );
+
+        buffer_list.unpin(&block0).unwrap();
+        assert_eq!(buffer_list.pin_count(&block0), 1);
+        buffer_list.unpin_all().unwrap();
+        assert!(buffer_list.pinned_blocks().is_empty());
+    }
+
+    #[test]
+    fn test_pin_cache() {
+        let dir = tempdir().unwrap();
+        let buffer_manager = setup_catalog_buffer_manager(dir.path());
+        let mut buffer_list = BufferList::with_pin_cache(buffer_manager.clone(), 1);
+        let block0 = BlockId::new("testfile", 0);
+        let block1 = BlockId::new("testfile", 1);
+
+        buffer_list.pin(&block0).unwrap();
+        buffer_list.unpin(&block0).unwrap();
+        // unpin しても cache に残っているので buffer manager 上では pin されたまま
+        assert_eq!(buffer_manager.available().unwrap(), 2);
+        assert!(buffer_list.get_buffer(&block0).is_none());
+        // 同じ block を再度 pin しても新しく buffer は消費しない
+        buffer_list.pin(&block0).unwrap();
+        assert_eq!(buffer_manager.available().unwrap(), 2);
+        assert_eq!(buffer_list.pin_cache_hits(), 1);
+        buffer_list.unpin(&block0).unwrap();
+
+        // 容量を超えると最も古い block が追い出される
+        buffer_list.pin(&block1).unwrap();
+        buffer_list.unpin(&block1).unwrap();
+        assert_eq!(buffer_manager.available().unwrap(), 2);
+
+        // unpin_all で cache も空になる
+        buffer_list.unpin_all().unwrap();
+        assert_eq!(buffer_manager.available().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_pin_cache_releases_buffer_when_pool_is_exhausted() {
+        let dir = tempdir().unwrap();
+        let buffer_manager = setup_catalog_buffer_manager(dir.path());
+        let mut buffer_list = BufferList::with_pin_cache(buffer_manager.clone(), 1);
+
+        buffer_list.pin(&BlockId::new("testfile", 0)).unwrap();
+        buffer_list.unpin(&BlockId::new("testfile", 0)).unwrap();
+        buffer_list.pin(&BlockId::new("testfile", 1)).unwrap();
+        buffer_list.pin(&BlockId::new("testfile", 2)).unwrap();
+        // 空きがない状態でも、cache が保持している buffer を手放して pin できる
+        assert!(buffer_list.pin(&BlockId::new("testfile", 3)).is_ok());
+    }
+
+    #[test]
+    fn test_pin_cache_releases_buffer_of_same_pool() {
+        let dir = tempdir().unwrap();
+        let file_manager = Arc::new(FileManager::new(dir.path(), 400));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let sizes = BufferPoolSizes {
+            data: 2,
+            catalog: 1,
+            index: 0,
+        };
+        let buffer_manager = Arc::new(BufferManager::with_pools(
+            file_manager,
+            log_manager,
+            sizes,
+            Some(10),
+        ));
+        buffer_manager
+            .register_file("catalog", BufferPoolKind::Catalog)
+            .unwrap();
+        let mut buffer_list = BufferList::with_pin_cache(buffer_manager.clone(), 2);
+        let catalog0 = BlockId::new("catalog", 0);
+        let data0 = BlockId::new("testfile", 0);
+
+        buffer_list.pin(&catalog0).unwrap();
+        buffer_list.unpin(&catalog0).unwrap();
+        buffer_list.pin(&data0).unwrap();
+        buffer_list.unpin(&data0).unwrap();
+        // catalog の block だけが cache に残り、data の block はすぐに buffer manager に返す
+        assert_eq!(
+            buffer_manager
+                .available_in(BufferPoolKind::Catalog)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            buffer_manager.available_in(BufferPoolKind::Data).unwrap(),
+            2
+        );
+        buffer_list.pin(&data0).unwrap();
+        buffer_list.unpin(&data0).unwrap();
+        assert_eq!(buffer_list.pin_cache_hits(), 0);
+
+        // catalog 用の buffer に空きがなければ、cache が保持しているものを手放して pin する
+        assert!(buffer_list.pin(&BlockId::new("catalog", 1)).is_ok());
+        // data 用の buffer はすべて空いているので、pin_range で cache の buffer を手放す必要はない
+        let blocks = buffer_list.pin_range("testfile", 1, 2).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(buffer_manager.available().unwrap(), 0);
     }
 }
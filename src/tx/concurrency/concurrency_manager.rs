@@ -1,10 +1,11 @@
 use std::collections::hash_map::Entry::{Occupied, Vacant};
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
 
 use crate::file::blockid::BlockId;
 
-use super::lock_table::{LockTable, LockTableError};
+use super::lock_table::{BlockLockCounts, LockTable, LockTableError};
 
 /**
  * 一つの transaction の中で扱われる lock の管理を行い、並行実行制御を行うクラス
@@ -14,6 +15,14 @@ use super::lock_table::{LockTable, LockTableError};
 pub struct ConcurrencyManager {
     lock_table: Arc<LockTable>,
     locks: HashMap<BlockId, LockType>,
+    // lock escalation で取得した file 単位の lock
+    file_locks: HashMap<String, LockType>,
+    // file ごとの、locks に入っている block の lock の数
+    block_lock_counts: HashMap<String, usize>,
+    // lock escalation に失敗した file。同じ transaction の中では再び試さない
+    escalation_failed: HashSet<String>,
+    // この transaction が append した block
+    appended_blocks: HashMap<String, HashSet<usize>>,
 }
 
 impl ConcurrencyManager {
@@ -21,24 +30,42 @@ impl ConcurrencyManager {
         ConcurrencyManager {
             lock_table,
             locks: HashMap::new(),
+            file_locks: HashMap::new(),
+            block_lock_counts: HashMap::new(),
+            escalation_failed: HashSet::new(),
+            appended_blocks: HashMap::new(),
         }
     }
 
     // 共有ロックを取得
     pub fn slock(&mut self, block: &BlockId) -> Result<(), LockTableError> {
+        // file 単位の lock を持っていれば、その file のすべての block の lock を持っている
+        if self.file_locks.contains_key(block.file_name()) {
+            return Ok(());
+        }
         match self.locks.get(block) {
             Some(_) => Ok(()),
             None => {
                 // まだ lock を取っていなかったら lock を取って登録
                 self.lock_table.slock(block)?;
                 self.locks.insert(block.clone(), LockType::Shared);
-                Ok(())
+                self.on_block_locked(block)
             }
         }
     }
 
     // 排他的ロックを取得
     pub fn xlock(&mut self, block: &BlockId) -> Result<(), LockTableError> {
+        match self.file_locks.get_mut(block.file_name()) {
+            Some(LockType::Exclusive) => return Ok(()),
+            Some(file_lock) => {
+                // file 単位の slock を持っている場合は、file 単位の xlock に昇格する
+                self.lock_table.promote_file_lock(block.file_name())?;
+                *file_lock = LockType::Exclusive;
+                return Ok(());
+            }
+            None => {}
+        }
         let entry = self.locks.entry(block.clone());
         match entry {
             Occupied(occupied) => {
@@ -59,9 +86,152 @@ impl ConcurrencyManager {
             Vacant(vacant) => {
                 self.lock_table.xlock(block)?;
                 vacant.insert(LockType::Exclusive);
-                Ok(())
+                self.on_block_locked(block)
+            }
+        }
+    }
+
+    /// block の lock を新しく取得した後に呼び出す
+    /// 同じ file の block の lock が閾値に達したら、file 単位の lock にまとめる
+    fn on_block_locked(&mut self, block: &BlockId) -> Result<(), LockTableError> {
+        let file_name = block.file_name();
+        let count = self
+            .block_lock_counts
+            .entry(file_name.to_string())
+            .or_default();
+        *count += 1;
+        match self.lock_table.escalation_threshold() {
+            Some(threshold)
+                if *count >= threshold && !self.escalation_failed.contains(file_name) =>
+            {
+                self.escalate(file_name)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// file_name の block の lock を、file 単位の lock 1 つにまとめる
+    /// 他の transaction が同じ file の block の lock を持っていてまとめられない場合は、block の lock のまま続ける
+    fn escalate(&mut self, file_name: &str) -> Result<(), LockTableError> {
+        let blocks = self
+            .locks
+            .iter()
+            .filter(|(block, _)| block.file_name() == file_name)
+            .map(|(block, lock_type)| (block.clone(), matches!(lock_type, LockType::Exclusive)))
+            .collect::<Vec<_>>();
+        let own = BlockLockCounts {
+            shared: blocks.iter().filter(|(_, exclusive)| !exclusive).count(),
+            exclusive: blocks.iter().filter(|(_, exclusive)| *exclusive).count(),
+        };
+        // 書き込んだ block があれば xlock、読んだだけなら slock にまとめる
+        let exclusive = own.exclusive > 0;
+        if !self.lock_table.try_escalate(file_name, exclusive, own)? {
+            self.escalation_failed.insert(file_name.to_string());
+            return Ok(());
+        }
+        for (block, _) in &blocks {
+            self.lock_table.unlock(block)?;
+            self.locks.remove(block);
+        }
+        self.block_lock_counts.remove(file_name);
+        let lock_type = if exclusive {
+            LockType::Exclusive
+        } else {
+            LockType::Shared
+        };
+        self.file_locks.insert(file_name.to_string(), lock_type);
+        Ok(())
+    }
+
+    /// 現在持っている lock の一覧を返す
+    pub fn lock_state(&self) -> LockState {
+        let mut state = LockState::default();
+        for (block, lock_type) in &self.locks {
+            match lock_type {
+                LockType::Shared => state.shared.push(block.clone()),
+                LockType::Exclusive => state.exclusive.push(block.clone()),
             }
         }
+        for (file_name, lock_type) in &self.file_locks {
+            match lock_type {
+                LockType::Shared => state.shared_files.push(file_name.clone()),
+                LockType::Exclusive => state.exclusive_files.push(file_name.clone()),
+            }
+        }
+        // HashMap の順番に依存しないよう、表示と同じ順に並べる
+        state.shared.sort_by_key(|block| block.to_string());
+        state.exclusive.sort_by_key(|block| block.to_string());
+        state.shared_files.sort();
+        state.exclusive_files.sort();
+        state
+    }
+
+    // file_name への append の間だけ取得する latch を返す
+    pub fn append_latch(&self, file_name: &str) -> Arc<Mutex<()>> {
+        self.lock_table.append_latch(file_name)
+    }
+
+    // append した block の xlock を取り、transaction が終わるまで他の transaction からは見えないようにする
+    pub fn lock_appended(&mut self, block: &BlockId) -> Result<(), LockTableError> {
+        self.xlock(block)?;
+        self.lock_table.add_pending_append(block);
+        self.appended_blocks
+            .entry(block.file_name().to_string())
+            .or_default()
+            .insert(block.number());
+        Ok(())
+    }
+
+    // file の長さが length の時に、この transaction から読める block の数を返す
+    pub fn visible_length(&self, file_name: &str, length: usize) -> usize {
+        let empty = HashSet::new();
+        let own = self.appended_blocks.get(file_name).unwrap_or(&empty);
+        self.lock_table.visible_length(file_name, length, own)
+    }
+
+    // この transaction が append した block であれば true を返す
+    pub fn is_appended(&self, block: &BlockId) -> bool {
+        self.appended_blocks
+            .get(block.file_name())
+            .is_some_and(|numbers| numbers.contains(&block.number()))
+    }
+
+    // file の末尾まで読んだ後に、transaction の終わりまで他の transaction が append できないようにする
+    // 他の transaction が append してまだ終わっていない block があれば、その block の slock を取って終わるのを待つ
+    pub fn lock_end_of_file(&mut self, file_name: &str) -> Result<(), LockTableError> {
+        self.slock(&BlockId::new_end_of_file(file_name))?;
+        let empty = HashSet::new();
+        let own = self.appended_blocks.get(file_name).unwrap_or(&empty);
+        for block in self.lock_table.pending_appends(file_name, own) {
+            self.slock(&block)?;
+        }
+        Ok(())
+    }
+
+    // append の間だけ end of file の block の xlock を取る。append が終わったら end_append を呼ぶ
+    // 末尾まで読んだ transaction がいる場合は、その transaction が終わるまで待つ
+    pub fn begin_append(&mut self, file_name: &str) -> Result<(), LockTableError> {
+        let block = BlockId::new_end_of_file(file_name);
+        if self.file_locks.contains_key(file_name) {
+            return self.xlock(&block);
+        }
+        match self.locks.get(&block) {
+            // 自分も末尾まで読んでいる場合は、一時的に xlock に昇格する
+            Some(_) => self.lock_table.promote_to_xlock(&block),
+            None => self.lock_table.xlock(&block),
+        }
+    }
+
+    // begin_append で取った lock を元に戻す
+    pub fn end_append(&mut self, file_name: &str) -> Result<(), LockTableError> {
+        let block = BlockId::new_end_of_file(file_name);
+        if self.file_locks.contains_key(file_name) {
+            return Ok(());
+        }
+        match self.locks.get(&block) {
+            Some(_) => self.lock_table.demote_to_slock(&block),
+            None => self.lock_table.unlock(&block),
+        }
     }
 
     // 取得していたすべての lock を解放
@@ -69,7 +239,19 @@ impl ConcurrencyManager {
         for block in self.locks.keys() {
             self.lock_table.unlock(block)?;
         }
+        for file_name in self.file_locks.keys() {
+            self.lock_table.unlock_file(file_name)?;
+        }
         self.locks.clear();
+        self.file_locks.clear();
+        self.block_lock_counts.clear();
+        self.escalation_failed.clear();
+        for (file_name, numbers) in self.appended_blocks.drain() {
+            for number in numbers {
+                self.lock_table
+                    .remove_pending_append(&BlockId::new(&file_name, number));
+            }
+        }
         Ok(())
     }
 }
@@ -79,6 +261,86 @@ enum LockType {
     Exclusive,
 }
 
+/**
+ * ある transaction が持っている lock の一覧
+ *
+ * lock の取得が timeout した場合に、どの transaction がどの block を握っているのかを調べるために使う
+ */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LockState {
+    /// slock だけを持っている (読んだだけの) block
+    pub shared: Vec<BlockId>,
+    /// xlock を持っている (書き込んだ) block
+    pub exclusive: Vec<BlockId>,
+    /// lock escalation で file 単位の slock を持っている file
+    pub shared_files: Vec<String>,
+    /// lock escalation で file 単位の xlock を持っている file
+    pub exclusive_files: Vec<String>,
+}
+
+impl LockState {
+    /// other と同時には持てない lock の block を返す
+    /// どちらかが xlock (file 単位の xlock を含む) を持っている block が該当する
+    pub fn conflicts_with(&self, other: &LockState) -> Vec<BlockId> {
+        let mut conflicts = vec![];
+        for (lhs, rhs) in [(self, other), (other, self)] {
+            conflicts.extend(
+                lhs.exclusive
+                    .iter()
+                    .filter(|block| rhs.holds(block))
+                    .cloned(),
+            );
+            conflicts.extend(
+                lhs.shared
+                    .iter()
+                    .filter(|block| rhs.exclusive_files.iter().any(|f| f == block.file_name()))
+                    .cloned(),
+            );
+        }
+        conflicts.sort_by_key(|block| block.to_string());
+        conflicts.dedup();
+        conflicts
+    }
+
+    /// block の lock を持っているかどうか
+    fn holds(&self, block: &BlockId) -> bool {
+        self.shared.contains(block)
+            || self.exclusive.contains(block)
+            || self
+                .shared_files
+                .iter()
+                .chain(&self.exclusive_files)
+                .any(|file_name| file_name == block.file_name())
+    }
+}
+
+impl Display for LockState {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        let join = |blocks: &[BlockId]| {
+            blocks
+                .iter()
+                .map(|block| block.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        write!(
+            f,
+            "slock: {{{}}}, xlock: {{{}}}",
+            join(&self.shared),
+            join(&self.exclusive)
+        )?;
+        if !self.shared_files.is_empty() || !self.exclusive_files.is_empty() {
+            write!(
+                f,
+                ", file slock: {{{}}}, file xlock: {{{}}}",
+                self.shared_files.join(", "),
+                self.exclusive_files.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,4 +370,97 @@ mod tests {
         assert!(cm1.slock(&block).is_ok());
         assert!(cm1.xlock(&block).is_ok());
     }
+
+    #[test]
+    fn test_lock_state() {
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        let mut cm1 = ConcurrencyManager::new(lock_table.clone());
+        let mut cm2 = ConcurrencyManager::new(lock_table);
+
+        let (block0, block1, block2) = (
+            BlockId::new("testfile", 0),
+            BlockId::new("testfile", 1),
+            BlockId::new("testfile", 2),
+        );
+        cm1.slock(&block1).unwrap();
+        cm1.slock(&block0).unwrap();
+        cm1.xlock(&block2).unwrap();
+        // slock から昇格した block は xlock として数える
+        cm1.xlock(&block1).unwrap();
+
+        let state = cm1.lock_state();
+        assert_eq!(state.shared, vec![block0.clone()]);
+        assert_eq!(state.exclusive, vec![block1.clone(), block2.clone()]);
+        assert_eq!(
+            state.to_string(),
+            "slock: {[file testfile, block 0]}, xlock: {[file testfile, block 1], [file testfile, block 2]}"
+        );
+
+        // cm2 は block0 を共有できるが、block2 は cm1 と取り合いになる
+        cm2.slock(&block0).unwrap();
+        assert!(cm2.slock(&block2).is_err());
+        let state2 = cm2.lock_state();
+        assert!(state.conflicts_with(&state2).is_empty());
+        let wanted = LockState {
+            shared: vec![block0, block2.clone()],
+            ..Default::default()
+        };
+        assert_eq!(state.conflicts_with(&wanted), vec![block2.clone()]);
+        assert_eq!(wanted.conflicts_with(&state), vec![block2]);
+
+        cm1.release().unwrap();
+        assert_eq!(cm1.lock_state(), LockState::default());
+    }
+
+    #[test]
+    fn test_lock_escalation() {
+        let lock_table = Arc::new(LockTable::new(Some(10)).with_escalation_threshold(3));
+        let mut cm1 = ConcurrencyManager::new(lock_table.clone());
+        let mut cm2 = ConcurrencyManager::new(lock_table.clone());
+        let block = |n| BlockId::new("testfile", n);
+
+        // 3 つ目の slock で file 単位の slock にまとめる
+        for n in 0..3 {
+            cm1.slock(&block(n)).unwrap();
+        }
+        let state = cm1.lock_state();
+        assert!(state.shared.is_empty());
+        assert_eq!(state.shared_files, vec!["testfile".to_string()]);
+        assert_eq!(lock_table.escalation_count(), 1);
+        assert_eq!(lock_table.block_lock_counts("testfile").shared, 0);
+        // まとめた後は、lock table に問い合わせずに同じ file の block を読める
+        cm1.slock(&block(10)).unwrap();
+        assert_eq!(lock_table.block_lock_counts("testfile").shared, 0);
+
+        // cm2 は読めるが書けない
+        cm2.slock(&block(5)).unwrap();
+        assert!(cm2.xlock(&block(6)).is_err());
+        assert_eq!(
+            cm1.lock_state().conflicts_with(&LockState {
+                exclusive: vec![block(6)],
+                ..Default::default()
+            }),
+            vec![block(6)]
+        );
+        // cm2 が読んでいる間は、cm1 も書けない
+        assert!(cm1.xlock(&block(0)).is_err());
+        cm2.release().unwrap();
+        cm1.xlock(&block(0)).unwrap();
+        assert_eq!(
+            cm1.lock_state().exclusive_files,
+            vec!["testfile".to_string()]
+        );
+        assert!(cm2.slock(&block(5)).is_err());
+        cm1.release().unwrap();
+        cm2.slock(&block(5)).unwrap();
+
+        // 他の transaction が xlock を持っている file ではまとめず、block の lock のまま続ける
+        cm2.xlock(&block(9)).unwrap();
+        for n in 0..4 {
+            cm1.slock(&block(n)).unwrap();
+        }
+        assert_eq!(cm1.lock_state().shared.len(), 4);
+        assert!(cm1.lock_state().shared_files.is_empty());
+        assert_eq!(lock_table.escalation_count(), 1);
+    }
 }
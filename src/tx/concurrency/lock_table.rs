@@ -1,4 +1,6 @@
-use std::collections::VecDeque;
+use std::collections::{BTreeSet, HashSet, VecDeque};
+use std::hash::Hash;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, park_timeout};
 use std::time;
@@ -8,9 +10,16 @@ use thiserror::Error;
 
 use crate::file::blockid::BlockId;
 
+type WaitingQueue = Arc<Mutex<VecDeque<thread::Thread>>>;
+
 /**
  * ブロックごとの Lock を管理するクラス
  *
+ * 同じ file の block の lock をたくさん持った transaction は、それらを file 単位の lock 1 つにまとめられる (lock escalation)
+ * file 単位の lock は、その file のすべての block の lock を持っているものとして扱う
+ * file の長さは、append の間だけ取得する latch と、終わっていない append の記録で守る
+ * ただし末尾まで読んだ scan は phantom を防ぐために end of file の block の slock を取るので、append はその間だけ end of file の block の xlock を取る
+ *
  * プログラム全体で一つしかない想定
  */
 pub struct LockTable {
@@ -18,9 +27,34 @@ pub struct LockTable {
     locks: DashMap<BlockId, Arc<Mutex<Lock>>>,
     // block ごとの、lock を待っている thread のリスト
     // lock の開放を待っている場合、自分の thread をここに入れてから park する
-    queues: DashMap<BlockId, Arc<Mutex<VecDeque<thread::Thread>>>>,
+    queues: DashMap<BlockId, WaitingQueue>,
     // ロックを取得する最大の時間 (ms)
     max_waiting_time_ms: u64,
+    // file 単位の Lock を管理するテーブル
+    file_locks: DashMap<String, Arc<Mutex<Lock>>>,
+    // file ごとの、file 単位の lock に関わる変化を待っている thread のリスト
+    file_queues: DashMap<String, WaitingQueue>,
+    // file ごとの、block 単位で取得されている lock の数
+    block_lock_counts: DashMap<String, BlockLockCounts>,
+    // 1 つの transaction が同じ file の block の lock をこの数だけ持ったら file 単位の lock にまとめる
+    // None の場合はまとめない
+    escalation_threshold: Option<usize>,
+    // lock escalation を行った回数
+    escalation_count: AtomicUsize,
+    // file ごとの、append の間だけ取得する latch
+    // transaction の終わりまでは持たないので、append する transaction と size を読む transaction が互いを待たない
+    append_latches: DashMap<String, Arc<Mutex<()>>>,
+    // file ごとの、commit / rollback されていない transaction が append した block の番号
+    pending_appends: DashMap<String, BTreeSet<usize>>,
+}
+
+/// ある file の block に対して取得されている lock の数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockLockCounts {
+    /// slock の数。同じ block の slock を複数の transaction が持っている場合はその数だけ数える
+    pub shared: usize,
+    /// xlock を持たれている block の数
+    pub exclusive: usize,
 }
 
 #[derive(Error, Debug)]
@@ -42,9 +76,31 @@ impl LockTable {
                 Some(ms) => ms,
                 None => MAX_WAITING_TIME_MS,
             },
+            file_locks: DashMap::new(),
+            file_queues: DashMap::new(),
+            block_lock_counts: DashMap::new(),
+            escalation_threshold: None,
+            escalation_count: AtomicUsize::new(0),
+            append_latches: DashMap::new(),
+            pending_appends: DashMap::new(),
         }
     }
 
+    /// 1 つの transaction が同じ file の block の lock を threshold 個持ったら、file 単位の lock にまとめるようにする
+    pub fn with_escalation_threshold(mut self, threshold: usize) -> LockTable {
+        self.escalation_threshold = Some(threshold);
+        self
+    }
+
+    pub fn escalation_threshold(&self) -> Option<usize> {
+        self.escalation_threshold
+    }
+
+    /// これまでに lock escalation を行った回数
+    pub fn escalation_count(&self) -> usize {
+        self.escalation_count.load(Ordering::Relaxed)
+    }
+
     /**
      * 共有ロックを取得する
      */
@@ -63,6 +119,15 @@ impl LockTable {
             match *lock {
                 Lock::Shared(ref_count) => {
                     *lock = Lock::Shared(ref_count + 1);
+                    drop(lock);
+                    drop(lock_entry_inner);
+                    self.update_block_lock_counts(blk, |counts| counts.shared += 1);
+                    // 他の transaction が file 単位の xlock を持っている場合は、取った lock を戻して待つ
+                    if self.conflicts_with_file_lock(blk, false)? {
+                        self.unlock(blk)?;
+                        self.wait_for_file_lock(blk, false)?;
+                        continue;
+                    }
                     return Ok(());
                 }
                 Lock::Exclusive => {
@@ -122,6 +187,13 @@ impl LockTable {
                 dashmap::mapref::entry::Entry::Vacant(_) => {
                     let lock = Arc::new(Mutex::new(Lock::Exclusive));
                     lock_entry.insert(lock);
+                    self.update_block_lock_counts(blk, |counts| counts.exclusive += 1);
+                    // 他の transaction が file 単位の lock を持っている場合は、取った lock を戻して待つ
+                    if self.conflicts_with_file_lock(blk, true)? {
+                        self.unlock(blk)?;
+                        self.wait_for_file_lock(blk, true)?;
+                        continue;
+                    }
                     return Ok(());
                 }
             }
@@ -152,6 +224,18 @@ impl LockTable {
                 match *lock {
                     Lock::Shared(1) => {
                         *lock = Lock::Exclusive;
+                        drop(lock);
+                        drop(lock_entry);
+                        self.update_block_lock_counts(blk, |counts| {
+                            counts.shared -= 1;
+                            counts.exclusive += 1;
+                        });
+                        // 他の transaction が file 単位の lock を持っている場合は、slock に戻して待つ
+                        if self.conflicts_with_file_lock(blk, true)? {
+                            self.demote_to_slock(blk)?;
+                            self.wait_for_file_lock(blk, true)?;
+                            continue;
+                        }
                         return Ok(());
                     }
                     Lock::Shared(_) | Lock::Exclusive => {
@@ -200,6 +284,7 @@ impl LockTable {
                     ))
                 })?;
                 let mut should_remove = false;
+                let was_exclusive = matches!(*lock, Lock::Exclusive);
                 match *lock {
                     Lock::Shared(1) | Lock::Exclusive => {
                         should_remove = true;
@@ -209,6 +294,13 @@ impl LockTable {
                     }
                 };
                 drop(lock);
+                self.update_block_lock_counts(blk, |counts| {
+                    if was_exclusive {
+                        counts.exclusive -= 1;
+                    } else {
+                        counts.shared -= 1;
+                    }
+                });
 
                 if should_remove {
                     lock_entry.remove();
@@ -233,7 +325,8 @@ impl LockTable {
                         }
                     }
                 }
-                Ok(())
+                // file 単位の lock を取ろうとしている thread は block の lock の数の変化を待っている
+                wake_all(&self.file_queues, blk.file_name())
             }
             dashmap::mapref::entry::Entry::Vacant(_) => Err(LockTableError::General(
                 "unlock method must be called after the specified block is locked".into(),
@@ -241,12 +334,307 @@ impl LockTable {
         }
     }
 
-    fn get_or_create_queue(&self, blk: &BlockId) -> Arc<Mutex<VecDeque<thread::Thread>>> {
-        self.queues
-            .entry(blk.clone())
-            .or_insert_with(|| Arc::new(Mutex::new(VecDeque::new())))
+    /**
+     * file_name の file 単位の lock を、待たずに取得することを試みる
+     *
+     * 呼び出し元の transaction が持っている、その file の block の lock の数を own で渡す
+     * 他の transaction がその file の block に同時に持てない lock を持っている場合は、何もせず false を返す
+     * 取得できた場合、呼び出し元は own の block の lock を unlock する
+     */
+    pub fn try_escalate(
+        &self,
+        file_name: &str,
+        exclusive: bool,
+        own: BlockLockCounts,
+    ) -> Result<bool, LockTableError> {
+        {
+            let entry = self.file_locks.entry(file_name.to_string());
+            match entry {
+                dashmap::mapref::entry::Entry::Vacant(vacant) => {
+                    let lock = if exclusive {
+                        Lock::Exclusive
+                    } else {
+                        Lock::Shared(1)
+                    };
+                    vacant.insert(Arc::new(Mutex::new(lock)));
+                }
+                dashmap::mapref::entry::Entry::Occupied(occupied) => {
+                    let mut lock = occupied.get().lock().map_err(|_| {
+                        LockTableError::Lock(format!(
+                            "failed to acquire the file lock value for {}",
+                            file_name
+                        ))
+                    })?;
+                    match *lock {
+                        Lock::Shared(ref_count) if !exclusive => {
+                            *lock = Lock::Shared(ref_count + 1)
+                        }
+                        _ => return Ok(false),
+                    }
+                }
+            }
+        }
+        // file 単位の lock を登録してから block の lock の数を確認するので、
+        // 同時に block の lock を取ろうとした thread とは、少なくとも一方が相手の lock に気づく
+        let counts = self.block_lock_counts(file_name);
+        let others = BlockLockCounts {
+            shared: counts.shared.saturating_sub(own.shared),
+            exclusive: counts.exclusive.saturating_sub(own.exclusive),
+        };
+        if others.exclusive > 0 || (exclusive && others.shared > 0) {
+            self.unlock_file(file_name)?;
+            return Ok(false);
+        }
+        self.escalation_count.fetch_add(1, Ordering::Relaxed);
+        Ok(true)
+    }
+
+    /**
+     * file 単位の slock を持っていた状態から、file 単位の xlock を取得する
+     *
+     * 他の transaction がその file の block の lock か、file 単位の slock を持っている場合は待つ
+     */
+    pub fn promote_file_lock(&self, file_name: &str) -> Result<(), LockTableError> {
+        let start = time::Instant::now();
+        while get_waiting_time(start) < self.max_waiting_time_ms {
+            // 確認している間に解放されても起こしてもらえるよう、先に queue に入っておく
+            enqueue(&get_or_create_queue(&self.file_queues, file_name))?;
+            {
+                let lock = self.file_locks.get(file_name).ok_or_else(|| {
+                    LockTableError::General(
+                        "promote_file_lock method must be called after the file is shared locked"
+                            .into(),
+                    )
+                })?;
+                let mut lock = lock.lock().map_err(|_| {
+                    LockTableError::Lock(format!(
+                        "failed to acquire the file lock value for {}",
+                        file_name
+                    ))
+                })?;
+                if matches!(*lock, Lock::Shared(1)) {
+                    *lock = Lock::Exclusive;
+                    drop(lock);
+                    let counts = self.block_lock_counts(file_name);
+                    if counts.shared == 0 && counts.exclusive == 0 {
+                        return Ok(());
+                    }
+                    // まだ block の lock を持っている transaction がいるので、slock に戻して待つ
+                    if let Some(lock) = self.file_locks.get(file_name) {
+                        *lock.lock().map_err(|_| {
+                            LockTableError::Lock(format!(
+                                "failed to acquire the file lock value for {}",
+                                file_name
+                            ))
+                        })? = Lock::Shared(1);
+                    }
+                }
+            }
+            park_timeout(time::Duration::from_millis(self.max_waiting_time_ms));
+        }
+        Err(LockTableError::Timeout(
+            "failed to acquire exclusive file lock within the time limit".into(),
+        ))
+    }
+
+    /// try_escalate で取得した file 単位の lock を解放する
+    pub fn unlock_file(&self, file_name: &str) -> Result<(), LockTableError> {
+        let entry = self.file_locks.entry(file_name.to_string());
+        match entry {
+            dashmap::mapref::entry::Entry::Occupied(entry) => {
+                let mut lock = entry.get().lock().map_err(|_| {
+                    LockTableError::Lock(format!(
+                        "failed to unlock the file lock value for {}",
+                        file_name
+                    ))
+                })?;
+                let should_remove = match *lock {
+                    Lock::Shared(1) | Lock::Exclusive => true,
+                    Lock::Shared(ref_count) => {
+                        *lock = Lock::Shared(ref_count - 1);
+                        false
+                    }
+                };
+                drop(lock);
+                if should_remove {
+                    entry.remove();
+                }
+            }
+            dashmap::mapref::entry::Entry::Vacant(_) => {
+                return Err(LockTableError::General(
+                    "unlock_file method must be called after the file is locked".into(),
+                ))
+            }
+        }
+        wake_all(&self.file_queues, file_name)
+    }
+
+    /// file_name への append と長さの読み取りを直列化する latch を返す
+    pub fn append_latch(&self, file_name: &str) -> Arc<Mutex<()>> {
+        self.append_latches
+            .entry(file_name.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(())))
             .clone()
     }
+
+    /// まだ commit / rollback されていない transaction が blk を append したことを記録する
+    pub fn add_pending_append(&self, blk: &BlockId) {
+        self.pending_appends
+            .entry(blk.file_name().to_string())
+            .or_default()
+            .insert(blk.number());
+    }
+
+    /// blk を append した transaction が終わったので、他の transaction からも読めるようにする
+    pub fn remove_pending_append(&self, blk: &BlockId) {
+        if let Some(mut blocks) = self.pending_appends.get_mut(blk.file_name()) {
+            blocks.remove(&blk.number());
+        }
+        self.pending_appends
+            .remove_if(blk.file_name(), |_, blocks| blocks.is_empty());
+    }
+
+    /**
+     * file の長さが length の時に、transaction から読める block の数を返す
+     *
+     * own 以外の transaction が append して終わっていない block より前の block と、own の block までを読める
+     * 読む側はこの長さまでを読むので、append した transaction の xlock を待たない
+     * (own の block より前に他の transaction の block がある場合、その block を読むときには xlock を待つ)
+     */
+    pub fn visible_length(&self, file_name: &str, length: usize, own: &HashSet<usize>) -> usize {
+        let Some(blocks) = self.pending_appends.get(file_name) else {
+            return length;
+        };
+        let committed = blocks
+            .iter()
+            .find(|number| !own.contains(number))
+            .map_or(length, |number| (*number).min(length));
+        let appended = own.iter().max().map_or(0, |number| number + 1);
+        committed.max(appended).min(length)
+    }
+
+    /// own 以外の transaction が append して、まだ終わっていない file_name の block を返す
+    pub fn pending_appends(&self, file_name: &str, own: &HashSet<usize>) -> Vec<BlockId> {
+        self.pending_appends
+            .get(file_name)
+            .map(|blocks| {
+                blocks
+                    .iter()
+                    .filter(|number| !own.contains(number))
+                    .map(|number| BlockId::new(file_name, *number))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// file_name の block に対して取得されている lock の数を返す
+    pub fn block_lock_counts(&self, file_name: &str) -> BlockLockCounts {
+        self.block_lock_counts
+            .get(file_name)
+            .map(|counts| *counts)
+            .unwrap_or_default()
+    }
+
+    fn update_block_lock_counts(&self, blk: &BlockId, update: impl FnOnce(&mut BlockLockCounts)) {
+        let mut counts = self
+            .block_lock_counts
+            .entry(blk.file_name().to_string())
+            .or_default();
+        update(&mut counts);
+    }
+
+    /// blk の file に、blk に対して取得した lock と同時に持てない file 単位の lock があるかどうか
+    fn conflicts_with_file_lock(
+        &self,
+        blk: &BlockId,
+        exclusive: bool,
+    ) -> Result<bool, LockTableError> {
+        let Some(lock) = self.file_locks.get(blk.file_name()) else {
+            return Ok(false);
+        };
+        let lock = lock.lock().map_err(|_| {
+            LockTableError::Lock(format!(
+                "failed to acquire the file lock value for {}",
+                blk.file_name()
+            ))
+        })?;
+        Ok(match *lock {
+            Lock::Exclusive => true,
+            Lock::Shared(_) => exclusive,
+        })
+    }
+
+    /// blk の file 単位の lock が解放されるまで待つ
+    fn wait_for_file_lock(&self, blk: &BlockId, exclusive: bool) -> Result<(), LockTableError> {
+        enqueue(&get_or_create_queue(&self.file_queues, blk.file_name()))?;
+        // queue に入る前に解放されていた場合は待たない
+        if self.conflicts_with_file_lock(blk, exclusive)? {
+            park_timeout(time::Duration::from_millis(self.max_waiting_time_ms));
+        }
+        Ok(())
+    }
+
+    /// promote_to_xlock で xlock にした block を slock に戻す
+    pub fn demote_to_slock(&self, blk: &BlockId) -> Result<(), LockTableError> {
+        if let Some(lock) = self.locks.get(blk) {
+            *lock.lock().map_err(|_| {
+                LockTableError::Lock(format!(
+                    "failed to acquire the lock value for blk {:?}",
+                    blk.clone()
+                ))
+            })? = Lock::Shared(1);
+        }
+        self.update_block_lock_counts(blk, |counts| {
+            counts.exclusive -= 1;
+            counts.shared += 1;
+        });
+        // xlock だったので待っていた slock の thread を起こす
+        wake_all(&self.queues, blk)
+    }
+
+    fn get_or_create_queue(&self, blk: &BlockId) -> Arc<Mutex<VecDeque<thread::Thread>>> {
+        get_or_create_queue(&self.queues, blk)
+    }
+}
+
+fn get_or_create_queue<K, Q>(queues: &DashMap<K, WaitingQueue>, key: &Q) -> WaitingQueue
+where
+    K: Eq + Hash + std::borrow::Borrow<Q>,
+    Q: ToOwned<Owned = K> + Eq + Hash + ?Sized,
+{
+    queues
+        .entry(key.to_owned())
+        .or_insert_with(|| Arc::new(Mutex::new(VecDeque::new())))
+        .clone()
+}
+
+/// 自分の thread を queue に入れる。この後 park すると、queue の thread を起こした時に起こされる
+fn enqueue(queue: &WaitingQueue) -> Result<(), LockTableError> {
+    queue
+        .lock()
+        .map_err(|_| {
+            LockTableError::Lock("failed to acquire the lock of waiting queue list".into())
+        })?
+        .push_back(thread::current());
+    Ok(())
+}
+
+/// queue で待っている thread をすべて起こす
+fn wake_all<K, Q>(queues: &DashMap<K, WaitingQueue>, key: &Q) -> Result<(), LockTableError>
+where
+    K: Eq + Hash + std::borrow::Borrow<Q>,
+    Q: Eq + Hash + ?Sized,
+{
+    let Some(queue) = queues.get(key).map(|queue| queue.clone()) else {
+        return Ok(());
+    };
+    let mut queue = queue.lock().map_err(|_| {
+        LockTableError::Lock("failed to acquire the lock of waiting queue list".into())
+    })?;
+    while let Some(thread) = queue.pop_front() {
+        thread.unpark();
+    }
+    Ok(())
 }
 
 enum Lock {
@@ -345,6 +733,54 @@ mod lock_table_test {
         lock_table.slock(&blk1).unwrap();
     }
 
+    #[test]
+    fn test_file_lock() {
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        let (blk0, blk1, blk2) = (
+            BlockId::new("test", 0),
+            BlockId::new("test", 1),
+            BlockId::new("test", 2),
+        );
+        let other_file = BlockId::new("other", 0);
+
+        // 自分の slock 2 つを file 単位の slock にまとめる
+        lock_table.slock(&blk0).unwrap();
+        lock_table.slock(&blk1).unwrap();
+        assert_eq!(
+            lock_table.block_lock_counts("test"),
+            BlockLockCounts {
+                shared: 2,
+                exclusive: 0
+            }
+        );
+        let own = lock_table.block_lock_counts("test");
+        assert!(lock_table.try_escalate("test", false, own).unwrap());
+        lock_table.unlock(&blk0).unwrap();
+        lock_table.unlock(&blk1).unwrap();
+        assert_eq!(lock_table.escalation_count(), 1);
+
+        // file 単位の slock があっても、他の block の slock と別の file の xlock は取れる
+        lock_table.slock(&blk2).unwrap();
+        lock_table.xlock(&other_file).unwrap();
+        // 同じ file の block の xlock は取れない
+        assert!(lock_table.xlock(&blk0).is_err());
+        assert_eq!(lock_table.block_lock_counts("test").exclusive, 0);
+        // 他の transaction が block の slock を持っている間は、file 単位の xlock に昇格できない
+        assert!(lock_table.promote_file_lock("test").is_err());
+        lock_table.unlock(&blk2).unwrap();
+        lock_table.promote_file_lock("test").unwrap();
+        assert!(lock_table.slock(&blk2).is_err());
+
+        // file 単位の lock を解放すると、block の lock を取れる
+        lock_table.unlock_file("test").unwrap();
+        lock_table.xlock(&blk0).unwrap();
+        // 他の transaction が block の xlock を持っている場合はまとめられない
+        assert!(!lock_table
+            .try_escalate("test", false, BlockLockCounts::default())
+            .unwrap());
+        assert!(lock_table.unlock_file("test").is_err());
+    }
+
     #[test]
     fn test_unlock_notification_for_xlock() {
         // 複数の thread 間で同じ block の lock を取り合った場合、park, unpark が正しく動作することを確認する
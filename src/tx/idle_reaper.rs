@@ -0,0 +1,245 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, Weak},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use thiserror::Error;
+
+use crate::{buffer::buffer_manager::BufferManager, log::log_manager::LogManager};
+
+use super::{
+    concurrency::{concurrency_manager::ConcurrencyManager, lock_table::LockTableError},
+    log::{log_record_writer::LogRecordWriter, record::log_record::LogRecordError},
+    recovery::recovery_manager::{RecoveryError, RecoveryManager},
+};
+
+/// idle transaction reaper に abort された transaction を操作しようとした
+#[derive(Error, Debug)]
+#[error("transaction {0} was aborted because it was idle for too long")]
+pub struct TransactionAbortedError(pub u32);
+
+#[derive(Error, Debug)]
+pub enum IdleReaperError {
+    #[error("Failed to acquire lock")]
+    Lock,
+    #[error("Log record error: {0}")]
+    LogRecord(#[from] LogRecordError),
+    #[error("recovery error: {0}")]
+    Recovery(#[from] RecoveryError),
+    #[error("Lock table error: {0}")]
+    LockTable(#[from] LockTableError),
+}
+
+struct ActivityState {
+    // 実行中の Transaction のメソッドの数
+    active_calls: usize,
+    last_activity: Instant,
+    aborted: bool,
+}
+
+/**
+ * 一つの transaction の開始時刻と最後に操作された時刻を記録する構造体
+ *
+ * Transaction のメソッドは実行中に begin で得た guard を持ち続けるので、reaper は実行中の transaction を abort しない
+ * reaper が abort した後は begin が error を返すので、abort された transaction が block を読み書きすることはない
+ */
+pub(crate) struct TransactionActivity {
+    started: Instant,
+    state: Mutex<ActivityState>,
+}
+
+impl TransactionActivity {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            state: Mutex::new(ActivityState {
+                active_calls: 0,
+                last_activity: now,
+                aborted: false,
+            }),
+        }
+    }
+
+    /// transaction の操作を始める。abort されていた場合は None を返す
+    pub fn begin(&self) -> Option<ActivityGuard<'_>> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        if state.aborted {
+            return None;
+        }
+        state.active_calls += 1;
+        state.last_activity = Instant::now();
+        Some(ActivityGuard { activity: self })
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .aborted
+    }
+
+    // 操作中でなく、最後の操作から idle_limit 以上経っていれば abort を呼び、成功したら abort 済みにして idle だった時間を返す
+    // abort の間は state の lock を持ち続けるので、その間に transaction の操作が始まることはない
+    fn try_abort<E>(
+        &self,
+        idle_limit: Duration,
+        abort: impl FnOnce() -> Result<(), E>,
+    ) -> Result<Option<Duration>, E> {
+        let mut state = self.state.lock().unwrap_or_else(|err| err.into_inner());
+        let idle = state.last_activity.elapsed();
+        if state.aborted || state.active_calls > 0 || idle < idle_limit {
+            return Ok(None);
+        }
+        abort()?;
+        state.aborted = true;
+        Ok(Some(idle))
+    }
+}
+
+/// Transaction のメソッドの実行中であることを示す guard。drop した時点を最後の操作の時刻とする
+pub(crate) struct ActivityGuard<'a> {
+    activity: &'a TransactionActivity,
+}
+
+impl Drop for ActivityGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self
+            .activity
+            .state
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        state.active_calls -= 1;
+        state.last_activity = Instant::now();
+    }
+}
+
+#[derive(Clone)]
+struct ReapTarget {
+    activity: Arc<TransactionActivity>,
+    concurrency_manager: Arc<Mutex<ConcurrencyManager>>,
+}
+
+/**
+ * commit も rollback もされずに放置された transaction を abort するクラス
+ *
+ * TransactionFactory が作った transaction を登録しておき、最後の操作から idle_limit 以上経った transaction の変更を取り消して lock を解放する
+ * transaction 自体は別の thread が持っているので、変更は log をもとに buffer に直接書き戻す (RecoveryManager::rollback_detached)
+ * abort された transaction を持っている thread には、次の操作で TransactionAbortedError が返される
+ * その thread が rollback を呼ぶと pin の解放などの残りの後始末が行われる
+ *
+ * プログラム全体で一つしかない想定 (TransactionFactory が持つ)
+ */
+pub struct IdleTransactionReaper {
+    idle_limit: Duration,
+    recovery_manager: RecoveryManager,
+    log_record_writer: LogRecordWriter,
+    transactions: Mutex<HashMap<u32, ReapTarget>>,
+}
+
+impl IdleTransactionReaper {
+    pub fn new(
+        log_manager: Arc<LogManager>,
+        buffer_manager: Arc<BufferManager>,
+        idle_limit: Duration,
+    ) -> Self {
+        Self {
+            idle_limit,
+            recovery_manager: RecoveryManager::new(log_manager.clone(), buffer_manager),
+            log_record_writer: LogRecordWriter::new(log_manager),
+            transactions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn idle_limit(&self) -> Duration {
+        self.idle_limit
+    }
+
+    /// interval ごとに reap を呼ぶ thread を起動する。reaper が drop されると thread も終了する
+    pub fn start(reaper: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let reaper: Weak<Self> = Arc::downgrade(reaper);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let Some(reaper) = reaper.upgrade() else {
+                return;
+            };
+            if let Err(err) = reaper.reap() {
+                eprintln!("failed to abort idle transactions: {}", err);
+            }
+        })
+    }
+
+    pub(crate) fn register(
+        &self,
+        txnum: u32,
+        activity: Arc<TransactionActivity>,
+        concurrency_manager: Arc<Mutex<ConcurrencyManager>>,
+    ) -> Result<(), IdleReaperError> {
+        self.transactions
+            .lock()
+            .map_err(|_| IdleReaperError::Lock)?
+            .insert(
+                txnum,
+                ReapTarget {
+                    activity,
+                    concurrency_manager,
+                },
+            );
+        Ok(())
+    }
+
+    pub(crate) fn unregister(&self, txnum: u32) {
+        if let Ok(mut transactions) = self.transactions.lock() {
+            transactions.remove(&txnum);
+        }
+    }
+
+    /// 登録されている transaction の数を返す
+    pub fn num_transactions(&self) -> usize {
+        self.transactions
+            .lock()
+            .map(|transactions| transactions.len())
+            .unwrap_or_default()
+    }
+
+    /// idle_limit 以上操作されていない transaction を abort し、abort した transaction の番号を返す
+    pub fn reap(&self) -> Result<Vec<u32>, IdleReaperError> {
+        // 取り消しの間に buffer の pin を待つことがあるので、登録の lock は先に外しておく
+        let mut targets = self
+            .transactions
+            .lock()
+            .map_err(|_| IdleReaperError::Lock)?
+            .iter()
+            .map(|(txnum, target)| (*txnum, target.clone()))
+            .collect::<Vec<_>>();
+        targets.sort_by_key(|(txnum, _)| *txnum);
+        let mut aborted = vec![];
+        for (txnum, target) in targets {
+            let abort = || -> Result<(), IdleReaperError> {
+                // Transaction::rollback と同じ順番で、rollback の log を書いてから変更を取り消し、lock を解放する
+                self.log_record_writer.log_rollback(txnum)?;
+                self.recovery_manager.rollback_detached(txnum)?;
+                target
+                    .concurrency_manager
+                    .lock()
+                    .map_err(|_| IdleReaperError::Lock)?
+                    .release()?;
+                Ok(())
+            };
+            let Some(idle) = target.activity.try_abort(self.idle_limit, abort)? else {
+                continue;
+            };
+            eprintln!(
+                "aborted transaction {} (started {:?} ago, idle for {:?})",
+                txnum,
+                target.activity.started.elapsed(),
+                idle
+            );
+            self.unregister(txnum);
+            aborted.push(txnum);
+        }
+        Ok(aborted)
+    }
+}
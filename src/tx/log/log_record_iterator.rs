@@ -1,6 +1,6 @@
 use super::record::log_record::LogRecord;
 use crate::file::file_manager::FileManagerError;
-use crate::log::log_iterator::{LogIterator, LogReverseIterator};
+use crate::log::log_iterator::{InvalidLogRecordError, LogIterator, LogReverseIterator};
 use crate::log::log_manager::{self, LogError};
 
 use std::sync::Arc;
@@ -34,6 +34,39 @@ impl LogRecordIterator {
         let log_iter = lm.iterator()?;
         Ok(LogRecordIterator { log_iter })
     }
+
+    /// log を flush せずに、メモリ上の log page から読み始める iterator を作成する (LogManager::tail_iterator を参照)
+    /// LogRecordReverseIterator の作成には使えない
+    pub fn from_tail(lm: Arc<log_manager::LogManager>) -> Result<Self, LogError> {
+        let log_iter = lm.tail_iterator()?;
+        Ok(LogRecordIterator { log_iter })
+    }
+
+    /**
+     * 次の log record を読む。Iterator::next と異なり、log record として解釈できない byte 列を読んだ場合はその位置を error として返す
+     *
+     * 長さが読めている (error の framed が true の) 場合は、続けて呼び出すとその前の log record を読める
+     */
+    pub fn try_next(&mut self) -> Option<Result<LogRecord, InvalidLogRecordError>> {
+        Some(match self.log_iter.try_next()? {
+            Ok(bytes) => LogRecord::new(&bytes).map_err(|_| {
+                // 読めた直後なので位置は必ずある
+                let (block, offset) = self.log_iter.last_position().unwrap();
+                InvalidLogRecordError {
+                    block,
+                    offset,
+                    framed: true,
+                }
+            }),
+            Err(e) => Err(e),
+        })
+    }
+
+    /// iterator と、その時点で最新の log record の LSN を返す (LogManager::iterator_with_latest_lsn を参照)
+    pub fn with_latest_lsn(lm: Arc<log_manager::LogManager>) -> Result<(Self, u64), LogError> {
+        let (log_iter, latest_lsn) = lm.iterator_with_latest_lsn()?;
+        Ok((LogRecordIterator { log_iter }, latest_lsn))
+    }
 }
 
 impl LogRecordReverseIterator {
@@ -1,3 +1,4 @@
+use super::record::append_record::AppendRecord;
 use super::record::commit_record::CommitRecord;
 use super::record::rollback_record::RollbackRecord;
 use super::record::{
@@ -5,6 +6,7 @@ use super::record::{
     set_string_record::SetStringRecord, start_record::StartRecord,
 };
 use crate::buffer::buffer;
+use crate::file::blockid::BlockId;
 use crate::log::log_manager;
 
 use std::sync::Arc;
@@ -26,9 +28,10 @@ impl LogRecordWriter {
         LogRecordWriter { lm }
     }
 
-    pub fn log_check_point(&self) -> Result<u64, LogRecordError> {
-        let lsn = CheckPointRecord::write_to_log(&self.lm)?;
+    pub fn log_check_point(&self, max_txnum: u32) -> Result<u64, LogRecordError> {
+        let lsn = CheckPointRecord::write_to_log(&self.lm, max_txnum)?;
         self.lm.flush(lsn)?;
+        self.lm.set_last_checkpoint_lsn(lsn)?;
         Ok(lsn)
     }
 
@@ -82,4 +85,10 @@ impl LogRecordWriter {
         let lsn = SetIntRecord::write_to_log(&self.lm, txnum, block, offset, old_val, new_val)?;
         Ok(lsn)
     }
+
+    // file の末尾に block を追加したことを書き込む
+    pub fn log_append(&self, txnum: u32, block: &BlockId) -> Result<u64, LogRecordError> {
+        let lsn = AppendRecord::write_to_log(&self.lm, txnum, block)?;
+        Ok(lsn)
+    }
 }
@@ -1,3 +1,4 @@
+pub mod append_record;
 pub mod check_point_record;
 pub mod commit_record;
 pub mod log_record;
@@ -0,0 +1,116 @@
+use std::string::FromUtf8Error;
+
+use super::log_record::LogOp;
+use crate::constants::INTEGER_BYTE_LEN;
+use crate::file::{blockid, page};
+use crate::log::log_manager;
+
+/**
+ * transaction が file の末尾に block を追加したことを示す log record
+ *
+ * 追加した block を記録することで、append 後の file の長さが log に残る
+ * recovery の redo では、commit された append の後の長さまでファイルを伸ばす
+ * 追加された block は format 前の状態でも全 slot が empty として読めるので、undo で行うことはない
+ */
+#[derive(Debug, Eq, PartialEq)]
+pub struct AppendRecord {
+    txnum: u32,
+    block: blockid::BlockId,
+}
+
+impl AppendRecord {
+    /**
+     * byte 列から AppendRecord を再現する
+     */
+    pub fn new(bytes: &[u8]) -> Result<Self, FromUtf8Error> {
+        let p = page::Page::new_from_vec(bytes);
+        let tpos = INTEGER_BYTE_LEN;
+        let txnum = p.get_int(tpos) as u32;
+
+        let fpos = tpos + INTEGER_BYTE_LEN;
+        let filename = p.get_string(fpos)?;
+        let bpos = fpos + filename.len() + INTEGER_BYTE_LEN;
+        let blknum = p.get_int(bpos) as usize;
+        let block = blockid::BlockId::new(&filename, blknum);
+
+        Ok(AppendRecord { txnum, block })
+    }
+
+    /**
+     * transaction 番号を取得する
+     */
+    pub fn tx_num(&self) -> u32 {
+        self.txnum
+    }
+
+    /**
+     * 追加された block を取得する
+     */
+    pub fn block(&self) -> &blockid::BlockId {
+        &self.block
+    }
+
+    /**
+     * append した直後の file の block 数を取得する
+     */
+    pub fn file_length(&self) -> usize {
+        self.block.number() + 1
+    }
+
+    /**
+     * file の末尾に block を追加したことを log に書き込む関数
+     *
+     * 成功した場合、書き込まれた log sequence number を返す
+     */
+    pub fn write_to_log(
+        lm: &log_manager::LogManager,
+        txnum: u32,
+        block: &blockid::BlockId,
+    ) -> Result<u64, log_manager::LogError> {
+        let tpos = INTEGER_BYTE_LEN;
+        let fpos = tpos + INTEGER_BYTE_LEN;
+        let bpos = fpos + block.file_name().len() + INTEGER_BYTE_LEN;
+        let record_len = bpos + INTEGER_BYTE_LEN;
+
+        let mut p = page::Page::new_from_size(record_len);
+        p.set_int(0, LogOp::Append as i32);
+        p.set_int(tpos, txnum as i32);
+        p.set_string(fpos, block.file_name());
+        p.set_int(bpos, block.number() as i32);
+
+        let lsn = lm.append(p.contents())?;
+        Ok(lsn)
+    }
+}
+
+#[cfg(test)]
+mod append_record_test {
+    use crate::file::blockid::BlockId;
+    use crate::file::file_manager::FileManager;
+    use crate::log::log_manager::LogManager;
+    use crate::tx::log::record::log_record::LogRecord;
+
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    use super::AppendRecord;
+
+    #[test]
+    fn test_append_record_log() {
+        let dir = tempdir().unwrap();
+        let fm = FileManager::new(dir.path(), 400);
+        let lm = LogManager::new(Arc::new(fm), "test.log").unwrap();
+
+        let block = BlockId::new("testfile", 3);
+        AppendRecord::write_to_log(&lm, 7, &block).unwrap();
+
+        let mut log_iter = lm.iterator().unwrap();
+        let record = LogRecord::new(&log_iter.next().unwrap()).unwrap();
+        let LogRecord::Append(record) = record else {
+            panic!("expected append record, got {:?}", record);
+        };
+        assert_eq!(record.tx_num(), 7);
+        assert_eq!(record.block(), &block);
+        assert_eq!(record.file_length(), 4);
+    }
+}
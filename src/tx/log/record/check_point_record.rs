@@ -5,23 +5,50 @@ use crate::log::log_manager::{LogError, LogManager};
 
 /**
  * recovery が完了し、これより前の record はすべて完了した transaction として block に書き込まれたことを示す log record
+ *
+ * 再起動後に transaction の番号が以前のものと重ならないように、書き込んだ時点で最大の transaction の番号も記録する
  */
 #[derive(Debug, Eq, PartialEq)]
-pub(crate) struct CheckPointRecord {}
+pub struct CheckPointRecord {
+    max_txnum: u32,
+}
 
 impl CheckPointRecord {
+    /**
+     * byte 列から CheckPointRecord を再現する
+     *
+     * transaction の番号を記録していない古い形式の record の場合は 0 とみなす
+     */
+    pub fn new(bytes: &[u8]) -> Self {
+        let p = Page::new_from_vec(bytes);
+        let max_txnum = if bytes.len() >= INTEGER_BYTE_LEN * 2 {
+            p.get_int(INTEGER_BYTE_LEN) as u32
+        } else {
+            0
+        };
+
+        CheckPointRecord { max_txnum }
+    }
+
     /**
      * check point record の内容を log として書き込むための関数
      *
      * 成功した場合、書き込まれた log sequence number を返す
      */
-    pub fn write_to_log(lm: &LogManager) -> Result<u64, LogError> {
-        let mut p = Page::new_from_size(INTEGER_BYTE_LEN);
+    pub fn write_to_log(lm: &LogManager, max_txnum: u32) -> Result<u64, LogError> {
+        let record_len = INTEGER_BYTE_LEN * 2;
+        let mut p = Page::new_from_size(record_len);
         p.set_int(0, LogOp::CheckPoint as i32);
+        p.set_int(INTEGER_BYTE_LEN, max_txnum as i32);
 
         let lsn = lm.append(p.contents())?;
         Ok(lsn)
     }
+
+    /// checkpoint を書き込んだ時点で最大の transaction の番号
+    pub fn max_tx_num(&self) -> u32 {
+        self.max_txnum
+    }
 }
 
 #[cfg(test)]
@@ -42,10 +69,17 @@ mod check_point_record_test {
         let fm = FileManager::new(dir.path(), 400);
         let lm = LogManager::new(Arc::new(fm), "test.log").unwrap();
 
-        CheckPointRecord::write_to_log(&lm).unwrap();
+        CheckPointRecord::write_to_log(&lm, 12).unwrap();
 
         let mut log_iter = lm.iterator().unwrap();
-        let page = Page::new_from_vec(&log_iter.next().unwrap());
+        let bytes = log_iter.next().unwrap();
+        let page = Page::new_from_vec(&bytes);
         assert_eq!(page.get_int(0), LogOp::CheckPoint as i32);
+        assert_eq!(CheckPointRecord::new(&bytes).max_tx_num(), 12);
+
+        // transaction の番号を持たない古い形式の record も読める
+        let mut old = Page::new_from_size(4);
+        old.set_int(0, LogOp::CheckPoint as i32);
+        assert_eq!(CheckPointRecord::new(old.contents()).max_tx_num(), 0);
     }
 }
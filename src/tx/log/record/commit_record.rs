@@ -7,7 +7,7 @@ use crate::log::log_manager::{LogError, LogManager};
  * transaction が正常に完了したことを示す log record
  */
 #[derive(Debug, Eq, PartialEq)]
-pub(crate) struct CommitRecord {
+pub struct CommitRecord {
     txnum: u32,
 }
 
@@ -5,20 +5,23 @@ use crate::log::log_manager;
 use crate::tx::buffer_list::BufferListError;
 use crate::tx::transaction::TransactionSetError;
 
+use super::append_record::AppendRecord;
+use super::check_point_record::CheckPointRecord;
 use super::commit_record::CommitRecord;
 use super::rollback_record::RollbackRecord;
 use super::set_int_record::SetIntRecord;
-use super::set_string_record::SetStringRecord;
+use super::set_string_record::{SetStringRecord, SetStringRecordError};
 use super::start_record::StartRecord;
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum LogRecord {
-    CheckPoint(),
+    CheckPoint(CheckPointRecord),
     Start(StartRecord),
     Commit(CommitRecord),
     Rollback(RollbackRecord),
     SetIntRecord(SetIntRecord),
     SetStringRecord(SetStringRecord),
+    Append(AppendRecord),
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -29,6 +32,9 @@ pub enum LogOp {
     Rollback = 3,
     SetInt = 4,
     SetString = 5,
+    Append = 6,
+    /// 変更前の値を変更後の値との差分として書き込んだ SetString
+    SetStringDelta = 7,
 }
 
 #[derive(Error, Debug)]
@@ -37,6 +43,8 @@ pub enum LogRecordError {
     LogErrorError(#[from] log_manager::LogError),
     #[error("FromUtf8Error: {0}")]
     FromUtf8Error(#[from] std::string::FromUtf8Error),
+    #[error("Set string record error: {0}")]
+    SetStringRecordError(#[from] SetStringRecordError),
     #[error("Log record error: {0}")]
     GeneralError(#[from] anyhow::Error),
 }
@@ -52,12 +60,13 @@ pub enum LogReplayError {
 impl LogRecord {
     pub fn op(&self) -> LogOp {
         match self {
-            LogRecord::CheckPoint() => LogOp::CheckPoint,
+            LogRecord::CheckPoint(_) => LogOp::CheckPoint,
             LogRecord::Start(_) => LogOp::Start,
             LogRecord::Commit(_) => LogOp::Commit,
             LogRecord::Rollback(_) => LogOp::Rollback,
             LogRecord::SetIntRecord(_) => LogOp::SetInt,
             LogRecord::SetStringRecord(_) => LogOp::SetString,
+            LogRecord::Append(_) => LogOp::Append,
         }
     }
 
@@ -65,12 +74,20 @@ impl LogRecord {
      * byte 列から LogRecord を作成する
      */
     pub fn new(bytes: &[u8]) -> Result<LogRecord, LogRecordError> {
+        if bytes.len() < crate::constants::INTEGER_BYTE_LEN {
+            return Err(LogRecordError::GeneralError(anyhow::anyhow!(
+                "log record is too short"
+            )));
+        }
         let page = Page::new_from_vec(bytes);
         let op = LogOp::from_i32(page.get_int(0)).ok_or_else(|| {
             LogRecordError::GeneralError(anyhow::anyhow!("Unknown log record operation"))
         })?;
         match op {
-            LogOp::CheckPoint => Ok(LogRecord::CheckPoint()),
+            LogOp::CheckPoint => {
+                let inner = CheckPointRecord::new(bytes);
+                Ok(LogRecord::CheckPoint(inner))
+            }
             LogOp::Start => {
                 let inner = StartRecord::new(bytes);
                 Ok(LogRecord::Start(inner))
@@ -91,6 +108,14 @@ impl LogRecord {
                 let inner = SetStringRecord::new(bytes)?;
                 Ok(LogRecord::SetStringRecord(inner))
             }
+            LogOp::Append => {
+                let inner = AppendRecord::new(bytes)?;
+                Ok(LogRecord::Append(inner))
+            }
+            LogOp::SetStringDelta => {
+                let inner = SetStringRecord::new_from_delta(bytes)?;
+                Ok(LogRecord::SetStringRecord(inner))
+            }
         }
     }
 }
@@ -104,6 +129,8 @@ impl LogOp {
             3 => Some(LogOp::Rollback),
             4 => Some(LogOp::SetInt),
             5 => Some(LogOp::SetString),
+            6 => Some(LogOp::Append),
+            7 => Some(LogOp::SetStringDelta),
             _ => None,
         }
     }
@@ -114,7 +141,6 @@ mod log_record_test {
     use crate::file::blockid::BlockId;
     use crate::file::file_manager::FileManager;
     use crate::log::log_manager::LogManager;
-    use crate::tx::log::record::check_point_record::CheckPointRecord;
 
     use std::sync::Arc;
     use tempfile::tempdir;
@@ -128,7 +154,7 @@ mod log_record_test {
         let lm = LogManager::new(Arc::new(fm), "test.log").unwrap();
 
         // checkpoint -> tx1 start -> tx1 set_int -> tx1 rollback -> tx2 start -> tx2 set_string -> tx2 commit
-        let lsn = CheckPointRecord::write_to_log(&lm).unwrap();
+        let lsn = CheckPointRecord::write_to_log(&lm, 4).unwrap();
         assert_eq!(lsn, 1);
         let lsn = StartRecord::write_to_log(&lm, 5).unwrap();
         assert_eq!(lsn, 2);
@@ -7,7 +7,7 @@ use crate::log::log_manager::{LogError, LogManager};
  * transaction が正常に完了せず、変更を戻したことを示す log record
  */
 #[derive(Debug, Eq, PartialEq)]
-pub(crate) struct RollbackRecord {
+pub struct RollbackRecord {
     txnum: u32,
 }
 
@@ -21,6 +21,13 @@ impl RollbackRecord {
 
         RollbackRecord { txnum }
     }
+
+    /**
+     * transaction 番号を取得する
+     */
+    pub fn tx_num(&self) -> u32 {
+        self.txnum
+    }
     /**
      * transaction が正常に完了せず、変更を戻したことを log に書き込む関数
      *
@@ -10,7 +10,7 @@ use crate::tx::transaction::Transaction;
  * 数値を変更したことを示す log record で保持する情報
  */
 #[derive(Debug, Eq, PartialEq)]
-pub(crate) struct SetIntRecord {
+pub struct SetIntRecord {
     txnum: u32,
     block: blockid::BlockId,
     offset: usize,
@@ -62,22 +62,73 @@ impl SetIntRecord {
      * log record の内容を元に、指定された transaction のもとで undo を実行する
      * rollback や recovery で利用される
      */
-    pub fn undo(&self, tx: &mut Transaction) -> Result<(), LogReplayError> {
+    pub fn undo(&self, tx: &Transaction) -> Result<(), LogReplayError> {
         tx.pin(&self.block)?;
         tx.set_int(&self.block, self.offset, self.old_value, false)?;
+        tx.unpin(&self.block)?;
         Ok(())
     }
 
+    /**
+     * undo と同じ値を、取り消し自体も log に記録しながら書き込む
+     * savepoint までの rollback で利用される。その後 transaction が commit されても、recovery の redo で取り消しが再現される
+     */
+    pub fn undo_logged(&self, tx: &Transaction) -> Result<(), LogReplayError> {
+        tx.pin(&self.block)?;
+        tx.set_int(&self.block, self.offset, self.old_value, true)?;
+        tx.unpin(&self.block)?;
+        Ok(())
+    }
+
+    /**
+     * 変更された block を取得する
+     */
+    pub fn block(&self) -> &blockid::BlockId {
+        &self.block
+    }
+
+    /**
+     * undo と同じ値を、変更された block ではなく block に書き込む
+     * 過去の時点の table の内容を、別のファイル上に再現するために利用される
+     */
+    pub fn undo_to(
+        &self,
+        tx: &Transaction,
+        block: &blockid::BlockId,
+    ) -> Result<(), LogReplayError> {
+        tx.pin(block)?;
+        tx.set_int(block, self.offset, self.old_value, false)?;
+        tx.unpin(block)?;
+        Ok(())
+    }
+
+    /**
+     * undo と同じ変更を、transaction を通さずに page (変更された block の内容) に直接書き込む
+     * 別の thread の transaction を idle transaction reaper が abort する場合に利用される
+     */
+    pub fn undo_on_page(&self, page: &mut page::Page) {
+        page.set_int(self.offset, self.old_value);
+    }
+
     /**
      * log record の内容を元に、指定された transaction のもとで redo を実行する
      * recovery で利用される
      */
-    pub fn redo(&self, tx: &mut Transaction) -> Result<(), LogReplayError> {
+    pub fn redo(&self, tx: &Transaction) -> Result<(), LogReplayError> {
         tx.pin(&self.block)?;
         tx.set_int(&self.block, self.offset, self.new_value, false)?;
+        tx.unpin(&self.block)?;
         Ok(())
     }
 
+    /**
+     * redo と同じ変更を、transaction を通さずに page (変更された block の内容) に直接書き込む
+     * recovery の redo を複数の thread で行う場合に利用される
+     */
+    pub fn redo_on_page(&self, page: &mut page::Page) {
+        page.set_int(self.offset, self.new_value);
+    }
+
     /**
      * SetInt log record の内容を log として書き込むための関数
      *
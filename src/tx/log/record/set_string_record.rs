@@ -1,16 +1,46 @@
 use std::string::FromUtf8Error;
 
+use thiserror::Error;
+
 use super::log_record::{LogOp, LogReplayError};
 use crate::constants::INTEGER_BYTE_LEN;
 use crate::file::{blockid, page};
 use crate::log::log_manager;
 use crate::tx::transaction::Transaction;
 
+#[derive(Error, Debug)]
+pub enum SetStringRecordError {
+    #[error("FromUtf8Error: {0}")]
+    FromUtf8Error(#[from] FromUtf8Error),
+    #[error("unknown value encoding: {0}")]
+    UnknownEncoding(i32),
+    #[error("invalid delta: prefix {prefix}, suffix {suffix}, new value length {len}")]
+    InvalidDelta {
+        prefix: usize,
+        suffix: usize,
+        len: usize,
+    },
+}
+
+/**
+ * SetStringDelta record で、変更前の値を書き込む方式
+ * record の中に version tag として書き込み、読む時はこの値を見て復元の方法を決める
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueEncoding {
+    /// 変更後の値との共通の prefix と suffix の長さと、その間の部分だけを書き込む
+    PrefixSuffix = 1,
+}
+
 /**
  * 文字列を変更したことを示す log record で保持する情報
+ *
+ * 変更前と変更後の値をそのまま書き込む SetString と、変更前の値を変更後の値との差分として書き込む SetStringDelta の 2 つの形式がある
+ * 長い文字列の一部だけを書き換える場合は SetStringDelta の方が log が小さくなるので、書き込む時に小さい方を選ぶ
+ * どちらの形式から読んでも同じ SetStringRecord になる
  */
 #[derive(Debug, Eq, PartialEq)]
-pub(crate) struct SetStringRecord {
+pub struct SetStringRecord {
     txnum: u32,
     block: blockid::BlockId,
     offset: usize,
@@ -51,6 +81,64 @@ impl SetStringRecord {
         })
     }
 
+    /**
+     * SetStringDelta 形式の byte 列から SetStringRecord を再現する
+     */
+    pub fn new_from_delta(bytes: &[u8]) -> Result<Self, SetStringRecordError> {
+        let p = page::Page::new_from_vec(bytes);
+        let tpos = INTEGER_BYTE_LEN;
+        let txnum = p.get_int(tpos) as u32;
+
+        let fpos = tpos + INTEGER_BYTE_LEN;
+        let filename = p.get_string(fpos)?;
+        let bpos = fpos + filename.len() + INTEGER_BYTE_LEN;
+        let blknum = p.get_int(bpos) as usize;
+        let block = blockid::BlockId::new(&filename, blknum);
+
+        let opos = bpos + INTEGER_BYTE_LEN;
+        let offset = p.get_int(opos) as usize;
+
+        let epos = opos + INTEGER_BYTE_LEN;
+        let encoding = p.get_int(epos);
+        if encoding != ValueEncoding::PrefixSuffix as i32 {
+            return Err(SetStringRecordError::UnknownEncoding(encoding));
+        }
+
+        let nvpos = epos + INTEGER_BYTE_LEN;
+        let new_value = p.get_string(nvpos)?;
+        let ppos = nvpos + new_value.len() + INTEGER_BYTE_LEN;
+        let prefix = p.get_int(ppos) as usize;
+        let spos = ppos + INTEGER_BYTE_LEN;
+        let suffix = p.get_int(spos) as usize;
+        let mpos = spos + INTEGER_BYTE_LEN;
+        let middle = p.get_string(mpos)?;
+
+        let invalid_delta = || SetStringRecordError::InvalidDelta {
+            prefix,
+            suffix,
+            len: new_value.len(),
+        };
+        let suffix_start = new_value
+            .len()
+            .checked_sub(suffix)
+            .filter(|start| *start >= prefix)
+            .ok_or_else(invalid_delta)?;
+        let old_value = format!(
+            "{}{}{}",
+            new_value.get(..prefix).ok_or_else(invalid_delta)?,
+            middle,
+            new_value.get(suffix_start..).ok_or_else(invalid_delta)?
+        );
+
+        Ok(SetStringRecord {
+            txnum,
+            block,
+            offset,
+            old_value,
+            new_value,
+        })
+    }
+
     /**
      * transaction 番号を取得する
      */
@@ -62,24 +150,76 @@ impl SetStringRecord {
      * log record の内容を元に、指定された transaction のもとで undo を実行する
      * rollback や recovery で利用される
      */
-    pub fn undo(&self, tx: &mut Transaction) -> Result<(), LogReplayError> {
+    pub fn undo(&self, tx: &Transaction) -> Result<(), LogReplayError> {
         tx.pin(&self.block)?;
         tx.set_string(&self.block, self.offset, &self.old_value, false)?;
+        tx.unpin(&self.block)?;
+        Ok(())
+    }
+
+    /**
+     * undo と同じ値を、取り消し自体も log に記録しながら書き込む
+     * savepoint までの rollback で利用される。その後 transaction が commit されても、recovery の redo で取り消しが再現される
+     */
+    pub fn undo_logged(&self, tx: &Transaction) -> Result<(), LogReplayError> {
+        tx.pin(&self.block)?;
+        tx.set_string(&self.block, self.offset, &self.old_value, true)?;
+        tx.unpin(&self.block)?;
         Ok(())
     }
 
+    /**
+     * 変更された block を取得する
+     */
+    pub fn block(&self) -> &blockid::BlockId {
+        &self.block
+    }
+
+    /**
+     * undo と同じ値を、変更された block ではなく block に書き込む
+     * 過去の時点の table の内容を、別のファイル上に再現するために利用される
+     */
+    pub fn undo_to(
+        &self,
+        tx: &Transaction,
+        block: &blockid::BlockId,
+    ) -> Result<(), LogReplayError> {
+        tx.pin(block)?;
+        tx.set_string(block, self.offset, &self.old_value, false)?;
+        tx.unpin(block)?;
+        Ok(())
+    }
+
+    /**
+     * undo と同じ変更を、transaction を通さずに page (変更された block の内容) に直接書き込む
+     * 別の thread の transaction を idle transaction reaper が abort する場合に利用される
+     */
+    pub fn undo_on_page(&self, page: &mut page::Page) {
+        page.set_string(self.offset, &self.old_value);
+    }
+
     /**
      * log record の内容を元に、指定された transaction のもとで redo を実行する
      * recovery で利用される
      */
-    pub fn redo(&self, tx: &mut Transaction) -> Result<(), LogReplayError> {
+    pub fn redo(&self, tx: &Transaction) -> Result<(), LogReplayError> {
         tx.pin(&self.block)?;
         tx.set_string(&self.block, self.offset, &self.new_value, false)?;
+        tx.unpin(&self.block)?;
         Ok(())
     }
 
+    /**
+     * redo と同じ変更を、transaction を通さずに page (変更された block の内容) に直接書き込む
+     * recovery の redo を複数の thread で行う場合に利用される
+     */
+    pub fn redo_on_page(&self, page: &mut page::Page) {
+        page.set_string(self.offset, &self.new_value);
+    }
+
     /**
      * SetString log record の内容を log として書き込むための関数
+     * SetString と SetStringDelta のうち、書き込む量が少ない方の形式で書き込む
      *
      * 成功した場合、書き込まれた log sequence number を返す
      */
@@ -90,6 +230,24 @@ impl SetStringRecord {
         offset: usize,
         old_val: &str,
         new_val: &str,
+    ) -> Result<u64, log_manager::LogError> {
+        let (prefix, suffix) = common_prefix_suffix(old_val, new_val);
+        let middle = &old_val[prefix..old_val.len() - suffix];
+        // delta 形式では encoding と prefix, suffix の長さの分だけ int が増え、old_val の代わりに middle を書き込む
+        if middle.len() + 3 * INTEGER_BYTE_LEN < old_val.len() {
+            Self::write_delta_to_log(lm, txnum, block, offset, new_val, prefix, suffix, middle)
+        } else {
+            Self::write_full_to_log(lm, txnum, block, offset, old_val, new_val)
+        }
+    }
+
+    fn write_full_to_log(
+        lm: &log_manager::LogManager,
+        txnum: u32,
+        block: &blockid::BlockId,
+        offset: usize,
+        old_val: &str,
+        new_val: &str,
     ) -> Result<u64, log_manager::LogError> {
         let tpos = INTEGER_BYTE_LEN;
         let fpos = tpos + INTEGER_BYTE_LEN;
@@ -112,6 +270,63 @@ impl SetStringRecord {
 
         Ok(lsn)
     }
+
+    #[allow(clippy::too_many_arguments)]
+    fn write_delta_to_log(
+        lm: &log_manager::LogManager,
+        txnum: u32,
+        block: &blockid::BlockId,
+        offset: usize,
+        new_val: &str,
+        prefix: usize,
+        suffix: usize,
+        middle: &str,
+    ) -> Result<u64, log_manager::LogError> {
+        let tpos = INTEGER_BYTE_LEN;
+        let fpos = tpos + INTEGER_BYTE_LEN;
+        let bpos = fpos + block.file_name().len() + INTEGER_BYTE_LEN;
+        let opos = bpos + INTEGER_BYTE_LEN;
+        let epos = opos + INTEGER_BYTE_LEN;
+        let nvpos = epos + INTEGER_BYTE_LEN;
+        let ppos = nvpos + new_val.len() + INTEGER_BYTE_LEN;
+        let spos = ppos + INTEGER_BYTE_LEN;
+        let mpos = spos + INTEGER_BYTE_LEN;
+        let record_len = mpos + middle.len() + INTEGER_BYTE_LEN;
+
+        let mut p = page::Page::new_from_size(record_len);
+        p.set_int(0, LogOp::SetStringDelta as i32);
+        p.set_int(tpos, txnum as i32);
+        p.set_string(fpos, block.file_name());
+        p.set_int(bpos, block.number() as i32);
+        p.set_int(opos, offset as i32);
+        p.set_int(epos, ValueEncoding::PrefixSuffix as i32);
+        p.set_string(nvpos, new_val);
+        p.set_int(ppos, prefix as i32);
+        p.set_int(spos, suffix as i32);
+        p.set_string(mpos, middle);
+
+        let lsn = lm.append(p.contents())?;
+
+        Ok(lsn)
+    }
+}
+
+/// old と new に共通する prefix と suffix の byte 数を返す。文字の途中では区切らず、prefix と suffix は重ならない
+fn common_prefix_suffix(old: &str, new: &str) -> (usize, usize) {
+    let prefix = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(o, n)| o == n)
+        .map(|(c, _)| c.len_utf8())
+        .sum::<usize>();
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(o, n)| o == n)
+        .map(|(c, _)| c.len_utf8())
+        .sum::<usize>();
+    (prefix, suffix)
 }
 
 #[cfg(test)]
@@ -123,7 +338,10 @@ mod set_string_record_test {
     use std::sync::Arc;
     use tempfile::tempdir;
 
-    use super::SetStringRecord;
+    use super::{SetStringRecord, SetStringRecordError};
+    use crate::constants::INTEGER_BYTE_LEN;
+    use crate::file::page::Page;
+    use crate::tx::log::record::log_record::{LogOp, LogRecord};
 
     #[test]
     fn test_set_int_record_log() {
@@ -142,4 +360,99 @@ mod set_string_record_test {
         assert_eq!(record.old_value, "old");
         assert_eq!(record.new_value, "new");
     }
+
+    #[test]
+    fn test_set_string_delta_record_log() {
+        let dir = tempdir().unwrap();
+        let fm = FileManager::new(dir.path(), 400);
+        let lm = Arc::new(LogManager::new(Arc::new(fm), "test.log").unwrap());
+
+        let cases = [
+            // 真ん中だけを書き換える
+            (
+                "the quick brown fox jumps over the lazy dog",
+                "the quick red fox jumps over the lazy dog",
+            ),
+            // 末尾に追加する
+            (
+                "a long string value that is appended",
+                "a long string value that is appended to",
+            ),
+            // 先頭を削除する
+            (
+                "prefix removed from this long string",
+                "removed from this long string",
+            ),
+            // 同じ値で上書きする
+            ("unchanged long string value", "unchanged long string value"),
+            // 複数 byte の文字の途中では区切らない
+            (
+                "日本語の長い文字列を書き換える",
+                "日本語の短い文字列を書き換える",
+            ),
+            // 繰り返しがあっても prefix と suffix は重ならない
+            ("aaaaaaaaaaaaaaaaaaaaaaaa", "aaaaaaaaaaaaaaaaaaaa"),
+            ("aaaaaaaaaaaaaaaaaaaa", "aaaaaaaaaaaaaaaaaaaaaaaa"),
+        ];
+        for (old, new) in cases {
+            let lsn =
+                SetStringRecord::write_to_log(&lm, 5, &BlockId::new("testfile", 0), 80, old, new)
+                    .unwrap();
+            lm.flush(lsn).unwrap();
+            let bytes = lm.iterator().unwrap().next().unwrap();
+            // 共通部分の長い値は delta 形式で書き込まれ、変更前の値をそのまま書くより小さくなる
+            assert_eq!(
+                Page::new_from_vec(&bytes).get_int(0),
+                LogOp::SetStringDelta as i32,
+                "{} -> {}",
+                old,
+                new
+            );
+            let full_len = 7 * INTEGER_BYTE_LEN + "testfile".len() + old.len() + new.len();
+            assert!(bytes.len() < full_len);
+            let record = SetStringRecord::new_from_delta(&bytes).unwrap();
+            assert_eq!(record.txnum, 5);
+            assert_eq!(record.block, BlockId::new("testfile", 0));
+            assert_eq!(record.offset, 80);
+            assert_eq!(record.old_value, old);
+            assert_eq!(record.new_value, new);
+            // rollback や recovery で読む時も同じ record になる
+            assert_eq!(
+                LogRecord::new(&bytes).unwrap(),
+                LogRecord::SetStringRecord(record)
+            );
+        }
+
+        // 共通部分が短い場合は、そのまま書き込む
+        let lsn =
+            SetStringRecord::write_to_log(&lm, 5, &BlockId::new("testfile", 0), 80, "old", "new")
+                .unwrap();
+        lm.flush(lsn).unwrap();
+        let bytes = lm.iterator().unwrap().next().unwrap();
+        assert_eq!(
+            Page::new_from_vec(&bytes).get_int(0),
+            LogOp::SetString as i32
+        );
+    }
+
+    #[test]
+    fn test_unknown_encoding() {
+        let dir = tempdir().unwrap();
+        let fm = FileManager::new(dir.path(), 400);
+        let lm = LogManager::new(Arc::new(fm), "test.log").unwrap();
+        let old = "a long value with a small change";
+        let new = "a long value with a large change";
+        SetStringRecord::write_to_log(&lm, 5, &BlockId::new("testfile", 0), 80, old, new).unwrap();
+        let bytes = lm.iterator().unwrap().next().unwrap();
+
+        // encoding の位置を書き換える
+        let mut page = Page::new_from_vec(&bytes);
+        let epos = 4 * INTEGER_BYTE_LEN + "testfile".len() + INTEGER_BYTE_LEN;
+        assert_eq!(page.get_int(epos), 1);
+        page.set_int(epos, 99);
+        assert!(matches!(
+            SetStringRecord::new_from_delta(page.contents()),
+            Err(SetStringRecordError::UnknownEncoding(99))
+        ));
+    }
 }
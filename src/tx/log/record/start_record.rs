@@ -7,7 +7,7 @@ use crate::log::log_manager::{LogError, LogManager};
  * transaction が開始されたことを示す log record
  */
 #[derive(Debug, Eq, PartialEq)]
-pub(crate) struct StartRecord {
+pub struct StartRecord {
     txnum: u32,
 }
 
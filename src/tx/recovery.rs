@@ -0,0 +1 @@
+pub mod recovery_manager;
@@ -0,0 +1,228 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use thiserror::Error;
+
+use crate::{
+    buffer::buffer_manager::{BufferManager, BufferManagerError},
+    file::file_manager::{FileManager, FileManagerError},
+};
+
+use super::transaction::Transaction;
+
+#[derive(Error, Debug)]
+pub enum TempFileManagerError {
+    #[error("Failed to acquire lock")]
+    Lock,
+    #[error("Buffer manager error: {0}")]
+    BufferManager(#[from] BufferManagerError),
+    #[error("File manager error: {0}")]
+    FileManager(#[from] FileManagerError),
+}
+
+/**
+ * sort や materialize などの operator が途中結果を保存する temp table の名前を払い出し、その後始末をするクラス
+ *
+ * 名前は temp_{txnum}_{seq} の形で、払い出した transaction ごとに記録しておく
+ * transaction が commit または rollback された時に、その transaction の temp table のファイルを削除する
+ * 途中で database が落ちた場合に残ったファイルは、FileManager が次の起動時に削除する
+ *
+ * プログラム全体で一つしかない想定 (seq の管理をする必要があるため)
+ */
+pub struct TempFileManager {
+    file_manager: Arc<FileManager>,
+    buffer_manager: Arc<BufferManager>,
+    next_seq: Mutex<u64>,
+    // transaction 番号 -> その transaction に払い出した temp table の名前
+    tables: Arc<Mutex<HashMap<u32, Vec<String>>>>,
+}
+
+impl TempFileManager {
+    const PREFIX: &'static str = "temp_";
+
+    pub fn new(file_manager: Arc<FileManager>, buffer_manager: Arc<BufferManager>) -> Self {
+        Self {
+            file_manager,
+            buffer_manager,
+            next_seq: Mutex::new(0),
+            tables: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// tx で使う temp table の名前を新しく払い出す
+    /// 払い出した名前の table のファイルは、tx が終わった時に削除される
+    pub fn next_table_name(&self, tx: &Transaction) -> Result<String, TempFileManagerError> {
+        let seq = {
+            let mut next_seq = self
+                .next_seq
+                .lock()
+                .map_err(|_| TempFileManagerError::Lock)?;
+            *next_seq += 1;
+            *next_seq
+        };
+        let table_name = format!("{}{}_{}", Self::PREFIX, tx.tx_num(), seq);
+        self.register_table(tx, &table_name)?;
+        Ok(table_name)
+    }
+
+    /// next_table_name で払い出した名前とは別の table を、tx が終わった時に削除する temp table として記録する
+    /// partition に分けた temp table の、partition ごとの table などに使う
+    pub fn register_table(
+        &self,
+        tx: &Transaction,
+        table_name: &str,
+    ) -> Result<(), TempFileManagerError> {
+        let txnum = tx.tx_num();
+        let mut tables = self.tables.lock().map_err(|_| TempFileManagerError::Lock)?;
+        if !tables.contains_key(&txnum) {
+            // transaction ごとに、最初に払い出した時だけ後始末の hook を登録する
+            let tables = self.tables.clone();
+            let file_manager = self.file_manager.clone();
+            let buffer_manager = self.buffer_manager.clone();
+            tx.on_complete(Box::new(move |_| {
+                let table_names = match tables.lock() {
+                    Ok(mut tables) => tables.remove(&txnum).unwrap_or_default(),
+                    Err(_) => return,
+                };
+                for table_name in table_names {
+                    if let Err(err) =
+                        Self::delete_table_file(&file_manager, &buffer_manager, &table_name)
+                    {
+                        // 削除できなかったファイルは次の起動時に削除される
+                        eprintln!("failed to delete temp table {}: {}", table_name, err);
+                    }
+                }
+            }));
+        }
+        tables
+            .entry(txnum)
+            .or_default()
+            .push(table_name.to_string());
+        Ok(())
+    }
+
+    /// 終わっていない transaction に払い出した temp table の名前を返す
+    pub fn table_names(&self, txnum: u32) -> Result<Vec<String>, TempFileManagerError> {
+        Ok(self
+            .tables
+            .lock()
+            .map_err(|_| TempFileManagerError::Lock)?
+            .get(&txnum)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    fn delete_table_file(
+        file_manager: &FileManager,
+        buffer_manager: &BufferManager,
+        table_name: &str,
+    ) -> Result<(), TempFileManagerError> {
+        let filename = format!("{}.tbl", table_name);
+        // 変更が後から buffer から書き込まれるとファイルが作り直されるので、先に buffer から外す
+        buffer_manager.discard_file(&filename)?;
+        file_manager.delete_file(&filename)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod temp_file_manager_test {
+    use std::path::Path;
+
+    use tempfile::{tempdir, TempDir};
+
+    use super::*;
+    use crate::{
+        log::log_manager::LogManager,
+        tx::{concurrency::lock_table::LockTable, transaction::TransactionFactory},
+    };
+
+    fn setup(dir: &TempDir) -> (TransactionFactory, TempFileManager, Arc<BufferManager>) {
+        let file_manager = Arc::new(FileManager::new(dir.path(), 400));
+        let log_manager = Arc::new(LogManager::new(file_manager.clone(), "test.log").unwrap());
+        let buffer_manager = Arc::new(BufferManager::new(
+            file_manager.clone(),
+            log_manager.clone(),
+            8,
+            Some(10),
+        ));
+        let lock_table = Arc::new(LockTable::new(Some(10)));
+        let temp_file_manager = TempFileManager::new(file_manager.clone(), buffer_manager.clone());
+        let factory = TransactionFactory::new(
+            file_manager,
+            log_manager,
+            buffer_manager.clone(),
+            lock_table,
+        );
+        (factory, temp_file_manager, buffer_manager)
+    }
+
+    // temp table のファイルに 1 block 書き込む
+    fn write_block(tx: &Transaction, table_name: &str) {
+        let block = tx.append(&format!("{}.tbl", table_name)).unwrap();
+        tx.pin(&block).unwrap();
+        tx.set_int(&block, 0, 1, false).unwrap();
+        tx.unpin(&block).unwrap();
+    }
+
+    fn exists(dir: &TempDir, table_name: &str) -> bool {
+        Path::new(dir.path())
+            .join(format!("{}.tbl", table_name))
+            .exists()
+    }
+
+    #[test]
+    fn test_next_table_name() {
+        let dir = tempdir().unwrap();
+        let (factory, temp_file_manager, _) = setup(&dir);
+        let tx1 = factory.create().unwrap();
+        let tx2 = factory.create().unwrap();
+
+        let name1 = temp_file_manager.next_table_name(&tx1).unwrap();
+        let name2 = temp_file_manager.next_table_name(&tx2).unwrap();
+        let name3 = temp_file_manager.next_table_name(&tx1).unwrap();
+        assert_eq!(name1, format!("temp_{}_1", tx1.tx_num()));
+        assert_eq!(name2, format!("temp_{}_2", tx2.tx_num()));
+        assert_eq!(name3, format!("temp_{}_3", tx1.tx_num()));
+        assert_eq!(
+            temp_file_manager.table_names(tx1.tx_num()).unwrap(),
+            vec![name1, name3]
+        );
+    }
+
+    #[test]
+    fn test_delete_on_completion() {
+        let dir = tempdir().unwrap();
+        let (factory, temp_file_manager, buffer_manager) = setup(&dir);
+
+        // commit した transaction の temp table のファイルは削除される
+        let mut tx1 = factory.create().unwrap();
+        let name1 = temp_file_manager.next_table_name(&tx1).unwrap();
+        write_block(&tx1, &name1);
+        assert!(exists(&dir, &name1));
+        tx1.commit().unwrap();
+        assert!(!exists(&dir, &name1));
+        assert!(temp_file_manager
+            .table_names(tx1.tx_num())
+            .unwrap()
+            .is_empty());
+
+        // rollback した場合も削除される。他の transaction の temp table は残る
+        let mut tx2 = factory.create().unwrap();
+        let mut tx3 = factory.create().unwrap();
+        let name2 = temp_file_manager.next_table_name(&tx2).unwrap();
+        let name3 = temp_file_manager.next_table_name(&tx3).unwrap();
+        write_block(&tx2, &name2);
+        write_block(&tx3, &name3);
+        tx2.rollback().unwrap();
+        assert!(!exists(&dir, &name2));
+        assert!(exists(&dir, &name3));
+
+        // buffer に残っていた変更が後から書き込まれてファイルが作り直されることはない
+        tx3.commit().unwrap();
+        buffer_manager.flush_all().unwrap();
+        assert!(!exists(&dir, &name3));
+    }
+}
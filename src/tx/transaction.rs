@@ -1,18 +1,31 @@
-use std::collections::HashSet;
-use std::sync::{Arc, Mutex};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
 
 use thiserror::Error;
 
 use super::buffer_list::{self, BufferList, BufferListError};
 use super::concurrency::lock_table::{LockTable, LockTableError};
-use super::log::log_record_iterator::{LogRecordIterator, LogRecordReverseIterator};
+use super::idle_reaper::{
+    ActivityGuard, IdleTransactionReaper, TransactionAbortedError, TransactionActivity,
+};
+use super::log::log_record_iterator::LogRecordIterator;
 use super::log::record::log_record::{LogRecord, LogRecordError, LogReplayError};
-use crate::buffer::buffer_manager::{BufferManager, BufferManagerError};
+use crate::buffer::buffer_manager::{BufferManager, BufferManagerError, BufferPoolKind};
+use crate::constants::INTEGER_BYTE_LEN;
+use crate::error::SimpleDbResult;
 use crate::file::file_manager::FileManagerError;
-use crate::file::{blockid::BlockId, file_manager::FileManager};
+use crate::file::{blockid::BlockId, file_manager::FileManager, page::Page};
 use crate::log::log_manager::{LogError, LogManager};
-use crate::tx::concurrency::concurrency_manager::ConcurrencyManager;
+use crate::tx::concurrency::concurrency_manager::{ConcurrencyManager, LockState};
 use crate::tx::log::log_record_writer::LogRecordWriter;
+use crate::tx::recovery::recovery_manager::{
+    RecoveryError, RecoveryManager, RecoveryOptions, RecoveryStats,
+};
+
+// transaction が終わった時に呼ぶ関数。commit した場合は true, rollback した場合は false を渡す
+pub type CompletionHook = Box<dyn FnOnce(bool)>;
 
 /**
  * db を操作するひとまとまりの処理単位である transaction を表すクラス
@@ -23,13 +36,35 @@ use crate::tx::log::log_record_writer::LogRecordWriter;
  * このクラスのインスタンスはプログラム中に何個あっても良い
  */
 pub struct Transaction {
-    concurrency_manager: ConcurrencyManager,
+    // 読み書きのメソッドを &self で呼べるように、transaction 内部の状態は RefCell で持つ
+    // これにより、scan の評価中に別の scan が同じ transaction を読んでも RefCell の二重借用にならない
+    // 各メソッドの中でのみ借用し、借用したまま外部のコードを呼び出すことはない
+    // idle transaction reaper が別の thread から lock を解放できるように、Mutex で持つ
+    concurrency_manager: Arc<Mutex<ConcurrencyManager>>,
     log_record_writer: LogRecordWriter,
+    recovery_manager: RecoveryManager,
     log_manager: Arc<LogManager>,
     buffer_manager: Arc<BufferManager>,
     file_manager: Arc<FileManager>,
     txnum: u32,
-    buffer_list: BufferList,
+    buffer_list: RefCell<BufferList>,
+    completion_hooks: RefCell<Vec<CompletionHook>>,
+    // 変更を log に記録しないファイル (register_temp_file を参照)
+    temp_files: RefCell<HashSet<String>>,
+    activity: Arc<TransactionActivity>,
+}
+
+/**
+ * transaction の途中の状態を表す
+ *
+ * Transaction::rollback_to_savepoint に渡すと、savepoint を作成した後に行った変更だけを取り消せる
+ * lock は取り消した変更の分も transaction の終わりまで持ち続ける
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Savepoint {
+    txnum: u32,
+    // savepoint を作成した時点で最新の log record の LSN
+    lsn: u64,
 }
 
 /**
@@ -45,12 +80,19 @@ pub struct TransactionFactory {
     log_manager: Arc<LogManager>,
     buffer_manager: Arc<BufferManager>,
     lock_table: Arc<LockTable>,
+    // 各 transaction の pin cache の容量。None の場合は pin cache を使わない
+    // 既定では catalog の block を DEFAULT_PIN_CACHE_CAPACITY 個まで保持する
+    pin_cache_capacity: Option<usize>,
+    // 設定されている場合、作成した transaction を登録して放置されたものを abort する
+    idle_reaper: Option<Arc<IdleTransactionReaper>>,
 }
 
 #[derive(Error, Debug)]
 pub enum TransactionCommitError {
     #[error("Lock table error: {0}")]
     LockTable(#[from] LockTableError),
+    #[error("{0}")]
+    Aborted(#[from] TransactionAbortedError),
     #[error("Log record error: {0}")]
     LogRecord(#[from] LogRecordError),
     #[error("Buffer list error: {0}")]
@@ -61,36 +103,32 @@ pub enum TransactionCommitError {
 pub enum TransactionRollbackError {
     #[error("Lock table error: {0}")]
     LockTable(#[from] LockTableError),
+    #[error("{0}")]
+    Aborted(#[from] TransactionAbortedError),
+    #[error("invalid savepoint: {0}")]
+    InvalidSavepoint(String),
     #[error("Log record error: {0}")]
     LogRecord(#[from] LogRecordError),
     #[error("Buffer list error: {0}")]
     BufferList(#[from] BufferListError),
-    #[error("Log error: {0}")]
-    Log(#[from] LogError),
-    #[error("log replay error: {0}")]
-    LogReplay(#[from] LogReplayError),
+    #[error("recovery error: {0}")]
+    Recovery(#[from] RecoveryError),
 }
 
 #[derive(Error, Debug)]
 pub enum TransactionRecoverError {
     #[error("Lock table error: {0}")]
     LockTable(#[from] LockTableError),
-    #[error("Log record error: {0}")]
-    LogRecord(#[from] LogRecordError),
-    #[error("Log error: {0}")]
-    Log(#[from] LogError),
-    #[error("log replay error: {0}")]
-    LogReplay(#[from] LogReplayError),
-    #[error("buffer manager error: {0}")]
-    BufferManager(#[from] BufferManagerError),
-    #[error("file manager error: {0}")]
-    FileManager(#[from] FileManagerError),
+    #[error("recovery error: {0}")]
+    Recovery(#[from] RecoveryError),
 }
 
 #[derive(Error, Debug)]
 pub enum TransactionGetError {
     #[error("Lock table error: {0}")]
     LockTable(#[from] LockTableError),
+    #[error("{0}")]
+    Aborted(#[from] TransactionAbortedError),
     #[error("Buffer list error: {0}")]
     BufferList(#[from] BufferListError),
     #[error("lock error: {0}")]
@@ -99,12 +137,18 @@ pub enum TransactionGetError {
     InvalidMethodCall(String),
     #[error("from utf8 error: {0}")]
     FromUtf8(#[from] std::string::FromUtf8Error),
+    #[error("utf8 error: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
+    #[error("buffer manager error: {0}")]
+    BufferManager(#[from] BufferManagerError),
 }
 
 #[derive(Error, Debug)]
 pub enum TransactionSetError {
     #[error("Lock table error: {0}")]
     LockTable(#[from] LockTableError),
+    #[error("{0}")]
+    Aborted(#[from] TransactionAbortedError),
     #[error("Log record error: {0}")]
     LogRecordError(#[from] LogRecordError),
     #[error("lock error: {0}")]
@@ -113,69 +157,192 @@ pub enum TransactionSetError {
     InvalidMethodCall(String),
 }
 
+#[derive(Error, Debug)]
+pub enum TransactionCopyError {
+    #[error("Lock table error: {0}")]
+    LockTable(#[from] LockTableError),
+    #[error("Buffer list error: {0}")]
+    BufferList(#[from] BufferListError),
+    #[error("transaction size error: {0}")]
+    Size(#[from] TransactionSizeError),
+    #[error("Log error: {0}")]
+    Log(#[from] LogError),
+    #[error("log replay error: {0}")]
+    LogReplay(#[from] LogReplayError),
+    #[error("lock error: {0}")]
+    Lock(String),
+    #[error("invalid method call error: {0}")]
+    InvalidMethodCall(String),
+    #[error("buffer manager error: {0}")]
+    BufferManager(#[from] BufferManagerError),
+}
+
 #[derive(Error, Debug)]
 pub enum TransactionSizeError {
     #[error("Lock table error: {0}")]
     LockTableError(#[from] LockTableError),
+    #[error("{0}")]
+    Aborted(#[from] TransactionAbortedError),
     #[error("file manager error: {0}")]
     FileManagerError(#[from] FileManagerError),
+    #[error("Log record error: {0}")]
+    LogRecord(#[from] LogRecordError),
 }
 
 impl Transaction {
     // WAL のルールに則って transaction の内容を commit する
-    pub fn commit(&mut self) -> Result<(), TransactionCommitError> {
+    pub fn commit(&mut self) -> SimpleDbResult<()> {
+        Ok(self.commit_internal()?)
+    }
+
+    // WAL のルールに則って transaction の内容を rollback する
+    pub fn rollback(&mut self) -> SimpleDbResult<()> {
+        Ok(self.rollback_internal()?)
+    }
+
+    // 現在の状態を表す savepoint を作成する
+    pub fn savepoint(&self) -> Result<Savepoint, LogError> {
+        Ok(Savepoint {
+            txnum: self.txnum,
+            lsn: self.log_manager.latest_lsn()?,
+        })
+    }
+
+    // savepoint を作成した後にこの transaction が行った変更を取り消す
+    // transaction は続けて使うことができ、同じ savepoint に何度でも戻れる
+    pub fn rollback_to_savepoint(&self, savepoint: &Savepoint) -> SimpleDbResult<()> {
+        Ok(self.rollback_to_savepoint_internal(savepoint)?)
+    }
+
+    // 現在までの log の内容をもとに、database の状態を復元する
+    // Note: このメソッドを呼び出す場合、他の transaction は走っていないことが前提とされている。db の立ち上げのときなどに呼び出すのが良い
+    pub fn recover(&mut self) -> SimpleDbResult<()> {
+        self.recover_with(RecoveryOptions::new())?;
+        Ok(())
+    }
+
+    // options に従って recover を行い、処理した log record の数を返す
+    // dry run の場合は database の状態を変更しないので、recovery にかかる時間の見積もりや log の調査に使える
+    pub fn recover_with(&mut self, options: RecoveryOptions) -> SimpleDbResult<RecoveryStats> {
+        Ok(self.recover_internal(options)?)
+    }
+
+    fn commit_internal(&mut self) -> Result<(), TransactionCommitError> {
+        // abort された transaction は commit できない。rollback で後始末をする
+        if self.activity.is_aborted() {
+            return Err(TransactionAbortedError(self.txnum).into());
+        }
         self.log_record_writer.log_commit(self.txnum)?;
-        self.concurrency_manager.release()?;
-        self.buffer_list.unpin_all()?;
+        self.concurrency_manager().release()?;
+        self.buffer_list.borrow_mut().unpin_all()?;
+        self.run_completion_hooks(true);
 
         Ok(())
     }
 
-    // WAL のルールに則って transaction の内容を rollback する
-    pub fn rollback(&mut self) -> Result<(), TransactionRollbackError> {
-        self.log_record_writer.log_rollback(self.txnum)?;
-        self.do_rollback()?;
-        self.concurrency_manager.release()?;
-        self.buffer_list.unpin_all()?;
+    fn rollback_internal(&mut self) -> Result<(), TransactionRollbackError> {
+        // idle transaction reaper に abort された場合は、変更の取り消しと lock の解放は済んでいる
+        if !self.activity.is_aborted() {
+            self.log_record_writer.log_rollback(self.txnum)?;
+            self.recovery_manager.rollback(self)?;
+            self.concurrency_manager().release()?;
+        }
+        self.buffer_list.borrow_mut().unpin_all()?;
+        self.run_completion_hooks(false);
 
         Ok(())
     }
 
-    // 現在までの log の内容をもとに、database の状態を復元する
-    // Note: このメソッドを呼び出す場合、他の transaction は走っていないことが前提とされている。db の立ち上げのときなどに呼び出すのが良い
-    pub fn recover(&mut self) -> Result<(), TransactionRecoverError> {
-        self.do_recover()?;
-        self.concurrency_manager.release()?;
-        // recover では log に書き込む前に buffer manager を flush する
-        self.buffer_manager.flush_all()?;
-        let lsn = self.log_record_writer.log_check_point()?;
-        self.log_manager.flush(lsn)?;
+    fn rollback_to_savepoint_internal(
+        &self,
+        savepoint: &Savepoint,
+    ) -> Result<(), TransactionRollbackError> {
+        if savepoint.txnum != self.txnum {
+            return Err(TransactionRollbackError::InvalidSavepoint(format!(
+                "savepoint of transaction {} is used in transaction {}",
+                savepoint.txnum, self.txnum
+            )));
+        }
+        let _activity = self.begin()?;
+        self.recovery_manager.rollback_to(self, savepoint.lsn)?;
         Ok(())
     }
 
+    fn recover_internal(
+        &mut self,
+        options: RecoveryOptions,
+    ) -> Result<RecoveryStats, TransactionRecoverError> {
+        let stats = self.recovery_manager.recover(self, options)?;
+        self.concurrency_manager().release()?;
+        Ok(stats)
+    }
+
     // block の読み書きをするために必要な準備である、pin を行う
-    pub fn pin(&mut self, block: &BlockId) -> Result<(), BufferListError> {
-        self.buffer_list.pin(block)?;
+    pub fn pin(&self, block: &BlockId) -> Result<(), BufferListError> {
+        self.buffer_list.borrow_mut().pin(block)?;
         Ok(())
     }
 
+    // filename の start 番目から count 個の連続した block をまとめて pin する
+    // 全ての block を pin できない場合は何も pin せずにエラーを返す (BufferManager::pin_range を参照)
+    // 返した block はそれぞれ unpin する必要がある
+    pub fn pin_range(
+        &self,
+        filename: &str,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<BlockId>, BufferListError> {
+        self.buffer_list
+            .borrow_mut()
+            .pin_range(filename, start, count)
+    }
+
+    // この transaction が block を pin している回数を返す
+    // scan などで pin の漏れがないかを確認するために使う
+    pub fn pin_count(&self, block: &BlockId) -> usize {
+        self.buffer_list.borrow().pin_count(block)
+    }
+
+    // この transaction が pin している block と、それぞれの pin 数を返す
+    pub fn pinned_blocks(&self) -> HashMap<BlockId, usize> {
+        self.buffer_list.borrow().pinned_blocks()
+    }
+
+    // pin cache に残っていた catalog の block を、buffer manager を経由せずに pin した回数を返す
+    pub fn pin_cache_hits(&self) -> usize {
+        self.buffer_list.borrow().pin_cache_hits()
+    }
+
     // block の読み書き終了後、不要になった block の pin を解除する
     // Note: pin することと lock を取ることは独立に行えるので、lock を取っている状態であっても pin を解除することができる
     //       unpin して内容が flush されたとしても、lock を取り続けていれば uncommitted read は起きないし、
     //       flush された内容が log に書き出されても commit log が入っていない限りは recovery で元に戻される
     // Note: pin を呼び出した回数分だけ unpin する必要がある
-    pub fn unpin(&mut self, block: &BlockId) -> Result<(), BufferListError> {
-        self.buffer_list.unpin(block)?;
+    pub fn unpin(&self, block: &BlockId) -> Result<(), BufferListError> {
+        self.buffer_list.borrow_mut().unpin(block)?;
         Ok(())
     }
 
-    pub fn get_int(&mut self, block: &BlockId, offset: usize) -> Result<i32, TransactionGetError> {
-        self.concurrency_manager.slock(block)?;
-        let buffer = self.buffer_list.get_buffer(block).ok_or_else(|| {
-            TransactionGetError::InvalidMethodCall(
-                "buffer must be pinned first to read the value".to_string(),
-            )
-        })?;
+    // block の内容の写しを、block を pin せずに取得する (BufferManager::read_block_copy を参照)
+    // 読み込み専用の scan のように、block 全体を読むだけで書き込まない場合に使う
+    pub fn read_block_copy(&self, block: &BlockId) -> Result<Page, TransactionGetError> {
+        let _activity = self.begin()?;
+        self.concurrency_manager().slock(block)?;
+        Ok(self.buffer_manager.read_block_copy(block)?)
+    }
+
+    pub fn get_int(&self, block: &BlockId, offset: usize) -> Result<i32, TransactionGetError> {
+        let _activity = self.begin()?;
+        self.concurrency_manager().slock(block)?;
+        let buffer = self
+            .buffer_list
+            .borrow_mut()
+            .get_buffer(block)
+            .ok_or_else(|| {
+                TransactionGetError::InvalidMethodCall(
+                    "buffer must be pinned first to read the value".to_string(),
+                )
+            })?;
         let buffer = buffer
             .lock()
             .map_err(|_| TransactionGetError::Lock("Failed to lock buffer".to_string()))?;
@@ -184,40 +351,75 @@ impl Transaction {
     }
 
     pub fn get_string(
-        &mut self,
+        &self,
         block: &BlockId,
         offset: usize,
     ) -> Result<String, TransactionGetError> {
-        self.concurrency_manager.slock(block)?;
-        let buffer = self.buffer_list.get_buffer(block).ok_or_else(|| {
-            TransactionGetError::InvalidMethodCall(
-                "buffer must be pinned first to read the value".to_string(),
-            )
-        })?;
+        self.read_str(block, offset, str::to_string)
+    }
+
+    /// offset にある文字列を、String を作らずに buffer の中を指す &str として f に渡し、f の結果を返す
+    /// buffer の lock を取ったまま f を呼ぶので、f の中でこの transaction の他の操作を呼んではいけない
+    pub fn read_str<R>(
+        &self,
+        block: &BlockId,
+        offset: usize,
+        f: impl FnOnce(&str) -> R,
+    ) -> Result<R, TransactionGetError> {
+        let _activity = self.begin()?;
+        self.concurrency_manager().slock(block)?;
+        let buffer = self
+            .buffer_list
+            .borrow_mut()
+            .get_buffer(block)
+            .ok_or_else(|| {
+                TransactionGetError::InvalidMethodCall(
+                    "buffer must be pinned first to read the value".to_string(),
+                )
+            })?;
         let buffer = buffer
             .lock()
             .map_err(|_| TransactionGetError::Lock("Failed to lock buffer".to_string()))?;
         let page = buffer.contents();
-        Ok(page.get_string(offset)?)
+        Ok(f(page.get_str(offset)?))
+    }
+
+    /// offset から 8 byte を i64 として読む
+    /// set_bigint と同じく上位と下位の 4 byte を int として読むので、Page::try_get_i64 で読んだ値と一致する
+    pub fn get_bigint(&self, block: &BlockId, offset: usize) -> Result<i64, TransactionGetError> {
+        let high = self.get_int(block, offset)?;
+        let low = self.get_int(block, offset + INTEGER_BYTE_LEN)?;
+        Ok(((high as i64) << 32) | (low as u32 as i64))
+    }
+
+    /// offset から 8 byte を f64 として読む
+    /// set_double は f64 の bit 列を bigint として書き込むので、Page::get_double で読んだ値と一致する
+    pub fn get_double(&self, block: &BlockId, offset: usize) -> Result<f64, TransactionGetError> {
+        Ok(f64::from_bits(self.get_bigint(block, offset)? as u64))
     }
 
     pub fn set_int(
-        &mut self,
+        &self,
         block: &BlockId,
         offset: usize,
         val: i32,
         is_ok_to_log: bool,
     ) -> Result<(), TransactionSetError> {
-        self.concurrency_manager.xlock(block)?;
-        let buffer = self.buffer_list.get_buffer(block).ok_or_else(|| {
-            TransactionSetError::InvalidMethodCall(
-                "buffer must be pinned first to set the value".to_string(),
-            )
-        })?;
+        let _activity = self.begin()?;
+        self.concurrency_manager().xlock(block)?;
+        let buffer = self
+            .buffer_list
+            .borrow_mut()
+            .get_buffer(block)
+            .ok_or_else(|| {
+                TransactionSetError::InvalidMethodCall(
+                    "buffer must be pinned first to set the value".to_string(),
+                )
+            })?;
         let mut buffer = buffer
             .lock()
             .map_err(|_| TransactionSetError::Lock("Failed to lock buffer".to_string()))?;
-        let lsn = if is_ok_to_log {
+        let lsn = if self.should_log(block, is_ok_to_log) {
             let lsn = self
                 .log_record_writer
                 .log_set_int(self.txnum, &buffer, offset, val)?;
@@ -233,23 +435,54 @@ impl Transaction {
         Ok(())
     }
 
+    /// offset から 8 byte に i64 を big endian で書き込む
+    /// 上位と下位の 4 byte をそれぞれ int として書き込むので、log も int 2 つ分になる
+    /// 同じ transaction の中で書き込むので、rollback や recovery では両方が元に戻る
+    pub fn set_bigint(
+        &self,
+        block: &BlockId,
+        offset: usize,
+        val: i64,
+        is_ok_to_log: bool,
+    ) -> Result<(), TransactionSetError> {
+        self.set_int(block, offset, (val >> 32) as i32, is_ok_to_log)?;
+        self.set_int(block, offset + INTEGER_BYTE_LEN, val as i32, is_ok_to_log)
+    }
+
+    /// offset から 8 byte に f64 を書き込む
+    /// log は int と string の値しか扱えないので、bit 列を bigint として (int 2 つ分として) 書き込む
+    pub fn set_double(
+        &self,
+        block: &BlockId,
+        offset: usize,
+        val: f64,
+        is_ok_to_log: bool,
+    ) -> Result<(), TransactionSetError> {
+        self.set_bigint(block, offset, val.to_bits() as i64, is_ok_to_log)
+    }
+
     pub fn set_string(
-        &mut self,
+        &self,
         block: &BlockId,
         offset: usize,
         val: &str,
         is_ok_to_log: bool,
     ) -> Result<(), TransactionSetError> {
-        self.concurrency_manager.xlock(block)?;
-        let buffer = self.buffer_list.get_buffer(block).ok_or_else(|| {
-            TransactionSetError::InvalidMethodCall(
-                "buffer must be pinned first to set the value".to_string(),
-            )
-        })?;
+        let _activity = self.begin()?;
+        self.concurrency_manager().xlock(block)?;
+        let buffer = self
+            .buffer_list
+            .borrow_mut()
+            .get_buffer(block)
+            .ok_or_else(|| {
+                TransactionSetError::InvalidMethodCall(
+                    "buffer must be pinned first to set the value".to_string(),
+                )
+            })?;
         let mut buffer = buffer
             .lock()
             .map_err(|_| TransactionSetError::Lock("Failed to lock buffer".to_string()))?;
-        let lsn = if is_ok_to_log {
+        let lsn = if self.should_log(block, is_ok_to_log) {
             let lsn = self
                 .log_record_writer
                 .log_set_string(self.txnum, &buffer, offset, val)?;
@@ -265,46 +498,227 @@ impl Transaction {
         Ok(())
     }
 
-    pub fn size(&mut self, filename: &str) -> Result<usize, TransactionSizeError> {
-        let block = BlockId::new_end_of_file(filename);
-        self.concurrency_manager.slock(&block)?;
-        Ok(self.file_manager.length(filename)?)
+    // 実際の block ではなく、index の key のような predicate を表す BlockId の slock を取る
+    // 通常の block と同じく、transaction が終わるまで保持する
+    pub fn slock_predicate(&self, predicate: &BlockId) -> Result<(), TransactionGetError> {
+        let _activity = self.begin()?;
+        self.concurrency_manager().slock(predicate)?;
+        Ok(())
+    }
+
+    // predicate を表す BlockId の xlock を取る
+    // その predicate を満たす record を追加・削除する前に呼ぶ
+    pub fn xlock_predicate(&self, predicate: &BlockId) -> Result<(), TransactionSetError> {
+        let _activity = self.begin()?;
+        self.concurrency_manager().xlock(predicate)?;
+        Ok(())
+    }
+
+    // file の block 数を返す
+    // 他の transaction が append してまだ commit / rollback していない block は含めないので、append している transaction を待たない
+    pub fn size(&self, filename: &str) -> Result<usize, TransactionSizeError> {
+        let _activity = self.begin()?;
+        let latch = self.concurrency_manager().append_latch(filename);
+        let _guard = latch
+            .lock()
+            .map_err(|_| LockTableError::Lock("append latch is poisoned".into()))?;
+        let length = self.file_manager.length(filename)?;
+        Ok(self.concurrency_manager().visible_length(filename, length))
+    }
+
+    // file の末尾に block を追加する
+    // latch は append の間だけ持ち、追加した block の xlock は transaction の終わりまで持つ
+    // 末尾まで読んだ他の transaction がいる場合は、phantom を防ぐためにその transaction が終わるまで待つ
+    pub fn append(&self, filename: &str) -> Result<BlockId, TransactionSizeError> {
+        let _activity = self.begin()?;
+        self.concurrency_manager().begin_append(filename)?;
+        let result = self.append_block(filename);
+        self.concurrency_manager().end_append(filename)?;
+        result
     }
 
-    pub fn append(&mut self, filename: &str) -> Result<BlockId, TransactionSizeError> {
-        let block = BlockId::new_end_of_file(filename);
-        self.concurrency_manager.xlock(&block)?;
+    fn append_block(&self, filename: &str) -> Result<BlockId, TransactionSizeError> {
+        let latch = self.concurrency_manager().append_latch(filename);
+        let _guard = latch
+            .lock()
+            .map_err(|_| LockTableError::Lock("append latch is poisoned".into()))?;
         let new_block = self.file_manager.append(filename)?;
+        self.concurrency_manager().lock_appended(&new_block)?;
+        if !self.is_temp_file(filename) {
+            self.log_record_writer.log_append(self.txnum, &new_block)?;
+        }
         Ok(new_block)
     }
 
+    // file の末尾まで読んだ scan が呼び出し、transaction の終わりまで他の transaction が block を append できないようにする (phantom の防止)
+    // 他の transaction が append してまだ終わっていない block があれば、その transaction が終わるのを待つ
+    // lock を取った後の file の block 数を返す
+    pub fn lock_end_of_file(&self, filename: &str) -> Result<usize, TransactionSizeError> {
+        {
+            let _activity = self.begin()?;
+            self.concurrency_manager().lock_end_of_file(filename)?;
+        }
+        self.size(filename)
+    }
+
+    // block がこの transaction が append したものであれば true を返す
+    pub fn is_appended(&self, block: &BlockId) -> bool {
+        self.concurrency_manager().is_appended(block)
+    }
+
+    // この transaction が持っている lock の一覧を返す
+    // lock の取得が timeout した場合に、別の transaction の一覧と比べて取り合っている block を調べるために使う
+    pub fn debug_lock_state(&self) -> LockState {
+        self.concurrency_manager().lock_state()
+    }
+
+    fn concurrency_manager(&self) -> MutexGuard<'_, ConcurrencyManager> {
+        self.concurrency_manager
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    // block の読み書きを始める。返した guard を持っている間は idle transaction reaper に abort されない
+    fn begin(&self) -> Result<ActivityGuard<'_>, TransactionAbortedError> {
+        self.activity
+            .begin()
+            .ok_or(TransactionAbortedError(self.txnum))
+    }
+
+    // この transaction の番号を返す
+    pub fn tx_num(&self) -> u32 {
+        self.txnum
+    }
+
+    // transaction が commit または rollback された時に呼ぶ関数を登録する
+    // 関数には commit された場合は true, rollback された場合は false が渡される
+    pub fn on_complete(&self, hook: CompletionHook) {
+        self.completion_hooks.borrow_mut().push(hook);
+    }
+
+    /// filename を、この transaction の中でだけ使う一時的なファイルとして扱う
+    /// 以降の変更は log に記録せず、transaction が commit または rollback された時にファイルを削除する
+    /// log に記録しないので、rollback_to_savepoint でもファイルの変更は取り消されない
+    pub fn register_temp_file(&self, filename: &str) {
+        self.temp_files.borrow_mut().insert(filename.to_string());
+        let filename = filename.to_string();
+        let file_manager = self.file_manager.clone();
+        let buffer_manager = self.buffer_manager.clone();
+        self.on_complete(Box::new(move |_| {
+            Self::delete_file(&file_manager, &buffer_manager, &filename);
+        }));
+    }
+
+    fn is_temp_file(&self, filename: &str) -> bool {
+        self.temp_files.borrow().contains(filename)
+    }
+
+    // block の変更を log に記録するかどうか
+    fn should_log(&self, block: &BlockId, is_ok_to_log: bool) -> bool {
+        is_ok_to_log && !self.is_temp_file(block.file_name())
+    }
+
+    fn run_completion_hooks(&self, committed: bool) {
+        // hook の中から on_complete が呼ばれても二重借用にならないように、取り出してから呼ぶ
+        let hooks = self.completion_hooks.take();
+        for hook in hooks {
+            hook(committed);
+        }
+    }
+
     pub fn block_size(&self) -> usize {
         self.file_manager.block_size()
     }
 
+    /// ファイルの block size を返す (ファイルごとに設定されていない場合は block_size と同じ)
+    pub fn block_size_of(&self, filename: &str) -> Result<usize, FileManagerError> {
+        self.file_manager.block_size_of(filename)
+    }
+
+    /// ファイルの page を圧縮して保存するように設定する
+    pub fn set_compressed(&self, filename: &str) -> Result<(), FileManagerError> {
+        self.file_manager.set_compressed(filename)
+    }
+
+    /// ファイルの block size を設定する
+    pub fn set_block_size(
+        &self,
+        filename: &str,
+        block_size: usize,
+    ) -> Result<(), FileManagerError> {
+        self.file_manager.set_block_size(filename, block_size)
+    }
+
+    /// ファイルの長さが length block より短い場合は length まで伸ばす
+    /// recovery で、log に記録された append 後のファイルの長さを復元するために使う
+    pub fn ensure_length(&self, filename: &str, length: usize) -> Result<(), FileManagerError> {
+        self.file_manager.ensure_length(filename, length)
+    }
+
+    /// filename の変更を、commit を待たずに disk に書き込む (BufferManager::flush_file を参照)
+    pub fn flush_file(&self, filename: &str) -> Result<(), BufferManagerError> {
+        self.buffer_manager.flush_file(filename)
+    }
+
+    /// filename の block を kind 用の buffer に割り当てるようにする (BufferManager::register_file を参照)
+    pub fn register_file(
+        &self,
+        filename: &str,
+        kind: BufferPoolKind,
+    ) -> Result<(), BufferManagerError> {
+        self.buffer_manager.register_file(filename, kind)
+    }
+
     pub fn available_buffers(&self) -> Result<usize, BufferManagerError> {
         self.buffer_manager.available()
     }
 
-    fn do_rollback(&mut self) -> Result<(), TransactionRollbackError> {
-        // commit 済のトランザクションのリスト
-        let iter = LogRecordIterator::new(self.log_manager.clone())?;
-        for log_record in iter {
+    /// filename の内容を、LSN が lsn の log record が書き込まれた時点で commit 済だった状態に戻して snapshot_filename に書き出す
+    /// LSN は log file の先頭の log record から 1 から順に振られる番号で、起動をまたいでも変わらない (lsn が 0 の場合は log が空だった時点の状態になる)
+    /// snapshot_filename への書き込みは log に記録しないので、temp table のファイルにのみ使うこと
+    /// Note: block size や圧縮の設定は、呼び出す前に両方のファイルに対して行っておく必要がある
+    pub fn copy_file_as_of(
+        &self,
+        filename: &str,
+        snapshot_filename: &str,
+        lsn: u64,
+    ) -> Result<(), TransactionCopyError> {
+        // まず現在の内容をそのままコピーする
+        // コピー元の block には slock を取るので、この後 log を読み終わるまでに他の transaction が変更することはない
+        for blknum in 0..self.size(filename)? {
+            let snapshot_block = if blknum < self.size(snapshot_filename)? {
+                BlockId::new(snapshot_filename, blknum)
+            } else {
+                self.append(snapshot_filename)?
+            };
+            self.copy_block(&BlockId::new(filename, blknum), &snapshot_block)?;
+        }
+
+        // lsn より後の変更と、lsn の時点で commit されていなかった変更を、新しいものから順に取り消す
+        let (iter, latest_lsn) = LogRecordIterator::with_latest_lsn(self.log_manager.clone())?;
+        // lsn の時点で commit または rollback を終えていた transaction
+        let mut finished_txs: HashSet<u32> = HashSet::new();
+        for (log_record, record_lsn) in iter.zip((1..=latest_lsn).rev()) {
             match log_record {
-                LogRecord::Start(inner) => {
-                    if inner.tx_num() == self.txnum {
-                        break;
-                    }
+                LogRecord::Commit(inner) if record_lsn <= lsn => {
+                    finished_txs.insert(inner.tx_num());
+                }
+                LogRecord::Rollback(inner) if record_lsn <= lsn => {
+                    finished_txs.insert(inner.tx_num());
                 }
-                LogRecord::SetStringRecord(record) => {
-                    if record.tx_num() == self.txnum {
-                        record.undo(self)?;
-                    }
+                LogRecord::SetIntRecord(record)
+                    if record.block().file_name() == filename
+                        && (record_lsn > lsn || !finished_txs.contains(&record.tx_num())) =>
+                {
+                    let block = BlockId::new(snapshot_filename, record.block().number());
+                    record.undo_to(self, &block)?;
                 }
-                LogRecord::SetIntRecord(record) => {
-                    if record.tx_num() == self.txnum {
-                        record.undo(self)?;
-                    }
+                LogRecord::SetStringRecord(record)
+                    if record.block().file_name() == filename
+                        && (record_lsn > lsn || !finished_txs.contains(&record.tx_num())) =>
+                {
+                    let block = BlockId::new(snapshot_filename, record.block().number());
+                    record.undo_to(self, &block)?;
                 }
                 _ => {}
             }
@@ -312,61 +726,103 @@ impl Transaction {
         Ok(())
     }
 
-    /**
-     * undo-redo recovery を行う
-     */
-    fn do_recover(&mut self) -> Result<(), TransactionRecoverError> {
-        // undo stage
+    /// filename のファイルの内容を new_filename にコピーし、transaction が commit された時に filename を、rollback された時に new_filename を削除する
+    /// コピーは log に記録しないので、commit より前にコピー先のファイルを書き出しておく
+    /// new_filename に既に block がある場合はエラーを返す
+    /// Note: block size や圧縮の設定は、呼び出す前に両方のファイルに対して行っておく必要がある
+    /// Note: commit や rollback の前に database が落ちた場合、削除されるはずだったファイルは残る
+    pub fn rename_file(
+        &self,
+        filename: &str,
+        new_filename: &str,
+    ) -> Result<(), TransactionCopyError> {
+        if self.size(new_filename)? > 0 {
+            return Err(TransactionCopyError::InvalidMethodCall(format!(
+                "file {} already exists",
+                new_filename
+            )));
+        }
+        // 他の transaction が append してまだ終わっていない block も落とさずにコピーするため、先に末尾の lock を取ってそれを待つ
+        // この lock によって、コピーした後に他の transaction が元のファイルに append することもない
+        for blknum in 0..self.lock_end_of_file(filename)? {
+            let block = BlockId::new(filename, blknum);
+            // 他の transaction がコピーした後の元のファイルを変更しないように、先に xlock を取る
+            self.concurrency_manager().xlock(&block)?;
+            let new_block = self.append(new_filename)?;
+            self.copy_block(&block, &new_block)?;
+        }
+        self.buffer_manager.flush_all()?;
 
-        // commit 済のトランザクションのリスト
-        let mut committed_txs: HashSet<u32> = HashSet::new();
-        let mut iter = LogRecordIterator::new(self.log_manager.clone())?;
-        for log_record in iter.by_ref() {
-            match log_record {
-                LogRecord::CheckPoint() => {
-                    // redo stage へ移行
-                    break;
-                }
-                LogRecord::SetStringRecord(record) => {
-                    if !committed_txs.contains(&record.tx_num()) {
-                        record.undo(self)?;
-                    }
-                }
-                LogRecord::SetIntRecord(record) => {
-                    if !committed_txs.contains(&record.tx_num()) {
-                        record.undo(self)?;
-                    }
-                }
-                LogRecord::Commit(inner) => {
-                    committed_txs.insert(inner.tx_num());
-                }
-                _ => {}
-            }
+        let (filename, new_filename) = (filename.to_string(), new_filename.to_string());
+        let file_manager = self.file_manager.clone();
+        let buffer_manager = self.buffer_manager.clone();
+        self.on_complete(Box::new(move |committed| {
+            let filename = if committed { filename } else { new_filename };
+            Self::delete_file(&file_manager, &buffer_manager, &filename);
+        }));
+        Ok(())
+    }
+
+    // transaction の終了時の hook から filename を削除する。hook は error を返せないので、失敗した場合は出力するだけにする
+    fn delete_file(file_manager: &FileManager, buffer_manager: &BufferManager, filename: &str) {
+        // 変更が後から buffer から書き込まれるとファイルが作り直されるので、先に buffer から外す
+        let result = buffer_manager
+            .discard_file(filename)
+            .map_err(|err| err.to_string())
+            .and_then(|_| {
+                file_manager
+                    .delete_file(filename)
+                    .map_err(|err| err.to_string())
+            });
+        if let Err(err) = result {
+            eprintln!("failed to delete file {}: {}", filename, err);
         }
+    }
 
-        // redo stage
-        let rev_iter = LogRecordReverseIterator::new(&iter)?;
-        for log_record in rev_iter {
-            // commit された変更を再適用する
-            match log_record {
-                LogRecord::SetStringRecord(record) => {
-                    if committed_txs.contains(&record.tx_num()) {
-                        record.redo(self)?;
-                    }
-                }
-                LogRecord::SetIntRecord(record) => {
-                    if committed_txs.contains(&record.tx_num()) {
-                        record.redo(self)?;
-                    }
-                }
-                _ => {}
-            }
+    /// src の内容を log に記録せずに dst にコピーする
+    fn copy_block(&self, src: &BlockId, dst: &BlockId) -> Result<(), TransactionCopyError> {
+        self.concurrency_manager().slock(src)?;
+        self.concurrency_manager().xlock(dst)?;
+        self.pin(src)?;
+        self.pin(dst)?;
+        let get_buffer = |block: &BlockId| {
+            self.buffer_list
+                .borrow_mut()
+                .get_buffer(block)
+                .ok_or_else(|| {
+                    TransactionCopyError::InvalidMethodCall(format!(
+                        "block {:?} is not pinned",
+                        block
+                    ))
+                })
+        };
+        let contents = get_buffer(src)?
+            .lock()
+            .map_err(|_| TransactionCopyError::Lock("Failed to lock buffer".to_string()))?
+            .contents()
+            .contents()
+            .clone();
+        {
+            let buffer = get_buffer(dst)?;
+            let mut buffer = buffer
+                .lock()
+                .map_err(|_| TransactionCopyError::Lock("Failed to lock buffer".to_string()))?;
+            buffer
+                .contents_mut()
+                .contents_mut()
+                .copy_from_slice(&contents);
+            buffer.set_modified(self.txnum as u64, None);
         }
+        self.unpin(src)?;
+        self.unpin(dst)?;
         Ok(())
     }
 }
 
 impl TransactionFactory {
+    // catalog 用の buffer は少ないので、並行に動く transaction が保持しすぎないように小さくしておく
+    const DEFAULT_PIN_CACHE_CAPACITY: usize = 2;
+
     pub fn new(
         file_manager: Arc<FileManager>,
         log_manager: Arc<LogManager>,
@@ -379,29 +835,123 @@ impl TransactionFactory {
             buffer_manager,
             lock_table,
             next_txnum: Mutex::new(0),
+            pin_cache_capacity: Some(Self::DEFAULT_PIN_CACHE_CAPACITY),
+            idle_reaper: None,
         }
     }
 
+    /// 最後の操作から idle_limit 以上経っても commit / rollback されない transaction を abort するようにする
+    /// abort するかどうかは idle_limit の 1/4 の間隔で別の thread から調べる
+    pub fn with_idle_reaper(mut self, idle_limit: Duration) -> TransactionFactory {
+        let reaper = Arc::new(IdleTransactionReaper::new(
+            self.log_manager.clone(),
+            self.buffer_manager.clone(),
+            idle_limit,
+        ));
+        IdleTransactionReaper::start(&reaper, idle_limit / 4);
+        self.idle_reaper = Some(reaper);
+        self
+    }
+
+    pub fn idle_reaper(&self) -> Option<&Arc<IdleTransactionReaper>> {
+        self.idle_reaper.as_ref()
+    }
+
+    /**
+     * log に記録されている最大の transaction の番号の次から番号を振るようにする
+     *
+     * 再起動のたびに 1 から番号を振ると、以前の起動で書かれた log record と同じ番号になり、recovery で commit 済かどうかを取り違える
+     * log を新しいものから順に読み、start record の番号と、最初に見つかった checkpoint に記録された番号の最大値を使う
+     * 他の transaction を作成する前に呼ぶ必要がある
+     */
+    pub fn with_tx_num_from_log(self) -> Result<TransactionFactory, LogError> {
+        let mut max_txnum = 0;
+        let mut iter = LogRecordIterator::new(self.log_manager.clone())?;
+        while let Some(log_record) = iter.try_next() {
+            match log_record {
+                Ok(LogRecord::Start(inner)) => max_txnum = max_txnum.max(inner.tx_num()),
+                Ok(LogRecord::CheckPoint(inner)) => {
+                    // checkpoint より前の transaction の番号は checkpoint に記録されている
+                    max_txnum = max_txnum.max(inner.max_tx_num());
+                    break;
+                }
+                // 番号を知るためだけに読むので、解釈できない log record は読み飛ばす
+                _ => {}
+            }
+        }
+        *self.next_txnum.lock().unwrap() = max_txnum;
+        Ok(self)
+    }
+
+    /// 次に作成する transaction の番号が next_txnum 以上になるようにする
+    /// control file に保存しておいた番号で、log に残っていない番号を再び使わないようにするために使う
+    pub fn with_min_next_tx_num(self, next_txnum: u32) -> TransactionFactory {
+        {
+            let mut txnum = self.next_txnum.lock().unwrap();
+            *txnum = (*txnum).max(next_txnum.saturating_sub(1));
+        }
+        self
+    }
+
+    /// 次に作成する transaction の番号
+    pub fn next_tx_num(&self) -> u32 {
+        *self.next_txnum.lock().unwrap() + 1
+    }
+
+    /// 作成する transaction で、capacity 個までの catalog の block を保持する pin cache を使うようにする
+    /// capacity が 0 の場合は pin cache を使わない
+    pub fn with_pin_cache(mut self, capacity: usize) -> TransactionFactory {
+        self.pin_cache_capacity = (capacity > 0).then_some(capacity);
+        self
+    }
+
     pub fn create(&self) -> Result<Transaction, LogRecordError> {
         let mut txnum = self.next_txnum.lock().unwrap();
         *txnum += 1;
         let log_record_writer = LogRecordWriter::new(self.log_manager.clone());
         log_record_writer.log_start(*txnum)?;
-        Ok(Transaction {
-            concurrency_manager: ConcurrencyManager::new(self.lock_table.clone()),
+        let tx = Transaction {
+            concurrency_manager: Arc::new(Mutex::new(ConcurrencyManager::new(
+                self.lock_table.clone(),
+            ))),
             log_record_writer,
-            buffer_list: buffer_list::BufferList::new(self.buffer_manager.clone()),
+            recovery_manager: RecoveryManager::new(
+                self.log_manager.clone(),
+                self.buffer_manager.clone(),
+            ),
+            buffer_list: RefCell::new(match self.pin_cache_capacity {
+                Some(capacity) => {
+                    buffer_list::BufferList::with_pin_cache(self.buffer_manager.clone(), capacity)
+                }
+                None => buffer_list::BufferList::new(self.buffer_manager.clone()),
+            }),
             log_manager: self.log_manager.clone(),
             buffer_manager: self.buffer_manager.clone(),
             file_manager: self.file_manager.clone(),
             txnum: *txnum,
-        })
+            completion_hooks: RefCell::new(vec![]),
+            temp_files: RefCell::new(HashSet::new()),
+            activity: Arc::new(TransactionActivity::new()),
+        };
+        if let Some(reaper) = &self.idle_reaper {
+            reaper
+                .register(
+                    tx.txnum,
+                    tx.activity.clone(),
+                    tx.concurrency_manager.clone(),
+                )
+                .map_err(anyhow::Error::from)?;
+            let reaper = reaper.clone();
+            let txnum = tx.txnum;
+            tx.on_complete(Box::new(move |_| reaper.unregister(txnum)));
+        }
+        Ok(tx)
     }
 }
 
 #[cfg(test)]
 mod transaction_test {
-    use std::sync::Arc;
+    use std::{rc::Rc, sync::Arc};
     use tempfile::{tempdir, TempDir};
 
     use super::*;
@@ -482,6 +1032,9 @@ mod transaction_test {
         // tx1 が xlock しているので、tx2 は slock も xlock もできない
         assert!(tx2.get_int(&block, 80).is_err());
         assert!(tx2.set_int(&block, 80, 2, true).is_err());
+        // 取り合っている block は tx1 の lock の一覧から分かる
+        assert_eq!(tx1.debug_lock_state().exclusive, vec![block.clone()]);
+        assert!(tx2.debug_lock_state().exclusive.is_empty());
 
         tx1.unpin(&block).unwrap();
         // unpin しても lock は残るので、tx2 はやはり slock も xlock もできない
@@ -489,12 +1042,74 @@ mod transaction_test {
         assert!(tx2.set_int(&block, 80, 2, true).is_err());
 
         tx1.commit().unwrap();
+        assert_eq!(tx1.debug_lock_state(), LockState::default());
         // tx1 が commit したので、lock が解放され、tx2 は slock ができるようになる
         assert!(tx2.get_int(&block, 80).is_ok());
         assert!(tx2.set_int(&block, 80, 2, true).is_ok());
         tx2.commit().unwrap();
     }
 
+    #[test]
+    fn test_size_does_not_wait_for_append() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+
+        let mut tx1 = factory.create().unwrap();
+        let mut tx2 = factory.create().unwrap();
+        assert_eq!(tx1.size("testfile").unwrap(), 0);
+
+        let block = tx1.append("testfile").unwrap();
+        assert_eq!(block, BlockId::new("testfile", 0));
+        // tx1 は自分が append した block を読める
+        assert_eq!(tx1.size("testfile").unwrap(), 1);
+        // tx2 は tx1 の終わっていない append を待たずに、それを除いた長さを得る
+        assert_eq!(tx2.size("testfile").unwrap(), 0);
+        // append は xlock を transaction の終わりまで持たないので、tx2 も続けて append できる
+        assert_eq!(tx2.append("testfile").unwrap(), BlockId::new("testfile", 1));
+        // tx2 は自分が append した block 1 までを読む (block 0 を読む時には tx1 の xlock を待つ)
+        assert_eq!(tx2.size("testfile").unwrap(), 2);
+        assert!(tx2.is_appended(&BlockId::new("testfile", 1)));
+        assert!(!tx2.is_appended(&BlockId::new("testfile", 0)));
+
+        tx1.commit().unwrap();
+        assert_eq!(tx2.size("testfile").unwrap(), 2);
+        tx2.commit().unwrap();
+
+        // append した block は log に残る
+        let appended = LogRecordIterator::new(factory.log_manager.clone())
+            .unwrap()
+            .filter_map(|record| match record {
+                LogRecord::Append(inner) => Some(inner.file_length()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(appended, vec![2, 1]);
+    }
+
+    #[test]
+    fn test_lock_end_of_file() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+
+        let mut tx1 = factory.create().unwrap();
+        let mut tx2 = factory.create().unwrap();
+        tx1.append("testfile").unwrap();
+        // tx1 の append が終わっていないので、末尾まで読んだ tx2 はそれを待つ
+        assert!(tx2.lock_end_of_file("testfile").is_err());
+        tx1.commit().unwrap();
+        assert_eq!(tx2.lock_end_of_file("testfile").unwrap(), 1);
+
+        // 末尾まで読んだ tx2 が終わるまでは、他の transaction は append できない
+        let mut tx3 = factory.create().unwrap();
+        assert!(tx3.append("testfile").is_err());
+        assert_eq!(tx3.size("testfile").unwrap(), 1);
+        // tx2 自身は append できる
+        assert_eq!(tx2.append("testfile").unwrap(), BlockId::new("testfile", 1));
+        tx2.commit().unwrap();
+        assert_eq!(tx3.append("testfile").unwrap(), BlockId::new("testfile", 2));
+        tx3.commit().unwrap();
+    }
+
     #[test]
     fn test_recover() {
         let dir = tempdir().unwrap();
@@ -520,17 +1135,318 @@ mod transaction_test {
         tx3.set_int(&block, 80, 3, true).unwrap();
         tx3.set_string(&block, 40, "three", true).unwrap();
         // tx3 の途中で crash した状況を再現するため、lock 解放 -> unpin を自前で行う
-        tx3.concurrency_manager.release().unwrap();
-        tx3.buffer_list.unpin_all().unwrap();
+        tx3.concurrency_manager().release().unwrap();
+        tx3.buffer_list.get_mut().unpin_all().unwrap();
 
         // act: recover を行う
         let mut tx4 = factory.create().unwrap();
         tx4.recover().unwrap();
 
         // assert: tx1, tx2 は commit された変更が復元されている
-        let mut tx5 = factory.create().unwrap();
+        let tx5 = factory.create().unwrap();
         tx5.pin(&block).unwrap();
         assert_eq!(tx5.get_int(&block, 80).unwrap(), 1);
         assert_eq!(tx5.get_string(&block, 40).unwrap(), "one");
     }
+
+    #[test]
+    fn test_tx_num_resumes_from_log() {
+        let dir = tempdir().unwrap();
+        {
+            let factory = setup_factory(&dir);
+            for _ in 0..2 {
+                factory.create().unwrap().commit().unwrap();
+            }
+        }
+
+        // 再起動後は、log に残っている番号の続きから振る
+        {
+            let factory = setup_factory(&dir).with_tx_num_from_log().unwrap();
+            let mut tx = factory.create().unwrap();
+            assert_eq!(tx.tx_num(), 3);
+            // recovery の checkpoint に番号が記録され、それより前の log record は読まなくてよい
+            tx.recover().unwrap();
+            tx.commit().unwrap();
+        }
+        let factory = setup_factory(&dir).with_tx_num_from_log().unwrap();
+        assert_eq!(factory.create().unwrap().tx_num(), 4);
+
+        // log が空の場合は 1 から振る
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir).with_tx_num_from_log().unwrap();
+        assert_eq!(factory.next_tx_num(), 1);
+        // 下限を指定した場合は、log に残っている番号より大きければその番号から振る
+        let factory = factory.with_min_next_tx_num(10).with_min_next_tx_num(5);
+        assert_eq!(factory.next_tx_num(), 10);
+        assert_eq!(factory.create().unwrap().tx_num(), 10);
+    }
+
+    #[test]
+    fn test_recover_dry_run() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+
+        // arrange: tx1 で commit, tx2 で commit も rollback もされていない変更を作成
+        let block = BlockId::new("testfile", 0);
+        let mut tx1 = factory.create().unwrap();
+        tx1.pin(&block).unwrap();
+        tx1.set_int(&block, 80, 1, true).unwrap();
+        tx1.set_string(&block, 40, "one", true).unwrap();
+        tx1.commit().unwrap();
+
+        let mut tx2 = factory.create().unwrap();
+        tx2.pin(&block).unwrap();
+        tx2.set_int(&block, 80, 2, true).unwrap();
+        tx2.concurrency_manager().release().unwrap();
+        tx2.buffer_list.get_mut().unpin_all().unwrap();
+
+        // act: dry run で recover を行う
+        let mut progress_calls = 0;
+        let mut tx3 = factory.create().unwrap();
+        let stats = tx3
+            .recover_with(
+                RecoveryOptions::new()
+                    .with_dry_run(true)
+                    .with_progress(|_| progress_calls += 1),
+            )
+            .unwrap();
+
+        // assert: tx2 の変更が undo, tx1 の変更が redo の対象として数えられる
+        assert_eq!(stats.undone, 1);
+        assert_eq!(stats.redone, 2);
+        assert_eq!(stats.records_scanned, progress_calls);
+        assert!(stats.records_scanned > 0);
+
+        // assert: dry run なので block の内容は変更されていない
+        let tx4 = factory.create().unwrap();
+        tx4.pin(&block).unwrap();
+        assert_eq!(tx4.get_int(&block, 80).unwrap(), 2);
+        assert_eq!(tx4.get_string(&block, 40).unwrap(), "one");
+    }
+
+    #[test]
+    fn test_read_block_copy() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+
+        let block = BlockId::new("testfile", 0);
+        let mut tx1 = factory.create().unwrap();
+        tx1.pin(&block).unwrap();
+        tx1.set_int(&block, 80, 1, true).unwrap();
+
+        // tx1 が xlock を持っている間は読めない
+        let mut tx2 = factory.create().unwrap();
+        assert!(tx2.read_block_copy(&block).is_err());
+        tx2.rollback().unwrap();
+
+        tx1.commit().unwrap();
+        let mut tx3 = factory.create().unwrap();
+        let page = tx3.read_block_copy(&block).unwrap();
+        assert_eq!(page.get_int(80), 1);
+        assert!(tx3.pinned_blocks().is_empty());
+        tx3.commit().unwrap();
+    }
+
+    #[test]
+    fn test_completion_hooks() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let results = Rc::new(RefCell::new(vec![]));
+
+        let mut tx1 = factory.create().unwrap();
+        let r = results.clone();
+        tx1.on_complete(Box::new(move |committed| {
+            r.borrow_mut().push((1, committed))
+        }));
+        tx1.commit().unwrap();
+
+        let mut tx2 = factory.create().unwrap();
+        let r = results.clone();
+        tx2.on_complete(Box::new(move |committed| {
+            r.borrow_mut().push((2, committed))
+        }));
+        tx2.rollback().unwrap();
+
+        assert_eq!(*results.borrow(), vec![(1, true), (2, false)]);
+    }
+
+    #[test]
+    fn test_register_temp_file() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+
+        let mut tx = factory.create().unwrap();
+        tx.register_temp_file("tempfile");
+        let lsn = factory.log_manager.latest_lsn().unwrap();
+        // temp file の変更は、append も含めて log に記録しない
+        let block = tx.append("tempfile").unwrap();
+        tx.pin(&block).unwrap();
+        tx.set_int(&block, 80, 1, true).unwrap();
+        tx.set_string(&block, 40, "one", true).unwrap();
+        assert_eq!(factory.log_manager.latest_lsn().unwrap(), lsn);
+        assert_eq!(tx.get_int(&block, 80).unwrap(), 1);
+        assert!(dir.path().join("tempfile").exists());
+
+        // transaction が終わるとファイルは削除される
+        tx.commit().unwrap();
+        assert!(!dir.path().join("tempfile").exists());
+    }
+
+    // op として存在しない値を持つ log record
+    const TORN_LOG_RECORD: [u8; 8] = [0, 0, 0, 99, 1, 2, 3, 4];
+
+    #[test]
+    fn test_recover_ignores_torn_tail() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+
+        // arrange: tx1 で commit した後、書き込み途中の log record が末尾に残った状況を再現する
+        let block = BlockId::new("testfile", 0);
+        let mut tx1 = factory.create().unwrap();
+        tx1.pin(&block).unwrap();
+        tx1.set_int(&block, 80, 1, true).unwrap();
+        tx1.commit().unwrap();
+        factory.log_manager.append(&TORN_LOG_RECORD).unwrap();
+
+        // act
+        let mut tx2 = factory.create().unwrap();
+        let stats = tx2.recover_with(RecoveryOptions::new()).unwrap();
+
+        // assert: 壊れた log record は読み飛ばされ、tx1 の変更は redo される
+        assert_eq!(stats.skipped, 1);
+        assert_eq!(stats.redone, 1);
+        let tx3 = factory.create().unwrap();
+        tx3.pin(&block).unwrap();
+        assert_eq!(tx3.get_int(&block, 80).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_recover_fails_on_corruption_before_tail() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+
+        // arrange: 壊れた log record の後に、別の transaction の log record が続いている
+        factory.log_manager.append(&TORN_LOG_RECORD).unwrap();
+        let block = BlockId::new("testfile", 0);
+        let mut tx1 = factory.create().unwrap();
+        tx1.pin(&block).unwrap();
+        tx1.set_int(&block, 80, 1, true).unwrap();
+        tx1.commit().unwrap();
+
+        // act & assert
+        let mut tx2 = factory.create().unwrap();
+        let err = tx2.recover().unwrap_err();
+        assert_eq!(err.category(), crate::error::ErrorCategory::Corruption);
+    }
+
+    #[test]
+    fn test_idle_reaper_aborts_idle_transaction() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir).with_idle_reaper(Duration::from_millis(200));
+        let block = BlockId::new("testfile", 0);
+        let mut tx1 = factory.create().unwrap();
+        tx1.pin(&block).unwrap();
+        tx1.set_int(&block, 80, 1, true).unwrap();
+        tx1.set_string(&block, 40, "one", true).unwrap();
+        tx1.commit().unwrap();
+
+        // arrange: tx2 が値を書き換えたまま放置される
+        let mut tx2 = factory.create().unwrap();
+        tx2.pin(&block).unwrap();
+        tx2.set_int(&block, 80, 2, true).unwrap();
+        tx2.set_string(&block, 40, "two", true).unwrap();
+        assert_eq!(factory.idle_reaper().unwrap().num_transactions(), 1);
+        std::thread::sleep(Duration::from_millis(400));
+
+        // act: background の thread が abort していなければここで abort される
+        factory.idle_reaper().unwrap().reap().unwrap();
+
+        // assert: tx2 の変更は取り消され、lock も解放されている
+        assert_eq!(factory.idle_reaper().unwrap().num_transactions(), 0);
+        let mut tx3 = factory.create().unwrap();
+        tx3.pin(&block).unwrap();
+        assert_eq!(tx3.get_int(&block, 80).unwrap(), 1);
+        assert_eq!(tx3.get_string(&block, 40).unwrap(), "one");
+        tx3.set_int(&block, 80, 3, true).unwrap();
+        tx3.commit().unwrap();
+
+        // assert: tx2 の操作は error になり、rollback で後始末ができる
+        assert!(matches!(
+            tx2.get_int(&block, 80),
+            Err(TransactionGetError::Aborted(_))
+        ));
+        let err = tx2.commit().unwrap_err();
+        assert_eq!(err.category(), crate::error::ErrorCategory::Lock);
+        tx2.rollback().unwrap();
+        assert_eq!(factory.buffer_manager.available().unwrap(), 8);
+    }
+
+    #[test]
+    fn test_idle_reaper_keeps_active_transaction() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir).with_idle_reaper(Duration::from_millis(200));
+        let block = BlockId::new("testfile", 0);
+
+        // arrange: 最後の操作から idle_limit が経つ前に操作し続ける
+        let mut tx = factory.create().unwrap();
+        tx.pin(&block).unwrap();
+        for i in 0..4 {
+            tx.set_int(&block, 80, i, true).unwrap();
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(factory.idle_reaper().unwrap().reap().unwrap().is_empty());
+        }
+
+        // assert
+        assert_eq!(tx.get_int(&block, 80).unwrap(), 3);
+        tx.commit().unwrap();
+        assert_eq!(factory.idle_reaper().unwrap().num_transactions(), 0);
+    }
+
+    #[test]
+    fn test_rollback_to_savepoint() {
+        let dir = tempdir().unwrap();
+        let factory = setup_factory(&dir);
+        let block = BlockId::new("testfile", 0);
+
+        let mut tx = factory.create().unwrap();
+        tx.pin(&block).unwrap();
+        tx.set_int(&block, 80, 1, true).unwrap();
+        let savepoint = tx.savepoint().unwrap();
+        tx.set_int(&block, 80, 2, true).unwrap();
+        tx.set_string(&block, 40, "two", true).unwrap();
+
+        // savepoint より後の変更だけが取り消され、transaction は続けて使える
+        tx.rollback_to_savepoint(&savepoint).unwrap();
+        assert_eq!(tx.get_int(&block, 80).unwrap(), 1);
+        assert_eq!(tx.get_string(&block, 40).unwrap(), "");
+        tx.set_int(&block, 80, 3, true).unwrap();
+        tx.rollback_to_savepoint(&savepoint).unwrap();
+        assert_eq!(tx.get_int(&block, 80).unwrap(), 1);
+
+        // 別の transaction の savepoint は使えない
+        let other = factory.create().unwrap();
+        assert!(other.rollback_to_savepoint(&savepoint).is_err());
+        tx.commit().unwrap();
+
+        // commit した後の recovery でも、savepoint までの取り消しが再現される
+        let mut tx = factory.create().unwrap();
+        tx.recover().unwrap();
+        tx.pin(&block).unwrap();
+        assert_eq!(tx.get_int(&block, 80).unwrap(), 1);
+        assert_eq!(tx.get_string(&block, 40).unwrap(), "");
+        tx.commit().unwrap();
+
+        // transaction 全体の rollback では、savepoint までの取り消しも含めて開始時点に戻る
+        let mut tx = factory.create().unwrap();
+        tx.pin(&block).unwrap();
+        tx.set_int(&block, 80, 10, true).unwrap();
+        let savepoint = tx.savepoint().unwrap();
+        tx.set_int(&block, 80, 20, true).unwrap();
+        tx.rollback_to_savepoint(&savepoint).unwrap();
+        tx.rollback().unwrap();
+        let mut tx = factory.create().unwrap();
+        tx.pin(&block).unwrap();
+        assert_eq!(tx.get_int(&block, 80).unwrap(), 1);
+        tx.commit().unwrap();
+    }
 }